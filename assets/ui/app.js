@@ -0,0 +1,95 @@
+"use strict";
+
+const $ = (id) => document.getElementById(id);
+let targets = [];
+let commonOptions = [];
+
+function optionInput(option) {
+  const label = document.createElement("label");
+  label.title = option.description;
+  const input = document.createElement("input");
+  input.dataset.option = option.name;
+  if (option.kind === "bool") {
+    input.type = "checkbox";
+    label.append(input, " " + option.name);
+  } else {
+    input.type = option.kind === "number" ? "number" : "text";
+    input.placeholder = option.description;
+    label.append(option.name, input);
+  }
+  input.addEventListener("input", update);
+  return label;
+}
+
+function renderOptions() {
+  const target = targets.find((t) => t.name === $("target").value);
+  const options = $("options");
+  options.replaceChildren(
+    ...commonOptions.concat(target ? target.options : []).map(optionInput)
+  );
+  update();
+}
+
+function buildUrl() {
+  const urls = $("urls")
+    .value.split("\n")
+    .map((line) => line.trim())
+    .filter((line) => line.length > 0);
+  if (urls.length === 0) {
+    return "";
+  }
+  const params = new URLSearchParams();
+  params.set("target", $("target").value);
+  params.set("url", urls.join("|"));
+  for (const input of $("options").querySelectorAll("input")) {
+    if (input.type === "checkbox") {
+      if (input.checked) {
+        params.set(input.dataset.option, "true");
+      }
+    } else if (input.value.trim() !== "") {
+      params.set(input.dataset.option, input.value.trim());
+    }
+  }
+  return `${location.origin}/sub?${params.toString()}`;
+}
+
+function update() {
+  $("result").value = buildUrl();
+}
+
+async function testUrl() {
+  const url = buildUrl();
+  const output = $("output");
+  if (!url) {
+    return;
+  }
+  output.hidden = false;
+  output.textContent = "Loading...";
+  try {
+    const response = await fetch(url, { headers: { Accept: "application/json, */*" } });
+    const text = await response.text();
+    output.textContent = response.ok ? text : `HTTP ${response.status}\n${text}`;
+  } catch (e) {
+    output.textContent = `Request failed: ${e}`;
+  }
+}
+
+async function init() {
+  const response = await fetch("/api/targets");
+  const list = await response.json();
+  targets = list.targets;
+  commonOptions = list.common_options;
+  for (const target of targets) {
+    const option = document.createElement("option");
+    option.value = target.name;
+    option.textContent = target.name;
+    $("target").append(option);
+  }
+  $("target").addEventListener("change", renderOptions);
+  $("urls").addEventListener("input", update);
+  $("copy").addEventListener("click", () => navigator.clipboard.writeText($("result").value));
+  $("test").addEventListener("click", testUrl);
+  renderOptions();
+}
+
+init();
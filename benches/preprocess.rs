@@ -0,0 +1,122 @@
+//! Renaming and emoji rules applied to a large subscription
+//!
+//! `preprocess_nodes` compiles each pattern once and shares it across nodes,
+//! `compile_per_node` is the same work compiling every pattern for every
+//! node, as before the patterns were cached.
+//!
+//! ```sh
+//! cargo bench --bench preprocess
+//! ```
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use regex::Regex;
+use subconverter::models::{ExtraSettings, RegexMatchConfig, RegexMatchConfigs};
+use subconverter::utils::preprocess_nodes;
+use subconverter::{Proxy, ProxyType};
+
+const NODE_COUNT: usize = 5000;
+
+const REGIONS: [(&str, &str, &str); 10] = [
+    ("Hong Kong", "HK", "🇭🇰"),
+    ("Japan", "JP", "🇯🇵"),
+    ("United States", "US", "🇺🇸"),
+    ("Singapore", "SG", "🇸🇬"),
+    ("Taiwan", "TW", "🇹🇼"),
+    ("Korea", "KR", "🇰🇷"),
+    ("Germany", "DE", "🇩🇪"),
+    ("United Kingdom", "UK", "🇬🇧"),
+    ("France", "FR", "🇫🇷"),
+    ("Canada", "CA", "🇨🇦"),
+];
+
+fn nodes() -> Vec<Proxy> {
+    (0..NODE_COUNT)
+        .map(|i| Proxy {
+            proxy_type: ProxyType::Shadowsocks,
+            remark: format!("{} IPLC {:04} [x{}]", REGIONS[i % 10].0, i, i % 3 + 1),
+            hostname: format!("node{}.example.com", i),
+            port: 443,
+            ..Default::default()
+        })
+        .collect()
+}
+
+fn rule(_match: String, replace: &str) -> RegexMatchConfig {
+    RegexMatchConfig {
+        _match,
+        replace: replace.to_string(),
+    }
+}
+
+/// 30 rename rules, three per region
+fn rename_rules() -> RegexMatchConfigs {
+    let mut rules = Vec::new();
+    for (name, code, _) in REGIONS {
+        rules.push(rule(format!("(?i){}", name), code));
+        rules.push(rule(
+            format!("^{} IPLC", code),
+            &format!("{} Premium", code),
+        ));
+        rules.push(rule(
+            format!(r"{} Premium (\d+)", code),
+            &format!("{} $1", code),
+        ));
+    }
+    rules
+}
+
+fn emoji_rules() -> RegexMatchConfigs {
+    REGIONS
+        .iter()
+        .map(|(name, code, emoji)| rule(format!("({}|{})", code, name), emoji))
+        .collect()
+}
+
+fn bench_preprocess(c: &mut Criterion) {
+    let nodes = nodes();
+    let rename = rename_rules();
+    let emoji = emoji_rules();
+    let mut extra = ExtraSettings::default();
+    extra.add_emoji = true;
+
+    let mut group = c.benchmark_group("5k nodes x 30 patterns");
+    group.sample_size(10);
+
+    group.bench_function("preprocess_nodes", |b| {
+        b.iter_batched(
+            || nodes.clone(),
+            |mut nodes| {
+                preprocess_nodes(&mut nodes, &extra, &rename, &emoji);
+                nodes
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.bench_function("compile_per_node", |b| {
+        b.iter_batched(
+            || nodes.clone(),
+            |mut nodes| {
+                for node in &mut nodes {
+                    for rule in &rename {
+                        let regex = Regex::new(&rule._match).unwrap();
+                        node.remark = regex.replace_all(&node.remark, &rule.replace).to_string();
+                    }
+                    if let Some(rule) = emoji
+                        .iter()
+                        .find(|rule| Regex::new(&rule._match).unwrap().is_match(&node.remark))
+                    {
+                        node.remark = format!("{} {}", rule.replace, node.remark);
+                    }
+                }
+                black_box(nodes)
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_preprocess);
+criterion_main!(benches);
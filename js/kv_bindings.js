@@ -317,6 +317,33 @@ async function kv_set_text(key, value /* String from Rust */) {
     }
 }
 
+// Write many text values in as few round-trips as the backend allows.
+// `entries_json` is a JSON array of [key, value] pairs produced by Rust.
+async function kv_set_text_batch(entries_json /* String from Rust */) {
+    const entries = JSON.parse(entries_json);
+    if (!Array.isArray(entries) || entries.length === 0) {
+        return;
+    }
+    try {
+        const kvClient = await getKv();
+        if (kvClient._baseKv && typeof kvClient._baseKv.mset === 'function') {
+            // Vercel KV: a single MSET covers the whole batch
+            const prefixed = {};
+            for (const [key, value] of entries) {
+                prefixed[`${VERCEL_KV_PREFIX}/${key}`] = value;
+            }
+            await kvClient._baseKv.mset(prefixed);
+        } else {
+            // Netlify Blobs and the in-memory fallback have no multi-set,
+            // so issue the writes concurrently instead
+            await Promise.all(entries.map(([key, value]) => kv_set_text(key, value)));
+        }
+    } catch (error) {
+        console.error(`KV set_text_batch error for ${entries.length} keys:`, error);
+        throw new Error(`Failed to set ${entries.length} keys: ${error.message}`);
+    }
+}
+
 async function kv_exists(key) {
     try {
         const kvClient = await getKv();
@@ -525,4 +552,5 @@ module.exports = {
     migrateStorage, // Expose migrate function if needed externally
     kv_get_text,
     kv_set_text,
+    kv_set_text_batch,
 }; 
\ No newline at end of file
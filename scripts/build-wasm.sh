@@ -126,7 +126,7 @@ if [ "$BUMP_BETA" = true ]; then
 
   # Build WASM locally (Release mode)
   echo "Building wasm package locally in release mode..."
-  wasm-pack build --release --target nodejs
+  wasm-pack build --release --target nodejs -- --no-default-features --features wasm-vfs
   echo "WASM beta build complete! Output is in the 'pkg' directory."
 
   # Update package.json in pkg
@@ -315,11 +315,11 @@ if [ "$RELEASE_MODE" = true ]; then
     fi
   fi
   
-  wasm-pack build --release --target nodejs
+  wasm-pack build --release --target nodejs -- --no-default-features --features wasm-vfs
   echo "WASM release build complete! Output is in the 'pkg' directory."
 else
   echo "Building wasm package in development mode..."
-  wasm-pack build --dev --target nodejs
+  wasm-pack build --dev --target nodejs -- --no-default-features --features wasm-vfs
   echo "WASM development build complete! Output is in the 'pkg' directory."
 fi
 
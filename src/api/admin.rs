@@ -73,6 +73,26 @@ pub async fn list_directory(path: String) -> Result<Vec<DirectoryEntry>, JsValue
     vfs.list_directory(&path).await.map_err(vfs_error_to_js)
 }
 
+/// List one page of directory contents - admin endpoint.
+/// Pass the returned `next_cursor` back in to fetch the following page.
+#[wasm_bindgen]
+pub async fn admin_list_directory_page(
+    path: String,
+    cursor: Option<String>,
+) -> Result<JsValue, JsValue> {
+    info!(
+        "admin_list_directory_page called for path: {} (cursor: {:?})",
+        path, cursor
+    );
+    let vfs = get_vfs().await.map_err(vfs_error_to_js)?;
+
+    match vfs.list_directory_page(&path, cursor.as_deref()).await {
+        Ok(page) => serde_wasm_bindgen::to_value(&page)
+            .map_err(|e| JsValue::from_str(&format!("Error serializing directory page: {}", e))),
+        Err(e) => Err(vfs_error_to_js(e)),
+    }
+}
+
 /// Load all files from a GitHub repository directory recursively.
 /// If shallow=true, only creates placeholder entries without downloading content.
 #[wasm_bindgen]
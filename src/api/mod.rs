@@ -1,18 +1,24 @@
-#[cfg(target_arch = "wasm32")]
+#[cfg(all(target_arch = "wasm32", feature = "wasm-vfs"))]
 pub mod admin;
-#[cfg(target_arch = "wasm32")]
+#[cfg(all(target_arch = "wasm32", feature = "wasm-vfs"))]
 pub mod init;
-#[cfg(target_arch = "wasm32")]
+pub mod parse;
+#[cfg(all(target_arch = "wasm32", feature = "wasm-vfs"))]
 pub mod rules;
-#[cfg(target_arch = "wasm32")]
+#[cfg(all(target_arch = "wasm32", feature = "wasm-vfs"))]
 pub mod short_urls;
+pub mod status;
 pub mod sub;
-#[cfg(target_arch = "wasm32")]
+pub mod targets;
+#[cfg(all(target_arch = "wasm32", feature = "wasm-vfs"))]
 pub use admin::*;
-#[cfg(target_arch = "wasm32")]
+#[cfg(all(target_arch = "wasm32", feature = "wasm-vfs"))]
 pub use init::*;
-#[cfg(target_arch = "wasm32")]
+pub use parse::*;
+#[cfg(all(target_arch = "wasm32", feature = "wasm-vfs"))]
 pub use rules::*;
-#[cfg(target_arch = "wasm32")]
+#[cfg(all(target_arch = "wasm32", feature = "wasm-vfs"))]
 pub use short_urls::*;
+pub use status::*;
 pub use sub::*;
+pub use targets::*;
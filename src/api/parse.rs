@@ -0,0 +1,266 @@
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::api::SubResponse;
+use crate::interfaces::subconverter::{
+    parse_subscription, split_subscription_urls, tag_subscription_url, ParseOptions,
+};
+use crate::models::{Proxy, ProxyType};
+use crate::Settings;
+
+/// Placeholder written in place of credentials
+const REDACTED: &str = "***";
+
+/// Query parameters for the parse endpoint
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+pub struct ParseQuery {
+    /// Subscription URLs or node links (pipe separated)
+    pub url: Option<String>,
+    /// Authentication token, required for local paths in API mode
+    pub token: Option<String>,
+    /// Return credentials unredacted when set to `1` or `true`, needs the access token
+    pub full: Option<String>,
+}
+
+impl ParseQuery {
+    fn wants_full(&self) -> bool {
+        matches!(self.full.as_deref(), Some("1") | Some("true"))
+    }
+}
+
+/// Normalized JSON view of a parsed node
+///
+/// Only fields that carry a value are serialized. Credentials are replaced
+/// with `***` unless the view is built with `full` set.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct NodeView {
+    #[serde(rename = "type")]
+    pub proxy_type: String,
+    pub remark: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub group: String,
+    pub server: String,
+    pub port: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uuid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plugin: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plugin_opts: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protocol_param: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub obfs: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub obfs_param: Option<String>,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub alter_id: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(skip_serializing_if = "is_false")]
+    pub tls: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sni: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub udp: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tfo: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip_cert_verify: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub private_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pre_shared_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auth: Option<String>,
+}
+
+fn is_zero(value: &u16) -> bool {
+    *value == 0
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+/// Non-empty value of an optional field
+fn non_empty(value: &Option<String>) -> Option<String> {
+    value.as_ref().filter(|value| !value.is_empty()).cloned()
+}
+
+impl NodeView {
+    /// Build the view of `node`, redacting credentials unless `full` is set
+    pub fn from_proxy(node: &Proxy, full: bool) -> Self {
+        let secret = |value: &Option<String>| {
+            non_empty(value).map(|value| if full { value } else { REDACTED.to_string() })
+        };
+
+        NodeView {
+            proxy_type: node.proxy_type.name().to_lowercase(),
+            remark: node.remark.clone(),
+            group: node.group.clone(),
+            server: node.hostname.clone(),
+            port: node.port,
+            username: non_empty(&node.username),
+            password: secret(&node.password),
+            uuid: secret(&node.user_id),
+            method: non_empty(&node.encrypt_method),
+            plugin: non_empty(&node.plugin),
+            plugin_opts: non_empty(&node.plugin_option),
+            protocol: non_empty(&node.protocol),
+            // SSR multi-user setups carry `uid:password` here
+            protocol_param: secret(&node.protocol_param),
+            obfs: non_empty(&node.obfs),
+            obfs_param: non_empty(&node.obfs_param),
+            alter_id: node.alter_id,
+            network: non_empty(&node.transfer_protocol),
+            host: non_empty(&node.host),
+            path: non_empty(&node.path),
+            tls: node.tls_secure,
+            sni: non_empty(&node.sni).or_else(|| non_empty(&node.server_name)),
+            udp: node.udp,
+            tfo: node.tcp_fast_open,
+            skip_cert_verify: node.allow_insecure,
+            public_key: non_empty(&node.public_key),
+            private_key: secret(&node.private_key),
+            pre_shared_key: secret(&node.pre_shared_key),
+            auth: secret(&node.auth).or_else(|| secret(&node.auth_str)),
+        }
+    }
+}
+
+/// Parse the requested subscriptions and return the nodes as a JSON array
+pub async fn parse_process(query: ParseQuery) -> SubResponse {
+    let url = match query.url.as_deref() {
+        Some(url) if !url.is_empty() => url.to_string(),
+        _ => return SubResponse::error("Invalid request!".to_string(), 400),
+    };
+
+    let global = Settings::current();
+    let token = query.token.as_deref().unwrap_or_default();
+    let authorized = !global.api_mode || token == global.api_access_token;
+    let full_allowed = !global.api_access_token.is_empty() && token == global.api_access_token;
+    if query.wants_full() && !full_allowed {
+        return SubResponse::error(
+            "Unauthorized request for full node details".to_string(),
+            403,
+        );
+    }
+
+    let opts = ParseOptions {
+        authorized,
+        max_download_size: global.max_allowed_download_size,
+        max_allowed_proxies: global.max_allowed_proxies,
+        settings: global,
+        ..Default::default()
+    };
+    let mut nodes: Vec<Proxy> = Vec::new();
+    for (group_id, link) in split_subscription_urls(&url).iter().enumerate() {
+        debug!("Parsing URL for inspection: {}", link);
+        match parse_subscription(&tag_subscription_url(link), opts.clone(), group_id as i32).await {
+            Ok(mut parsed) => nodes.append(&mut parsed),
+            Err(e) => {
+                warn!("Failed to parse URL '{}': {}", link, e);
+                return SubResponse::error(format!("Failed to parse URL '{}': {}", link, e), 400);
+            }
+        }
+    }
+
+    let views: Vec<NodeView> = nodes
+        .iter()
+        .filter(|node| node.proxy_type != ProxyType::Unknown)
+        .map(|node| NodeView::from_proxy(node, query.wants_full()))
+        .collect();
+    match serde_json::to_string(&views) {
+        Ok(json) => SubResponse::ok(json, "application/json".to_string()),
+        Err(e) => SubResponse::error(format!("Failed to serialize nodes: {}", e), 500),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::base64::base64_encode;
+
+    fn parse(url: &str, full: bool) -> serde_json::Value {
+        let mut proxy = Proxy::default();
+        assert!(crate::parser::explodes::explode(url, &mut proxy));
+        serde_json::to_value(NodeView::from_proxy(&proxy, full)).unwrap()
+    }
+
+    fn keys(value: &serde_json::Value) -> Vec<&str> {
+        let mut keys: Vec<&str> = value
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(|key| key.as_str())
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    #[test]
+    fn test_ss_node_view() {
+        let link = "ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@1.2.3.4:8388#Node%20A";
+        let view = parse(link, false);
+        assert_eq!(
+            keys(&view),
+            ["group", "method", "password", "port", "remark", "server", "type"]
+        );
+        assert_eq!(view["type"], "ss");
+        assert_eq!(view["server"], "1.2.3.4");
+        assert_eq!(view["method"], "aes-256-gcm");
+        assert_eq!(view["password"], "***");
+
+        assert_eq!(parse(link, true)["password"], "password");
+    }
+
+    #[test]
+    fn test_vmess_node_view() {
+        let json = r#"{"v":"2","ps":"VMess A","add":"example.com","port":"443","id":"b831381d-6324-4d53-ad4f-8cda48b30811","aid":"0","net":"ws","type":"none","host":"cdn.example.com","path":"/ws","tls":"tls"}"#;
+        let link = format!("vmess://{}", base64_encode(json));
+        let view = parse(&link, false);
+        assert_eq!(view["type"], "vmess");
+        assert_eq!(view["uuid"], "***");
+        assert_eq!(view["network"], "ws");
+        assert_eq!(view["path"], "/ws");
+        assert_eq!(view["tls"], true);
+        assert!(view.get("password").is_none());
+        assert!(view.get("alter_id").is_none());
+        for key in ["server", "port", "remark", "host"] {
+            assert!(view.get(key).is_some(), "missing {}", key);
+        }
+
+        let full = parse(&link, true);
+        assert_eq!(full["uuid"], "b831381d-6324-4d53-ad4f-8cda48b30811");
+    }
+
+    #[test]
+    fn test_full_needs_access_token() {
+        let query = ParseQuery {
+            url: Some("ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@1.2.3.4:8388#A".to_string()),
+            full: Some("1".to_string()),
+            ..Default::default()
+        };
+        let response = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(parse_process(query));
+        assert_eq!(response.status_code, 403);
+    }
+}
@@ -0,0 +1,216 @@
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime};
+
+use crate::api::SubResponse;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::rulesets::refresher::{ruleset_refresh_status, RulesetRefreshStatus};
+use crate::settings::settings::settings_struct::{
+    default_health_check_timeout, default_health_check_url,
+};
+use crate::utils::http::{parse_proxy, web_get_async};
+use crate::utils::memory_cache;
+use crate::utils::system::safe_system_time;
+use crate::Settings;
+
+/// Time the service was started, used to report uptime
+static START_TIME: OnceLock<SystemTime> = OnceLock::new();
+
+/// Record the service start time. Calling it more than once keeps the first value.
+pub fn mark_start_time() {
+    START_TIME.get_or_init(safe_system_time);
+}
+
+/// Query parameters for the status endpoint
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+pub struct StatusQuery {
+    /// Run an active outbound probe when set to `1` or `true`
+    pub probe: Option<String>,
+    /// Authentication token, required for probing in API mode
+    pub token: Option<String>,
+}
+
+impl StatusQuery {
+    fn wants_probe(&self) -> bool {
+        matches!(self.probe.as_deref(), Some("1") | Some("true"))
+    }
+}
+
+/// Result of an active outbound connectivity probe
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ProbeResult {
+    pub url: String,
+    pub ok: bool,
+    pub status: Option<u16>,
+    pub latency_ms: u64,
+    pub error: Option<String>,
+}
+
+/// Service status report
+#[derive(Serialize, Debug, Clone)]
+pub struct StatusReport {
+    pub version: String,
+    pub uptime_secs: u64,
+    pub ruleset_count: usize,
+    pub cached_entries: usize,
+    pub settings_path: String,
+    /// Refresh state of the rulesets kept by the background refresher
+    #[cfg(not(target_arch = "wasm32"))]
+    pub rulesets: Vec<RulesetRefreshStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub probe: Option<ProbeResult>,
+}
+
+fn elapsed_ms(since: SystemTime) -> u64 {
+    safe_system_time()
+        .duration_since(since)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Fetch `url` through the regular http stack, giving up after `timeout`
+pub async fn probe_url(url: &str, timeout: Duration) -> ProbeResult {
+    let proxy_config = parse_proxy(&Settings::current().proxy_subscription);
+    let started = safe_system_time();
+
+    #[cfg(not(target_arch = "wasm32"))]
+    let outcome = match tokio::time::timeout(timeout, web_get_async(url, &proxy_config, None)).await
+    {
+        Ok(result) => result.map_err(|e| e.to_string()),
+        Err(_) => Err(format!("Probe timed out after {}ms", timeout.as_millis())),
+    };
+    // Fetch in the wasm runtime is bounded by the host, no timer is available here
+    #[cfg(target_arch = "wasm32")]
+    let outcome = {
+        let _ = timeout;
+        web_get_async(url, &proxy_config, None)
+            .await
+            .map_err(|e| e.to_string())
+    };
+
+    let latency_ms = elapsed_ms(started);
+    match outcome {
+        Ok(response) => ProbeResult {
+            url: url.to_string(),
+            ok: (200..400).contains(&response.status),
+            status: Some(response.status),
+            latency_ms,
+            error: None,
+        },
+        Err(e) => {
+            warn!("Health check probe to {} failed: {}", url, e);
+            ProbeResult {
+                url: url.to_string(),
+                ok: false,
+                status: None,
+                latency_ms,
+                error: Some(e),
+            }
+        }
+    }
+}
+
+/// Build the status report, running the outbound probe if requested and authorized
+pub async fn status_process(query: StatusQuery) -> SubResponse {
+    let (settings_path, ruleset_count, authorized, probe_url_setting, probe_timeout) = {
+        let global = Settings::current();
+        let authorized = !global.api_mode
+            || query.token.as_deref().unwrap_or_default() == global.api_access_token;
+        let url = if global.health_check_url.is_empty() {
+            default_health_check_url()
+        } else {
+            global.health_check_url.clone()
+        };
+        let timeout = if global.health_check_timeout == 0 {
+            default_health_check_timeout()
+        } else {
+            global.health_check_timeout
+        };
+        (
+            global.pref_path.clone(),
+            global.custom_rulesets.len(),
+            authorized,
+            url,
+            timeout,
+        )
+    };
+
+    let probe = if query.wants_probe() {
+        if !authorized {
+            return SubResponse::error("Unauthorized probe request".to_string(), 403);
+        }
+        debug!("Running health check probe against {}", probe_url_setting);
+        Some(
+            probe_url(
+                &probe_url_setting,
+                Duration::from_secs(probe_timeout as u64),
+            )
+            .await,
+        )
+    } else {
+        None
+    };
+
+    let report = StatusReport {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        uptime_secs: START_TIME
+            .get()
+            .map(|start| elapsed_ms(*start) / 1000)
+            .unwrap_or_default(),
+        ruleset_count,
+        cached_entries: memory_cache::size(),
+        settings_path,
+        #[cfg(not(target_arch = "wasm32"))]
+        rulesets: ruleset_refresh_status(),
+        probe,
+    };
+
+    match serde_json::to_string(&report) {
+        Ok(json) => SubResponse::ok(json, "application/json".to_string()),
+        Err(e) => SubResponse::error(format!("Failed to serialize status: {}", e), 500),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn test_status_json_shape() {
+        mark_start_time();
+        let response = block_on(status_process(StatusQuery::default()));
+        assert_eq!(response.status_code, 200);
+        assert_eq!(response.content_type, "application/json");
+
+        let json: serde_json::Value = serde_json::from_str(&response.content).unwrap();
+        assert_eq!(json["version"], env!("CARGO_PKG_VERSION"));
+        assert!(json["uptime_secs"].is_u64());
+        assert!(json["ruleset_count"].is_u64());
+        assert!(json["cached_entries"].is_u64());
+        assert!(json["settings_path"].is_string());
+        assert!(json["rulesets"].is_array());
+        assert!(json.get("probe").is_none());
+    }
+
+    #[test]
+    fn test_probe_timeout() {
+        // 10.255.255.1 is non-routable, so the connection attempt hangs until the timeout
+        let result = block_on(probe_url(
+            "http://10.255.255.1/generate_204",
+            Duration::from_millis(200),
+        ));
+        assert!(!result.ok);
+        assert!(result.status.is_none());
+        assert!(result.latency_ms < 5000);
+        assert!(result.error.is_some());
+    }
+}
@@ -1,16 +1,22 @@
 use log::{debug, error};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-
-use crate::constants::regex_black_list::REGEX_BLACK_LIST;
-use crate::interfaces::subconverter::{subconverter, SubconverterConfigBuilder};
-use crate::models::ruleset::RulesetConfigs;
-use crate::models::{ProxyGroupConfigs, RegexMatchConfigs, SubconverterTarget};
+use std::sync::Arc;
+
+use crate::interfaces::conversion_error::ConversionError;
+use crate::interfaces::query_config::{build_config_from_query, parse_rename_query};
+use crate::interfaces::subconverter::{
+    subconverter, subconverter_batch, subconverter_preview, SubconverterConfig, SubconverterResult,
+};
+use crate::models::{ConversionWarning, SubconverterTarget};
 use crate::settings::external::ExternalSettings;
+use crate::settings::refresh_configuration;
 use crate::settings::settings::init_settings;
-use crate::settings::{refresh_configuration, FromIni, FromIniWithDelimiter};
-use crate::utils::reg_valid;
-use crate::{RuleBases, Settings, TemplateArgs};
+use crate::utils::base64::base64_encode;
+use crate::utils::fetcher::{default_fetcher, SubscriptionFetcher};
+use crate::utils::tribool::deserialize_tribool;
+use crate::utils::url::url_encode;
+use crate::Settings;
 
 #[cfg(target_arch = "wasm32")]
 use {js_sys::Promise, wasm_bindgen::prelude::*, wasm_bindgen_futures::future_to_promise};
@@ -31,7 +37,8 @@ pub struct SubconverterQuery {
     pub new_name: Option<bool>,
     /// URLs to convert (pipe separated)
     pub url: Option<String>,
-    /// Custom group name
+    /// Custom group name of all nodes
+    #[serde(alias = "groupname")]
     pub group: Option<String>,
     /// Upload path (optional)
     pub upload_path: Option<String>,
@@ -39,28 +46,55 @@ pub struct SubconverterQuery {
     pub include: Option<String>,
     /// Exclude remarks regex, multiple regexes separated by '|'
     pub exclude: Option<String>,
+    /// Proxy types to keep, comma separated (e.g. `ss,vmess`)
+    pub include_types: Option<String>,
+    /// Proxy types to drop, comma separated, these win over `include_types`
+    pub exclude_types: Option<String>,
+    /// Keep nodes whose server address matches this regex
+    pub incsrv: Option<String>,
+    /// Drop nodes whose server address matches this regex
+    pub excsrv: Option<String>,
+    /// What proxy groups matching no node get: `direct` (default), `reject` or `error`
+    pub empty_group: Option<String>,
     /// custom groups
     pub groups: Option<String>,
     /// Ruleset contents
     pub ruleset: Option<String>,
     /// External configuration file (optional)
     pub config: Option<String>,
+    /// User-Agent of the subscription requests, defaults to one fitting the target
+    pub ua: Option<String>,
+    /// Fetch the `proxy-providers` of Clash configs, always done for authorized requests
+    pub follow_providers: Option<bool>,
 
     /// Device ID (for device-specific configurations)
     pub dev_id: Option<String>,
+    /// Reference the nodes through QuantumultX `server_remote`
+    pub server_remote: Option<bool>,
+    /// `as-policy` of the QuantumultX `server_remote` reference (e.g. `static`)
+    pub as_policy: Option<String>,
+    /// Use the proxy groups of a full Surge source config
+    pub source_groups: Option<bool>,
     /// Whether to insert nodes
     pub insert: Option<bool>,
     /// Whether to prepend insert nodes
     pub prepend: Option<bool>,
     /// Custom filename for download
     pub filename: Option<String>,
+    /// Web page sent to Clash clients in the `Profile-Web-Page-Url` header, empty to omit it
+    pub web_page_url: Option<String>,
+    /// Base64 encode the response body
+    pub b64: Option<bool>,
     /// Append proxy type to remarks
     pub append_type: Option<bool>,
+    /// Insert traffic and expiry info nodes
+    pub append_info: Option<bool>,
     /// Whether to remove old emoji and add new emoji
     pub emoji: Option<bool>,
     /// Whether to add emoji
     pub add_emoji: Option<bool>,
-    /// Whether to remove emoji
+    /// Whether to remove old emoji
+    #[serde(alias = "remove_old_emoji")]
     pub remove_emoji: Option<bool>,
     /// List mode (node list only)
     pub list: Option<bool>,
@@ -69,19 +103,31 @@ pub struct SubconverterQuery {
 
     /// Sort Script
     pub sort_script: Option<String>,
+    /// Nodes whose remark matches this regex are moved to the front after sorting
+    pub pin: Option<String>,
 
     /// argFilterDeprecated
     pub fdn: Option<bool>,
+    /// Include Shadowsocks nodes as origin/plain links in SSR output
+    pub ssr_format_ss: Option<bool>,
+    /// Maximum number of characters in a node remark
+    pub max_len: Option<usize>,
+    /// Template naming every node, e.g. `{flag} {remark}-{index:02}`
+    pub remark_tpl: Option<String>,
 
     /// Information for filtering, rename, emoji addition
     pub rename: Option<String>,
     /// Whether to enable TCP Fast Open
+    #[serde(default, deserialize_with = "deserialize_tribool")]
     pub tfo: Option<bool>,
     /// Whether to enable UDP
+    #[serde(default, deserialize_with = "deserialize_tribool")]
     pub udp: Option<bool>,
     /// Whether to skip certificate verification
+    #[serde(default, deserialize_with = "deserialize_tribool")]
     pub scv: Option<bool>,
     /// Whether to enable TLS 1.3
+    #[serde(default, deserialize_with = "deserialize_tribool")]
     pub tls13: Option<bool>,
     /// Enable rule generator
     pub rename_node: Option<bool>,
@@ -95,9 +141,29 @@ pub struct SubconverterQuery {
     pub token: Option<String>,
     /// Filter script
     pub filter: Option<String>,
+    /// Maximum subscription download size in bytes, authorized requests only
+    pub max_download_size: Option<i64>,
+    /// Maximum number of nodes, authorized requests only
+    pub max_proxies: Option<usize>,
+    /// Maximum number of rules, authorized requests only
+    pub max_rules: Option<usize>,
+    /// Attach the `X-Conversion-Profile` timing header (`1` or `true`), authorized requests only
+    pub profile: Option<String>,
+    /// Return the resolved groups and rulesets as JSON instead of the config (`1` or `true`)
+    pub dry_run: Option<String>,
+    /// Probe the nodes and mark the unreachable ones, authorized requests only
+    pub check: Option<bool>,
+    /// Drop unreachable nodes instead of marking them, implies `check`
+    pub check_drop: Option<bool>,
+    /// Prepend the flag of the server country to nodes no emoji rule matched, authorized requests only
+    pub geo_emoji: Option<bool>,
 
     /// Clash script
     pub script: Option<bool>,
+    /// Clash proxies output style (flow, block or compact)
+    pub proxies_style: Option<String>,
+    /// Clash proxy groups output style (flow, block or compact)
+    pub groups_style: Option<String>,
     pub classic: Option<bool>,
 
     pub expand: Option<bool>,
@@ -127,6 +193,9 @@ pub struct SubResponse {
     pub content_type: String,
     pub headers: HashMap<String, String>,
     pub status_code: u16,
+    /// Warnings of the conversion, summed up in the `X-Subconverter-Warnings` header
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<ConversionWarning>,
 }
 
 impl SubResponse {
@@ -136,6 +205,7 @@ impl SubResponse {
             content_type,
             headers: HashMap::new(),
             status_code: 200,
+            warnings: Vec::new(),
         }
     }
 
@@ -145,6 +215,7 @@ impl SubResponse {
             content_type: "text/plain".to_string(),
             headers: HashMap::new(),
             status_code,
+            warnings: Vec::new(),
         }
     }
 
@@ -152,337 +223,192 @@ impl SubResponse {
         self.headers = headers;
         self
     }
-}
-
-/// Handler for subscription conversion
-pub async fn sub_process(
-    req_url: Option<String>,
-    query: SubconverterQuery,
-) -> Result<SubResponse, Box<dyn std::error::Error>> {
-    let mut global = Settings::current();
 
-    // not initialized, in wasm that's common for cold start.
-    if global.pref_path.is_empty() {
-        debug!("Global config not initialized, reloading");
-        init_settings("").await?;
-        global = Settings::current();
-    } else if global.reload_conf_on_request && !global.api_mode && !global.generator_mode {
-        refresh_configuration().await;
-        global = Settings::current();
-    }
-
-    // Start building configuration
-    let mut builder = SubconverterConfigBuilder::new();
-
-    let target;
-    if let Some(_target) = &query.target {
-        match SubconverterTarget::from_str(&_target) {
-            Some(_target) => {
-                target = _target.clone();
-                if _target == SubconverterTarget::Auto {
-                    // TODO: Check user agent and set target accordingly
-                    // if let Some(user_agent) = req.headers().get("User-Agent") {
-                    //     if let Ok(user_agent) = user_agent.to_str() {
-
-                    //         // match_user_agent(
-                    //         //     user_agent,
-                    //         //     &target,
-                    //         //      query.new_name,
-                    //         //      &query.ver);
-                    //     }
-                    // }
-                    return Ok(SubResponse::error(
-                        "Auto user agent is not supported for now.".to_string(),
-                        400,
-                    ));
-                }
-                builder.target(_target);
-            }
-            None => {
-                return Ok(SubResponse::error(
-                    "Invalid target parameter".to_string(),
-                    400,
-                ));
-            }
+    /// Turn an error response into a JSON problem-details body
+    ///
+    /// The body is `{"error": code, "message": ..., "detail": ...}` where
+    /// `detail` is the original error text. Successful responses are returned
+    /// unchanged.
+    pub fn into_problem_details(self) -> Self {
+        if self.status_code < 400 {
+            return self;
+        }
+        let (code, message) = match self.status_code {
+            400 => ("invalid_request", "The request parameters are invalid"),
+            403 => ("forbidden", "The request is not authorized"),
+            422 => ("unprocessable", "The subscriptions could not be converted"),
+            502 => (
+                "upstream_failed",
+                "Failed to fetch or parse the subscriptions",
+            ),
+            _ => ("internal_error", "Failed to generate the configuration"),
+        };
+        let body = serde_json::json!({
+            "error": code,
+            "message": message,
+            "detail": self.content,
+        });
+        Self {
+            content: body.to_string(),
+            content_type: "application/json".to_string(),
+            ..self
         }
-    } else {
-        return Ok(SubResponse::error(
-            "Missing target parameter".to_string(),
-            400,
-        ));
     }
+}
 
-    builder.update_interval(match query.interval {
-        Some(interval) => interval,
-        None => global.update_interval,
-    });
-    // Check if we should authorize the request, if we are in API mode
-    let authorized =
-        !global.api_mode || query.token.as_deref().unwrap_or_default() == global.api_access_token;
-    builder.authorized(authorized);
-    builder.update_strict(query.strict.unwrap_or(global.update_strict));
-
-    if query
-        .include
-        .clone()
-        .is_some_and(|include| REGEX_BLACK_LIST.contains(&include))
-        || query
-            .exclude
-            .clone()
-            .is_some_and(|exclude| REGEX_BLACK_LIST.contains(&exclude))
-    {
-        return Ok(SubResponse::error(
-            "Invalid regex in request!".to_string(),
-            400,
-        ));
+/// HTTP status for an error returned by [`subconverter`]
+///
+/// 422 when the subscriptions held no usable nodes or broke a limit, 502
+/// when fetching or parsing the upstream subscriptions failed and 500 when
+/// no output could be generated. Unless failed links are skipped, the first
+/// URL failing stops the conversion before the other URLs are fetched, so a
+/// single failed URL is an upstream failure like all URLs failing.
+fn conversion_error_status(error: &ConversionError) -> u16 {
+    match error {
+        ConversionError::NoNodes
+        | ConversionError::TooManyNodes { .. }
+        | ConversionError::TooManyRules { .. }
+        | ConversionError::EmptyGroups(_) => 422,
+        ConversionError::UrlFailed { .. } | ConversionError::AllUrlsFailed(_) => 502,
+        ConversionError::EmptyOutput(_) => 500,
     }
+}
 
-    let enable_insert = match query.insert {
-        Some(insert) => insert,
-        None => global.enable_insert,
+/// Turn a conversion result into a response, base64 encoding the body if requested
+///
+/// Encoding happens last so headers computed from the plain content stay untouched.
+fn build_sub_response(result: SubconverterResult, b64: bool) -> SubResponse {
+    let mut response = if b64 {
+        SubResponse::ok(base64_encode(&result.content), "text/plain".to_string())
+    } else {
+        SubResponse::ok(result.content, result.content_type)
     };
+    response.warnings = result.warnings;
+    response.with_headers(result.headers)
+}
 
-    if enable_insert {
-        builder.insert_urls(global.insert_urls.clone());
-        // 加在前面还是加在后面
-        builder.prepend_insert(query.prepend.unwrap_or(global.prepend_insert));
-    }
-
-    let urls = match query.url.as_deref() {
-        Some(query_url) => query_url.split('|').map(|s| s.to_owned()).collect(),
-        None => {
-            if authorized {
-                global.default_urls.clone()
-            } else {
-                vec![]
-            }
-        }
+/// Report the skipped `rename` entries of a request in the `X-Rename-Errors` header
+fn add_rename_errors_header(response: &mut SubResponse, query: &SubconverterQuery) {
+    let Some(rename) = query.rename.as_deref() else {
+        return;
     };
-    builder.urls(urls);
-
-    // TODO: what if urls still empty after insert?
-
-    // Create template args from request parameters and other settings
-    let mut template_args = TemplateArgs::default();
-    template_args.global_vars = global.template_vars.clone();
+    let (_, errors) = parse_rename_query(rename);
+    if !errors.is_empty() {
+        let errors = errors
+            .iter()
+            .map(|error| url_encode(error))
+            .collect::<Vec<_>>();
+        response
+            .headers
+            .insert("X-Rename-Errors".to_string(), errors.join(","));
+    }
+}
 
-    template_args.request_params = query.clone();
+/// Whether the request asked for a structural preview instead of the config
+fn dry_run_requested(query: &SubconverterQuery) -> bool {
+    matches!(query.dry_run.as_deref(), Some("1") | Some("true"))
+}
 
-    builder.append_proxy_type(query.append_type.unwrap_or(global.append_type));
+/// Build the conversion config described by the request parameters
+///
+/// The external config of the request is loaded here, the parameters are
+/// mapped by [`build_config_from_query`]. The external config and the
+/// subscriptions are both fetched by `fetcher`, which becomes the fetcher of
+/// the config. Invalid parameters are reported as a ready to send error
+/// response.
+async fn build_sub_config(
+    query: &SubconverterQuery,
+    global: &Arc<Settings>,
+    fetcher: Arc<dyn SubscriptionFetcher>,
+) -> Result<SubconverterConfig, SubResponse> {
+    let external = load_external_config(query, global, fetcher.as_ref()).await;
+    let mut config = build_config_from_query(query, global, external.as_ref())
+        .map_err(|e| SubResponse::error(e, 400))?;
+    config.fetcher = fetcher;
+    Ok(config)
+}
 
-    let mut arg_expand_rulesets = query.expand;
-    if target.is_clash() && query.script.is_none() {
-        arg_expand_rulesets = Some(true);
+/// Load the external config of the request, or the default one of the settings
+///
+/// A config failing to load is logged and the request goes on without it.
+async fn load_external_config(
+    query: &SubconverterQuery,
+    global: &Settings,
+    fetcher: &dyn SubscriptionFetcher,
+) -> Option<ExternalSettings> {
+    let ext_config = query
+        .config
+        .as_deref()
+        .unwrap_or(&global.default_ext_config);
+    if ext_config.is_empty() {
+        return None;
     }
-
-    // flags
-    builder.tfo(query.tfo.or(global.tfo_flag));
-    builder.udp(query.udp.or(global.udp_flag));
-    builder.skip_cert_verify(query.scv.or(global.skip_cert_verify));
-    builder.tls13(query.tls13.or(global.tls13_flag));
-    builder.sort(query.sort.unwrap_or(global.enable_sort));
-    if let Some(script) = &query.sort_script {
-        builder.sort_script(script.clone());
+    debug!("Loading external config from {}", ext_config);
+
+    // In WebAssembly environment, we can't use std::thread::spawn
+    // Instead, we use the async version directly
+    match ExternalSettings::load_with_fetcher(ext_config, fetcher, global).await {
+        Ok(mut extconf) => {
+            debug!("Successfully loaded external config from {}", ext_config);
+            extconf.clear_unusable_bases(&global.base_path).await;
+            Some(extconf)
+        }
+        Err(e) => {
+            error!("Failed to load external config from {}: {}", ext_config, e);
+            None
+        }
     }
+}
 
-    builder.filter_deprecated(query.fdn.unwrap_or(global.filter_deprecated));
-    builder.clash_new_field_name(query.new_name.unwrap_or(global.clash_use_new_field));
-    builder.clash_script(query.script.unwrap_or_default());
-    builder.clash_classical_ruleset(query.classic.unwrap_or_default());
-    let nodelist = query.list.unwrap_or_default();
-    builder.nodelist(nodelist);
+/// Handler for subscription conversion
+pub async fn sub_process(
+    req_url: Option<String>,
+    query: SubconverterQuery,
+) -> Result<SubResponse, Box<dyn std::error::Error>> {
+    sub_process_with(req_url, query, Settings::current(), default_fetcher()).await
+}
 
-    if arg_expand_rulesets != Some(true) {
-        builder.clash_new_field_name(true);
-    } else {
-        builder.managed_config_prefix(global.managed_config_prefix.clone());
-        builder.clash_script(false);
+/// Handler for subscription conversion with a settings snapshot and a fetcher
+///
+/// The request runs with `global` even when the settings are reloaded
+/// meanwhile, settings that were never loaded are loaded first. The external
+/// config and the subscriptions are fetched by `fetcher`.
+pub async fn sub_process_with(
+    _req_url: Option<String>,
+    query: SubconverterQuery,
+    mut global: Arc<Settings>,
+    fetcher: Arc<dyn SubscriptionFetcher>,
+) -> Result<SubResponse, Box<dyn std::error::Error>> {
+    // not initialized, in wasm that's common for cold start.
+    if global.pref_path.is_empty() {
+        debug!("Global config not initialized, reloading");
+        init_settings("").await?;
+        global = Settings::current();
+    } else if global.reload_conf_on_request && !global.api_mode && !global.generator_mode {
+        refresh_configuration().await;
+        global = Settings::current();
     }
 
-    let mut ruleset_configs = global.custom_rulesets.clone();
-    let mut custom_group_configs = global.custom_proxy_groups.clone();
-
-    // 这部分参数有优先级：query > external > global
-    builder.include_remarks(global.include_remarks.clone());
-    builder.exclude_remarks(global.exclude_remarks.clone());
-    builder.rename_array(global.renames.clone());
-    builder.emoji_array(global.emojis.clone());
-    builder.add_emoji(global.add_emoji);
-    builder.remove_emoji(global.remove_emoji);
-    builder.enable_rule_generator(global.enable_rule_gen);
-    let mut rule_bases = RuleBases {
-        clash_rule_base: global.clash_base.clone(),
-        surge_rule_base: global.surge_base.clone(),
-        surfboard_rule_base: global.surfboard_base.clone(),
-        mellow_rule_base: global.mellow_base.clone(),
-        quan_rule_base: global.quan_base.clone(),
-        quanx_rule_base: global.quanx_base.clone(),
-        loon_rule_base: global.loon_base.clone(),
-        sssub_rule_base: global.ssub_base.clone(),
-        singbox_rule_base: global.singbox_base.clone(),
+    let config = match build_sub_config(&query, &global, fetcher).await {
+        Ok(config) => config,
+        Err(response) => return Ok(response),
     };
-    builder.rule_bases(rule_bases.clone());
-    builder.template_args(template_args.clone());
 
-    let ext_config = match query.config.as_deref() {
-        Some(config) => config.to_owned(),
-        None => global.default_ext_config.clone(),
-    };
-    if !ext_config.is_empty() {
-        debug!("Loading external config from {}", ext_config);
-
-        // In WebAssembly environment, we can't use std::thread::spawn
-        // Instead, we use the async version directly
-        let extconf_result = ExternalSettings::load_from_file(&ext_config).await;
-
-        match extconf_result {
-            Ok(extconf) => {
-                debug!("Successfully loaded external config from {}", ext_config);
-                if !nodelist {
-                    rule_bases
-                        .check_external_bases(&extconf, &global.base_path)
-                        .await;
-                    builder.rule_bases(rule_bases);
-
-                    if let Some(tpl_args) = extconf.tpl_args {
-                        template_args.local_vars = tpl_args;
-                    }
-
-                    builder.template_args(template_args);
-
-                    if !target.is_simple() {
-                        if !extconf.custom_rulesets.is_empty() {
-                            ruleset_configs = extconf.custom_rulesets;
-                        }
-                        if !extconf.custom_proxy_groups.is_empty() {
-                            custom_group_configs = extconf.custom_proxy_groups;
-                        }
-                        if let Some(enable_rule_gen) = extconf.enable_rule_generator {
-                            builder.enable_rule_generator(enable_rule_gen);
-                        }
-                        if let Some(overwrite_original_rules) = extconf.overwrite_original_rules {
-                            builder.overwrite_original_rules(overwrite_original_rules);
-                        }
-                    }
-                }
-                if !extconf.rename_nodes.is_empty() {
-                    builder.rename_array(extconf.rename_nodes);
-                }
-                if !extconf.emojis.is_empty() {
-                    builder.emoji_array(extconf.emojis);
-                }
-                if !extconf.include_remarks.is_empty() {
-                    builder.include_remarks(extconf.include_remarks);
-                }
-                if !extconf.exclude_remarks.is_empty() {
-                    builder.exclude_remarks(extconf.exclude_remarks);
-                }
-                if extconf.add_emoji.is_some() {
-                    builder.add_emoji(extconf.add_emoji.unwrap());
-                }
-                if extconf.remove_old_emoji.is_some() {
-                    builder.remove_emoji(extconf.remove_old_emoji.unwrap());
-                }
-            }
+    if dry_run_requested(&query) {
+        return Ok(match subconverter_preview(config).await {
+            Ok(preview) => SubResponse::ok(
+                serde_json::to_string_pretty(&preview)?,
+                "application/json".to_string(),
+            ),
             Err(e) => {
-                error!("Failed to load external config from {}: {}", ext_config, e);
+                error!("Subconverter preview error: {}", e);
+                SubResponse::error(
+                    format!("Conversion error: {}", e),
+                    conversion_error_status(&e),
+                )
             }
-        }
-    }
-
-    // 请求参数的覆盖优先级最高
-    if let Some(include) = query.include.as_deref() {
-        if reg_valid(&include) {
-            builder.include_remarks(vec![include.to_owned()]);
-        }
-    }
-    if let Some(exclude) = query.exclude.as_deref() {
-        if reg_valid(&exclude) {
-            builder.exclude_remarks(vec![exclude.to_owned()]);
-        }
-    }
-    if let Some(emoji) = query.emoji {
-        builder.add_emoji(emoji);
-        builder.remove_emoji(true);
-    }
-
-    if let Some(add_emoji) = query.add_emoji {
-        builder.add_emoji(add_emoji);
+        });
     }
-    if let Some(remove_emoji) = query.remove_emoji {
-        builder.remove_emoji(remove_emoji);
-    }
-    if let Some(rename) = query.rename.as_deref() {
-        if !rename.is_empty() {
-            let v_array: Vec<String> = rename.split('`').map(|s| s.to_string()).collect();
-            builder.rename_array(RegexMatchConfigs::from_ini_with_delimiter(&v_array, "@"));
-        }
-    }
-
-    if !target.is_simple() {
-        // loading custom groups
-        if !query
-            .groups
-            .as_deref()
-            .is_none_or(|groups| groups.is_empty())
-            && !nodelist
-        {
-            if let Some(groups) = query.groups.as_deref() {
-                let v_array: Vec<String> = groups.split('@').map(|s| s.to_string()).collect();
-                custom_group_configs = ProxyGroupConfigs::from_ini(&v_array);
-            }
-        }
-        // loading custom rulesets
-        if !query
-            .ruleset
-            .as_deref()
-            .is_none_or(|ruleset| ruleset.is_empty())
-            && !nodelist
-        {
-            if let Some(ruleset) = query.ruleset.as_deref() {
-                let v_array: Vec<String> = ruleset.split('@').map(|s| s.to_string()).collect();
-                ruleset_configs = RulesetConfigs::from_ini(&v_array);
-            }
-        }
-    }
-    builder.proxy_groups(custom_group_configs);
-    builder.ruleset_configs(ruleset_configs);
-
-    // TODO: process with the script runtime
-
-    // parse settings
-
-    // Process group name
-    builder.group_name(query.group.clone());
-    builder.filename(query.filename.clone());
-    builder.upload(query.upload.unwrap_or_default());
-
-    // // Process filter script
-    // if let Some(filter) = &query.filter {
-    //     builder = builder.filter_script(Some(filter.clone()));
-    // }
-
-    // // Process device ID
-    // if let Some(dev_id) = &query.dev_id {
-    //     builder = builder.device_id(Some(dev_id.clone()));
-    // }
-
-    // // Set managed config prefix from global settings
-    // if !global.managed_config_prefix.is_empty() {
-    //     builder = builder.managed_config_prefix(global.managed_config_prefix.clone());
-    // }
-
-    // Build and validate configuration
-    let config = match builder.build() {
-        Ok(cfg) => cfg,
-        Err(e) => {
-            error!("Failed to build subconverter config: {}", e);
-            return Ok(SubResponse::error(
-                format!("Configuration error: {}", e),
-                400,
-            ));
-        }
-    };
 
     // Run subconverter directly instead of spawning a thread
     // This is necessary for WebAssembly compatibility
@@ -491,26 +417,68 @@ pub async fn sub_process(
 
     match subconverter_result {
         Ok(result) => {
-            // Determine content type based on target
-            let content_type = match target {
-                SubconverterTarget::Clash
-                | SubconverterTarget::ClashR
-                | SubconverterTarget::SingBox => "application/yaml",
-                SubconverterTarget::SSSub | SubconverterTarget::SSD => "application/json",
-                _ => "text/plain",
-            };
-
             debug!("Subconverter completed successfully");
-            Ok(SubResponse::ok(result.content, content_type.to_string())
-                .with_headers(result.headers))
+            let mut response = build_sub_response(result, query.b64.unwrap_or(false));
+            add_rename_errors_header(&mut response, &query);
+            Ok(response)
         }
         Err(e) => {
             error!("Subconverter error: {}", e);
-            Ok(SubResponse::error(format!("Conversion error: {}", e), 500))
+            Ok(SubResponse::error(
+                format!("Conversion error: {}", e),
+                conversion_error_status(&e),
+            ))
         }
     }
 }
 
+/// Convert the subscriptions of a request to several targets at once
+///
+/// The subscriptions are fetched and parsed only once. When the query has no
+/// target the first of `targets` is used for the target dependent parameters.
+/// Every target gets its own response, an invalid request or a failure of the
+/// shared stages is returned as the error response. All targets are converted
+/// with the `global` snapshot.
+pub async fn sub_process_batch(
+    mut query: SubconverterQuery,
+    targets: &[SubconverterTarget],
+    global: &Arc<Settings>,
+) -> Result<Vec<(SubconverterTarget, SubResponse)>, SubResponse> {
+    let first = targets
+        .first()
+        .ok_or_else(|| SubResponse::error("Missing target parameter".to_string(), 400))?;
+    if query.target.is_none() {
+        query.target = Some(first.to_str());
+    }
+
+    let config = build_sub_config(&query, global, default_fetcher()).await?;
+    let b64 = query.b64.unwrap_or(false);
+    let results = subconverter_batch(config, targets).await.map_err(|e| {
+        error!("Subconverter error: {}", e);
+        SubResponse::error(
+            format!("Conversion error: {}", e),
+            conversion_error_status(&e),
+        )
+    })?;
+    Ok(results
+        .into_iter()
+        .map(|(target, result)| {
+            let response = match result {
+                Ok(result) => {
+                    let mut response = build_sub_response(result, b64);
+                    add_rename_errors_header(&mut response, &query);
+                    response
+                }
+                Err(e) => SubResponse::error(
+                    format!("Conversion error: {}", e),
+                    conversion_error_status(&e),
+                ),
+            };
+            (target, response)
+        })
+        .collect())
+}
+
 #[cfg(target_arch = "wasm32")]
 #[wasm_bindgen]
 pub fn sub_process_wasm(query_json: &str) -> Promise {
@@ -563,3 +531,168 @@ pub fn init_settings_wasm(pref_path: &str) -> Promise {
 
     future_to_promise(future)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interfaces::subconverter::SubconverterConfigBuilder;
+    use crate::utils::base64::base64_decode;
+
+    const LINK: &str = "ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@1.2.3.4:8388#Node%20A";
+
+    fn convert(target: SubconverterTarget, b64: bool) -> SubResponse {
+        let mut builder = SubconverterConfigBuilder::new();
+        builder
+            .target(target)
+            .add_url(&format!("data:text/plain;base64,{}", base64_encode(LINK)))
+            .nodelist(true);
+        let config = builder.build().unwrap();
+
+        let result = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(subconverter(config))
+            .unwrap();
+        build_sub_response(result, b64)
+    }
+
+    #[test]
+    fn test_content_type_per_target() {
+        let clash = convert(SubconverterTarget::Clash, false);
+        assert_eq!(clash.content_type, "text/yaml");
+        assert!(clash.content.contains("Node A"));
+
+        let singbox = convert(SubconverterTarget::SingBox, false);
+        assert_eq!(singbox.content_type, "application/json");
+        assert!(serde_json::from_str::<serde_json::Value>(&singbox.content).is_ok());
+
+        let ssd = convert(SubconverterTarget::SSD, false);
+        assert_eq!(ssd.content_type, "text/plain");
+        assert!(ssd.content.starts_with("ssd://"));
+    }
+
+    #[test]
+    fn test_mixed_b64_response() {
+        let plain = convert(SubconverterTarget::Mixed, false);
+        let encoded = convert(SubconverterTarget::Mixed, true);
+        assert_eq!(encoded.content_type, "text/plain");
+        assert_eq!(encoded.headers, plain.headers);
+        assert_eq!(base64_decode(&encoded.content, false), plain.content);
+    }
+
+    #[test]
+    fn test_conversion_error_status() {
+        let status_of = |content: &str, exclude: Vec<String>| {
+            let mut builder = SubconverterConfigBuilder::new();
+            builder
+                .target(SubconverterTarget::Clash)
+                .add_url(&format!(
+                    "data:text/plain;base64,{}",
+                    base64_encode(content)
+                ))
+                .exclude_remarks(exclude);
+            let error = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap()
+                .block_on(subconverter(builder.build().unwrap()))
+                .unwrap_err();
+            conversion_error_status(&error)
+        };
+
+        assert_eq!(status_of(LINK, vec!["Node".to_string()]), 422);
+        assert_eq!(status_of("not a subscription", vec![]), 502);
+        assert_eq!(
+            conversion_error_status(&ConversionError::EmptyOutput("clash".to_string())),
+            500
+        );
+    }
+
+    #[test]
+    fn test_problem_details() {
+        let response =
+            SubResponse::error("No nodes were found!".to_string(), 422).into_problem_details();
+        assert_eq!(response.status_code, 422);
+        assert_eq!(response.content_type, "application/json");
+        let body: serde_json::Value = serde_json::from_str(&response.content).unwrap();
+        assert_eq!(body["error"], "unprocessable");
+        assert_eq!(body["detail"], "No nodes were found!");
+
+        let ok = SubResponse::ok("proxies: []".to_string(), "text/yaml".to_string());
+        assert_eq!(ok.into_problem_details().content, "proxies: []");
+    }
+
+    #[test]
+    fn test_rename_errors_header() {
+        let query = actix_web::web::Query::<SubconverterQuery>::from_query(
+            "target=clash&rename=%5E(HK)%20(%5Cd%2B)%40%241-%242%40edge%60Node%2540Node%20%60%5Bbad%40x%60no-at",
+        )
+        .unwrap()
+        .into_inner();
+        let mut response = SubResponse::ok(String::new(), "text/plain".to_string());
+        add_rename_errors_header(&mut response, &query);
+        assert_eq!(
+            response.headers["X-Rename-Errors"],
+            "invalid%20regex%20in%20%27%5Bbad%40x%27,missing%20%27%40%27%20in%20%27no-at%27"
+        );
+    }
+
+    #[test]
+    fn test_invalid_config_is_rejected_with_every_error() {
+        let query = actix_web::web::Query::<SubconverterQuery>::from_query(&format!(
+            "target=clash&url={}&include=%28HK&upload=true",
+            url_encode(LINK)
+        ))
+        .unwrap()
+        .into_inner();
+        let response = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(build_sub_config(&query, &Arc::default(), default_fetcher()))
+            .unwrap_err();
+        assert_eq!(response.status_code, 400);
+        assert_eq!(
+            response.content,
+            "Configuration error:\n- upload_path: upload is enabled but no upload path is set\n- include_remarks: invalid regex '(HK'"
+        );
+    }
+
+    #[test]
+    fn test_external_config_uses_the_fetcher() {
+        use crate::utils::fetcher::{FetchedBody, MemoryFetcher};
+
+        let mut fetcher = MemoryFetcher::new();
+        fetcher
+            .insert(
+                "https://example.com/ext.ini",
+                FetchedBody::new("[custom]\ncustom_proxy_group=Auto`select`.*\n"),
+            )
+            .insert(
+                "https://example.com/sub",
+                FetchedBody::new(base64_encode(LINK)),
+            );
+        let query = actix_web::web::Query::<SubconverterQuery>::from_query(&format!(
+            "target=clash&url={}&config={}",
+            url_encode("https://example.com/sub"),
+            url_encode("https://example.com/ext.ini")
+        ))
+        .unwrap()
+        .into_inner();
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let config = runtime
+            .block_on(build_sub_config(&query, &Arc::default(), Arc::new(fetcher)))
+            .unwrap();
+        assert_eq!(config.proxy_groups.len(), 1);
+        assert_eq!(config.proxy_groups[0].name, "Auto");
+
+        // The subscriptions go through the same fetcher
+        let result = runtime.block_on(subconverter(config)).unwrap();
+        assert!(result.content.contains("Node A"), "{}", result.content);
+    }
+}
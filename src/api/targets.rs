@@ -0,0 +1,236 @@
+use serde::Serialize;
+
+use crate::api::SubResponse;
+use crate::models::SubconverterTarget;
+
+/// Kind of value a query option takes, used by the web UI to pick an input
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OptionKind {
+    Bool,
+    Text,
+    Number,
+}
+
+/// A query option of the `/sub` endpoint
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct OptionHint {
+    pub name: &'static str,
+    pub kind: OptionKind,
+    pub description: &'static str,
+}
+
+/// A conversion target and the options specific to it
+#[derive(Serialize, Debug, Clone)]
+pub struct TargetInfo {
+    /// Value of the `target` query parameter
+    pub name: String,
+    pub content_type: &'static str,
+    pub extension: &'static str,
+    pub options: Vec<OptionHint>,
+}
+
+/// Response of the `/api/targets` endpoint
+#[derive(Serialize, Debug, Clone)]
+pub struct TargetList {
+    pub targets: Vec<TargetInfo>,
+    pub common_options: Vec<OptionHint>,
+}
+
+const fn hint(name: &'static str, kind: OptionKind, description: &'static str) -> OptionHint {
+    OptionHint {
+        name,
+        kind,
+        description,
+    }
+}
+
+/// Options understood by every target
+const COMMON_OPTIONS: [OptionHint; 19] = [
+    hint("config", OptionKind::Text, "External config URL"),
+    hint(
+        "ua",
+        OptionKind::Text,
+        "User-Agent sent when fetching the subscriptions",
+    ),
+    hint(
+        "follow_providers",
+        OptionKind::Bool,
+        "Fetch the proxy-providers of Clash configs",
+    ),
+    hint(
+        "include",
+        OptionKind::Text,
+        "Keep nodes matching this regex",
+    ),
+    hint(
+        "exclude",
+        OptionKind::Text,
+        "Drop nodes matching this regex",
+    ),
+    hint(
+        "incsrv",
+        OptionKind::Text,
+        "Keep nodes whose server matches this regex",
+    ),
+    hint(
+        "excsrv",
+        OptionKind::Text,
+        "Drop nodes whose server matches this regex",
+    ),
+    hint(
+        "rename",
+        OptionKind::Text,
+        "Rename rules, match@replace joined by `",
+    ),
+    hint("filename", OptionKind::Text, "File name of the download"),
+    hint("emoji", OptionKind::Bool, "Add flag emoji to node names"),
+    hint(
+        "append_type",
+        OptionKind::Bool,
+        "Prefix node names with their type",
+    ),
+    hint("list", OptionKind::Bool, "Output the node list only"),
+    hint("sort", OptionKind::Bool, "Sort nodes by name"),
+    hint("udp", OptionKind::Bool, "Enable UDP relay"),
+    hint("tfo", OptionKind::Bool, "Enable TCP Fast Open"),
+    hint("scv", OptionKind::Bool, "Skip certificate verification"),
+    hint(
+        "fdn",
+        OptionKind::Bool,
+        "Filter nodes the target does not support",
+    ),
+    hint(
+        "max_len",
+        OptionKind::Number,
+        "Maximum length of node names",
+    ),
+    hint(
+        "remark_tpl",
+        OptionKind::Text,
+        "Template of node names, e.g. {flag} {remark}-{index:02}",
+    ),
+];
+
+/// Targets offered by the web UI, in display order
+fn supported_targets() -> Vec<SubconverterTarget> {
+    vec![
+        SubconverterTarget::Clash,
+        SubconverterTarget::ClashR,
+        SubconverterTarget::SingBox,
+        SubconverterTarget::Surge(4),
+        SubconverterTarget::Surfboard,
+        SubconverterTarget::QuantumultX,
+        SubconverterTarget::Quantumult,
+        SubconverterTarget::Loon,
+        SubconverterTarget::Mellow,
+        SubconverterTarget::SSSub,
+        SubconverterTarget::SSD,
+        SubconverterTarget::SS,
+        SubconverterTarget::SSR,
+        SubconverterTarget::V2Ray,
+        SubconverterTarget::Trojan,
+        SubconverterTarget::Mixed,
+    ]
+}
+
+/// Options only meaningful for `target`
+fn target_options(target: &SubconverterTarget) -> Vec<OptionHint> {
+    match target {
+        SubconverterTarget::Clash | SubconverterTarget::ClashR => vec![
+            hint(
+                "new_name",
+                OptionKind::Bool,
+                "Use the new Clash field names",
+            ),
+            hint("script", OptionKind::Bool, "Generate a Clash script config"),
+            hint("classic", OptionKind::Bool, "Use classical rule providers"),
+            hint(
+                "expand",
+                OptionKind::Bool,
+                "Put the full rules into the config",
+            ),
+            hint(
+                "interval",
+                OptionKind::Number,
+                "Update interval in seconds, sent in hours",
+            ),
+            hint(
+                "web_page_url",
+                OptionKind::Text,
+                "Web page of the subscription shown by the client",
+            ),
+        ],
+        SubconverterTarget::Surge(_) => vec![
+            hint("ver", OptionKind::Number, "Surge version"),
+            hint(
+                "expand",
+                OptionKind::Bool,
+                "Put the full rules into the config",
+            ),
+        ],
+        SubconverterTarget::QuantumultX | SubconverterTarget::Loon => vec![hint(
+            "expand",
+            OptionKind::Bool,
+            "Put the full rules into the config",
+        )],
+        SubconverterTarget::SSR => vec![hint(
+            "ssr_format_ss",
+            OptionKind::Bool,
+            "Output compatible Shadowsocks nodes as SSR links",
+        )],
+        _ => Vec::new(),
+    }
+}
+
+/// List the supported targets and their query options
+pub fn targets_process() -> SubResponse {
+    let targets = supported_targets()
+        .iter()
+        .map(|target| TargetInfo {
+            name: match target {
+                SubconverterTarget::Surge(_) => "surge".to_string(),
+                _ => target.to_str(),
+            },
+            content_type: target.content_type(),
+            extension: target.file_extension(),
+            options: target_options(target),
+        })
+        .collect();
+    let list = TargetList {
+        targets,
+        common_options: COMMON_OPTIONS.to_vec(),
+    };
+
+    match serde_json::to_string(&list) {
+        Ok(json) => SubResponse::ok(json, "application/json".to_string()),
+        Err(e) => SubResponse::error(format!("Failed to serialize targets: {}", e), 500),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_targets_process() {
+        let response = targets_process();
+        assert_eq!(response.status_code, 200);
+        assert_eq!(response.content_type, "application/json");
+
+        let json: serde_json::Value = serde_json::from_str(&response.content).unwrap();
+        let targets = json["targets"].as_array().unwrap();
+        for target in targets {
+            let name = target["name"].as_str().unwrap();
+            assert!(SubconverterTarget::from_str(name).is_some(), "{}", name);
+        }
+        assert_eq!(targets[0]["name"], "clash");
+        assert_eq!(targets[0]["extension"], "yml");
+        assert_eq!(targets[0]["options"][0]["kind"], "bool");
+        assert!(json["common_options"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|option| option["name"] == "include" && option["kind"] == "text"));
+    }
+}
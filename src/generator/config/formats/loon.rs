@@ -1,14 +1,29 @@
-use crate::generator::config::group::group_generate;
+use crate::generator::config::group::{
+    apply_group_filters, group_generate, group_names, other_group_names,
+};
 use crate::generator::config::remark::process_remark;
+use crate::generator::ruleconvert::ruleset_to_clash_providers::provider_base_name;
 use crate::generator::ruleconvert::ruleset_to_surge::ruleset_to_surge;
 use crate::models::{
     BalanceStrategy, ExtraSettings, Proxy, ProxyGroupConfigs, ProxyGroupType, ProxyType,
-    RulesetContent,
+    RulesetContent, RulesetType,
 };
+use crate::utils::base64::url_safe_base64_encode;
+use crate::utils::file_exists;
 use crate::utils::ini_reader::IniReader;
+use crate::utils::network::{bracket_host, is_link};
+use crate::utils::plugin_options::plugin_option_value;
 use crate::utils::string::join;
+use crate::utils::tribool::TriboolExt;
 use log::error;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+
+/// Append `tls13` to a proxy line when it is defined
+fn push_tls13(proxy: &mut String, tls13: Option<bool>) {
+    if let Some(tls13) = tls13 {
+        proxy.push_str(&format!(",tls13={}", tls13));
+    }
+}
 
 /// Convert proxies to Loon format
 ///
@@ -57,13 +72,12 @@ pub async fn proxy_to_loon(
     for node in nodes.iter_mut() {
         // Add proxy type prefix if enabled
         if ext.append_proxy_type {
-            let proxy_type = node.proxy_type.to_string();
-            node.remark = format!("[{}] {}", proxy_type, node.remark);
+            node.remark = format!("[{}] {}", node.proxy_type, node.remark);
         }
 
         // Process remark
         let mut remark = node.remark.clone();
-        process_remark(&mut remark, &remarks_list, false);
+        process_remark(&mut remark, &remarks_list, false, ext.max_remark_length);
 
         // Extract node properties for easier access
         let hostname = &node.hostname;
@@ -84,8 +98,12 @@ pub async fn proxy_to_loon(
         let tls_secure = node.tls_secure;
 
         // Define tribool values with defaults from ext and override with node-specific values
+        let udp = node.udp.define(ext.udp);
+        let tfo = node.tcp_fast_open.define(ext.tfo);
         let scv = ext.skip_cert_verify;
         let scv = node.allow_insecure.as_ref().map_or(scv, |val| Some(*val));
+        let tls13 = node.tls13.define(ext.tls13);
+        // Loon has no per-node underlying proxy, chains are left out
 
         let mut proxy;
 
@@ -98,11 +116,15 @@ pub async fn proxy_to_loon(
                 );
 
                 if plugin == "simple-obfs" || plugin == "obfs-local" {
-                    if !pluginopts.is_empty() {
-                        // Replace obfs=xxx;obfs-host=yyy with xxx,yyy
-                        let plugin_opts =
-                            pluginopts.replace(";obfs-host=", ",").replace("obfs=", "");
-                        proxy.push_str(&format!(",{}", plugin_opts));
+                    // Loon takes the obfs mode and host as positional fields
+                    if let Some(obfs) = plugin_option_value(pluginopts, "obfs") {
+                        proxy.push_str(&format!(",{}", obfs));
+                        if let Some(host) = plugin_option_value(pluginopts, "obfs-host") {
+                            proxy.push_str(&format!(",{}", host));
+                        }
+                        if obfs == "tls" {
+                            push_tls13(&mut proxy, tls13);
+                        }
                     }
                 } else if !plugin.is_empty() {
                     continue;
@@ -126,6 +148,7 @@ pub async fn proxy_to_loon(
 
                 if tls_secure {
                     proxy.push_str(&format!(",tls-name={}", host));
+                    push_tls13(&mut proxy, tls13);
                 }
 
                 match transproto {
@@ -151,9 +174,19 @@ pub async fn proxy_to_loon(
             }
             ProxyType::ShadowsocksR => {
                 proxy = format!(
-                    "ShadowsocksR,{},{},{},\"{}\",protocol={},protocol-param={},obfs={},obfs-param={}",
-                    hostname, port, method, password, protocol, protoparam, obfs, obfsparam
+                    "ShadowsocksR,{},{},{},\"{}\",protocol={}",
+                    hostname, port, method, password, protocol
                 );
+
+                if !protoparam.is_empty() {
+                    proxy.push_str(&format!(",protocol-param={}", protoparam));
+                }
+
+                proxy.push_str(&format!(",obfs={}", obfs));
+
+                if !obfsparam.is_empty() {
+                    proxy.push_str(&format!(",obfs-param={}", obfsparam));
+                }
             }
             ProxyType::HTTP => {
                 proxy = format!("http,{},{}", hostname, port);
@@ -198,6 +231,7 @@ pub async fn proxy_to_loon(
                 if !host.is_empty() {
                     proxy.push_str(&format!(",tls-name={}", host));
                 }
+                push_tls13(&mut proxy, tls13);
 
                 if scv.is_some() {
                     proxy.push_str(&format!(
@@ -276,16 +310,19 @@ pub async fn proxy_to_loon(
                 // Add peer info
                 proxy.push_str(&format!(", peers=[{{{}}}]", generate_peer(node, true)));
             }
-            _ => continue,
+            _ => {
+                ext.warnings.unsupported_node(node, "Loon");
+                continue;
+            }
         }
 
         // Add fast-open option if enabled
-        if ext.tfo.unwrap_or(false) {
+        if tfo.unwrap_or(false) {
             proxy.push_str(",fast-open=true");
         }
 
         // Add UDP option if enabled
-        if ext.udp.unwrap_or(false) {
+        if udp.unwrap_or(false) {
             proxy.push_str(",udp=true");
         }
 
@@ -293,7 +330,7 @@ pub async fn proxy_to_loon(
         if ext.nodelist {
             output_nodelist.push_str(&format!("{} = {}\n", remark, proxy));
         } else {
-            ini.set("{NONAME}", &format!("{} = {}", remark, proxy), "")
+            ini.set("{NONAME}", "{NONAME}", &format!("{} = {}", remark, proxy))
                 .unwrap_or(());
             nodelist.push(node.clone());
             remarks_list.push(remark);
@@ -309,7 +346,9 @@ pub async fn proxy_to_loon(
     ini.set_current_section("Proxy Group");
     ini.erase_section();
 
+    let group_names = group_names(extra_proxy_group);
     for group in extra_proxy_group {
+        let group_names = other_group_names(&group_names, &group.name);
         let mut filtered_nodelist = Vec::new();
         let mut group_str;
 
@@ -326,8 +365,12 @@ pub async fn proxy_to_loon(
                 }
                 group_str = format!("{},default={},", group.type_str(), group.proxies[0]);
                 group_str.push_str(&join(&group.proxies[1..], ","));
-                ini.set("{NONAME}", &format!("{} = {}", group.name, group_str), "")
-                    .unwrap_or(());
+                ini.set(
+                    "{NONAME}",
+                    "{NONAME}",
+                    &format!("{} = {}", group.name, group_str),
+                )
+                .unwrap_or(());
                 continue;
             }
             _ => continue,
@@ -335,11 +378,19 @@ pub async fn proxy_to_loon(
 
         // Generate filtered proxy list
         for proxy_name in &group.proxies {
-            group_generate(proxy_name, &nodelist, &mut filtered_nodelist, true, ext);
+            group_generate(
+                proxy_name,
+                &nodelist,
+                &group_names,
+                &mut filtered_nodelist,
+                true,
+                ext,
+            );
         }
+        apply_group_filters(group, &nodelist, &mut filtered_nodelist);
 
         if filtered_nodelist.is_empty() {
-            filtered_nodelist.push("DIRECT".to_string());
+            filtered_nodelist.push(ext.empty_group_placeholder.policy().to_string());
         }
 
         // Update original_groups handling to extract image URLs
@@ -392,8 +443,12 @@ pub async fn proxy_to_loon(
             group_str.push_str(&format!(",{}", img_url));
         }
 
-        ini.set("{NONAME}", &format!("{} = {}", group.name, group_str), "")
-            .unwrap_or(());
+        ini.set(
+            "{NONAME}",
+            "{NONAME}",
+            &format!("{} = {}", group.name, group_str),
+        )
+        .unwrap_or(());
     }
 
     // Generate rules if enabled
@@ -403,6 +458,8 @@ pub async fn proxy_to_loon(
             ruleset_content_array,
             ext.overwrite_original_rules,
             &ext.managed_config_prefix,
+            ext.max_allowed_rules,
+            ext.expand_rulesets,
         )
         .await;
     }
@@ -425,7 +482,11 @@ fn generate_peer(node: &Proxy, client_id_as_reserved: bool) -> String {
         peer.push_str(&format!("public-key={}", public_key));
     }
 
-    peer.push_str(&format!(", endpoint={}:{}", node.hostname, node.port));
+    peer.push_str(&format!(
+        ", endpoint={}:{}",
+        bracket_host(&node.hostname),
+        node.port
+    ));
 
     if !node.allowed_ips.is_empty() {
         peer.push_str(&format!(", allowed-ips={}", node.allowed_ips));
@@ -444,18 +505,338 @@ fn generate_peer(node: &Proxy, client_id_as_reserved: bool) -> String {
     peer
 }
 
+/// Write the rules of the rulesets, the remote ones go to `[Remote Rule]`
+/// unless `expand_rulesets` is set
+///
+/// Rulesets that cannot be referenced, see [`loon_remote_rule`], are written
+/// as rules.
 async fn ruleset_to_loon(
     ini: &mut IniReader,
-    ruleset_content_array: &mut Vec<RulesetContent>,
+    ruleset_content_array: &[RulesetContent],
     overwrite_original_rules: bool,
     managed_config_prefix: &str,
+    max_allowed_rules: usize,
+    expand_rulesets: bool,
 ) {
+    let mut remote_rules = Vec::new();
+    let mut used_tags = HashSet::new();
+    let mut expanded = Vec::new();
+    for ruleset in ruleset_content_array {
+        let remote = if expand_rulesets || ruleset.rule_path.is_empty() {
+            None
+        } else if is_link(&ruleset.rule_path) || file_exists(&ruleset.rule_path).await {
+            loon_remote_rule(ruleset, managed_config_prefix, &mut used_tags)
+        } else {
+            None
+        };
+        match remote {
+            Some(line) => remote_rules.push(line),
+            None => expanded.push(ruleset.clone()),
+        }
+    }
+
+    // Clears `[Remote Rule]` as well when overwriting
     ruleset_to_surge(
         ini,
-        ruleset_content_array,
+        &expanded,
         -4,
         overwrite_original_rules,
         managed_config_prefix,
+        max_allowed_rules,
     )
     .await;
+
+    for line in remote_rules {
+        let _ = ini.set("Remote Rule", "{NONAME}", &line);
+    }
+}
+
+/// `[Remote Rule]` line of a ruleset, `None` when it has to be expanded
+///
+/// Loon reads Surge rule lists from their URL. Other types and local files
+/// are served converted by `/getruleset`, which needs `managed_config_prefix`.
+/// Tags already in `used_tags` get a numeric suffix, as rule providers do.
+fn loon_remote_rule(
+    ruleset: &RulesetContent,
+    managed_config_prefix: &str,
+    used_tags: &mut HashSet<String>,
+) -> Option<String> {
+    let url = if is_link(&ruleset.rule_path) && ruleset.rule_type == RulesetType::Surge {
+        ruleset.rule_path.clone()
+    } else if !managed_config_prefix.is_empty() {
+        format!(
+            "{}/getruleset?type=1&url={}",
+            managed_config_prefix,
+            url_safe_base64_encode(&ruleset.rule_path_typed)
+        )
+    } else {
+        return None;
+    };
+
+    let base_name = provider_base_name(&ruleset.rule_path);
+    let mut tag = base_name.clone();
+    let mut index = 1;
+    while !used_tags.insert(tag.clone()) {
+        tag = format!("{} {}", base_name, index);
+        index += 1;
+    }
+
+    let mut line = format!("{}, policy={}, tag={}", url, ruleset.group, tag);
+    if ruleset.update_interval > 0 {
+        line.push_str(&format!(", update-interval={}", ruleset.update_interval));
+    }
+    line.push_str(", enabled=true");
+    Some(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ruleset(group: &str, path: &str, content: &str) -> RulesetContent {
+        let mut ruleset = RulesetContent::new(path, group);
+        ruleset.rule_path_typed = path.to_string();
+        ruleset.set_rule_content(content);
+        ruleset
+    }
+
+    fn loon_rules(rulesets: &[RulesetContent], prefix: &str, expand: bool) -> String {
+        let mut ini = IniReader::new();
+        ini.store_any_line = true;
+        ini.parse("[Rule]\nFINAL,DIRECT\n[Remote Rule]\n").unwrap();
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(ruleset_to_loon(&mut ini, rulesets, true, prefix, 0, expand));
+        ini.to_string()
+    }
+
+    #[test]
+    fn test_remote_and_inline_rulesets() {
+        let mut remote = ruleset(
+            "🚀 Proxy",
+            "https://example.com/rules/proxy.list",
+            "DOMAIN-SUFFIX,google.com",
+        );
+        remote.update_interval = 86400;
+        let rulesets = [
+            remote,
+            ruleset("🎯 Direct", "", "[]GEOIP,CN"),
+            ruleset(
+                "🎯 Direct",
+                "tests/fixtures/rules/direct.list",
+                "DOMAIN-SUFFIX,local",
+            ),
+        ];
+
+        let output = loon_rules(&rulesets, "", false);
+        assert!(output.contains(
+            "[Remote Rule]\nhttps://example.com/rules/proxy.list, policy=🚀 Proxy, tag=proxy, update-interval=86400, enabled=true\n"
+        ), "{}", output);
+        // Local rulesets need the prefix to be served
+        assert!(output.contains("GEOIP,CN,🎯 Direct\n"), "{}", output);
+        assert!(
+            output.contains("DOMAIN-SUFFIX,local,🎯 Direct\n"),
+            "{}",
+            output
+        );
+        assert!(!output.contains("google.com"), "{}", output);
+        assert!(!output.contains("FINAL,DIRECT"), "{}", output);
+
+        let output = loon_rules(&rulesets, "https://sub.example.com", false);
+        assert!(output.contains(&format!(
+            "https://sub.example.com/getruleset?type=1&url={}, policy=🎯 Direct, tag=direct, enabled=true",
+            url_safe_base64_encode("tests/fixtures/rules/direct.list")
+        )), "{}", output);
+        assert!(!output.contains("DOMAIN-SUFFIX,local"), "{}", output);
+
+        let output = loon_rules(&rulesets, "https://sub.example.com", true);
+        assert!(!output.contains("policy="), "{}", output);
+        assert!(
+            output.contains("DOMAIN-SUFFIX,google.com,🚀 Proxy\n"),
+            "{}",
+            output
+        );
+        assert!(
+            output.contains("DOMAIN-SUFFIX,local,🎯 Direct\n"),
+            "{}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_remote_rule_tags_are_unique() {
+        let rulesets = [
+            ruleset("🎥 Netflix", "https://a.example.com/Netflix.list", ""),
+            ruleset("🎥 Netflix", "https://b.example.com/Netflix.list", ""),
+            ruleset("🎥 Netflix", "https://c.example.com/Netflix.list", ""),
+        ];
+
+        let output = loon_rules(&rulesets, "", false);
+        let tags: Vec<&str> = output
+            .lines()
+            .filter_map(|line| line.split(", tag=").nth(1))
+            .filter_map(|rest| rest.split(',').next())
+            .collect();
+        assert_eq!(tags, ["Netflix", "Netflix 1", "Netflix 2"], "{}", output);
+    }
+
+    #[test]
+    fn test_converted_remote_rulesets() {
+        let mut clash = ruleset(
+            "🚀 Proxy",
+            "https://example.com/rules/proxy.yaml",
+            "payload:\n  - '+.google.com'\n",
+        );
+        clash.rule_path_typed = "clash-domain:https://example.com/rules/proxy.yaml".to_string();
+        clash.rule_type = RulesetType::ClashDomain;
+        let rulesets = [clash];
+
+        let output = loon_rules(&rulesets, "https://sub.example.com", false);
+        assert!(output.contains(&format!(
+            "https://sub.example.com/getruleset?type=1&url={}, policy=🚀 Proxy, tag=proxy, enabled=true",
+            url_safe_base64_encode("clash-domain:https://example.com/rules/proxy.yaml")
+        )), "{}", output);
+
+        // Without a prefix Loon could not read the list, so it is expanded
+        let output = loon_rules(&rulesets, "", false);
+        assert!(!output.contains("policy="), "{}", output);
+        assert!(
+            output.contains("DOMAIN-SUFFIX,google.com,🚀 Proxy"),
+            "{}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_ssr_params() {
+        let ssr = |remark: &str, obfs_param: &str, proto_param: &str| {
+            Proxy::ssr_construct(
+                "Provider",
+                remark,
+                "1.2.3.4",
+                8388,
+                "auth_aes128_md5",
+                "aes-256-cfb",
+                "tls1.2_ticket_auth",
+                "password",
+                obfs_param,
+                proto_param,
+                None,
+                None,
+                None,
+                "",
+            )
+        };
+        let mut nodes = vec![
+            ssr("SSR", "cdn.example.com", "1234:abcd"),
+            ssr("SSR Plain", "", ""),
+        ];
+        let mut ext = ExtraSettings {
+            enable_rule_generator: false,
+            nodelist: true,
+            ..Default::default()
+        };
+        let output = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(proxy_to_loon(
+                &mut nodes,
+                "",
+                &mut Vec::new(),
+                &Vec::new(),
+                &mut ext,
+            ));
+
+        assert_eq!(
+            output,
+            [
+                "SSR = ShadowsocksR,1.2.3.4,8388,aes-256-cfb,\"password\",protocol=auth_aes128_md5,protocol-param=1234:abcd,obfs=tls1.2_ticket_auth,obfs-param=cdn.example.com\n",
+                "SSR Plain = ShadowsocksR,1.2.3.4,8388,aes-256-cfb,\"password\",protocol=auth_aes128_md5,obfs=tls1.2_ticket_auth\n",
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn test_tls13() {
+        let trojan = Proxy {
+            proxy_type: ProxyType::Trojan,
+            remark: "Trojan".to_string(),
+            hostname: "1.2.3.4".to_string(),
+            port: 443,
+            password: Some("secret".to_string()),
+            tls_secure: true,
+            ..Default::default()
+        };
+        let vmess = Proxy {
+            proxy_type: ProxyType::VMess,
+            remark: "VMess".to_string(),
+            hostname: "1.2.3.4".to_string(),
+            port: 443,
+            encrypt_method: Some("auto".to_string()),
+            user_id: Some("b831381d-6324-4d53-ad4f-8cda48b30811".to_string()),
+            transfer_protocol: Some("tcp".to_string()),
+            host: Some("example.com".to_string()),
+            tls_secure: true,
+            ..Default::default()
+        };
+        let ss = |remark: &str, obfs: &str| Proxy {
+            proxy_type: ProxyType::Shadowsocks,
+            remark: remark.to_string(),
+            hostname: "1.2.3.4".to_string(),
+            port: 8388,
+            encrypt_method: Some("aes-256-gcm".to_string()),
+            password: Some("secret".to_string()),
+            plugin: Some("obfs-local".to_string()),
+            plugin_option: Some(format!("obfs={};obfs-host=example.com", obfs)),
+            ..Default::default()
+        };
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let output_with = |tls13: Option<bool>| {
+            let mut ext = ExtraSettings {
+                enable_rule_generator: false,
+                nodelist: true,
+                tls13,
+                ..Default::default()
+            };
+            runtime.block_on(proxy_to_loon(
+                &mut vec![
+                    trojan.clone(),
+                    vmess.clone(),
+                    ss("SS TLS", "tls"),
+                    ss("SS HTTP", "http"),
+                ],
+                "",
+                &mut Vec::new(),
+                &Vec::new(),
+                &mut ext,
+            ))
+        };
+        let lines = |tls13: &str| {
+            [
+                format!("Trojan = trojan,1.2.3.4,443,\"secret\"{}\n", tls13),
+                format!(
+                    "VMess = vmess,1.2.3.4,443,chacha20-ietf-poly1305,\"b831381d-6324-4d53-ad4f-8cda48b30811\",over-tls=true,tls-name=example.com{},transport=tcp\n",
+                    tls13
+                ),
+                format!(
+                    "SS TLS = Shadowsocks,1.2.3.4,8388,aes-256-gcm,\"secret\",tls,example.com{}\n",
+                    tls13
+                ),
+                "SS HTTP = Shadowsocks,1.2.3.4,8388,aes-256-gcm,\"secret\",http,example.com\n"
+                    .to_string(),
+            ]
+            .concat()
+        };
+
+        assert_eq!(output_with(Some(true)), lines(",tls13=true"));
+        assert_eq!(output_with(Some(false)), lines(",tls13=false"));
+        assert_eq!(output_with(None), lines(""));
+    }
 }
@@ -1,4 +1,6 @@
-use crate::generator::config::group::group_generate;
+use crate::generator::config::group::{
+    apply_group_filters, group_generate, group_names, other_group_names,
+};
 use crate::generator::config::remark::process_remark;
 use crate::generator::ruleconvert::ruleset_to_surge::ruleset_to_surge;
 use crate::models::{
@@ -85,13 +87,12 @@ async fn proxy_to_mellow_internal(
     for node in nodes {
         // Add proxy type prefix if enabled
         if ext.append_proxy_type {
-            let proxy_type = node.proxy_type.to_string();
-            node.remark = format!("[{}] {}", proxy_type, node.remark);
+            node.remark = format!("[{}] {}", node.proxy_type, node.remark);
         }
 
         // Process remark
         let mut remark = node.remark.clone();
-        process_remark(&mut remark, &remarks_list, true);
+        process_remark(&mut remark, &remarks_list, true, ext.max_remark_length);
         node.remark = remark;
 
         // Extract node properties for easier access
@@ -128,7 +129,7 @@ async fn proxy_to_mellow_internal(
                 }
 
                 _proxy_str = format!(
-                    "{}, ss, ss://{}/{}:{}",
+                    "{}, ss, ss://{}@{}:{}",
                     node.remark,
                     url_safe_base64_encode(&format!("{}:{}", method, password)),
                     hostname,
@@ -218,11 +219,41 @@ async fn proxy_to_mellow_internal(
                     node.remark, hostname, port, username, password
                 );
             }
-            _ => continue,
+            ProxyType::Trojan => {
+                // Mellow only supports plain TLS trojan without transports
+                if !transproto.is_empty() && transproto != "tcp" {
+                    continue;
+                }
+
+                _proxy_str = format!(
+                    "{}, builtin, trojan, address={}, port={}, password={}",
+                    node.remark, hostname, port, password
+                );
+
+                let sni = node.sni.as_deref().unwrap_or(host);
+                if !sni.is_empty() {
+                    _proxy_str.push_str(&format!(", tls.servername={}", sni));
+                }
+
+                if !scv.is_undef() {
+                    _proxy_str.push_str(&format!(
+                        ", tls.allowinsecure={}",
+                        if scv.unwrap_or(false) {
+                            "true"
+                        } else {
+                            "false"
+                        }
+                    ));
+                }
+            }
+            _ => {
+                ext.warnings.unsupported_node(node, "Mellow");
+                continue;
+            }
         }
 
         // Add to INI
-        ini.set("{NONAME}", &_proxy_str, "").unwrap_or(());
+        ini.set_current("{NONAME}", &_proxy_str).unwrap_or(());
         remarks_list.push(node.remark.clone());
         nodelist.push(node.clone());
     }
@@ -230,7 +261,9 @@ async fn proxy_to_mellow_internal(
     // Process endpoint groups
     ini.set_current_section("EndpointGroup");
 
+    let group_names = group_names(extra_proxy_group);
     for group in extra_proxy_group {
+        let group_names = other_group_names(&group_names, &group.name);
         // Only process certain group types
         match group.group_type {
             ProxyGroupType::Select
@@ -242,27 +275,37 @@ async fn proxy_to_mellow_internal(
 
                 // Process each proxy in the group
                 for proxy_name in &group.proxies {
-                    group_generate(proxy_name, &nodelist, &mut filtered_nodelist, false, ext);
+                    group_generate(
+                        proxy_name,
+                        &nodelist,
+                        &group_names,
+                        &mut filtered_nodelist,
+                        false,
+                        ext,
+                    );
                 }
+                apply_group_filters(group, &nodelist, &mut filtered_nodelist);
 
-                // Use default if filtered list is empty
+                // Use DIRECT if filtered list is empty
                 if filtered_nodelist.is_empty() {
-                    if remarks_list.is_empty() {
-                        filtered_nodelist.push("DIRECT".to_string());
-                    } else {
-                        filtered_nodelist = remarks_list.clone();
-                    }
+                    filtered_nodelist.push(ext.empty_group_placeholder.policy().to_string());
                 }
 
                 // Create group string with joined node list
                 let proxy_str = format!(
-                    "{}, {}, latency, interval=300, timeout=6",
+                    "{}, {}, latency, interval={}, timeout={}",
                     group.name,
-                    join(&filtered_nodelist, ":")
+                    join(&filtered_nodelist, ":"),
+                    if group.interval > 0 {
+                        group.interval
+                    } else {
+                        300
+                    },
+                    if group.timeout > 0 { group.timeout } else { 6 }
                 );
 
                 // Add to INI
-                ini.set("{NONAME}", &proxy_str, "").unwrap_or(());
+                ini.set_current("{NONAME}", &proxy_str).unwrap_or(());
             }
             _ => continue,
         }
@@ -276,7 +319,67 @@ async fn proxy_to_mellow_internal(
             0,
             ext.overwrite_original_rules,
             "",
+            ext.max_allowed_rules,
         )
         .await;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ProxyGroupConfig;
+
+    #[test]
+    fn test_endpoints_and_groups() {
+        let mut nodes = vec![
+            Proxy {
+                proxy_type: ProxyType::Trojan,
+                remark: "HK Trojan".to_string(),
+                hostname: "hk.example.com".to_string(),
+                port: 443,
+                password: Some("secret".to_string()),
+                sni: Some("sni.example.com".to_string()),
+                tls_secure: true,
+                ..Default::default()
+            },
+            Proxy {
+                proxy_type: ProxyType::Socks5,
+                remark: "US Socks".to_string(),
+                hostname: "us.example.com".to_string(),
+                port: 1080,
+                username: Some("user".to_string()),
+                password: Some("pass".to_string()),
+                ..Default::default()
+            },
+        ];
+
+        let mut auto = ProxyGroupConfig::new("Auto".to_string(), ProxyGroupType::URLTest);
+        auto.proxies = vec![".*".to_string()];
+        auto.interval = 600;
+        auto.timeout = 5;
+        let mut empty = ProxyGroupConfig::new("Empty".to_string(), ProxyGroupType::Select);
+        empty.proxies = vec!["^JP".to_string()];
+
+        let mut ext = ExtraSettings::default();
+        ext.enable_rule_generator = false;
+        let output = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(proxy_to_mellow(
+                &mut nodes,
+                "[Endpoint]\n\n[EndpointGroup]\n",
+                &mut Vec::new(),
+                &vec![auto, empty],
+                &mut ext,
+            ));
+
+        assert!(output.contains(
+            "[Endpoint]\nHK Trojan, builtin, trojan, address=hk.example.com, port=443, password=secret, tls.servername=sni.example.com\nUS Socks, builtin, socks, address=us.example.com, port=1080, user=user, pass=pass\n"
+        ));
+        assert!(output.contains(
+            "[EndpointGroup]\nAuto, HK Trojan:US Socks, latency, interval=600, timeout=5\nEmpty, DIRECT, latency, interval=300, timeout=6\n"
+        ));
+    }
+}
@@ -1,4 +1,6 @@
-use crate::generator::config::group::group_generate;
+use crate::generator::config::group::{
+    apply_group_filters, group_generate, group_names, other_group_names,
+};
 use crate::generator::config::remark::process_remark;
 use crate::generator::ruleconvert::ruleset_to_surge::ruleset_to_surge;
 use crate::models::{
@@ -6,6 +8,7 @@ use crate::models::{
 };
 use crate::utils::base64::{base64_encode, url_safe_base64_encode};
 use crate::utils::ini_reader::IniReader;
+use crate::utils::plugin_options::parse_plugin_options;
 use crate::utils::regexp::reg_get_match;
 use crate::utils::string::{join, replace_all_distinct, trim_of};
 use crate::utils::tribool::BoolTriboolExt;
@@ -25,7 +28,7 @@ use log::error;
 /// * `ext` - Extra settings for conversion
 ///
 /// # Returns
-/// * Quantumult configuration as a string
+/// * Base64 encoded Quantumult configuration, or base64 encoded node links in nodelist mode
 pub async fn proxy_to_quan(
     nodes: &mut Vec<Proxy>,
     base_conf: &str,
@@ -58,7 +61,7 @@ pub async fn proxy_to_quan(
     // Return result based on mode (nodelist or full config)
     if ext.nodelist {
         let mut all_nodes = Vec::new();
-        if let Ok(nodes) = ini.get_all_current("") {
+        if let Ok(nodes) = ini.get_all_current("{NONAME}") {
             all_nodes = nodes;
         }
 
@@ -70,7 +73,7 @@ pub async fn proxy_to_quan(
         return String::new();
     }
 
-    ini.to_string()
+    base64_encode(&ini.to_string())
 }
 
 /// Internal function for converting proxies to Quantumult format
@@ -101,13 +104,12 @@ async fn proxy_to_quan_internal(
     for node in nodes {
         // Add proxy type prefix if enabled
         if ext.append_proxy_type {
-            let proxy_type = node.proxy_type.to_string();
-            node.remark = format!("[{}] {}", proxy_type, node.remark);
+            node.remark = format!("[{}] {}", node.proxy_type, node.remark);
         }
 
         // Process remark
         let mut remark = node.remark.clone();
-        process_remark(&mut remark, &remarks_list, false);
+        process_remark(&mut remark, &remarks_list, false, ext.max_remark_length);
         node.remark = remark;
 
         // Extract node properties for easier access
@@ -139,10 +141,15 @@ async fn proxy_to_quan_internal(
         // Format proxy string based on proxy type
         match node.proxy_type {
             ProxyType::VMess => {
-                let mut actual_method = method;
-                if method == "auto" {
-                    actual_method = "chacha20-ietf-poly1305";
-                }
+                let actual_method = match method {
+                    "" | "auto" => "chacha20-ietf-poly1305",
+                    _ => method,
+                };
+                let host = if host.is_empty() {
+                    hostname.as_str()
+                } else {
+                    host
+                };
 
                 proxy_str = format!(
                     "{} = vmess, {}, {}, {}, \"{}\", group={}",
@@ -234,11 +241,10 @@ async fn proxy_to_quan_internal(
                         node.remark, hostname, port, method, password, group
                     );
 
-                    if plugin == "obfs-local" && !pluginopts.is_empty() {
-                        proxy_str.push_str(&format!(
-                            ", {}",
-                            replace_all_distinct(pluginopts, ";", ", ")
-                        ));
+                    if plugin == "obfs-local" {
+                        for (key, value) in parse_plugin_options(pluginopts) {
+                            proxy_str.push_str(&format!(", {}={}", key, value));
+                        }
                     }
                 }
             }
@@ -310,11 +316,14 @@ async fn proxy_to_quan_internal(
                     proxy_str = format!("socks://{}", url_safe_base64_encode(&proxy_str));
                 }
             }
-            _ => continue,
+            _ => {
+                ext.warnings.unsupported_node(node, "Quantumult");
+                continue;
+            }
         }
 
         // Add to INI
-        ini.set("{NONAME}", &proxy_str, "").unwrap_or(());
+        ini.set_current("{NONAME}", &proxy_str).unwrap_or(());
         remarks_list.push(node.remark.clone());
         nodelist.push(node.clone());
     }
@@ -328,7 +337,9 @@ async fn proxy_to_quan_internal(
     ini.set_current_section("POLICY");
     ini.erase_section();
 
+    let group_names = group_names(extra_proxy_group);
     for group in extra_proxy_group {
+        let group_names = other_group_names(&group_names, &group.name);
         let mut filtered_nodelist = Vec::new();
         let mut single_group;
 
@@ -337,11 +348,19 @@ async fn proxy_to_quan_internal(
             ProxyGroupType::Select | ProxyGroupType::Fallback => {
                 // Process as static type
                 for proxy_name in &group.proxies {
-                    group_generate(proxy_name, &nodelist, &mut filtered_nodelist, true, ext);
+                    group_generate(
+                        proxy_name,
+                        &nodelist,
+                        &group_names,
+                        &mut filtered_nodelist,
+                        true,
+                        ext,
+                    );
                 }
+                apply_group_filters(group, &nodelist, &mut filtered_nodelist);
 
                 if filtered_nodelist.is_empty() {
-                    filtered_nodelist.push("direct".to_string());
+                    filtered_nodelist.push(ext.empty_group_placeholder.policy().to_lowercase());
                 }
 
                 let proxies = join(&filtered_nodelist, "\n");
@@ -352,11 +371,19 @@ async fn proxy_to_quan_internal(
             ProxyGroupType::URLTest => {
                 // Process as auto type
                 for proxy_name in &group.proxies {
-                    group_generate(proxy_name, &nodelist, &mut filtered_nodelist, true, ext);
+                    group_generate(
+                        proxy_name,
+                        &nodelist,
+                        &group_names,
+                        &mut filtered_nodelist,
+                        true,
+                        ext,
+                    );
                 }
+                apply_group_filters(group, &nodelist, &mut filtered_nodelist);
 
                 if filtered_nodelist.is_empty() {
-                    filtered_nodelist.push("direct".to_string());
+                    filtered_nodelist.push(ext.empty_group_placeholder.policy().to_lowercase());
                 }
 
                 let proxies = join(&filtered_nodelist, "\n");
@@ -373,11 +400,19 @@ async fn proxy_to_quan_internal(
             ProxyGroupType::LoadBalance => {
                 // Process as balance type
                 for proxy_name in &group.proxies {
-                    group_generate(proxy_name, &nodelist, &mut filtered_nodelist, true, ext);
+                    group_generate(
+                        proxy_name,
+                        &nodelist,
+                        &group_names,
+                        &mut filtered_nodelist,
+                        true,
+                        ext,
+                    );
                 }
+                apply_group_filters(group, &nodelist, &mut filtered_nodelist);
 
                 if filtered_nodelist.is_empty() {
-                    filtered_nodelist.push("direct".to_string());
+                    filtered_nodelist.push(ext.empty_group_placeholder.policy().to_lowercase());
                 }
 
                 let proxies = join(&filtered_nodelist, "\n");
@@ -440,7 +475,7 @@ async fn proxy_to_quan_internal(
 
         // Add group to INI if not empty
         if !single_group.is_empty() {
-            ini.set("{NONAME}", &base64_encode(&single_group), "")
+            ini.set_current("{NONAME}", &base64_encode(&single_group))
                 .unwrap_or(());
         }
     }
@@ -453,7 +488,88 @@ async fn proxy_to_quan_internal(
             -2,
             ext.overwrite_original_rules,
             "",
+            ext.max_allowed_rules,
         )
         .await;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::base64::base64_decode;
+
+    fn convert(nodes: &mut Vec<Proxy>) -> String {
+        let mut ext = ExtraSettings::default();
+        ext.enable_rule_generator = false;
+        let output = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(proxy_to_quan(
+                nodes,
+                "[SERVER]\n\n[POLICY]\n",
+                &mut Vec::new(),
+                &Vec::new(),
+                &mut ext,
+            ));
+        base64_decode(&output, false)
+    }
+
+    #[test]
+    fn test_vmess_lines() {
+        let mut nodes = vec![
+            Proxy::vmess_construct(
+                "Group",
+                "HK",
+                "example.com",
+                443,
+                "none",
+                "b831381d-6324-4d53-ad4f-8cda48b30811",
+                0,
+                "ws",
+                "auto",
+                "/ws",
+                "cdn.example.com",
+                "",
+                "tls",
+                "",
+                None,
+                None,
+                None,
+                None,
+                "",
+            ),
+            Proxy::vmess_construct(
+                "Group",
+                "JP",
+                "1.2.3.4",
+                10086,
+                "none",
+                "b831381d-6324-4d53-ad4f-8cda48b30811",
+                0,
+                "tcp",
+                "aes-128-gcm",
+                "",
+                "",
+                "",
+                "",
+                "",
+                None,
+                None,
+                None,
+                None,
+                "",
+            ),
+        ];
+        let content = convert(&mut nodes);
+        let lines: Vec<&str> = content.lines().collect();
+
+        assert!(lines.contains(
+            &"HK = vmess, example.com, 443, chacha20-ietf-poly1305, \"b831381d-6324-4d53-ad4f-8cda48b30811\", group=Group, over-tls=true, tls-host=cdn.example.com, obfs=ws, obfs-path=\"/ws\", obfs-header=\"Host: cdn.example.com\""
+        ));
+        assert!(lines.contains(
+            &"JP = vmess, 1.2.3.4, 10086, aes-128-gcm, \"b831381d-6324-4d53-ad4f-8cda48b30811\", group=Group"
+        ));
+    }
+}
@@ -1,13 +1,18 @@
-use crate::generator::config::group::group_generate;
+use crate::generator::config::group::{
+    apply_group_filters, group_generate, group_names, other_group_names,
+};
 use crate::generator::config::remark::process_remark;
 use crate::generator::ruleconvert::ruleset_to_surge::ruleset_to_surge;
 use crate::models::{
-    ExtraSettings, Proxy, ProxyGroupConfigs, ProxyGroupType, ProxyType, RulesetContent,
+    ExtraSettings, Proxy, ProxyGroupConfigs, ProxyGroupType, ProxyType, QuanxServerRemote,
+    RulesetContent, SubconverterTarget,
 };
 use crate::utils::ini_reader::IniReader;
+use crate::utils::network::format_host_for;
+use crate::utils::plugin_options::{parse_plugin_options, plugin_option_value};
 use crate::utils::string::{hash, join, replace_all_distinct, trim};
 use crate::utils::tribool::BoolTriboolExt;
-use crate::utils::url::get_url_arg;
+use crate::utils::url::url_encode;
 use log::error;
 
 /// Convert proxies to QuantumultX format (main entry point)
@@ -107,17 +112,16 @@ async fn proxy_to_quanx_internal(
     for node in nodes {
         // Add proxy type prefix if enabled
         if ext.append_proxy_type {
-            let proxy_type = node.proxy_type.to_string();
-            node.remark = format!("[{}] {}", proxy_type, node.remark);
+            node.remark = format!("[{}] {}", node.proxy_type, node.remark);
         }
 
         // Process remark
         let mut remark = node.remark.clone();
-        process_remark(&mut remark, &remarks_list, false);
+        process_remark(&mut remark, &remarks_list, false, ext.max_remark_length);
         node.remark = remark;
 
         // Extract node properties for easier access
-        let hostname = &node.hostname;
+        let hostname = format_host_for(&SubconverterTarget::QuantumultX, &node.hostname);
         let port = node.port.to_string();
         let username = node.username.as_deref().unwrap_or("");
         let password = node.password.as_deref().unwrap_or("");
@@ -200,20 +204,16 @@ async fn proxy_to_quanx_internal(
                     let plugin_hash = hash(plugin);
 
                     if plugin_hash == hash("simple-obfs") || plugin_hash == hash("obfs-local") {
-                        if !pluginopts.is_empty() {
-                            _proxy_str.push_str(&format!(
-                                ", {}",
-                                replace_all_distinct(pluginopts, ";", ", ")
-                            ));
+                        for (key, value) in parse_plugin_options(pluginopts) {
+                            _proxy_str.push_str(&format!(", {}={}", key, value));
                         }
                     } else if plugin_hash == hash("v2ray-plugin") {
-                        let opts = replace_all_distinct(pluginopts, ";", "&");
-                        let mode = get_url_arg(&opts, "mode");
+                        let mode = plugin_option_value(pluginopts, "mode").unwrap_or("");
                         let mut plugin_type =
                             (if mode == "websocket" { "ws" } else { "" }).to_string();
-                        let plugin_host = get_url_arg(&opts, "host");
-                        let plugin_path = get_url_arg(&opts, "path");
-                        let is_tls = opts.contains("tls");
+                        let plugin_host = plugin_option_value(pluginopts, "host").unwrap_or("");
+                        let plugin_path = plugin_option_value(pluginopts, "path").unwrap_or("");
+                        let is_tls = plugin_option_value(pluginopts, "tls").is_some();
 
                         if is_tls && plugin_type == "ws" {
                             plugin_type.push('s');
@@ -334,7 +334,10 @@ async fn proxy_to_quanx_internal(
                     }
                 }
             }
-            _ => continue,
+            _ => {
+                ext.warnings.unsupported_node(node, "Quantumult X");
+                continue;
+            }
         }
 
         // Add common options
@@ -380,7 +383,7 @@ async fn proxy_to_quanx_internal(
         _proxy_str.push_str(&format!(", tag={}", node.remark));
 
         // Add to INI
-        ini.set("{NONAME}", &_proxy_str, "").unwrap_or(());
+        ini.set("{NONAME}", "{NONAME}", &_proxy_str).unwrap_or(());
         remarks_list.push(node.remark.clone());
         nodelist.push(node.clone());
     }
@@ -390,6 +393,17 @@ async fn proxy_to_quanx_internal(
         return;
     }
 
+    // Policies keep referring to the node tags, QuantumultX resolves them from the resource
+    if let Some(remote) = &ext.quanx_server_remote {
+        ini.erase_section();
+        ini.set(
+            "server_remote",
+            "{NONAME}",
+            &quanx_server_remote_line(remote, &ext.quanx_dev_id),
+        )
+        .unwrap_or(());
+    }
+
     // Process policy section
     ini.set_current_section("policy");
     let mut original_groups = Vec::new();
@@ -399,7 +413,9 @@ async fn proxy_to_quanx_internal(
     ini.erase_section();
 
     // Process proxy groups
+    let group_names = group_names(extra_proxy_group);
     for group in extra_proxy_group {
+        let group_names = other_group_names(&group_names, &group.name);
         let mut _type_str = String::new();
         let mut filtered_nodelist = Vec::new();
 
@@ -431,11 +447,19 @@ async fn proxy_to_quanx_internal(
         // Generate node list for non-SSID groups
         if group.group_type != ProxyGroupType::SSID {
             for proxy_name in &group.proxies {
-                group_generate(proxy_name, &nodelist, &mut filtered_nodelist, true, ext);
+                group_generate(
+                    proxy_name,
+                    &nodelist,
+                    &group_names,
+                    &mut filtered_nodelist,
+                    true,
+                    ext,
+                );
             }
+            apply_group_filters(group, &nodelist, &mut filtered_nodelist);
 
             if filtered_nodelist.is_empty() {
-                filtered_nodelist.push("direct".to_string());
+                filtered_nodelist.push(ext.empty_group_placeholder.policy().to_lowercase());
             }
 
             // Force groups with 1 node to be static
@@ -477,7 +501,7 @@ async fn proxy_to_quanx_internal(
         }
 
         // Add to INI
-        ini.set("{NONAME}", &single_group, "").unwrap_or(());
+        ini.set("{NONAME}", "{NONAME}", &single_group).unwrap_or(());
     }
 
     // Generate rules if enabled
@@ -488,7 +512,326 @@ async fn proxy_to_quanx_internal(
             -1,
             ext.overwrite_original_rules,
             &ext.managed_config_prefix,
+            ext.max_allowed_rules,
         )
         .await;
     }
+
+    if !ext.quanx_dev_id.is_empty() {
+        apply_quanx_dev_id(ini, &ext.quanx_dev_id, &ext.managed_config_prefix);
+    }
+}
+
+/// Pass the device ID on to QuantumultX
+///
+/// The ID is written to `[general]` and appended to the ruleset references
+/// served by this instance, so remote resources are generated for the same device.
+fn apply_quanx_dev_id(ini: &mut IniReader, dev_id: &str, remote_path_prefix: &str) {
+    let mut general = ini.get_items("general").unwrap_or_default();
+    general.retain(|(key, line)| {
+        let key = if key == "{NONAME}" {
+            line.split('=').next().unwrap_or("")
+        } else {
+            key
+        };
+        trim(key) != "dev_id"
+    });
+    ini.erase_section_by_name("general");
+    ini.set("general", "dev_id", dev_id).unwrap_or(());
+    for (key, value) in general {
+        ini.set("general", &key, &value).unwrap_or(());
+    }
+
+    if remote_path_prefix.is_empty() {
+        return;
+    }
+    let generated_prefix = format!("{}/getruleset?", remote_path_prefix);
+    let filters = ini.get_items("filter_remote").unwrap_or_default();
+    if filters.is_empty() {
+        return;
+    }
+    ini.erase_section_by_name("filter_remote");
+    for (key, value) in filters {
+        let value = match value.split_once(',') {
+            Some((url, rest)) if url.starts_with(&generated_prefix) => {
+                format!("{}&dev_id={},{}", url, url_encode(dev_id), rest)
+            }
+            _ => value,
+        };
+        ini.set("filter_remote", &key, &value).unwrap_or(());
+    }
+}
+
+/// The `server_remote` line of a node list resource
+///
+/// The device ID is passed on to the node list URL so the referenced list is
+/// generated for the same device.
+fn quanx_server_remote_line(remote: &QuanxServerRemote, dev_id: &str) -> String {
+    let mut line = remote.url.clone();
+    if !dev_id.is_empty() {
+        line.push_str(if line.contains('?') { "&" } else { "?" });
+        line.push_str(&format!("dev_id={}", url_encode(dev_id)));
+    }
+    line.push_str(&format!(", tag={}", remote.tag));
+    if let Some(as_policy) = remote.as_policy.as_deref().filter(|p| !p.is_empty()) {
+        line.push_str(&format!(", as-policy={}", as_policy));
+    }
+    line.push_str(", enabled=true");
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ipv6_server() {
+        let mut nodes = vec![Proxy::ss_construct(
+            "Provider",
+            "SS v6",
+            "2001:db8::1",
+            8388,
+            "password",
+            "aes-128-gcm",
+            "",
+            "",
+            None,
+            None,
+            None,
+            None,
+            "",
+        )];
+        let mut ext = ExtraSettings::default();
+        ext.enable_rule_generator = false;
+        let output = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(proxy_to_quanx(
+                &mut nodes,
+                "[server_local]\n\n[policy]\n",
+                &mut Vec::new(),
+                &Vec::new(),
+                &mut ext,
+            ));
+
+        assert!(output
+            .contains("shadowsocks = [2001:db8::1]:8388, method=aes-128-gcm, password=password"));
+    }
+
+    fn golden_nodes() -> Vec<Proxy> {
+        vec![
+            Proxy::ss_construct(
+                "Provider",
+                "SS",
+                "1.2.3.4",
+                8388,
+                "password",
+                "aes-128-gcm",
+                "",
+                "",
+                None,
+                None,
+                None,
+                None,
+                "",
+            ),
+            Proxy::vmess_construct(
+                "Provider",
+                "VMess",
+                "example.com",
+                443,
+                "",
+                "uuid",
+                0,
+                "ws",
+                "auto",
+                "/ws",
+                "cdn.example.com",
+                "",
+                "tls",
+                "",
+                None,
+                None,
+                Some(true),
+                None,
+                "",
+            ),
+            Proxy::trojan_construct(
+                "Provider".to_string(),
+                "Trojan".to_string(),
+                "5.6.7.8".to_string(),
+                443,
+                "pw".to_string(),
+                None,
+                Some("t.example.com".to_string()),
+                None,
+                None,
+                true,
+                Some(true),
+                None,
+                None,
+                None,
+                None,
+            ),
+        ]
+    }
+
+    fn convert(base: &str, ext: &mut ExtraSettings) -> String {
+        let groups = vec![crate::models::ProxyGroupConfig {
+            name: "Proxy".to_string(),
+            proxies: vec![".*".to_string()],
+            ..Default::default()
+        }];
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(proxy_to_quanx(
+                &mut golden_nodes(),
+                base,
+                &mut Vec::new(),
+                &groups,
+                ext,
+            ))
+    }
+
+    #[test]
+    fn test_nodelist_lines() {
+        let mut ext = ExtraSettings {
+            enable_rule_generator: false,
+            nodelist: true,
+            ..Default::default()
+        };
+        let output = convert("", &mut ext);
+
+        assert_eq!(
+            output,
+            [
+                "shadowsocks = 1.2.3.4:8388, method=aes-128-gcm, password=password, tag=SS",
+                "vmess = example.com:443, method=chacha20-ietf-poly1305, password=uuid, obfs=wss, obfs-host=cdn.example.com, obfs-uri=/ws, tls-verification=false, tag=VMess",
+                "trojan = 5.6.7.8:443, password=pw, over-tls=true, tls-host=t.example.com, udp-relay=true, tag=Trojan",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_ssr_params() {
+        let ssr = |remark: &str, obfs_param: &str, proto_param: &str| {
+            Proxy::ssr_construct(
+                "Provider",
+                remark,
+                "1.2.3.4",
+                8388,
+                "auth_aes128_md5",
+                "aes-256-cfb",
+                "tls1.2_ticket_auth",
+                "password",
+                obfs_param,
+                proto_param,
+                None,
+                None,
+                None,
+                "",
+            )
+        };
+        let mut nodes = vec![
+            ssr("SSR", "cdn.example.com", "1234:abcd"),
+            ssr("SSR Plain", "", ""),
+        ];
+        let mut ext = ExtraSettings {
+            enable_rule_generator: false,
+            nodelist: true,
+            ..Default::default()
+        };
+        let output = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(proxy_to_quanx(
+                &mut nodes,
+                "",
+                &mut Vec::new(),
+                &Vec::new(),
+                &mut ext,
+            ));
+
+        assert_eq!(
+            output,
+            [
+                "shadowsocks = 1.2.3.4:8388, method=aes-256-cfb, password=password, ssr-protocol=auth_aes128_md5, ssr-protocol-param=1234:abcd, obfs=tls1.2_ticket_auth, obfs-host=cdn.example.com, tag=SSR",
+                "shadowsocks = 1.2.3.4:8388, method=aes-256-cfb, password=password, ssr-protocol=auth_aes128_md5, obfs=tls1.2_ticket_auth, tag=SSR Plain",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_server_remote_reference() {
+        let mut ext = ExtraSettings {
+            enable_rule_generator: false,
+            quanx_dev_id: "dev 1".to_string(),
+            quanx_server_remote: Some(QuanxServerRemote {
+                url: "https://sub.example.com/sub?target=quanx&list=true&url=x".to_string(),
+                tag: "Subscription".to_string(),
+                as_policy: Some("static".to_string()),
+            }),
+            ..Default::default()
+        };
+        let output = convert("[server_local]\n\n[server_remote]\n\n[policy]\n", &mut ext);
+
+        assert!(output.contains(
+            "[server_remote]\nhttps://sub.example.com/sub?target=quanx&list=true&url=x&dev_id=dev%201, tag=Subscription, as-policy=static, enabled=true\n"
+        ));
+        assert!(!output.contains("shadowsocks = "));
+        assert!(output.contains("static=Proxy, SS, VMess, Trojan\n"));
+    }
+
+    fn convert_with_rules(ext: &mut ExtraSettings) -> String {
+        let mut rulesets = vec![RulesetContent::new(
+            "https://example.com/rules/Apple.list",
+            "Proxy",
+        )];
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(proxy_to_quanx(
+                &mut golden_nodes(),
+                "[general]\nserver_check_url=http://www.gstatic.com/generate_204\n\n[server_local]\n\n[policy]\n",
+                &mut rulesets,
+                &Vec::new(),
+                ext,
+            ))
+    }
+
+    #[test]
+    fn test_dev_id_in_output() {
+        let mut ext = ExtraSettings {
+            enable_rule_generator: true,
+            quanx_dev_id: "dev 1".to_string(),
+            managed_config_prefix: "https://sub.example.com".to_string(),
+            ..Default::default()
+        };
+        let output = convert_with_rules(&mut ext);
+
+        assert!(output.contains(
+            "[general]\ndev_id=dev 1\nserver_check_url=http://www.gstatic.com/generate_204\n"
+        ));
+        assert!(output.contains("&dev_id=dev%201, tag=Proxy, enabled=true\n"));
+    }
+
+    #[test]
+    fn test_no_dev_id_in_output() {
+        let mut ext = ExtraSettings {
+            enable_rule_generator: true,
+            managed_config_prefix: "https://sub.example.com".to_string(),
+            ..Default::default()
+        };
+        let output = convert_with_rules(&mut ext);
+
+        assert!(!output.contains("dev_id"));
+        assert!(output.contains("https://sub.example.com/getruleset?type=2&url="));
+    }
 }
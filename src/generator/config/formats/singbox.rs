@@ -1,13 +1,18 @@
-use crate::generator::config::group::group_generate;
+use crate::generator::config::group::{
+    apply_group_filters, group_generate, group_names, other_group_names,
+};
 use crate::generator::config::remark::process_remark;
 use crate::generator::ruleconvert::ruleset_to_sing_box::ruleset_to_sing_box;
+use crate::models::proxy_node::combined::CombinedProxy;
+use crate::models::proxy_node::vless::VlessProxy;
 use crate::models::{
-    ExtraSettings, Proxy, ProxyGroupConfigs, ProxyGroupType, ProxyType, RulesetContent,
+    ExtraSettings, Proxy, ProxyGroupConfigs, ProxyGroupType, ProxyType, RulesetContent, WarningKind,
 };
 use crate::utils::base64::base64_encode;
-use crate::Settings;
-use log::error;
+use crate::utils::plugin_options::parse_plugin_options;
+use log::{error, warn};
 use serde_json::{json, Map, Value as JsonValue};
+use std::collections::HashMap;
 
 /// Format SingBox interval from seconds
 ///
@@ -103,6 +108,150 @@ fn build_singbox_transport(proxy: &Proxy) -> JsonValue {
     JsonValue::Object(transport)
 }
 
+/// Build the SingBox transport of a VLESS node
+fn build_singbox_vless_transport(vless: &VlessProxy) -> JsonValue {
+    let mut transport = Map::new();
+
+    match vless.network.as_deref().unwrap_or("") {
+        "ws" => {
+            transport.insert("type".to_string(), JsonValue::String("ws".to_string()));
+            let path = vless.ws_path.clone().unwrap_or_else(|| "/".to_string());
+            transport.insert("path".to_string(), JsonValue::String(path));
+            if let Some(headers) = vless.ws_headers.as_ref().filter(|h| !h.is_empty()) {
+                transport.insert("headers".to_string(), json!(headers));
+            }
+        }
+        "http" | "h2" => {
+            transport.insert("type".to_string(), JsonValue::String("http".to_string()));
+            let hosts = vless.h2_host.clone().or_else(|| {
+                vless
+                    .http_headers
+                    .as_ref()
+                    .and_then(|headers| headers.get("Host").cloned())
+            });
+            if let Some(hosts) = hosts.filter(|hosts| !hosts.is_empty()) {
+                transport.insert("host".to_string(), json!(hosts));
+            }
+            if let Some(path) = vless.h2_path.as_ref().or(vless.http_path.as_ref()) {
+                transport.insert("path".to_string(), JsonValue::String(path.clone()));
+            }
+            if let Some(method) = &vless.http_method {
+                transport.insert("method".to_string(), JsonValue::String(method.clone()));
+            }
+        }
+        "grpc" => {
+            transport.insert("type".to_string(), JsonValue::String("grpc".to_string()));
+            if let Some(name) = vless.grpc_service_name.as_ref().filter(|n| !n.is_empty()) {
+                transport.insert("service_name".to_string(), JsonValue::String(name.clone()));
+            }
+        }
+        _ => {}
+    }
+
+    JsonValue::Object(transport)
+}
+
+/// Build the `tls` object of a node, `None` when the node does not use TLS
+///
+/// sing-box only dials REALITY through uTLS, `chrome` is used when the node
+/// sets no fingerprint.
+fn build_singbox_tls(node: &Proxy, scv: Option<bool>) -> Option<Map<String, JsonValue>> {
+    let vless = match &node.combined_proxy {
+        Some(CombinedProxy::Vless(vless)) => Some(vless),
+        _ => None,
+    };
+    let reality_public_key = node
+        .reality_public_key
+        .as_ref()
+        .or(vless.and_then(|vless| vless.reality_public_key.as_ref()))
+        .filter(|key| !key.is_empty());
+    if !node.tls_secure && !vless.is_some_and(|vless| vless.tls) && reality_public_key.is_none() {
+        return None;
+    }
+
+    let mut tls = Map::new();
+    tls.insert("enabled".to_string(), JsonValue::Bool(true));
+
+    // Set server_name from ServerName, SNI or Host
+    let server_name = [
+        node.server_name.as_deref(),
+        vless.and_then(|vless| vless.servername.as_deref()),
+        node.sni.as_deref(),
+        node.host.as_deref(),
+    ]
+    .into_iter()
+    .flatten()
+    .find(|name| !name.is_empty());
+    if let Some(server_name) = server_name {
+        tls.insert(
+            "server_name".to_string(),
+            JsonValue::String(server_name.to_string()),
+        );
+    }
+
+    // Add insecure option
+    if let Some(allow_insecure) = scv {
+        tls.insert("insecure".to_string(), JsonValue::Bool(allow_insecure));
+    }
+
+    let alpn = match vless {
+        Some(vless) if !vless.alpn.is_empty() => &vless.alpn,
+        _ => &node.alpn,
+    };
+    if !alpn.is_empty() {
+        tls.insert("alpn".to_string(), json!(alpn));
+    }
+
+    let fingerprint = node
+        .client_fingerprint
+        .as_deref()
+        .or(vless.and_then(|vless| vless.client_fingerprint.as_deref()))
+        .filter(|fingerprint| !fingerprint.is_empty())
+        .or(reality_public_key.map(|_| "chrome"));
+    if let Some(fingerprint) = fingerprint {
+        tls.insert(
+            "utls".to_string(),
+            json!({ "enabled": true, "fingerprint": fingerprint }),
+        );
+    }
+
+    if let Some(public_key) = reality_public_key {
+        let mut reality = Map::new();
+        reality.insert("enabled".to_string(), JsonValue::Bool(true));
+        reality.insert(
+            "public_key".to_string(),
+            JsonValue::String(public_key.clone()),
+        );
+        let short_id = node
+            .reality_short_id
+            .as_ref()
+            .or(vless.and_then(|vless| vless.reality_short_id.as_ref()));
+        if let Some(short_id) = short_id.filter(|id| !id.is_empty()) {
+            reality.insert("short_id".to_string(), JsonValue::String(short_id.clone()));
+        }
+        tls.insert("reality".to_string(), JsonValue::Object(reality));
+    }
+
+    // sing-box reads the ECHConfigList as PEM, without one it asks the DNS
+    if node.ech_enabled == Some(true) {
+        let mut ech = Map::new();
+        ech.insert("enabled".to_string(), JsonValue::Bool(true));
+        if let Some(config) = &node.ech_config {
+            ech.insert(
+                "config".to_string(),
+                json!([
+                    "-----BEGIN ECH CONFIGS-----",
+                    config,
+                    "-----END ECH CONFIGS-----"
+                ]),
+            );
+        }
+        tls.insert("ech".to_string(), JsonValue::Object(ech));
+    }
+
+    Some(tls)
+}
+
 /// Add common members to a SingBox proxy configuration
 ///
 /// # Arguments
@@ -132,6 +281,64 @@ fn add_singbox_common_members(
     );
 }
 
+/// Point the detours of the outbounds at the final tags of their nodes
+///
+/// Detours to no outbound are kept with a warning.
+fn follow_detours(outbounds: &mut [JsonValue], final_tags: &HashMap<String, String>) {
+    let tags: Vec<String> = outbounds
+        .iter()
+        .filter_map(|outbound| outbound["tag"].as_str().map(str::to_string))
+        .collect();
+    for outbound in outbounds.iter_mut() {
+        let Some(detour) = outbound["detour"].as_str() else {
+            continue;
+        };
+        let detour = final_tags
+            .get(detour)
+            .cloned()
+            .unwrap_or_else(|| detour.to_string());
+        if !tags.contains(&detour) {
+            warn!(
+                "Detour '{}' of outbound '{}' is not among the nodes",
+                detour,
+                outbound["tag"].as_str().unwrap_or_default()
+            );
+        }
+        outbound["detour"] = json!(detour);
+    }
+}
+
+/// Build the `shadowtls` outbound a Shadowsocks node with the shadow-tls plugin
+/// is dialed through
+///
+/// The plugin options are the `host=...;password=...;version=...` pairs kept
+/// by the parsers. The outbound is tagged `<remark>-shadowtls`.
+fn shadowtls_detour(proxy: &Proxy, plugin_opts: &str) -> JsonValue {
+    let opts: HashMap<&str, &str> = parse_plugin_options(plugin_opts).into_iter().collect();
+    let version = opts
+        .get("version")
+        .and_then(|version| version.parse::<u64>().ok())
+        .unwrap_or(1);
+
+    let mut detour = json!({
+        "type": "shadowtls",
+        "tag": format!("{}-shadowtls", proxy.remark),
+        "server": proxy.hostname,
+        "server_port": proxy.port,
+        "version": version,
+        "tls": {
+            "enabled": true,
+        },
+    });
+    if let Some(password) = opts.get("password").filter(|password| !password.is_empty()) {
+        detour["password"] = json!(password);
+    }
+    if let Some(host) = opts.get("host").filter(|host| !host.is_empty()) {
+        detour["tls"]["server_name"] = json!(host);
+    }
+    detour
+}
+
 /// Convert string array to JSON array
 ///
 /// # Arguments
@@ -209,18 +416,25 @@ pub fn proxy_to_singbox(
         }));
     }
 
+    // Final tags by remark, to follow the chains of underlying proxies
+    let mut final_tags: HashMap<String, String> = HashMap::new();
+
     // Process each proxy node
     for node in nodes.iter_mut() {
+        let original_remark = node.remark.clone();
+
         // Add proxy type prefix if enabled
         if ext.append_proxy_type {
-            let proxy_type = node.proxy_type.to_string();
-            node.remark = format!("[{}] {}", proxy_type, node.remark);
+            node.remark = format!("[{}] {}", node.proxy_type, node.remark);
         }
 
         // Process remark
         let mut remark = node.remark.clone();
-        process_remark(&mut remark, &remarks_list, false);
+        process_remark(&mut remark, &remarks_list, false, ext.max_remark_length);
         node.remark = remark;
+        final_tags
+            .entry(original_remark)
+            .or_insert_with(|| node.remark.clone());
 
         // Define tribool values with defaults from ext and override with node-specific values
         let mut udp = ext.udp;
@@ -231,6 +445,9 @@ pub fn proxy_to_singbox(
         tfo = node.tcp_fast_open.as_ref().map_or(tfo, |val| Some(*val));
         scv = node.allow_insecure.as_ref().map_or(scv, |val| Some(*val));
 
+        // Outbound the node is dialed through, e.g. shadow-tls for Shadowsocks
+        let mut detour_obj = None;
+
         // Create proxy object based on type
         let mut proxy_obj = match node.proxy_type {
             ProxyType::Shadowsocks => {
@@ -248,7 +465,20 @@ pub fn proxy_to_singbox(
 
                 // Handle plugin if present
                 if let (Some(plugin), Some(plugin_opts)) = (&node.plugin, &node.plugin_option) {
-                    if !plugin.is_empty() && !plugin_opts.is_empty() {
+                    if plugin == "shadow-tls" {
+                        let detour = shadowtls_detour(node, plugin_opts);
+                        obj.insert("detour".to_string(), detour["tag"].clone());
+                        detour_obj = Some(detour);
+                    } else if plugin == "restls" {
+                        ext.warnings.push(
+                            WarningKind::UnsupportedNode,
+                            format!(
+                                "Skipping node {}: restls is not supported by sing-box",
+                                node.remark
+                            ),
+                        );
+                        continue;
+                    } else if !plugin.is_empty() && !plugin_opts.is_empty() {
                         let plugin_name = if plugin == "simple-obfs" {
                             "obfs-local"
                         } else {
@@ -348,6 +578,49 @@ pub fn proxy_to_singbox(
 
                 obj
             }
+            ProxyType::Vless => {
+                let vless = match &node.combined_proxy {
+                    Some(CombinedProxy::Vless(vless)) => vless,
+                    _ => {
+                        ext.warnings.push(
+                            WarningKind::UnsupportedNode,
+                            format!("Skipping node {}: VLESS settings are missing", node.remark),
+                        );
+                        continue;
+                    }
+                };
+                let mut obj = Map::new();
+                add_singbox_common_members(&mut obj, node, "vless");
+                obj.insert("uuid".to_string(), JsonValue::String(vless.uuid.clone()));
+
+                if let Some(flow) = vless.flow.as_ref().filter(|flow| !flow.is_empty()) {
+                    obj.insert("flow".to_string(), JsonValue::String(flow.clone()));
+                }
+
+                // `packet` is the share link name of `packetaddr`
+                match vless.packet_encoding.as_deref() {
+                    Some("packet") => {
+                        obj.insert(
+                            "packet_encoding".to_string(),
+                            JsonValue::String("packetaddr".to_string()),
+                        );
+                    }
+                    Some(encoding) if !encoding.is_empty() && encoding != "none" => {
+                        obj.insert(
+                            "packet_encoding".to_string(),
+                            JsonValue::String(encoding.to_string()),
+                        );
+                    }
+                    _ => {}
+                }
+
+                let transport = build_singbox_vless_transport(vless);
+                if !transport.as_object().unwrap().is_empty() {
+                    obj.insert("transport".to_string(), transport);
+                }
+
+                obj
+            }
             ProxyType::WireGuard => {
                 let mut obj = Map::new();
                 obj.insert(
@@ -490,13 +763,19 @@ pub fn proxy_to_singbox(
                 let mut tls = Map::new();
                 tls.insert("enabled".to_string(), JsonValue::Bool(true));
 
+                if let Some(sni) = node.sni.as_deref().filter(|sni| !sni.is_empty()) {
+                    tls.insert(
+                        "server_name".to_string(),
+                        JsonValue::String(sni.to_string()),
+                    );
+                }
+
                 if let Some(allow_insecure) = scv {
                     tls.insert("insecure".to_string(), JsonValue::Bool(allow_insecure));
                 }
 
-                if !node.alpn.is_empty() && node.alpn.len() > 0 {
-                    let alpn = vec![JsonValue::String(node.alpn.iter().next().unwrap().clone())];
-                    tls.insert("alpn".to_string(), JsonValue::Array(alpn));
+                if !node.alpn.is_empty() {
+                    tls.insert("alpn".to_string(), json!(node.alpn));
                 }
 
                 if let Some(ca) = &node.ca {
@@ -561,13 +840,19 @@ pub fn proxy_to_singbox(
                 let mut tls = Map::new();
                 tls.insert("enabled".to_string(), JsonValue::Bool(true));
 
+                if let Some(sni) = node.sni.as_deref().filter(|sni| !sni.is_empty()) {
+                    tls.insert(
+                        "server_name".to_string(),
+                        JsonValue::String(sni.to_string()),
+                    );
+                }
+
                 if let Some(allow_insecure) = scv {
                     tls.insert("insecure".to_string(), JsonValue::Bool(allow_insecure));
                 }
 
-                if !node.alpn.is_empty() && node.alpn.len() > 0 {
-                    let alpn = vec![JsonValue::String(node.alpn.iter().next().unwrap().clone())];
-                    tls.insert("alpn".to_string(), JsonValue::Array(alpn));
+                if !node.alpn.is_empty() {
+                    tls.insert("alpn".to_string(), json!(node.alpn));
                 }
 
                 if let Some(ca) = &node.ca {
@@ -617,34 +902,28 @@ pub fn proxy_to_singbox(
 
                 obj
             }
-            _ => continue, // Skip unsupported types
+            _ => {
+                ext.warnings.unsupported_node(node, "sing-box");
+                continue;
+            }
         };
 
         // Add TLS settings for protocols that need it
-        if node.tls_secure {
-            let mut tls = Map::new();
-            tls.insert("enabled".to_string(), JsonValue::Bool(true));
-
-            // Set server_name from ServerName or Host
-            if let Some(server_name) = &node.server_name {
-                if !server_name.is_empty() {
-                    tls.insert(
-                        "server_name".to_string(),
-                        JsonValue::String(server_name.clone()),
-                    );
-                }
-            } else if let Some(host) = &node.host {
-                if !host.is_empty() {
-                    tls.insert("server_name".to_string(), JsonValue::String(host.clone()));
-                }
-            }
+        if let Some(tls) = build_singbox_tls(node, scv) {
+            proxy_obj.insert("tls".to_string(), JsonValue::Object(tls));
+        }
 
-            // Add insecure option
-            if let Some(allow_insecure) = scv {
-                tls.insert("insecure".to_string(), JsonValue::Bool(allow_insecure));
+        // Add multiplex settings when smux is enabled
+        if node.smux_enabled == Some(true) {
+            let mut multiplex = Map::new();
+            multiplex.insert("enabled".to_string(), JsonValue::Bool(true));
+            if let Some(max_streams) = node.smux_max_streams.filter(|v| *v > 0) {
+                multiplex.insert(
+                    "max_streams".to_string(),
+                    JsonValue::Number(max_streams.into()),
+                );
             }
-
-            proxy_obj.insert("tls".to_string(), JsonValue::Object(tls));
+            proxy_obj.insert("multiplex".to_string(), JsonValue::Object(multiplex));
         }
 
         // Add UDP and TFO settings
@@ -658,11 +937,41 @@ pub fn proxy_to_singbox(
             proxy_obj.insert("tcp_fast_open".to_string(), JsonValue::Bool(tfo_enabled));
         }
 
+        // Dial options belong to the outbound connecting to the server
+        let dialer = match detour_obj.as_mut().and_then(JsonValue::as_object_mut) {
+            Some(detour) => detour,
+            None => &mut proxy_obj,
+        };
+        if let Some(interface) = node.interface.as_ref().or(ext.interface.as_ref()) {
+            dialer.insert("bind_interface".to_string(), json!(interface));
+        }
+        if let Some(routing_mark) = node.routing_mark.or(ext.routing_mark) {
+            dialer.insert("routing_mark".to_string(), json!(routing_mark));
+        }
+
+        // Chain through the underlying proxy, shadow-tls takes the detour itself
+        if let Some(parent) = node
+            .underlying_proxy
+            .as_deref()
+            .filter(|name| !name.is_empty())
+        {
+            match detour_obj.as_mut() {
+                Some(detour) => detour["detour"] = json!(parent),
+                None => {
+                    proxy_obj.insert("detour".to_string(), json!(parent));
+                }
+            }
+        }
+
         // Add to node list and outbounds
         nodelist.push(node.clone());
         remarks_list.push(node.remark.clone());
         outbounds.push(JsonValue::Object(proxy_obj));
+        if let Some(detour) = detour_obj {
+            outbounds.push(detour);
+        }
     }
+    follow_detours(&mut outbounds, &final_tags);
 
     // If nodelist mode, just return outbounds
     if ext.nodelist {
@@ -674,26 +983,34 @@ pub fn proxy_to_singbox(
     }
 
     // Process proxy groups
+    let group_names = group_names(extra_proxy_group);
     for group in extra_proxy_group {
+        let group_names = other_group_names(&group_names, &group.name);
         let mut filtered_nodelist = Vec::new();
 
         // Determine group type
         let group_type = match group.group_type {
             ProxyGroupType::Select => "selector",
-            ProxyGroupType::URLTest | ProxyGroupType::Fallback | ProxyGroupType::LoadBalance => {
-                "urltest"
-            }
+            _ if group.is_tested_group() => "urltest",
             _ => continue, // Skip unsupported types
         };
 
         // Generate filtered proxy list
         for proxy_name in &group.proxies {
-            group_generate(proxy_name, &nodelist, &mut filtered_nodelist, true, ext);
+            group_generate(
+                proxy_name,
+                &nodelist,
+                &group_names,
+                &mut filtered_nodelist,
+                true,
+                ext,
+            );
         }
+        apply_group_filters(group, &nodelist, &mut filtered_nodelist);
 
         // Add DIRECT if empty
         if filtered_nodelist.is_empty() {
-            filtered_nodelist.push("DIRECT".to_string());
+            filtered_nodelist.push(ext.empty_group_placeholder.policy().to_string());
         }
 
         // Create group object
@@ -713,13 +1030,20 @@ pub fn proxy_to_singbox(
         group_obj.insert("outbounds".to_string(), JsonValue::Array(group_outbounds));
 
         // Add URL Test specific settings
-        if group.group_type == ProxyGroupType::URLTest {
+        if group.is_tested_group() {
             group_obj.insert("url".to_string(), JsonValue::String(group.url.clone()));
             group_obj.insert(
                 "interval".to_string(),
                 JsonValue::String(format_singbox_interval(group.interval as u32)),
             );
 
+            if group.timeout > 0 {
+                group_obj.insert(
+                    "idle_timeout".to_string(),
+                    JsonValue::String(format_singbox_interval(group.timeout)),
+                );
+            }
+
             if group.tolerance > 0 {
                 group_obj.insert(
                     "tolerance".to_string(),
@@ -731,10 +1055,8 @@ pub fn proxy_to_singbox(
         outbounds.push(JsonValue::Object(group_obj));
     }
 
-    let global = Settings::current();
-
     // Add global group if enabled
-    if global.singbox_add_clash_modes {
+    if ext.singbox_add_clash_modes {
         let mut global_group = Map::new();
         global_group.insert(
             "type".to_string(),
@@ -768,6 +1090,8 @@ pub fn proxy_to_singbox(
         &mut json,
         ruleset_content_array,
         ext.overwrite_original_rules,
+        ext.max_allowed_rules,
+        ext.singbox_add_clash_modes,
     );
 
     serde_json::to_string_pretty(&json).unwrap_or_default()
@@ -783,3 +1107,206 @@ pub fn generate_singbox(
 ) -> String {
     String::new()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::yaml::clash::parse_clash_yaml;
+
+    #[test]
+    fn test_shadow_tls_detour() {
+        let source = r#"
+proxies:
+  - name: ss-shadow-tls
+    type: ss
+    server: example.com
+    port: 443
+    cipher: 2022-blake3-aes-128-gcm
+    password: c3MtcGFzc3dvcmQ=
+    plugin: shadow-tls
+    plugin-opts:
+      host: cloud.tencent.com
+      password: shadow_tls_password
+      version: 3
+"#;
+        let mut nodes = parse_clash_yaml(source).unwrap();
+        let mut ext = ExtraSettings::default();
+        ext.nodelist = true;
+        let output = proxy_to_singbox(&mut nodes, "", &mut Vec::new(), &Vec::new(), &mut ext);
+
+        let json: JsonValue = serde_json::from_str(&output).unwrap();
+        let outbounds = json["outbounds"].as_array().unwrap();
+        assert_eq!(outbounds.len(), 2);
+        assert_eq!(outbounds[0]["type"], "shadowsocks");
+        assert_eq!(outbounds[0]["detour"], "ss-shadow-tls-shadowtls");
+        assert!(outbounds[0].get("plugin").is_none());
+        assert_eq!(outbounds[1]["type"], "shadowtls");
+        assert_eq!(outbounds[1]["tag"], "ss-shadow-tls-shadowtls");
+        assert_eq!(outbounds[1]["server_port"], 443);
+        assert_eq!(outbounds[1]["version"], 3);
+        assert_eq!(outbounds[1]["password"], "shadow_tls_password");
+        assert_eq!(outbounds[1]["tls"]["server_name"], "cloud.tencent.com");
+    }
+
+    #[test]
+    fn test_detour_follows_renamed_parent() {
+        let source = r#"
+proxies:
+  - {name: HK Relay, type: ss, server: hk.example.com, port: 8388, cipher: aes-128-gcm, password: secret}
+  - {name: US Exit, type: ss, server: us.example.com, port: 8388, cipher: aes-128-gcm, password: secret, dialer-proxy: HK Relay}
+  - {name: JP Exit, type: ss, server: jp.example.com, port: 8388, cipher: aes-128-gcm, password: secret, dialer-proxy: Gone}
+"#;
+        let mut nodes = parse_clash_yaml(source).unwrap();
+        let mut ext = ExtraSettings::default();
+        ext.nodelist = true;
+        ext.append_proxy_type = true;
+        let output = proxy_to_singbox(&mut nodes, "", &mut Vec::new(), &Vec::new(), &mut ext);
+
+        let json: JsonValue = serde_json::from_str(&output).unwrap();
+        let outbounds = json["outbounds"].as_array().unwrap();
+        assert_eq!(outbounds[0]["tag"], "[SS] HK Relay");
+        assert!(outbounds[0].get("detour").is_none());
+        assert_eq!(outbounds[1]["detour"], "[SS] HK Relay");
+        // Kept as it is, with a warning
+        assert_eq!(outbounds[2]["detour"], "Gone");
+    }
+
+    #[test]
+    fn test_interface_and_routing_mark() {
+        let source = r#"
+proxies:
+  - {name: HK 01, type: ss, server: hk.example.com, port: 8388, cipher: aes-128-gcm, password: secret, interface-name: eth1, routing-mark: 255}
+  - {name: JP 01, type: ss, server: jp.example.com, port: 8388, cipher: aes-128-gcm, password: secret}
+"#;
+        let mut nodes = parse_clash_yaml(source).unwrap();
+        assert_eq!(nodes[0].interface.as_deref(), Some("eth1"));
+        assert_eq!(nodes[0].routing_mark, Some(255));
+        assert_eq!(nodes[1].interface, None);
+
+        let mut ext = ExtraSettings::default();
+        ext.nodelist = true;
+        ext.interface = Some("wlan0".to_string());
+        ext.routing_mark = Some(6666);
+        let output = proxy_to_singbox(&mut nodes, "", &mut Vec::new(), &Vec::new(), &mut ext);
+
+        let json: JsonValue = serde_json::from_str(&output).unwrap();
+        let outbounds = json["outbounds"].as_array().unwrap();
+        assert_eq!(outbounds[0]["bind_interface"], "eth1");
+        assert_eq!(outbounds[0]["routing_mark"], 255);
+        // The defaults fill in what the node does not set
+        assert_eq!(outbounds[1]["bind_interface"], "wlan0");
+        assert_eq!(outbounds[1]["routing_mark"], 6666);
+    }
+
+    #[test]
+    fn test_hysteria_alpn_list() {
+        let source = r#"
+proxies:
+  - {name: HY, type: hysteria, server: hy.example.com, port: 443, auth-str: secret, up: 10, down: 50, alpn: [h3, h2]}
+  - {name: HY2, type: hysteria2, server: hy2.example.com, port: 443, password: secret, alpn: [h3, h2]}
+"#;
+        let mut nodes = parse_clash_yaml(source).unwrap();
+        let mut ext = ExtraSettings::default();
+        ext.nodelist = true;
+        let output = proxy_to_singbox(&mut nodes, "", &mut Vec::new(), &Vec::new(), &mut ext);
+
+        let json: JsonValue = serde_json::from_str(&output).unwrap();
+        let outbounds = json["outbounds"].as_array().unwrap();
+        for outbound in outbounds {
+            assert_eq!(outbound["tls"]["alpn"], json!(["h3", "h2"]));
+        }
+    }
+
+    #[test]
+    fn test_reality_and_ech_tls() {
+        let source = r#"
+proxies:
+  - name: vless-reality
+    type: vless
+    server: 1.2.3.4
+    port: 443
+    uuid: b831381d-6324-4d53-ad4f-8cda48b30811
+    flow: xtls-rprx-vision
+    network: tcp
+    tls: true
+    servername: www.example.com
+    client-fingerprint: safari
+    reality-opts:
+      public-key: PUBKEY
+      short-id: 0123abcd
+  - name: trojan-ech
+    type: trojan
+    server: 5.6.7.8
+    port: 443
+    password: secret
+    sni: trojan.example.com
+    skip-cert-verify: true
+    ech-opts:
+      enable: true
+      config: RUNIQ09ORklH
+"#;
+        let mut nodes = parse_clash_yaml(source).unwrap();
+        let mut ext = ExtraSettings::default();
+        ext.nodelist = true;
+        let output = proxy_to_singbox(&mut nodes, "", &mut Vec::new(), &Vec::new(), &mut ext);
+
+        let json: JsonValue = serde_json::from_str(&output).unwrap();
+        let outbounds = json["outbounds"].as_array().unwrap();
+        assert_eq!(outbounds.len(), 2);
+        assert_eq!(outbounds[0]["type"], "vless");
+        assert_eq!(outbounds[0]["uuid"], "b831381d-6324-4d53-ad4f-8cda48b30811");
+        assert_eq!(outbounds[0]["flow"], "xtls-rprx-vision");
+        assert!(outbounds[0].get("transport").is_none());
+        assert_eq!(
+            outbounds[0]["tls"],
+            json!({
+                "enabled": true,
+                "server_name": "www.example.com",
+                "utls": { "enabled": true, "fingerprint": "safari" },
+                "reality": { "enabled": true, "public_key": "PUBKEY", "short_id": "0123abcd" }
+            })
+        );
+
+        assert_eq!(outbounds[1]["type"], "trojan");
+        assert_eq!(
+            outbounds[1]["tls"],
+            json!({
+                "enabled": true,
+                "server_name": "trojan.example.com",
+                "insecure": true,
+                "ech": {
+                    "enabled": true,
+                    "config": [
+                        "-----BEGIN ECH CONFIGS-----",
+                        "RUNIQ09ORklH",
+                        "-----END ECH CONFIGS-----"
+                    ]
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_reality_share_link_defaults_to_chrome() {
+        let mut node = Proxy::default();
+        assert!(crate::parser::explodes::explode(
+            "vless://b831381d-6324-4d53-ad4f-8cda48b30811@1.2.3.4:443?security=reality&pbk=PUBKEY&sid=ab&sni=www.example.com&type=grpc&serviceName=svc#R",
+            &mut node
+        ));
+        node.client_fingerprint = None;
+        if let Some(CombinedProxy::Vless(vless)) = &mut node.combined_proxy {
+            vless.client_fingerprint = None;
+        }
+
+        let tls = build_singbox_tls(&node, None).unwrap();
+        assert_eq!(tls["utls"]["fingerprint"], "chrome");
+        assert_eq!(tls["reality"]["short_id"], "ab");
+        assert_eq!(
+            build_singbox_vless_transport(match &node.combined_proxy {
+                Some(CombinedProxy::Vless(vless)) => vless,
+                _ => unreachable!(),
+            }),
+            json!({ "type": "grpc", "service_name": "svc" })
+        );
+    }
+}
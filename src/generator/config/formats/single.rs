@@ -1,5 +1,7 @@
 use crate::models::{ExtraSettings, Proxy, ProxyType, SSR_CIPHERS, SS_CIPHERS};
 use crate::utils::base64::{base64_encode, url_safe_base64_encode};
+use crate::utils::network::bracket_host;
+use crate::utils::tribool::TriboolExt;
 use crate::utils::url::url_encode;
 use log::error;
 // Bitflags for proxy types used in conversions
@@ -16,6 +18,23 @@ bitflags! {
     }
 }
 
+impl ProxyUriTypes {
+    /// Name of the links, as used in warnings
+    fn name(self) -> &'static str {
+        if self == Self::SS {
+            "SS links"
+        } else if self == Self::SSR {
+            "SSR links"
+        } else if self == Self::VMESS {
+            "VMess links"
+        } else if self == Self::TROJAN {
+            "Trojan links"
+        } else {
+            "mixed links"
+        }
+    }
+}
+
 /// Generate a VMess link
 ///
 /// # Arguments
@@ -25,6 +44,7 @@ bitflags! {
 /// * `fake_type` - Fake type
 /// * `user_id` - User ID
 /// * `alter_id` - Alter ID
+/// * `cipher` - Security, e.g. `auto`, `zero` or `none`, left out when empty
 /// * `transfer_protocol` - Transfer protocol
 /// * `path` - Path
 /// * `host` - Host
@@ -39,6 +59,7 @@ fn vmess_link_construct(
     fake_type: Option<&str>,
     user_id: &str,
     alter_id: u16,
+    cipher: &str,
     transfer_protocol: &str,
     path: &str,
     host: &str,
@@ -60,6 +81,9 @@ fn vmess_link_construct(
     if let Some(ft) = fake_type {
         json["type"] = serde_json::Value::String(ft.to_string());
     }
+    if !cipher.is_empty() {
+        json["scy"] = serde_json::Value::String(cipher.to_string());
+    }
 
     match serde_json::to_string(&json) {
         Ok(result) => result,
@@ -91,6 +115,8 @@ pub fn proxy_to_single(
     for node in nodes {
         let remark = &node.remark;
         let hostname = &node.hostname;
+        // Host as written in `host:port` style links
+        let server = bracket_host(hostname);
         let port = node.port.to_string();
 
         // Extract optional fields with safe defaults
@@ -120,7 +146,7 @@ pub fn proxy_to_single(
                     _proxy_str = format!(
                         "ss://{}@{}:{}",
                         url_safe_base64_encode(&format!("{}:{}", method, password)),
-                        hostname,
+                        server,
                         port
                     );
 
@@ -132,26 +158,24 @@ pub fn proxy_to_single(
                     }
 
                     _proxy_str.push_str(&format!("#{}", url_encode(remark)));
-                } else if types.contains(ProxyUriTypes::SSR) {
+                } else if types.contains(ProxyUriTypes::SSR)
+                    && ext.ssr_format_ss
+                    && SSR_CIPHERS.contains(&method)
+                    && plugin.is_empty()
+                {
                     // Convert SS to SSR if compatible
-                    if SSR_CIPHERS.contains(&method) && plugin.is_empty() {
-                        _proxy_str = format!(
-                            "ssr://{}",
-                            url_safe_base64_encode(&format!(
-                                "{}:{}:origin:{}:plain:{}/?group={}&remarks={}",
-                                hostname,
-                                port,
-                                method,
-                                url_safe_base64_encode(password),
-                                url_safe_base64_encode(group),
-                                url_safe_base64_encode(remark)
-                            ))
-                        );
-                    } else {
-                        continue;
-                    }
-                } else {
-                    continue;
+                    _proxy_str = format!(
+                        "ssr://{}",
+                        url_safe_base64_encode(&format!(
+                            "{}:{}:origin:{}:plain:{}/?group={}&remarks={}",
+                            hostname,
+                            port,
+                            method,
+                            url_safe_base64_encode(password),
+                            url_safe_base64_encode(group),
+                            url_safe_base64_encode(remark)
+                        ))
+                    );
                 }
             }
             ProxyType::ShadowsocksR => {
@@ -173,28 +197,22 @@ pub fn proxy_to_single(
                             url_safe_base64_encode(protocol_param)
                         ))
                     );
-                } else if types.contains(ProxyUriTypes::SS) {
+                } else if types.contains(ProxyUriTypes::SS)
+                    && SS_CIPHERS.contains(&method)
+                    && protocol == "origin"
+                    && obfs == "plain"
+                {
                     // Convert SSR to SS if compatible
-                    if SS_CIPHERS.contains(&method) && protocol == "origin" && obfs == "plain" {
-                        _proxy_str = format!(
-                            "ss://{}@{}:{}#{}",
-                            url_safe_base64_encode(&format!("{}:{}", method, password)),
-                            hostname,
-                            port,
-                            url_encode(remark)
-                        );
-                    } else {
-                        continue;
-                    }
-                } else {
-                    continue;
+                    _proxy_str = format!(
+                        "ss://{}@{}:{}#{}",
+                        url_safe_base64_encode(&format!("{}:{}", method, password)),
+                        server,
+                        port,
+                        url_encode(remark)
+                    );
                 }
             }
-            ProxyType::VMess => {
-                if !types.contains(ProxyUriTypes::VMESS) {
-                    continue;
-                }
-
+            ProxyType::VMess if types.contains(ProxyUriTypes::VMESS) => {
                 // VMess format
                 let vmess_json = vmess_link_construct(
                     remark,
@@ -203,6 +221,7 @@ pub fn proxy_to_single(
                     fake_type,
                     user_id,
                     alter_id,
+                    method,
                     transfer_protocol,
                     path,
                     host,
@@ -211,18 +230,18 @@ pub fn proxy_to_single(
 
                 _proxy_str = format!("vmess://{}", base64_encode(&vmess_json));
             }
-            ProxyType::Trojan => {
-                if !types.contains(ProxyUriTypes::TROJAN) {
-                    continue;
-                }
-
+            ProxyType::Trojan if types.contains(ProxyUriTypes::TROJAN) => {
                 // Trojan format
                 _proxy_str = format!(
                     "trojan://{}@{}:{}?allowInsecure={}",
                     password,
-                    hostname,
+                    server,
                     port,
-                    if node.allow_insecure.unwrap_or(false) {
+                    if node
+                        .allow_insecure
+                        .define(ext.skip_cert_verify)
+                        .unwrap_or(false)
+                    {
                         "1"
                     } else {
                         "0"
@@ -242,7 +261,11 @@ pub fn proxy_to_single(
 
                 _proxy_str.push_str(&format!("#{}", url_encode(remark)));
             }
-            _ => continue,
+            _ => {}
+        }
+        if _proxy_str.is_empty() {
+            ext.warnings.unsupported_node(node, types.name());
+            continue;
         }
 
         all_links.push_str(&_proxy_str);
@@ -256,3 +279,207 @@ pub fn proxy_to_single(
         base64_encode(&all_links)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::explodes::{explode_ss, explode_ssr, explode_trojan, explode_vmess};
+    use crate::utils::base64::base64_decode;
+
+    fn ssr_links(nodes: &mut Vec<Proxy>, ssr_format_ss: bool) -> Vec<Proxy> {
+        let mut ext = ExtraSettings::default();
+        ext.ssr_format_ss = ssr_format_ss;
+        let output = proxy_to_single(nodes, ProxyUriTypes::SSR, &mut ext);
+        base64_decode(&output, false)
+            .lines()
+            .map(|link| {
+                let mut node = Proxy::default();
+                assert!(explode_ssr(link, &mut node));
+                node
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_ssr_round_trip() {
+        let mut nodes = vec![
+            Proxy::ssr_construct(
+                "Provider",
+                "HK 01",
+                "hk.example.com",
+                8443,
+                "auth_aes128_md5",
+                "aes-256-cfb",
+                "tls1.2_ticket_auth",
+                "secret",
+                "cdn.example.com",
+                "1:abc",
+                None,
+                None,
+                None,
+                "",
+            ),
+            Proxy::ss_construct(
+                "Provider",
+                "JP 01",
+                "jp.example.com",
+                8388,
+                "password",
+                "aes-128-cfb",
+                "",
+                "",
+                None,
+                None,
+                None,
+                None,
+                "",
+            ),
+        ];
+
+        let parsed = ssr_links(&mut nodes, false);
+        assert_eq!(parsed.len(), 1);
+        let ssr = &parsed[0];
+        assert_eq!(ssr.proxy_type, ProxyType::ShadowsocksR);
+        assert_eq!(ssr.remark, "HK 01");
+        assert_eq!(ssr.group, "Provider");
+        assert_eq!((ssr.hostname.as_str(), ssr.port), ("hk.example.com", 8443));
+        assert_eq!(ssr.protocol.as_deref(), Some("auth_aes128_md5"));
+        assert_eq!(ssr.protocol_param.as_deref(), Some("1:abc"));
+        assert_eq!(ssr.obfs.as_deref(), Some("tls1.2_ticket_auth"));
+        assert_eq!(ssr.obfs_param.as_deref(), Some("cdn.example.com"));
+        assert_eq!(ssr.encrypt_method.as_deref(), Some("aes-256-cfb"));
+        assert_eq!(ssr.password.as_deref(), Some("secret"));
+
+        let parsed = ssr_links(&mut nodes, true);
+        assert_eq!(parsed.len(), 2);
+        let ss = &parsed[1];
+        assert_eq!(ss.proxy_type, ProxyType::Shadowsocks);
+        assert_eq!(ss.remark, "JP 01");
+        assert_eq!(ss.encrypt_method.as_deref(), Some("aes-128-cfb"));
+        assert_eq!(ss.password.as_deref(), Some("password"));
+    }
+
+    #[test]
+    fn test_ipv6_links() {
+        let mut nodes = vec![
+            Proxy::ss_construct(
+                "Provider",
+                "SS v6",
+                "2001:db8::1",
+                8388,
+                "password",
+                "aes-128-gcm",
+                "",
+                "",
+                None,
+                None,
+                None,
+                None,
+                "",
+            ),
+            Proxy::trojan_construct(
+                "Provider".to_string(),
+                "Trojan v6".to_string(),
+                "2001:db8::2".to_string(),
+                443,
+                "password".to_string(),
+                None,
+                Some("example.com".to_string()),
+                None,
+                None,
+                true,
+                None,
+                None,
+                None,
+                None,
+                None,
+            ),
+        ];
+        let mut ext = ExtraSettings::default();
+        ext.nodelist = true;
+        let output = proxy_to_single(
+            &mut nodes,
+            ProxyUriTypes::SS | ProxyUriTypes::TROJAN,
+            &mut ext,
+        );
+        let links: Vec<&str> = output.lines().collect();
+        assert_eq!(links.len(), 2);
+        assert!(links[0].contains("@[2001:db8::1]:8388"));
+        assert!(links[1].starts_with("trojan://password@[2001:db8::2]:443?"));
+
+        let mut ss = Proxy::default();
+        assert!(explode_ss(links[0], &mut ss));
+        assert_eq!((ss.hostname.as_str(), ss.port), ("2001:db8::1", 8388));
+
+        let mut trojan = Proxy::default();
+        assert!(explode_trojan(links[1], &mut trojan));
+        assert_eq!(
+            (trojan.hostname.as_str(), trojan.port),
+            ("2001:db8::2", 443)
+        );
+
+        // Older clients write the address without brackets
+        let unbracketed = links[0].replace("[2001:db8::1]", "2001:db8::1");
+        let mut ss = Proxy::default();
+        assert!(explode_ss(&unbracketed, &mut ss));
+        assert_eq!((ss.hostname.as_str(), ss.port), ("2001:db8::1", 8388));
+
+        for add in ["2001:db8::3", "[2001:db8::3]"] {
+            let json = vmess_link_construct(
+                "VMess v6",
+                add,
+                10086,
+                None,
+                "b831381d-6324-4d53-ad4f-8cda48b30811",
+                0,
+                "",
+                "tcp",
+                "",
+                "",
+                "",
+            );
+            let mut vmess = Proxy::default();
+            assert!(explode_vmess(
+                &format!("vmess://{}", base64_encode(&json)),
+                &mut vmess
+            ));
+            assert_eq!(
+                (vmess.hostname.as_str(), vmess.port),
+                ("2001:db8::3", 10086)
+            );
+        }
+    }
+    #[test]
+    fn test_vmess_links_keep_cipher() {
+        for cipher in ["auto", "zero", "none", "aes-128-gcm"] {
+            let mut nodes = vec![Proxy::vmess_construct(
+                "Provider",
+                "VMess",
+                "vmess.example.com",
+                443,
+                "",
+                "b831381d-6324-4d53-ad4f-8cda48b30811",
+                0,
+                "ws",
+                cipher,
+                "/ws",
+                "cdn.example.com",
+                "",
+                "tls",
+                "",
+                None,
+                None,
+                None,
+                None,
+                "",
+            )];
+            let mut ext = ExtraSettings::default();
+            ext.nodelist = true;
+            let output = proxy_to_single(&mut nodes, ProxyUriTypes::VMESS, &mut ext);
+
+            let mut vmess = Proxy::default();
+            assert!(explode_vmess(output.trim(), &mut vmess));
+            assert_eq!(vmess.encrypt_method.as_deref(), Some(cipher));
+        }
+    }
+}
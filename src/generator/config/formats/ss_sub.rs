@@ -15,11 +15,7 @@ use serde_json::{json, Value as JsonValue};
 ///
 /// # Returns
 /// * JSON string in SIP008 format
-pub fn proxy_to_ss_sub(
-    base_conf: &str,
-    nodes: &mut Vec<Proxy>,
-    _ext: &mut ExtraSettings,
-) -> String {
+pub fn proxy_to_ss_sub(base_conf: &str, nodes: &mut Vec<Proxy>, ext: &mut ExtraSettings) -> String {
     // Parse the base configuration
     let base_conf = trim_whitespace(base_conf, true, true);
     let base_conf = if base_conf.is_empty() {
@@ -64,10 +60,14 @@ pub fn proxy_to_ss_sub(
             ProxyType::ShadowsocksR => {
                 // Skip incompatible SSR nodes
                 if !SS_CIPHERS.contains(&method) || protocol != "origin" || obfs != "plain" {
+                    ext.warnings.unsupported_node(node, "SS subscriptions");
                     continue;
                 }
             }
-            _ => continue, // Skip all other proxy types
+            _ => {
+                ext.warnings.unsupported_node(node, "SS subscriptions");
+                continue;
+            }
         }
 
         // Create a proxy object
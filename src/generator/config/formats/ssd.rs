@@ -16,7 +16,7 @@ pub fn proxy_to_ssd(
     nodes: &mut Vec<Proxy>,
     group: &str,
     userinfo: &str,
-    _ext: &crate::models::ExtraSettings,
+    ext: &crate::models::ExtraSettings,
 ) -> String {
     let mut index = 0;
     let group = if group.is_empty() { "SSD" } else { group };
@@ -133,7 +133,10 @@ pub fn proxy_to_ssd(
                     index += 1;
                 }
             }
-            _ => continue,
+            _ => {
+                ext.warnings.unsupported_node(node, "SSD");
+                continue;
+            }
         }
     }
 
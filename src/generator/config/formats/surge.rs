@@ -1,15 +1,19 @@
-use crate::generator::config::group::group_generate;
+use crate::generator::config::group::{
+    apply_group_filters, group_generate, group_names, other_group_names,
+};
 use crate::generator::config::remark::process_remark;
 use crate::generator::ruleconvert::ruleset_to_surge::ruleset_to_surge;
 use crate::models::{
-    ExtraSettings, Proxy, ProxyGroupConfigs, ProxyGroupType, ProxyType, RulesetContent,
+    ExtraSettings, Proxy, ProxyGroupConfig, ProxyGroupConfigs, ProxyGroupType, ProxyType,
+    RulesetContent, SubconverterTarget, WarningKind,
 };
 use crate::utils::ini_reader::IniReader;
-use crate::utils::network::{hostname_to_ip_addr, is_ipv4, is_ipv6};
+use crate::utils::network::{bracket_host, format_host_for, hostname_to_ip_addr, is_ipv4, is_ipv6};
+use crate::utils::plugin_options::parse_plugin_options;
 use crate::utils::string::{hash, join, to_lower};
 use crate::utils::tribool::{BoolTriboolExt, TriboolExt};
-use crate::Settings;
-use log::error;
+use log::{error, warn};
+use std::collections::HashMap;
 
 /// Generate a WireGuard peer configuration string
 ///
@@ -29,7 +33,7 @@ fn generate_peer(node: &Proxy, client_id_as_reserved: bool) -> String {
     }
 
     peer.push_str("endpoint = ");
-    peer.push_str(&node.hostname);
+    peer.push_str(&bracket_host(&node.hostname));
     peer.push_str(":");
     peer.push_str(&node.port.to_string());
 
@@ -53,6 +57,104 @@ fn generate_peer(node: &Proxy, client_id_as_reserved: bool) -> String {
     peer
 }
 
+/// Append `tls13` to a proxy line when it is defined
+fn push_tls13(proxy: &mut String, tls13: Option<bool>) {
+    if let Some(tls13) = tls13 {
+        proxy.push_str(&format!(", tls13={}", tls13));
+    }
+}
+
+/// Version passed to `proxy_to_surge` to generate a Surfboard configuration
+pub const SURFBOARD_VERSION: i32 = -3;
+
+/// Proxy types and options understood by the targeted Surge-like client
+///
+/// Surfboard reads the Surge format but only a subset of it, lines it does not
+/// know make the app reject the whole configuration, so they are left out.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct SurgeCapabilities {
+    surfboard: bool,
+    /// `ss` lines instead of the SSEncrypt module
+    native_ss: bool,
+    /// The `2022-blake3-*` ciphers of Shadowsocks 2022
+    ss_2022: bool,
+    vmess: bool,
+    /// `tls13` on the lines of TLS proxies
+    tls13: bool,
+    trojan: bool,
+    snell: bool,
+    wireguard: bool,
+    /// `test-url` on WireGuard proxies
+    wireguard_test_url: bool,
+    hysteria2: bool,
+    /// SSR through an external binary
+    external: bool,
+    /// HTTPS proxies written as `https` lines instead of `http, ..., tls=true`
+    https_line: bool,
+    tfo: bool,
+    load_balance: bool,
+    ssid_group: bool,
+    smart_group: bool,
+    /// `persistent` and `evaluate-before-use` on tested groups
+    group_test_options: bool,
+    policy_path: bool,
+}
+
+impl SurgeCapabilities {
+    fn for_version(surge_ver: i32) -> Self {
+        if surge_ver == SURFBOARD_VERSION {
+            return Self {
+                surfboard: true,
+                native_ss: true,
+                ss_2022: false,
+                vmess: true,
+                tls13: false,
+                trojan: true,
+                snell: false,
+                wireguard: true,
+                wireguard_test_url: false,
+                hysteria2: false,
+                external: false,
+                https_line: true,
+                tfo: false,
+                load_balance: true,
+                ssid_group: false,
+                smart_group: false,
+                group_test_options: false,
+                policy_path: true,
+            };
+        }
+        Self {
+            surfboard: false,
+            native_ss: surge_ver >= 3,
+            ss_2022: surge_ver >= 4,
+            vmess: surge_ver >= 4,
+            tls13: true,
+            trojan: surge_ver >= 4,
+            snell: true,
+            wireguard: surge_ver >= 4,
+            wireguard_test_url: true,
+            hysteria2: surge_ver >= 4,
+            external: surge_ver >= 2,
+            https_line: false,
+            tfo: true,
+            load_balance: surge_ver >= 1,
+            ssid_group: true,
+            smart_group: true,
+            group_test_options: true,
+            policy_path: surge_ver >= 3,
+        }
+    }
+
+    fn client_name(&self) -> &'static str {
+        if self.surfboard {
+            "Surfboard"
+        } else {
+            "Surge"
+        }
+    }
+}
+
 /// Convert proxies to Surge format
 ///
 /// This function converts a list of proxies to the Surge configuration format,
@@ -81,7 +183,12 @@ pub async fn proxy_to_surge(
     let mut nodelist = Vec::new();
     let mut local_port = 1080;
     let mut remarks_list = Vec::new();
-    let global = Settings::current();
+    let caps = SurgeCapabilities::for_version(surge_ver);
+    let target = if caps.surfboard {
+        SubconverterTarget::Surfboard
+    } else {
+        SubconverterTarget::Surge(surge_ver)
+    };
 
     // Configure INI reader
     ini.store_any_line = true;
@@ -115,20 +222,26 @@ pub async fn proxy_to_surge(
     ini.erase_section();
     ini.set("{NONAME}", "DIRECT", "direct").unwrap_or(());
 
+    // Lines with their underlying proxy, written once every node has its final name
+    let mut lines: Vec<(String, String, String)> = Vec::new();
+    let mut final_names: HashMap<String, String> = HashMap::new();
+
     // Process each proxy node
     for node in nodes.iter_mut() {
+        let original_remark = node.remark.clone();
+
         // Add proxy type prefix if enabled
         if ext.append_proxy_type {
-            let proxy_type = node.proxy_type.to_string();
-            node.remark = format!("[{}] {}", proxy_type, node.remark);
+            node.remark = format!("[{}] {}", node.proxy_type, node.remark);
         }
 
         // Process remark
         let mut remark = node.remark.clone();
-        process_remark(&mut remark, &remarks_list, false);
+        process_remark(&mut remark, &remarks_list, false, ext.max_remark_length);
 
         // Extract node properties for easier access
         let hostname = &node.hostname;
+        let server = format_host_for(&target, hostname);
         let port = node.port.to_string();
         let username = node.username.as_deref().unwrap_or("");
         let password = node.password.as_deref().unwrap_or("");
@@ -167,21 +280,34 @@ pub async fn proxy_to_surge(
         // Build proxy string based on type
         match node.proxy_type {
             ProxyType::Shadowsocks => {
-                if surge_ver >= 3 || surge_ver == -3 {
+                if !caps.ss_2022 && method.starts_with("2022-") {
+                    ext.warnings.push(
+                        WarningKind::UnsupportedNode,
+                        format!(
+                            "Skipping node '{}', {} does not support cipher {}",
+                            remark,
+                            caps.client_name(),
+                            method
+                        ),
+                    );
+                    continue;
+                }
+
+                if caps.native_ss {
                     _proxy = format!(
                         "ss, {}, {}, encrypt-method={}, password={}",
-                        hostname, port, method, password
+                        server, port, method, password
                     );
                 } else {
                     _proxy = format!("custom, {}, {}, {}, {}, https://github.com/pobizhe/SSEncrypt/raw/master/SSEncrypt.module", 
-                                   hostname, port, method, password);
+                                   server, port, method, password);
                 }
 
                 if !plugin.is_empty() {
                     match plugin {
                         "simple-obfs" | "obfs-local" => {
-                            if !pluginopts.is_empty() {
-                                _proxy.push_str(&format!(",{}", pluginopts.replace(';', ",")));
+                            for (key, value) in parse_plugin_options(pluginopts) {
+                                _proxy.push_str(&format!(", {}={}", key, value));
                             }
                         }
                         _ => continue,
@@ -189,28 +315,22 @@ pub async fn proxy_to_surge(
                 }
             }
             ProxyType::VMess => {
-                if surge_ver < 4 && surge_ver != -3 {
+                if !caps.vmess {
+                    ext.warnings.unsupported_node(node, caps.client_name());
                     continue;
                 }
 
                 _proxy = format!(
                     "vmess, {}, {}, username={}, tls={}, vmess-aead={}",
-                    hostname,
+                    server,
                     port,
                     id,
                     if tls_secure { "true" } else { "false" },
                     if node.alter_id == 0 { "true" } else { "false" }
                 );
 
-                if tls_secure && !tls13.is_undef() {
-                    _proxy.push_str(&format!(
-                        ", tls13={}",
-                        if tls13.unwrap_or(false) {
-                            "true"
-                        } else {
-                            "false"
-                        }
-                    ));
+                if tls_secure && caps.tls13 {
+                    push_tls13(&mut _proxy, tls13);
                 }
 
                 match transproto {
@@ -252,7 +372,8 @@ pub async fn proxy_to_surge(
                 }
             }
             ProxyType::ShadowsocksR => {
-                if ext.surge_ssr_path.is_empty() || surge_ver < 2 {
+                if ext.surge_ssr_path.is_empty() || !caps.external {
+                    ext.warnings.unsupported_node(node, caps.client_name());
                     continue;
                 }
 
@@ -289,7 +410,7 @@ pub async fn proxy_to_surge(
 
                 if is_ipv4(hostname) || is_ipv6(hostname) {
                     _proxy.push_str(&format!(", addresses={}", hostname));
-                } else if global.surge_resolve_hostname {
+                } else if ext.surge_resolve_hostname {
                     if let Some(ip) = hostname_to_ip_addr(hostname) {
                         _proxy.push_str(&format!(", addresses={}", ip));
                     }
@@ -298,7 +419,7 @@ pub async fn proxy_to_surge(
                 local_port += 1;
             }
             ProxyType::Socks5 => {
-                _proxy = format!("socks5, {}, {}", hostname, port);
+                _proxy = format!("socks5, {}, {}", server, port);
 
                 if !username.is_empty() {
                     _proxy.push_str(&format!(", username={}", username));
@@ -320,37 +441,28 @@ pub async fn proxy_to_surge(
                 }
             }
             ProxyType::HTTPS => {
-                if surge_ver == -3 {
-                    _proxy = format!("https, {}, {}, {}, {}", hostname, port, username, password);
-
-                    if scv.is_some() {
-                        _proxy.push_str(&format!(
-                            ", skip-cert-verify={}",
-                            if scv.unwrap_or(false) {
-                                "true"
-                            } else {
-                                "false"
-                            }
-                        ));
+                if caps.https_line {
+                    _proxy = format!("https, {}, {}, {}, {}", server, port, username, password);
+                } else {
+                    _proxy = format!("http, {}, {}", server, port);
+
+                    if !username.is_empty() {
+                        _proxy.push_str(&format!(", username={}", username));
                     }
-                    break;
-                }
-                // Fall through to HTTP case for non -3 versions
-                _proxy = format!("http, {}, {}", hostname, port);
 
-                if !username.is_empty() {
-                    _proxy.push_str(&format!(", username={}", username));
-                }
+                    if !password.is_empty() {
+                        _proxy.push_str(&format!(", password={}", password));
+                    }
 
-                if !password.is_empty() {
-                    _proxy.push_str(&format!(", password={}", password));
+                    _proxy.push_str(&format!(
+                        ", tls={}",
+                        if tls_secure { "true" } else { "false" }
+                    ));
+                    if tls_secure && caps.tls13 {
+                        push_tls13(&mut _proxy, tls13);
+                    }
                 }
 
-                _proxy.push_str(&format!(
-                    ", tls={}",
-                    if tls_secure { "true" } else { "false" }
-                ));
-
                 if scv.is_some() {
                     _proxy.push_str(&format!(
                         ", skip-cert-verify={}",
@@ -363,7 +475,7 @@ pub async fn proxy_to_surge(
                 }
             }
             ProxyType::HTTP => {
-                _proxy = format!("http, {}, {}", hostname, port);
+                _proxy = format!("http, {}, {}", server, port);
 
                 if !username.is_empty() {
                     _proxy.push_str(&format!(", username={}", username));
@@ -390,11 +502,12 @@ pub async fn proxy_to_surge(
                 }
             }
             ProxyType::Trojan => {
-                if surge_ver < 4 && surge_ver != -3 {
+                if !caps.trojan {
+                    ext.warnings.unsupported_node(node, caps.client_name());
                     continue;
                 }
 
-                _proxy = format!("trojan, {}, {}, password={}", hostname, port, password);
+                _proxy = format!("trojan, {}, {}, password={}", server, port, password);
 
                 if node.snell_version != 0 {
                     _proxy.push_str(&format!(", version={}", node.snell_version));
@@ -404,6 +517,10 @@ pub async fn proxy_to_surge(
                     _proxy.push_str(&format!(", sni={}", host));
                 }
 
+                if caps.tls13 {
+                    push_tls13(&mut _proxy, tls13);
+                }
+
                 if scv.is_some() {
                     _proxy.push_str(&format!(
                         ", skip-cert-verify={}",
@@ -416,7 +533,12 @@ pub async fn proxy_to_surge(
                 }
             }
             ProxyType::Snell => {
-                _proxy = format!("snell, {}, {}, psk={}", hostname, port, password);
+                if !caps.snell {
+                    ext.warnings.unsupported_node(node, caps.client_name());
+                    continue;
+                }
+
+                _proxy = format!("snell, {}, {}, psk={}", server, port, password);
 
                 if !obfs.is_empty() {
                     _proxy.push_str(&format!(", obfs={}", obfs));
@@ -429,9 +551,20 @@ pub async fn proxy_to_surge(
                 if node.snell_version != 0 {
                     _proxy.push_str(&format!(", version={}", node.snell_version));
                 }
+
+                // Options introduced with Snell v4
+                if node.snell_version >= 4 {
+                    if let Some(reuse) = node.reuse {
+                        _proxy.push_str(&format!(", reuse={}", reuse));
+                    }
+                    if let Some(ecn) = node.ecn {
+                        _proxy.push_str(&format!(", ecn={}", ecn));
+                    }
+                }
             }
             ProxyType::WireGuard => {
-                if surge_ver < 4 && surge_ver != -3 {
+                if !caps.wireguard {
+                    ext.warnings.unsupported_node(node, caps.client_name());
                     continue;
                 }
 
@@ -440,7 +573,7 @@ pub async fn proxy_to_surge(
                 _real_section = format!("WireGuard {}", _section);
                 _proxy = format!("wireguard, section-name={}", _section);
 
-                if let Some(test_url) = &node.test_url {
+                if let Some(test_url) = node.test_url.as_ref().filter(|_| caps.wireguard_test_url) {
                     if !test_url.is_empty() {
                         _proxy.push_str(&format!(", test-url={}", test_url));
                     }
@@ -493,11 +626,12 @@ pub async fn proxy_to_surge(
                 .unwrap_or(());
             }
             ProxyType::Hysteria2 => {
-                if surge_ver < 4 {
+                if !caps.hysteria2 {
+                    ext.warnings.unsupported_node(node, caps.client_name());
                     continue;
                 }
 
-                _proxy = format!("hysteria, {}, {}, password={}", hostname, port, password);
+                _proxy = format!("hysteria2, {}, {}, password={}", server, port, password);
 
                 if node.down_speed > 0 {
                     _proxy.push_str(&format!(", download-bandwidth={}", node.down_speed));
@@ -505,7 +639,7 @@ pub async fn proxy_to_surge(
 
                 if scv.is_some() {
                     _proxy.push_str(&format!(
-                        ",skip-cert-verify={}",
+                        ", skip-cert-verify={}",
                         if scv.unwrap_or(false) {
                             "true"
                         } else {
@@ -517,21 +651,24 @@ pub async fn proxy_to_surge(
                 if let Some(fingerprint) = &node.fingerprint {
                     if !fingerprint.is_empty() {
                         _proxy
-                            .push_str(&format!(",server-cert-fingerprint-sha256={}", fingerprint));
+                            .push_str(&format!(", server-cert-fingerprint-sha256={}", fingerprint));
                     }
                 }
 
                 if let Some(sni) = &node.sni {
                     if !sni.is_empty() {
-                        _proxy.push_str(&format!(",sni={}", sni));
+                        _proxy.push_str(&format!(", sni={}", sni));
                     }
                 }
             }
-            _ => continue,
+            _ => {
+                ext.warnings.unsupported_node(node, caps.client_name());
+                continue;
+            }
         }
 
         // Add common options
-        if !tfo.is_undef() {
+        if caps.tfo && !tfo.is_undef() {
             _proxy.push_str(&format!(
                 ", tfo={}",
                 if tfo.unwrap_or(false) {
@@ -553,20 +690,36 @@ pub async fn proxy_to_surge(
             ));
         }
 
-        if !underlying_proxy.is_empty() {
-            _proxy.push_str(&format!(", underlying-proxy={}", underlying_proxy));
+        if !ext.nodelist {
+            nodelist.push(node.clone());
         }
+        final_names
+            .entry(original_remark)
+            .or_insert_with(|| remark.clone());
+        lines.push((remark.clone(), _proxy, underlying_proxy.to_string()));
+        remarks_list.push(remark);
+    }
 
-        // Add to nodelist or INI
+    // Add to nodelist or INI, chained through the final name of the underlying proxy
+    for (remark, mut line, parent) in lines {
+        if !parent.is_empty() {
+            let parent = final_names.get(&parent).unwrap_or(&parent);
+            if !remarks_list.contains(parent)
+                && !extra_proxy_group.iter().any(|g| &g.name == parent)
+            {
+                warn!(
+                    "Underlying proxy '{}' of '{}' is not in the output",
+                    parent, remark
+                );
+            }
+            line.push_str(&format!(", underlying-proxy={}", parent));
+        }
         if ext.nodelist {
-            output_nodelist.push_str(&format!("{} = {}\n", remark, _proxy));
+            output_nodelist.push_str(&format!("{} = {}\n", remark, line));
         } else {
-            ini.set("{NONAME}", &format!("{} = {}", remark, _proxy), "")
+            ini.set("{NONAME}", "{NONAME}", &format!("{} = {}", remark, line))
                 .unwrap_or(());
-            nodelist.push(node.clone());
         }
-
-        remarks_list.push(remark);
     }
 
     if ext.nodelist {
@@ -577,42 +730,80 @@ pub async fn proxy_to_surge(
     ini.set_current_section("Proxy Group");
     ini.erase_section();
 
+    let group_names = group_names(extra_proxy_group);
     for group in extra_proxy_group {
+        let group_names = other_group_names(&group_names, &group.name);
         let mut filtered_nodelist = Vec::new();
         let mut _group_str = String::new();
 
         match group.group_type {
-            ProxyGroupType::Select
-            | ProxyGroupType::Smart
-            | ProxyGroupType::URLTest
-            | ProxyGroupType::Fallback => {
+            ProxyGroupType::Select | ProxyGroupType::URLTest | ProxyGroupType::Fallback => {
                 // These types are supported
             }
+            ProxyGroupType::Smart => {
+                if !caps.smart_group {
+                    warn!(
+                        "Writing smart group '{}' as url-test, {} has no smart groups",
+                        group.name,
+                        caps.client_name()
+                    );
+                }
+            }
             ProxyGroupType::LoadBalance => {
-                if surge_ver < 1 && surge_ver != -3 {
+                if !caps.load_balance {
                     continue;
                 }
             }
             ProxyGroupType::SSID => {
+                if !caps.ssid_group {
+                    warn!(
+                        "Skipping SSID group '{}', {} does not support it",
+                        group.name,
+                        caps.client_name()
+                    );
+                    continue;
+                }
+
                 _group_str = format!("{},default={},", group.type_str(), group.proxies[0]);
                 _group_str.push_str(&join(&group.proxies[1..], ","));
-                ini.set("{NONAME}", &format!("{} = {}", group.name, _group_str), "")
-                    .unwrap_or(());
+                ini.set(
+                    "{NONAME}",
+                    "{NONAME}",
+                    &format!("{} = {}", group.name, _group_str),
+                )
+                .unwrap_or(());
                 continue;
             }
             _ => continue,
         }
 
-        // Generate filtered proxy list
+        // Generate filtered proxy list, `[]name` of a known provider is a provider reference
+        let mut providers: Vec<&str> = group.using_provider.iter().map(String::as_str).collect();
         for proxy_name in &group.proxies {
-            group_generate(proxy_name, &nodelist, &mut filtered_nodelist, true, ext);
+            match proxy_name.strip_prefix("[]") {
+                Some(provider) if ext.provider_urls.contains_key(provider) => {
+                    if !providers.contains(&provider) {
+                        providers.push(provider);
+                    }
+                }
+                _ => group_generate(
+                    proxy_name,
+                    &nodelist,
+                    &group_names,
+                    &mut filtered_nodelist,
+                    true,
+                    ext,
+                ),
+            }
         }
+        apply_group_filters(group, &nodelist, &mut filtered_nodelist);
+        let policy_path = surge_policy_path(group, &providers, &caps, ext);
 
-        if filtered_nodelist.is_empty() {
-            filtered_nodelist.push("DIRECT".to_string());
+        if filtered_nodelist.is_empty() && policy_path.is_none() {
+            filtered_nodelist.push(ext.empty_group_placeholder.policy().to_string());
         }
 
-        if filtered_nodelist.len() == 1 {
+        if filtered_nodelist.len() == 1 && policy_path.is_none() {
             let proxy_name = to_lower(&filtered_nodelist[0]);
             match proxy_name.as_str() {
                 "direct" | "reject" | "reject-tinygif" => {
@@ -628,13 +819,22 @@ pub async fn proxy_to_surge(
             }
         }
 
-        // Build group string
-        _group_str = format!("{},", group.type_str());
-        _group_str.push_str(&join(&filtered_nodelist, ","));
+        // Build group string, smart groups fall back to url-test where they are unknown
+        let as_url_test = group.group_type == ProxyGroupType::Smart && !caps.smart_group;
+        _group_str = if as_url_test {
+            ProxyGroupType::URLTest.as_str().to_string()
+        } else {
+            group.type_str().to_string()
+        };
+        for policy in filtered_nodelist.iter().chain(policy_path.iter()) {
+            _group_str.push(',');
+            _group_str.push_str(policy);
+        }
 
         if group.group_type == ProxyGroupType::URLTest
             || group.group_type == ProxyGroupType::Fallback
             || group.group_type == ProxyGroupType::LoadBalance
+            || as_url_test
         {
             _group_str.push_str(&format!(",url={},interval={}", group.url, group.interval));
 
@@ -647,18 +847,22 @@ pub async fn proxy_to_surge(
             }
 
             // Handle persistent field directly
-            if group.persistent {
+            if caps.group_test_options && group.persistent {
                 _group_str.push_str(",persistent=true");
             }
 
             // Handle evaluate_before_use field directly
-            if group.evaluate_before_use {
+            if caps.group_test_options && group.evaluate_before_use {
                 _group_str.push_str(",evaluate-before-use=true");
             }
         }
 
-        ini.set("{NONAME}", &format!("{} = {}", group.name, _group_str), "")
-            .unwrap_or(());
+        ini.set(
+            "{NONAME}",
+            "{NONAME}",
+            &format!("{} = {}", group.name, _group_str),
+        )
+        .unwrap_or(());
     }
 
     // Generate rules if enabled
@@ -669,9 +873,390 @@ pub async fn proxy_to_surge(
             surge_ver,
             ext.overwrite_original_rules,
             &ext.managed_config_prefix,
+            ext.max_allowed_rules,
         )
         .await;
     }
 
     ini.to_string()
 }
+
+/// The `policy-path` and `update-interval` of a group referencing providers
+///
+/// Only Surge 3 and later and Surfboard load policies from a path, and a group
+/// takes a single path, so further providers and providers without a known URL
+/// are skipped.
+fn surge_policy_path(
+    group: &ProxyGroupConfig,
+    providers: &[&str],
+    caps: &SurgeCapabilities,
+    ext: &ExtraSettings,
+) -> Option<String> {
+    let (first, rest) = providers.split_first()?;
+    if !caps.policy_path {
+        warn!(
+            "Proxy group '{}' uses providers which this {} version does not support",
+            group.name,
+            caps.client_name()
+        );
+        return None;
+    }
+    if !rest.is_empty() {
+        warn!(
+            "Proxy group '{}' uses several providers, only '{}' is written as policy-path",
+            group.name, first
+        );
+    }
+    let Some(url) = ext.provider_urls.get(*first) else {
+        warn!(
+            "Proxy group '{}' uses provider '{}' which has no node list URL",
+            group.name, first
+        );
+        return None;
+    };
+
+    let mut policy_path = format!("policy-path={}", url);
+    if ext.provider_update_interval > 0 {
+        policy_path.push_str(&format!(
+            ",update-interval={}",
+            ext.provider_update_interval
+        ));
+    }
+    Some(policy_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ipv6_server() {
+        let mut nodes = vec![Proxy::ss_construct(
+            "Provider",
+            "SS v6",
+            "2001:db8::1",
+            8388,
+            "password",
+            "aes-128-gcm",
+            "",
+            "",
+            None,
+            None,
+            None,
+            None,
+            "",
+        )];
+        let mut ext = ExtraSettings::default();
+        ext.nodelist = true;
+        let output = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(proxy_to_surge(
+                &mut nodes,
+                "",
+                &mut Vec::new(),
+                &Vec::new(),
+                4,
+                &mut ext,
+            ));
+
+        assert!(output.contains(
+            "SS v6 = ss, [2001:db8::1], 8388, encrypt-method=aes-128-gcm, password=password"
+        ));
+    }
+
+    #[test]
+    fn test_underlying_proxy_follows_renamed_parent() {
+        let ss = |remark: &str| {
+            Proxy::ss_construct(
+                "Provider",
+                remark,
+                "1.2.3.4",
+                8388,
+                "password",
+                "aes-128-gcm",
+                "",
+                "",
+                None,
+                None,
+                None,
+                None,
+                "",
+            )
+        };
+        // The chained node comes first, before the name of its parent is known
+        let mut exit = ss("US Exit");
+        exit.underlying_proxy = Some("HK Relay".to_string());
+        let mut nodes = vec![exit, ss("HK Relay")];
+        let mut ext = ExtraSettings::default();
+        ext.nodelist = true;
+        ext.append_proxy_type = true;
+        let output = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(proxy_to_surge(
+                &mut nodes,
+                "",
+                &mut Vec::new(),
+                &Vec::new(),
+                4,
+                &mut ext,
+            ));
+
+        let exit_line = output.lines().next().unwrap();
+        assert!(exit_line.starts_with("[SS] US Exit = ss"));
+        assert!(exit_line.ends_with(", underlying-proxy=[SS] HK Relay"));
+        assert!(!output.lines().nth(1).unwrap().contains("underlying-proxy"));
+    }
+
+    #[test]
+    fn test_snell_v4() {
+        let mut node = Proxy::snell_construct(
+            "Provider".to_string(),
+            "Snell v4".to_string(),
+            "1.2.3.4".to_string(),
+            443,
+            "psk".to_string(),
+            "http".to_string(),
+            "example.com".to_string(),
+            4,
+            None,
+            None,
+            None,
+            None,
+        );
+        node.reuse = Some(true);
+        let mut ext = ExtraSettings::default();
+        ext.nodelist = true;
+        let output = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(proxy_to_surge(
+                &mut vec![node],
+                "",
+                &mut Vec::new(),
+                &Vec::new(),
+                4,
+                &mut ext,
+            ));
+
+        assert!(output.contains(
+            "Snell v4 = snell, 1.2.3.4, 443, psk=psk, obfs=http, obfs-host=example.com, version=4, reuse=true"
+        ));
+    }
+
+    #[test]
+    fn test_policy_path_groups() {
+        let mut nodes = vec![Proxy::ss_construct(
+            "Provider",
+            "HK 1",
+            "1.2.3.4",
+            8388,
+            "password",
+            "aes-128-gcm",
+            "",
+            "",
+            None,
+            None,
+            None,
+            None,
+            "",
+        )];
+        let groups = vec![
+            ProxyGroupConfig {
+                name: "Proxy".to_string(),
+                proxies: vec![".*".to_string(), "[]Remote".to_string()],
+                ..Default::default()
+            },
+            ProxyGroupConfig {
+                name: "Auto".to_string(),
+                group_type: ProxyGroupType::URLTest,
+                using_provider: vec!["Remote".to_string()],
+                url: "http://www.gstatic.com/generate_204".to_string(),
+                interval: 300,
+                ..Default::default()
+            },
+        ];
+        let mut ext = ExtraSettings {
+            enable_rule_generator: false,
+            provider_update_interval: 86400,
+            ..Default::default()
+        };
+        ext.provider_urls.insert(
+            "Remote".to_string(),
+            "https://example.com/remote.list".to_string(),
+        );
+        let output = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(proxy_to_surge(
+                &mut nodes,
+                "[General]\nloglevel = notify\n",
+                &mut Vec::new(),
+                &groups,
+                4,
+                &mut ext,
+            ));
+
+        assert!(output.contains(
+            "Proxy = select,HK 1,policy-path=https://example.com/remote.list,update-interval=86400"
+        ));
+        assert!(output.contains(
+            "Auto = url-test,policy-path=https://example.com/remote.list,update-interval=86400,url=http://www.gstatic.com/generate_204,interval=300"
+        ));
+    }
+
+    fn surge_and_surfboard_output(nodes: &[Proxy], groups: &ProxyGroupConfigs) -> (String, String) {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let mut outputs = [4, SURFBOARD_VERSION].map(|surge_ver| {
+            let mut ext = ExtraSettings {
+                enable_rule_generator: false,
+                tfo: Some(true),
+                ..Default::default()
+            };
+            runtime.block_on(proxy_to_surge(
+                &mut nodes.to_vec(),
+                "[General]\nloglevel = notify\n",
+                &mut Vec::new(),
+                groups,
+                surge_ver,
+                &mut ext,
+            ))
+        });
+        let surfboard = std::mem::take(&mut outputs[1]);
+        (std::mem::take(&mut outputs[0]), surfboard)
+    }
+
+    #[test]
+    fn test_surfboard_capabilities() {
+        let snell = Proxy::snell_construct(
+            "Provider".to_string(),
+            "Snell".to_string(),
+            "1.2.3.4".to_string(),
+            443,
+            "psk".to_string(),
+            "".to_string(),
+            "".to_string(),
+            4,
+            None,
+            None,
+            None,
+            None,
+        );
+        let wireguard = Proxy::wireguard_construct(
+            "Provider".to_string(),
+            "WG".to_string(),
+            "5.6.7.8".to_string(),
+            51820,
+            "10.0.0.2".to_string(),
+            "".to_string(),
+            "private".to_string(),
+            "public".to_string(),
+            "".to_string(),
+            vec!["1.1.1.1".to_string()],
+            None,
+            None,
+            "http://www.gstatic.com/generate_204".to_string(),
+            "".to_string(),
+            None,
+            None,
+        );
+        let ss_2022 = Proxy::ss_construct(
+            "Provider",
+            "SS 2022",
+            "9.9.9.9",
+            8388,
+            "password",
+            "2022-blake3-aes-128-gcm",
+            "",
+            "",
+            None,
+            None,
+            None,
+            None,
+            "",
+        );
+        let groups = vec![ProxyGroupConfig {
+            name: "Auto".to_string(),
+            group_type: ProxyGroupType::Smart,
+            proxies: vec![".*".to_string()],
+            url: "http://www.gstatic.com/generate_204".to_string(),
+            interval: 300,
+            persistent: true,
+            ..Default::default()
+        }];
+
+        let (surge, surfboard) = surge_and_surfboard_output(&[snell, wireguard, ss_2022], &groups);
+
+        assert!(surge.contains("Snell = snell, 1.2.3.4, 443, psk=psk, version=4, tfo=true"));
+        assert!(
+            surge.contains("SS 2022 = ss, 9.9.9.9, 8388, encrypt-method=2022-blake3-aes-128-gcm")
+        );
+        assert!(surge.contains("test-url=http://www.gstatic.com/generate_204"));
+        assert!(surge.contains("Auto = smart,Snell,WG,SS 2022"));
+
+        assert!(!surfboard.contains("snell"));
+        assert!(!surfboard.contains("2022-blake3"));
+        assert!(!surfboard.contains("test-url"));
+        assert!(!surfboard.contains("tfo="));
+        assert!(surfboard.contains("peer=(public-key = public, endpoint = 5.6.7.8:51820"));
+        assert!(surfboard
+            .contains("Auto = url-test,WG,url=http://www.gstatic.com/generate_204,interval=300"));
+        assert!(!surfboard.contains("persistent"));
+    }
+
+    #[test]
+    fn test_tls13() {
+        let trojan = Proxy {
+            proxy_type: ProxyType::Trojan,
+            remark: "Trojan".to_string(),
+            hostname: "1.2.3.4".to_string(),
+            port: 443,
+            password: Some("secret".to_string()),
+            tls_secure: true,
+            ..Default::default()
+        };
+        let mut pinned = trojan.clone();
+        pinned.remark = "Pinned".to_string();
+        pinned.tls13 = Some(false);
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let output_with = |tls13: Option<bool>, surge_ver: i32| {
+            let mut ext = ExtraSettings {
+                nodelist: true,
+                tls13,
+                ..Default::default()
+            };
+            runtime.block_on(proxy_to_surge(
+                &mut vec![trojan.clone(), pinned.clone()],
+                "",
+                &mut Vec::new(),
+                &Vec::new(),
+                surge_ver,
+                &mut ext,
+            ))
+        };
+
+        let enabled = output_with(Some(true), 4);
+        assert!(enabled.contains("Trojan = trojan, 1.2.3.4, 443, password=secret, tls13=true"));
+        // The node overrides the request
+        assert!(enabled.contains("Pinned = trojan, 1.2.3.4, 443, password=secret, tls13=false"));
+
+        let disabled = output_with(Some(false), 4);
+        assert!(disabled.contains("Trojan = trojan, 1.2.3.4, 443, password=secret, tls13=false"));
+
+        let undefined = output_with(None, 4);
+        assert!(undefined.contains("Trojan = trojan, 1.2.3.4, 443, password=secret\n"));
+
+        assert!(!output_with(Some(true), SURFBOARD_VERSION).contains("tls13"));
+    }
+}
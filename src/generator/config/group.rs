@@ -3,10 +3,10 @@
 //! This module provides functionality for generating proxy groups.
 
 use crate::{
-    models::ExtraSettings,
+    models::{ExtraSettings, ProxyGroupConfig, ProxyGroupConfigs},
     utils::{
         matcher::{apply_matcher, reg_find},
-        starts_with,
+        pin_rank, starts_with,
     },
     Proxy,
 };
@@ -15,10 +15,14 @@ use crate::{
 ///
 /// # Arguments
 ///
+/// A rule naming one of `group_names` exactly refers to that group, like the
+/// same name prefixed with `[]` does.
+///
 /// * `rule` - The rule to apply to filter nodes
 /// * `nodelist` - List of all available proxy nodes
+/// * `group_names` - Names of the proxy groups of the configuration
 /// * `filtered_nodelist` - Output parameter that will contain the filtered node list
-/// * `add_direct` - Whether to add direct connection to the list
+/// * `add_direct` - Whether `[]` references and group names are added to the list
 /// * `ext` - Extra settings
 ///
 /// # Returns
@@ -27,26 +31,119 @@ use crate::{
 pub fn group_generate(
     rule: &str,
     nodelist: &[Proxy],
+    group_names: &[String],
     filtered_nodelist: &mut Vec<String>,
     add_direct: bool,
     ext: &ExtraSettings,
 ) {
     // Rule parsing
-    if starts_with(rule, "[]") && add_direct {
-        filtered_nodelist.push(rule[2..].to_string());
+    if starts_with(rule, "[]") {
+        if add_direct {
+            filtered_nodelist.push(rule[2..].to_string());
+        }
+    } else if group_names.iter().any(|name| name == rule) {
+        if add_direct {
+            filtered_nodelist.push(rule.to_string());
+        }
     } else if starts_with(rule, "script:") && ext.authorized {
         // TODO: javascript
     } else {
         // Include only nodes that match the rule
+        let mut matched = Vec::new();
         for node in nodelist {
             let mut real_rule = String::new();
             if apply_matcher(rule, &mut real_rule, node) {
                 if real_rule.is_empty() || reg_find(&node.remark, &real_rule) {
-                    filtered_nodelist.push(node.remark.clone());
+                    matched.push(node);
                 }
             }
         }
+        // Order the matches the way the node list itself is pinned, nodes of
+        // insert URLs leading the list keep leading
+        if !ext.pin_regex.is_empty() {
+            let inserted_first = nodelist.first().map_or(false, |node| node.group_id < 0);
+            matched.sort_by_key(|node| pin_rank(node, &ext.pin_regex, inserted_first));
+        }
+        filtered_nodelist.extend(matched.into_iter().map(|node| node.remark.clone()));
+    }
+}
+
+/// Names of the proxy groups, to resolve references between groups
+pub fn group_names(groups: &ProxyGroupConfigs) -> Vec<String> {
+    groups.iter().map(|group| group.name.clone()).collect()
+}
+
+/// Names of the groups `group` may refer to, every group but itself
+///
+/// A group listing itself would form a loop, so a rule naming the group
+/// matches node remarks instead.
+pub fn other_group_names(group_names: &[String], group: &str) -> Vec<String> {
+    group_names
+        .iter()
+        .filter(|name| name.as_str() != group)
+        .cloned()
+        .collect()
+}
+
+/// Members of a group: every rule through [`group_generate`], then the group filters
+///
+/// Groups, policies and `[]` references are part of the members, the same way
+/// the generators list them.
+pub fn group_members(
+    group: &ProxyGroupConfig,
+    nodelist: &[Proxy],
+    group_names: &[String],
+    ext: &ExtraSettings,
+) -> Vec<String> {
+    let group_names = other_group_names(group_names, &group.name);
+    let mut members = Vec::new();
+    for rule in &group.proxies {
+        group_generate(rule, nodelist, &group_names, &mut members, true, ext);
     }
+    apply_group_filters(group, nodelist, &mut members);
+    members
+}
+
+/// Names of the groups none of whose rules match a node, group or policy
+///
+/// Groups loading nodes from a provider are never empty.
+pub fn empty_groups(
+    groups: &ProxyGroupConfigs,
+    nodelist: &[Proxy],
+    ext: &ExtraSettings,
+) -> Vec<String> {
+    let names = group_names(groups);
+    groups
+        .iter()
+        .filter(|group| group.using_provider.is_empty())
+        .filter(|group| group_members(group, nodelist, &names, ext).is_empty())
+        .map(|group| group.name.clone())
+        .collect()
+}
+
+/// Applies the `include_filter` and `exclude_filter` of a group to its members
+///
+/// Runs after every rule of the group went through [`group_generate`]. Only
+/// members naming a node are filtered, other groups and policies such as
+/// `DIRECT` are kept as they are.
+pub fn apply_group_filters(
+    group: &ProxyGroupConfig,
+    nodelist: &[Proxy],
+    filtered_nodelist: &mut Vec<String>,
+) {
+    let include = group.include_filter.as_deref().filter(|s| !s.is_empty());
+    let exclude = group.exclude_filter.as_deref().filter(|s| !s.is_empty());
+    if include.is_none() && exclude.is_none() {
+        return;
+    }
+
+    filtered_nodelist.retain(|name| {
+        if !nodelist.iter().any(|node| &node.remark == name) {
+            return true;
+        }
+        include.map_or(true, |rule| reg_find(name, rule))
+            && !exclude.map_or(false, |rule| reg_find(name, rule))
+    });
 }
 
 #[cfg(test)]
@@ -108,7 +205,7 @@ mod tests {
         let ext = ExtraSettings::default();
 
         // 测试直接字符串规则
-        group_generate("[]DIRECT", &nodes, &mut filtered, true, &ext);
+        group_generate("[]DIRECT", &nodes, &[], &mut filtered, true, &ext);
 
         assert_eq!(filtered.len(), 1);
         assert_eq!(filtered[0], "DIRECT");
@@ -121,7 +218,7 @@ mod tests {
         let ext = ExtraSettings::default();
 
         // 测试组匹配规则
-        group_generate("!!GROUP=HK", &nodes, &mut filtered, true, &ext);
+        group_generate("!!GROUP=HK", &nodes, &[], &mut filtered, true, &ext);
 
         assert_eq!(filtered.len(), 2);
         assert!(filtered.contains(&"HK Node 1".to_string()));
@@ -135,7 +232,7 @@ mod tests {
         let ext = ExtraSettings::default();
 
         // 测试类型匹配规则
-        group_generate("!!TYPE=VMESS", &nodes, &mut filtered, true, &ext);
+        group_generate("!!TYPE=VMESS", &nodes, &[], &mut filtered, true, &ext);
 
         assert_eq!(filtered.len(), 1);
         assert_eq!(filtered[0], "JP Node 1");
@@ -148,7 +245,7 @@ mod tests {
         let ext = ExtraSettings::default();
 
         // 测试端口匹配规则
-        group_generate("!!PORT=443", &nodes, &mut filtered, true, &ext);
+        group_generate("!!PORT=443", &nodes, &[], &mut filtered, true, &ext);
 
         assert_eq!(filtered.len(), 2);
         assert!(filtered.contains(&"HK Node 1".to_string()));
@@ -161,11 +258,10 @@ mod tests {
         let mut filtered = Vec::new();
         let ext = ExtraSettings::default();
 
-        // 测试没有匹配节点时添加 DIRECT
-        group_generate("!!GROUP=SG", &nodes, &mut filtered, true, &ext);
+        // 没有匹配节点时不添加任何成员，空分组由生成器填充
+        group_generate("!!GROUP=SG", &nodes, &[], &mut filtered, true, &ext);
 
-        assert_eq!(filtered.len(), 1);
-        assert_eq!(filtered[0], "DIRECT");
+        assert!(filtered.is_empty());
     }
 
     #[test]
@@ -175,7 +271,7 @@ mod tests {
         let ext = ExtraSettings::default();
 
         // 测试没有匹配节点且不添加 DIRECT
-        group_generate("!!GROUP=SG", &nodes, &mut filtered, false, &ext);
+        group_generate("!!GROUP=SG", &nodes, &[], &mut filtered, false, &ext);
 
         assert_eq!(filtered.len(), 0);
     }
@@ -187,7 +283,7 @@ mod tests {
         let ext = ExtraSettings::default();
 
         // 测试正则表达式匹配
-        group_generate("Node \\d", &nodes, &mut filtered, true, &ext);
+        group_generate("Node \\d", &nodes, &[], &mut filtered, true, &ext);
 
         assert_eq!(filtered.len(), 4);
         assert!(filtered.contains(&"HK Node 1".to_string()));
@@ -195,4 +291,114 @@ mod tests {
         assert!(filtered.contains(&"JP Node 1".to_string()));
         assert!(filtered.contains(&"US Node 1".to_string()));
     }
+
+    #[test]
+    fn test_group_generate_pinned_first() {
+        let nodes = create_test_nodes();
+        let mut filtered = Vec::new();
+        let mut ext = ExtraSettings::default();
+        ext.pin_regex = "^(JP|US)".to_string();
+
+        group_generate("Node \\d", &nodes, &[], &mut filtered, true, &ext);
+
+        assert_eq!(
+            filtered,
+            ["JP Node 1", "US Node 1", "HK Node 1", "HK Node 2"]
+        );
+    }
+
+    #[test]
+    fn test_group_exclude_filter() {
+        let mut nodes = create_test_nodes();
+        nodes[1].remark = "HK Node x0.1".to_string();
+        let ext = ExtraSettings::default();
+        let mut group = ProxyGroupConfig {
+            proxies: vec!["HK|JP".to_string(), "[]DIRECT".to_string()],
+            exclude_filter: Some("x0\\.1".to_string()),
+            ..Default::default()
+        };
+
+        let mut filtered = Vec::new();
+        for rule in &group.proxies {
+            group_generate(rule, &nodes, &[], &mut filtered, true, &ext);
+        }
+        assert_eq!(filtered.len(), 4);
+
+        apply_group_filters(&group, &nodes, &mut filtered);
+        assert_eq!(filtered, vec!["HK Node 1", "JP Node 1", "DIRECT"]);
+
+        // The include filter narrows the members further
+        group.include_filter = Some("^HK".to_string());
+        apply_group_filters(&group, &nodes, &mut filtered);
+        assert_eq!(filtered, vec!["HK Node 1", "DIRECT"]);
+    }
+
+    #[test]
+    fn test_group_references_and_empty_groups() {
+        let nodes = create_test_nodes();
+        let ext = ExtraSettings::default();
+        let groups = vec![
+            ProxyGroupConfig {
+                name: "Auto".to_string(),
+                proxies: vec!["^HK".to_string()],
+                ..Default::default()
+            },
+            ProxyGroupConfig {
+                name: "Proxy".to_string(),
+                proxies: vec!["Auto".to_string(), "[]Empty".to_string(), "US".to_string()],
+                ..Default::default()
+            },
+            ProxyGroupConfig {
+                name: "Empty".to_string(),
+                proxies: vec!["!!GROUP=SG".to_string()],
+                ..Default::default()
+            },
+        ];
+        let names = group_names(&groups);
+
+        // Group names resolve with or without the `[]` prefix, other rules still match remarks
+        let mut filtered = Vec::new();
+        for rule in &groups[1].proxies {
+            group_generate(rule, &nodes, &names, &mut filtered, true, &ext);
+        }
+        assert_eq!(filtered, vec!["Auto", "Empty", "US Node 1"]);
+
+        assert_eq!(empty_groups(&groups, &nodes, &ext), vec!["Empty"]);
+    }
+
+    #[test]
+    fn test_group_does_not_reference_itself() {
+        let nodes = vec![
+            Proxy {
+                remark: "HK 01".to_string(),
+                ..Default::default()
+            },
+            Proxy {
+                remark: "US 01".to_string(),
+                ..Default::default()
+            },
+        ];
+        let ext = ExtraSettings::default();
+        let groups = vec![
+            ProxyGroupConfig {
+                name: "HK".to_string(),
+                proxies: vec!["HK".to_string()],
+                ..Default::default()
+            },
+            ProxyGroupConfig {
+                name: "Proxy".to_string(),
+                proxies: vec!["HK".to_string()],
+                ..Default::default()
+            },
+        ];
+        let names = group_names(&groups);
+
+        // The rule of `HK` naming itself matches nodes, `Proxy` still refers to the group
+        assert_eq!(
+            group_members(&groups[0], &nodes, &names, &ext),
+            vec!["HK 01"]
+        );
+        assert_eq!(group_members(&groups[1], &nodes, &names, &ext), vec!["HK"]);
+        assert!(empty_groups(&groups, &nodes, &ext).is_empty());
+    }
 }
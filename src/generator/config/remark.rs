@@ -4,6 +4,7 @@
 
 use lazy_static::lazy_static;
 use regex::Regex;
+use unicode_segmentation::UnicodeSegmentation;
 
 /// Processes a remark string according to a list of remark rules
 ///
@@ -12,14 +13,25 @@ use regex::Regex;
 /// * `remark` - The remark to process (will be modified in-place)
 /// * `remarks_list` - List of already processed remarks to avoid duplicates
 /// * `proc_comma` - Whether to process comma replacements
+/// * `max_length` - Maximum number of characters, duplicate suffixes included
 ///
 /// # Returns
 ///
 /// Nothing, modifies the remark in-place
-pub fn process_remark(remark: &mut String, remarks_list: &Vec<String>, proc_comma: bool) {
+pub fn process_remark(
+    remark: &mut String,
+    remarks_list: &Vec<String>,
+    proc_comma: bool,
+    max_length: Option<usize>,
+) {
     // Replace every '=' with '-' in the remark string to avoid parse errors from clients
     *remark = remark.replace('=', "-");
 
+    // Truncate before checking for duplicates so that collisions still get a suffix
+    if let Some(max_length) = max_length {
+        *remark = truncate_remark(remark, max_length);
+    }
+
     if proc_comma {
         // If the remark contains a comma, wrap it in quotes
         if remark.contains(',') {
@@ -31,7 +43,15 @@ pub fn process_remark(remark: &mut String, remarks_list: &Vec<String>, proc_comm
     let mut temp_remark = remark.clone();
     let mut cnt = 2;
     while remarks_list.contains(&temp_remark) {
-        temp_remark = format!("{} {}", remark, cnt);
+        let suffix = format!(" {}", cnt);
+        temp_remark = match max_length {
+            Some(max_length) => format!(
+                "{}{}",
+                truncate_remark(remark, max_length.saturating_sub(suffix.chars().count())),
+                suffix
+            ),
+            None => format!("{}{}", remark, suffix),
+        };
         cnt += 1;
     }
     *remark = temp_remark;
@@ -42,6 +62,40 @@ pub fn process_remark(remark: &mut String, remarks_list: &Vec<String>, proc_comm
     // process_filters(remark, remarks_list);
 }
 
+/// Shortens a remark to at most `max_length` characters
+///
+/// Characters are counted as user-perceived characters, so an emoji built from
+/// several code points (ZWJ sequences, flags, keycaps, skin tones) is never cut
+/// apart. A trailing numeric suffix like ` 2` is kept and the text before it is
+/// shortened instead.
+pub fn truncate_remark(remark: &str, max_length: usize) -> String {
+    let clusters = split_clusters(remark);
+    if clusters.len() <= max_length {
+        return remark.to_string();
+    }
+
+    if let Some((stem, number)) = remark.rsplit_once(' ') {
+        let suffix_length = number.chars().count() + 1;
+        if !stem.trim().is_empty()
+            && !number.is_empty()
+            && number.chars().all(|c| c.is_ascii_digit())
+            && suffix_length < max_length
+        {
+            let stem_clusters = split_clusters(stem);
+            let stem =
+                stem_clusters[..stem_clusters.len().min(max_length - suffix_length)].concat();
+            return format!("{} {}", stem.trim_end(), number);
+        }
+    }
+
+    clusters[..max_length].concat().trim_end().to_string()
+}
+
+/// Splits a string into user-perceived characters, its extended grapheme clusters
+fn split_clusters(text: &str) -> Vec<&str> {
+    text.graphemes(true).collect()
+}
+
 /// Process filters in the remark string
 pub fn process_filters(remark: &mut String, remarks_list: &Vec<String>) {
     lazy_static! {
@@ -101,3 +155,35 @@ pub fn process_filters(remark: &mut String, remarks_list: &Vec<String>) {
     *remark = MULTI_SPACE_REGEX.replace_all(remark, " ").to_string();
     *remark = remark.trim().to_string();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate_remark() {
+        assert_eq!(truncate_remark("香港 HKT 01", 20), "香港 HKT 01");
+        assert_eq!(truncate_remark("🇭🇰 香港高级线路", 4), "🇭🇰 香港");
+        assert_eq!(truncate_remark("🇭🇰 香港高级线路 2", 7), "🇭🇰 香港高 2");
+        assert_eq!(truncate_remark("👨‍👩‍👧 家庭节点", 1), "👨‍👩‍👧");
+        assert_eq!(truncate_remark("👍🏽👍🏽👍🏽", 2), "👍🏽👍🏽");
+        assert_eq!(truncate_remark("日本东京节点", 3), "日本东");
+        // Conjoining Hangul jamo form a single syllable
+        assert_eq!(
+            truncate_remark("\u{1100}\u{1161}\u{11A8}\u{1100}\u{1161}", 1),
+            "\u{1100}\u{1161}\u{11A8}"
+        );
+    }
+
+    #[test]
+    fn test_process_remark_dedup_after_truncation() {
+        let mut remarks_list = Vec::new();
+        for remark in ["🇯🇵 日本东京 A", "🇯🇵 日本东京 B", "🇯🇵 日本东京 C"]
+        {
+            let mut remark = remark.to_string();
+            process_remark(&mut remark, &remarks_list, false, Some(6));
+            remarks_list.push(remark);
+        }
+        assert_eq!(remarks_list, vec!["🇯🇵 日本东京", "🇯🇵 日本 2", "🇯🇵 日本 3"]);
+    }
+}
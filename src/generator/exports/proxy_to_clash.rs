@@ -1,10 +1,14 @@
-use crate::generator::config::group::group_generate;
+use crate::generator::config::group::{
+    apply_group_filters, group_generate, group_names, other_group_names,
+};
 use crate::generator::config::remark::process_remark;
-use crate::generator::ruleconvert::ruleset_to_clash_str;
-use crate::generator::yaml::clash::clash_output::ClashProxyOutput;
+use crate::generator::ruleconvert::{ruleset_to_clash_providers, ruleset_to_clash_str, RuleTarget};
+use crate::generator::yaml::clash::clash_output::{ClashProxyCommon, ClashProxyOutput};
+use crate::generator::yaml::clash::sanitize::sanitize_clash_yaml;
 use crate::generator::yaml::proxy_group_output::convert_proxy_groups;
+use crate::generator::yaml::style::to_styled_string;
 use crate::models::{ExtraSettings, Proxy, ProxyGroupConfigs, ProxyType, RulesetContent};
-use log::error;
+use log::{debug, error, warn};
 use serde_yaml::{self, Mapping, Sequence, Value as YamlValue};
 use std::collections::{HashMap, HashSet};
 
@@ -103,20 +107,33 @@ pub fn proxy_to_clash(
         ext,
     );
 
+    if ext.sanitize_output {
+        let fixes = sanitize_clash_yaml(&mut yaml_node);
+        if fixes > 0 {
+            debug!(
+                "Applied {} fixes to the generated Clash configuration",
+                fixes
+            );
+        }
+    }
+
     // If nodelist mode is enabled, just return the YAML node
     if ext.nodelist {
-        return match serde_yaml::to_string(&yaml_node) {
-            Ok(result) => result,
-            Err(_) => String::new(),
-        };
+        return render_clash_yaml(&yaml_node, ext);
+    }
+
+    if ext.profile_update_interval > 0 {
+        if let Some(map) = yaml_node.as_mapping_mut() {
+            map.insert(
+                YamlValue::String("profile-update-interval".to_string()),
+                YamlValue::Number(ext.profile_update_interval.into()),
+            );
+        }
     }
 
     // Handle rule generation if enabled
     if !ext.enable_rule_generator {
-        return match serde_yaml::to_string(&yaml_node) {
-            Ok(result) => result,
-            Err(_) => String::new(),
-        };
+        return render_clash_yaml(&yaml_node, ext);
     }
 
     // Handle managed config and clash script
@@ -146,12 +163,15 @@ pub fn proxy_to_clash(
             }
         }
 
-        // TODO: Implement renderClashScript
-        // For now, just return the YAML
-        return match serde_yaml::to_string(&yaml_node) {
-            Ok(result) => result,
-            Err(_) => String::new(),
-        };
+        // TODO: Implement clash script rendering, rulesets are served as rule providers for now
+        ruleset_to_clash_providers(
+            &mut yaml_node,
+            ruleset_content_array,
+            &ext.managed_config_prefix,
+            ext.overwrite_original_rules,
+            ext.clash_new_field_name,
+        );
+        return render_clash_yaml(&yaml_node, ext);
     }
 
     // Generate rules and return combined output
@@ -160,16 +180,104 @@ pub fn proxy_to_clash(
         ruleset_content_array,
         ext.overwrite_original_rules,
         ext.clash_new_field_name,
+        ext.max_allowed_rules,
+        if clash_r {
+            RuleTarget::Clash
+        } else {
+            RuleTarget::Meta
+        },
     );
 
-    let yaml_output = match serde_yaml::to_string(&yaml_node) {
-        Ok(result) => result,
-        Err(_) => String::new(),
-    };
+    let yaml_output = render_clash_yaml(&yaml_node, ext);
 
     format!("{}{}", yaml_output, rules_str)
 }
 
+/// Serialize the Clash YAML node using the configured proxies and proxy groups styles
+fn render_clash_yaml(yaml_node: &YamlValue, ext: &ExtraSettings) -> String {
+    let proxies_style = ext.clash_proxies_style.as_str();
+    let groups_style = ext.clash_proxy_groups_style.as_str();
+    to_styled_string(
+        yaml_node,
+        &[
+            ("proxies", proxies_style),
+            ("Proxy", proxies_style),
+            ("proxy-groups", groups_style),
+            ("Proxy Group", groups_style),
+        ],
+    )
+}
+
+/// Names of the providers defined under `key` in the base configuration
+fn base_provider_names(yaml_node: &YamlValue, key: &str) -> HashSet<String> {
+    match yaml_node.get(key) {
+        Some(YamlValue::Mapping(providers)) => providers
+            .keys()
+            .filter_map(|name| name.as_str().map(str::to_string))
+            .collect(),
+        _ => HashSet::new(),
+    }
+}
+
+/// Point the dialer proxies at the final names of their nodes
+///
+/// Names of no proxy or proxy group in the output are kept with a warning.
+fn follow_dialer_proxies(
+    proxies: &mut [ClashProxyOutput],
+    final_names: &HashMap<String, String>,
+    known_names: &HashSet<String>,
+) {
+    for proxy in proxies.iter_mut() {
+        let common = proxy.common_mut();
+        let Some(parent) = common.dialer_proxy.as_mut() else {
+            continue;
+        };
+        if let Some(name) = final_names.get(parent.as_str()) {
+            *parent = name.clone();
+        }
+        if !known_names.contains(parent.as_str()) {
+            warn!(
+                "Dialer proxy '{}' of '{}' is not in the output",
+                parent, common.name
+            );
+        }
+    }
+}
+
+/// The proxies as a YAML sequence, the verbatim ones last
+fn proxies_yaml(
+    proxies: &[ClashProxyOutput],
+    passthrough: Vec<YamlValue>,
+    clash_r: bool,
+) -> YamlValue {
+    let mut value = serde_yaml::to_value(proxies).unwrap_or(YamlValue::Sequence(Vec::new()));
+    if let YamlValue::Sequence(seq) = &mut value {
+        if clash_r {
+            seq.iter_mut().for_each(clashr_ssr_param_keys);
+        }
+        seq.extend(passthrough);
+    }
+    value
+}
+
+/// ClashR spells the SSR params `protocolparam` and `obfsparam`
+fn clashr_ssr_param_keys(proxy: &mut YamlValue) {
+    if proxy["type"] != "ssr" {
+        return;
+    }
+    let Some(map) = proxy.as_mapping_mut() else {
+        return;
+    };
+    for (key, clashr_key) in [
+        ("protocol-param", "protocolparam"),
+        ("obfs-param", "obfsparam"),
+    ] {
+        if let Some(value) = map.remove(key) {
+            map.insert(YamlValue::String(clashr_key.to_string()), value);
+        }
+    }
+}
+
 /// Convert proxies to Clash format with YAML node
 ///
 /// This function modifies a YAML node in place to add Clash configuration
@@ -190,16 +298,20 @@ pub fn proxy_to_clash_yaml(
     clash_r: bool,
     ext: &mut ExtraSettings,
 ) {
-    // Style settings - in C++ this is used to set serialization style but in Rust we have less control
-    // over the serialization format. We keep them for compatibility but their actual effect may differ.
-    let _proxy_block = ext.clash_proxies_style == "block";
-    let _proxy_compact = ext.clash_proxies_style == "compact";
-    let _group_block = ext.clash_proxy_groups_style == "block";
-    let _group_compact = ext.clash_proxy_groups_style == "compact";
+    // Providers of the base configuration can be referenced by groups without redefining them
+    let base_providers = base_provider_names(yaml_node, "proxy-providers");
+
+    // Proxies kept verbatim from a Clash subscription are for Clash.Meta only
+    if clash_r || !ext.preserve_unknown_proxies {
+        nodes.retain(|node| !node.is_passthrough());
+    }
 
     // Create JSON structure for the proxies
     let mut proxies_json = Vec::new();
+    let mut passthrough = Vec::new();
     let mut remarks_list = Vec::new();
+    // Final names by remark, to follow the chains of dialer proxies
+    let mut final_names: HashMap<String, String> = HashMap::new();
 
     // Process each node
     for node in nodes.iter_mut() {
@@ -208,12 +320,27 @@ pub fn proxy_to_clash_yaml(
 
         // Add proxy type prefix if enabled
         if ext.append_proxy_type {
-            remark = format!("[{}] {}", node.proxy_type.to_string(), remark);
+            remark = format!("[{}] {}", node.proxy_type, remark);
         }
 
         // Process remark with optional remarks list
-        process_remark(&mut remark, &remarks_list, false);
+        process_remark(&mut remark, &remarks_list, false, ext.max_remark_length);
         remarks_list.push(remark.clone());
+        final_names
+            .entry(node.remark.clone())
+            .or_insert_with(|| remark.clone());
+
+        // Written untouched after the other proxies, but for the name
+        if let Some(raw) = &node.raw_passthrough {
+            let mut raw = raw.clone();
+            raw.insert(
+                YamlValue::String("name".to_string()),
+                YamlValue::String(remark),
+            );
+            passthrough.push(YamlValue::Mapping(raw));
+            continue;
+        }
+
         // Check if this proxy type should be skipped
         let should_skip = match node.proxy_type {
             // Skip Snell v4+ if exists - exactly matching C++ behavior
@@ -241,22 +368,24 @@ pub fn proxy_to_clash_yaml(
             }
 
             // Skip unsupported proxy types
-            ProxyType::Unknown | ProxyType::HTTPS => true,
+            ProxyType::Unknown | ProxyType::HTTPS | ProxyType::Tuic => true,
 
             // Process all other types
             _ => false,
         };
 
         if should_skip {
+            ext.warnings
+                .unsupported_node(node, if clash_r { "ClashR" } else { "Clash" });
             continue;
         }
 
         // 创建代理副本，并应用所有必要的属性设置
-        let proxy_copy = node.clone().set_remark(remark).apply_default_values(
-            ext.udp,
-            ext.tfo,
-            ext.skip_cert_verify,
-        );
+        let proxy_copy = node
+            .clone()
+            .set_remark(remark)
+            .apply_default_values(ext.udp, ext.tfo, ext.skip_cert_verify)
+            .apply_dial_defaults(ext.interface.clone(), ext.routing_mark);
 
         // 使用 From trait 自动转换为 ClashProxyOutput
         let clash_proxy = ClashProxyOutput::from(proxy_copy);
@@ -265,10 +394,21 @@ pub fn proxy_to_clash_yaml(
         proxies_json.push(clash_proxy);
     }
 
+    let mut known_names: HashSet<String> = proxies_json
+        .iter()
+        .map(|proxy| proxy.common().name.clone())
+        .chain(
+            passthrough
+                .iter()
+                .filter_map(|proxy| proxy["name"].as_str().map(str::to_string)),
+        )
+        .collect();
+    known_names.extend(group_names(extra_proxy_group));
+    follow_dialer_proxies(&mut proxies_json, &final_names, &known_names);
+
     if ext.nodelist {
         let mut provider = YamlValue::Mapping(Mapping::new());
-        provider["proxies"] =
-            serde_yaml::to_value(&proxies_json).unwrap_or(YamlValue::Sequence(Vec::new()));
+        provider["proxies"] = proxies_yaml(&proxies_json, passthrough, clash_r);
         *yaml_node = provider;
         return;
     }
@@ -276,8 +416,7 @@ pub fn proxy_to_clash_yaml(
     // Update the YAML node with proxies
     if let Some(ref mut map) = yaml_node.as_mapping_mut() {
         // Convert JSON proxies array to YAML
-        let proxies_yaml_value =
-            serde_yaml::to_value(&proxies_json).unwrap_or(YamlValue::Sequence(Vec::new()));
+        let proxies_yaml_value = proxies_yaml(&proxies_json, passthrough, clash_r);
         if ext.clash_new_field_name {
             map.insert(YamlValue::String("proxies".to_string()), proxies_yaml_value);
         } else {
@@ -301,23 +440,50 @@ pub fn proxy_to_clash_yaml(
         };
 
         // Build filtered nodes map for each group
+        let mut groups = extra_proxy_group.clone();
         let mut filtered_nodes_map = HashMap::new();
-        for group in extra_proxy_group {
+        let group_names = group_names(&groups);
+        for group in groups.iter_mut() {
+            let group_names = other_group_names(&group_names, &group.name);
             let mut filtered_nodes = Vec::new();
             for proxy_name in &group.proxies {
-                group_generate(proxy_name, nodes, &mut filtered_nodes, true, ext);
+                // `[]name` of a base proxy provider goes to the `use` field
+                match proxy_name.strip_prefix("[]") {
+                    Some(provider) if base_providers.contains(provider) => {
+                        if !group.using_provider.iter().any(|used| used == provider) {
+                            group.using_provider.push(provider.to_string());
+                        }
+                    }
+                    _ => group_generate(
+                        proxy_name,
+                        nodes,
+                        &group_names,
+                        &mut filtered_nodes,
+                        true,
+                        ext,
+                    ),
+                }
+            }
+            apply_group_filters(group, nodes, &mut filtered_nodes);
+            for provider in &group.using_provider {
+                if !base_providers.contains(provider) {
+                    warn!(
+                        "Proxy group '{}' uses provider '{}' which is not defined in the base configuration",
+                        group.name, provider
+                    );
+                }
             }
 
             // Add DIRECT if empty
             if filtered_nodes.is_empty() && group.using_provider.is_empty() {
-                filtered_nodes.push("DIRECT".to_string());
+                filtered_nodes.push(ext.empty_group_placeholder.policy().to_string());
             }
 
             filtered_nodes_map.insert(group.name.clone(), filtered_nodes);
         }
 
         // Convert proxy groups using the new serialization
-        let clash_proxy_groups = convert_proxy_groups(extra_proxy_group, Some(&filtered_nodes_map));
+        let clash_proxy_groups = convert_proxy_groups(&groups, Some(&filtered_nodes_map));
 
         // Merge with existing groups
         for group in clash_proxy_groups {
@@ -366,3 +532,305 @@ pub fn proxy_to_clash_yaml(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ProxyGroupConfig, ProxyGroupType};
+
+    #[test]
+    fn test_group_uses_base_provider() {
+        let base = "proxy-providers:\n  airport:\n    type: http\n    url: https://example.com/provider.yaml\n    path: ./airport.yaml\n";
+        let mut nodes = vec![Proxy {
+            proxy_type: ProxyType::Shadowsocks,
+            remark: "HK 01".to_string(),
+            hostname: "1.2.3.4".to_string(),
+            port: 8388,
+            password: Some("password".to_string()),
+            encrypt_method: Some("aes-256-gcm".to_string()),
+            ..Default::default()
+        }];
+        let mut group = ProxyGroupConfig::new("Proxy".to_string(), ProxyGroupType::Select);
+        group.proxies = vec!["[]airport".to_string(), ".*".to_string()];
+
+        let mut ext = ExtraSettings::default();
+        ext.enable_rule_generator = false;
+        let output = proxy_to_clash(
+            &mut nodes,
+            base,
+            &mut Vec::new(),
+            &vec![group],
+            false,
+            &mut ext,
+        );
+
+        let yaml: YamlValue = serde_yaml::from_str(&output).unwrap();
+        assert_eq!(
+            yaml["proxy-providers"]["airport"]["url"],
+            "https://example.com/provider.yaml"
+        );
+        let group = &yaml["proxy-groups"][0];
+        assert_eq!(group["use"][0], "airport");
+        assert_eq!(group["proxies"].as_sequence().unwrap().len(), 1);
+        assert_eq!(group["proxies"][0], "HK 01");
+    }
+
+    #[test]
+    fn test_unknown_proxy_round_trip() {
+        let subscription = r#"
+proxies:
+  - {name: HK 01, type: ss, server: hk.example.com, port: 8388, cipher: aes-128-gcm, password: secret}
+  - {name: JP AnyTLS, type: anytls, server: jp.example.com, port: 443, password: secret, idle-session-timeout: 30}
+"#;
+        let nodes = crate::parser::explode_any(subscription).unwrap();
+        let mut group = ProxyGroupConfig::new("JP".to_string(), ProxyGroupType::Select);
+        group.proxies = vec!["JP".to_string()];
+
+        let convert = |preserve: bool, clash_r: bool| {
+            let mut ext = ExtraSettings::default();
+            ext.enable_rule_generator = false;
+            ext.preserve_unknown_proxies = preserve;
+            let output = proxy_to_clash(
+                &mut nodes.clone(),
+                "",
+                &mut Vec::new(),
+                &vec![group.clone()],
+                clash_r,
+                &mut ext,
+            );
+            serde_yaml::from_str::<YamlValue>(&output).unwrap()
+        };
+
+        let yaml = convert(true, false);
+        let proxies = yaml["proxies"].as_sequence().unwrap();
+        assert_eq!(proxies.len(), 2);
+        assert_eq!(proxies[1]["type"], "anytls");
+        assert_eq!(proxies[1]["name"], "JP AnyTLS");
+        assert_eq!(proxies[1]["idle-session-timeout"], 30);
+        assert_eq!(yaml["proxy-groups"][0]["proxies"][0], "JP AnyTLS");
+
+        for yaml in [convert(false, false), convert(true, true)] {
+            assert_eq!(yaml["proxies"].as_sequence().unwrap().len(), 1);
+            assert_eq!(yaml["proxy-groups"][0]["proxies"][0], "DIRECT");
+        }
+    }
+
+    #[test]
+    fn test_dialer_proxy_follows_renamed_parent() {
+        let subscription = r#"
+proxies:
+  - {name: HK Relay, type: ss, server: hk.example.com, port: 8388, cipher: aes-128-gcm, password: secret}
+  - {name: US Exit, type: ss, server: us.example.com, port: 8388, cipher: aes-128-gcm, password: secret, dialer-proxy: HK Relay}
+"#;
+        let mut nodes = crate::parser::explode_any(subscription).unwrap();
+        assert_eq!(nodes[1].underlying_proxy.as_deref(), Some("HK Relay"));
+
+        let mut ext = ExtraSettings::default();
+        ext.enable_rule_generator = false;
+        ext.append_proxy_type = true;
+        let output = proxy_to_clash(
+            &mut nodes,
+            "",
+            &mut Vec::new(),
+            &Vec::new(),
+            false,
+            &mut ext,
+        );
+        let yaml: YamlValue = serde_yaml::from_str(&output).unwrap();
+        assert_eq!(yaml["proxies"][0]["name"], "[SS] HK Relay");
+        assert!(yaml["proxies"][0].get("dialer-proxy").is_none());
+        assert_eq!(yaml["proxies"][1]["dialer-proxy"], "[SS] HK Relay");
+    }
+
+    #[test]
+    fn test_interface_and_routing_mark() {
+        let subscription = r#"
+proxies:
+  - {name: HK 01, type: ss, server: hk.example.com, port: 8388, cipher: aes-128-gcm, password: secret, interface-name: eth1, routing-mark: 255}
+  - {name: JP 01, type: trojan, server: jp.example.com, port: 443, password: secret}
+"#;
+        for new_field_name in [true, false] {
+            let mut nodes = crate::parser::explode_any(subscription).unwrap();
+            let mut ext = ExtraSettings::default();
+            ext.enable_rule_generator = false;
+            ext.clash_new_field_name = new_field_name;
+            ext.routing_mark = Some(6666);
+            let output = proxy_to_clash(
+                &mut nodes,
+                "",
+                &mut Vec::new(),
+                &Vec::new(),
+                false,
+                &mut ext,
+            );
+            let yaml: YamlValue = serde_yaml::from_str(&output).unwrap();
+            let proxies = if new_field_name {
+                &yaml["proxies"]
+            } else {
+                &yaml["Proxy"]
+            };
+            assert_eq!(proxies[0]["interface-name"], "eth1");
+            assert_eq!(proxies[0]["routing-mark"], 255);
+            assert!(proxies[1].get("interface-name").is_none());
+            assert_eq!(proxies[1]["routing-mark"], 6666);
+
+            // The output parses back to the same options
+            let reparsed = crate::parser::explode_any(&output).unwrap();
+            assert_eq!(reparsed[0].interface.as_deref(), Some("eth1"));
+            assert_eq!(reparsed[0].routing_mark, Some(255));
+            assert_eq!(reparsed[1].routing_mark, Some(6666));
+        }
+    }
+
+    #[test]
+    fn test_ssr_param_keys() {
+        let nodes = vec![Proxy::ssr_construct(
+            "Provider",
+            "SSR",
+            "1.2.3.4",
+            8388,
+            "auth_aes128_md5",
+            "aes-256-cfb",
+            "tls1.2_ticket_auth",
+            "password",
+            "cdn.example.com",
+            "1234:abcd",
+            None,
+            None,
+            None,
+            "",
+        )];
+        let convert = |clash_r: bool| {
+            let mut ext = ExtraSettings::default();
+            ext.enable_rule_generator = false;
+            ext.filter_deprecated = false;
+            let output = proxy_to_clash(
+                &mut nodes.clone(),
+                "",
+                &mut Vec::new(),
+                &Vec::new(),
+                clash_r,
+                &mut ext,
+            );
+            let yaml: YamlValue = serde_yaml::from_str(&output).unwrap();
+            yaml["proxies"][0].clone()
+        };
+
+        let clash = convert(false);
+        assert_eq!(clash["type"], "ssr");
+        assert_eq!(clash["protocol"], "auth_aes128_md5");
+        assert_eq!(clash["protocol-param"], "1234:abcd");
+        assert_eq!(clash["obfs"], "tls1.2_ticket_auth");
+        assert_eq!(clash["obfs-param"], "cdn.example.com");
+        assert!(clash.get("protocolparam").is_none());
+
+        let clashr = convert(true);
+        assert_eq!(clashr["protocolparam"], "1234:abcd");
+        assert_eq!(clashr["obfsparam"], "cdn.example.com");
+        assert!(clashr.get("protocol-param").is_none());
+        assert!(clashr.get("obfs-param").is_none());
+    }
+
+    #[test]
+    fn test_skip_snell_v4() {
+        let mut nodes = vec![Proxy::snell_construct(
+            "Provider".to_string(),
+            "Snell v4".to_string(),
+            "1.2.3.4".to_string(),
+            443,
+            "psk".to_string(),
+            String::new(),
+            String::new(),
+            4,
+            None,
+            None,
+            None,
+            None,
+        )];
+        let mut ext = ExtraSettings::default();
+        ext.nodelist = true;
+        let output = proxy_to_clash(
+            &mut nodes,
+            "",
+            &mut Vec::new(),
+            &Vec::new(),
+            false,
+            &mut ext,
+        );
+
+        assert!(!output.contains("Snell v4"));
+    }
+
+    #[test]
+    fn test_node_flags_take_precedence() {
+        let values = [Some(true), Some(false), None];
+        for node_value in values {
+            for ext_value in values {
+                let mut nodes = vec![Proxy {
+                    proxy_type: ProxyType::Trojan,
+                    remark: "Trojan".to_string(),
+                    hostname: "1.2.3.4".to_string(),
+                    port: 443,
+                    password: Some("password".to_string()),
+                    udp: node_value,
+                    tcp_fast_open: node_value,
+                    allow_insecure: node_value,
+                    ..Default::default()
+                }];
+                let mut ext = ExtraSettings {
+                    nodelist: true,
+                    udp: ext_value,
+                    tfo: ext_value,
+                    skip_cert_verify: ext_value,
+                    ..Default::default()
+                };
+                let output = proxy_to_clash(
+                    &mut nodes,
+                    "",
+                    &mut Vec::new(),
+                    &Vec::new(),
+                    false,
+                    &mut ext,
+                );
+
+                let yaml: YamlValue = serde_yaml::from_str(&output).unwrap();
+                let proxy = &yaml["proxies"][0];
+                let expected = node_value.or(ext_value);
+                for key in ["udp", "tfo", "skip-cert-verify"] {
+                    assert_eq!(
+                        proxy.get(key).and_then(|value| value.as_bool()),
+                        expected,
+                        "{} with node {:?} and ext {:?}",
+                        key,
+                        node_value,
+                        ext_value
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_vless_node_flags_kept() {
+        let source = "proxies:\n  - name: vless\n    type: vless\n    server: example.com\n    port: 443\n    uuid: 00000000-0000-0000-0000-000000000000\n    tls: true\n    udp: false\n    skip-cert-verify: false\n";
+        let mut nodes = crate::parser::yaml::clash::parse_clash_yaml(source).unwrap();
+        let mut ext = ExtraSettings {
+            nodelist: true,
+            udp: Some(true),
+            skip_cert_verify: Some(true),
+            ..Default::default()
+        };
+        let output = proxy_to_clash(
+            &mut nodes,
+            "",
+            &mut Vec::new(),
+            &Vec::new(),
+            false,
+            &mut ext,
+        );
+
+        let yaml: YamlValue = serde_yaml::from_str(&output).unwrap();
+        assert_eq!(yaml["proxies"][0]["udp"], false);
+        assert_eq!(yaml["proxies"][0]["skip-cert-verify"], false);
+    }
+}
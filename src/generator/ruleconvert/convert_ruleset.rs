@@ -2,7 +2,7 @@
 //!
 //! Converts proxy rule formats between Clash, Surge, and Quantumult X
 
-use crate::models::RulesetType;
+use crate::models::{RulesetContent, RulesetType};
 use crate::utils::network::is_ipv4;
 use crate::utils::string::ends_with;
 use regex::Regex;
@@ -166,3 +166,28 @@ pub fn convert_ruleset(content: &str, ruleset_type: RulesetType) -> String {
 
     output
 }
+
+/// Counts the rules a list of rulesets expands to
+///
+/// Empty lines and comments are skipped the same way the generators skip them.
+pub fn count_rules(ruleset_content_array: &[RulesetContent]) -> usize {
+    ruleset_content_array
+        .iter()
+        .map(|ruleset| {
+            let content = ruleset.get_rule_content();
+            if content.starts_with("[]") {
+                return 1;
+            }
+            convert_ruleset(&content, ruleset.rule_type)
+                .lines()
+                .map(str::trim)
+                .filter(|line| {
+                    !line.is_empty()
+                        && !line.starts_with(';')
+                        && !line.starts_with('#')
+                        && !line.starts_with("//")
+                })
+                .count()
+        })
+        .sum()
+}
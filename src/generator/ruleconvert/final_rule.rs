@@ -0,0 +1,255 @@
+//! Final rule of the written rules
+//!
+//! The rule catching what no other rule matched is `MATCH` in Clash and
+//! `FINAL` in Surge, Quantumult X and Loon, sing-box has `route.final`
+//! instead. Rulesets use either spelling, so the final rule of the merged
+//! rules is written again in the spelling of the target. Only the last final
+//! rule is kept and it is moved to the end, as the clients expect.
+
+use log::warn;
+
+use super::rule_matrix::RuleTarget;
+
+/// Whether `line` is a `MATCH` or `FINAL` rule, in any case
+pub fn is_final_rule(line: &str) -> bool {
+    let rule_type = line.split(',').next().unwrap_or_default().trim();
+    rule_type.eq_ignore_ascii_case("MATCH") || rule_type.eq_ignore_ascii_case("FINAL")
+}
+
+/// Name of the final rule in `target`, `None` for sing-box
+pub fn final_rule_type(target: RuleTarget) -> Option<&'static str> {
+    match target {
+        RuleTarget::Clash | RuleTarget::Meta => Some("MATCH"),
+        RuleTarget::Surge | RuleTarget::QuanX | RuleTarget::Loon => Some("FINAL"),
+        RuleTarget::SingBox => None,
+    }
+}
+
+/// Keep the last final rule of `rules` and write it last for `target`
+///
+/// Extra final rules are dropped with a warning. Clash takes no options
+/// after the policy, the other targets keep them, e.g. Surge `dns-failed`.
+pub fn normalize_final_rules(rules: &mut Vec<String>, target: RuleTarget) {
+    let mut finals = 0;
+    let mut last = None;
+    rules.retain(|rule| {
+        if !is_final_rule(rule) {
+            return true;
+        }
+        finals += 1;
+        last = Some(rule.clone());
+        false
+    });
+    let (Some(last), Some(rule_type)) = (last, final_rule_type(target)) else {
+        return;
+    };
+    if finals > 1 {
+        warn!(
+            "Keeping the last of {} final rules for {}: '{}'",
+            finals,
+            target.name(),
+            last
+        );
+    }
+
+    let mut parts = last.split(',').skip(1).map(str::trim);
+    let rule = match target {
+        RuleTarget::Clash | RuleTarget::Meta => match parts.next() {
+            Some(policy) => format!("{},{}", rule_type, policy),
+            None => rule_type.to_string(),
+        },
+        _ => std::iter::once(rule_type)
+            .chain(parts)
+            .collect::<Vec<_>>()
+            .join(","),
+    };
+    rules.push(rule);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::ruleconvert::{
+        ruleset_to_clash_providers, ruleset_to_clash_str, ruleset_to_sing_box, ruleset_to_surge,
+    };
+    use crate::models::RulesetContent;
+    use crate::utils::ini_reader::IniReader;
+    use serde_json::json;
+
+    fn inline(group: &str, rule: &str) -> RulesetContent {
+        let mut ruleset = RulesetContent::new("", group);
+        ruleset.set_rule_content(&format!("[]{}", rule));
+        ruleset
+    }
+
+    /// A ruleset file ending with a final rule, then inline final rules
+    fn rulesets(spelling: &str) -> Vec<RulesetContent> {
+        let mut list = RulesetContent::new("rules.list", "Proxy");
+        list.set_rule_content(&format!("DOMAIN-SUFFIX,example.com\n{}\n", spelling));
+        vec![
+            list,
+            inline("DIRECT", &spelling.to_lowercase()),
+            inline("Direct", "GEOIP,CN"),
+            inline("Final", spelling),
+        ]
+    }
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    fn surge_rules(rulesets: &[RulesetContent], surge_ver: i32, section: &str) -> Vec<String> {
+        let mut ini = IniReader::new();
+        block_on(ruleset_to_surge(&mut ini, rulesets, surge_ver, true, "", 0));
+        ini.get_items(section)
+            .unwrap()
+            .into_iter()
+            .map(|(_, rule)| rule)
+            .collect()
+    }
+
+    fn clash_rules(rulesets: &[RulesetContent]) -> Vec<String> {
+        let output = ruleset_to_clash_str(
+            &serde_yaml::Value::Null,
+            rulesets,
+            true,
+            true,
+            0,
+            RuleTarget::Clash,
+        );
+        output
+            .lines()
+            .filter_map(|line| line.strip_prefix("  - "))
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn test_final_rule_by_target() {
+        for spelling in ["MATCH", "FINAL"] {
+            let rulesets = rulesets(spelling);
+
+            for (target, rules, expected) in [
+                ("clash", clash_rules(&rulesets), "MATCH,Final"),
+                ("surge", surge_rules(&rulesets, 4, "Rule"), "FINAL,Final"),
+                (
+                    "quanx",
+                    surge_rules(&rulesets, -1, "filter_local"),
+                    "FINAL,Final",
+                ),
+                ("loon", surge_rules(&rulesets, -4, "Rule"), "FINAL,Final"),
+            ] {
+                let finals: Vec<&String> = rules.iter().filter(|r| is_final_rule(r)).collect();
+                assert_eq!(
+                    finals,
+                    [expected],
+                    "{} from {}: {:?}",
+                    target,
+                    spelling,
+                    rules
+                );
+                assert_eq!(
+                    rules.last().unwrap(),
+                    expected,
+                    "{} from {}",
+                    target,
+                    spelling
+                );
+                assert!(rules.iter().any(|r| r.starts_with("GEOIP,CN,Direct")));
+            }
+
+            let mut yaml = serde_yaml::Value::Mapping(Default::default());
+            ruleset_to_clash_providers(&mut yaml, &rulesets, "", true, true);
+            let rules: Vec<&str> = yaml["rules"]
+                .as_sequence()
+                .unwrap()
+                .iter()
+                .filter_map(|rule| rule.as_str())
+                .collect();
+            assert_eq!(rules.last(), Some(&"MATCH,Final"), "{:?}", rules);
+            assert_eq!(rules.iter().filter(|r| is_final_rule(r)).count(), 1);
+
+            let mut base = json!({});
+            ruleset_to_sing_box(&mut base, &rulesets, true, 0, false);
+            assert_eq!(base["route"]["final"], "Final", "{}", spelling);
+            assert!(base["route"]["rules"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .all(|rule| rule.get("outbound") != Some(&json!("Final"))));
+        }
+    }
+
+    #[test]
+    fn test_final_rule_of_the_base_is_merged() {
+        let mut ini = IniReader::new();
+        ini.store_any_line = true;
+        ini.parse("[Rule]\nFINAL,DIRECT,dns-failed\nDOMAIN,a.com,Proxy\n")
+            .unwrap();
+        block_on(ruleset_to_surge(
+            &mut ini,
+            &[inline("Proxy", "DOMAIN,b.com")],
+            4,
+            false,
+            "",
+            0,
+        ));
+        let rules: Vec<String> = ini
+            .get_items("Rule")
+            .unwrap()
+            .into_iter()
+            .map(|(_, rule)| rule)
+            .collect();
+        assert_eq!(
+            rules,
+            [
+                "DOMAIN,a.com,Proxy",
+                "DOMAIN,b.com,Proxy",
+                "FINAL,DIRECT,dns-failed"
+            ]
+        );
+
+        // The final rule of the rulesets wins over the one of the base
+        let base =
+            serde_yaml::from_str("rules:\n  - MATCH,DIRECT\n  - DOMAIN,a.com,Proxy\n").unwrap();
+        let output = ruleset_to_clash_str(
+            &base,
+            &[inline("Proxy", "FINAL")],
+            false,
+            true,
+            0,
+            RuleTarget::Clash,
+        );
+        assert_eq!(
+            output,
+            "\nrules:\n  - DOMAIN,a.com,Proxy\n  - MATCH,Proxy\n"
+        );
+    }
+
+    #[test]
+    fn test_normalize_final_rules() {
+        let mut rules = vec![
+            "FINAL,Proxy,dns-failed".to_string(),
+            "DOMAIN,a.com,Proxy".to_string(),
+        ];
+        normalize_final_rules(&mut rules, RuleTarget::Clash);
+        assert_eq!(rules, ["DOMAIN,a.com,Proxy", "MATCH,Proxy"]);
+
+        let mut rules = vec!["match, Proxy, dns-failed".to_string()];
+        normalize_final_rules(&mut rules, RuleTarget::Surge);
+        assert_eq!(rules, ["FINAL,Proxy,dns-failed"]);
+
+        let mut rules = vec!["DOMAIN,a.com,Proxy".to_string()];
+        normalize_final_rules(&mut rules, RuleTarget::Surge);
+        assert_eq!(rules, ["DOMAIN,a.com,Proxy"]);
+
+        // sing-box writes `route.final` instead
+        let mut rules = vec!["MATCH,Proxy".to_string()];
+        normalize_final_rules(&mut rules, RuleTarget::SingBox);
+        assert!(rules.is_empty());
+    }
+}
@@ -0,0 +1,273 @@
+//! Logical rule parsing
+//!
+//! Logical rules combine other rules, e.g. `AND,((DOMAIN,foo.com),(DST-PORT,443)),Proxy`.
+//! Sub-rules are wrapped in parentheses and may contain commas or be logical
+//! rules themselves, so they are split on balanced parentheses.
+
+use serde_json::{json, Map, Value};
+
+/// Logical operator of a logical rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogicalOp {
+    And,
+    Or,
+    Not,
+}
+
+impl LogicalOp {
+    fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_uppercase().as_str() {
+            "AND" => Some(LogicalOp::And),
+            "OR" => Some(LogicalOp::Or),
+            "NOT" => Some(LogicalOp::Not),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            LogicalOp::And => "AND",
+            LogicalOp::Or => "OR",
+            LogicalOp::Not => "NOT",
+        }
+    }
+}
+
+/// A parsed rule, either a plain rule or a logical combination of rules
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleNode {
+    Logical { op: LogicalOp, rules: Vec<RuleNode> },
+    Simple { rule_type: String, payload: String },
+}
+
+/// Check whether a rule line is a logical rule
+pub fn is_logical_rule(line: &str) -> bool {
+    line.split_once(',')
+        .is_some_and(|(rule_type, _)| LogicalOp::parse(rule_type).is_some())
+}
+
+/// Find the end of the parenthesized group starting at `start`
+fn matching_paren(s: &str, start: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    for (i, c) in s[start..].char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth = depth.checked_sub(1)?;
+                if depth == 0 {
+                    return Some(start + i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Split `(a),(b,c),((d))` into `a`, `b,c` and `(d)`
+fn split_sub_rules(s: &str) -> Option<Vec<&str>> {
+    let mut parts = Vec::new();
+    let mut pos = 0;
+    while pos < s.len() {
+        let rest = &s[pos..];
+        let open = pos + rest.find(|c: char| !c.is_whitespace())?;
+        if !s[open..].starts_with('(') {
+            return None;
+        }
+        let close = matching_paren(s, open)?;
+        parts.push(s[open + 1..close].trim());
+
+        let rest = s[close + 1..].trim_start();
+        if rest.is_empty() {
+            break;
+        }
+        let rest = rest.strip_prefix(',')?;
+        pos = s.len() - rest.len();
+    }
+    Some(parts)
+}
+
+fn parse_rule_node(s: &str) -> Option<RuleNode> {
+    let (rule_type, payload) = s.split_once(',')?;
+    if LogicalOp::parse(rule_type).is_some() {
+        let (node, group) = parse_logical_rule(s)?;
+        // Sub-rules never carry a policy
+        return group.is_none().then_some(node);
+    }
+    let rule_type = rule_type.trim();
+    let payload = payload.trim();
+    if rule_type.is_empty() || payload.is_empty() {
+        return None;
+    }
+    Some(RuleNode::Simple {
+        rule_type: rule_type.to_uppercase(),
+        payload: payload.to_string(),
+    })
+}
+
+/// Parse a logical rule line
+///
+/// # Returns
+/// The rule tree and the trailing policy group if the line has one,
+/// `None` if the line is not a well formed logical rule
+pub fn parse_logical_rule(line: &str) -> Option<(RuleNode, Option<String>)> {
+    let (op, rest) = line.trim().split_once(',')?;
+    let op = LogicalOp::parse(op)?;
+
+    let rest = rest.trim_start();
+    if !rest.starts_with('(') {
+        return None;
+    }
+    let close = matching_paren(rest, 0)?;
+    let rules = split_sub_rules(&rest[1..close])?
+        .into_iter()
+        .map(parse_rule_node)
+        .collect::<Option<Vec<_>>>()?;
+    let arity_ok = match op {
+        LogicalOp::Not => rules.len() == 1,
+        LogicalOp::And | LogicalOp::Or => !rules.is_empty(),
+    };
+    if !arity_ok {
+        return None;
+    }
+
+    let group = match rest[close + 1..].trim() {
+        "" => None,
+        tail => Some(tail.strip_prefix(',')?.trim().to_string()),
+    };
+    Some((RuleNode::Logical { op, rules }, group))
+}
+
+impl RuleNode {
+    /// Render the rule in Clash / Surge syntax, without policy group
+    pub fn to_clash(&self) -> String {
+        match self {
+            RuleNode::Logical { op, rules } => {
+                let rules: Vec<String> = rules
+                    .iter()
+                    .map(|rule| format!("({})", rule.to_clash()))
+                    .collect();
+                format!("{},({})", op.as_str(), rules.join(","))
+            }
+            RuleNode::Simple { rule_type, payload } => format!("{},{}", rule_type, payload),
+        }
+    }
+
+    /// Render the rule as a sing-box route rule, without outbound
+    ///
+    /// Returns `None` if a sub-rule has no sing-box equivalent.
+    pub fn to_singbox(&self) -> Option<Value> {
+        match self {
+            RuleNode::Logical {
+                op: LogicalOp::Not,
+                rules,
+            } => {
+                let mut inner = rules.first()?.to_singbox()?;
+                let invert = !inner["invert"].as_bool().unwrap_or(false);
+                inner["invert"] = Value::Bool(invert);
+                Some(inner)
+            }
+            RuleNode::Logical { op, rules } => {
+                let rules = rules
+                    .iter()
+                    .map(RuleNode::to_singbox)
+                    .collect::<Option<Vec<_>>>()?;
+                Some(json!({
+                    "type": "logical",
+                    "mode": op.as_str().to_lowercase(),
+                    "rules": rules,
+                }))
+            }
+            RuleNode::Simple { rule_type, payload } => {
+                let field = match rule_type.as_str() {
+                    "DOMAIN" => "domain",
+                    "DOMAIN-SUFFIX" => "domain_suffix",
+                    "DOMAIN-KEYWORD" => "domain_keyword",
+                    "DOMAIN-REGEX" => "domain_regex",
+                    "IP-CIDR" | "IP-CIDR6" => "ip_cidr",
+                    "SRC-IP-CIDR" => "source_ip_cidr",
+                    "GEOIP" => "geoip",
+                    "GEOSITE" => "geosite",
+                    "DST-PORT" | "PORT" => "port",
+                    "SRC-PORT" => "source_port",
+                    "PROCESS-NAME" => "process_name",
+                    "PROCESS-PATH" => "process_path",
+                    "NETWORK" => "network",
+                    "PROTOCOL" => "protocol",
+                    _ => return None,
+                };
+                let value = payload.split(',').next().unwrap_or_default().trim();
+                // Domains are case-insensitive, regexes and process names are not
+                let value = match field {
+                    "port" | "source_port" => Value::from(value.parse::<u16>().ok()?),
+                    "domain" | "domain_suffix" | "domain_keyword" => {
+                        Value::String(value.to_lowercase())
+                    }
+                    _ => Value::String(value.to_string()),
+                };
+                let mut rule = Map::new();
+                rule.insert(field.to_string(), Value::Array(vec![value]));
+                Some(Value::Object(rule))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_nested_logical_rule() {
+        let line = "AND,((OR,((DOMAIN,a.com),(DOMAIN-SUFFIX,b.com))),(NOT,((DST-PORT,80)))),Proxy";
+        let (node, group) = parse_logical_rule(line).unwrap();
+        assert_eq!(group.as_deref(), Some("Proxy"));
+        assert_eq!(node.to_clash(), &line[..line.len() - ",Proxy".len()]);
+
+        let RuleNode::Logical { op, rules } = &node else {
+            panic!("expected logical rule");
+        };
+        assert_eq!(*op, LogicalOp::And);
+        assert_eq!(rules.len(), 2);
+
+        let singbox = node.to_singbox().unwrap();
+        assert_eq!(singbox["type"], "logical");
+        assert_eq!(singbox["mode"], "and");
+        assert_eq!(singbox["rules"][0]["mode"], "or");
+        assert_eq!(singbox["rules"][0]["rules"][1]["domain_suffix"][0], "b.com");
+        assert_eq!(singbox["rules"][1]["port"][0], 80);
+        assert_eq!(singbox["rules"][1]["invert"], true);
+    }
+
+    #[test]
+    fn test_singbox_keeps_case_sensitive_values() {
+        let line = "AND,((DOMAIN-SUFFIX,Example.COM),(OR,((DOMAIN-REGEX,^API\\.[A-Z]+$),(PROCESS-NAME,Telegram.exe)))),Proxy";
+        let (node, _) = parse_logical_rule(line).unwrap();
+        let singbox = node.to_singbox().unwrap();
+        assert_eq!(singbox["rules"][0]["domain_suffix"][0], "example.com");
+        let or = &singbox["rules"][1];
+        assert_eq!(or["mode"], "or");
+        assert_eq!(or["rules"][0]["domain_regex"][0], "^API\\.[A-Z]+$");
+        assert_eq!(or["rules"][1]["process_name"][0], "Telegram.exe");
+    }
+
+    #[test]
+    fn test_parse_keeps_commas_in_sub_rules() {
+        let (node, group) =
+            parse_logical_rule("OR,((IP-CIDR,10.0.0.0/8,no-resolve),(GEOIP,CN))").unwrap();
+        assert!(group.is_none());
+        assert_eq!(
+            node.to_clash(),
+            "OR,((IP-CIDR,10.0.0.0/8,no-resolve),(GEOIP,CN))"
+        );
+    }
+
+    #[test]
+    fn test_reject_malformed_logical_rules() {
+        assert!(is_logical_rule("AND,((DOMAIN,a.com)),Proxy"));
+        assert!(!is_logical_rule("DOMAIN,android.com"));
+        assert!(parse_logical_rule("AND,((DOMAIN,a.com),Proxy").is_none());
+        assert!(parse_logical_rule("NOT,((DOMAIN,a.com),(DOMAIN,b.com))").is_none());
+        assert!(parse_logical_rule("AND,DOMAIN,a.com").is_none());
+    }
+}
@@ -5,14 +5,20 @@
 
 pub mod common;
 pub mod convert_ruleset;
+pub mod final_rule;
+pub mod logical_rule;
+pub mod rule_matrix;
 // Keep the ruleset module for now but don't use its RulesetType
 mod ruleset;
 // mod ruleset_to_clash; // @deprecated
+pub mod ruleset_to_clash_providers;
 pub mod ruleset_to_clash_str;
 pub mod ruleset_to_sing_box;
 pub mod ruleset_to_surge;
 
-pub use convert_ruleset::convert_ruleset;
+pub use convert_ruleset::{convert_ruleset, count_rules};
+pub use rule_matrix::{DroppedRules, RuleTarget};
+pub use ruleset_to_clash_providers::ruleset_to_clash_providers;
 pub use ruleset_to_clash_str::ruleset_to_clash_str;
 pub use ruleset_to_sing_box::ruleset_to_sing_box;
 pub use ruleset_to_surge::ruleset_to_surge;
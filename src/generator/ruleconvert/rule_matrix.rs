@@ -0,0 +1,387 @@
+//! Source, port and process rule types by target
+//!
+//! Targets name these matchers differently, the source address is
+//! `SRC-IP-CIDR` in Clash, `SRC-IP` in Surge and `source_ip_cidr` in sing-box,
+//! and some targets have no such matcher at all. Rules of a type the target
+//! lacks are dropped and counted instead of being written for the client to
+//! reject.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Rule dialects the conversion writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleTarget {
+    /// Clash and ClashR
+    Clash,
+    Meta,
+    /// Surge and the clients sharing its rules, Surfboard and Mellow
+    Surge,
+    /// Quantumult X and Quantumult
+    QuanX,
+    Loon,
+    SingBox,
+}
+
+impl RuleTarget {
+    /// The dialect of the rules written by `ruleset_to_surge` for `surge_ver`
+    pub fn from_surge_ver(surge_ver: i32) -> Self {
+        match surge_ver {
+            -1 | -2 => RuleTarget::QuanX,
+            -4 => RuleTarget::Loon,
+            _ => RuleTarget::Surge,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RuleTarget::Clash => "clash",
+            RuleTarget::Meta => "clash.meta",
+            RuleTarget::Surge => "surge",
+            RuleTarget::QuanX => "quanx",
+            RuleTarget::Loon => "loon",
+            RuleTarget::SingBox => "singbox",
+        }
+    }
+
+    fn column(self) -> usize {
+        match self {
+            RuleTarget::Clash => 0,
+            RuleTarget::Meta => 1,
+            RuleTarget::Surge => 2,
+            RuleTarget::QuanX => 3,
+            RuleTarget::Loon => 4,
+            RuleTarget::SingBox => 5,
+        }
+    }
+}
+
+/// Name of each rule type by target, in the order of [`RuleTarget`], `None`
+/// where the target has no such rule
+///
+/// sing-box matches inbounds by tag only, so `IN-PORT` has no equivalent there.
+/// Surge matches full paths with `PROCESS-NAME`.
+const RULE_MATRIX: [(&str, [Option<&str>; 6]); 5] = [
+    (
+        "SRC-IP-CIDR",
+        [
+            Some("SRC-IP-CIDR"),
+            Some("SRC-IP-CIDR"),
+            Some("SRC-IP"),
+            None,
+            Some("SRC-IP"),
+            Some("source_ip_cidr"),
+        ],
+    ),
+    (
+        "DST-PORT",
+        [
+            Some("DST-PORT"),
+            Some("DST-PORT"),
+            Some("DEST-PORT"),
+            None,
+            Some("DEST-PORT"),
+            Some("port"),
+        ],
+    ),
+    (
+        "SRC-PORT",
+        [
+            Some("SRC-PORT"),
+            Some("SRC-PORT"),
+            Some("SRC-PORT"),
+            None,
+            Some("SRC-PORT"),
+            Some("source_port"),
+        ],
+    ),
+    (
+        "IN-PORT",
+        [None, Some("IN-PORT"), Some("IN-PORT"), None, None, None],
+    ),
+    (
+        "PROCESS-PATH",
+        [
+            Some("PROCESS-PATH"),
+            Some("PROCESS-PATH"),
+            Some("PROCESS-NAME"),
+            None,
+            None,
+            Some("process_path"),
+        ],
+    ),
+];
+
+/// How a target writes a rule type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleMapping {
+    /// Not a rule type of the matrix, left to the checks of the generator
+    Other,
+    /// Written under this name
+    Renamed(&'static str),
+    /// The target has no such rule, `0` is the Clash name of the type
+    Unsupported(&'static str),
+}
+
+/// Look `rule_type` up in the matrix for `target`
+///
+/// The Surge names `SRC-IP` and `DEST-PORT` are understood as well.
+pub fn map_rule_type(rule_type: &str, target: RuleTarget) -> RuleMapping {
+    let rule_type = rule_type.trim();
+    let canonical = if rule_type.eq_ignore_ascii_case("SRC-IP") {
+        "SRC-IP-CIDR"
+    } else if rule_type.eq_ignore_ascii_case("DEST-PORT") {
+        "DST-PORT"
+    } else {
+        rule_type
+    };
+    match RULE_MATRIX
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(canonical))
+    {
+        Some((name, targets)) => match targets[target.column()] {
+            Some(target_name) => RuleMapping::Renamed(target_name),
+            None => RuleMapping::Unsupported(name),
+        },
+        None => RuleMapping::Other,
+    }
+}
+
+/// Rewrite the type of a rule line for `target`
+///
+/// Returns `None` and counts the rule in `dropped` when the target has no
+/// such rule, lines of other types are returned as they are.
+pub fn map_rule_line(line: &str, target: RuleTarget, dropped: &mut DroppedRules) -> Option<String> {
+    let (rule_type, rest) = match line.split_once(',') {
+        Some((rule_type, rest)) => (rule_type, Some(rest)),
+        None => (line, None),
+    };
+    match map_rule_type(rule_type, target) {
+        RuleMapping::Other => Some(line.to_string()),
+        RuleMapping::Renamed(name) => Some(match rest {
+            Some(rest) => format!("{},{}", name, rest),
+            None => name.to_string(),
+        }),
+        RuleMapping::Unsupported(name) => {
+            dropped.add(name);
+            None
+        }
+    }
+}
+
+/// Rules dropped for lack of support in the target, by type
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DroppedRules(BTreeMap<&'static str, usize>);
+
+impl DroppedRules {
+    pub fn add(&mut self, rule_type: &'static str) {
+        *self.0.entry(rule_type).or_default() += 1;
+    }
+
+    /// Dropped rules of `rule_type`
+    pub fn get(&self, rule_type: &str) -> usize {
+        self.0.get(rule_type).copied().unwrap_or(0)
+    }
+
+    pub fn total(&self) -> usize {
+        self.0.values().sum()
+    }
+}
+
+/// `IN-PORT:2,SRC-PORT:1` or `0` when nothing was dropped
+impl fmt::Display for DroppedRules {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, "0");
+        }
+        let counts: Vec<String> = self
+            .0
+            .iter()
+            .map(|(rule_type, count)| format!("{}:{}", rule_type, count))
+            .collect();
+        write!(f, "{}", counts.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::ruleconvert::{
+        ruleset_to_clash_str, ruleset_to_sing_box, ruleset_to_surge,
+    };
+    use crate::models::RulesetContent;
+    use crate::utils::ini_reader::IniReader;
+    use serde_json::{json, Value};
+
+    /// Each rule and what Clash.Meta, Surge, Quantumult X and Loon write of
+    /// it, `None` when dropped
+    const CASES: [(&str, [Option<&str>; 4]); 6] = [
+        (
+            "SRC-IP-CIDR,192.168.1.0/24",
+            [
+                Some("SRC-IP-CIDR,192.168.1.0/24"),
+                Some("SRC-IP,192.168.1.0/24"),
+                None,
+                Some("SRC-IP,192.168.1.0/24"),
+            ],
+        ),
+        (
+            "SRC-IP,10.0.0.2",
+            [
+                Some("SRC-IP-CIDR,10.0.0.2"),
+                Some("SRC-IP,10.0.0.2"),
+                None,
+                Some("SRC-IP,10.0.0.2"),
+            ],
+        ),
+        (
+            "DST-PORT,443",
+            [
+                Some("DST-PORT,443"),
+                Some("DEST-PORT,443"),
+                None,
+                Some("DEST-PORT,443"),
+            ],
+        ),
+        (
+            "SRC-PORT,5000",
+            [
+                Some("SRC-PORT,5000"),
+                Some("SRC-PORT,5000"),
+                None,
+                Some("SRC-PORT,5000"),
+            ],
+        ),
+        (
+            "IN-PORT,7890",
+            [Some("IN-PORT,7890"), Some("IN-PORT,7890"), None, None],
+        ),
+        (
+            "PROCESS-PATH,/usr/bin/curl",
+            [
+                Some("PROCESS-PATH,/usr/bin/curl"),
+                Some("PROCESS-NAME,/usr/bin/curl"),
+                None,
+                None,
+            ],
+        ),
+    ];
+
+    fn ruleset(rule: &str) -> [RulesetContent; 1] {
+        let mut ruleset = RulesetContent::new("", "Proxy");
+        ruleset.set_rule_content(&format!("[]{}", rule));
+        [ruleset]
+    }
+
+    fn surge_rules(rule: &str, surge_ver: i32) -> String {
+        let mut ini = IniReader::new();
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(ruleset_to_surge(
+                &mut ini,
+                &ruleset(rule),
+                surge_ver,
+                true,
+                "",
+                0,
+            ));
+        ini.to_string()
+    }
+
+    #[test]
+    fn test_rule_types_by_target() {
+        for (rule, expected) in CASES {
+            let [meta, surge, quanx, loon] = expected;
+
+            let clash = ruleset_to_clash_str(
+                &serde_yaml::Value::Null,
+                &ruleset(rule),
+                true,
+                true,
+                0,
+                RuleTarget::Meta,
+            );
+            match meta {
+                Some(meta) => assert!(
+                    clash.contains(&format!("  - {},Proxy\n", meta)),
+                    "{}",
+                    clash
+                ),
+                None => assert!(!clash.contains("  - "), "{}", clash),
+            }
+
+            for (surge_ver, expected) in [(4, surge), (-1, quanx), (-4, loon)] {
+                let output = surge_rules(rule, surge_ver);
+                let written = output.lines().any(|line| line.ends_with(",Proxy"));
+                match expected {
+                    Some(line) => assert!(
+                        output.lines().any(|l| l == format!("{},Proxy", line)),
+                        "{} for {}: {}",
+                        rule,
+                        surge_ver,
+                        output
+                    ),
+                    None => assert!(!written, "{} for {}: {}", rule, surge_ver, output),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_sing_box_rule_fields() {
+        for (rule, expected) in [
+            (
+                "SRC-IP-CIDR,192.168.1.0/24",
+                Some(("source_ip_cidr", json!("192.168.1.0/24"))),
+            ),
+            ("DST-PORT,443", Some(("port", json!(443)))),
+            ("SRC-PORT,5000", Some(("source_port", json!(5000)))),
+            ("IN-PORT,7890", None),
+            (
+                "PROCESS-PATH,/usr/bin/curl",
+                Some(("process_path", json!("/usr/bin/curl"))),
+            ),
+        ] {
+            // A ruleset file, its rules are merged by type
+            let mut ruleset = RulesetContent::new("rules.list", "Proxy");
+            ruleset.set_rule_content(rule);
+            let mut base = json!({});
+            ruleset_to_sing_box(&mut base, &[ruleset], true, 0, false);
+
+            let rules = base["route"]["rules"].as_array().unwrap();
+            let rule_obj = rules.iter().find(|r| r["outbound"] == "Proxy");
+            match expected {
+                Some((field, value)) => {
+                    assert_eq!(
+                        rule_obj.unwrap()[field],
+                        Value::Array(vec![value]),
+                        "{}",
+                        rule
+                    )
+                }
+                None => assert!(rule_obj.is_none(), "{}", rule),
+            }
+        }
+    }
+
+    #[test]
+    fn test_dropped_rules_are_counted() {
+        let mut dropped = DroppedRules::default();
+        for (rule, _) in CASES {
+            map_rule_line(rule, RuleTarget::QuanX, &mut dropped);
+        }
+        assert_eq!(
+            map_rule_line("DOMAIN,a.com", RuleTarget::QuanX, &mut dropped).as_deref(),
+            Some("DOMAIN,a.com")
+        );
+        assert_eq!(dropped.total(), 6);
+        assert_eq!(dropped.get("SRC-IP-CIDR"), 2);
+        assert_eq!(
+            dropped.to_string(),
+            "DST-PORT:1,IN-PORT:1,PROCESS-PATH:1,SRC-IP-CIDR:2,SRC-PORT:1"
+        );
+        assert_eq!(DroppedRules::default().to_string(), "0");
+    }
+}
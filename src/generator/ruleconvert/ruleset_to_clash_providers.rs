@@ -0,0 +1,277 @@
+//! Ruleset to Clash rule-providers conversion
+//!
+//! Used when rulesets are not expanded into the configuration: every remote
+//! ruleset becomes an http rule provider referenced by a `RULE-SET` rule.
+
+use crate::models::{RulesetBehavior, RulesetContent, RulesetType};
+use crate::utils::base64::url_safe_base64_encode;
+use crate::utils::network::is_link;
+use log::{debug, warn};
+use serde_yaml::{Mapping, Value as YamlValue};
+use std::collections::HashSet;
+
+use super::common::transform_rule_to_common;
+use super::final_rule::normalize_final_rules;
+use super::logical_rule::{is_logical_rule, parse_logical_rule};
+use super::rule_matrix::RuleTarget;
+
+/// Provider name derived from the ruleset file name
+pub(crate) fn provider_base_name(rule_path: &str) -> String {
+    let file_name = rule_path
+        .split(['?', '#'])
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or_default();
+    let stem = match file_name.rsplit_once('.') {
+        Some((stem, _)) if !stem.is_empty() => stem,
+        _ => file_name,
+    };
+    if stem.is_empty() {
+        "ruleset".to_string()
+    } else {
+        stem.to_string()
+    }
+}
+
+/// Whether the provider at the URL of `ruleset` serves a YAML `payload`
+///
+/// Clash rulesets and `.yaml` files do, Surge and QuantumultX lists are
+/// plain text and need `format: text`.
+fn serves_yaml(ruleset: &RulesetContent) -> bool {
+    let path = ruleset
+        .rule_path
+        .split(['?', '#'])
+        .next()
+        .unwrap_or_default();
+    matches!(
+        ruleset.rule_type,
+        RulesetType::ClashDomain | RulesetType::ClashIpcidr | RulesetType::ClashClassical
+    ) || path.ends_with(".yaml")
+        || path.ends_with(".yml")
+}
+
+/// `getruleset` type code for each provider behavior
+fn getruleset_type(behavior: RulesetBehavior) -> u8 {
+    match behavior {
+        RulesetBehavior::Domain => 3,
+        RulesetBehavior::Ipcidr => 4,
+        RulesetBehavior::Classical => 6,
+    }
+}
+
+/// Write rulesets as Clash rule-providers into the YAML node
+///
+/// # Arguments
+///
+/// * `yaml_node` - The Clash configuration to modify
+/// * `ruleset_content_array` - Array of ruleset contents
+/// * `remote_path_prefix` - Managed config prefix used to serve converted rulesets,
+///   rulesets are linked directly when it is empty
+/// * `overwrite_original_rules` - Whether to overwrite original rules, otherwise
+///   generated providers are merged into the existing `rule-providers` and a
+///   provider of the base configuration wins over a generated one of the same name
+/// * `new_field_name` - Whether to use "rules" or "Rule" as the field name
+pub fn ruleset_to_clash_providers(
+    yaml_node: &mut YamlValue,
+    ruleset_content_array: &[RulesetContent],
+    remote_path_prefix: &str,
+    overwrite_original_rules: bool,
+    new_field_name: bool,
+) {
+    let field_name = if new_field_name { "rules" } else { "Rule" };
+    let mut rules: Vec<String> = if overwrite_original_rules {
+        Vec::new()
+    } else {
+        yaml_node
+            .get(field_name)
+            .and_then(|rules| rules.as_sequence())
+            .map(|rules| {
+                rules
+                    .iter()
+                    .filter_map(|rule| rule.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    let mut providers = if overwrite_original_rules {
+        Mapping::new()
+    } else {
+        yaml_node
+            .get("rule-providers")
+            .and_then(|providers| providers.as_mapping())
+            .cloned()
+            .unwrap_or_default()
+    };
+    let mut used_names = HashSet::new();
+
+    for ruleset in ruleset_content_array {
+        let rule_group = &ruleset.group;
+
+        // Inline rules are added as they are
+        if ruleset.rule_path.is_empty() {
+            let content = ruleset.get_rule_content();
+            let Some(rule_line) = content.strip_prefix("[]") else {
+                continue;
+            };
+            let rule = if is_logical_rule(rule_line) {
+                match parse_logical_rule(rule_line) {
+                    Some((node, _)) => format!("{},{}", node.to_clash(), rule_group),
+                    None => {
+                        warn!("Skipping malformed logical rule '{}'", rule_line);
+                        continue;
+                    }
+                }
+            } else {
+                transform_rule_to_common(rule_line, rule_group, false)
+            };
+            rules.push(rule);
+            continue;
+        }
+
+        let behavior = ruleset.behavior();
+        // Rulesets served through the managed prefix are converted to YAML
+        let text = remote_path_prefix.is_empty() && !serves_yaml(ruleset);
+        let url = if !remote_path_prefix.is_empty() {
+            format!(
+                "{}/getruleset?type={}&url={}",
+                remote_path_prefix,
+                getruleset_type(behavior),
+                url_safe_base64_encode(&ruleset.rule_path_typed)
+            )
+        } else if is_link(&ruleset.rule_path) {
+            ruleset.rule_path.clone()
+        } else {
+            // Local rulesets can only be served through the managed prefix
+            continue;
+        };
+
+        let base_name = provider_base_name(&ruleset.rule_path);
+        let mut name = base_name.clone();
+        let mut index = 1;
+        while !used_names.insert(name.clone()) {
+            name = format!("{} {}", base_name, index);
+            index += 1;
+        }
+
+        let mut provider = Mapping::new();
+        provider.insert("type".into(), "http".into());
+        provider.insert("behavior".into(), behavior.as_str().into());
+        if text {
+            provider.insert("format".into(), "text".into());
+        }
+        provider.insert("url".into(), url.into());
+        provider.insert(
+            "path".into(),
+            format!(
+                "./providers/rule-provider_{}.{}",
+                name,
+                if text { "txt" } else { "yaml" }
+            )
+            .into(),
+        );
+        if ruleset.update_interval > 0 {
+            provider.insert("interval".into(), ruleset.update_interval.into());
+        }
+        if providers.contains_key(name.as_str()) {
+            debug!("Keeping rule provider '{}' of the base configuration", name);
+        } else {
+            providers.insert(name.clone().into(), YamlValue::Mapping(provider));
+        }
+        rules.push(format!("RULE-SET,{},{}", name, rule_group));
+    }
+
+    normalize_final_rules(&mut rules, RuleTarget::Clash);
+    let rules = rules.into_iter().map(YamlValue::String).collect();
+
+    if let Some(map) = yaml_node.as_mapping_mut() {
+        if !providers.is_empty() {
+            map.insert("rule-providers".into(), YamlValue::Mapping(providers));
+        }
+        map.insert(field_name.into(), YamlValue::Sequence(rules));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_providers_use_detected_behavior() {
+        let mut domain_set = RulesetContent::new("https://example.com/rules/apple.txt", "Apple");
+        domain_set.set_rule_content(".apple.com\nicloud.com\n");
+        domain_set.update_interval = 86400;
+
+        let mut ipcidr = RulesetContent::new("https://example.com/lan.yaml", "DIRECT");
+        ipcidr.rule_path_typed = "clash-ipcidr:https://example.com/lan.yaml".to_string();
+        ipcidr.rule_type = RulesetType::ClashIpcidr;
+        ipcidr.set_rule_content("payload:\n  - '10.0.0.0/8'\n");
+
+        let mut final_rule = RulesetContent::new("", "Proxy");
+        final_rule.set_rule_content("[]FINAL");
+
+        let mut node: YamlValue = serde_yaml::from_str("mode: rule\n").unwrap();
+        ruleset_to_clash_providers(&mut node, &[domain_set, ipcidr, final_rule], "", true, true);
+
+        let providers = &node["rule-providers"];
+        assert_eq!(providers["apple"]["behavior"], "domain");
+        assert_eq!(providers["apple"]["interval"], 86400);
+        // Lists are not YAML
+        assert_eq!(providers["apple"]["format"], "text");
+        assert_eq!(
+            providers["apple"]["path"],
+            "./providers/rule-provider_apple.txt"
+        );
+        assert_eq!(providers["lan"]["behavior"], "ipcidr");
+        assert_eq!(providers["lan"]["url"], "https://example.com/lan.yaml");
+        assert!(providers["lan"].get("format").is_none());
+
+        let rules: Vec<&str> = node["rules"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .filter_map(|rule| rule.as_str())
+            .collect();
+        assert_eq!(
+            rules,
+            ["RULE-SET,apple,Apple", "RULE-SET,lan,DIRECT", "MATCH,Proxy"]
+        );
+    }
+
+    #[test]
+    fn test_merge_with_base_providers() {
+        let mut apple = RulesetContent::new("https://example.com/rules/apple.txt", "Apple");
+        apple.set_rule_content(".apple.com\n");
+        let mut google = RulesetContent::new("https://example.com/rules/google.txt", "Google");
+        google.set_rule_content(".google.com\n");
+
+        let base = "rule-providers:\n  apple:\n    type: file\n    behavior: domain\n    path: ./apple.yaml\nrules:\n  - DOMAIN,example.com,DIRECT\n";
+        let mut node: YamlValue = serde_yaml::from_str(base).unwrap();
+        ruleset_to_clash_providers(&mut node, &[apple.clone(), google.clone()], "", false, true);
+
+        let providers = node["rule-providers"].as_mapping().unwrap();
+        assert_eq!(providers.len(), 2);
+        assert_eq!(node["rule-providers"]["apple"]["type"], "file");
+        assert_eq!(node["rule-providers"]["google"]["type"], "http");
+        assert_eq!(node["rules"].as_sequence().unwrap().len(), 3);
+
+        let mut node: YamlValue = serde_yaml::from_str(base).unwrap();
+        ruleset_to_clash_providers(&mut node, &[apple, google], "", true, true);
+        assert_eq!(node["rule-providers"]["apple"]["type"], "http");
+        assert_eq!(node["rules"].as_sequence().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_managed_prefix_serves_yaml() {
+        let mut apple = RulesetContent::new("https://example.com/rules/apple.list", "Apple");
+        apple.set_rule_content("DOMAIN-SUFFIX,apple.com\n");
+
+        let mut node: YamlValue = serde_yaml::from_str("mode: rule\n").unwrap();
+        ruleset_to_clash_providers(&mut node, &[apple], "https://sub.example.com", true, true);
+        let provider = &node["rule-providers"]["apple"];
+        assert!(provider.get("format").is_none());
+        assert_eq!(provider["path"], "./providers/rule-provider_apple.yaml");
+    }
+}
@@ -4,14 +4,16 @@
 
 use crate::models::RulesetContent;
 use crate::utils::string::{find_str, starts_with, trim};
-use crate::Settings;
 use lazy_static::lazy_static;
-use log::warn;
+use log::{info, warn};
 use serde_yaml::Value as YamlValue;
 use std::collections::HashSet;
 
 use super::common::transform_rule_to_common;
 use super::convert_ruleset::convert_ruleset;
+use super::final_rule::normalize_final_rules;
+use super::logical_rule::{is_logical_rule, parse_logical_rule};
+use super::rule_matrix::{map_rule_line, DroppedRules, RuleTarget};
 
 lazy_static! {
     static ref CLASH_RULE_TYPES: HashSet<&'static str> = {
@@ -30,10 +32,23 @@ lazy_static! {
         types.insert("SRC-PORT");
         types.insert("DST-PORT");
         types.insert("PROCESS-NAME");
+        types.insert("PROCESS-PATH");
+        types.insert("IN-PORT");
         types
     };
 }
 
+/// Render a logical rule for Clash.Meta, `None` if it is malformed
+fn logical_rule_to_clash(line: &str, group: &str) -> Option<String> {
+    match parse_logical_rule(line) {
+        Some((node, _)) => Some(format!("{},{}", node.to_clash(), group)),
+        None => {
+            warn!("Skipping malformed logical rule '{}'", line);
+            None
+        }
+    }
+}
+
 /// Converts rulesets to Clash YAML string format
 ///
 /// # Arguments
@@ -42,6 +57,11 @@ lazy_static! {
 /// * `ruleset_content_array` - Array of ruleset content
 /// * `overwrite_original_rules` - Whether to overwrite original rules
 /// * `new_field_name` - Whether to use "rules" or "Rule" as the field name
+/// * `max_allowed_rules` - Maximum number of rules to write, 0 for unlimited
+/// * `target` - Clash or Clash.Meta, rules of types the target lacks are dropped
+///
+/// The base and ruleset rules are merged, a single `MATCH` rule is written
+/// last whether the rulesets spell it `MATCH` or `FINAL`.
 ///
 /// # Returns
 ///
@@ -51,31 +71,28 @@ pub fn ruleset_to_clash_str(
     ruleset_content_array: &[RulesetContent],
     overwrite_original_rules: bool,
     new_field_name: bool,
+    max_allowed_rules: usize,
+    target: RuleTarget,
 ) -> String {
-    // Get global settings
-    let settings = Settings::current();
-
     // Set field name based on parameter
     let field_name = if new_field_name { "rules" } else { "Rule" };
-    let mut output_content = format!("\n{}:\n", field_name);
+    let mut rules = Vec::new();
     let mut total_rules = 0;
+    let mut dropped = DroppedRules::default();
 
     // Include existing rules if not overwriting
     if !overwrite_original_rules {
-        if let Some(rules) = base_rule.get(field_name) {
-            if let Some(rules_array) = rules.as_sequence() {
+        if let Some(base_rules) = base_rule.get(field_name) {
+            if let Some(rules_array) = base_rules.as_sequence() {
                 for rule in rules_array {
                     if let Some(rule_str) = rule.as_str() {
-                        output_content.push_str(&format!("  - {}\n", rule_str));
+                        rules.push(rule_str.to_string());
                     }
                 }
             }
         }
     }
 
-    // Use the max_allowed_rules from global settings
-    let max_allowed_rules = settings.max_allowed_rules;
-
     // Process each ruleset content
     for ruleset in ruleset_content_array {
         // Check if we've reached the maximum number of rules
@@ -100,16 +117,22 @@ pub fn ruleset_to_clash_str(
 
         // Handle special case for rules that start with "[]"
         if starts_with(&retrieved_rules, "[]") {
-            let mut rule_line = retrieved_rules[2..].to_string();
+            let rule_line = retrieved_rules[2..].to_string();
 
-            // Replace FINAL with MATCH for Clash compatibility
-            if starts_with(&rule_line, "FINAL") {
-                rule_line = rule_line.replacen("FINAL", "MATCH", 1);
+            if is_logical_rule(&rule_line) {
+                if let Some(rule) = logical_rule_to_clash(&rule_line, rule_group) {
+                    rules.push(rule);
+                    total_rules += 1;
+                }
+                continue;
             }
 
+            let Some(rule_line) = map_rule_line(&rule_line, target, &mut dropped) else {
+                continue;
+            };
+
             // Transform rule to common format
-            let transformed = transform_rule_to_common(&rule_line, rule_group, false);
-            output_content.push_str(&format!("  - {}\n", transformed));
+            rules.push(transform_rule_to_common(&rule_line, rule_group, false));
             total_rules += 1;
             continue;
         }
@@ -143,6 +166,25 @@ pub fn ruleset_to_clash_str(
                 continue;
             }
 
+            // Remove inline comments
+            if let Some(comment_pos) = find_str(&str_line, "//") {
+                str_line = str_line[..comment_pos].to_string();
+                str_line = trim(&str_line).to_string();
+            }
+
+            // Logical rules are emitted natively for Clash.Meta
+            if is_logical_rule(&str_line) {
+                if let Some(rule) = logical_rule_to_clash(&str_line, rule_group) {
+                    rules.push(rule);
+                    total_rules += 1;
+                }
+                continue;
+            }
+
+            let Some(str_line) = map_rule_line(&str_line, target, &mut dropped) else {
+                continue;
+            };
+
             // Check if the rule type is supported by Clash
             if !CLASH_RULE_TYPES
                 .iter()
@@ -151,18 +193,23 @@ pub fn ruleset_to_clash_str(
                 continue;
             }
 
-            // Remove inline comments
-            if let Some(comment_pos) = find_str(&str_line, "//") {
-                str_line = str_line[..comment_pos].to_string();
-                str_line = trim(&str_line).to_string();
-            }
-
             // Transform rule to common format and add to output
-            let transformed = transform_rule_to_common(&str_line, rule_group, false);
-            output_content.push_str(&format!("  - {}\n", transformed));
+            rules.push(transform_rule_to_common(&str_line, rule_group, false));
             total_rules += 1;
         }
     }
 
+    info!(
+        "stage=rules target={} rules={} dropped={}",
+        target.name(),
+        total_rules,
+        dropped
+    );
+
+    normalize_final_rules(&mut rules, target);
+    let mut output_content = format!("\n{}:\n", field_name);
+    for rule in rules {
+        output_content.push_str(&format!("  - {}\n", rule));
+    }
     output_content
 }
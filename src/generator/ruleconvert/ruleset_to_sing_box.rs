@@ -5,13 +5,45 @@
 use crate::models::RulesetContent;
 use crate::utils::string::{find_str, starts_with, to_lower};
 use crate::utils::trim;
-use crate::Settings;
-use log::warn;
+use log::{info, warn};
 use serde_json::{json, Map, Value};
 
 use super::convert_ruleset::convert_ruleset;
+use super::final_rule::is_final_rule;
+use super::logical_rule::{is_logical_rule, parse_logical_rule};
+use super::rule_matrix::{map_rule_type, DroppedRules, RuleMapping, RuleTarget};
 use super::ruleset::SINGBOX_RULE_TYPES;
 
+/// Value of a rule field of the rule matrix, ports are numbers for sing-box
+///
+/// Returns `None` for a port that is not a number.
+fn rule_field_value(field: &str, value: &str) -> Option<Value> {
+    match field {
+        "port" | "source_port" => value.trim().parse::<u16>().ok().map(Value::from),
+        // Paths are case sensitive
+        "process_path" => Some(Value::String(value.to_string())),
+        _ => Some(Value::String(to_lower(value))),
+    }
+}
+
+/// Convert a logical rule to a sing-box rule routed to `outbound`
+///
+/// Returns `None` and logs a warning if the rule is malformed or uses rule
+/// types sing-box does not support.
+fn logical_rule_to_sing_box(line: &str, outbound: &str) -> Option<Value> {
+    let rule = parse_logical_rule(line).and_then(|(node, _)| node.to_singbox());
+    match rule {
+        Some(mut rule) => {
+            rule["outbound"] = Value::String(outbound.to_string());
+            Some(rule)
+        }
+        None => {
+            warn!("Skipping logical rule unsupported by sing-box: '{}'", line);
+            None
+        }
+    }
+}
+
 /// Converts rulesets to Sing-Box format and updates the JSON configuration
 ///
 /// # Arguments
@@ -19,14 +51,15 @@ use super::ruleset::SINGBOX_RULE_TYPES;
 /// * `base_rule` - The base Sing-Box configuration as JSON
 /// * `ruleset_content_array` - Array of ruleset contents to process
 /// * `overwrite_original_rules` - Whether to overwrite original rules
+/// * `max_allowed_rules` - Maximum number of rules to write, 0 for unlimited
+/// * `add_clash_modes` - Whether to route the Clash `Global` and `Direct` modes
 pub fn ruleset_to_sing_box(
     base_rule: &mut Value,
     ruleset_content_array: &[RulesetContent],
     overwrite_original_rules: bool,
+    max_allowed_rules: usize,
+    add_clash_modes: bool,
 ) {
-    // Get global settings
-    let settings = Settings::current();
-
     // Create rules array
     let mut rules = Value::Array(Vec::new());
 
@@ -42,7 +75,7 @@ pub fn ruleset_to_sing_box(
     }
 
     // Add Clash modes if enabled
-    if settings.singbox_add_clash_modes {
+    if add_clash_modes {
         let global_object = json!({
             "clash_mode": "Global",
             "outbound": "GLOBAL"
@@ -71,11 +104,13 @@ pub fn ruleset_to_sing_box(
 
     // Process each ruleset
     let mut total_rules = 0;
-    let mut final_rule = String::new();
+    // Outbounds of the final rules, only the last one is used
+    let mut final_outbounds = Vec::new();
+    let mut dropped = DroppedRules::default();
 
     for ruleset in ruleset_content_array {
         // Check if we've reached the maximum number of rules
-        if settings.max_allowed_rules > 0 && total_rules >= settings.max_allowed_rules {
+        if max_allowed_rules > 0 && total_rules >= max_allowed_rules {
             break;
         }
 
@@ -94,8 +129,18 @@ pub fn ruleset_to_sing_box(
         if starts_with(&retrieved_rules, "[]") {
             let str_line = &retrieved_rules[2..];
 
-            if starts_with(str_line, "FINAL") || starts_with(str_line, "MATCH") {
-                final_rule = rule_group.clone();
+            if is_final_rule(str_line) {
+                final_outbounds.push(rule_group.clone());
+                continue;
+            }
+
+            if is_logical_rule(str_line) {
+                if let Some(rule) = logical_rule_to_sing_box(str_line, rule_group) {
+                    if let Some(rules_array) = rules.as_array_mut() {
+                        rules_array.push(rule);
+                        total_rules += 1;
+                    }
+                }
                 continue;
             }
 
@@ -105,26 +150,33 @@ pub fn ruleset_to_sing_box(
                 continue;
             }
 
-            let rule_type = to_lower(parts[0]);
-            let rule_value = to_lower(parts[1]);
-
             let mut rule_obj = Map::new();
 
-            // Convert type names from Clash format to SingBox format
-            let rule_type = rule_type
-                .replace("-", "_")
-                .replace("ip_cidr6", "ip_cidr")
-                .replace("src_", "source_");
-
-            if rule_type == "match" || rule_type == "final" {
-                rule_obj.insert("outbound".to_string(), Value::String(rule_value));
-            } else {
-                rule_obj.insert(rule_type, Value::String(rule_value));
-                rule_obj.insert(
-                    "outbound".to_string(),
-                    Value::String(rule_group.to_string()),
-                );
-            }
+            // Source, port and process rules have their own names
+            let (rule_type, rule_value) = match map_rule_type(parts[0], RuleTarget::SingBox) {
+                RuleMapping::Renamed(field) => match rule_field_value(field, parts[1]) {
+                    Some(value) => (field.to_string(), value),
+                    None => continue,
+                },
+                RuleMapping::Unsupported(rule_type) => {
+                    dropped.add(rule_type);
+                    continue;
+                }
+                // Convert type names from Clash format to SingBox format
+                RuleMapping::Other => (
+                    to_lower(parts[0])
+                        .replace("-", "_")
+                        .replace("ip_cidr6", "ip_cidr")
+                        .replace("src_", "source_"),
+                    Value::String(to_lower(parts[1])),
+                ),
+            };
+
+            rule_obj.insert(rule_type, rule_value);
+            rule_obj.insert(
+                "outbound".to_string(),
+                Value::String(rule_group.to_string()),
+            );
 
             if let Some(rules_array) = rules.as_array_mut() {
                 rules_array.push(Value::Object(rule_obj));
@@ -142,7 +194,7 @@ pub fn ruleset_to_sing_box(
 
         // Process each rule line
         for line in converted_rules.lines() {
-            if settings.max_allowed_rules > 0 && total_rules >= settings.max_allowed_rules {
+            if max_allowed_rules > 0 && total_rules >= max_allowed_rules {
                 break;
             }
 
@@ -163,25 +215,50 @@ pub fn ruleset_to_sing_box(
                 str_line = trim(&str_line).to_string();
             }
 
-            // Process the rule
-            let rule_parts: Vec<&str> = str_line.split(',').collect();
-            if rule_parts.len() < 2 {
+            // A final rule in a list routes what nothing matched to its group
+            if is_final_rule(&str_line) {
+                final_outbounds.push(rule_group.clone());
                 continue;
             }
 
-            let rule_type = rule_parts[0];
+            // Logical rules can't be merged, each one becomes its own rule
+            if is_logical_rule(&str_line) {
+                if let Some(rule) = logical_rule_to_sing_box(&str_line, rule_group) {
+                    if let Some(rules_array) = rules.as_array_mut() {
+                        rules_array.push(rule);
+                        total_rules += 1;
+                    }
+                }
+                continue;
+            }
 
-            // Skip if rule type is not supported
-            if !SINGBOX_RULE_TYPES.contains(rule_type) {
+            // Process the rule
+            let rule_parts: Vec<&str> = str_line.split(',').collect();
+            if rule_parts.len() < 2 {
                 continue;
             }
 
-            let real_type = to_lower(rule_type)
-                .replace("-", "_")
-                .replace("ip_cidr6", "ip_cidr")
-                .replace("src_", "source_");
+            let rule_type = rule_parts[0];
 
-            let rule_value = to_lower(rule_parts[1]);
+            let (real_type, rule_value) = match map_rule_type(rule_type, RuleTarget::SingBox) {
+                RuleMapping::Renamed(field) => match rule_field_value(field, rule_parts[1]) {
+                    Some(value) => (field.to_string(), value),
+                    None => continue,
+                },
+                RuleMapping::Unsupported(rule_type) => {
+                    dropped.add(rule_type);
+                    continue;
+                }
+                // Skip if rule type is not supported
+                RuleMapping::Other if !SINGBOX_RULE_TYPES.contains(rule_type) => continue,
+                RuleMapping::Other => (
+                    to_lower(rule_type)
+                        .replace("-", "_")
+                        .replace("ip_cidr6", "ip_cidr")
+                        .replace("src_", "source_"),
+                    Value::String(to_lower(rule_parts[1])),
+                ),
+            };
 
             // Add to rule object
             let values = rule_obj
@@ -189,7 +266,7 @@ pub fn ruleset_to_sing_box(
                 .or_insert_with(|| Value::Array(Vec::new()));
 
             if let Value::Array(ref mut arr) = values {
-                arr.push(Value::String(rule_value));
+                arr.push(rule_value);
                 total_rules += 1;
             }
         }
@@ -205,16 +282,35 @@ pub fn ruleset_to_sing_box(
         }
     }
 
+    info!(
+        "stage=rules target={} rules={} dropped={}",
+        RuleTarget::SingBox.name(),
+        total_rules,
+        dropped
+    );
+
     // Ensure "route" section exists in the base rule
     if base_rule.get("route").is_none() {
         base_rule["route"] = json!({});
     }
 
-    // Update the rules array in the base rule
+    if final_outbounds.len() > 1 {
+        warn!(
+            "Keeping the last of {} final rules for {}: '{}'",
+            final_outbounds.len(),
+            RuleTarget::SingBox.name(),
+            final_outbounds.last().unwrap()
+        );
+    }
+
+    // Update the rules array in the base rule, the final outbound of the base
+    // is kept when the rulesets have none
     if let Some(route) = base_rule.get_mut("route") {
         if let Some(route_obj) = route.as_object_mut() {
             route_obj.insert("rules".to_string(), rules);
-            route_obj.insert("final".to_string(), Value::String(final_rule));
+            if let Some(outbound) = final_outbounds.pop() {
+                route_obj.insert("final".to_string(), Value::String(outbound));
+            }
         }
     }
 }
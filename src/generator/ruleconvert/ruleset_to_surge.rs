@@ -8,13 +8,15 @@ use crate::utils::ini_reader::IniReader;
 use crate::utils::network::is_link;
 use crate::utils::string::{find_str, starts_with};
 use crate::utils::{file_exists, trim};
-use crate::Settings;
 use lazy_static::lazy_static;
-use log::warn;
+use log::{info, warn};
 use std::collections::HashSet;
 
 use super::common::transform_rule_to_common;
 use super::convert_ruleset::convert_ruleset;
+use super::final_rule::{is_final_rule, normalize_final_rules};
+use super::logical_rule::is_logical_rule;
+use super::rule_matrix::{map_rule_line, DroppedRules, RuleTarget};
 
 lazy_static! {
     static ref QUANX_RULE_TYPES: HashSet<&'static str> = {
@@ -52,6 +54,7 @@ lazy_static! {
         types.insert("PROCESS-NAME");
         types.insert("IN-PORT");
         types.insert("DEST-PORT");
+        types.insert("SRC-PORT");
         types.insert("SRC-IP");
         types
     };
@@ -74,6 +77,7 @@ lazy_static! {
         types.insert("PROCESS-NAME");
         types.insert("IN-PORT");
         types.insert("DEST-PORT");
+        types.insert("SRC-PORT");
         types.insert("SRC-IP");
         types
     };
@@ -99,13 +103,40 @@ lazy_static! {
         types.insert("PROCESS-NAME");
         types.insert("IN-PORT");
         types.insert("DEST-PORT");
+        types.insert("SRC-PORT");
         types.insert("SRC-IP");
         types
     };
 }
 
+/// Remove the final rules from `section` of `base_rule` and return them
+fn take_final_rules(base_rule: &mut IniReader, section: &str) -> Vec<String> {
+    let Ok(items) = base_rule.get_items(section) else {
+        return Vec::new();
+    };
+    let is_final = |(key, value): &(String, String)| key == "{NONAME}" && is_final_rule(value);
+    if !items.iter().any(is_final) {
+        return Vec::new();
+    }
+
+    base_rule.erase_section_by_name(section);
+    let mut finals = Vec::new();
+    for item in items {
+        if is_final(&item) {
+            finals.push(item.1);
+        } else {
+            let _ = base_rule.set(section, &item.0, &item.1);
+        }
+    }
+    finals
+}
+
 /// Converts rulesets to Surge format and updates the INI configuration
 ///
+/// Loon rulesets are written as rules, `ruleset_to_loon` keeps the remote
+/// ones in `[Remote Rule]` before calling this. A single final rule is
+/// written last, see [`normalize_final_rules`].
+///
 /// # Arguments
 ///
 /// * `base_rule` - The base Surge configuration as IniReader
@@ -113,6 +144,7 @@ lazy_static! {
 /// * `surge_ver` - Surge version (or negative for other clients)
 /// * `overwrite_original_rules` - Whether to overwrite original rules
 /// * `remote_path_prefix` - Prefix for remote ruleset URLs
+/// * `max_allowed_rules` - Maximum number of rules to write, 0 for unlimited
 ///
 /// # Returns
 ///
@@ -123,17 +155,16 @@ pub async fn ruleset_to_surge(
     surge_ver: i32,
     overwrite_original_rules: bool,
     remote_path_prefix: &str,
+    max_allowed_rules: usize,
 ) {
-    // Get global settings
-    let settings = Settings::current();
-
     // Set the appropriate section based on surge_ver
-    match surge_ver {
-        0 => base_rule.set_current_section("RoutingRule"), // Mellow
-        -1 => base_rule.set_current_section("filter_local"), // Quantumult X
-        -2 => base_rule.set_current_section("TCP"),        // Quantumult
-        _ => base_rule.set_current_section("Rule"),
-    }
+    let section = match surge_ver {
+        0 => "RoutingRule",   // Mellow
+        -1 => "filter_local", // Quantumult X
+        -2 => "TCP",          // Quantumult
+        _ => "Rule",
+    };
+    base_rule.set_current_section(section);
 
     // Handle overwriting original rules
     if overwrite_original_rules {
@@ -145,14 +176,21 @@ pub async fn ruleset_to_surge(
         }
     }
 
-    // Keep track of all rules to add
-    let mut all_rules = Vec::new();
+    // Keep track of all rules to add, the final rule of the base is merged
+    // with the ones of the rulesets
+    let mut all_rules = if overwrite_original_rules {
+        Vec::new()
+    } else {
+        take_final_rules(base_rule, section)
+    };
     let mut total_rules = 0;
+    let target = RuleTarget::from_surge_ver(surge_ver);
+    let mut dropped = DroppedRules::default();
 
     // Process each ruleset
     for ruleset in ruleset_content_array {
         // Check if we've reached the maximum number of rules
-        if settings.max_allowed_rules > 0 && total_rules >= settings.max_allowed_rules {
+        if max_allowed_rules > 0 && total_rules >= max_allowed_rules {
             break;
         }
 
@@ -162,22 +200,22 @@ pub async fn ruleset_to_surge(
 
         if rule_path.is_empty() {
             // Special case for rules that start with "[]"
-            let mut str_line = ruleset.get_rule_content()[2..].to_string();
-            if str_line == "MATCH" {
-                str_line = "FINAL".to_string();
-            }
+            let str_line = ruleset.get_rule_content()[2..].to_string();
 
-            if surge_ver == -1 || surge_ver == -2 {
-                str_line = transform_rule_to_common(&str_line, rule_group, true);
-            } else {
-                if !starts_with(&str_line, "AND")
-                    && !starts_with(&str_line, "OR")
-                    && !starts_with(&str_line, "NOT")
-                {
-                    str_line = transform_rule_to_common(&str_line, rule_group, false);
-                }
+            if is_logical_rule(&str_line) {
+                warn!(
+                    "Skipping logical rule unsupported by the target: '{}'",
+                    str_line
+                );
+                continue;
             }
 
+            let Some(mut str_line) = map_rule_line(&str_line, target, &mut dropped) else {
+                continue;
+            };
+            str_line =
+                transform_rule_to_common(&str_line, rule_group, surge_ver == -1 || surge_ver == -2);
+
             // Replace double commas with single comma
             str_line = str_line.replace(",,", ",");
             all_rules.push(str_line);
@@ -223,16 +261,6 @@ pub async fn ruleset_to_surge(
 
                     let _ = base_rule.set("filter_remote", "{NONAME}", &str_line);
                     continue;
-                } else if surge_ver == -4 && !remote_path_prefix.is_empty() {
-                    let str_line = format!(
-                        "{}/getruleset?type=1&url={},{}",
-                        remote_path_prefix,
-                        url_safe_base64_encode(rule_path_typed),
-                        rule_group
-                    );
-
-                    let _ = base_rule.set("Remote Rule", "{NONAME}", &str_line);
-                    continue;
                 }
             } else if is_link(rule_path) {
                 if surge_ver > 2 {
@@ -277,10 +305,6 @@ pub async fn ruleset_to_surge(
 
                     let _ = base_rule.set("filter_remote", "{NONAME}", &str_line);
                     continue;
-                } else if surge_ver == -4 {
-                    let str_line = format!("{},{}", rule_path, rule_group);
-                    let _ = base_rule.set("Remote Rule", "{NONAME}", &str_line);
-                    continue;
                 }
             } else {
                 continue;
@@ -307,11 +331,11 @@ pub async fn ruleset_to_surge(
             // Process each rule line
             for line in converted_rules.lines() {
                 // Check if we've reached the maximum number of rules
-                if settings.max_allowed_rules > 0 && total_rules >= settings.max_allowed_rules {
+                if max_allowed_rules > 0 && total_rules >= max_allowed_rules {
                     break;
                 }
 
-                let mut str_line = trim(line).to_string();
+                let str_line = trim(line).to_string();
                 let line_size = str_line.len();
 
                 // Skip empty lines and comments
@@ -322,6 +346,18 @@ pub async fn ruleset_to_surge(
                     continue;
                 }
 
+                if is_logical_rule(&str_line) {
+                    warn!(
+                        "Skipping logical rule unsupported by the target: '{}'",
+                        str_line
+                    );
+                    continue;
+                }
+
+                let Some(mut str_line) = map_rule_line(&str_line, target, &mut dropped) else {
+                    continue;
+                };
+
                 // Check if rule type is supported by the target
                 let rule_supported = match surge_ver {
                     -2 => {
@@ -369,12 +405,7 @@ pub async fn ruleset_to_surge(
                     }
                     str_line = transform_rule_to_common(&str_line, rule_group, true);
                 } else {
-                    if !starts_with(&str_line, "AND")
-                        && !starts_with(&str_line, "OR")
-                        && !starts_with(&str_line, "NOT")
-                    {
-                        str_line = transform_rule_to_common(&str_line, rule_group, false);
-                    }
+                    str_line = transform_rule_to_common(&str_line, rule_group, false);
                 }
 
                 all_rules.push(str_line);
@@ -383,8 +414,56 @@ pub async fn ruleset_to_surge(
         }
     }
 
+    info!(
+        "stage=rules target={} rules={} dropped={}",
+        target.name(),
+        total_rules,
+        dropped
+    );
+
+    normalize_final_rules(&mut all_rules, target);
+
     // Add all collected rules to the INI
     for rule in all_rules {
         let _ = base_rule.set_current("{NONAME}", &rule);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_logical_rules_skipped() {
+        let mut logical = RulesetContent::new("", "Proxy");
+        logical.set_rule_content("[]AND,((OR,((DOMAIN,a.com),(DOMAIN,c.com))),(DST-PORT,443))");
+        let mut domain = RulesetContent::new("", "DIRECT");
+        domain.set_rule_content("[]DOMAIN,b.com");
+        let rulesets = [logical, domain];
+
+        // Surge 4, Quantumult X and Loon
+        for surge_ver in [4, -1, -4] {
+            let mut ini = IniReader::new();
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap()
+                .block_on(ruleset_to_surge(
+                    &mut ini, &rulesets, surge_ver, true, "", 0,
+                ));
+
+            let output = ini.to_string();
+            assert!(
+                !output.contains("AND,"),
+                "surge_ver {}: {}",
+                surge_ver,
+                output
+            );
+            assert!(
+                output.contains("DOMAIN,b.com,DIRECT"),
+                "surge_ver {}",
+                surge_ver
+            );
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use crate::utils::is_empty_option_string;
 use crate::{generator::yaml::clash::output_proxy_types::*, Proxy, ProxyType};
+use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 
 /// Represents a complete Clash configuration output
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -51,7 +51,7 @@ pub struct ClashYamlOutput {
     pub profile: Option<ClashProfile>,
 
     #[serde(flatten)]
-    pub extra_options: HashMap<String, serde_yaml::Value>,
+    pub extra_options: IndexMap<String, serde_yaml::Value>,
 }
 
 /// DNS configuration for Clash
@@ -71,7 +71,7 @@ pub struct ClashDns {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub fallback_filter: Option<ClashDnsFallbackFilter>,
     #[serde(flatten)]
-    pub extra_options: HashMap<String, serde_yaml::Value>,
+    pub extra_options: IndexMap<String, serde_yaml::Value>,
 }
 
 /// DNS fallback filter configuration
@@ -85,7 +85,7 @@ pub struct ClashDnsFallbackFilter {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub domain: Option<Vec<String>>,
     #[serde(flatten)]
-    pub extra_options: HashMap<String, serde_yaml::Value>,
+    pub extra_options: IndexMap<String, serde_yaml::Value>,
 }
 
 /// TUN configuration for Clash
@@ -105,7 +105,7 @@ pub struct ClashTun {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub auto_detect_interface: Option<bool>,
     #[serde(flatten)]
-    pub extra_options: HashMap<String, serde_yaml::Value>,
+    pub extra_options: IndexMap<String, serde_yaml::Value>,
 }
 
 /// Profile settings for Clash
@@ -117,7 +117,7 @@ pub struct ClashProfile {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub store_fake_ip: Option<bool>,
     #[serde(flatten)]
-    pub extra_options: HashMap<String, serde_yaml::Value>,
+    pub extra_options: IndexMap<String, serde_yaml::Value>,
 }
 
 /// Represents a single proxy in Clash configuration
@@ -375,7 +375,7 @@ impl Default for ClashYamlOutput {
             rules: Vec::new(),
             tun: None,
             profile: None,
-            extra_options: HashMap::new(),
+            extra_options: IndexMap::new(),
         }
     }
 }
@@ -383,7 +383,13 @@ impl Default for ClashYamlOutput {
 /// Implementation of From trait for ClashProxyOutput
 impl From<Proxy> for ClashProxyOutput {
     fn from(proxy: Proxy) -> Self {
-        match proxy.proxy_type {
+        let dialer_proxy = proxy
+            .underlying_proxy
+            .clone()
+            .filter(|name| !name.is_empty());
+        let interface = proxy.interface.clone();
+        let routing_mark = proxy.routing_mark;
+        let mut output = match proxy.proxy_type {
             ProxyType::Shadowsocks => ClashProxyOutput::Shadowsocks(ShadowsocksProxy::from(proxy)),
             ProxyType::ShadowsocksR => {
                 ClashProxyOutput::ShadowsocksR(ShadowsocksRProxy::from(proxy))
@@ -423,6 +429,11 @@ impl From<Proxy> for ClashProxyOutput {
                 .build();
                 ClashProxyOutput::Http(HttpProxy::new(common))
             }
-        }
+        };
+        let common = output.common_mut();
+        common.dialer_proxy = dialer_proxy;
+        common.interface = interface;
+        common.routing_mark = routing_mark;
+        output
     }
 }
@@ -1,2 +1,3 @@
 pub mod clash_output;
 pub mod output_proxy_types;
+pub mod sanitize;
@@ -1,5 +1,6 @@
 use super::CommonProxyOptions;
 use crate::models::Proxy;
+use crate::utils::string::format_mbps;
 use crate::utils::{is_empty_option_string, is_u32_option_zero};
 use serde::{Deserialize, Serialize};
 
@@ -20,12 +21,8 @@ pub struct HysteriaProxy {
     pub obfs_protocol: Option<String>,
     #[serde(skip_serializing_if = "is_empty_option_string")]
     pub up: Option<String>,
-    #[serde(rename = "up-speed", skip_serializing_if = "is_u32_option_zero")]
-    pub up_speed: Option<u32>,
     #[serde(skip_serializing_if = "is_empty_option_string")]
     pub down: Option<String>,
-    #[serde(rename = "down-speed", skip_serializing_if = "is_u32_option_zero")]
-    pub down_speed: Option<u32>,
     #[serde(skip_serializing_if = "is_empty_option_string")]
     pub auth: Option<String>,
     #[serde(rename = "auth-str", skip_serializing_if = "is_empty_option_string")]
@@ -67,9 +64,7 @@ impl HysteriaProxy {
             protocol: None,
             obfs_protocol: None,
             up: None,
-            up_speed: None,
             down: None,
-            down_speed: None,
             auth: None,
             auth_str: None,
             obfs: None,
@@ -103,13 +98,11 @@ impl From<Proxy> for HysteriaProxy {
         hysteria.obfs_protocol = proxy.obfs.clone();
 
         if proxy.up_speed > 0 {
-            hysteria.up = Some(format!("{}Mbps", proxy.up_speed));
-            hysteria.up_speed = Some(proxy.up_speed);
+            hysteria.up = Some(format_mbps(proxy.up_speed));
         }
 
         if proxy.down_speed > 0 {
-            hysteria.down = Some(format!("{}Mbps", proxy.down_speed));
-            hysteria.down_speed = Some(proxy.down_speed);
+            hysteria.down = Some(format_mbps(proxy.down_speed));
         }
 
         hysteria.auth = proxy.auth;
@@ -1,5 +1,6 @@
 use super::CommonProxyOptions;
 use crate::models::Proxy;
+use crate::utils::string::format_mbps;
 use crate::utils::{is_empty_option_string, is_u32_option_zero};
 use serde::{Deserialize, Serialize};
 
@@ -99,11 +100,11 @@ impl From<Proxy> for Hysteria2Proxy {
         hysteria2.ports = proxy.ports;
 
         if proxy.up_speed > 0 {
-            hysteria2.up = Some(format!("{}Mbps", proxy.up_speed));
+            hysteria2.up = Some(format_mbps(proxy.up_speed));
         }
 
         if proxy.down_speed > 0 {
-            hysteria2.down = Some(format!("{}Mbps", proxy.down_speed));
+            hysteria2.down = Some(format_mbps(proxy.down_speed));
         }
 
         hysteria2.fingerprint = proxy.fingerprint;
@@ -141,3 +142,38 @@ impl From<Proxy> for Hysteria2Proxy {
         hysteria2
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::generator::yaml::clash::clash_output::ClashProxyOutput;
+    use crate::parser::yaml::clash::parse_clash_yaml;
+    use serde_yaml::Value as YamlValue;
+
+    #[test]
+    fn test_hysteria2_bandwidth_round_trip() {
+        let source = r#"
+proxies:
+  - name: hy2
+    type: hysteria2
+    server: example.com
+    port: 443
+    password: secret
+    up: "30 Mbps"
+    down: 200
+"#;
+        let proxies = parse_clash_yaml(source).unwrap();
+        assert_eq!(proxies[0].up_speed, 30);
+        assert_eq!(proxies[0].down_speed, 200);
+
+        let output = serde_yaml::to_value(ClashProxyOutput::from(proxies[0].clone())).unwrap();
+        assert_eq!(output["up"].as_str(), Some("30 Mbps"));
+        assert_eq!(output["down"].as_str(), Some("200 Mbps"));
+
+        let mut document = serde_yaml::Mapping::new();
+        document.insert("proxies".into(), YamlValue::Sequence(vec![output.clone()]));
+        let reparsed = parse_clash_yaml(&serde_yaml::to_string(&document).unwrap()).unwrap();
+        let again = serde_yaml::to_value(ClashProxyOutput::from(reparsed[0].clone())).unwrap();
+        assert_eq!(again["up"], output["up"]);
+        assert_eq!(again["down"], output["down"]);
+    }
+}
@@ -1,8 +1,10 @@
 use super::CommonProxyOptions;
 use crate::models::Proxy;
 use crate::utils::is_empty_option_string;
+use crate::utils::plugin_options::parse_plugin_options;
+use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde_yaml::Value as YamlValue;
 
 /// Shadowsocks proxy configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -17,7 +19,7 @@ pub struct ShadowsocksProxy {
     #[serde(skip_serializing_if = "is_empty_option_string")]
     pub plugin: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub plugin_opts: Option<HashMap<String, String>>,
+    pub plugin_opts: Option<IndexMap<String, YamlValue>>,
     // Additional fields from the C++ implementation
     #[serde(skip_serializing_if = "Option::is_none")]
     pub udp_over_tcp: Option<bool>,
@@ -35,10 +37,10 @@ pub struct ShadowsocksProxy {
     // - tfo (already implemented as tcp_fast_open)
     // - skip_cert_verify (already implemented)
     // - mptcp (not implemented yet)
-    // - interface (not implemented yet)
-    // - routing_mark (not implemented yet)
+    // - interface (already implemented as interface-name)
+    // - routing_mark (already implemented)
     // - ip_version (not implemented yet)
-    // - dialer_proxy (not implemented yet)
+    // - dialer_proxy (already implemented)
 }
 
 impl ShadowsocksProxy {
@@ -56,6 +58,43 @@ impl ShadowsocksProxy {
     }
 }
 
+/// Typed value of a plugin option, Clash.Meta expects the shadow-tls and
+/// restls `version` as a number and the v2ray-plugin switches as booleans
+fn plugin_opt_value(key: &str, value: &str) -> YamlValue {
+    match key {
+        "version" => match value.parse::<u64>() {
+            Ok(number) => YamlValue::Number(number.into()),
+            Err(_) => YamlValue::String(value.to_string()),
+        },
+        "tls" | "skip-cert-verify" => match value {
+            "" | "true" | "1" => YamlValue::Bool(true),
+            "false" | "0" => YamlValue::Bool(false),
+            _ => YamlValue::String(value.to_string()),
+        },
+        // SIP003 gives the mux concurrency, Clash only turns it on or off
+        "mux" => match value {
+            "" | "true" => YamlValue::Bool(true),
+            "false" => YamlValue::Bool(false),
+            _ => match value.parse::<u64>() {
+                Ok(concurrency) => YamlValue::Bool(concurrency > 0),
+                Err(_) => YamlValue::String(value.to_string()),
+            },
+        },
+        _ => YamlValue::String(value.to_string()),
+    }
+}
+
+/// Clash plugin name and option key of a plugin kept in SIP003 form
+///
+/// Clash calls simple-obfs `obfs` with `mode` and `host` options.
+fn clash_plugin_key<'a>(plugin: &str, key: &'a str) -> &'a str {
+    match (plugin, key) {
+        ("obfs-local" | "simple-obfs", "obfs") => "mode",
+        ("obfs-local" | "simple-obfs", "obfs-host") => "host",
+        _ => key,
+    }
+}
+
 impl From<Proxy> for ShadowsocksProxy {
     fn from(proxy: Proxy) -> Self {
         let common =
@@ -70,15 +109,28 @@ impl From<Proxy> for ShadowsocksProxy {
 
         ss.cipher = proxy.encrypt_method;
         ss.password = proxy.password;
-        ss.plugin = proxy.plugin;
+        let plugin = proxy.plugin.clone().unwrap_or_default();
+        ss.plugin = match plugin.as_str() {
+            "obfs-local" | "simple-obfs" => Some("obfs".to_string()),
+            _ => proxy.plugin,
+        };
 
-        if let Some(plugin_opts) = proxy.plugin_option {
-            let mut opts = HashMap::new();
+        if let Some(plugin_opts) = proxy.plugin_option.filter(|opts| !opts.is_empty()) {
+            let mut opts = IndexMap::new();
 
-            for opt in plugin_opts.split(';') {
-                let parts: Vec<&str> = opt.split('=').collect();
-                if parts.len() == 2 {
-                    opts.insert(parts[0].to_string(), parts[1].to_string());
+            for (key, value) in parse_plugin_options(&plugin_opts) {
+                let key = clash_plugin_key(&plugin, key);
+                opts.insert(key.to_string(), plugin_opt_value(key, value));
+            }
+
+            // A value set in the plugin options is the node's own and wins,
+            // otherwise use the already resolved node > request > global flag
+            if ss.plugin.as_deref() == Some("v2ray-plugin")
+                && opts.get("tls") == Some(&YamlValue::Bool(true))
+                && !opts.contains_key("skip-cert-verify")
+            {
+                if let Some(scv) = proxy.allow_insecure {
+                    opts.insert("skip-cert-verify".to_string(), YamlValue::Bool(scv));
                 }
             }
 
@@ -100,3 +152,75 @@ impl From<Proxy> for ShadowsocksProxy {
         ss
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::generator::yaml::clash::clash_output::ClashProxyOutput;
+    use crate::parser::yaml::clash::parse_clash_yaml;
+
+    const SHADOW_TLS: &str = r#"
+proxies:
+  - name: ss-shadow-tls
+    type: ss
+    server: example.com
+    port: 443
+    cipher: 2022-blake3-aes-128-gcm
+    password: c3MtcGFzc3dvcmQ=
+    plugin: shadow-tls
+    plugin-opts:
+      host: cloud.tencent.com
+      password: shadow_tls_password
+      version: 3
+"#;
+
+    #[test]
+    fn test_shadow_tls_round_trip() {
+        let proxies = parse_clash_yaml(SHADOW_TLS).unwrap();
+        assert_eq!(proxies[0].plugin.as_deref(), Some("shadow-tls"));
+
+        let output = serde_yaml::to_value(ClashProxyOutput::from(proxies[0].clone())).unwrap();
+        assert_eq!(output["plugin"], "shadow-tls");
+        assert_eq!(output["password"], "c3MtcGFzc3dvcmQ=");
+        assert_eq!(output["plugin-opts"]["host"], "cloud.tencent.com");
+        assert_eq!(output["plugin-opts"]["password"], "shadow_tls_password");
+        assert_eq!(output["plugin-opts"]["version"].as_u64(), Some(3));
+    }
+
+    #[test]
+    fn test_restls_passes_through() {
+        let source = SHADOW_TLS
+            .replace("plugin: shadow-tls", "plugin: restls")
+            .replace("version: 3", "version-hint: tls13");
+        let proxies = parse_clash_yaml(&source).unwrap();
+        let output = serde_yaml::to_value(ClashProxyOutput::from(proxies[0].clone())).unwrap();
+        assert_eq!(output["plugin"], "restls");
+        assert_eq!(output["plugin-opts"]["version-hint"], "tls13");
+    }
+
+    #[test]
+    fn test_v2ray_plugin_skip_cert_verify() {
+        let source = r#"
+proxies:
+  - name: ss-v2ray
+    type: ss
+    server: example.com
+    port: 443
+    cipher: aes-128-gcm
+    password: password
+    plugin: v2ray-plugin
+    plugin-opts:
+      mode: websocket
+      tls: true
+"#;
+        let proxies = parse_clash_yaml(source).unwrap();
+
+        let mut node = proxies[0].clone();
+        node.allow_insecure = Some(false);
+        let output = serde_yaml::to_value(ClashProxyOutput::from(node)).unwrap();
+        assert_eq!(output["plugin-opts"]["tls"], true);
+        assert_eq!(output["plugin-opts"]["skip-cert-verify"], false);
+
+        let output = serde_yaml::to_value(ClashProxyOutput::from(proxies[0].clone())).unwrap();
+        assert!(output["plugin-opts"].get("skip-cert-verify").is_none());
+    }
+}
@@ -1,8 +1,8 @@
 use super::CommonProxyOptions;
 use crate::models::Proxy;
 use crate::utils::{is_empty_option_string, is_u32_option_zero};
+use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 
 /// Snell proxy configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -17,7 +17,7 @@ pub struct SnellProxy {
     #[serde(skip_serializing_if = "is_empty_option_string")]
     pub obfs: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub obfs_opts: Option<HashMap<String, String>>,
+    pub obfs_opts: Option<IndexMap<String, String>>,
 }
 
 impl SnellProxy {
@@ -50,7 +50,7 @@ impl From<Proxy> for SnellProxy {
         snell.obfs = proxy.obfs;
 
         if let Some(obfs_opts) = proxy.obfs_param {
-            let mut opts = HashMap::new();
+            let mut opts = IndexMap::new();
 
             for opt in obfs_opts.split(';') {
                 let parts: Vec<&str> = opt.split('=').collect();
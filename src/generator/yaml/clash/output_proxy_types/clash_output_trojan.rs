@@ -1,8 +1,8 @@
-use super::CommonProxyOptions;
+use super::{CommonProxyOptions, SmuxOptions};
 use crate::models::Proxy;
 use crate::utils::is_empty_option_string;
+use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 
 /// Trojan proxy configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -26,7 +26,7 @@ pub struct WsOptions {
     #[serde(skip_serializing_if = "is_empty_option_string")]
     pub path: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub headers: Option<HashMap<String, String>>,
+    pub headers: Option<IndexMap<String, String>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -57,6 +57,8 @@ impl From<Proxy> for TrojanProxy {
                 .tfo(proxy.tcp_fast_open)
                 .skip_cert_verify(proxy.allow_insecure)
                 .sni(proxy.sni.clone())
+                .client_fingerprint(proxy.client_fingerprint.clone())
+                .smux(SmuxOptions::from_proxy(&proxy))
                 .build();
 
         let mut trojan = TrojanProxy::new(common);
@@ -77,7 +79,7 @@ impl From<Proxy> for TrojanProxy {
                     }
 
                     if let Some(host) = &proxy.host {
-                        let mut headers = HashMap::new();
+                        let mut headers = IndexMap::new();
                         headers.insert("Host".to_string(), host.clone());
                         ws_opts.headers = Some(headers);
                     }
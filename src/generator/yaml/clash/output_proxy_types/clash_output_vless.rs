@@ -1,8 +1,8 @@
-use super::CommonProxyOptions;
+use super::{CommonProxyOptions, SmuxOptions};
 use crate::models::Proxy;
 use crate::utils::{is_empty_option_string, is_u32_option_zero};
+use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 
 /// Reality options for VLESS proxy
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,7 +23,7 @@ pub struct HTTPOptions {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub path: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub headers: Option<HashMap<String, Vec<String>>>,
+    pub headers: Option<IndexMap<String, Vec<String>>>,
 }
 
 /// HTTP2 options for VLESS proxy
@@ -54,7 +54,7 @@ pub struct WSOptions {
     #[serde(skip_serializing_if = "is_empty_option_string")]
     pub path: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub headers: Option<HashMap<String, String>>,
+    pub headers: Option<IndexMap<String, String>>,
     #[serde(rename = "max-early-data", skip_serializing_if = "is_u32_option_zero")]
     pub max_early_data: Option<u32>,
     #[serde(
@@ -105,7 +105,7 @@ pub struct VLessProxy {
     #[serde(skip_serializing_if = "is_empty_option_string")]
     pub ws_path: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub ws_headers: Option<HashMap<String, String>>,
+    pub ws_headers: Option<IndexMap<String, String>>,
     #[serde(skip_serializing_if = "is_empty_option_string")]
     pub servername: Option<String>,
     #[serde(skip_serializing_if = "is_empty_option_string")]
@@ -152,6 +152,7 @@ impl From<Proxy> for VLessProxy {
                 .tfo(proxy.tcp_fast_open)
                 .skip_cert_verify(proxy.allow_insecure)
                 .sni(proxy.sni.clone())
+                .smux(SmuxOptions::from_proxy(&proxy))
                 .build();
 
         let mut vless = VLessProxy::new(common);
@@ -166,12 +167,18 @@ impl From<Proxy> for VLessProxy {
                 vless.tls = Some(vless_proxy.tls);
                 vless.network = vless_proxy.network.clone();
                 vless.packet_addr = vless_proxy.packet_addr;
-                // vless should force udp to true
-                vless.common.udp = Some(vless_proxy.udp);
+                // The node's own udp flag has already been resolved against the
+                // defaults, only fall back to the VLESS default when undefined
+                if vless.common.udp.is_none() {
+                    vless.common.udp = Some(vless_proxy.udp);
+                }
                 vless.xudp = vless_proxy.xudp;
                 vless.packet_encoding = vless_proxy.packet_encoding.clone();
                 vless.fingerprint = vless_proxy.fingerprint.clone();
-                vless.client_fingerprint = vless_proxy.client_fingerprint.clone();
+                vless.client_fingerprint = vless_proxy
+                    .client_fingerprint
+                    .clone()
+                    .or_else(|| proxy.client_fingerprint.clone());
 
                 // 处理 ALPN
                 if !vless_proxy.alpn.is_empty() {
@@ -253,7 +260,7 @@ impl From<Proxy> for VLessProxy {
                         }
 
                         if let Some(host) = &proxy.host {
-                            let mut headers = HashMap::new();
+                            let mut headers = IndexMap::new();
                             headers.insert("Host".to_string(), host.clone());
                             vless.ws_headers = Some(headers);
                         }
@@ -281,3 +288,50 @@ impl From<Proxy> for VLessProxy {
         vless
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::generator::yaml::clash::clash_output::ClashProxyOutput;
+    use crate::parser::yaml::clash::parse_clash_yaml;
+    use serde_yaml::Value as YamlValue;
+
+    #[test]
+    fn test_vless_utls_smux_round_trip() {
+        let source = r#"
+proxies:
+  - name: vless-utls
+    type: vless
+    server: example.com
+    port: 443
+    uuid: 00000000-0000-0000-0000-000000000000
+    network: tcp
+    tls:
+      enabled: true
+      utls:
+        enabled: true
+        fingerprint: chrome
+    smux:
+      enabled: true
+      max-streams: 8
+"#;
+        let proxies = parse_clash_yaml(source).unwrap();
+        assert_eq!(proxies.len(), 1);
+        assert_eq!(proxies[0].client_fingerprint.as_deref(), Some("chrome"));
+        assert_eq!(proxies[0].smux_enabled, Some(true));
+        assert_eq!(proxies[0].smux_max_streams, Some(8));
+
+        let output = serde_yaml::to_value(ClashProxyOutput::from(proxies[0].clone())).unwrap();
+        assert_eq!(output["tls"], YamlValue::Bool(true));
+        assert_eq!(output["client-fingerprint"].as_str(), Some("chrome"));
+        assert_eq!(output["smux"]["enabled"], YamlValue::Bool(true));
+        assert_eq!(output["smux"]["max-streams"].as_u64(), Some(8));
+
+        // The generated node must parse back to the same settings
+        let mut document = serde_yaml::Mapping::new();
+        document.insert("proxies".into(), YamlValue::Sequence(vec![output]));
+        let reparsed = parse_clash_yaml(&serde_yaml::to_string(&document).unwrap()).unwrap();
+        assert_eq!(reparsed[0].client_fingerprint.as_deref(), Some("chrome"));
+        assert_eq!(reparsed[0].smux_enabled, Some(true));
+        assert_eq!(reparsed[0].smux_max_streams, Some(8));
+    }
+}
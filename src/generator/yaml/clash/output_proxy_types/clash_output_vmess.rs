@@ -1,8 +1,8 @@
-use super::CommonProxyOptions;
+use super::{CommonProxyOptions, SmuxOptions};
 use crate::models::Proxy;
 use crate::utils::{is_empty_option_string, is_u32_option_zero};
+use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 
 /// Vmess proxy configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -17,6 +17,8 @@ pub struct VmessProxy {
     #[serde(skip_serializing_if = "is_empty_option_string")]
     pub cipher: Option<String>,
     #[serde(skip_serializing_if = "is_empty_option_string")]
+    pub servername: Option<String>,
+    #[serde(skip_serializing_if = "is_empty_option_string")]
     pub network: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub ws_opts: Option<VmessWsOptions>,
@@ -34,7 +36,7 @@ pub struct VmessWsOptions {
     #[serde(skip_serializing_if = "is_empty_option_string")]
     pub path: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub headers: Option<HashMap<String, String>>,
+    pub headers: Option<IndexMap<String, String>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -43,7 +45,7 @@ pub struct VmessHttpOptions {
     #[serde(skip_serializing_if = "is_empty_option_string")]
     pub path: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub headers: Option<HashMap<String, String>>,
+    pub headers: Option<IndexMap<String, String>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -70,6 +72,7 @@ impl VmessProxy {
             uuid: None,
             alter_id: None,
             cipher: None,
+            servername: None,
             network: None,
             ws_opts: None,
             http_opts: None,
@@ -86,7 +89,10 @@ impl From<Proxy> for VmessProxy {
                 .udp(proxy.udp)
                 .tfo(proxy.tcp_fast_open)
                 .skip_cert_verify(proxy.allow_insecure)
+                .tls(proxy.tls_secure.then_some(true))
                 .sni(proxy.sni.clone())
+                .client_fingerprint(proxy.client_fingerprint.clone())
+                .smux(SmuxOptions::from_proxy(&proxy))
                 .build();
 
         let mut vmess = VmessProxy::new(common);
@@ -94,6 +100,9 @@ impl From<Proxy> for VmessProxy {
         vmess.uuid = proxy.user_id.clone();
         vmess.alter_id = Some(proxy.alter_id as u32);
         vmess.cipher = proxy.encrypt_method.clone();
+        if proxy.tls_secure {
+            vmess.servername = proxy.server_name.clone();
+        }
         vmess.network = proxy.transfer_protocol.clone();
 
         if let Some(network) = &proxy.transfer_protocol {
@@ -109,7 +118,7 @@ impl From<Proxy> for VmessProxy {
                     }
 
                     if let Some(host) = &proxy.host {
-                        let mut headers = HashMap::new();
+                        let mut headers = IndexMap::new();
                         headers.insert("Host".to_string(), host.clone());
                         ws_opts.headers = Some(headers);
                     }
@@ -127,7 +136,7 @@ impl From<Proxy> for VmessProxy {
                     }
 
                     if let Some(host) = &proxy.host {
-                        let mut headers = HashMap::new();
+                        let mut headers = IndexMap::new();
                         headers.insert("Host".to_string(), host.clone());
                         http_opts.headers = Some(headers);
                     }
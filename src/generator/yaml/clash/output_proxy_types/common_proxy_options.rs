@@ -1,5 +1,25 @@
-use crate::utils::is_empty_option_string;
+use crate::models::Proxy;
+use crate::utils::{is_empty_option_string, is_u32_option_zero};
 use serde::{Deserialize, Serialize};
+
+/// smux multiplexing options for Clash.Meta
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SmuxOptions {
+    pub enabled: bool,
+    #[serde(skip_serializing_if = "is_u32_option_zero")]
+    pub max_streams: Option<u32>,
+}
+
+impl SmuxOptions {
+    /// Build smux options from a proxy, `None` when smux was never configured
+    pub fn from_proxy(proxy: &Proxy) -> Option<Self> {
+        proxy.smux_enabled.map(|enabled| Self {
+            enabled,
+            max_streams: proxy.smux_max_streams,
+        })
+    }
+}
 /// Common proxy options that can be used across different proxy types
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
@@ -24,14 +44,20 @@ pub struct CommonProxyOptions {
     // Additional fields from the Go implementations
     #[serde(skip_serializing_if = "Option::is_none")]
     pub mptcp: Option<bool>,
-    #[serde(skip_serializing_if = "is_empty_option_string")]
+    #[serde(
+        rename = "interface-name",
+        alias = "interface",
+        skip_serializing_if = "is_empty_option_string"
+    )]
     pub interface: Option<String>, // interface-name in ClashMeta
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub routing_mark: Option<i32>, // routing-mark in ClashMeta
+    pub routing_mark: Option<u32>, // routing-mark in ClashMeta
     #[serde(skip_serializing_if = "Option::is_none")]
     pub ip_version: Option<String>, // ip-version in ClashMeta
     #[serde(skip_serializing_if = "Option::is_none")]
     pub dialer_proxy: Option<String>, // dialer-proxy in ClashMeta
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub smux: Option<SmuxOptions>,
 }
 
 impl CommonProxyOptions {
@@ -53,6 +79,7 @@ impl CommonProxyOptions {
             routing_mark: None,
             ip_version: None,
             dialer_proxy: None,
+            smux: None,
         }
     }
 
@@ -125,7 +152,7 @@ impl CommonProxyOptionsBuilder {
     }
 
     /// Set routing_mark option
-    pub fn routing_mark(mut self, value: Option<i32>) -> Self {
+    pub fn routing_mark(mut self, value: Option<u32>) -> Self {
         self.common.routing_mark = value;
         self
     }
@@ -142,6 +169,12 @@ impl CommonProxyOptionsBuilder {
         self
     }
 
+    /// Set smux option
+    pub fn smux(mut self, value: Option<SmuxOptions>) -> Self {
+        self.common.smux = value;
+        self
+    }
+
     /// Build the final CommonProxyOptions
     pub fn build(self) -> CommonProxyOptions {
         self.common
@@ -0,0 +1,260 @@
+//! Normalization pass over generated Clash YAML
+//!
+//! Clash.Meta parses configurations strictly and rejects some values that the
+//! original Clash core silently ignored. This pass runs over the final YAML node
+//! and fixes those quirks in place, logging every change it makes.
+
+use log::info;
+use serde_yaml::{Mapping, Value as YamlValue};
+
+/// Proxy fields that must be kept even when empty
+const REQUIRED_PROXY_FIELDS: [&str; 5] = ["name", "type", "server", "password", "uuid"];
+
+/// Proxy fields that Clash.Meta expects to be numbers
+const NUMERIC_PROXY_FIELDS: [&str; 5] =
+    ["port", "alterId", "version", "udp-over-tcp-version", "mtu"];
+
+/// Proxy group fields that Clash.Meta expects to be numbers
+const NUMERIC_GROUP_FIELDS: [&str; 3] = ["interval", "tolerance", "timeout"];
+
+/// Group fields that are only valid for some group types
+fn invalid_group_fields(group_type: &str) -> &'static [&'static str] {
+    match group_type {
+        "select" | "relay" => &[
+            "url",
+            "interval",
+            "tolerance",
+            "lazy",
+            "timeout",
+            "strategy",
+        ],
+        "url-test" => &["strategy"],
+        "fallback" => &["tolerance", "strategy"],
+        "load-balance" => &["tolerance"],
+        _ => &[],
+    }
+}
+
+fn key_str(key: &YamlValue) -> &str {
+    key.as_str().unwrap_or_default()
+}
+
+fn entry_name(map: &Mapping) -> String {
+    map.get("name")
+        .and_then(|name| name.as_str())
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Remove empty string values from a mapping and its nested mappings, except `keep` fields
+fn remove_empty_strings(map: &mut Mapping, keep: &[&str], owner: &str) -> usize {
+    let mut fixes = 0;
+    let empty_keys: Vec<YamlValue> = map
+        .iter()
+        .filter(|(key, value)| {
+            value.as_str().is_some_and(|s| s.is_empty()) && !keep.contains(&key_str(key))
+        })
+        .map(|(key, _)| key.clone())
+        .collect();
+    for key in empty_keys {
+        info!(
+            "Clash output '{}': removed empty field '{}'",
+            owner,
+            key_str(&key)
+        );
+        map.shift_remove(&key);
+        fixes += 1;
+    }
+
+    for (_, value) in map.iter_mut() {
+        if let YamlValue::Mapping(nested) = value {
+            fixes += remove_empty_strings(nested, &[], owner);
+        }
+    }
+    fixes
+}
+
+/// Convert numeric strings to numbers for the given fields
+fn coerce_numbers(map: &mut Mapping, fields: &[&str], owner: &str) -> usize {
+    let mut fixes = 0;
+    for field in fields {
+        let number = match map.get(*field).and_then(|value| value.as_str()) {
+            Some(value) => match value.trim().parse::<u64>() {
+                Ok(number) => number,
+                Err(_) => continue,
+            },
+            None => continue,
+        };
+        info!("Clash output '{}': coerced '{}' to a number", owner, field);
+        map.insert(YamlValue::from(*field), YamlValue::from(number));
+        fixes += 1;
+    }
+    fixes
+}
+
+/// Remove fields from a mapping, logging each one that was present
+fn remove_fields(map: &mut Mapping, fields: &[&str], owner: &str, reason: &str) -> usize {
+    let mut fixes = 0;
+    for field in fields {
+        if map.shift_remove(*field).is_some() {
+            info!("Clash output '{}': removed '{}' {}", owner, field, reason);
+            fixes += 1;
+        }
+    }
+    fixes
+}
+
+fn sanitize_proxy(proxy: &mut Mapping) -> usize {
+    let name = entry_name(proxy);
+    let mut fixes = remove_empty_strings(proxy, &REQUIRED_PROXY_FIELDS, &name);
+    fixes += coerce_numbers(proxy, &NUMERIC_PROXY_FIELDS, &name);
+
+    // Meta rejects skip-cert-verify inside obfs plugin options
+    let is_obfs = proxy.get("plugin").and_then(|p| p.as_str()) == Some("obfs");
+    if is_obfs {
+        if let Some(YamlValue::Mapping(opts)) = proxy.get_mut("plugin-opts") {
+            fixes += remove_fields(opts, &["skip-cert-verify"], &name, "from obfs plugin-opts");
+        }
+    }
+    fixes
+}
+
+fn sanitize_group(group: &mut Mapping) -> usize {
+    let name = entry_name(group);
+    let mut fixes = remove_empty_strings(group, &["name", "type"], &name);
+    fixes += coerce_numbers(group, &NUMERIC_GROUP_FIELDS, &name);
+
+    let group_type = group
+        .get("type")
+        .and_then(|t| t.as_str())
+        .unwrap_or_default()
+        .to_string();
+    let reason = format!("which is not valid for {} groups", group_type);
+    fixes += remove_fields(group, invalid_group_fields(&group_type), &name, &reason);
+    fixes
+}
+
+/// Normalize a generated Clash configuration for Clash.Meta
+///
+/// Removes empty optional fields, coerces numeric fields stored as strings and
+/// drops group fields that are invalid for the group type.
+///
+/// # Returns
+/// The number of fixes applied
+pub fn sanitize_clash_yaml(yaml_node: &mut YamlValue) -> usize {
+    let mut fixes = 0;
+
+    for key in ["proxies", "Proxy"] {
+        if let Some(YamlValue::Sequence(proxies)) = yaml_node.get_mut(key) {
+            for proxy in proxies.iter_mut() {
+                if let YamlValue::Mapping(map) = proxy {
+                    fixes += sanitize_proxy(map);
+                }
+            }
+        }
+    }
+
+    for key in ["proxy-groups", "Proxy Group"] {
+        if let Some(YamlValue::Sequence(groups)) = yaml_node.get_mut(key) {
+            for group in groups.iter_mut() {
+                if let YamlValue::Mapping(map) = group {
+                    fixes += sanitize_group(map);
+                }
+            }
+        }
+    }
+
+    fixes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_dirty_output() {
+        let mut node: YamlValue = serde_yaml::from_str(
+            r#"
+proxies:
+  - name: vmess-node
+    type: vmess
+    server: example.com
+    port: "443"
+    uuid: 00000000-0000-0000-0000-000000000000
+    alterId: "0"
+    cipher: ""
+    ws-opts:
+      path: ""
+  - name: obfs-node
+    type: ss
+    server: 1.2.3.4
+    port: 8388
+    cipher: aes-128-gcm
+    password: ""
+    plugin: obfs
+    plugin-opts:
+      mode: tls
+      skip-cert-verify: true
+proxy-groups:
+  - name: Select
+    type: select
+    proxies: [vmess-node, obfs-node]
+    tolerance: 150
+    url: http://www.gstatic.com/generate_204
+  - name: Auto
+    type: url-test
+    proxies: [vmess-node]
+    url: http://www.gstatic.com/generate_204
+    interval: "300"
+    tolerance: "50"
+"#,
+        )
+        .unwrap();
+
+        let fixes = sanitize_clash_yaml(&mut node);
+
+        let expected: YamlValue = serde_yaml::from_str(
+            r#"
+proxies:
+  - name: vmess-node
+    type: vmess
+    server: example.com
+    port: 443
+    uuid: 00000000-0000-0000-0000-000000000000
+    alterId: 0
+    ws-opts: {}
+  - name: obfs-node
+    type: ss
+    server: 1.2.3.4
+    port: 8388
+    cipher: aes-128-gcm
+    password: ""
+    plugin: obfs
+    plugin-opts:
+      mode: tls
+proxy-groups:
+  - name: Select
+    type: select
+    proxies: [vmess-node, obfs-node]
+  - name: Auto
+    type: url-test
+    proxies: [vmess-node]
+    url: http://www.gstatic.com/generate_204
+    interval: 300
+    tolerance: 50
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(node, expected);
+        assert_eq!(fixes, 9);
+    }
+
+    #[test]
+    fn test_sanitize_clean_output_untouched() {
+        let source = "proxies:\n- name: a\n  type: ss\n  server: 1.2.3.4\n  port: 8388\n";
+        let mut node: YamlValue = serde_yaml::from_str(source).unwrap();
+        assert_eq!(sanitize_clash_yaml(&mut node), 0);
+        assert_eq!(serde_yaml::to_string(&node).unwrap(), source);
+    }
+}
@@ -0,0 +1,269 @@
+//! Deep merge of YAML configurations
+//!
+//! Used to lay a Clash base from an external config over the base of the
+//! settings: nested sections such as `dns`, `tun` or `sniffer` are merged key
+//! by key instead of the whole top-level key being replaced.
+
+use std::collections::HashMap;
+
+use log::warn;
+use serde_yaml::Value as YamlValue;
+
+use crate::generator::ruleconvert::final_rule::normalize_final_rules;
+use crate::generator::ruleconvert::RuleTarget;
+
+/// How a sequence of the overlay is combined with the one of the base
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SequenceMerge {
+    /// The overlay sequence replaces the base one
+    Replace,
+    /// The overlay items follow the base ones
+    Append,
+    /// The overlay rules follow the base ones, the last final rule of either
+    /// is written after them so the added rules can still match
+    AppendRules,
+}
+
+/// Sequence strategies of a merge
+#[derive(Debug, Clone)]
+pub struct MergeOptions {
+    /// Strategies by key path, e.g. `rules` or `dns.nameserver`
+    pub sequences: HashMap<String, SequenceMerge>,
+    /// Strategy of the sequences not listed
+    pub default_sequence: SequenceMerge,
+}
+
+impl Default for MergeOptions {
+    fn default() -> Self {
+        MergeOptions {
+            sequences: HashMap::new(),
+            default_sequence: SequenceMerge::Replace,
+        }
+    }
+}
+
+impl MergeOptions {
+    /// Strategies for Clash bases: rules, listeners and proxies add up,
+    /// every other sequence such as the DNS servers is replaced
+    pub fn clash() -> Self {
+        let mut options = Self::default();
+        options.sequence("rules", SequenceMerge::AppendRules);
+        for key in ["listeners", "proxies"] {
+            options.sequence(key, SequenceMerge::Append);
+        }
+        options
+    }
+
+    /// Set the strategy of the sequence at `path`
+    pub fn sequence(&mut self, path: &str, merge: SequenceMerge) -> &mut Self {
+        self.sequences.insert(path.to_string(), merge);
+        self
+    }
+
+    fn sequence_merge(&self, path: &str) -> SequenceMerge {
+        self.sequences
+            .get(path)
+            .copied()
+            .unwrap_or(self.default_sequence)
+    }
+}
+
+/// Merge `overlay` into `base`, values of the overlay win
+///
+/// Mappings are merged key by key, sequences follow `options` and any other
+/// value of the overlay replaces the one of the base.
+pub fn deep_merge_yaml(base: &mut YamlValue, overlay: YamlValue, options: &MergeOptions) {
+    merge_at(base, overlay, "", options);
+}
+
+fn merge_at(base: &mut YamlValue, overlay: YamlValue, path: &str, options: &MergeOptions) {
+    match (base, overlay) {
+        (YamlValue::Mapping(base_map), YamlValue::Mapping(overlay_map)) => {
+            for (key, value) in overlay_map {
+                let child_path = match key.as_str() {
+                    Some(name) if path.is_empty() => name.to_string(),
+                    Some(name) => format!("{}.{}", path, name),
+                    None => path.to_string(),
+                };
+                match base_map.get_mut(&key) {
+                    Some(existing) => merge_at(existing, value, &child_path, options),
+                    None => {
+                        base_map.insert(key, value);
+                    }
+                }
+            }
+        }
+        (YamlValue::Sequence(base_seq), YamlValue::Sequence(overlay_seq))
+            if options.sequence_merge(path) == SequenceMerge::Append =>
+        {
+            base_seq.extend(overlay_seq);
+        }
+        (YamlValue::Sequence(base_seq), YamlValue::Sequence(overlay_seq))
+            if options.sequence_merge(path) == SequenceMerge::AppendRules =>
+        {
+            base_seq.extend(overlay_seq);
+            let mut rules: Vec<String> = base_seq
+                .iter()
+                .filter_map(|rule| rule.as_str().map(str::to_string))
+                .collect();
+            // Rules that are not strings are left where they are
+            if rules.len() == base_seq.len() {
+                normalize_final_rules(&mut rules, RuleTarget::Clash);
+                *base_seq = rules.into_iter().map(YamlValue::String).collect();
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Lay the Clash base `overlay` over the base `base`
+///
+/// When either of them is empty or not valid YAML, the other one is used as
+/// it is, the overlay taking precedence. The merged base is written again
+/// from its values, so the comments of both bases are not kept.
+pub fn merge_clash_bases(base: &str, overlay: &str) -> String {
+    if base.trim().is_empty() {
+        return overlay.to_string();
+    }
+    if overlay.trim().is_empty() {
+        return base.to_string();
+    }
+
+    let parsed = serde_yaml::from_str::<YamlValue>(base)
+        .and_then(|base| serde_yaml::from_str::<YamlValue>(overlay).map(|overlay| (base, overlay)));
+    let (mut merged, overlay_node) = match parsed {
+        Ok(nodes) => nodes,
+        Err(e) => {
+            warn!("Not merging Clash bases, failed to parse them: {}", e);
+            return overlay.to_string();
+        }
+    };
+    deep_merge_yaml(&mut merged, overlay_node, &MergeOptions::clash());
+
+    serde_yaml::to_string(&merged).unwrap_or_else(|e| {
+        warn!("Failed to serialize the merged Clash base: {}", e);
+        overlay.to_string()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GLOBAL_BASE: &str = r#"
+port: 7890
+mode: rule
+dns:
+  enable: true
+  ipv6: false
+  nameserver:
+    - 223.5.5.5
+    - 119.29.29.29
+  fallback:
+    - tls://1.1.1.1
+tun:
+  enable: false
+  stack: system
+rules:
+  - DOMAIN-SUFFIX,local,DIRECT
+  - MATCH,Proxy
+"#;
+
+    const EXTERNAL_BASE: &str = r#"
+mode: global
+dns:
+  ipv6: true
+  nameserver:
+    - https://dns.example/dns-query
+tun:
+  enable: true
+listeners:
+  - name: mixed-in
+    type: mixed
+    port: 7891
+rules:
+  - GEOIP,CN,DIRECT
+"#;
+
+    #[test]
+    fn test_merge_clash_bases() {
+        let merged: YamlValue =
+            serde_yaml::from_str(&merge_clash_bases(GLOBAL_BASE, EXTERNAL_BASE)).unwrap();
+        let expected: YamlValue = serde_yaml::from_str(
+            r#"
+port: 7890
+mode: global
+dns:
+  enable: true
+  ipv6: true
+  nameserver:
+    - https://dns.example/dns-query
+  fallback:
+    - tls://1.1.1.1
+tun:
+  enable: true
+  stack: system
+rules:
+  - DOMAIN-SUFFIX,local,DIRECT
+  - GEOIP,CN,DIRECT
+  - MATCH,Proxy
+listeners:
+  - name: mixed-in
+    type: mixed
+    port: 7891
+"#,
+        )
+        .unwrap();
+        assert_eq!(merged, expected);
+    }
+
+    #[test]
+    fn test_sequence_strategy_by_path() {
+        let mut base: YamlValue = serde_yaml::from_str(GLOBAL_BASE).unwrap();
+        let overlay: YamlValue = serde_yaml::from_str(EXTERNAL_BASE).unwrap();
+        let mut options = MergeOptions::default();
+        options.sequence("dns.nameserver", SequenceMerge::Append);
+        deep_merge_yaml(&mut base, overlay, &options);
+
+        let servers: Vec<&str> = base["dns"]["nameserver"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .filter_map(YamlValue::as_str)
+            .collect();
+        assert_eq!(
+            servers,
+            ["223.5.5.5", "119.29.29.29", "https://dns.example/dns-query"]
+        );
+        // Not listed, so replaced
+        assert_eq!(base["rules"].as_sequence().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_final_rule_of_overlay_wins() {
+        let overlay = "rules:\n  - GEOIP,CN,DIRECT\n  - FINAL,DIRECT\n";
+        let merged: YamlValue =
+            serde_yaml::from_str(&merge_clash_bases(GLOBAL_BASE, overlay)).unwrap();
+        let rules: Vec<&str> = merged["rules"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .filter_map(YamlValue::as_str)
+            .collect();
+        assert_eq!(
+            rules,
+            [
+                "DOMAIN-SUFFIX,local,DIRECT",
+                "GEOIP,CN,DIRECT",
+                "MATCH,DIRECT"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_empty_or_invalid_base_is_skipped() {
+        assert_eq!(merge_clash_bases("", EXTERNAL_BASE), EXTERNAL_BASE);
+        assert_eq!(merge_clash_bases(GLOBAL_BASE, " \n"), GLOBAL_BASE);
+        assert_eq!(merge_clash_bases("port: [", EXTERNAL_BASE), EXTERNAL_BASE);
+    }
+}
@@ -1,2 +1,4 @@
 pub mod clash;
+pub mod merge;
 pub mod proxy_group_output;
+pub mod style;
@@ -56,6 +56,10 @@ where
                 map.serialize_entry("interval", &group.interval)?;
             }
 
+            if group.timeout > 0 {
+                map.serialize_entry("timeout", &clash_timeout(group.timeout))?;
+            }
+
             if group.tolerance > 0 {
                 map.serialize_entry("tolerance", &group.tolerance)?;
             }
@@ -73,6 +77,10 @@ where
                 map.serialize_entry("interval", &group.interval)?;
             }
 
+            if group.timeout > 0 {
+                map.serialize_entry("timeout", &clash_timeout(group.timeout))?;
+            }
+
             if group.tolerance > 0 {
                 map.serialize_entry("tolerance", &group.tolerance)?;
             }
@@ -85,6 +93,10 @@ where
                 map.serialize_entry("interval", &group.interval)?;
             }
 
+            if group.timeout > 0 {
+                map.serialize_entry("timeout", &clash_timeout(group.timeout))?;
+            }
+
             if group.tolerance > 0 {
                 map.serialize_entry("tolerance", &group.tolerance)?;
             }
@@ -155,7 +167,7 @@ pub struct ClashProxyGroup {
     #[serde(skip_serializing_if = "is_zero_u32")]
     pub interval: u32,
 
-    /// Timeout in seconds for tests
+    /// Timeout in milliseconds for tests, as expected by Clash.Meta
     #[serde(skip_serializing_if = "is_zero_u32")]
     pub timeout: u32,
 
@@ -184,6 +196,11 @@ pub struct ClashProxyGroup {
     pub evaluate_before_use: bool,
 }
 
+/// Group timeouts are configured in seconds, Clash.Meta reads them as milliseconds
+fn clash_timeout(timeout: u32) -> u32 {
+    timeout.saturating_mul(1000)
+}
+
 // Helper functions for serde skip conditions
 fn is_zero_u32(val: &u32) -> bool {
     *val == 0
@@ -230,11 +247,13 @@ impl From<&ProxyGroupConfig> for ClashProxyGroup {
                 clash_group.lazy = config.lazy;
                 clash_group.url = config.url.clone();
                 clash_group.interval = config.interval;
+                clash_group.timeout = clash_timeout(config.timeout);
                 clash_group.tolerance = config.tolerance;
             }
             ProxyGroupType::URLTest | ProxyGroupType::Smart | ProxyGroupType::Fallback => {
                 clash_group.url = config.url.clone();
                 clash_group.interval = config.interval;
+                clash_group.timeout = clash_timeout(config.timeout);
                 clash_group.tolerance = config.tolerance;
 
                 // Only URLTest and Smart use lazy loading
@@ -361,3 +380,36 @@ pub fn example_clash_groups() -> Vec<ClashProxyGroup> {
     // Convert to Clash format
     convert_proxy_groups(&groups, None)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ProxyGroupConfigs;
+    use crate::settings::ini_bindings::FromIni;
+
+    #[test]
+    fn test_group_times_in_clash_output() {
+        let lines = vec![
+            "Auto`url-test`.*`http://www.gstatic.com/generate_204`300,5,100".to_string(),
+            "Smart`smart`.*`http://www.gstatic.com/generate_204,600,3,50".to_string(),
+        ];
+        let groups = ProxyGroupConfigs::from_ini(&lines);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].proxies, vec![".*".to_string()]);
+        assert_eq!(
+            (groups[0].interval, groups[0].timeout, groups[0].tolerance),
+            (300, 5, 100)
+        );
+        assert_eq!(groups[1].url, "http://www.gstatic.com/generate_204");
+        assert_eq!(
+            (groups[1].interval, groups[1].timeout, groups[1].tolerance),
+            (600, 3, 50)
+        );
+
+        let yaml = serde_yaml::to_string(&convert_proxy_groups(&groups, None)).unwrap();
+        assert!(yaml.contains("interval: 300"));
+        assert!(yaml.contains("timeout: 5000"));
+        assert!(yaml.contains("tolerance: 100"));
+        assert!(yaml.contains("timeout: 3000"));
+    }
+}
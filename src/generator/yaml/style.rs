@@ -0,0 +1,162 @@
+//! Styled YAML emission for Clash configurations
+//!
+//! serde_yaml always emits block style, so the `flow` and `compact` styles for the
+//! proxies and proxy groups sections are rendered here by hand, following the
+//! behavior of the C++ version:
+//! - `block`: every item is emitted as a nested block mapping
+//! - `flow`: block sequence, each item on a single line as a flow mapping
+//! - `compact`: the whole sequence on a single line as a flow sequence
+
+use serde_yaml::{Mapping, Value as YamlValue};
+
+/// Check whether a style value is one of the supported Clash output styles
+pub fn is_valid_style(style: &str) -> bool {
+    matches!(style, "flow" | "block" | "compact")
+}
+
+/// Render a scalar so that it is also valid inside flow collections
+fn scalar_to_flow_string(value: &YamlValue) -> String {
+    let rendered = serde_yaml::to_string(value).unwrap_or_default();
+    let rendered = rendered.trim_end_matches('\n');
+
+    match value {
+        YamlValue::String(s) => {
+            let quoted = rendered.starts_with('\'') || rendered.starts_with('"');
+            if rendered.contains('\n') || (!quoted && s.contains([',', '[', ']', '{', '}'])) {
+                // Double quoted JSON strings are valid YAML scalars
+                serde_json::to_string(s).unwrap_or_default()
+            } else {
+                rendered.to_string()
+            }
+        }
+        _ => rendered.to_string(),
+    }
+}
+
+/// Render a YAML value on a single line using flow style
+pub fn to_flow_string(value: &YamlValue) -> String {
+    match value {
+        YamlValue::Sequence(items) => {
+            let items: Vec<String> = items.iter().map(to_flow_string).collect();
+            format!("[{}]", items.join(", "))
+        }
+        YamlValue::Mapping(map) => {
+            let entries: Vec<String> = map
+                .iter()
+                .map(|(key, value)| format!("{}: {}", to_flow_string(key), to_flow_string(value)))
+                .collect();
+            format!("{{{}}}", entries.join(", "))
+        }
+        YamlValue::Tagged(tagged) => format!("{} {}", tagged.tag, to_flow_string(&tagged.value)),
+        _ => scalar_to_flow_string(value),
+    }
+}
+
+/// Render a single top level sequence section in the requested style
+fn render_section(key: &YamlValue, items: &[YamlValue], style: &str) -> String {
+    let key = to_flow_string(key);
+    if style == "compact" || items.is_empty() {
+        let items: Vec<String> = items.iter().map(to_flow_string).collect();
+        return format!("{}: [{}]\n", key, items.join(", "));
+    }
+
+    let mut output = format!("{}:\n", key);
+    for item in items {
+        output.push_str("  - ");
+        output.push_str(&to_flow_string(item));
+        output.push('\n');
+    }
+    output
+}
+
+/// Serialize a YAML document, emitting the listed top level sections in the given styles
+///
+/// # Arguments
+/// * `node` - The document to serialize
+/// * `section_styles` - Pairs of top level key and style, sections not listed use block style
+pub fn to_styled_string(node: &YamlValue, section_styles: &[(&str, &str)]) -> String {
+    let map = match node.as_mapping() {
+        Some(map) => map,
+        None => return serde_yaml::to_string(node).unwrap_or_default(),
+    };
+
+    let mut output = String::new();
+    for (key, value) in map {
+        let style = key.as_str().and_then(|name| {
+            section_styles
+                .iter()
+                .find(|(section, _)| *section == name)
+                .map(|(_, style)| *style)
+        });
+
+        match (style, value) {
+            (Some(style), YamlValue::Sequence(items)) if style != "block" => {
+                output.push_str(&render_section(key, items, style));
+            }
+            _ => {
+                let mut single = Mapping::new();
+                single.insert(key.clone(), value.clone());
+                output.push_str(&serde_yaml::to_string(&single).unwrap_or_default());
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOCUMENT: &str = r#"
+port: 7890
+proxies:
+  - name: "Node, A"
+    type: ss
+    server: 1.2.3.4
+    port: 8388
+    udp: true
+  - name: Node B
+    type: ss
+    server: 5.6.7.8
+    port: 8388
+    plugin-opts:
+      mode: websocket
+"#;
+
+    #[test]
+    fn test_styles_round_trip() {
+        let node: YamlValue = serde_yaml::from_str(DOCUMENT).unwrap();
+
+        for style in ["block", "flow", "compact"] {
+            let output = to_styled_string(&node, &[("proxies", style)]);
+            let parsed: YamlValue = serde_yaml::from_str(&output).unwrap();
+            assert_eq!(parsed, node, "style {} changed the document", style);
+        }
+    }
+
+    #[test]
+    fn test_style_formatting() {
+        let node: YamlValue = serde_yaml::from_str(DOCUMENT).unwrap();
+
+        let block = to_styled_string(&node, &[("proxies", "block")]);
+        assert_eq!(block, serde_yaml::to_string(&node).unwrap());
+
+        let flow = to_styled_string(&node, &[("proxies", "flow")]);
+        let lines: Vec<&str> = flow.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], "port: 7890");
+        assert_eq!(lines[1], "proxies:");
+        assert!(lines[2].starts_with("  - {name: "));
+        assert!(lines[2].ends_with("type: ss, server: 1.2.3.4, port: 8388, udp: true}"));
+        assert_eq!(
+            lines[3],
+            "  - {name: Node B, type: ss, server: 5.6.7.8, port: 8388, plugin-opts: {mode: websocket}}"
+        );
+
+        let compact = to_styled_string(&node, &[("proxies", "compact")]);
+        let lines: Vec<&str> = compact.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].starts_with("proxies: [{name: "));
+        assert!(lines[1].ends_with("plugin-opts: {mode: websocket}}]"));
+    }
+}
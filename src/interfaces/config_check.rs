@@ -0,0 +1,146 @@
+//! Check of an external config without converting anything
+//!
+//! Backs `subconverter check-config`. The groups, rulesets and patterns go
+//! through the same checks as the requests using the config, then the
+//! rulesets are loaded.
+
+use std::fmt;
+
+use crate::interfaces::validation::{validate_external, ValidationIssue};
+use crate::models::ruleset::strip_ruleset_prefix;
+use crate::models::RulesetConfig;
+use crate::rulesets::ruleset::load_ruleset;
+use crate::settings::ExternalSettings;
+use crate::utils::http::parse_proxy;
+use crate::utils::is_link;
+use crate::Settings;
+
+/// Problems found in an external config
+#[derive(Debug, Clone, Default)]
+pub struct ConfigReport {
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ConfigReport {
+    pub fn has_errors(&self) -> bool {
+        self.issues.iter().any(ValidationIssue::is_error)
+    }
+}
+
+/// One `error:` or `warning:` line per problem, then the counts
+impl fmt::Display for ConfigReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for issue in &self.issues {
+            let level = if issue.is_error() { "error" } else { "warning" };
+            writeln!(f, "{}: {}", level, issue)?;
+        }
+        let errors = self.issues.iter().filter(|issue| issue.is_error()).count();
+        write!(
+            f,
+            "{} error(s), {} warning(s)",
+            errors,
+            self.issues.len() - errors
+        )
+    }
+}
+
+/// Load the external config at `path` and report its problems
+///
+/// Local rulesets are always read, remote ones are only downloaded with
+/// `fetch`.
+pub async fn check_external_config(path: &str, fetch: bool) -> ConfigReport {
+    let settings = Settings::current();
+    let ext = match ExternalSettings::load_from_file(path, &settings).await {
+        Ok(ext) => ext,
+        Err(e) => {
+            return ConfigReport {
+                issues: vec![ValidationIssue::error("config", e.to_string())],
+            }
+        }
+    };
+
+    let mut issues = validate_external(&ext);
+    issues.extend(load_rulesets(&ext.custom_rulesets, fetch, &settings).await);
+    ConfigReport { issues }
+}
+
+/// Load every ruleset that is not inline, failures are errors
+async fn load_rulesets(
+    rulesets: &[RulesetConfig],
+    fetch: bool,
+    settings: &Settings,
+) -> Vec<ValidationIssue> {
+    let proxy = parse_proxy(&settings.proxy_ruleset);
+    let mut issues = Vec::new();
+
+    for ruleset in rulesets {
+        if ruleset.url.contains("[]") || (is_link(strip_ruleset_prefix(&ruleset.url)) && !fetch) {
+            continue;
+        }
+        match load_ruleset(
+            ruleset,
+            &proxy,
+            settings.max_allowed_download_size,
+            0,
+            false,
+        )
+        .await
+        {
+            Ok(content) if content.get_rule_content().trim().is_empty() => {
+                issues.push(ValidationIssue::warning(
+                    "rulesets",
+                    format!("ruleset '{}' has no rules", ruleset.url),
+                ));
+            }
+            Ok(_) => {}
+            Err(e) => issues.push(ValidationIssue::error(
+                "rulesets",
+                format!("ruleset '{}' failed to load: {}", ruleset.url, e),
+            )),
+        }
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(path: &str) -> ConfigReport {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(check_external_config(path, false))
+    }
+
+    #[test]
+    fn test_good_config() {
+        let report = check("tests/fixtures/external/good.ini");
+        assert!(report.issues.is_empty(), "{}", report);
+        assert_eq!(report.to_string(), "0 error(s), 0 warning(s)");
+    }
+
+    #[test]
+    fn test_broken_config() {
+        let report = check("tests/fixtures/external/broken.ini");
+        assert!(report.has_errors());
+        let lines: Vec<String> = report.to_string().lines().map(str::to_string).collect();
+        assert_eq!(
+            lines,
+            [
+                "error: include_remarks: invalid regex '(HK'",
+                "warning: rename_array: invalid regex '[bad'",
+                "warning: proxy_groups: group '🚀 Proxy' references unknown group 'Auto'",
+                "error: proxy_groups: invalid regex '(US' in group '🇺🇸 US'",
+                "error: proxy_groups: group '🚀 Proxy' is defined more than once",
+                "warning: rulesets: rules target group '🎥 Streaming' which is not defined",
+                "error: rulesets: ruleset 'tests/fixtures/rules/missing.list' failed to load: Rule file not found: tests/fixtures/rules/missing.list",
+                "4 error(s), 3 warning(s)",
+            ]
+        );
+
+        let report = check("tests/fixtures/external/does-not-exist.ini");
+        assert!(report.has_errors());
+    }
+}
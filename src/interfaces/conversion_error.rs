@@ -0,0 +1,78 @@
+//! Errors of the conversion pipeline
+
+use std::fmt;
+
+/// Error returned by the stages of a conversion, from [`fetch_nodes`] to
+/// [`generate`]
+///
+/// [`fetch_nodes`]: super::fetch_nodes
+/// [`generate`]: super::generate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConversionError {
+    /// A subscription URL could not be fetched or parsed and failed URLs are
+    /// not skipped
+    UrlFailed {
+        url: String,
+        error: String,
+        insert: bool,
+    },
+    /// Every main URL failed, with the first one and its error
+    AllUrlsFailed(Option<(String, String)>),
+    /// The subscriptions held no node, or none passed the filters
+    NoNodes,
+    /// More nodes than allowed
+    TooManyNodes { count: usize, limit: usize },
+    /// More rules than allowed
+    TooManyRules { count: usize, limit: usize },
+    /// Proxy groups matched no node and empty groups are an error
+    EmptyGroups(Vec<String>),
+    /// The target produced no output
+    EmptyOutput(String),
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConversionError::UrlFailed { url, error, insert } => write!(
+                f,
+                "Failed to parse {}URL '{}': {}",
+                if *insert { "insert " } else { "" },
+                url,
+                error
+            ),
+            ConversionError::AllUrlsFailed(Some((url, error))) => write!(
+                f,
+                "Failed to parse all subscription URLs, first '{}': {}",
+                url, error
+            ),
+            ConversionError::AllUrlsFailed(None) => {
+                write!(f, "Failed to parse all subscription URLs")
+            }
+            ConversionError::NoNodes => write!(f, "No nodes were found!"),
+            ConversionError::TooManyNodes { count, limit } => write!(
+                f,
+                "Too many nodes: {} exceeds the limit of {}",
+                count, limit
+            ),
+            ConversionError::TooManyRules { count, limit } => write!(
+                f,
+                "Too many rules: {} exceeds the limit of {}",
+                count, limit
+            ),
+            ConversionError::EmptyGroups(groups) => {
+                write!(f, "Proxy groups matched no nodes: {}", groups.join(", "))
+            }
+            ConversionError::EmptyOutput(target) => {
+                write!(f, "Failed to generate {} output", target)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+impl From<ConversionError> for String {
+    fn from(error: ConversionError) -> Self {
+        error.to_string()
+    }
+}
@@ -1,3 +1,17 @@
+pub mod config_check;
+pub mod conversion_error;
+pub mod preview;
+pub mod profile;
+pub mod query_config;
 pub mod subconverter;
+pub mod validation;
 
+pub use config_check::{check_external_config, ConfigReport};
+pub use conversion_error::ConversionError;
+pub use preview::{ConversionPreview, GroupPreview, RulesetPreview};
+pub use profile::{ConversionProfile, UrlProfile};
+pub use query_config::build_config_from_query;
 pub use subconverter::*;
+pub use validation::{
+    validate_external, ConfigValidationError, ValidationIssue, ValidationSeverity,
+};
@@ -0,0 +1,165 @@
+//! Structural preview of a conversion, returned for `dry_run` requests
+
+use serde::Serialize;
+
+use crate::generator::config::group::{group_members, group_names};
+use crate::generator::ruleconvert::count_rules;
+use crate::interfaces::subconverter::{with_source_groups, NodeSet, SubconverterConfig};
+use crate::models::ConversionWarning;
+
+/// Number of members listed for every group of a preview
+const MEMBERS_PREVIEW_LEN: usize = 10;
+
+/// A proxy group as the generators would resolve it
+#[derive(Debug, Clone, Serialize)]
+pub struct GroupPreview {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub group_type: String,
+    /// Members that are nodes, other groups and policies are not counted
+    pub matched_node_count: usize,
+    /// First members of the group, nodes, groups and policies alike
+    pub members_preview: Vec<String>,
+}
+
+/// A ruleset applied by the rule generator
+#[derive(Debug, Clone, Serialize)]
+pub struct RulesetPreview {
+    pub group: String,
+    /// Path or URL of the ruleset, empty for inline rules
+    pub path: String,
+    pub rule_count: usize,
+}
+
+/// Groups, rulesets and node count of a conversion, without its output
+#[derive(Debug, Clone, Serialize)]
+pub struct ConversionPreview {
+    pub total_nodes: usize,
+    pub groups: Vec<GroupPreview>,
+    pub rulesets: Vec<RulesetPreview>,
+    /// Warnings of fetching and processing the nodes
+    pub warnings: Vec<ConversionWarning>,
+}
+
+/// Resolve the groups of `config` against processed nodes
+pub fn preview(node_set: &NodeSet, config: &SubconverterConfig) -> ConversionPreview {
+    let config = with_source_groups(config, node_set.source_groups.as_ref());
+    let config = config.as_ref();
+    let nodes = &node_set.nodes;
+    let names = group_names(&config.proxy_groups);
+    let groups = config
+        .proxy_groups
+        .iter()
+        .map(|group| {
+            let members = group_members(group, nodes, &names, &config.extra);
+            GroupPreview {
+                name: group.name.clone(),
+                group_type: group.group_type.as_str().to_string(),
+                matched_node_count: members
+                    .iter()
+                    .filter(|member| nodes.iter().any(|node| &node.remark == *member))
+                    .count(),
+                members_preview: members.into_iter().take(MEMBERS_PREVIEW_LEN).collect(),
+            }
+        })
+        .collect();
+    let rulesets = node_set
+        .ruleset_content
+        .iter()
+        .map(|ruleset| RulesetPreview {
+            group: ruleset.group.clone(),
+            path: ruleset.rule_path.clone(),
+            rule_count: count_rules(std::slice::from_ref(ruleset)),
+        })
+        .collect();
+
+    ConversionPreview {
+        total_nodes: nodes.len(),
+        groups,
+        rulesets,
+        warnings: node_set.warnings.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interfaces::profile::ConversionProfile;
+    use crate::interfaces::subconverter::SubconverterConfigBuilder;
+    use crate::models::{Proxy, ProxyGroupConfig, ProxyGroupType, RulesetContent};
+    use crate::utils::system::safe_system_time;
+    use serde_json::json;
+
+    #[test]
+    fn test_preview_json() {
+        let nodes: Vec<Proxy> = (1..=12)
+            .map(|i| Proxy {
+                remark: format!("{} {:02}", if i <= 3 { "HK" } else { "US" }, i),
+                ..Default::default()
+            })
+            .collect();
+        let mut rules = RulesetContent::new("rules/Apple.list", "Proxy");
+        rules.set_rule_content("DOMAIN,apple.com\n# comment\nDOMAIN-SUFFIX,icloud.com\n");
+        let mut final_rule = RulesetContent::new("", "Proxy");
+        final_rule.set_rule_content("[]FINAL");
+        let node_set = NodeSet {
+            nodes,
+            sub_info: None,
+            failed_urls: Vec::new(),
+            source_groups: None,
+            ruleset_content: vec![rules, final_rule],
+            profile: ConversionProfile::new("clash"),
+            started: safe_system_time(),
+            warnings: Vec::new(),
+        };
+
+        let mut builder = SubconverterConfigBuilder::new();
+        builder
+            .add_url("https://example.com/sub")
+            .proxy_groups(vec![
+                ProxyGroupConfig {
+                    name: "Proxy".to_string(),
+                    group_type: ProxyGroupType::Select,
+                    proxies: vec!["HK".to_string(), "[]DIRECT".to_string(), "Auto".to_string()],
+                    ..Default::default()
+                },
+                ProxyGroupConfig {
+                    name: "Auto".to_string(),
+                    group_type: ProxyGroupType::URLTest,
+                    proxies: vec![".*".to_string()],
+                    ..Default::default()
+                },
+            ]);
+        let config = builder.build().unwrap();
+
+        let preview = serde_json::to_value(preview(&node_set, &config)).unwrap();
+        assert_eq!(
+            preview,
+            json!({
+                "total_nodes": 12,
+                "groups": [
+                    {
+                        "name": "Proxy",
+                        "type": "select",
+                        "matched_node_count": 3,
+                        "members_preview": ["HK 01", "HK 02", "HK 03", "DIRECT", "Auto"],
+                    },
+                    {
+                        "name": "Auto",
+                        "type": "url-test",
+                        "matched_node_count": 12,
+                        "members_preview": [
+                            "HK 01", "HK 02", "HK 03", "US 04", "US 05",
+                            "US 06", "US 07", "US 08", "US 09", "US 10",
+                        ],
+                    },
+                ],
+                "rulesets": [
+                    {"group": "Proxy", "path": "rules/Apple.list", "rule_count": 2},
+                    {"group": "Proxy", "path": "", "rule_count": 1},
+                ],
+                "warnings": [],
+            })
+        );
+    }
+}
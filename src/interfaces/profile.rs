@@ -0,0 +1,114 @@
+//! Per-stage timing collected while processing a conversion request
+
+use std::fmt;
+use std::time::Duration;
+
+/// Timing of a single subscription URL
+#[derive(Debug, Clone, Default)]
+pub struct UrlProfile {
+    /// Time spent downloading the subscription
+    pub fetch: Duration,
+    /// Time spent parsing the content, excluding the download
+    pub parse: Duration,
+    /// Nodes found before the include/exclude filters
+    pub nodes: usize,
+}
+
+/// Timing and node counts of one conversion request
+///
+/// Displayed as space separated `key=value` pairs, with per-URL values
+/// separated by commas in the order the URLs were fetched:
+///
+/// ```text
+/// target=clash urls=2 fetch_ms=120,35 parse_ms=3,1 nodes_before=40 nodes_after=32 legacy_vmess=0 rulesets_ms=80 generate_ms=6 total_ms=246
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ConversionProfile {
+    /// Name of the conversion target
+    pub target: String,
+    /// Timing of every insert and main URL
+    pub urls: Vec<UrlProfile>,
+    /// Nodes left after filtering and preprocessing
+    pub nodes_after: usize,
+    /// VMess nodes among them that came with a non-zero alterId
+    pub legacy_vmess: usize,
+    /// Time spent refreshing rulesets
+    pub rulesets: Duration,
+    /// Time spent generating the output
+    pub generate: Duration,
+    /// Total time of the request
+    pub total: Duration,
+}
+
+impl ConversionProfile {
+    /// Create an empty profile for the given target
+    pub fn new(target: &str) -> Self {
+        ConversionProfile {
+            target: target.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Nodes found across all URLs before filtering
+    pub fn nodes_before(&self) -> usize {
+        self.urls.iter().map(|url| url.nodes).sum()
+    }
+}
+
+fn join_ms<F: Fn(&UrlProfile) -> Duration>(urls: &[UrlProfile], stage: F) -> String {
+    if urls.is_empty() {
+        return "0".to_string();
+    }
+    urls.iter()
+        .map(|url| stage(url).as_millis().to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+impl fmt::Display for ConversionProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "target={} urls={} fetch_ms={} parse_ms={} nodes_before={} nodes_after={} legacy_vmess={} rulesets_ms={} generate_ms={} total_ms={}",
+            self.target,
+            self.urls.len(),
+            join_ms(&self.urls, |url| url.fetch),
+            join_ms(&self.urls, |url| url.parse),
+            self.nodes_before(),
+            self.nodes_after,
+            self.legacy_vmess,
+            self.rulesets.as_millis(),
+            self.generate.as_millis(),
+            self.total.as_millis()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_display() {
+        let mut profile = ConversionProfile::new("clash");
+        profile.urls.push(UrlProfile {
+            fetch: Duration::from_millis(120),
+            parse: Duration::from_millis(3),
+            nodes: 30,
+        });
+        profile.urls.push(UrlProfile {
+            fetch: Duration::from_millis(35),
+            parse: Duration::from_millis(1),
+            nodes: 10,
+        });
+        profile.nodes_after = 32;
+        profile.legacy_vmess = 3;
+        profile.generate = Duration::from_millis(6);
+        profile.total = Duration::from_millis(246);
+
+        assert_eq!(
+            profile.to_string(),
+            "target=clash urls=2 fetch_ms=120,35 parse_ms=3,1 nodes_before=40 nodes_after=32 legacy_vmess=3 rulesets_ms=0 generate_ms=6 total_ms=246"
+        );
+    }
+}
@@ -0,0 +1,1152 @@
+//! Conversion config of a `/sub` request
+//!
+//! Maps the request parameters to a [`SubconverterConfig`] without fetching
+//! anything, the handler loads the external config and passes it in.
+
+use std::sync::Arc;
+
+use log::{error, warn};
+
+use crate::api::SubconverterQuery;
+use crate::constants::regex_black_list::REGEX_BLACK_LIST;
+use crate::generator::yaml::style::is_valid_style;
+use crate::interfaces::subconverter::{RuleBases, SubconverterConfig, SubconverterConfigBuilder};
+use crate::models::ruleset::RulesetConfigs;
+use crate::models::{
+    EmptyGroupPlaceholder, ProxyGroupConfigs, RegexMatchConfig, RegexMatchConfigs,
+    SubconverterTarget,
+};
+use crate::settings::external::ExternalSettings;
+use crate::settings::FromIni;
+use crate::utils::url::url_decode;
+use crate::utils::{parse_proxy_types, reg_valid};
+use crate::{Settings, TemplateArgs};
+
+/// Build the conversion config described by the request parameters
+///
+/// Parameters shared with the settings and the external config take the
+/// value of the request first, then the one of `external` and last the one
+/// of `global`. `external` is used as loaded, see
+/// [`ExternalSettings::clear_unusable_bases`] for its rule bases. Nothing is
+/// fetched, invalid parameters are returned as the message of a bad request.
+///
+/// The conversion runs with the `global` snapshot as well, so a reload of
+/// the settings does not change it halfway.
+pub fn build_config_from_query(
+    query: &SubconverterQuery,
+    global: &Arc<Settings>,
+    external: Option<&ExternalSettings>,
+) -> Result<SubconverterConfig, String> {
+    // Start building configuration, the defaults come from `global`
+    let mut builder = SubconverterConfigBuilder::with_settings(global.clone());
+
+    let target;
+    if let Some(_target) = &query.target {
+        match SubconverterTarget::from_str(&_target) {
+            Some(_target) => {
+                target = _target.clone();
+                if _target == SubconverterTarget::Auto {
+                    // TODO: Check user agent and set target accordingly
+                    // if let Some(user_agent) = req.headers().get("User-Agent") {
+                    //     if let Ok(user_agent) = user_agent.to_str() {
+
+                    //         // match_user_agent(
+                    //         //     user_agent,
+                    //         //     &target,
+                    //         //      query.new_name,
+                    //         //      &query.ver);
+                    //     }
+                    // }
+                    return Err("Auto user agent is not supported for now.".to_string());
+                }
+                builder.target(_target);
+            }
+            None => {
+                return Err("Invalid target parameter".to_string());
+            }
+        }
+    } else {
+        return Err("Missing target parameter".to_string());
+    }
+
+    apply_update_query(&mut builder, query, global);
+    // Check if we should authorize the request, if we are in API mode
+    let authorized =
+        !global.api_mode || query.token.as_deref().unwrap_or_default() == global.api_access_token;
+    builder.authorized(authorized);
+    apply_limit_query(&mut builder, query, authorized);
+    builder.profile(profile_requested(query, authorized));
+    apply_check_query(&mut builder, query, authorized);
+    apply_geo_emoji_query(&mut builder, query, authorized);
+
+    if query
+        .include
+        .clone()
+        .is_some_and(|include| REGEX_BLACK_LIST.contains(&include))
+        || query
+            .exclude
+            .clone()
+            .is_some_and(|exclude| REGEX_BLACK_LIST.contains(&exclude))
+    {
+        return Err("Invalid regex in request!".to_string());
+    }
+
+    apply_insert_query(&mut builder, query, external, global);
+
+    match query.url.as_deref() {
+        Some(query_url) => {
+            builder.urls_from_str(query_url);
+        }
+        None if authorized => {
+            builder.urls(global.default_urls.clone());
+        }
+        None => {}
+    }
+
+    builder.user_agent(query.ua.clone().filter(|ua| !ua.is_empty()));
+    builder.follow_providers(query.follow_providers.unwrap_or(false));
+
+    // TODO: what if urls still empty after insert?
+
+    // Create template args from request parameters and other settings
+    let mut template_args = TemplateArgs::default();
+    template_args.global_vars = global.template_vars.clone();
+
+    template_args.request_params = query.clone();
+
+    builder.append_proxy_type(query.append_type.unwrap_or(global.append_type));
+    builder.append_info(query.append_info.unwrap_or(global.append_info));
+
+    let mut arg_expand_rulesets = query.expand;
+    if target.is_clash() && query.script.is_none() {
+        arg_expand_rulesets = Some(true);
+    }
+
+    // flags
+    apply_flag_query(&mut builder, query, global);
+    builder.sort(query.sort.unwrap_or(global.enable_sort));
+    if let Some(script) = &query.sort_script {
+        builder.sort_script(script.clone());
+    }
+    if let Some(pin) = query.pin.as_deref() {
+        builder.pin_regex(pin.to_owned());
+    }
+
+    builder.filter_deprecated(query.fdn.unwrap_or(global.filter_deprecated));
+    builder.ssr_format_ss(query.ssr_format_ss.unwrap_or_default());
+    builder.max_remark_length(query.max_len.filter(|len| *len > 0));
+    builder.remark_template(query.remark_tpl.clone().filter(|tpl| !tpl.is_empty()));
+    builder.clash_new_field_name(query.new_name.unwrap_or(global.clash_use_new_field));
+    builder.clash_script(query.script.unwrap_or_default());
+    builder.clash_classical_ruleset(query.classic.unwrap_or_default());
+    builder.expand_rulesets(arg_expand_rulesets.unwrap_or(true));
+    for style in [&query.proxies_style, &query.groups_style]
+        .into_iter()
+        .flatten()
+    {
+        if !is_valid_style(style) {
+            return Err(format!(
+                "Invalid style '{}', expected flow, block or compact",
+                style
+            ));
+        }
+    }
+    if let Some(style) = &query.proxies_style {
+        builder.clash_proxies_style(style.clone());
+    }
+    if let Some(style) = &query.groups_style {
+        builder.clash_proxy_groups_style(style.clone());
+    }
+    let nodelist = query.list.unwrap_or_default();
+    builder.nodelist(nodelist);
+
+    if arg_expand_rulesets != Some(true) {
+        builder.clash_new_field_name(true);
+    } else {
+        builder.managed_config_prefix(global.managed_config_prefix.clone());
+        builder.clash_script(false);
+    }
+
+    let mut ruleset_configs = global.custom_rulesets.clone();
+    let mut custom_group_configs = global.custom_proxy_groups.clone();
+
+    // 这部分参数有优先级：query > external > global
+    apply_global_node_rules(&mut builder, global);
+    builder.enable_rule_generator(global.enable_rule_gen);
+    let mut rule_bases = RuleBases {
+        clash_rule_base: global.clash_base.clone(),
+        surge_rule_base: global.surge_base.clone(),
+        surfboard_rule_base: global.surfboard_base.clone(),
+        mellow_rule_base: global.mellow_base.clone(),
+        quan_rule_base: global.quan_base.clone(),
+        quanx_rule_base: global.quanx_base.clone(),
+        loon_rule_base: global.loon_base.clone(),
+        sssub_rule_base: global.ssub_base.clone(),
+        singbox_rule_base: global.singbox_base.clone(),
+        ..Default::default()
+    };
+    builder.rule_bases(rule_bases.clone());
+    builder.template_args(template_args.clone());
+
+    if let Some(extconf) = external {
+        if !nodelist {
+            rule_bases.apply_external_bases(extconf);
+            builder.rule_bases(rule_bases);
+
+            if let Some(tpl_args) = &extconf.tpl_args {
+                template_args.local_vars = tpl_args.clone();
+            }
+
+            builder.template_args(template_args);
+
+            if !target.is_simple() {
+                if !extconf.custom_rulesets.is_empty() {
+                    ruleset_configs = extconf.custom_rulesets.clone();
+                }
+                if !extconf.custom_proxy_groups.is_empty() {
+                    custom_group_configs = extconf.custom_proxy_groups.clone();
+                }
+                if let Some(enable_rule_gen) = extconf.enable_rule_generator {
+                    builder.enable_rule_generator(enable_rule_gen);
+                }
+                if let Some(overwrite_original_rules) = extconf.overwrite_original_rules {
+                    builder.overwrite_original_rules(overwrite_original_rules);
+                }
+                if !extconf.rule_group_map.is_empty() {
+                    builder.rule_group_map(extconf.rule_group_map.clone());
+                }
+            }
+        }
+        if !extconf.rename_nodes.is_empty() {
+            builder.rename_array(extconf.rename_nodes.clone());
+        }
+        if !extconf.emojis.is_empty() {
+            builder.emoji_array(extconf.emojis.clone());
+        }
+        if !extconf.include_remarks.is_empty() {
+            builder.include_remarks(extconf.include_remarks.clone());
+        }
+        if !extconf.exclude_remarks.is_empty() {
+            builder.exclude_remarks(extconf.exclude_remarks.clone());
+        }
+        if let Some(add_emoji) = extconf.add_emoji {
+            builder.add_emoji(add_emoji);
+        }
+        if let Some(remove_old_emoji) = extconf.remove_old_emoji {
+            builder.remove_emoji(remove_old_emoji);
+        }
+    }
+
+    // 请求参数的覆盖优先级最高
+    // Invalid patterns are rejected by the builder
+    if let Some(include) = query.include.as_deref() {
+        builder.include_remarks(vec![include.to_owned()]);
+    }
+    if let Some(exclude) = query.exclude.as_deref() {
+        builder.exclude_remarks(vec![exclude.to_owned()]);
+    }
+    if let Some(include_types) = query.include_types.as_deref() {
+        builder.include_types(parse_proxy_types(include_types));
+    }
+    if let Some(exclude_types) = query.exclude_types.as_deref() {
+        builder.exclude_types(parse_proxy_types(exclude_types));
+    }
+    if let Some(include) = query.incsrv.as_deref() {
+        builder.include_servers(vec![include.to_owned()]);
+    }
+    if let Some(exclude) = query.excsrv.as_deref() {
+        builder.exclude_servers(vec![exclude.to_owned()]);
+    }
+    if let Some(empty_group) = query.empty_group.as_deref() {
+        match EmptyGroupPlaceholder::from_name(empty_group) {
+            Some(placeholder) => {
+                builder.empty_group_placeholder(placeholder);
+            }
+            None => warn!("Ignoring unknown empty_group value '{}'", empty_group),
+        }
+    }
+    apply_emoji_query(&mut builder, query);
+    apply_rename_query(&mut builder, query);
+
+    if !target.is_simple() {
+        // loading custom groups
+        if !query
+            .groups
+            .as_deref()
+            .is_none_or(|groups| groups.is_empty())
+            && !nodelist
+        {
+            if let Some(groups) = query.groups.as_deref() {
+                let v_array: Vec<String> = groups.split('@').map(|s| s.to_string()).collect();
+                custom_group_configs = ProxyGroupConfigs::from_ini(&v_array);
+            }
+        }
+        // loading custom rulesets
+        if !query
+            .ruleset
+            .as_deref()
+            .is_none_or(|ruleset| ruleset.is_empty())
+            && !nodelist
+        {
+            if let Some(ruleset) = query.ruleset.as_deref() {
+                let v_array: Vec<String> = ruleset.split('@').map(|s| s.to_string()).collect();
+                ruleset_configs = RulesetConfigs::from_ini(&v_array);
+            }
+        }
+    }
+    builder.proxy_groups(custom_group_configs);
+    builder.ruleset_configs(ruleset_configs);
+
+    // TODO: process with the script runtime
+
+    // parse settings
+
+    apply_group_query(&mut builder, query);
+    builder.filename(query.filename.clone());
+
+    // // Process filter script
+    // if let Some(filter) = &query.filter {
+    //     builder = builder.filter_script(Some(filter.clone()));
+    // }
+
+    // Process device ID and the QuantumultX server_remote reference
+    builder.device_id(query.dev_id.clone().filter(|dev_id| !dev_id.is_empty()));
+    builder.quanx_server_remote(query.server_remote.unwrap_or_default());
+    builder.quanx_as_policy(query.as_policy.clone());
+    builder.source_groups(query.source_groups.unwrap_or_default());
+
+    // // Set managed config prefix from global settings
+    // if !global.managed_config_prefix.is_empty() {
+    //     builder = builder.managed_config_prefix(global.managed_config_prefix.clone());
+    // }
+
+    // Build and validate configuration, every error is listed on its own line
+    let config = builder.build().map_err(|e| {
+        error!("Failed to build subconverter config: {}", e);
+        let errors: Vec<String> = e.errors().map(|issue| format!("- {}", issue)).collect();
+        format!("Configuration error:\n{}", errors.join("\n"))
+    })?;
+    for warning in &config.warnings {
+        warn!("Configuration warning: {}", warning);
+    }
+    Ok(config)
+}
+
+/// Apply the filter, rename and emoji rules from the settings file
+fn apply_global_node_rules(builder: &mut SubconverterConfigBuilder, global: &Settings) {
+    builder.include_remarks(global.include_remarks.clone());
+    builder.exclude_remarks(global.exclude_remarks.clone());
+    builder.rename_array(global.renames.clone());
+    builder.emoji_array(global.emojis.clone());
+    builder.add_emoji(global.add_emoji);
+    builder.remove_emoji(global.remove_emoji);
+}
+
+/// Parse the `rename` request parameter, backtick separated `regex@replacement` entries
+///
+/// Every entry is URL-decoded and split on its first `@`, so the replacement
+/// may contain `@`. Entries without `@` or with an invalid regex are returned
+/// as errors instead.
+pub(crate) fn parse_rename_query(rename: &str) -> (RegexMatchConfigs, Vec<String>) {
+    let mut patterns = Vec::new();
+    let mut errors = Vec::new();
+    for entry in rename.split('`').filter(|entry| !entry.is_empty()) {
+        let entry = url_decode(entry);
+        match entry.split_once('@') {
+            Some((pattern, replace)) if !pattern.is_empty() && reg_valid(pattern) => {
+                patterns.push(RegexMatchConfig {
+                    _match: pattern.to_string(),
+                    replace: replace.to_string(),
+                });
+            }
+            Some(_) => errors.push(format!("invalid regex in '{}'", entry)),
+            None => errors.push(format!("missing '@' in '{}'", entry)),
+        }
+    }
+    (patterns, errors)
+}
+
+/// Apply the `rename` request parameter after the rename rules of the settings
+fn apply_rename_query(builder: &mut SubconverterConfigBuilder, query: &SubconverterQuery) {
+    let Some(rename) = query.rename.as_deref() else {
+        return;
+    };
+    let (patterns, errors) = parse_rename_query(rename);
+    for error in &errors {
+        warn!("Skipping rename entry: {}", error);
+    }
+    builder.add_renames(patterns);
+}
+
+/// Apply the `emoji`, `add_emoji` and `remove_emoji` request parameters
+///
+/// `emoji` turns on removing old emoji together with adding new ones, the
+/// other two override it individually.
+fn apply_emoji_query(builder: &mut SubconverterConfigBuilder, query: &SubconverterQuery) {
+    if let Some(emoji) = query.emoji {
+        builder.add_emoji(emoji);
+        builder.remove_emoji(true);
+    }
+    if let Some(add_emoji) = query.add_emoji {
+        builder.add_emoji(add_emoji);
+    }
+    if let Some(remove_emoji) = query.remove_emoji {
+        builder.remove_emoji(remove_emoji);
+    }
+}
+
+/// Apply the `udp`, `tfo`, `scv` and `tls13` request parameters
+///
+/// A parameter left undefined falls back to the global setting, and the
+/// generators only use the result for nodes that don't set the value themselves.
+fn apply_flag_query(
+    builder: &mut SubconverterConfigBuilder,
+    query: &SubconverterQuery,
+    global: &Settings,
+) {
+    builder.tfo(query.tfo.or(global.tfo_flag));
+    builder.udp(query.udp.or(global.udp_flag));
+    builder.skip_cert_verify(query.scv.or(global.skip_cert_verify));
+    builder.tls13(query.tls13.or(global.tls13_flag));
+}
+
+/// Apply the `insert` and `prepend` request parameters
+///
+/// `insert`, `enable_insert` of the external config and `enable_insert` of
+/// the settings decide in that order whether nodes are inserted. The insert
+/// URLs of the external config replace the ones of the settings, and
+/// `prepend` overrides `prepend_insert_url` of the external config, then of
+/// the settings, for the order of the inserted nodes.
+fn apply_insert_query(
+    builder: &mut SubconverterConfigBuilder,
+    query: &SubconverterQuery,
+    external: Option<&ExternalSettings>,
+    global: &Settings,
+) {
+    let enabled = query
+        .insert
+        .or(external.and_then(|extconf| extconf.enable_insert))
+        .unwrap_or(global.enable_insert);
+    if !enabled {
+        return;
+    }
+    let insert_urls = external
+        .map(|extconf| &extconf.insert_urls)
+        .filter(|urls| !urls.is_empty())
+        .unwrap_or(&global.insert_urls);
+    builder.insert_urls(insert_urls.clone());
+    // 加在前面还是加在后面
+    let prepend = query
+        .prepend
+        .or(external.and_then(|extconf| extconf.prepend_insert))
+        .unwrap_or(global.prepend_insert);
+    builder.prepend_insert(prepend);
+}
+
+/// Apply the `max_download_size`, `max_proxies` and `max_rules` request parameters
+///
+/// The limits from the settings protect the server, so only authorized
+/// requests may change them.
+fn apply_limit_query(
+    builder: &mut SubconverterConfigBuilder,
+    query: &SubconverterQuery,
+    authorized: bool,
+) {
+    if !authorized {
+        return;
+    }
+    if let Some(size) = query.max_download_size {
+        builder.max_download_size(size);
+    }
+    if let Some(max) = query.max_proxies {
+        builder.max_allowed_proxies(max);
+    }
+    if let Some(max) = query.max_rules {
+        builder.max_allowed_rules(max);
+    }
+}
+
+/// Apply the `check` and `check_drop` request parameters
+///
+/// Probing opens connections to every node, so only authorized requests may
+/// ask for it.
+fn apply_check_query(
+    builder: &mut SubconverterConfigBuilder,
+    query: &SubconverterQuery,
+    authorized: bool,
+) {
+    let drop = query.check_drop.unwrap_or(false);
+    let check = query.check.unwrap_or(false) || drop;
+    if check && !authorized {
+        warn!("Ignoring node check of an unauthorized request");
+        return;
+    }
+    builder.check_nodes(check);
+    builder.check_drop(drop);
+}
+
+/// Enable country flags from GeoIP lookups
+///
+/// Lookups resolve the server of every node, so only authorized requests may
+/// ask for them.
+fn apply_geo_emoji_query(
+    builder: &mut SubconverterConfigBuilder,
+    query: &SubconverterQuery,
+    authorized: bool,
+) {
+    if !query.geo_emoji.unwrap_or(false) {
+        return;
+    }
+    if !authorized {
+        warn!("Ignoring geo emoji of an unauthorized request");
+        return;
+    }
+    builder.geo_emoji(true);
+}
+
+/// Group name replacing the group of every node, an empty name is ignored
+///
+/// Without it nodes keep the group of their link or subscription, e.g. the
+/// airport of a SSD subscription.
+fn apply_group_query(builder: &mut SubconverterConfigBuilder, query: &SubconverterQuery) {
+    builder.group_name(query.group.clone().filter(|group| !group.is_empty()));
+}
+
+/// Map the managed config update and upload parameters
+///
+/// An explicit `interval` is also written to Clash output as
+/// `profile-update-interval` and its header, without one only managed
+/// configs get it.
+fn apply_update_query(
+    builder: &mut SubconverterConfigBuilder,
+    query: &SubconverterQuery,
+    global: &Settings,
+) {
+    builder.update_interval(query.interval.unwrap_or(global.update_interval));
+    builder.clash_update_interval(query.interval.is_some());
+    let web_page_url = query
+        .web_page_url
+        .clone()
+        .unwrap_or_else(|| global.profile_web_page_url.clone());
+    builder.profile_web_page_url(Some(web_page_url).filter(|url| !url.is_empty()));
+    builder.update_strict(query.strict.unwrap_or(global.update_strict));
+    builder.upload(query.upload.unwrap_or_default());
+    builder.upload_path(query.upload_path.clone().filter(|path| !path.is_empty()));
+}
+
+/// Whether the request asked for the `X-Conversion-Profile` header and may see it
+fn profile_requested(query: &SubconverterQuery, authorized: bool) -> bool {
+    authorized && matches!(query.profile.as_deref(), Some("1") | Some("true"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interfaces::subconverter::subconverter;
+    use crate::settings::FromIniWithDelimiter;
+    use crate::utils::base64::base64_encode;
+
+    const LINK: &str = "ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@1.2.3.4:8388#Node%20A";
+
+    fn query(query_string: &str) -> SubconverterQuery {
+        actix_web::web::Query::<SubconverterQuery>::from_query(query_string)
+            .unwrap()
+            .into_inner()
+    }
+
+    /// Config of a request for `https://example.com/sub`
+    fn config_of(
+        query_string: &str,
+        global: &Settings,
+        external: Option<&ExternalSettings>,
+    ) -> SubconverterConfig {
+        let query = query(&format!("url=https://example.com/sub&{}", query_string));
+        build_config_from_query(&query, &Arc::new(global.clone()), external).unwrap()
+    }
+
+    fn rule(pattern: &str, replace: &str) -> RegexMatchConfig {
+        RegexMatchConfig {
+            _match: pattern.to_string(),
+            replace: replace.to_string(),
+        }
+    }
+
+    fn global() -> Settings {
+        Settings {
+            include_remarks: vec!["global".to_string()],
+            exclude_remarks: vec!["global".to_string()],
+            renames: vec![rule("global", "G")],
+            emojis: vec![rule("global", "🌐")],
+            add_emoji: false,
+            remove_emoji: false,
+            enable_rule_gen: true,
+            udp_flag: Some(true),
+            skip_cert_verify: Some(true),
+            enable_sort: true,
+            filter_deprecated: true,
+            update_interval: 43200,
+            managed_config_prefix: "https://sub.example.com".to_string(),
+            clash_base: "base/all_base.tpl".to_string(),
+            custom_rulesets: RulesetConfigs::from_ini(&["🎯 Direct,[]GEOIP,CN".to_string()]),
+            custom_proxy_groups: ProxyGroupConfigs::from_ini(&[
+                "🎯 Direct`select`[]DIRECT".to_string()
+            ]),
+            ..Default::default()
+        }
+    }
+
+    fn external() -> ExternalSettings {
+        ExternalSettings {
+            include_remarks: vec!["external".to_string()],
+            exclude_remarks: vec!["external".to_string()],
+            rename_nodes: vec![rule("external", "E")],
+            emojis: vec![rule("external", "🏳️")],
+            add_emoji: Some(true),
+            remove_old_emoji: Some(true),
+            enable_rule_generator: Some(false),
+            clash_rule_base: "https://example.com/base.yml".to_string(),
+            custom_rulesets: RulesetConfigs::from_ini(&["🚀 Proxy,[]FINAL".to_string()]),
+            custom_proxy_groups: ProxyGroupConfigs::from_ini(&[
+                "🚀 Proxy`select`[]DIRECT".to_string()
+            ]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_target_is_required() {
+        let error_of = |query_string: &str| {
+            build_config_from_query(&query(query_string), &Arc::default(), None).unwrap_err()
+        };
+        assert_eq!(
+            error_of("url=https://example.com/sub"),
+            "Missing target parameter"
+        );
+        assert_eq!(
+            error_of("target=nope&url=https://example.com/sub"),
+            "Invalid target parameter"
+        );
+        assert_eq!(
+            error_of("target=clash&url=https://example.com/sub&proxies_style=wide"),
+            "Invalid style 'wide', expected flow, block or compact"
+        );
+    }
+
+    #[test]
+    fn test_remark_filters_precedence() {
+        let (global, external) = (global(), external());
+        let config = config_of("target=clash", &global, None);
+        assert_eq!(config.include_remarks, ["global"]);
+        assert_eq!(config.exclude_remarks, ["global"]);
+
+        let config = config_of("target=clash", &global, Some(&external));
+        assert_eq!(config.include_remarks, ["external"]);
+        assert_eq!(config.exclude_remarks, ["external"]);
+
+        let config = config_of(
+            "target=clash&include=query&exclude=query",
+            &global,
+            Some(&external),
+        );
+        assert_eq!(config.include_remarks, ["query"]);
+        assert_eq!(config.exclude_remarks, ["query"]);
+    }
+
+    #[test]
+    fn test_rename_and_emoji_precedence() {
+        let (global, external) = (global(), external());
+        let matches = |rules: &RegexMatchConfigs| {
+            rules
+                .iter()
+                .map(|rule| rule._match.clone())
+                .collect::<Vec<_>>()
+        };
+
+        let config = config_of("target=clash", &global, None);
+        assert_eq!(matches(&config.extra.rename_array), ["global"]);
+        assert_eq!(matches(&config.extra.emoji_array), ["global"]);
+        assert!(!config.extra.add_emoji);
+        assert!(!config.extra.remove_emoji);
+
+        let config = config_of("target=clash", &global, Some(&external));
+        assert_eq!(matches(&config.extra.rename_array), ["external"]);
+        assert_eq!(matches(&config.extra.emoji_array), ["external"]);
+        assert!(config.extra.add_emoji);
+        assert!(config.extra.remove_emoji);
+
+        // The renames of the request follow the others
+        let config = config_of(
+            "target=clash&rename=query%40Q&emoji=false&remove_emoji=false",
+            &global,
+            Some(&external),
+        );
+        assert_eq!(matches(&config.extra.rename_array), ["external", "query"]);
+        assert!(!config.extra.add_emoji);
+        assert!(!config.extra.remove_emoji);
+    }
+
+    #[test]
+    fn test_flag_precedence() {
+        let global = global();
+        let config = config_of("target=clash", &global, None);
+        assert_eq!(config.extra.udp, Some(true));
+        assert_eq!(config.extra.skip_cert_verify, Some(true));
+        assert!(config.extra.sort_flag);
+        assert!(config.extra.filter_deprecated);
+
+        let config = config_of(
+            "target=clash&udp=false&scv=false&sort=false&fdn=false",
+            &global,
+            None,
+        );
+        assert_eq!(config.extra.udp, Some(false));
+        assert_eq!(config.extra.skip_cert_verify, Some(false));
+        assert!(!config.extra.sort_flag);
+        assert!(!config.extra.filter_deprecated);
+
+        let config = config_of("target=clash", &Settings::default(), None);
+        assert_eq!(config.extra.udp, None);
+        assert_eq!(config.extra.skip_cert_verify, None);
+    }
+
+    #[test]
+    fn test_user_agent_query() {
+        let global = global();
+        let config = config_of("target=clash", &global, None);
+        assert_eq!(config.user_agent, None);
+        let config = config_of("target=clash&ua=", &global, None);
+        assert_eq!(config.user_agent, None);
+        let config = config_of("target=clash&ua=Shadowrocket%2F2070", &global, None);
+        assert_eq!(config.user_agent.as_deref(), Some("Shadowrocket/2070"));
+    }
+
+    #[test]
+    fn test_server_filter_query() {
+        let global = global();
+        let config = config_of("target=clash", &global, None);
+        assert!(config.extra.include_servers.is_empty());
+        assert!(config.extra.exclude_servers.is_empty());
+        let config = config_of(
+            "target=clash&incsrv=example%5C.com&excsrv=%5Ehk",
+            &global,
+            None,
+        );
+        assert_eq!(config.extra.include_servers, [r"example\.com"]);
+        assert_eq!(config.extra.exclude_servers, ["^hk"]);
+    }
+
+    #[test]
+    fn test_follow_providers_query() {
+        let global = global();
+        let config = config_of("target=clash", &global, None);
+        assert!(!config.follow_providers);
+        let config = config_of("target=clash&follow_providers=true", &global, None);
+        assert!(config.follow_providers);
+    }
+
+    #[test]
+    fn test_expand_interval_and_filename() {
+        let global = global();
+        let config = config_of("target=surge&ver=4", &global, None);
+        assert!(config.managed_config_prefix.is_empty());
+        assert_eq!(config.update_interval, 43200);
+        assert_eq!(config.filename, None);
+
+        let config = config_of(
+            "target=surge&ver=4&expand=true&interval=3600&filename=my.conf",
+            &global,
+            None,
+        );
+        assert_eq!(config.managed_config_prefix, "https://sub.example.com");
+        assert_eq!(config.update_interval, 3600);
+        assert_eq!(config.filename.as_deref(), Some("my.conf"));
+
+        // Clash rulesets are expanded unless a script is requested
+        let config = config_of("target=clash", &global, None);
+        assert_eq!(config.managed_config_prefix, "https://sub.example.com");
+    }
+
+    #[test]
+    fn test_groups_and_rulesets_precedence() {
+        let (global, external) = (global(), external());
+        let names_of = |config: &SubconverterConfig| {
+            (
+                config.proxy_groups[0].name.clone(),
+                config.ruleset_configs[0].group.clone(),
+                config.extra.enable_rule_generator,
+            )
+        };
+
+        let config = config_of("target=clash", &global, None);
+        assert_eq!(
+            names_of(&config),
+            ("🎯 Direct".to_string(), "🎯 Direct".to_string(), true)
+        );
+
+        let config = config_of("target=clash", &global, Some(&external));
+        assert_eq!(
+            names_of(&config),
+            ("🚀 Proxy".to_string(), "🚀 Proxy".to_string(), false)
+        );
+
+        let config = config_of(
+            "target=clash&groups=Query%60select%60%5B%5DDIRECT&ruleset=Query%2C%5B%5DFINAL",
+            &global,
+            Some(&external),
+        );
+        assert_eq!(
+            names_of(&config),
+            ("Query".to_string(), "Query".to_string(), false)
+        );
+
+        // Node lists keep the groups of the settings
+        let config = config_of("target=clash&list=true", &global, Some(&external));
+        assert_eq!(config.proxy_groups[0].name, "🎯 Direct");
+    }
+
+    #[test]
+    fn test_rule_bases_precedence() {
+        let (global, external) = (global(), external());
+        let config = config_of("target=clash", &global, None);
+        assert_eq!(config.rule_bases.clash_rule_base, "base/all_base.tpl");
+        assert!(config.rule_bases.clash_global_base.is_empty());
+
+        let config = config_of("target=clash", &global, Some(&external));
+        assert_eq!(
+            config.rule_bases.clash_rule_base,
+            "https://example.com/base.yml"
+        );
+        assert_eq!(config.rule_bases.clash_global_base, "base/all_base.tpl");
+    }
+
+    #[test]
+    fn test_settings_emoji_rules() {
+        let mut global = Settings::default();
+        global.emojis = RegexMatchConfigs::from_ini_with_delimiter(
+            &[
+                "(港|HK|Hong Kong),🇭🇰".to_string(),
+                "(日本|JP|Japan),🇯🇵".to_string(),
+            ],
+            ",",
+        );
+        let query: SubconverterQuery =
+            serde_json::from_str(r#"{"emoji": true, "remove_old_emoji": true}"#).unwrap();
+
+        let mut builder = SubconverterConfigBuilder::new();
+        builder
+            .target(SubconverterTarget::Clash)
+            .add_url(&format!(
+                "data:text/plain;base64,{}",
+                base64_encode("ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@1.2.3.4:8388#Hong%20Kong%2001")
+            ))
+            .nodelist(true);
+        apply_global_node_rules(&mut builder, &global);
+        apply_emoji_query(&mut builder, &query);
+        let config = builder.build().unwrap();
+        assert!(config.extra.remove_emoji);
+
+        let result = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(subconverter(config))
+            .unwrap();
+        assert!(result.content.contains("🇭🇰 Hong Kong 01"));
+    }
+
+    #[test]
+    fn test_limit_query_needs_authorization() {
+        let query: SubconverterQuery =
+            serde_json::from_str(r#"{"max_proxies": 1, "max_rules": 2}"#).unwrap();
+
+        let mut builder = SubconverterConfigBuilder::new();
+        builder.add_url(LINK);
+        let defaults = builder.clone().build().unwrap();
+        apply_limit_query(&mut builder, &query, false);
+        let config = builder.clone().build().unwrap();
+        assert_eq!(config.max_allowed_proxies, defaults.max_allowed_proxies);
+        assert_eq!(
+            config.extra.max_allowed_rules,
+            defaults.extra.max_allowed_rules
+        );
+
+        apply_limit_query(&mut builder, &query, true);
+        let config = builder.build().unwrap();
+        assert_eq!(config.max_allowed_proxies, 1);
+        assert_eq!(config.extra.max_allowed_rules, 2);
+    }
+
+    #[test]
+    fn test_profile_query_needs_authorization() {
+        let query: SubconverterQuery = serde_json::from_str(r#"{"profile": "1"}"#).unwrap();
+        assert!(profile_requested(&query, true));
+        assert!(!profile_requested(&query, false));
+
+        let query: SubconverterQuery = serde_json::from_str(r#"{"profile": "0"}"#).unwrap();
+        assert!(!profile_requested(&query, true));
+    }
+
+    #[test]
+    fn test_insert_query() {
+        let insert_of =
+            |enable_insert: bool, external: Option<&ExternalSettings>, query_string: &str| {
+                let global = Settings {
+                    enable_insert,
+                    insert_urls: vec!["https://example.com/pinned".to_string()],
+                    prepend_insert: true,
+                    ..Default::default()
+                };
+                let query = actix_web::web::Query::<SubconverterQuery>::from_query(query_string)
+                    .unwrap()
+                    .into_inner();
+                let mut builder = SubconverterConfigBuilder::new();
+                builder.add_url("https://example.com/sub");
+                apply_insert_query(&mut builder, &query, external, &global);
+                let config = builder.build().unwrap();
+                (config.insert_urls, config.prepend_insert)
+            };
+        let pinned = || vec!["https://example.com/pinned".to_string()];
+
+        assert_eq!(insert_of(true, None, "target=clash"), (pinned(), true));
+        assert_eq!(
+            insert_of(true, None, "target=clash&insert=false"),
+            (vec![], false)
+        );
+        assert_eq!(insert_of(false, None, "target=clash"), (vec![], false));
+        assert_eq!(
+            insert_of(false, None, "target=clash&insert=true"),
+            (pinned(), true)
+        );
+        assert_eq!(
+            insert_of(true, None, "target=clash&prepend=false"),
+            (pinned(), false)
+        );
+
+        // The external config comes between the request and the settings
+        let external = ExternalSettings {
+            enable_insert: Some(true),
+            insert_urls: vec!["https://example.com/external".to_string()],
+            prepend_insert: Some(false),
+            ..Default::default()
+        };
+        let external_urls = || vec!["https://example.com/external".to_string()];
+        assert_eq!(
+            insert_of(false, Some(&external), "target=clash"),
+            (external_urls(), false)
+        );
+        assert_eq!(
+            insert_of(false, Some(&external), "target=clash&insert=false"),
+            (vec![], false)
+        );
+        assert_eq!(
+            insert_of(false, Some(&external), "target=clash&prepend=true"),
+            (external_urls(), true)
+        );
+
+        let external = ExternalSettings {
+            enable_insert: Some(false),
+            ..Default::default()
+        };
+        assert_eq!(
+            insert_of(true, Some(&external), "target=clash"),
+            (vec![], false)
+        );
+        assert_eq!(
+            insert_of(true, Some(&external), "target=clash&insert=true"),
+            (pinned(), true)
+        );
+    }
+
+    #[test]
+    fn test_check_query_needs_authorization() {
+        let check_of = |query_string: &str, authorized: bool| {
+            let query = actix_web::web::Query::<SubconverterQuery>::from_query(query_string)
+                .unwrap()
+                .into_inner();
+            let mut builder = SubconverterConfigBuilder::new();
+            builder.add_url("https://example.com/sub");
+            apply_check_query(&mut builder, &query, authorized);
+            let config = builder.build().unwrap();
+            (config.check_nodes, config.check_drop)
+        };
+
+        assert_eq!(check_of("target=clash&check=true", true), (true, false));
+        assert_eq!(check_of("target=clash&check_drop=true", true), (true, true));
+        assert_eq!(check_of("target=clash&check=true", false), (false, false));
+        assert_eq!(check_of("target=clash", true), (false, false));
+    }
+
+    #[test]
+    fn test_update_query() {
+        let global = Settings {
+            update_interval: 43200,
+            update_strict: true,
+            profile_web_page_url: "https://example.com/panel".to_string(),
+            ..Default::default()
+        };
+        let config_of = |query_string: &str| {
+            let query = actix_web::web::Query::<SubconverterQuery>::from_query(query_string)
+                .unwrap()
+                .into_inner();
+            let mut builder = SubconverterConfigBuilder::new();
+            builder.add_url("https://example.com/sub");
+            apply_update_query(&mut builder, &query, &global);
+            builder.build().unwrap()
+        };
+
+        let config = config_of("target=clash");
+        assert_eq!(config.update_interval, 43200);
+        assert!(!config.clash_update_interval);
+        assert!(config.update_strict);
+        assert!(!config.upload);
+        assert_eq!(config.upload_path, None);
+        assert_eq!(
+            config.profile_web_page_url.as_deref(),
+            Some("https://example.com/panel")
+        );
+
+        let config = config_of("target=clash&web_page_url=");
+        assert_eq!(config.profile_web_page_url, None);
+        let config = config_of("target=clash&web_page_url=https%3A%2F%2Fpanel.example");
+        assert_eq!(
+            config.profile_web_page_url.as_deref(),
+            Some("https://panel.example")
+        );
+
+        let config =
+            config_of("target=clash&interval=7200&strict=false&upload=true&upload_path=my.yaml");
+        assert_eq!(config.update_interval, 7200);
+        assert!(config.clash_update_interval);
+        assert!(!config.update_strict);
+        assert!(config.upload);
+        assert_eq!(config.upload_path.as_deref(), Some("my.yaml"));
+    }
+
+    #[test]
+    fn test_geo_emoji_query_needs_authorization() {
+        let geo_emoji_of = |query_string: &str, authorized: bool| {
+            let query = actix_web::web::Query::<SubconverterQuery>::from_query(query_string)
+                .unwrap()
+                .into_inner();
+            let mut builder = SubconverterConfigBuilder::new();
+            builder.add_url("https://example.com/sub");
+            apply_geo_emoji_query(&mut builder, &query, authorized);
+            builder.build().unwrap().geo_emoji
+        };
+
+        assert!(geo_emoji_of("target=clash&geo_emoji=true", true));
+        assert!(!geo_emoji_of("target=clash&geo_emoji=true", false));
+        assert!(!geo_emoji_of("target=clash", true));
+    }
+
+    #[test]
+    fn test_udp_query_tribool() {
+        let udp_of = |query_string: &str| {
+            let query = actix_web::web::Query::<SubconverterQuery>::from_query(query_string)
+                .unwrap()
+                .into_inner();
+            let mut builder = SubconverterConfigBuilder::new();
+            builder
+                .target(SubconverterTarget::Clash)
+                .add_url(&format!("data:text/plain;base64,{}", base64_encode(LINK)))
+                .nodelist(true);
+            apply_flag_query(&mut builder, &query, &Settings::default());
+            let result = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap()
+                .block_on(subconverter(builder.build().unwrap()))
+                .unwrap();
+            let yaml: serde_yaml::Value = serde_yaml::from_str(&result.content).unwrap();
+            yaml["proxies"][0]["udp"].as_bool()
+        };
+
+        assert_eq!(udp_of("target=clash"), None);
+        assert_eq!(udp_of("target=clash&udp="), None);
+        assert_eq!(udp_of("target=clash&udp=1"), Some(true));
+        assert_eq!(udp_of("target=clash&udp=true"), Some(true));
+        assert_eq!(udp_of("target=clash&udp=0"), Some(false));
+    }
+
+    #[test]
+    fn test_rename_query_runs_after_settings() {
+        let query = actix_web::web::Query::<SubconverterQuery>::from_query(
+            "target=clash&rename=%5E(HK)%20(%5Cd%2B)%40%241-%242%40edge%60Node%2540Node%20%60%5Bbad%40x%60no-at",
+        )
+        .unwrap()
+        .into_inner();
+        let (patterns, errors) = parse_rename_query(query.rename.as_deref().unwrap());
+        assert_eq!(
+            patterns
+                .iter()
+                .map(|p| (p._match.as_str(), p.replace.as_str()))
+                .collect::<Vec<_>>(),
+            [("^(HK) (\\d+)", "$1-$2@edge"), ("Node", "Node ")]
+        );
+        assert_eq!(errors.len(), 2);
+
+        let mut global = Settings::default();
+        global.renames = vec![RegexMatchConfig {
+            _match: "Hong Kong".to_string(),
+            replace: "HK".to_string(),
+        }];
+        let mut builder = SubconverterConfigBuilder::new();
+        apply_global_node_rules(&mut builder, &global);
+        apply_rename_query(&mut builder, &query);
+        builder.add_url("https://example.com/sub");
+        let renames = builder.build().unwrap().extra.rename_array;
+        assert_eq!(renames.len(), 3);
+        assert_eq!(renames[0]._match, "Hong Kong");
+    }
+
+    #[test]
+    fn test_group_query_with_ssd_subscription() {
+        use crate::interfaces::subconverter::{fetch_nodes, process_nodes};
+        use crate::utils::matcher::apply_matcher;
+
+        let ssd = format!(
+            "ssd://{}",
+            base64_encode(
+                r#"{"airport":"MyAirport","port":8388,"encryption":"aes-256-gcm","password":"password","servers":[{"server":"1.2.3.4","remarks":"HK 01"}]}"#
+            )
+        );
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let group_of = |query_string: &str| {
+            let query = actix_web::web::Query::<SubconverterQuery>::from_query(query_string)
+                .unwrap()
+                .into_inner();
+            let mut builder = SubconverterConfigBuilder::new();
+            builder.add_url(&format!("data:text/plain;base64,{}", base64_encode(&ssd)));
+            apply_group_query(&mut builder, &query);
+            let config = builder.build().unwrap();
+            let node_set = runtime
+                .block_on(async { process_nodes(fetch_nodes(&config).await?, &config).await })
+                .unwrap();
+            node_set.nodes[0].clone()
+        };
+
+        let mut real_rule = String::new();
+        for query_string in ["target=clash", "target=clash&group="] {
+            let node = group_of(query_string);
+            assert_eq!(node.group, "MyAirport");
+            assert!(apply_matcher(
+                "!!GROUP=MyAirport!!.*",
+                &mut real_rule,
+                &node
+            ));
+        }
+        for query_string in ["target=clash&group=Mine", "target=clash&groupname=Mine"] {
+            let node = group_of(query_string);
+            assert_eq!(node.group, "Mine");
+            assert!(apply_matcher("!!GROUP=Mine!!.*", &mut real_rule, &node));
+            assert!(!apply_matcher(
+                "!!GROUP=MyAirport!!.*",
+                &mut real_rule,
+                &node
+            ));
+        }
+    }
+}
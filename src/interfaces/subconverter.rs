@@ -1,23 +1,58 @@
 use crate::generator::config::formats::single::{proxy_to_single, ProxyUriTypes};
 use crate::generator::config::formats::ssd::proxy_to_ssd;
 use crate::generator::config::formats::{
-    loon::proxy_to_loon, mellow::proxy_to_mellow, quan::proxy_to_quan, quanx::proxy_to_quanx,
-    singbox::proxy_to_singbox, ss_sub::proxy_to_ss_sub, surge::proxy_to_surge,
+    loon::proxy_to_loon,
+    mellow::proxy_to_mellow,
+    quan::proxy_to_quan,
+    quanx::proxy_to_quanx,
+    singbox::proxy_to_singbox,
+    ss_sub::proxy_to_ss_sub,
+    surge::{proxy_to_surge, SURFBOARD_VERSION},
 };
+use crate::generator::config::group::empty_groups;
 use crate::generator::exports::proxy_to_clash::proxy_to_clash;
-use crate::models::ruleset::RulesetConfigs;
+use crate::generator::ruleconvert::count_rules;
+use crate::generator::yaml::merge::merge_clash_bases;
+use crate::interfaces::conversion_error::ConversionError;
+use crate::interfaces::preview::{preview, ConversionPreview};
+use crate::interfaces::profile::{ConversionProfile, UrlProfile};
+use crate::interfaces::validation::{validate, ConfigValidationError, ValidationIssue};
+use crate::models::conversion_warning::warnings_header;
+use crate::models::ruleset::{RulesetConfig, RulesetConfigs};
+use crate::models::subconverter_target::{fetch_base_url, load_base_content};
 use crate::models::{
-    ExtraSettings, Proxy, ProxyGroupConfigs, RegexMatchConfig, RulesetContent, SubconverterTarget,
+    ConversionWarning, EmptyGroupPlaceholder, ExtraSettings, NodeFilter, NodeTransform, Proxy,
+    ProxyGroupConfigs, ProxyType, QuanxServerRemote, RegexMatchConfig, RulesetContent,
+    SourceProxyGroups, SubconverterTarget, WarningCollector, WarningKind,
 };
+use crate::parser::infoparser::build_info_nodes;
 use crate::parser::parse_settings::ParseSettings;
 use crate::parser::subparser::add_nodes;
-use crate::rulesets::ruleset::refresh_rulesets;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::rulesets::refresher::ruleset_store;
+use crate::rulesets::ruleset::{missing_rule_groups, refresh_rulesets, remap_rule_groups};
+use crate::settings::external::ExternalSettings;
+use crate::settings::settings::settings_struct::{
+    default_info_expire_remark, default_info_traffic_remark, default_node_check_concurrency,
+    default_node_check_deadline, default_node_check_marker, default_node_check_timeout,
+};
+use crate::utils::base64::base64_encode;
+use crate::utils::fetcher::{default_fetcher, SubscriptionFetcher};
 use crate::utils::file_get_async;
-use crate::utils::http::parse_proxy;
 use crate::utils::http::web_get_async;
+use crate::utils::http::{content_disposition, parse_proxy};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::utils::node_check::{check_nodes, NodeCheckOptions};
+use crate::utils::system::{elapsed_since, safe_system_time};
+use crate::utils::url::{url_decode, url_encode};
+use crate::utils::{normalize_vmess_alter_ids, pin_nodes};
 use crate::{Settings, TemplateArgs};
+use case_insensitive_string::CaseInsensitiveString;
 use log::{debug, error, info, warn};
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
 #[derive(Debug, Clone, Default)]
 pub struct RuleBases {
@@ -31,6 +66,11 @@ pub struct RuleBases {
     pub loon_rule_base: String,
     pub sssub_rule_base: String,
     pub singbox_rule_base: String,
+    /// Base the Clash rule base is deep merged over, empty to use the rule base alone
+    ///
+    /// Set when an external config replaces the Clash base of the settings,
+    /// so the sections it does not mention are kept.
+    pub clash_global_base: String,
 }
 
 /// Configuration for subconverter
@@ -48,6 +88,8 @@ pub struct SubconverterConfig {
     pub group_name: Option<String>,
     /// Ruleset configs
     pub ruleset_configs: RulesetConfigs,
+    /// Renames applied to the groups of the rulesets, exact name or regex to new name
+    pub rule_group_map: Vec<(String, String)>,
     /// Custom proxy groups
     pub proxy_groups: ProxyGroupConfigs,
     /// Include nodes matching these remarks
@@ -62,6 +104,10 @@ pub struct SubconverterConfig {
     pub filename: Option<String>,
     /// Update interval in seconds
     pub update_interval: u32,
+    /// Write the update interval to Clash output even without a managed config prefix
+    pub clash_update_interval: bool,
+    /// Sent as the `Profile-Web-Page-Url` header of Clash responses
+    pub profile_web_page_url: Option<String>,
     /// Filter script
     pub filter_script: Option<String>,
     /// Whether update is strict
@@ -74,22 +120,63 @@ pub struct SubconverterConfig {
     pub upload: bool,
     /// Proxy for fetching subscriptions
     pub proxy: Option<String>,
+    /// User-Agent of the subscription requests, `None` for the default of the target
+    pub user_agent: Option<String>,
+    /// Extra headers of the subscription requests, e.g. `Authorization`
+    ///
+    /// Only the URLs of the request get them, not the insert URLs of the
+    /// settings nor the rulesets.
+    pub sub_headers: HashMap<String, String>,
     /// Authentication token
     pub token: Option<String>,
     /// Whether this request is authorized
     pub authorized: bool,
+    /// Whether the `proxy-providers` of Clash configs are fetched, always
+    /// done for authorized requests
+    pub follow_providers: bool,
     /// Subscription information
     pub sub_info: Option<String>,
+    /// Whether to insert traffic and expiry info nodes
+    pub append_info: bool,
+    /// Maximum size in bytes of a downloaded subscription, 0 for unlimited
+    pub max_download_size: i64,
+    /// Maximum number of nodes after parsing, 0 for unlimited
+    pub max_allowed_proxies: usize,
+    /// Whether to attach the `X-Conversion-Profile` timing header
+    pub profile: bool,
+    /// Whether to probe the nodes and mark the unreachable ones
+    pub check_nodes: bool,
+    /// Drop unreachable nodes instead of marking them
+    pub check_drop: bool,
+    /// Prepend the flag of the server country to nodes no emoji rule matched
+    pub geo_emoji: bool,
+    /// Reference the nodes through QuantumultX `server_remote` instead of `server_local`
+    pub quanx_server_remote: bool,
+    /// `as-policy` of the QuantumultX `server_remote` reference
+    pub quanx_as_policy: Option<String>,
+    /// Use the proxy groups of a full Surge source config instead of `proxy_groups`
+    ///
+    /// When a source defines groups, a full configuration with those groups
+    /// and without generated rules is written, even for a node list request.
+    pub source_groups: bool,
     /// Rule bases
     pub rule_bases: RuleBases,
     /// Template arguments
     pub template_args: Option<TemplateArgs>,
+    /// Problems found by the builder that do not stop the conversion
+    pub warnings: Vec<ValidationIssue>,
+    /// Loads the subscriptions, see [`SubscriptionFetcher`]
+    pub fetcher: Arc<dyn SubscriptionFetcher>,
+    /// Settings snapshot the conversion runs with, see [`Settings::current`]
+    pub settings: Arc<Settings>,
 }
 
 /// Builder for SubconverterConfig
 #[derive(Debug, Clone)]
 pub struct SubconverterConfigBuilder {
     config: SubconverterConfig,
+    /// Surge version applied in `build` when the target is Surge
+    surge_version: Option<i32>,
 }
 
 impl Default for SubconverterConfigBuilder {
@@ -99,8 +186,16 @@ impl Default for SubconverterConfigBuilder {
 }
 
 impl SubconverterConfigBuilder {
-    /// Create a new default builder
+    /// Create a new default builder with a snapshot of the current settings
     pub fn new() -> Self {
+        Self::with_settings(Settings::current())
+    }
+
+    /// Create a new default builder converting with `settings`
+    ///
+    /// The defaults are taken from `settings` and the conversion reads them
+    /// instead of the global settings, which may be reloaded meanwhile.
+    pub fn with_settings(settings: Arc<Settings>) -> Self {
         SubconverterConfigBuilder {
             config: SubconverterConfig {
                 target: SubconverterTarget::Clash,
@@ -109,25 +204,46 @@ impl SubconverterConfigBuilder {
                 prepend_insert: false,
                 group_name: None,
                 ruleset_configs: RulesetConfigs::default(),
+                rule_group_map: settings.ruleset_group_map.clone(),
                 proxy_groups: Vec::new(),
                 include_remarks: Vec::new(),
                 exclude_remarks: Vec::new(),
-                extra: ExtraSettings::default(),
+                extra: ExtraSettings::from_settings(&settings),
                 device_id: None,
                 filename: None,
                 update_interval: 86400, // 24 hours
+                clash_update_interval: false,
+                profile_web_page_url: Some(settings.profile_web_page_url.clone())
+                    .filter(|url| !url.is_empty()),
                 filter_script: None,
                 update_strict: false,
                 managed_config_prefix: String::new(),
                 upload_path: None,
                 upload: false,
                 proxy: None,
+                user_agent: None,
+                sub_headers: HashMap::new(),
                 token: None,
                 authorized: false,
+                follow_providers: false,
                 sub_info: None,
+                append_info: false,
+                max_download_size: settings.max_allowed_download_size,
+                max_allowed_proxies: settings.max_allowed_proxies,
+                profile: false,
+                check_nodes: false,
+                check_drop: false,
+                geo_emoji: false,
+                quanx_server_remote: false,
+                quanx_as_policy: None,
+                source_groups: false,
                 rule_bases: RuleBases::default(),
                 template_args: None,
+                warnings: Vec::new(),
+                fetcher: default_fetcher(),
+                settings,
             },
+            surge_version: None,
         }
     }
 
@@ -145,29 +261,71 @@ impl SubconverterConfigBuilder {
         self
     }
 
-    /// Set Surge version if target is Surge
+    /// Set the Surge version, ignored with a warning for other targets
     pub fn surge_version(&mut self, version: i32) -> &mut Self {
-        if let SubconverterTarget::Surge(_) = self.config.target {
-            self.config.target = SubconverterTarget::Surge(version);
-        }
+        self.surge_version = Some(version);
+        self
+    }
+
+    /// Set the fetcher of the subscriptions, e.g. to serve them from memory in tests
+    pub fn fetcher(&mut self, fetcher: Arc<dyn SubscriptionFetcher>) -> &mut Self {
+        self.config.fetcher = fetcher;
+        self
+    }
+
+    /// Set the User-Agent of the subscription requests, `None` for the default of the target
+    pub fn user_agent(&mut self, user_agent: Option<String>) -> &mut Self {
+        self.config.user_agent = user_agent;
+        self
+    }
+
+    /// Set the extra headers of the subscription requests
+    pub fn sub_headers(&mut self, headers: HashMap<String, String>) -> &mut Self {
+        self.config.sub_headers = headers;
+        self
+    }
+
+    /// Add a header to the subscription requests
+    pub fn add_sub_header(&mut self, name: &str, value: &str) -> &mut Self {
+        self.config
+            .sub_headers
+            .insert(name.to_string(), value.to_string());
         self
     }
 
-    /// Add a URL to parse
+    /// Add a URL to parse, see [`tag_subscription_url`] for the `#Label` syntax
     pub fn add_url(&mut self, url: &str) -> &mut Self {
-        self.config.urls.push(url.to_string());
+        self.config.urls.push(tag_subscription_url(url));
+        self
+    }
+
+    /// Add subscription content to parse in place of a URL
+    ///
+    /// The content is passed on as a data URI, nothing is fetched.
+    pub fn add_content(&mut self, content: &str) -> &mut Self {
+        self.config
+            .urls
+            .push(format!("data:text/plain;base64,{}", base64_encode(content)));
         self
     }
 
-    /// Set URLs to parse
+    /// Set URLs to parse, empty entries are skipped
     pub fn urls(&mut self, urls: Vec<String>) -> &mut Self {
-        self.config.urls = urls;
+        self.config.urls = urls
+            .iter()
+            .map(|url| url.trim())
+            .filter(|url| !url.is_empty())
+            .map(tag_subscription_url)
+            .collect();
         self
     }
 
-    /// Set URLs from pipe-separated string
+    /// Set URLs from a string of links joined with `|`, see [`split_subscription_urls`]
     pub fn urls_from_str(&mut self, urls: &str) -> &mut Self {
-        self.config.urls = urls.split('|').map(|s| s.trim().to_string()).collect();
+        self.config.urls = split_subscription_urls(urls)
+            .iter()
+            .map(|url| tag_subscription_url(url))
+            .collect();
         self
     }
 
@@ -189,9 +347,9 @@ impl SubconverterConfigBuilder {
         self
     }
 
-    /// Set insert URLs from pipe-separated string
+    /// Set insert URLs from a string of links joined with `|`, see [`split_subscription_urls`]
     pub fn insert_urls_from_str(&mut self, urls: &str) -> &mut Self {
-        self.config.insert_urls = urls.split('|').map(|s| s.trim().to_string()).collect();
+        self.config.insert_urls = split_subscription_urls(urls);
         self
     }
 
@@ -218,6 +376,12 @@ impl SubconverterConfigBuilder {
         self
     }
 
+    /// Set the renames applied to the groups of the rulesets
+    pub fn rule_group_map(&mut self, map: Vec<(String, String)>) -> &mut Self {
+        self.config.rule_group_map = map;
+        self
+    }
+
     /// Add an include remark pattern
     pub fn add_include_remark(&mut self, pattern: &str) -> &mut Self {
         self.config.include_remarks.push(pattern.to_string());
@@ -252,6 +416,12 @@ impl SubconverterConfigBuilder {
         self
     }
 
+    /// Append rename patterns, run after the ones already set
+    pub fn add_renames(&mut self, patterns: Vec<RegexMatchConfig>) -> &mut Self {
+        self.config.extra.rename_array.extend(patterns);
+        self
+    }
+
     pub fn add_emoji(&mut self, add: bool) -> &mut Self {
         self.config.extra.add_emoji = add;
         self
@@ -298,6 +468,18 @@ impl SubconverterConfigBuilder {
         self
     }
 
+    /// Set the interface the nodes are dialed from, unless they set one
+    pub fn interface(&mut self, interface: Option<String>) -> &mut Self {
+        self.config.extra.interface = interface;
+        self
+    }
+
+    /// Set the routing mark of the nodes, unless they set one
+    pub fn routing_mark(&mut self, routing_mark: Option<u32>) -> &mut Self {
+        self.config.extra.routing_mark = routing_mark;
+        self
+    }
+
     /// Set whether to sort nodes
     pub fn sort(&mut self, sort: bool) -> &mut Self {
         self.config.extra.sort_flag = sort;
@@ -310,6 +492,12 @@ impl SubconverterConfigBuilder {
         self
     }
 
+    /// Set the regex of the nodes moved to the front after sorting
+    pub fn pin_regex(&mut self, pattern: String) -> &mut Self {
+        self.config.extra.pin_regex = pattern;
+        self
+    }
+
     /// Set whether to filter deprecated nodes
     pub fn filter_deprecated(&mut self, filter: bool) -> &mut Self {
         self.config.extra.filter_deprecated = filter;
@@ -333,6 +521,36 @@ impl SubconverterConfigBuilder {
         self
     }
 
+    /// Set whether Loon configs get the rules of the rulesets or remote rules
+    pub fn expand_rulesets(&mut self, expand: bool) -> &mut Self {
+        self.config.extra.expand_rulesets = expand;
+        self
+    }
+
+    /// Set output style of the Clash proxies section (flow, block or compact)
+    pub fn clash_proxies_style(&mut self, style: String) -> &mut Self {
+        self.config.extra.clash_proxies_style = style;
+        self
+    }
+
+    /// Set output style of the Clash proxy groups section (flow, block or compact)
+    pub fn clash_proxy_groups_style(&mut self, style: String) -> &mut Self {
+        self.config.extra.clash_proxy_groups_style = style;
+        self
+    }
+
+    /// Set whether to normalize generated Clash output for Clash.Meta
+    pub fn sanitize_output(&mut self, sanitize: bool) -> &mut Self {
+        self.config.extra.sanitize_output = sanitize;
+        self
+    }
+
+    /// Set whether to include Shadowsocks nodes as origin/plain links in SSR output
+    pub fn ssr_format_ss(&mut self, enable: bool) -> &mut Self {
+        self.config.extra.ssr_format_ss = enable;
+        self
+    }
+
     /// Set whether to generate node list
     pub fn nodelist(&mut self, nodelist: bool) -> &mut Self {
         self.config.extra.nodelist = nodelist;
@@ -357,18 +575,48 @@ impl SubconverterConfigBuilder {
         self
     }
 
+    /// Set whether QuantumultX configs reference the nodes through `server_remote`
+    pub fn quanx_server_remote(&mut self, server_remote: bool) -> &mut Self {
+        self.config.quanx_server_remote = server_remote;
+        self
+    }
+
+    /// Set the `as-policy` of the QuantumultX `server_remote` reference
+    pub fn quanx_as_policy(&mut self, as_policy: Option<String>) -> &mut Self {
+        self.config.quanx_as_policy = as_policy;
+        self
+    }
+
+    /// Set whether the proxy groups of a full Surge source config are used
+    pub fn source_groups(&mut self, source_groups: bool) -> &mut Self {
+        self.config.source_groups = source_groups;
+        self
+    }
+
     /// Set filename
     pub fn filename(&mut self, filename: Option<String>) -> &mut Self {
         self.config.filename = filename;
         self
     }
 
+    /// Set the web page sent in the `Profile-Web-Page-Url` header of Clash responses
+    pub fn profile_web_page_url(&mut self, url: Option<String>) -> &mut Self {
+        self.config.profile_web_page_url = url;
+        self
+    }
+
     /// Set update interval
     pub fn update_interval(&mut self, interval: u32) -> &mut Self {
         self.config.update_interval = interval;
         self
     }
 
+    /// Set whether Clash output gets the update interval without a managed config prefix
+    pub fn clash_update_interval(&mut self, enabled: bool) -> &mut Self {
+        self.config.clash_update_interval = enabled;
+        self
+    }
+
     /// Set filter script
     pub fn filter_script(&mut self, script: Option<String>) -> &mut Self {
         self.config.filter_script = script;
@@ -417,11 +665,147 @@ impl SubconverterConfigBuilder {
         self
     }
 
+    /// Set whether the `proxy-providers` of Clash configs are fetched
+    pub fn follow_providers(&mut self, follow_providers: bool) -> &mut Self {
+        self.config.follow_providers = follow_providers;
+        self
+    }
+
     /// Set subscription information
     pub fn sub_info(&mut self, sub_info: Option<String>) -> &mut Self {
         self.config.sub_info = sub_info;
         self
     }
+
+    /// Set whether to insert traffic and expiry info nodes
+    pub fn append_info(&mut self, append: bool) -> &mut Self {
+        self.config.append_info = append;
+        self
+    }
+
+    /// Set the maximum size in bytes of a downloaded subscription, 0 for unlimited
+    pub fn max_download_size(&mut self, size: i64) -> &mut Self {
+        self.config.max_download_size = size;
+        self
+    }
+
+    /// Set the maximum number of nodes, 0 for unlimited
+    pub fn max_allowed_proxies(&mut self, max: usize) -> &mut Self {
+        self.config.max_allowed_proxies = max;
+        self
+    }
+
+    /// Set the maximum number of rules, 0 for unlimited
+    pub fn max_allowed_rules(&mut self, max: usize) -> &mut Self {
+        self.config.extra.max_allowed_rules = max;
+        self
+    }
+
+    /// Set the maximum number of characters in a node remark, `None` for no limit
+    ///
+    /// A length of 0 is rejected by [`build`](Self::build).
+    pub fn max_remark_length(&mut self, max: Option<usize>) -> &mut Self {
+        self.config.extra.max_remark_length = max;
+        self
+    }
+
+    /// Set the template naming every node, `None` to keep the remarks
+    pub fn remark_template(&mut self, template: Option<String>) -> &mut Self {
+        self.config.extra.remark_template = template;
+        self
+    }
+
+    /// Set the node list URLs of proxy providers, keyed by provider name
+    pub fn provider_urls(&mut self, urls: HashMap<String, String>) -> &mut Self {
+        self.config.extra.provider_urls = urls;
+        self
+    }
+
+    /// Add the node list URL of a proxy provider
+    pub fn add_provider_url(&mut self, name: &str, url: &str) -> &mut Self {
+        self.config
+            .extra
+            .provider_urls
+            .insert(name.to_string(), url.to_string());
+        self
+    }
+
+    /// Set what proxy groups whose rules matched nothing are filled with
+    pub fn empty_group_placeholder(&mut self, placeholder: EmptyGroupPlaceholder) -> &mut Self {
+        self.config.extra.empty_group_placeholder = placeholder;
+        self
+    }
+
+    /// Set the proxy types to keep, empty to keep all types
+    pub fn include_types(&mut self, types: Vec<ProxyType>) -> &mut Self {
+        self.config.extra.include_types = types;
+        self
+    }
+
+    /// Set the proxy types to drop, these win over `include_types`
+    pub fn exclude_types(&mut self, types: Vec<ProxyType>) -> &mut Self {
+        self.config.extra.exclude_types = types;
+        self
+    }
+
+    /// Set the regexes of the server addresses to keep, empty to keep all nodes
+    pub fn include_servers(&mut self, patterns: Vec<String>) -> &mut Self {
+        self.config.extra.include_servers = patterns;
+        self
+    }
+
+    /// Set the regexes of the server addresses to drop, these win over `include_servers`
+    pub fn exclude_servers(&mut self, patterns: Vec<String>) -> &mut Self {
+        self.config.extra.exclude_servers = patterns;
+        self
+    }
+
+    /// Set whether Clash proxies of unknown types are written back to Clash output
+    pub fn preserve_unknown_proxies(&mut self, preserve: bool) -> &mut Self {
+        self.config.extra.preserve_unknown_proxies = preserve;
+        self
+    }
+
+    /// Add a node filter, run before renaming and emoji
+    pub fn add_filter(&mut self, filter: Box<dyn NodeFilter>) -> &mut Self {
+        self.config.extra.plugins.filters.push(Arc::from(filter));
+        self
+    }
+
+    /// Add a node transform, run after the filters and before renaming and emoji
+    pub fn add_transform(&mut self, transform: Box<dyn NodeTransform>) -> &mut Self {
+        self.config
+            .extra
+            .plugins
+            .transforms
+            .push(Arc::from(transform));
+        self
+    }
+
+    /// Set whether to attach the `X-Conversion-Profile` timing header
+    pub fn profile(&mut self, profile: bool) -> &mut Self {
+        self.config.profile = profile;
+        self
+    }
+
+    /// Set whether to probe the nodes and mark the unreachable ones
+    pub fn check_nodes(&mut self, check: bool) -> &mut Self {
+        self.config.check_nodes = check;
+        self
+    }
+
+    /// Set whether nodes without a country hint get the flag of their server country
+    pub fn geo_emoji(&mut self, geo_emoji: bool) -> &mut Self {
+        self.config.geo_emoji = geo_emoji;
+        self
+    }
+
+    /// Set whether unreachable nodes are dropped instead of marked
+    pub fn check_drop(&mut self, drop: bool) -> &mut Self {
+        self.config.check_drop = drop;
+        self
+    }
+
     /// rule bases updates
     pub fn rule_bases(&mut self, rule_bases: RuleBases) -> &mut Self {
         self.config.rule_bases = rule_bases;
@@ -440,6 +824,12 @@ impl SubconverterConfigBuilder {
         self
     }
 
+    /// Set the Clash base the Clash rule base is deep merged over
+    pub fn clash_global_base(&mut self, path: &str) -> &mut Self {
+        self.config.rule_bases.clash_global_base = path.to_string();
+        self
+    }
+
     /// Set rule base for Surge
     pub fn surge_rule_base(&mut self, path: &str) -> &mut Self {
         self.config.rule_bases.surge_rule_base = path.to_string();
@@ -489,14 +879,21 @@ impl SubconverterConfigBuilder {
     }
 
     /// Build the final configuration
-    pub fn build(self) -> Result<SubconverterConfig, String> {
-        let config = self.config;
-
-        // Basic validation
-        if config.urls.is_empty() && config.insert_urls.is_empty() {
-            return Err("No URLs provided".to_string());
+    ///
+    /// Every problem is reported at once, the build fails when any of them
+    /// is an error. The warnings are kept in `SubconverterConfig::warnings`.
+    pub fn build(self) -> Result<SubconverterConfig, ConfigValidationError> {
+        let mut config = self.config;
+        if let (SubconverterTarget::Surge(_), Some(version)) = (&config.target, self.surge_version)
+        {
+            config.target = SubconverterTarget::Surge(version);
         }
 
+        let issues = validate(&config, self.surge_version);
+        if issues.iter().any(|issue| issue.is_error()) {
+            return Err(ConfigValidationError { issues });
+        }
+        config.warnings = issues;
         Ok(config)
     }
 }
@@ -508,6 +905,23 @@ pub struct SubconverterResult {
     pub content: String,
     /// Response headers
     pub headers: HashMap<String, String>,
+    /// Content-Type of the converted content
+    pub content_type: String,
+    /// Problems that did not stop the conversion
+    pub warnings: Vec<ConversionWarning>,
+}
+
+/// Nodes and metadata parsed from a single subscription URL
+#[derive(Debug, Clone, Default)]
+pub struct ParsedSubscription {
+    /// Nodes left after the include/exclude filters
+    pub nodes: Vec<Proxy>,
+    /// Subscription info found in the headers or nodes
+    pub sub_info: Option<String>,
+    /// Fetch and parse timing of this URL
+    pub profile: UrlProfile,
+    /// Proxy groups when the subscription is a full Surge configuration
+    pub source_groups: Option<SourceProxyGroups>,
 }
 
 /// Options for parsing subscriptions
@@ -521,15 +935,128 @@ pub struct ParseOptions {
 
     /// Whether the request is authorized
     pub authorized: bool,
+
+    /// Whether the `proxy-providers` of Clash configs are fetched
+    pub follow_providers: bool,
+
+    /// Maximum size in bytes of a downloaded subscription, 0 for unlimited
+    pub max_download_size: i64,
+
+    /// Maximum number of nodes in the subscription, 0 for unlimited
+    pub max_allowed_proxies: usize,
+
+    /// Loads the subscription
+    pub fetcher: Arc<dyn SubscriptionFetcher>,
+
+    /// Headers of the request when the subscription is downloaded
+    pub headers: HashMap<String, String>,
+
+    /// Settings snapshot the subscription is parsed with
+    pub settings: Arc<Settings>,
 }
 
 impl Default for ParseOptions {
     fn default() -> Self {
+        let global = Settings::current();
         Self {
             include_remarks: Vec::new(),
             exclude_remarks: Vec::new(),
             authorized: false,
+            follow_providers: false,
+            max_download_size: global.max_allowed_download_size,
+            max_allowed_proxies: global.max_allowed_proxies,
+            fetcher: default_fetcher(),
+            headers: HashMap::new(),
+            settings: global,
+        }
+    }
+}
+
+/// Split links joined with `|` or `%7C`
+///
+/// A piece that does not start a new link, i.e. has no `scheme:` prefix,
+/// continues the previous link when that one has a query or a fragment, so
+/// `https://example.com/sub?types=ss|vmess` stays one URL. The separators
+/// split everywhere else, also inside a `data:` URI: use its base64 form to
+/// keep a `|` in the content. Empty entries are dropped with a warning.
+pub fn split_subscription_urls(urls: &str) -> Vec<String> {
+    let mut entries: Vec<String> = Vec::new();
+    let mut separator = "";
+    let mut rest = urls;
+    loop {
+        let next = next_url_separator(rest);
+        let piece = &rest[..next.map_or(rest.len(), |(pos, _)| pos)];
+        let trimmed = piece.trim();
+        match entries.last_mut() {
+            Some(last) if !trimmed.is_empty() && !starts_link(trimmed) && continues_link(last) => {
+                last.push_str(separator);
+                last.push_str(piece.trim_end());
+            }
+            _ if trimmed.is_empty() => {
+                warn!("Skipping empty subscription URL in '{}'", urls);
+            }
+            _ => entries.push(trimmed.to_string()),
+        }
+        match next {
+            Some((pos, len)) => {
+                separator = &rest[pos..pos + len];
+                rest = &rest[pos + len..];
+            }
+            None => break,
+        }
+    }
+    entries
+}
+
+/// Position and length of the next `|` or `%7C`
+fn next_url_separator(urls: &str) -> Option<(usize, usize)> {
+    let bytes = urls.as_bytes();
+    (0..bytes.len()).find_map(|i| match bytes[i] {
+        b'|' => Some((i, 1)),
+        b'%' if bytes
+            .get(i + 1..i + 3)
+            .is_some_and(|hex| hex.eq_ignore_ascii_case(b"7C")) =>
+        {
+            Some((i, 3))
+        }
+        _ => None,
+    })
+}
+
+/// Whether `piece` starts with a URL scheme or is the `nullnode` placeholder
+fn starts_link(piece: &str) -> bool {
+    if piece == "nullnode" {
+        return true;
+    }
+    match piece.split_once(':') {
+        Some((scheme, _)) => {
+            scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
         }
+        None => false,
+    }
+}
+
+/// Whether a `|` may belong to the query or fragment of `link`
+fn continues_link(link: &str) -> bool {
+    link.contains("://") && (link.contains('?') || link.contains('#'))
+}
+
+/// Turn a `https://example.com/sub#Label` subscription URL into `tag:Label,https://example.com/sub`
+///
+/// The fragment is stripped before fetching and the label becomes the group of
+/// every node from that subscription. Only http(s) links are rewritten since the
+/// fragment of a node link is its remark.
+pub fn tag_subscription_url(url: &str) -> String {
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return url.to_string();
+    }
+    match url.split_once('#') {
+        Some((link, label)) if label.is_empty() => link.to_string(),
+        Some((link, label)) => format!("tag:{},{}", url_decode(label), link),
+        None => url.to_string(),
     }
 }
 
@@ -547,8 +1074,25 @@ pub async fn parse_subscription(
     options: ParseOptions,
     group_id: i32,
 ) -> Result<Vec<Proxy>, String> {
+    parse_subscription_with_info(url, options, group_id)
+        .await
+        .map(|parsed| parsed.nodes)
+}
+
+/// Parse a subscription URL, also returning the subscription info and timing
+///
+/// # Returns
+/// * `Ok(ParsedSubscription)` - The parsed proxies, subscription info and timing
+/// * `Err(String)` - Error message if parsing fails
+pub async fn parse_subscription_with_info(
+    url: &str,
+    options: ParseOptions,
+    group_id: i32,
+) -> Result<ParsedSubscription, String> {
+    let start = safe_system_time();
+
     // Create a new parse settings instance
-    let mut parse_settings = ParseSettings::default();
+    let mut parse_settings = ParseSettings::from_settings(&options.settings);
 
     // Set options from the provided config
     if !options.include_remarks.is_empty() {
@@ -560,6 +1104,19 @@ pub async fn parse_subscription(
     }
 
     parse_settings.authorized = options.authorized;
+    parse_settings.follow_providers = options.follow_providers;
+    parse_settings.max_download_size = options.max_download_size;
+    parse_settings.content_limits.max_nodes = options.max_allowed_proxies;
+    parse_settings.fetcher = options.fetcher;
+    if !options.headers.is_empty() {
+        parse_settings.request_header = Some(
+            options
+                .headers
+                .iter()
+                .map(|(name, value)| (CaseInsensitiveString::new(name.as_str()), value.clone()))
+                .collect(),
+        );
+    }
 
     // Create a vector to hold the nodes
     let mut nodes = Vec::new();
@@ -568,65 +1125,250 @@ pub async fn parse_subscription(
     // We use group_id = 0 since we don't care about it in this context
     add_nodes(url.to_string(), &mut nodes, group_id, &mut parse_settings).await?;
 
-    Ok(nodes)
+    let fetch = parse_settings.fetch_time;
+    Ok(ParsedSubscription {
+        nodes,
+        sub_info: parse_settings.sub_info,
+        profile: UrlProfile {
+            fetch,
+            parse: elapsed_since(start).saturating_sub(fetch),
+            nodes: parse_settings.parsed_count,
+        },
+        source_groups: parse_settings.source_groups,
+    })
 }
 
 /// Process a subscription conversion request
-pub async fn subconverter(config: SubconverterConfig) -> Result<SubconverterResult, String> {
-    let mut response_headers = HashMap::new();
-    let mut nodes = Vec::new();
-    let global = Settings::current();
-
+pub async fn subconverter(
+    config: SubconverterConfig,
+) -> Result<SubconverterResult, ConversionError> {
     info!(
         "Processing subscription conversion request to {}",
         config.target.to_str()
     );
 
+    let node_set = fetch_nodes(&config).await?;
+    let config = apply_source_groups(config, &node_set);
+    let node_set = process_nodes(node_set, &config).await?;
+    generate(&node_set, &config.target, &config).await
+}
+
+/// Fetch and process the nodes of a conversion without generating its output
+///
+/// The preview lists the proxy groups with the nodes they match and the
+/// rulesets the rule generator would apply.
+pub async fn subconverter_preview(
+    config: SubconverterConfig,
+) -> Result<ConversionPreview, ConversionError> {
+    info!(
+        "Processing subscription preview request to {}",
+        config.target.to_str()
+    );
+
+    let node_set = fetch_nodes(&config).await?;
+    let config = apply_source_groups(config, &node_set);
+    let node_set = process_nodes(node_set, &config).await?;
+    Ok(preview(&node_set, &config))
+}
+
+/// Convert the subscriptions of `config` to several targets at once
+///
+/// The subscriptions are fetched, parsed, filtered and the rulesets refreshed
+/// only once, then the output of every target is generated from the same
+/// nodes. The target set in `config` is ignored. Fails as a whole only when
+/// the shared stages fail, a target producing no output gets its own error.
+pub async fn subconverter_batch(
+    config: SubconverterConfig,
+    targets: &[SubconverterTarget],
+) -> Result<
+    Vec<(
+        SubconverterTarget,
+        Result<SubconverterResult, ConversionError>,
+    )>,
+    ConversionError,
+> {
+    info!(
+        "Processing subscription conversion request to {}",
+        targets
+            .iter()
+            .map(|target| target.to_str())
+            .collect::<Vec<_>>()
+            .join(",")
+    );
+
+    let node_set = fetch_nodes(&config).await?;
+    let config = apply_source_groups(config, &node_set);
+    let node_set = process_nodes(node_set, &config).await?;
+    let mut results = Vec::with_capacity(targets.len());
+    for target in targets {
+        let result = match generate(&node_set, target, &config).await {
+            Ok(result) if result.content.is_empty() => {
+                Err(ConversionError::EmptyOutput(target.to_str()))
+            }
+            result => result,
+        };
+        if let Err(e) = &result {
+            error!("Failed to generate {}: {}", target.to_str(), e);
+        }
+        results.push((target.clone(), result));
+    }
+    Ok(results)
+}
+
+/// Nodes of a conversion and what is known about their sources
+///
+/// Built by [`fetch_nodes`], completed by [`process_nodes`] and shared by
+/// every [`generate`] call, so one set of nodes can be converted to several
+/// targets.
+#[derive(Debug, Clone)]
+pub struct NodeSet {
+    pub nodes: Vec<Proxy>,
+    /// Subscription info of the first main URL that sent one, after
+    /// `process_nodes` the info that ends up in the response
+    pub sub_info: Option<String>,
+    /// URLs skipped because they failed, with the error
+    pub failed_urls: Vec<(String, String)>,
+    /// Proxy groups of the first main URL that is a full Surge configuration
+    pub source_groups: Option<SourceProxyGroups>,
+    /// Rulesets for the rule generator, loaded by `process_nodes`
+    pub ruleset_content: Vec<RulesetContent>,
+    /// Timing of the stages so far
+    pub profile: ConversionProfile,
+    /// When the conversion started
+    pub started: SystemTime,
+    /// Warnings of the stages so far, starting with those of the config
+    pub warnings: Vec<ConversionWarning>,
+}
+
+/// Headers of the subscription requests of `config`
+///
+/// The User-Agent is the one set on the config, else the one of
+/// `sub_headers`, else the default of the target.
+fn subscription_headers(config: &SubconverterConfig) -> HashMap<String, String> {
+    let mut headers = config.sub_headers.clone();
+    let is_user_agent = |name: &String| name.eq_ignore_ascii_case("User-Agent");
+    let user_agent = match &config.user_agent {
+        Some(user_agent) => {
+            headers.retain(|name, _| !is_user_agent(name));
+            Some(user_agent.as_str())
+        }
+        None if headers.keys().any(is_user_agent) => None,
+        None => config.target.subscription_user_agent(),
+    };
+    if let Some(user_agent) = user_agent {
+        headers.insert("User-Agent".to_string(), user_agent.to_string());
+    }
+    headers
+}
+
+/// Fetch and parse the insert and main URLs of a conversion
+///
+/// Failed URLs are skipped when the settings allow it, the nodes of the
+/// insert URLs are merged with the main nodes as configured.
+pub async fn fetch_nodes(config: &SubconverterConfig) -> Result<NodeSet, ConversionError> {
+    let started = safe_system_time();
+    let mut profile = ConversionProfile::new(&config.target.to_str());
+    let mut nodes = Vec::new();
+    let global = &config.settings;
+    let warnings = WarningCollector::new();
+
     // Parse subscription URLs
     let opts = ParseOptions {
         include_remarks: config.include_remarks.clone(),
         exclude_remarks: config.exclude_remarks.clone(),
         authorized: config.authorized,
+        follow_providers: config.follow_providers,
+        max_download_size: config.max_download_size,
+        max_allowed_proxies: config.max_allowed_proxies,
+        fetcher: config.fetcher.clone(),
+        headers: subscription_headers(config),
+        settings: config.settings.clone(),
     };
+    // The custom headers may hold credentials for the URLs of the request
+    let mut insert_opts = opts.clone();
+    insert_opts
+        .headers
+        .retain(|name, _| name.eq_ignore_ascii_case("User-Agent"));
 
     // Parse insert URLs first if needed
     let mut insert_nodes = Vec::new();
+    let mut failed_urls = Vec::new();
     if !config.insert_urls.is_empty() {
         let mut group_id = -1;
         info!("Fetching node data from insert URLs");
         for url in &config.insert_urls {
             debug!("Parsing insert URL: {}", url);
-            match parse_subscription(url, opts.clone(), group_id).await {
-                Ok(mut parsed_nodes) => {
-                    info!("Found {} nodes from insert URL", parsed_nodes.len());
-                    insert_nodes.append(&mut parsed_nodes);
+            match parse_subscription_with_info(url, insert_opts.clone(), group_id).await {
+                Ok(mut parsed) => {
+                    info!("Found {} nodes from insert URL", parsed.nodes.len());
+                    debug!(
+                        "stage=fetch url={} fetch_ms={} parse_ms={} nodes={}",
+                        url,
+                        parsed.profile.fetch.as_millis(),
+                        parsed.profile.parse.as_millis(),
+                        parsed.profile.nodes
+                    );
+                    profile.urls.push(parsed.profile);
+                    insert_nodes.append(&mut parsed.nodes);
                 }
                 Err(e) => {
-                    warn!("Failed to parse insert URL '{}': {}", url, e);
+                    let error = ConversionError::UrlFailed {
+                        url: url.clone(),
+                        error: e.clone(),
+                        insert: true,
+                    };
                     if !global.skip_failed_links {
-                        return Err(format!("Failed to parse insert URL '{}': {}", url, e));
+                        return Err(error);
                     }
+                    warnings.push(WarningKind::FailedUrl, error.to_string());
+                    failed_urls.push((url.clone(), e));
                 }
             }
-            group_id += 1;
+            // Insert URLs count down so their ids never clash with the main URLs
+            group_id -= 1;
         }
     }
 
     let mut group_id = 0;
+    let mut failed_main_urls = 0;
+    let mut parsed_sub_info = None;
+    let mut source_groups = None;
     // Parse main URLs
     info!("Fetching node data from main URLs");
     for url in &config.urls {
         debug!("Parsing URL: {}", url);
-        match parse_subscription(url, opts.clone(), group_id).await {
-            Ok(mut parsed_nodes) => {
-                info!("Found {} nodes from URL", parsed_nodes.len());
-                nodes.append(&mut parsed_nodes);
+        match parse_subscription_with_info(url, opts.clone(), group_id).await {
+            Ok(mut parsed) => {
+                info!("Found {} nodes from URL", parsed.nodes.len());
+                debug!(
+                    "stage=fetch url={} fetch_ms={} parse_ms={} nodes={}",
+                    url,
+                    parsed.profile.fetch.as_millis(),
+                    parsed.profile.parse.as_millis(),
+                    parsed.profile.nodes
+                );
+                profile.urls.push(parsed.profile);
+                if parsed_sub_info.is_none() {
+                    parsed_sub_info = parsed.sub_info;
+                }
+                if source_groups.is_none() {
+                    source_groups = parsed.source_groups;
+                }
+                nodes.append(&mut parsed.nodes);
             }
             Err(e) => {
-                error!("Failed to parse URL '{}': {}", url, e);
+                let error = ConversionError::UrlFailed {
+                    url: url.clone(),
+                    error: e.clone(),
+                    insert: false,
+                };
                 if !global.skip_failed_links {
-                    return Err(format!("Failed to parse URL '{}': {}", url, e));
+                    error!("{}", error);
+                    return Err(error);
                 }
+                warnings.push(WarningKind::FailedUrl, error.to_string());
+                failed_main_urls += 1;
+                failed_urls.push((url.clone(), e));
             }
         }
         group_id += 1;
@@ -634,7 +1376,16 @@ pub async fn subconverter(config: SubconverterConfig) -> Result<SubconverterResu
 
     // Exit if found nothing
     if nodes.is_empty() && insert_nodes.is_empty() {
-        return Err("No nodes were found!".to_string());
+        if failed_main_urls > 0 && failed_main_urls == config.urls.len() {
+            // The error of the first URL tells why, e.g. where its YAML breaks
+            return Err(ConversionError::AllUrlsFailed(
+                failed_urls
+                    .iter()
+                    .find(|(url, _)| config.urls.contains(url))
+                    .cloned(),
+            ));
+        }
+        return Err(ConversionError::NoNodes);
     }
 
     // Merge insert nodes and main nodes
@@ -658,10 +1409,95 @@ pub async fn subconverter(config: SubconverterConfig) -> Result<SubconverterResu
         nodes.append(&mut insert_nodes);
     }
 
+    if config.max_allowed_proxies > 0 && nodes.len() > config.max_allowed_proxies {
+        return Err(ConversionError::TooManyNodes {
+            count: nodes.len(),
+            limit: config.max_allowed_proxies,
+        });
+    }
+
+    Ok(NodeSet {
+        nodes,
+        sub_info: parsed_sub_info,
+        failed_urls,
+        source_groups,
+        ruleset_content: Vec::new(),
+        profile,
+        started,
+        warnings: config
+            .warnings
+            .iter()
+            .map(|issue| ConversionWarning {
+                kind: WarningKind::Config,
+                message: issue.to_string(),
+            })
+            .chain(warnings.take())
+            .collect(),
+    })
+}
+
+/// Switch `config` to the proxy groups of the sources when asked to and there are any
+pub fn apply_source_groups(
+    mut config: SubconverterConfig,
+    node_set: &NodeSet,
+) -> SubconverterConfig {
+    if !config.source_groups {
+        return config;
+    }
+    if let Some(source) = &node_set.source_groups {
+        info!(
+            "Using the {} proxy groups of the source configuration",
+            source.groups.len()
+        );
+        config.proxy_groups = source.groups.clone();
+        config
+            .extra
+            .provider_urls
+            .extend(source.provider_urls.clone());
+        // The rules of the source are not converted, generated rules would target other groups
+        config.extra.enable_rule_generator = false;
+        config.extra.nodelist = false;
+    }
+    config
+}
+
+/// `config` with the proxy groups of the source as `node_set` holds them
+///
+/// `process_nodes` points the members of the source groups at the renamed
+/// nodes, after `apply_source_groups` copied them to the config.
+pub(crate) fn with_source_groups<'a>(
+    config: &'a SubconverterConfig,
+    source: Option<&SourceProxyGroups>,
+) -> Cow<'a, SubconverterConfig> {
+    match source {
+        Some(source) if config.source_groups => {
+            let mut config = config.clone();
+            config.proxy_groups = source.groups.clone();
+            Cow::Owned(config)
+        }
+        _ => Cow::Borrowed(config),
+    }
+}
+
+/// Filter, rename and check the fetched nodes and load the rulesets
+///
+/// Everything that only depends on the nodes and `config` happens here, the
+/// result can be passed to `generate` for any number of targets.
+pub async fn process_nodes(
+    mut node_set: NodeSet,
+    config: &SubconverterConfig,
+) -> Result<NodeSet, ConversionError> {
+    let global = &config.settings;
+    let warnings = WarningCollector::new();
+    let mut extra = config.extra.clone();
+    extra.warnings = warnings.clone();
+    let nodes = &mut node_set.nodes;
+    let profile = &mut node_set.profile;
+
     // Apply group name if specified
     if let Some(group_name) = &config.group_name {
         info!("Setting group name to '{}'", group_name);
-        for node in &mut nodes {
+        for node in nodes.iter_mut() {
             node.group = group_name.clone();
         }
     }
@@ -670,7 +1506,7 @@ pub async fn subconverter(config: SubconverterConfig) -> Result<SubconverterResu
     if let Some(_script) = &config.filter_script {
         info!("Applying filter script");
         if config.authorized {
-            #[cfg(feature = "js_runtime")]
+            #[cfg(feature = "scripting")]
             {
                 // Run filter script in JavaScript context
                 // if let Some(runtime) = &config.extra.js_runtime {
@@ -701,7 +1537,7 @@ pub async fn subconverter(config: SubconverterConfig) -> Result<SubconverterResu
                 //     }
                 // }
             }
-            #[cfg(not(feature = "js_runtime"))]
+            #[cfg(not(feature = "scripting"))]
             {
                 warn!("JavaScript runtime feature not enabled, skipping filter script");
             }
@@ -709,83 +1545,326 @@ pub async fn subconverter(config: SubconverterConfig) -> Result<SubconverterResu
     }
 
     // Process nodes (rename, emoji, sort, etc.)
-    preprocess_nodes(
-        &mut nodes,
-        &config.extra,
+    let renamed = preprocess_nodes(
+        nodes,
+        &extra,
         &config.extra.rename_array,
         &config.extra.emoji_array,
     );
+    // The groups of the source name the nodes as they came
+    if let Some(source) = node_set.source_groups.as_mut() {
+        source.rename_members(&renamed);
+    }
+    profile.legacy_vmess = normalize_vmess_alter_ids(nodes, config.extra.vmess_aead_force);
+    if config.geo_emoji {
+        add_node_geo_emoji(nodes, config, global).await;
+    }
+    pin_nodes(nodes, &config.extra.pin_regex, config.prepend_insert);
+    if config.check_nodes {
+        check_node_reachability(nodes, config.check_drop, global).await;
+    }
+    profile.nodes_after = nodes.len();
+    debug!(
+        "stage=filter nodes_before={} nodes_after={}",
+        profile.nodes_before(),
+        profile.nodes_after
+    );
 
-    // Pass subscription info if provided
-    if let Some(sub_info) = &config.sub_info {
-        response_headers.insert("Subscription-UserInfo".to_string(), sub_info.clone());
+    // Pass subscription info if provided, otherwise use the one from the subscriptions
+    let sub_info = match &config.sub_info {
+        Some(sub_info) => Some(sub_info.clone()),
+        None if global.append_userinfo || config.append_info => node_set.sub_info.take(),
+        None => None,
+    };
+
+    // Insert info nodes at the top so they show up in every group
+    if config.append_info {
+        if let Some(sub_info) = &sub_info {
+            let traffic_remark = if global.info_traffic_remark.is_empty() {
+                default_info_traffic_remark()
+            } else {
+                global.info_traffic_remark.clone()
+            };
+            let expire_remark = if global.info_expire_remark.is_empty() {
+                default_info_expire_remark()
+            } else {
+                global.info_expire_remark.clone()
+            };
+            let mut info_nodes = build_info_nodes(sub_info, &traffic_remark, &expire_remark);
+            info!("Inserting {} subscription info nodes", info_nodes.len());
+            info_nodes.append(nodes);
+            *nodes = info_nodes;
+        }
     }
 
     // Refresh rulesets if needed
     let mut ruleset_content = Vec::new();
     if config.extra.enable_rule_generator {
-        // TODO: Check if we're using custom rulesets or global rulesets
-        // if config.ruleset_configs == global.custom_rulesets {
-        //     refresh_rulesets(&config.ruleset_configs, &mut global.rulesets_content).await;
-        //     debug!("Using global ruleset content");
-        //     // Use global ruleset content if it's the same configuration
-        //     ruleset_content = global.rulesets_content.clone();
+        let rulesets_start = safe_system_time();
+        match refreshed_rulesets(&config.ruleset_configs) {
+            Some(content) => {
+                // The rulesets of the settings are kept fresh in the background
+                debug!("Using the background refreshed rulesets");
+                ruleset_content = content;
+            }
+            None => {
+                info!("Refreshing rulesets with custom configuration");
+                refresh_rulesets(&config.ruleset_configs, &mut ruleset_content, global).await;
+            }
+        }
+        profile.rulesets = elapsed_since(rulesets_start);
+        debug!(
+            "stage=rulesets rulesets={} rulesets_ms={}",
+            ruleset_content.len(),
+            profile.rulesets.as_millis()
+        );
+
+        remap_rule_groups(&mut ruleset_content, &config.rule_group_map);
+        let group_names: Vec<&str> = config
+            .proxy_groups
+            .iter()
+            .map(|group| group.name.as_str())
+            .chain(nodes.iter().map(|node| node.remark.as_str()))
+            .collect();
+        let missing_groups = missing_rule_groups(&ruleset_content, &group_names);
+        for group in missing_groups {
+            warnings.push(
+                WarningKind::MissingRuleGroup,
+                format!(
+                    "Rules reference group '{}' missing from the proxy groups",
+                    group
+                ),
+            );
+        }
 
-        // Refresh rulesets with custom configuration
-        info!("Refreshing rulesets with custom configuration");
-        refresh_rulesets(&config.ruleset_configs, &mut ruleset_content).await;
+        let max_allowed_rules = config.extra.max_allowed_rules;
+        let total_rules = count_rules(&ruleset_content);
+        if max_allowed_rules > 0 && total_rules > max_allowed_rules {
+            return Err(ConversionError::TooManyRules {
+                count: total_rules,
+                limit: max_allowed_rules,
+            });
+        }
 
         // Prepend proxy direct ruleset if needed
         if global.prepend_proxy_direct_ruleset {
-            prepend_proxy_direct_ruleset(&mut ruleset_content, &nodes);
+            prepend_proxy_direct_ruleset(&mut ruleset_content, nodes);
         }
     }
 
+    node_set.sub_info = sub_info;
+    node_set.ruleset_content = ruleset_content;
+    node_set.warnings.append(&mut warnings.take());
+    Ok(node_set)
+}
+
+/// Rulesets of the background refresher, when it holds exactly `configs`
+#[cfg(not(target_arch = "wasm32"))]
+fn refreshed_rulesets(configs: &[RulesetConfig]) -> Option<Vec<RulesetContent>> {
+    ruleset_store().snapshot().contents_for(configs)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn refreshed_rulesets(_configs: &[RulesetConfig]) -> Option<Vec<RulesetContent>> {
+    None
+}
+
+/// Mark the nodes whose probe failed, or drop them when `drop` is set
+///
+/// Nodes without a result, skipped or past the deadline, are kept as they are.
+fn mark_unreachable_nodes(
+    nodes: &mut Vec<Proxy>,
+    results: &[Option<bool>],
+    drop: bool,
+    marker: &str,
+) {
+    if drop {
+        let mut results = results.iter();
+        nodes.retain(|_| results.next() != Some(&Some(false)));
+        return;
+    }
+    for (node, result) in nodes.iter_mut().zip(results) {
+        if *result == Some(false) {
+            node.remark = format!("{} {}", node.remark, marker);
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+async fn check_node_reachability(nodes: &mut Vec<Proxy>, drop: bool, global: &Settings) {
+    let options = NodeCheckOptions {
+        timeout: Duration::from_millis(u64::from(match global.node_check_timeout {
+            0 => default_node_check_timeout(),
+            timeout => timeout,
+        })),
+        concurrency: match global.node_check_concurrency {
+            0 => default_node_check_concurrency(),
+            concurrency => concurrency,
+        },
+        deadline: Duration::from_secs(u64::from(match global.node_check_deadline {
+            0 => default_node_check_deadline(),
+            deadline => deadline,
+        })),
+    };
+    let marker = if global.node_check_marker.is_empty() {
+        default_node_check_marker()
+    } else {
+        global.node_check_marker.clone()
+    };
+
+    let check_start = safe_system_time();
+    let results = check_nodes(nodes, &options).await;
+    let unreachable = results
+        .iter()
+        .filter(|result| **result == Some(false))
+        .count();
+    info!(
+        "Node check: {} of {} nodes unreachable, {} unchecked, took {}ms",
+        unreachable,
+        nodes.len(),
+        results.iter().filter(|result| result.is_none()).count(),
+        elapsed_since(check_start).as_millis()
+    );
+    mark_unreachable_nodes(nodes, &results, drop, &marker);
+}
+
+#[cfg(all(feature = "geoip", not(target_arch = "wasm32")))]
+async fn add_node_geo_emoji(nodes: &mut [Proxy], config: &SubconverterConfig, global: &Settings) {
+    use crate::utils::geoip::{add_geo_emoji, MaxmindDatabase, SystemResolver};
+
+    if global.geoip_database.is_empty() {
+        warn!("No GeoIP database configured, skipping geo emoji");
+        return;
+    }
+    let database = match MaxmindDatabase::shared(&global.geoip_database) {
+        Ok(database) => database,
+        Err(e) => {
+            warn!("{}", e);
+            return;
+        }
+    };
+    let tagged = add_geo_emoji(
+        nodes,
+        &config.extra.emoji_array,
+        &SystemResolver,
+        database.as_ref(),
+    )
+    .await;
+    info!(
+        "Tagged {} nodes with the flag of their server country",
+        tagged
+    );
+}
+
+#[cfg(not(all(feature = "geoip", not(target_arch = "wasm32"))))]
+async fn add_node_geo_emoji(
+    _nodes: &mut [Proxy],
+    _config: &SubconverterConfig,
+    _global: &Settings,
+) {
+    warn!("GeoIP feature not enabled, skipping geo emoji");
+}
+
+#[cfg(target_arch = "wasm32")]
+async fn check_node_reachability(_nodes: &mut Vec<Proxy>, _drop: bool, _global: &Settings) {
+    warn!("Node check is not supported on this platform, skipping");
+}
+
+/// Generate the output of one target from processed nodes
+///
+/// The target set in `config` is ignored, `node_set` is left untouched so
+/// it can be reused for other targets.
+pub async fn generate(
+    node_set: &NodeSet,
+    target: &SubconverterTarget,
+    config: &SubconverterConfig,
+) -> Result<SubconverterResult, ConversionError> {
+    let config = with_source_groups(config, node_set.source_groups.as_ref());
+    let config = config.as_ref();
+    let warnings = WarningCollector::new();
+    let mut extra = config.extra.clone();
+    extra.warnings = warnings.clone();
+    let mut nodes = node_set.nodes.clone();
+    // Proxies kept verbatim from a Clash subscription only fit Clash output
+    if !(*target == SubconverterTarget::Clash && config.extra.preserve_unknown_proxies) {
+        nodes.retain(|node| {
+            if node.is_passthrough() {
+                warnings.unsupported_node(node, &target.to_str());
+            }
+            !node.is_passthrough()
+        });
+    }
+    // Only the targets writing the groups need them to match nodes
+    if !target.is_simple() && !config.extra.nodelist {
+        check_empty_groups(config, &nodes, &warnings)?;
+    }
+    let mut ruleset_content = node_set.ruleset_content.clone();
+    let sub_info = &node_set.sub_info;
+    let mut profile = node_set.profile.clone();
+    profile.target = target.to_str();
+    let mut response_headers = HashMap::new();
+    if let Some(sub_info) = sub_info {
+        response_headers.insert("Subscription-UserInfo".to_string(), sub_info.clone());
+    }
+
     // Generate output based on target
-    let output_content = match &config.target {
+    let generate_start = safe_system_time();
+    let output_content = match target {
         SubconverterTarget::Clash => {
             info!("Generate target: Clash");
             let base = config
                 .rule_bases
-                .get_base_content(&SubconverterTarget::Clash, config.template_args.as_ref())
+                .get_base_content(
+                    &SubconverterTarget::Clash,
+                    config.template_args.as_ref(),
+                    &config.settings,
+                )
                 .await;
-            proxy_to_clash(
+            let output = proxy_to_clash(
                 &mut nodes,
                 &base,
                 &mut ruleset_content,
                 &config.proxy_groups,
                 false,
-                &mut config.extra.clone(),
-            )
+                &mut clash_extra(config, &extra),
+            );
+            with_userinfo_comment(output, sub_info.as_deref(), &config.settings)
         }
         SubconverterTarget::ClashR => {
             info!("Generate target: ClashR");
             let base = config
                 .rule_bases
-                .get_base_content(&SubconverterTarget::ClashR, config.template_args.as_ref())
+                .get_base_content(
+                    &SubconverterTarget::ClashR,
+                    config.template_args.as_ref(),
+                    &config.settings,
+                )
                 .await;
-            proxy_to_clash(
+            let output = proxy_to_clash(
                 &mut nodes,
                 &base,
                 &mut ruleset_content,
                 &config.proxy_groups,
                 true,
-                &mut config.extra.clone(),
-            )
+                &mut clash_extra(config, &extra),
+            );
+            with_userinfo_comment(output, sub_info.as_deref(), &config.settings)
         }
         SubconverterTarget::Surge(ver) => {
             info!("Generate target: Surge {}", ver);
             let base = config
                 .rule_bases
-                .get_base_content(&config.target, config.template_args.as_ref())
+                .get_base_content(target, config.template_args.as_ref(), &config.settings)
                 .await;
+            let mut ext = extra.clone();
+            ext.provider_update_interval = config.update_interval;
             let output = proxy_to_surge(
                 &mut nodes,
                 &base,
                 &mut ruleset_content,
                 &config.proxy_groups,
                 *ver,
-                &mut config.extra.clone(),
+                &mut ext,
             )
             .await;
 
@@ -818,15 +1897,17 @@ pub async fn subconverter(config: SubconverterConfig) -> Result<SubconverterResu
             info!("Generate target: Surfboard");
             let base = config
                 .rule_bases
-                .get_base_content(&config.target, config.template_args.as_ref())
+                .get_base_content(target, config.template_args.as_ref(), &config.settings)
                 .await;
+            let mut ext = extra.clone();
+            ext.provider_update_interval = config.update_interval;
             let output = proxy_to_surge(
                 &mut nodes,
                 &base,
                 &mut ruleset_content,
                 &config.proxy_groups,
-                -3, // Special version for Surfboard
-                &mut config.extra.clone(),
+                SURFBOARD_VERSION,
+                &mut ext,
             )
             .await;
 
@@ -858,14 +1939,14 @@ pub async fn subconverter(config: SubconverterConfig) -> Result<SubconverterResu
             info!("Generate target: Mellow");
             let base = config
                 .rule_bases
-                .get_base_content(&config.target, config.template_args.as_ref())
+                .get_base_content(target, config.template_args.as_ref(), &config.settings)
                 .await;
             proxy_to_mellow(
                 &mut nodes,
                 &base,
                 &mut ruleset_content,
                 &config.proxy_groups,
-                &mut config.extra.clone(),
+                &mut extra.clone(),
             )
             .await
         }
@@ -873,46 +1954,42 @@ pub async fn subconverter(config: SubconverterConfig) -> Result<SubconverterResu
             info!("Generate target: SS Subscription");
             let base = config
                 .rule_bases
-                .get_base_content(&config.target, config.template_args.as_ref())
+                .get_base_content(target, config.template_args.as_ref(), &config.settings)
                 .await;
-            proxy_to_ss_sub(&base, &mut nodes, &mut config.extra.clone())
+            proxy_to_ss_sub(&base, &mut nodes, &mut extra.clone())
         }
         SubconverterTarget::SS => {
             info!("Generate target: SS");
-            proxy_to_single(&mut nodes, ProxyUriTypes::SS, &mut config.extra.clone())
+            proxy_to_single(&mut nodes, ProxyUriTypes::SS, &mut extra.clone())
         }
         SubconverterTarget::SSR => {
             info!("Generate target: SSR");
-            proxy_to_single(
-                &mut nodes,
-                ProxyUriTypes::SSR | ProxyUriTypes::SS,
-                &mut config.extra.clone(),
-            )
+            proxy_to_single(&mut nodes, ProxyUriTypes::SSR, &mut extra.clone())
         }
         SubconverterTarget::V2Ray => {
             info!("Generate target: V2Ray");
-            proxy_to_single(&mut nodes, ProxyUriTypes::VMESS, &mut config.extra.clone())
+            proxy_to_single(&mut nodes, ProxyUriTypes::VMESS, &mut extra.clone())
         }
         SubconverterTarget::Trojan => {
             info!("Generate target: Trojan");
-            proxy_to_single(&mut nodes, ProxyUriTypes::TROJAN, &mut config.extra.clone())
+            proxy_to_single(&mut nodes, ProxyUriTypes::TROJAN, &mut extra.clone())
         }
         SubconverterTarget::Mixed => {
             info!("Generate target: Mixed");
-            proxy_to_single(&mut nodes, ProxyUriTypes::MIXED, &mut config.extra.clone())
+            proxy_to_single(&mut nodes, ProxyUriTypes::MIXED, &mut extra.clone())
         }
         SubconverterTarget::Quantumult => {
             info!("Generate target: Quantumult");
             let base = config
                 .rule_bases
-                .get_base_content(&config.target, config.template_args.as_ref())
+                .get_base_content(target, config.template_args.as_ref(), &config.settings)
                 .await;
             proxy_to_quan(
                 &mut nodes,
                 &base,
                 &mut ruleset_content,
                 &config.proxy_groups,
-                &mut config.extra.clone(),
+                &mut extra.clone(),
             )
             .await
         }
@@ -920,14 +1997,20 @@ pub async fn subconverter(config: SubconverterConfig) -> Result<SubconverterResu
             info!("Generate target: Quantumult X");
             let base = config
                 .rule_bases
-                .get_base_content(&config.target, config.template_args.as_ref())
+                .get_base_content(target, config.template_args.as_ref(), &config.settings)
                 .await;
+            let mut ext = extra.clone();
+            ext.quanx_dev_id = config
+                .device_id
+                .clone()
+                .unwrap_or_else(|| config.settings.quanx_dev_id.clone());
+            ext.quanx_server_remote = quanx_server_remote(config);
             proxy_to_quanx(
                 &mut nodes,
                 &base,
                 &mut ruleset_content,
                 &config.proxy_groups,
-                &mut config.extra.clone(),
+                &mut ext,
             )
             .await
         }
@@ -935,14 +2018,14 @@ pub async fn subconverter(config: SubconverterConfig) -> Result<SubconverterResu
             info!("Generate target: Loon");
             let base = config
                 .rule_bases
-                .get_base_content(&config.target, config.template_args.as_ref())
+                .get_base_content(target, config.template_args.as_ref(), &config.settings)
                 .await;
             proxy_to_loon(
                 &mut nodes,
                 &base,
                 &mut ruleset_content,
                 &config.proxy_groups,
-                &mut config.extra.clone(),
+                &mut extra.clone(),
             )
             .await
         }
@@ -951,22 +2034,22 @@ pub async fn subconverter(config: SubconverterConfig) -> Result<SubconverterResu
             proxy_to_ssd(
                 &mut nodes,
                 &config.group_name.as_deref().unwrap_or(""),
-                &config.sub_info.as_deref().unwrap_or(""),
-                &mut config.extra.clone(),
+                &sub_info.as_deref().unwrap_or(""),
+                &mut extra.clone(),
             )
         }
         SubconverterTarget::SingBox => {
             info!("Generate target: SingBox");
             let base = config
                 .rule_bases
-                .get_base_content(&config.target, config.template_args.as_ref())
+                .get_base_content(target, config.template_args.as_ref(), &config.settings)
                 .await;
             proxy_to_singbox(
                 &mut nodes,
                 &base,
                 &mut ruleset_content,
                 &config.proxy_groups,
-                &mut config.extra.clone(),
+                &mut extra.clone(),
             )
         }
         SubconverterTarget::Auto => {
@@ -975,26 +2058,45 @@ pub async fn subconverter(config: SubconverterConfig) -> Result<SubconverterResu
             info!("Generate target: Auto (defaulting to Clash)");
             let base = config
                 .rule_bases
-                .get_base_content(&SubconverterTarget::Clash, config.template_args.as_ref())
+                .get_base_content(
+                    &SubconverterTarget::Clash,
+                    config.template_args.as_ref(),
+                    &config.settings,
+                )
                 .await;
-            proxy_to_clash(
+            let output = proxy_to_clash(
                 &mut nodes,
                 &base,
                 &mut ruleset_content,
                 &config.proxy_groups,
                 false,
-                &mut config.extra.clone(),
-            )
+                &mut clash_extra(config, &extra),
+            );
+            with_userinfo_comment(output, sub_info.as_deref(), &config.settings)
         }
     };
+    profile.generate = elapsed_since(generate_start);
+    debug!(
+        "stage=generate target={} generate_ms={}",
+        profile.target,
+        profile.generate.as_millis()
+    );
 
     // Set filename header if provided
     if let Some(filename) = &config.filename {
         response_headers.insert(
             "Content-Disposition".to_string(),
-            format!("attachment; filename=\"{}\"; filename*=utf-8''", filename),
+            content_disposition(filename),
         );
     }
+    if target.is_clash() {
+        if let Some(hours) = profile_update_interval(config) {
+            response_headers.insert("Profile-Update-Interval".to_string(), hours.to_string());
+        }
+        if let Some(url) = &config.profile_web_page_url {
+            response_headers.insert("Profile-Web-Page-Url".to_string(), url.clone());
+        }
+    }
 
     // Upload result if needed
     if config.upload {
@@ -1004,22 +2106,121 @@ pub async fn subconverter(config: SubconverterConfig) -> Result<SubconverterResu
         }
     }
 
-    info!("Conversion completed");
+    profile.total = elapsed_since(node_set.started);
+    info!("Conversion completed: {}", profile);
+    if config.profile {
+        response_headers.insert("X-Conversion-Profile".to_string(), profile.to_string());
+    }
+    let mut result_warnings = node_set.warnings.clone();
+    result_warnings.append(&mut warnings.take());
+    if let Some(summary) = warnings_header(&result_warnings) {
+        response_headers.insert("X-Subconverter-Warnings".to_string(), summary);
+    }
     Ok(SubconverterResult {
         content: output_content,
         headers: response_headers,
+        content_type: target.content_type().to_string(),
+        warnings: result_warnings,
     })
 }
 
+/// The update interval in hours, as Clash expects it
+///
+/// Managed configs always get the interval, other outputs only when the
+/// request asked for one.
+fn profile_update_interval(config: &SubconverterConfig) -> Option<u32> {
+    if config.managed_config_prefix.is_empty() && !config.clash_update_interval {
+        return None;
+    }
+    Some(config.update_interval.div_ceil(3600).max(1))
+}
+
+/// `extra` of a Clash target, with the `profile-update-interval` in hours
+fn clash_extra(config: &SubconverterConfig, extra: &ExtraSettings) -> ExtraSettings {
+    let mut ext = extra.clone();
+    if let Some(hours) = profile_update_interval(config) {
+        ext.profile_update_interval = hours;
+    }
+    ext
+}
+
+/// Mirror the subscription userinfo as a leading comment of the output
+///
+/// Only done when `append_sub_userinfo` is enabled in the settings.
+fn with_userinfo_comment(output: String, sub_info: Option<&str>, global: &Settings) -> String {
+    match sub_info {
+        Some(sub_info) if !output.is_empty() && global.append_userinfo => {
+            format!("# {}\n{}", sub_info, output)
+        }
+        _ => output,
+    }
+}
+
 /// Preprocess nodes before conversion
+///
+/// Returns the final remark of each remark before renaming.
 pub fn preprocess_nodes(
     nodes: &mut Vec<Proxy>,
     extra: &ExtraSettings,
     rename_patterns: &Vec<RegexMatchConfig>,
     emoji_patterns: &Vec<RegexMatchConfig>,
-) {
+) -> HashMap<String, String> {
     // Call the implementation in utils/node_manip
-    crate::utils::preprocess_nodes(nodes, extra, rename_patterns, emoji_patterns);
+    crate::utils::preprocess_nodes(nodes, extra, rename_patterns, emoji_patterns)
+}
+
+/// Warn about proxy groups none of whose rules matched, failing when configured to
+fn check_empty_groups(
+    config: &SubconverterConfig,
+    nodes: &[Proxy],
+    warnings: &WarningCollector,
+) -> Result<(), ConversionError> {
+    let empty = empty_groups(&config.proxy_groups, nodes, &config.extra);
+    if empty.is_empty() {
+        return Ok(());
+    }
+    let placeholder = config.extra.empty_group_placeholder;
+    if placeholder == EmptyGroupPlaceholder::Error {
+        return Err(ConversionError::EmptyGroups(empty));
+    }
+    for group in empty {
+        warnings.push(
+            WarningKind::EmptyGroup,
+            format!(
+                "Proxy group '{}' matched no nodes and gets {}",
+                group,
+                placeholder.policy()
+            ),
+        );
+    }
+    Ok(())
+}
+
+/// The QuantumultX `server_remote` entry pointing at the node list of this conversion
+///
+/// The node list is served by the managed config prefix, so the reference is
+/// only written when one is configured.
+fn quanx_server_remote(config: &SubconverterConfig) -> Option<QuanxServerRemote> {
+    if !config.quanx_server_remote || config.extra.nodelist {
+        return None;
+    }
+    if config.managed_config_prefix.is_empty() {
+        warn!("QuantumultX server_remote needs a managed config prefix, writing the nodes to server_local");
+        return None;
+    }
+    Some(QuanxServerRemote {
+        url: format!(
+            "{}sub?target=quanx&list=true&url={}",
+            config.managed_config_prefix,
+            url_encode(&config.urls.join("|"))
+        ),
+        tag: config
+            .group_name
+            .clone()
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| "Subscription".to_string()),
+        as_policy: config.quanx_as_policy.clone(),
+    })
 }
 
 /// Prepend proxy direct ruleset to ruleset content
@@ -1029,15 +2230,15 @@ fn prepend_proxy_direct_ruleset(ruleset_content: &mut Vec<RulesetContent>, nodes
 
     info!("Prepending proxy direct ruleset");
 
-    // Create content for the ruleset
+    // Inline rules, like the `[]` rulesets of the settings
     for node in nodes {
         let mut content = String::new();
         if is_ipv6(&node.hostname) {
-            content.push_str(&format!("IP-CIDR6,{}/128,no-resolve", node.hostname));
+            content.push_str(&format!("[]IP-CIDR6,{}/128,no-resolve", node.hostname));
         } else if is_ipv4(&node.hostname) {
-            content.push_str(&format!("IP-CIDR,{}/32,no-resolve", node.hostname));
+            content.push_str(&format!("[]IP-CIDR,{}/32,no-resolve", node.hostname));
         } else {
-            content.push_str(&format!("DOMAIN,{}", node.hostname));
+            content.push_str(&format!("[]DOMAIN,{}", node.hostname));
         }
         // Create the ruleset
         let mut ruleset = RulesetContent::new("", "DIRECT");
@@ -1138,52 +2339,45 @@ impl RuleBases {
     }
 
     /// Get base content for a specific target
+    ///
+    /// Falls back to the default base of the target in the settings when no
+    /// rule base was supplied for it. A Clash rule base is merged over
+    /// `clash_global_base` when that is set.
     pub async fn get_base_content(
         &self,
         target: &SubconverterTarget,
         template_args: Option<&TemplateArgs>,
+        global: &Settings,
     ) -> String {
-        let global = Settings::current();
-        let proxy_config = parse_proxy(&global.proxy_config);
-
-        // Helper function to load content from file or URL
-        let load_content = async move |path: &str| -> String {
-            if path.is_empty() {
-                return String::new();
-            }
+        let content = self.load_base(target, template_args, global).await;
+        if !matches!(
+            target,
+            SubconverterTarget::Clash | SubconverterTarget::ClashR
+        ) || self.clash_global_base.is_empty()
+            || self.clash_global_base == self.clash_rule_base
+        {
+            return content;
+        }
 
-            // Check if path is a URL
-            if path.starts_with("http://") || path.starts_with("https://") {
-                match web_get_async(path, &proxy_config, None).await {
-                    Ok(response) => {
-                        let content = response.body;
-                        if content.is_empty() {
-                            debug!("Empty content from URL: {}", path);
-                            return String::new();
-                        }
-                        debug!("Loaded rule base from URL: {}", path);
-                        content
-                    }
-                    Err(e) => {
-                        warn!("Failed to load rule base from URL {}: {}", path, e);
-                        String::new()
-                    }
-                }
-            } else {
-                // Treat as file path
-                match file_get_async(path, None).await {
-                    Ok(content) => {
-                        debug!("Loaded rule base from file: {}", path);
-                        content
-                    }
-                    Err(e) => {
-                        warn!("Failed to load rule base from file {}: {}", path, e);
-                        String::new()
-                    }
-                }
-            }
+        let global_base = RuleBases {
+            clash_rule_base: self.clash_global_base.clone(),
+            ..Default::default()
         };
+        let global_content = global_base.load_base(target, template_args, global).await;
+        debug!(
+            "Merging Clash base {} over {}",
+            self.clash_rule_base, self.clash_global_base
+        );
+        merge_clash_bases(&global_content, &content)
+    }
 
+    /// Load and render the rule base of a target
+    async fn load_base(
+        &self,
+        target: &SubconverterTarget,
+        template_args: Option<&TemplateArgs>,
+        global: &Settings,
+    ) -> String {
         // Get path based on target
         let path = match target {
             SubconverterTarget::Clash | SubconverterTarget::ClashR => &self.clash_rule_base,
@@ -1198,8 +2392,20 @@ impl RuleBases {
             _ => return String::new(),
         };
 
+        let path = if path.is_empty() {
+            target
+                .get_base_path_from_settings(global)
+                .unwrap_or_default()
+                .to_string()
+        } else {
+            path.clone()
+        };
+
         // Load the base content
-        let content = load_content(path).await;
+        let content = load_base_content(&path, global.cache_config, |url| {
+            fetch_base_url(url, global)
+        })
+        .await;
         if content.is_empty() {
             return content;
         }
@@ -1208,7 +2414,12 @@ impl RuleBases {
         if let Some(args) = template_args {
             // Using template rendering
             info!("Applying template to rule base for {}", target.to_str());
-            match crate::template::render_template(&content, args, &global.template_path) {
+            match crate::template::render_template(
+                &content,
+                args,
+                &global.template_path,
+                &global.managed_config_prefix,
+            ) {
                 Ok(rendered) => {
                     debug!("Successfully rendered template for rule base");
                     rendered
@@ -1223,80 +2434,1092 @@ impl RuleBases {
         }
     }
 
-    /// Check and update rule bases with external configuration paths
+    /// Take the rule bases of an external configuration, empty ones keep
+    /// the bases of the settings
     ///
-    /// This method checks if paths from external configuration are valid
-    /// (either links or existing files) and updates the corresponding rule bases.
-    pub async fn check_external_bases(
-        &mut self,
-        ext_conf: &crate::settings::external::ExternalSettings,
-        base_path: &str,
-    ) {
-        Self::check_external_base(
-            &ext_conf.clash_rule_base,
-            &mut self.clash_rule_base,
-            base_path,
-        )
-        .await;
-        Self::check_external_base(
-            &ext_conf.surge_rule_base,
-            &mut self.surge_rule_base,
-            base_path,
-        )
-        .await;
-        Self::check_external_base(
-            &ext_conf.surfboard_rule_base,
-            &mut self.surfboard_rule_base,
-            base_path,
-        )
-        .await;
-        Self::check_external_base(
-            &ext_conf.mellow_rule_base,
-            &mut self.mellow_rule_base,
-            base_path,
-        )
-        .await;
-        Self::check_external_base(
-            &ext_conf.quan_rule_base,
-            &mut self.quan_rule_base,
-            base_path,
-        )
-        .await;
-        Self::check_external_base(
-            &ext_conf.quanx_rule_base,
-            &mut self.quanx_rule_base,
-            base_path,
-        )
-        .await;
-        Self::check_external_base(
-            &ext_conf.loon_rule_base,
-            &mut self.loon_rule_base,
-            base_path,
-        )
-        .await;
-        Self::check_external_base(
-            &ext_conf.sssub_rule_base,
-            &mut self.sssub_rule_base,
-            base_path,
-        )
-        .await;
-        Self::check_external_base(
-            &ext_conf.singbox_rule_base,
-            &mut self.singbox_rule_base,
-            base_path,
-        )
-        .await;
+    /// When the Clash base of the settings is replaced, it is kept as
+    /// `clash_global_base` to be merged under the external one.
+    pub fn apply_external_bases(&mut self, ext_conf: &ExternalSettings) {
+        if !ext_conf.clash_rule_base.is_empty() {
+            let global_clash_base =
+                std::mem::replace(&mut self.clash_rule_base, ext_conf.clash_rule_base.clone());
+            if !global_clash_base.is_empty() {
+                self.clash_global_base = global_clash_base;
+            }
+        }
+        for (dest, path) in [
+            (&mut self.surge_rule_base, &ext_conf.surge_rule_base),
+            (&mut self.surfboard_rule_base, &ext_conf.surfboard_rule_base),
+            (&mut self.mellow_rule_base, &ext_conf.mellow_rule_base),
+            (&mut self.quan_rule_base, &ext_conf.quan_rule_base),
+            (&mut self.quanx_rule_base, &ext_conf.quanx_rule_base),
+            (&mut self.loon_rule_base, &ext_conf.loon_rule_base),
+            (&mut self.sssub_rule_base, &ext_conf.sssub_rule_base),
+            (&mut self.singbox_rule_base, &ext_conf.singbox_rule_base),
+        ] {
+            if !path.is_empty() {
+                dest.clone_from(path);
+            }
+        }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    /// Check if a path is a link or exists in the base path and update the destination if valid
-    async fn check_external_base(path: &str, dest: &mut String, base_path: &str) -> bool {
-        if crate::utils::is_link(path)
-            || (crate::utils::starts_with(path, base_path) && crate::utils::file_exists(path).await)
+    #[test]
+    fn test_mark_unreachable_nodes() {
+        let nodes = ["A", "B", "C"]
+            .iter()
+            .map(|remark| Proxy {
+                remark: remark.to_string(),
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        let results = [Some(true), Some(false), None];
+
+        let mut marked = nodes.clone();
+        mark_unreachable_nodes(&mut marked, &results, false, "[DEAD]");
+        let remarks: Vec<&str> = marked.iter().map(|node| node.remark.as_str()).collect();
+        assert_eq!(remarks, vec!["A", "B [DEAD]", "C"]);
+
+        let mut dropped = nodes;
+        mark_unreachable_nodes(&mut dropped, &results, true, "[DEAD]");
+        let remarks: Vec<&str> = dropped.iter().map(|node| node.remark.as_str()).collect();
+        assert_eq!(remarks, vec!["A", "C"]);
+    }
+
+    #[test]
+    fn test_append_info_nodes_first_in_clash() {
+        let link = "ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@1.2.3.4:8388#Node%20A";
+        let mut builder = SubconverterConfigBuilder::new();
+        builder
+            .target(SubconverterTarget::Clash)
+            .add_url(&format!("data:text/plain;base64,{}", base64_encode(link)))
+            .nodelist(true)
+            .append_info(true)
+            .sub_info(Some(
+                "upload=0; download=32212254720; total=107374182400; expire=1767225600".to_string(),
+            ));
+        let config = builder.build().unwrap();
+
+        let result = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(subconverter(config))
+            .unwrap();
+        assert!(result.headers.contains_key("Subscription-UserInfo"));
+
+        let yaml: serde_yaml::Value = serde_yaml::from_str(&result.content).unwrap();
+        let proxies = yaml["proxies"].as_sequence().unwrap();
+        assert_eq!(proxies.len(), 3);
+        assert_eq!(proxies[0]["name"], "剩余流量: 70.00 GB");
+        assert_eq!(proxies[1]["name"], "到期时间: 2026-01-01");
+        assert_eq!(proxies[2]["name"], "Node A");
+        assert_eq!(proxies[0]["server"], "127.0.0.1");
+    }
+
+    #[test]
+    fn test_server_filters_after_remark_filters() {
+        let links = "ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@hk1.example.com:8388#HK%2001\n\
+                     ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@hk.cdn-relay.net:8388#HK%2002\n\
+                     ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@jp1.example.com:8388#JP%2001";
+        let mut builder = SubconverterConfigBuilder::new();
+        builder
+            .target(SubconverterTarget::SS)
+            .add_url(&format!("data:text/plain;base64,{}", base64_encode(links)))
+            .include_remarks(vec!["HK".to_string()])
+            .exclude_servers(vec![r"\.cdn-relay\.net$".to_string()])
+            .nodelist(true);
+        let config = builder.build().unwrap();
+
+        let result = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(subconverter(config))
+            .unwrap();
+        // HK 02 passes the remark filter but not the server one
+        assert!(result.content.contains("HK%2001"), "{}", result.content);
+        assert!(!result.content.contains("HK%2002"), "{}", result.content);
+        assert!(!result.content.contains("JP%2001"), "{}", result.content);
+
+        let mut builder = SubconverterConfigBuilder::new();
+        builder
+            .target(SubconverterTarget::SS)
+            .add_url("https://example.com/sub")
+            .include_servers(vec!["(bad".to_string()]);
+        let error = builder.build().unwrap_err();
+        assert_eq!(error.to_string(), "include_servers: invalid regex '(bad'");
+    }
+
+    #[test]
+    fn test_unsupported_node_warning() {
+        let links = "ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@1.2.3.4:8388#Node%20A\n\
+                     trojan://password@5.6.7.8:443#Node%20B";
+        let mut builder = SubconverterConfigBuilder::new();
+        builder
+            .target(SubconverterTarget::SS)
+            .add_url(&format!("data:text/plain;base64,{}", base64_encode(links)))
+            .nodelist(true);
+        let config = builder.build().unwrap();
+
+        let result = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(subconverter(config))
+            .unwrap();
+        assert!(result.content.contains("Node%20A"), "{}", result.content);
+        assert!(!result.content.contains("Node%20B"), "{}", result.content);
+
+        assert_eq!(result.warnings.len(), 1, "{:?}", result.warnings);
+        assert_eq!(result.warnings[0].kind, WarningKind::UnsupportedNode);
+        assert!(
+            result.warnings[0].message.contains("'Node B'"),
+            "{}",
+            result.warnings[0].message
+        );
+        assert_eq!(
+            result
+                .headers
+                .get("X-Subconverter-Warnings")
+                .map(String::as_str),
+            Some("1; unsupported_node")
+        );
+    }
+
+    #[test]
+    fn test_clash_profile_update_interval() {
+        let link = "ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@1.2.3.4:8388#Node%20A";
+        let sub_info = "upload=0; download=1024; total=2048; expire=1767225600";
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let output_of = |clash_update_interval: bool| {
+            let mut builder = SubconverterConfigBuilder::new();
+            builder
+                .target(SubconverterTarget::Clash)
+                .add_url(&format!("data:text/plain;base64,{}", base64_encode(link)))
+                .update_interval(7000)
+                .clash_update_interval(clash_update_interval)
+                .sub_info(Some(sub_info.to_string()));
+            runtime
+                .block_on(subconverter(builder.build().unwrap()))
+                .unwrap()
+                .content
+        };
+
+        let output = output_of(true);
+        assert!(
+            output.starts_with(&format!("# {}\n", sub_info)),
+            "{}",
+            output
+        );
+        let yaml: serde_yaml::Value = serde_yaml::from_str(&output).unwrap();
+        assert_eq!(yaml["profile-update-interval"], 2);
+
+        let yaml: serde_yaml::Value = serde_yaml::from_str(&output_of(false)).unwrap();
+        assert!(yaml.get("profile-update-interval").is_none());
+    }
+
+    #[test]
+    fn test_clash_profile_headers() {
+        let link = "ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@1.2.3.4:8388#Node%20A";
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let headers_of = |target: SubconverterTarget, configured: bool| {
+            let mut builder = SubconverterConfigBuilder::new();
+            builder
+                .target(target)
+                .add_url(&format!("data:text/plain;base64,{}", base64_encode(link)))
+                .update_interval(7000)
+                .clash_update_interval(configured)
+                .profile_web_page_url(
+                    Some("https://example.com/panel".to_string()).filter(|_| configured),
+                );
+            runtime
+                .block_on(subconverter(builder.build().unwrap()))
+                .unwrap()
+                .headers
+        };
+
+        let headers = headers_of(SubconverterTarget::Clash, true);
+        assert_eq!(headers["Profile-Update-Interval"], "2");
+        assert_eq!(headers["Profile-Web-Page-Url"], "https://example.com/panel");
+
+        let headers = headers_of(SubconverterTarget::Clash, false);
+        assert!(!headers.contains_key("Profile-Update-Interval"));
+        assert!(!headers.contains_key("Profile-Web-Page-Url"));
+
+        // Only Clash clients read them
+        let headers = headers_of(SubconverterTarget::SingBox, true);
+        assert!(!headers.contains_key("Profile-Update-Interval"));
+        assert!(!headers.contains_key("Profile-Web-Page-Url"));
+    }
+
+    #[test]
+    fn test_subscription_url_labels() {
+        let mut builder = SubconverterConfigBuilder::new();
+        builder.urls_from_str(
+            "https://a.example/sub?token=1#Provider%20A|https://b.example/sub#|ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@1.2.3.4:8388#Node",
+        );
+        assert_eq!(
+            builder.config.urls,
+            vec![
+                "tag:Provider A,https://a.example/sub?token=1",
+                "https://b.example/sub",
+                "ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@1.2.3.4:8388#Node",
+            ]
+        );
+
+        let link = "ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@1.2.3.4:8388#Node%20A";
+        let data = format!("data:text/plain;base64,{}", base64_encode(link));
+        let opts = ParseOptions {
+            authorized: true,
+            ..Default::default()
+        };
+        let nodes = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(parse_subscription(
+                &format!("tag:ProviderB,{}", data),
+                opts,
+                2,
+            ))
+            .unwrap();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].group, "ProviderB");
+        assert_eq!(nodes[0].group_id, 2);
+    }
+
+    #[test]
+    fn test_split_subscription_urls() {
+        // Pipes inside a query or fragment stay, both separators split links
+        assert_eq!(
+            split_subscription_urls(
+                "https://a.example/sub?types=ss|vmess#HK|JP%7Chttps://b.example/sub%7cnullnode"
+            ),
+            [
+                "https://a.example/sub?types=ss|vmess#HK|JP",
+                "https://b.example/sub",
+                "nullnode",
+            ]
+        );
+        // An encoded pipe of a query is kept as it was sent
+        assert_eq!(
+            split_subscription_urls(
+                "https://a.example/sub?types=ss%7Cvmess|tag:B,https://b.example"
+            ),
+            [
+                "https://a.example/sub?types=ss%7Cvmess",
+                "tag:B,https://b.example"
+            ]
+        );
+        // Empty entries are dropped
+        assert_eq!(
+            split_subscription_urls(" | https://a.example/sub || "),
+            ["https://a.example/sub"]
+        );
+        assert!(split_subscription_urls("").is_empty());
+        // A pipe without a query or fragment before it always splits
+        assert_eq!(
+            split_subscription_urls("https://a.example/sub|sub.txt"),
+            ["https://a.example/sub", "sub.txt"]
+        );
+
+        // A plain data URI is split at its pipes, the base64 form keeps them
+        let links = "ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@1.2.3.4:8388#A|ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@5.6.7.8:8388#B";
+        assert_eq!(
+            split_subscription_urls(&format!("data:,{}", links)).len(),
+            2
+        );
+        let data = format!("data:text/plain;base64,{}", base64_encode(links));
+        assert_eq!(
+            split_subscription_urls(&format!("{}|https://a.example/sub#Group%20A", data)),
+            [data.as_str(), "https://a.example/sub#Group%20A"]
+        );
+
+        let mut builder = SubconverterConfigBuilder::new();
+        builder.urls_from_str("https://a.example/sub?x=1|2#Group%20A||");
+        assert_eq!(
+            builder.config.urls,
+            ["tag:Group A,https://a.example/sub?x=1|2"]
+        );
+    }
+
+    fn convert(config: SubconverterConfig) -> Result<SubconverterResult, ConversionError> {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(subconverter(config))
+    }
+
+    #[test]
+    fn test_conversion_profile_header() {
+        let links = "ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@1.2.3.4:8388#A\nss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@5.6.7.8:8388#B";
+        let mut builder = SubconverterConfigBuilder::new();
+        builder
+            .target(SubconverterTarget::Clash)
+            .add_url(&format!("data:text/plain;base64,{}", base64_encode(links)))
+            .add_exclude_remark("B")
+            .nodelist(true);
+
+        let result = convert(builder.clone().build().unwrap()).unwrap();
+        assert!(!result.headers.contains_key("X-Conversion-Profile"));
+
+        builder.profile(true);
+        let result = convert(builder.build().unwrap()).unwrap();
+        let header = &result.headers["X-Conversion-Profile"];
+        let pairs: Vec<(&str, &str)> = header
+            .split(' ')
+            .map(|pair| pair.split_once('=').unwrap())
+            .collect();
+        let keys: Vec<&str> = pairs.iter().map(|(key, _)| *key).collect();
+        assert_eq!(
+            keys,
+            [
+                "target",
+                "urls",
+                "fetch_ms",
+                "parse_ms",
+                "nodes_before",
+                "nodes_after",
+                "legacy_vmess",
+                "rulesets_ms",
+                "generate_ms",
+                "total_ms"
+            ]
+        );
+        assert_eq!(pairs[0].1, "clash");
+        assert_eq!(pairs[1].1, "1");
+        assert_eq!(pairs[4].1, "2");
+        assert_eq!(pairs[5].1, "1");
+        for (_, value) in &pairs[2..] {
+            assert!(value.parse::<u128>().is_ok(), "{}", header);
+        }
+    }
+
+    #[test]
+    fn test_empty_group_placeholder() {
+        use crate::models::ProxyGroupConfig;
+
+        let group = |name: &str, rules: &[&str]| ProxyGroupConfig {
+            name: name.to_string(),
+            proxies: rules.iter().map(|rule| rule.to_string()).collect(),
+            ..Default::default()
+        };
+        let groups = vec![
+            group("Proxy", &["Auto", "^A$"]),
+            group("Auto", &[".*"]),
+            group("Empty", &["^SG"]),
+        ];
+        let nodes = vec![Proxy::ss_construct(
+            "Provider",
+            "A",
+            "1.2.3.4",
+            8388,
+            "password",
+            "aes-128-gcm",
+            "",
+            "",
+            None,
+            None,
+            None,
+            None,
+            "",
+        )];
+        let config_with = |placeholder| {
+            let mut builder = SubconverterConfigBuilder::new();
+            builder
+                .target(SubconverterTarget::Surge(4))
+                .add_url("https://example.com/sub")
+                .proxy_groups(groups.clone())
+                .enable_rule_generator(false)
+                .empty_group_placeholder(placeholder);
+            builder.build().unwrap()
+        };
+
+        for (placeholder, line) in [
+            (EmptyGroupPlaceholder::Direct, "Empty = direct"),
+            (EmptyGroupPlaceholder::Reject, "Empty = reject"),
+        ] {
+            let config = config_with(placeholder);
+            assert!(check_empty_groups(&config, &nodes, &WarningCollector::new()).is_ok());
+            let output = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap()
+                .block_on(proxy_to_surge(
+                    &mut nodes.clone(),
+                    "[General]\nloglevel = notify\n",
+                    &mut Vec::new(),
+                    &config.proxy_groups,
+                    4,
+                    &mut config.extra.clone(),
+                ));
+            assert!(output.contains(line), "{}", output);
+            assert!(output.contains("Proxy = select,Auto,A"), "{}", output);
+        }
+
+        let config = config_with(EmptyGroupPlaceholder::Error);
+        assert_eq!(
+            check_empty_groups(&config, &nodes, &WarningCollector::new()).unwrap_err(),
+            ConversionError::EmptyGroups(vec!["Empty".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_pipeline_stages() {
+        let links = "ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@1.2.3.4:8388#A\nss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@5.6.7.8:8388#B";
+        let rules = vec![RulesetConfig {
+            group: "DIRECT".to_string(),
+            url: "[]FINAL".to_string(),
+            interval: 0,
+        }];
+        let mut builder = SubconverterConfigBuilder::new();
+        builder
+            .target(SubconverterTarget::Clash)
+            .add_url(&format!("data:text/plain;base64,{}", base64_encode(links)))
+            .group_name(Some("Stage".to_string()))
+            .enable_rule_generator(true)
+            .ruleset_configs(rules);
+        let config = builder.build().unwrap();
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let fetched = runtime.block_on(fetch_nodes(&config)).unwrap();
+        assert_eq!(fetched.nodes.len(), 2);
+        assert!(fetched.failed_urls.is_empty());
+        assert!(fetched.ruleset_content.is_empty());
+        assert_eq!(fetched.profile.nodes_before(), 2);
+
+        let processed = runtime.block_on(process_nodes(fetched, &config)).unwrap();
+        assert!(processed.nodes.iter().all(|node| node.group == "Stage"));
+        // The FINAL rule after a DIRECT ruleset for the server of each node
+        assert_eq!(processed.ruleset_content.len(), 3);
+        assert_eq!(
+            processed.ruleset_content[0].get_rule_content(),
+            "[]IP-CIDR,5.6.7.8/32,no-resolve"
+        );
+        assert_eq!(processed.ruleset_content[2].get_rule_content(), "[]FINAL");
+        assert_eq!(processed.profile.nodes_after, 2);
+
+        // Generating leaves the node set untouched and matches the composed function
+        let first = runtime
+            .block_on(generate(&processed, &SubconverterTarget::Clash, &config))
+            .unwrap();
+        let second = runtime
+            .block_on(generate(&processed, &SubconverterTarget::Clash, &config))
+            .unwrap();
+        assert_eq!(first.content, second.content);
+        assert_eq!(first.content, convert(config).unwrap().content);
+    }
+
+    #[test]
+    fn test_surge_source_groups() {
+        use crate::models::ProxyGroupType;
+
+        let surge = "[General]
+loglevel = notify
+
+[Proxy]
+HK = ss, 1.2.3.4, 8388, encrypt-method=aes-128-gcm, password=pass
+JP = ss, 5.6.7.8, 8388, encrypt-method=aes-128-gcm, password=pass
+
+[Proxy Group]
+Proxy = select, Auto, HK, JP, DIRECT
+Auto = url-test, HK, JP, url=http://www.gstatic.com/generate_204, interval=600, tolerance=50
+Backup = fallback, JP, HK, url=http://www.gstatic.com/generate_204, interval=300, timeout=5
+";
+        let mut builder = SubconverterConfigBuilder::new();
+        builder
+            .target(SubconverterTarget::Clash)
+            .add_url(&format!("data:text/plain;base64,{}", base64_encode(surge)))
+            .nodelist(true)
+            .source_groups(true);
+        let config = builder.clone().build().unwrap();
+
+        let node_set = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(fetch_nodes(&config))
+            .unwrap();
+        assert_eq!(node_set.nodes.len(), 2);
+        let config = apply_source_groups(config, &node_set);
+        assert!(!config.extra.nodelist);
+        assert!(!config.extra.enable_rule_generator);
+        let auto = &config.proxy_groups[1];
+        assert_eq!(auto.group_type, ProxyGroupType::URLTest);
+        assert_eq!((auto.interval, auto.tolerance), (600, 50));
+
+        let output = proxy_to_clash(
+            &mut node_set.nodes.clone(),
+            "mixed-port: 7890\n",
+            &mut Vec::new(),
+            &config.proxy_groups,
+            false,
+            &mut config.extra.clone(),
+        );
+        let yaml: serde_yaml::Value = serde_yaml::from_str(&output).unwrap();
+        let groups: Vec<(String, String, Vec<String>)> = yaml["proxy-groups"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .map(|group| {
+                (
+                    group["name"].as_str().unwrap().to_string(),
+                    group["type"].as_str().unwrap().to_string(),
+                    serde_yaml::from_value(group["proxies"].clone()).unwrap(),
+                )
+            })
+            .collect();
+        let expected = [
+            ("Proxy", "select", vec!["Auto", "HK", "JP", "DIRECT"]),
+            ("Auto", "url-test", vec!["HK", "JP"]),
+            ("Backup", "fallback", vec!["JP", "HK"]),
+        ];
+        assert_eq!(groups.len(), expected.len());
+        for ((name, group_type, proxies), (expected_name, expected_type, expected_proxies)) in
+            groups.iter().zip(expected)
         {
-            *dest = path.to_string();
-            true
-        } else {
-            false
+            assert_eq!(name, expected_name);
+            assert_eq!(group_type, expected_type);
+            assert_eq!(proxies, &expected_proxies);
+        }
+
+        // Without the option the configured groups are kept
+        let mut config = builder.build().unwrap();
+        config.source_groups = false;
+        assert!(apply_source_groups(config, &node_set).extra.nodelist);
+    }
+
+    #[test]
+    fn test_source_groups_follow_renamed_nodes() {
+        let surge = "[General]
+loglevel = notify
+
+[Proxy]
+HK = ss, 1.2.3.4, 8388, encrypt-method=aes-128-gcm, password=pass
+JP = ss, 5.6.7.8, 8388, encrypt-method=aes-128-gcm, password=pass
+
+[Proxy Group]
+Proxy = select, HK, JP, DIRECT
+";
+        let rule = |pattern: &str, replace: &str| RegexMatchConfig {
+            _match: pattern.to_string(),
+            replace: replace.to_string(),
+        };
+        let mut builder = SubconverterConfigBuilder::new();
+        builder
+            .target(SubconverterTarget::Clash)
+            .add_url(&format!("data:text/plain;base64,{}", base64_encode(surge)))
+            .source_groups(true)
+            .rename_array(vec![rule("^JP$", "Japan")])
+            .emoji_array(vec![rule("HK", "🇭🇰")])
+            .add_emoji(true);
+        let config = builder.build().unwrap();
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let node_set = runtime.block_on(fetch_nodes(&config)).unwrap();
+        let config = apply_source_groups(config, &node_set);
+        let node_set = runtime.block_on(process_nodes(node_set, &config)).unwrap();
+        let remarks: Vec<&str> = node_set.nodes.iter().map(|n| n.remark.as_str()).collect();
+        assert_eq!(remarks, ["🇭🇰 HK", "Japan"]);
+
+        let config = with_source_groups(&config, node_set.source_groups.as_ref());
+        let output = proxy_to_clash(
+            &mut node_set.nodes.clone(),
+            "mixed-port: 7890\n",
+            &mut Vec::new(),
+            &config.proxy_groups,
+            false,
+            &mut config.extra.clone(),
+        );
+        let yaml: serde_yaml::Value = serde_yaml::from_str(&output).unwrap();
+        let proxies: Vec<String> =
+            serde_yaml::from_value(yaml["proxy-groups"][0]["proxies"].clone()).unwrap();
+        assert_eq!(proxies, ["🇭🇰 HK", "Japan", "DIRECT"]);
+    }
+
+    #[test]
+    fn test_max_allowed_proxies() {
+        let link_a = "ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@1.2.3.4:8388#A";
+        let link_b = "ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@5.6.7.8:8388#B";
+        let data_url = |links: &str| format!("data:text/plain;base64,{}", base64_encode(links));
+        let mut builder = SubconverterConfigBuilder::new();
+        builder
+            .target(SubconverterTarget::Clash)
+            .add_url(&data_url(link_a))
+            .add_url(&data_url(link_b))
+            .nodelist(true)
+            .max_allowed_proxies(1);
+
+        let err = convert(builder.clone().build().unwrap()).unwrap_err();
+        assert_eq!(err, ConversionError::TooManyNodes { count: 2, limit: 1 });
+        assert_eq!(err.to_string(), "Too many nodes: 2 exceeds the limit of 1");
+
+        builder.max_allowed_proxies(2);
+        assert!(convert(builder.clone().build().unwrap()).is_ok());
+
+        // A single subscription over the limit stops parsing that URL
+        let mut builder = SubconverterConfigBuilder::new();
+        builder
+            .target(SubconverterTarget::Clash)
+            .add_url(&data_url(&format!("{}\n{}", link_a, link_b)))
+            .nodelist(true)
+            .max_allowed_proxies(1);
+        let err = convert(builder.build().unwrap()).unwrap_err();
+        assert!(
+            matches!(err, ConversionError::UrlFailed { .. }),
+            "{:?}",
+            err
+        );
+        assert!(
+            err.to_string().contains("more than the limit of 1"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_max_allowed_rules() {
+        let mut ruleset = RulesetContent::new("", "DIRECT");
+        ruleset.set_rule_content("# comment\nDOMAIN,a.com\n\nDOMAIN,b.com\n// note\n");
+        assert_eq!(count_rules(&[ruleset]), 2);
+
+        let rules = ["[]DOMAIN,a.com", "[]DOMAIN,b.com", "[]FINAL"]
+            .iter()
+            .map(|url| crate::models::ruleset::RulesetConfig {
+                group: "DIRECT".to_string(),
+                url: url.to_string(),
+                interval: 0,
+            })
+            .collect();
+        let mut builder = SubconverterConfigBuilder::new();
+        builder
+            .target(SubconverterTarget::Clash)
+            .add_url("ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@1.2.3.4:8388#A")
+            .enable_rule_generator(true)
+            .ruleset_configs(rules)
+            .max_allowed_rules(2);
+
+        let err = convert(builder.clone().build().unwrap()).unwrap_err();
+        assert_eq!(err, ConversionError::TooManyRules { count: 3, limit: 2 });
+
+        builder.max_allowed_rules(3);
+        if let Err(err) = convert(builder.build().unwrap()) {
+            assert!(
+                !matches!(err, ConversionError::TooManyRules { .. }),
+                "{}",
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn test_batch_conversion() {
+        let link = "ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@1.2.3.4:8388#Node%20A";
+        let mut builder = SubconverterConfigBuilder::new();
+        builder
+            .target(SubconverterTarget::Clash)
+            .add_url(&format!("data:text/plain;base64,{}", base64_encode(link)))
+            .nodelist(true);
+        let config = builder.build().unwrap();
+
+        let results = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(subconverter_batch(
+                config,
+                &[SubconverterTarget::Clash, SubconverterTarget::SingBox],
+            ))
+            .unwrap();
+        assert_eq!(results.len(), 2);
+
+        let (target, clash) = &results[0];
+        assert_eq!(target, &SubconverterTarget::Clash);
+        let clash = clash.as_ref().unwrap();
+        assert_eq!(clash.content_type, "text/yaml");
+        let yaml: serde_yaml::Value = serde_yaml::from_str(&clash.content).unwrap();
+        assert_eq!(yaml["proxies"][0]["name"], "Node A");
+
+        let (target, singbox) = &results[1];
+        assert_eq!(target, &SubconverterTarget::SingBox);
+        let singbox = singbox.as_ref().unwrap();
+        assert_eq!(singbox.content_type, "application/json");
+        assert!(singbox.content.contains("\"Node A\""));
+    }
+
+    #[test]
+    fn test_batch_checks_empty_groups_per_target() {
+        use crate::models::ProxyGroupConfig;
+
+        let link = "ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@1.2.3.4:8388#Node%20A";
+        let mut builder = SubconverterConfigBuilder::new();
+        builder
+            .target(SubconverterTarget::SS)
+            .add_url(&format!("data:text/plain;base64,{}", base64_encode(link)))
+            .proxy_groups(vec![ProxyGroupConfig {
+                name: "Empty".to_string(),
+                proxies: vec!["^SG".to_string()],
+                ..Default::default()
+            }])
+            .enable_rule_generator(false)
+            .empty_group_placeholder(EmptyGroupPlaceholder::Error);
+        let config = builder.build().unwrap();
+
+        let results = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(subconverter_batch(
+                config,
+                &[SubconverterTarget::Clash, SubconverterTarget::SS],
+            ))
+            .unwrap();
+
+        // Clash writes the group, the SS link list does not
+        assert_eq!(
+            results[0].1.as_ref().unwrap_err(),
+            &ConversionError::EmptyGroups(vec!["Empty".to_string()])
+        );
+        assert!(results[1].1.is_ok());
+    }
+
+    #[test]
+    fn test_ss_obfs_plugin_across_targets() {
+        let link = "ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@1.2.3.4:8388/?plugin=obfs-local%3Bobfs%3Dhttp%3Bobfs-host%3Dcdn.example.com#Obfs";
+        let mut builder = SubconverterConfigBuilder::new();
+        builder
+            .add_url(&format!("data:text/plain;base64,{}", base64_encode(link)))
+            .nodelist(true);
+        let config = builder.build().unwrap();
+
+        let results = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(subconverter_batch(
+                config,
+                &[
+                    SubconverterTarget::Clash,
+                    SubconverterTarget::Surge(4),
+                    SubconverterTarget::Loon,
+                ],
+            ))
+            .unwrap();
+        let outputs: Vec<String> = results
+            .into_iter()
+            .map(|(_, result)| result.unwrap().content)
+            .collect();
+
+        let yaml: serde_yaml::Value = serde_yaml::from_str(&outputs[0]).unwrap();
+        assert_eq!(yaml["proxies"][0]["plugin"], "obfs");
+        assert_eq!(yaml["proxies"][0]["plugin-opts"]["mode"], "http");
+        assert_eq!(yaml["proxies"][0]["plugin-opts"]["host"], "cdn.example.com");
+
+        assert!(
+            outputs[1].contains(
+                "Obfs = ss, 1.2.3.4, 8388, encrypt-method=aes-256-gcm, password=password, obfs=http, obfs-host=cdn.example.com"
+            ),
+            "{}",
+            outputs[1]
+        );
+        assert!(
+            outputs[2].contains(
+                "Obfs = Shadowsocks,1.2.3.4,8388,aes-256-gcm,\"password\",http,cdn.example.com"
+            ),
+            "{}",
+            outputs[2]
+        );
+    }
+
+    #[test]
+    fn test_output_is_deterministic() {
+        let links = [
+            "ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@1.2.3.4:8388/?plugin=obfs-local%3Bobfs%3Dhttp%3Bobfs-host%3Dcdn.example.com#Obfs",
+            "vless://b831381d-6324-4d53-ad4f-8cda48b30811@5.6.7.8:443?security=tls&type=ws&host=ws.example.com&path=%2Fws&alpn=h2,http/1.1,h3#WS",
+            "hysteria2://secret@9.9.9.9:443?sni=hy.example.com&alpn=h3,h2,http/1.1#HY2",
+        ];
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let output_of = |target: SubconverterTarget| {
+            let mut builder = SubconverterConfigBuilder::new();
+            builder
+                .target(target)
+                .add_url(&format!(
+                    "data:text/plain;base64,{}",
+                    base64_encode(&links.join("\n"))
+                ))
+                .nodelist(true);
+            runtime
+                .block_on(subconverter(builder.build().unwrap()))
+                .unwrap()
+                .content
+        };
+
+        // Every run hashes with new random keys
+        for target in [SubconverterTarget::Clash, SubconverterTarget::SingBox] {
+            let first = output_of(target.clone());
+            for _ in 0..8 {
+                assert_eq!(output_of(target.clone()), first);
+            }
         }
+
+        let yaml: serde_yaml::Value =
+            serde_yaml::from_str(&output_of(SubconverterTarget::Clash)).unwrap();
+        assert_eq!(
+            yaml["proxies"][1]["alpn"],
+            serde_yaml::from_str::<serde_yaml::Value>("[h2, http/1.1, h3]").unwrap()
+        );
+        let opts = yaml["proxies"][0]["plugin-opts"].as_mapping().unwrap();
+        let keys: Vec<&str> = opts.keys().filter_map(|key| key.as_str()).collect();
+        assert_eq!(keys, ["mode", "host"]);
+    }
+
+    #[test]
+    fn test_conversion_with_memory_fetcher() {
+        use crate::utils::fetcher::{FetchedBody, MemoryFetcher};
+
+        let mut fetcher = MemoryFetcher::new();
+        fetcher
+            .insert(
+                "https://example.com/a",
+                FetchedBody::new(base64_encode(
+                    "ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@1.2.3.4:8388#Node%20A",
+                )),
+            )
+            .insert(
+                "https://example.com/b",
+                FetchedBody::new("trojan://secret@5.6.7.8:443#Node%20B"),
+            );
+        let mut builder = SubconverterConfigBuilder::new();
+        builder
+            .target(SubconverterTarget::Clash)
+            .add_url("https://example.com/a#Provider")
+            .add_url("https://example.com/b")
+            .nodelist(true)
+            .fetcher(Arc::new(fetcher));
+        let node_set = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(fetch_nodes(&builder.build().unwrap()))
+            .unwrap();
+
+        let nodes: Vec<(&str, &str)> = node_set
+            .nodes
+            .iter()
+            .map(|node| (node.group.as_str(), node.remark.as_str()))
+            .collect();
+        assert_eq!(nodes[0], ("Provider", "Node A"));
+        assert_eq!(nodes[1].1, "Node B");
+        assert!(node_set.failed_urls.is_empty());
+    }
+
+    /// Serves one node and records the headers of every request by URL
+    #[derive(Default)]
+    struct RecordingFetcher {
+        requests: std::sync::Mutex<Vec<(String, HashMap<String, String>)>>,
+    }
+
+    impl SubscriptionFetcher for RecordingFetcher {
+        fn fetch<'a>(
+            &'a self,
+            url: &'a str,
+            opts: &'a crate::utils::fetcher::FetchOptions,
+        ) -> crate::utils::fetcher::FetchFuture<'a> {
+            Box::pin(async move {
+                let headers = opts
+                    .headers
+                    .iter()
+                    .flatten()
+                    .map(|(name, value)| (name.to_string().to_lowercase(), value.clone()))
+                    .collect();
+                self.requests
+                    .lock()
+                    .unwrap()
+                    .push((url.to_string(), headers));
+                Ok(crate::utils::fetcher::FetchedBody::new(
+                    "trojan://secret@5.6.7.8:443#Node%20B",
+                ))
+            })
+        }
+    }
+
+    #[test]
+    fn test_subscription_request_headers() {
+        let requests_of =
+            |target: SubconverterTarget, configure: &dyn Fn(&mut SubconverterConfigBuilder)| {
+                let fetcher = Arc::new(RecordingFetcher::default());
+                let mut builder = SubconverterConfigBuilder::new();
+                builder
+                    .target(target)
+                    .add_url("https://example.com/sub")
+                    .nodelist(true)
+                    .fetcher(fetcher.clone());
+                configure(&mut builder);
+                tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .unwrap()
+                    .block_on(fetch_nodes(&builder.build().unwrap()))
+                    .unwrap();
+                let requests = fetcher.requests.lock().unwrap().clone();
+                requests
+            };
+        let headers = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+            pairs
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect()
+        };
+
+        // Clash targets ask for Clash YAML, the others keep the client default
+        let requests = requests_of(SubconverterTarget::Clash, &|_| {});
+        assert_eq!(
+            requests[0].1,
+            headers(&[("user-agent", "clash-verge/v1.7.7")])
+        );
+        let requests = requests_of(SubconverterTarget::Surge(4), &|_| {});
+        assert!(requests[0].1.is_empty());
+
+        // The custom headers only go to the URLs of the request
+        let requests = requests_of(SubconverterTarget::Clash, &|builder| {
+            builder
+                .user_agent(Some("Shadowrocket/2070".to_string()))
+                .add_sub_header("Authorization", "Bearer secret")
+                .add_sub_header("user-agent", "ignored")
+                .insert_urls(vec!["https://example.com/insert".to_string()]);
+        });
+        assert_eq!(requests[0].0, "https://example.com/insert");
+        assert_eq!(
+            requests[0].1,
+            headers(&[("user-agent", "Shadowrocket/2070")])
+        );
+        assert_eq!(requests[1].0, "https://example.com/sub");
+        assert_eq!(
+            requests[1].1,
+            headers(&[
+                ("user-agent", "Shadowrocket/2070"),
+                ("authorization", "Bearer secret"),
+            ])
+        );
+
+        // A User-Agent among the custom headers replaces the default
+        let requests = requests_of(SubconverterTarget::ClashR, &|builder| {
+            builder.add_sub_header("User-Agent", "custom/1.0");
+        });
+        assert_eq!(requests[0].1, headers(&[("user-agent", "custom/1.0")]));
+    }
+
+    /// Serves one node with its traffic info and swaps in new settings
+    /// meanwhile, like a reload in the middle of a request
+    struct ReloadingFetcher;
+
+    impl SubscriptionFetcher for ReloadingFetcher {
+        fn fetch<'a>(
+            &'a self,
+            _url: &'a str,
+            _opts: &'a crate::utils::fetcher::FetchOptions,
+        ) -> crate::utils::fetcher::FetchFuture<'a> {
+            Box::pin(async move {
+                Settings::replace(Settings {
+                    append_userinfo: true,
+                    ..Settings::clone(&Settings::current())
+                });
+                let mut body =
+                    crate::utils::fetcher::FetchedBody::new("trojan://secret@5.6.7.8:443#Node%20B");
+                body.headers.insert(
+                    "subscription-userinfo".to_string(),
+                    "upload=1; download=2; total=10".to_string(),
+                );
+                Ok(body)
+            })
+        }
+    }
+
+    #[test]
+    fn test_conversion_keeps_settings_snapshot() {
+        let snapshot = Arc::new(Settings {
+            append_userinfo: false,
+            ..Settings::clone(&Settings::current())
+        });
+        let mut builder = SubconverterConfigBuilder::with_settings(snapshot.clone());
+        builder
+            .target(SubconverterTarget::Clash)
+            .add_url("https://example.com/sub")
+            .nodelist(true)
+            .fetcher(Arc::new(ReloadingFetcher));
+        let result = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(subconverter(builder.build().unwrap()))
+            .unwrap();
+
+        let global = Settings::current();
+        assert!(!Arc::ptr_eq(&global, &snapshot));
+        assert!(global.append_userinfo);
+        // The traffic info stays out, as in the settings the request started with
+        assert!(result.content.contains("Node B"), "{}", result.content);
+        assert!(!result.content.starts_with('#'), "{}", result.content);
+        assert!(!result.headers.contains_key("Subscription-UserInfo"));
+    }
+
+    #[test]
+    fn test_external_clash_base_is_merged() {
+        let dir = tempfile::tempdir().unwrap();
+        let global = dir.path().join("global.yml");
+        let external = dir.path().join("external.yml");
+        std::fs::write(
+            &global,
+            "port: 7890\ndns:\n  enable: true\n  nameserver: [223.5.5.5]\nrules:\n  - MATCH,DIRECT\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &external,
+            "dns:\n  nameserver: [1.1.1.1]\ntun:\n  enable: true\n",
+        )
+        .unwrap();
+
+        let rule_bases = RuleBases {
+            clash_rule_base: external.to_string_lossy().into_owned(),
+            clash_global_base: global.to_string_lossy().into_owned(),
+            ..Default::default()
+        };
+        let content = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(rule_bases.get_base_content(
+                &SubconverterTarget::Clash,
+                None,
+                &Settings::current(),
+            ));
+
+        let yaml: serde_yaml::Value = serde_yaml::from_str(&content).unwrap();
+        assert_eq!(yaml["port"], 7890);
+        assert_eq!(yaml["dns"]["enable"], true);
+        assert_eq!(yaml["dns"]["nameserver"][0], "1.1.1.1");
+        assert_eq!(yaml["tun"]["enable"], true);
+        assert_eq!(yaml["rules"][0], "MATCH,DIRECT");
     }
 }
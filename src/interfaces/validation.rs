@@ -0,0 +1,463 @@
+//! Checks of a conversion configuration before anything is fetched
+//!
+//! `SubconverterConfigBuilder::build` collects every problem at once instead
+//! of failing on the first one. Errors reject the configuration, warnings
+//! are kept on the built config.
+
+use std::collections::HashSet;
+use std::fmt;
+
+use thiserror::Error;
+
+use crate::interfaces::subconverter::SubconverterConfig;
+use crate::models::ruleset::map_rule_group;
+use crate::models::{
+    Proxy, ProxyGroupConfig, RegexMatchConfigs, RulesetConfig, SubconverterTarget,
+};
+use crate::rulesets::ruleset::is_builtin_policy;
+use crate::settings::ExternalSettings;
+use crate::utils::matcher::apply_matcher;
+use crate::utils::reg_valid;
+
+/// Whether a problem stops the conversion
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationSeverity {
+    /// The configuration is rejected
+    Error,
+    /// The conversion runs, but the option has no effect
+    Warning,
+}
+
+/// A problem found in a configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationIssue {
+    pub severity: ValidationSeverity,
+    /// Name of the offending option
+    pub field: &'static str,
+    pub message: String,
+}
+
+impl ValidationIssue {
+    pub(crate) fn error(field: &'static str, message: impl Into<String>) -> Self {
+        ValidationIssue {
+            severity: ValidationSeverity::Error,
+            field,
+            message: message.into(),
+        }
+    }
+
+    pub(crate) fn warning(field: &'static str, message: impl Into<String>) -> Self {
+        ValidationIssue {
+            severity: ValidationSeverity::Warning,
+            field,
+            message: message.into(),
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == ValidationSeverity::Error
+    }
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+/// A configuration rejected by `SubconverterConfigBuilder::build`
+#[derive(Debug, Clone, Error)]
+#[error("{}", join_issues(self.errors()))]
+pub struct ConfigValidationError {
+    /// Every problem found, warnings included
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ConfigValidationError {
+    /// The problems that rejected the configuration
+    pub fn errors(&self) -> impl Iterator<Item = &ValidationIssue> {
+        self.issues.iter().filter(|issue| issue.is_error())
+    }
+}
+
+impl From<ConfigValidationError> for String {
+    fn from(error: ConfigValidationError) -> Self {
+        error.to_string()
+    }
+}
+
+fn join_issues<'a>(issues: impl Iterator<Item = &'a ValidationIssue>) -> String {
+    issues
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Every problem of `config`
+///
+/// `surge_version` is the version requested from the builder, it only
+/// applies to Surge targets.
+pub(crate) fn validate(
+    config: &SubconverterConfig,
+    surge_version: Option<i32>,
+) -> Vec<ValidationIssue> {
+    let mut issues = Vec::new();
+
+    if config.urls.is_empty() && config.insert_urls.is_empty() {
+        issues.push(ValidationIssue::error("urls", "No URLs provided"));
+    }
+    if config.upload && config.upload_path.as_deref().is_none_or(str::is_empty) {
+        issues.push(ValidationIssue::error(
+            "upload_path",
+            "upload is enabled but no upload path is set",
+        ));
+    }
+    if config.extra.max_remark_length == Some(0) {
+        issues.push(ValidationIssue::error(
+            "max_remark_length",
+            "must be at least 1, leave it unset for no limit",
+        ));
+    }
+    if !config.managed_config_prefix.is_empty() && !config.extra.enable_rule_generator {
+        issues.push(ValidationIssue::warning(
+            "managed_config_prefix",
+            "the managed config header is only written with the rule generator enabled",
+        ));
+    }
+    if let Some(version) = surge_version {
+        if !matches!(config.target, SubconverterTarget::Surge(_)) {
+            issues.push(ValidationIssue::warning(
+                "surge_version",
+                format!(
+                    "version {} is ignored for target '{}'",
+                    version,
+                    config.target.to_str()
+                ),
+            ));
+        }
+    }
+
+    for pattern in &config.include_remarks {
+        check_pattern(
+            &mut issues,
+            "include_remarks",
+            pattern,
+            ValidationSeverity::Error,
+        );
+    }
+    for pattern in &config.exclude_remarks {
+        check_pattern(
+            &mut issues,
+            "exclude_remarks",
+            pattern,
+            ValidationSeverity::Error,
+        );
+    }
+    for (field, patterns) in [
+        ("include_servers", &config.extra.include_servers),
+        ("exclude_servers", &config.extra.exclude_servers),
+    ] {
+        for pattern in patterns.iter().filter(|pattern| !reg_valid(pattern)) {
+            issues.push(ValidationIssue::error(
+                field,
+                format!("invalid regex '{}'", pattern),
+            ));
+        }
+    }
+    if !reg_valid(&config.extra.pin_regex) {
+        issues.push(ValidationIssue::error(
+            "pin_regex",
+            format!("invalid regex '{}'", config.extra.pin_regex),
+        ));
+    }
+    // Rename and emoji rules mostly come from the settings, a broken rule
+    // only disables itself
+    check_rules(&mut issues, "rename_array", &config.extra.rename_array);
+    check_rules(&mut issues, "emoji_array", &config.extra.emoji_array);
+    check_proxy_groups(&mut issues, &config.proxy_groups);
+    check_rulesets(
+        &mut issues,
+        &config.ruleset_configs,
+        &config.proxy_groups,
+        &config.rule_group_map,
+    );
+
+    issues
+}
+
+/// Every problem of an external config, checked the way the builder checks
+/// the same options
+///
+/// Only what can be told without fetching anything is checked, the rulesets
+/// are not loaded.
+pub fn validate_external(ext: &ExternalSettings) -> Vec<ValidationIssue> {
+    let mut issues = Vec::new();
+    for pattern in &ext.include_remarks {
+        check_pattern(
+            &mut issues,
+            "include_remarks",
+            pattern,
+            ValidationSeverity::Error,
+        );
+    }
+    for pattern in &ext.exclude_remarks {
+        check_pattern(
+            &mut issues,
+            "exclude_remarks",
+            pattern,
+            ValidationSeverity::Error,
+        );
+    }
+    check_rules(&mut issues, "rename_array", &ext.rename_nodes);
+    check_rules(&mut issues, "emoji_array", &ext.emojis);
+    check_proxy_groups(&mut issues, &ext.custom_proxy_groups);
+    check_rulesets(
+        &mut issues,
+        &ext.custom_rulesets,
+        &ext.custom_proxy_groups,
+        &ext.rule_group_map,
+    );
+    issues
+}
+
+/// Check the names, members and filters of the proxy groups
+///
+/// Members are other groups, `[]` references to groups or policies, and node
+/// patterns. A reference to an unknown group only warns, it may name a policy
+/// of the client.
+fn check_proxy_groups(issues: &mut Vec<ValidationIssue>, groups: &[ProxyGroupConfig]) {
+    const FIELD: &str = "proxy_groups";
+    let names: HashSet<&str> = groups.iter().map(|group| group.name.as_str()).collect();
+    let mut seen = HashSet::new();
+
+    for group in groups {
+        let name = group.name.as_str();
+        if name.trim().is_empty() {
+            issues.push(ValidationIssue::error(FIELD, "a group has no name"));
+        } else if !seen.insert(name) {
+            issues.push(ValidationIssue::error(
+                FIELD,
+                format!("group '{}' is defined more than once", name),
+            ));
+        }
+        if group.proxies.is_empty() && group.using_provider.is_empty() {
+            issues.push(ValidationIssue::error(
+                FIELD,
+                format!("group '{}' has no members", name),
+            ));
+        }
+
+        for member in &group.proxies {
+            if let Some(reference) = member.strip_prefix("[]") {
+                if !names.contains(reference) && !is_builtin_policy(reference) {
+                    issues.push(ValidationIssue::warning(
+                        FIELD,
+                        format!("group '{}' references unknown group '{}'", name, reference),
+                    ));
+                }
+            } else if !names.contains(member.as_str()) && !member.starts_with("script:") {
+                let mut real_rule = String::new();
+                apply_matcher(member, &mut real_rule, &Proxy::default());
+                if !reg_valid(&real_rule) {
+                    issues.push(ValidationIssue::error(
+                        FIELD,
+                        format!("invalid regex '{}' in group '{}'", member, name),
+                    ));
+                }
+            }
+        }
+        for filter in [&group.include_filter, &group.exclude_filter]
+            .into_iter()
+            .flatten()
+        {
+            if !reg_valid(filter) {
+                issues.push(ValidationIssue::error(
+                    FIELD,
+                    format!("invalid filter regex '{}' in group '{}'", filter, name),
+                ));
+            }
+        }
+    }
+}
+
+/// Check that every ruleset has a source and targets a known group
+///
+/// The groups are looked up after the `rename_rule_group` renames. Without
+/// any proxy group, e.g. for node lists, the targets are not checked.
+fn check_rulesets(
+    issues: &mut Vec<ValidationIssue>,
+    rulesets: &[RulesetConfig],
+    groups: &[ProxyGroupConfig],
+    rule_group_map: &[(String, String)],
+) {
+    const FIELD: &str = "rulesets";
+    let mut missing = HashSet::new();
+
+    for ruleset in rulesets {
+        if ruleset.group.trim().is_empty() {
+            issues.push(ValidationIssue::error(
+                FIELD,
+                format!("ruleset '{}' has no group", ruleset.url),
+            ));
+            continue;
+        }
+        if ruleset.url.trim().is_empty() || ruleset.url.trim() == "[]" {
+            issues.push(ValidationIssue::error(
+                FIELD,
+                format!("ruleset of group '{}' has no rules", ruleset.group),
+            ));
+        }
+
+        let group = map_rule_group(&ruleset.group, rule_group_map).unwrap_or(&ruleset.group);
+        if !groups.is_empty()
+            && !is_builtin_policy(group)
+            && !groups.iter().any(|config| config.name == group)
+            && missing.insert(group)
+        {
+            issues.push(ValidationIssue::warning(
+                FIELD,
+                format!("rules target group '{}' which is not defined", group),
+            ));
+        }
+    }
+}
+
+/// Check the regex of a remark pattern, after any `!!GROUP=`-like matcher prefix
+fn check_pattern(
+    issues: &mut Vec<ValidationIssue>,
+    field: &'static str,
+    pattern: &str,
+    severity: ValidationSeverity,
+) {
+    let mut real_rule = String::new();
+    apply_matcher(pattern, &mut real_rule, &Proxy::default());
+    if !reg_valid(&real_rule) {
+        issues.push(ValidationIssue {
+            severity,
+            field,
+            message: format!("invalid regex '{}'", pattern),
+        });
+    }
+}
+
+fn check_rules(issues: &mut Vec<ValidationIssue>, field: &'static str, rules: &RegexMatchConfigs) {
+    for rule in rules {
+        check_pattern(issues, field, &rule._match, ValidationSeverity::Warning);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interfaces::subconverter::SubconverterConfigBuilder;
+    use crate::models::{RegexMatchConfig, SubconverterTarget};
+
+    use super::*;
+
+    fn builder() -> SubconverterConfigBuilder {
+        let mut builder = SubconverterConfigBuilder::new();
+        builder.add_url("https://example.com/sub");
+        builder
+    }
+
+    fn fields(issues: &[ValidationIssue]) -> Vec<&'static str> {
+        issues.iter().map(|issue| issue.field).collect()
+    }
+
+    #[test]
+    fn test_valid_config_has_no_issues() {
+        let config = builder().build().unwrap();
+        assert!(config.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_missing_urls() {
+        let error = SubconverterConfigBuilder::new().build().unwrap_err();
+        assert_eq!(fields(&error.issues), ["urls"]);
+        assert_eq!(error.to_string(), "urls: No URLs provided");
+    }
+
+    #[test]
+    fn test_upload_without_path() {
+        let mut builder = builder();
+        builder.upload(true);
+        let error = builder.clone().build().unwrap_err();
+        assert_eq!(fields(&error.issues), ["upload_path"]);
+
+        builder.upload_path(Some("my.yaml".to_string()));
+        assert!(builder.build().is_ok());
+    }
+
+    #[test]
+    fn test_zero_remark_length() {
+        let mut builder = builder();
+        builder.max_remark_length(Some(0));
+        let error = builder.clone().build().unwrap_err();
+        assert_eq!(fields(&error.issues), ["max_remark_length"]);
+
+        builder.max_remark_length(Some(1));
+        assert!(builder.build().is_ok());
+    }
+
+    #[test]
+    fn test_invalid_remark_patterns() {
+        let mut builder = builder();
+        builder
+            .include_remarks(vec!["(HK".to_string(), "!!GROUP=A!!SG".to_string()])
+            .exclude_remarks(vec!["!!GROUP=A!![bad".to_string()])
+            .pin_regex("*".to_string());
+        let error = builder.build().unwrap_err();
+        assert_eq!(
+            fields(&error.issues),
+            ["include_remarks", "exclude_remarks", "pin_regex"]
+        );
+        assert_eq!(
+            error.to_string(),
+            "include_remarks: invalid regex '(HK'; exclude_remarks: invalid regex '!!GROUP=A!![bad'; pin_regex: invalid regex '*'"
+        );
+    }
+
+    #[test]
+    fn test_warnings_are_kept_on_the_config() {
+        let mut builder = builder();
+        builder
+            .managed_config_prefix("https://sub.example.com".to_string())
+            .enable_rule_generator(false)
+            .surge_version(4)
+            .rename_array(vec![RegexMatchConfig {
+                _match: "(bad".to_string(),
+                replace: "good".to_string(),
+            }]);
+        let config = builder.build().unwrap();
+        assert_eq!(
+            fields(&config.warnings),
+            ["managed_config_prefix", "surge_version", "rename_array"]
+        );
+        assert!(config.warnings.iter().all(|issue| !issue.is_error()));
+    }
+
+    #[test]
+    fn test_errors_list_warnings_too() {
+        let mut builder = SubconverterConfigBuilder::new();
+        builder
+            .target(SubconverterTarget::Clash)
+            .surge_version(4)
+            .upload(true);
+        let error = builder.build().unwrap_err();
+        assert_eq!(
+            fields(&error.issues),
+            ["urls", "upload_path", "surge_version"]
+        );
+        assert_eq!(error.errors().count(), 2);
+    }
+
+    #[test]
+    fn test_surge_version_applies_in_any_order() {
+        let mut builder = builder();
+        builder
+            .surge_version(3)
+            .target(SubconverterTarget::Surge(4));
+        let config = builder.build().unwrap();
+        assert_eq!(config.target, SubconverterTarget::Surge(3));
+        assert!(config.warnings.is_empty());
+    }
+}
@@ -1,16 +1,28 @@
+//! Convert between proxy subscription formats
+//!
+//! The supported library API lives in [`prelude`], the other public modules
+//! are shared with the server binary and are not covered by semver.
+
 pub mod api;
+#[doc(hidden)]
 pub mod constants;
+#[doc(hidden)]
 pub mod generator;
 pub mod interfaces;
 pub mod models;
 pub mod parser;
+pub mod prelude;
+#[doc(hidden)]
 pub mod rulesets;
 pub mod settings;
+#[doc(hidden)]
 pub mod template;
+#[doc(hidden)]
 pub mod utils;
-#[cfg(target_arch = "wasm32")]
+#[cfg(all(target_arch = "wasm32", feature = "wasm-vfs"))]
+#[doc(hidden)]
 pub mod vfs;
-#[cfg(feature = "web-api")]
+#[cfg(feature = "web")]
 pub mod web_handlers;
 
 // Re-export the main proxy types for easier access
@@ -19,6 +31,9 @@ pub use models::{Proxy, ProxyType};
 // Re-export configuration types
 pub use parser::types::ConfType;
 
+// Re-export content auto-detection
+pub use parser::{explode_any, explode_any_with_limits, ContentLimits, ParseError};
+
 // Re-export settings
 pub use settings::settings::settings_struct::update_settings_from_content;
 pub use settings::settings::settings_struct::update_settings_from_file;
@@ -30,8 +45,11 @@ pub use models::ruleset::RulesetType;
 // Re-export SubconverterTarget
 pub use models::SubconverterTarget;
 
-// Re-export interfaces
-pub use interfaces::*;
+// Re-export the conversion entry points
+pub use interfaces::subconverter::{
+    parse_subscription, subconverter, ParseOptions, SubconverterConfig, SubconverterConfigBuilder,
+    SubconverterResult,
+};
 
 // Re-export template functionality
 pub use template::{render_template, TemplateArgs};
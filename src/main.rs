@@ -1,12 +1,16 @@
-use std::sync::Arc;
-
 use actix_web::{test, web, App, HttpServer};
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use env_logger::Env;
 use log::{error, info};
 use std::fs;
+use std::path::Path;
 
+use subconverter::api::{mark_start_time, sub_process_batch, SubconverterQuery};
+use subconverter::interfaces::check_external_config;
+use subconverter::models::SubconverterTarget;
+use subconverter::rulesets::refresher::run_ruleset_refresher;
 use subconverter::settings::settings::settings_struct::init_settings;
+use subconverter::settings::SettingsFormat;
 use subconverter::{web_handlers, Settings};
 
 /// A more powerful utility to convert between proxy subscription format
@@ -32,18 +36,64 @@ struct Args {
     /// Output file path for subscription conversion (must be used with --url)
     #[arg(short, long, value_name = "OUTPUT_FILE")]
     output: Option<String>,
+
+    /// Comma separated targets to convert --url to in one run, e.g.
+    /// clash,singbox,surge4 (must be used with --output-dir)
+    #[arg(long, value_name = "TARGETS", value_delimiter = ',')]
+    targets: Option<Vec<String>>,
+
+    /// Directory receiving one config.<ext> per target (must be used with --targets)
+    #[arg(long, value_name = "DIR")]
+    output_dir: Option<String>,
+
+    /// Write the effective settings to a file and exit, the format follows the
+    /// file extension (.ini, .toml, .yml)
+    #[arg(long, value_name = "FILE")]
+    dump_config: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Check the groups, patterns and rulesets of an external config and
+    /// exit with code 1 on errors
+    CheckConfig {
+        /// Path or URL of the external config
+        path: String,
+
+        /// Download the remote rulesets as well
+        #[arg(long)]
+        fetch: bool,
+    },
 }
 
+/// Response headers a subscription client acts on, logged in direct mode
+const SUBSCRIPTION_HEADERS: [&str; 3] = [
+    "Subscription-UserInfo",
+    "Profile-Update-Interval",
+    "Profile-Web-Page-Url",
+];
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     // Initialize the logger
     env_logger::init_from_env(Env::default().default_filter_or("info"));
 
+    mark_start_time();
+
     // Parse command line arguments
     let args = Args::parse();
 
-    // Check if only one of url or output is provided
-    if args.url.is_some() != args.output.is_some() {
+    // Check that the direct processing flags are used together
+    let batch = args.targets.is_some() || args.output_dir.is_some();
+    if batch {
+        if args.targets.is_none() || args.output_dir.is_none() || args.url.is_none() {
+            eprintln!("Error: --url, --targets and --output-dir must be used together");
+            std::process::exit(1);
+        }
+    } else if args.url.is_some() != args.output.is_some() {
         eprintln!("Error: --url and -o/--output must be used together");
         std::process::exit(1);
     }
@@ -53,6 +103,45 @@ async fn main() -> std::io::Result<()> {
         .await
         .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
 
+    // Check an external config without starting anything
+    if let Some(Command::CheckConfig { path, fetch }) = &args.command {
+        let report = check_external_config(path, *fetch).await;
+        println!("{}", report);
+        if report.has_errors() {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    // Dump the merged settings if requested
+    if let Some(dump_path) = args.dump_config {
+        let settings = Settings::current().clone();
+        settings
+            .save_to_file(&dump_path, SettingsFormat::from_path(&dump_path))
+            .await
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+        info!("Wrote settings to {}", dump_path);
+        return Ok(());
+    }
+
+    // Convert to several targets at once
+    if let (Some(url), Some(targets), Some(output_dir)) =
+        (&args.url, &args.targets, &args.output_dir)
+    {
+        let failed = match run_batch(url, targets, output_dir).await {
+            Ok(failed) => failed,
+            Err(e) => {
+                error!("{}", e);
+                std::process::exit(1);
+            }
+        };
+        if failed > 0 {
+            error!("{} of {} targets failed", failed, targets.len());
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     // Check if URL is provided for direct processing
     if let Some(url) = args.url {
         let output_file = args
@@ -75,6 +164,12 @@ async fn main() -> std::io::Result<()> {
 
         // Check if the response is successful
         if resp.status().is_success() {
+            for name in SUBSCRIPTION_HEADERS {
+                if let Some(value) = resp.headers().get(name).and_then(|v| v.to_str().ok()) {
+                    info!("{}: {}", name, value);
+                }
+            }
+
             // Get response body
             let body = test::read_body(resp).await;
 
@@ -91,18 +186,15 @@ async fn main() -> std::io::Result<()> {
         // Proceed with starting the web server
         // Ensure we have a valid listen address
         let listen_address = {
-            // Get a mutable reference to the current settings
-            let mut settings_guard = Settings::current_mut();
-            let settings = Arc::make_mut(&mut *settings_guard);
-
             // Override settings with command line arguments if provided
+            let mut settings = Settings::clone(&Settings::current());
             if let Some(address) = args.address {
                 settings.listen_address = address;
             }
             if let Some(port) = args.port {
                 settings.listen_port = port;
             }
-            if settings.listen_address.trim().is_empty() {
+            let listen_address = if settings.listen_address.trim().is_empty() {
                 error!("Empty listen_address in settings, defaulting to 127.0.0.1");
                 format!("127.0.0.1:{}", settings.listen_port)
             } else {
@@ -114,16 +206,31 @@ async fn main() -> std::io::Result<()> {
                     // No port specified, use the one from settings
                     format!("{}:{}", settings.listen_address, settings.listen_port)
                 }
-            }
+            };
+            Settings::replace(settings);
+            listen_address
         };
 
+        // Reload settings when the settings file or local rulesets change
+        #[cfg(feature = "watch")]
+        if let Err(e) = subconverter::settings::watcher::start_settings_watcher() {
+            error!("Failed to start settings watcher: {}", e);
+        }
+
+        // Keep the rulesets of the settings fresh for the requests using them
+        actix_web::rt::spawn(run_ruleset_refresher());
+
         let max_concur_threads = Settings::current().max_concur_threads;
+        let settings = Settings::current();
 
         info!("Subconverter starting on {}", listen_address);
 
         // Start web server
         HttpServer::new(move || {
             App::new()
+                // Compress responses when enabled in the settings
+                .wrap(web_handlers::gzip_level_middleware(&settings))
+                .wrap(web_handlers::compress_middleware(&settings))
                 // Register web handlers
                 .configure(web_handlers::config)
                 // For health check
@@ -135,3 +242,60 @@ async fn main() -> std::io::Result<()> {
         .await
     }
 }
+
+/// Convert `url` to every target and write the results to `output_dir`
+///
+/// `url` is either a `/sub?...` request URI or a plain subscription URL. A target
+/// that fails is logged and skipped, the number of failed targets is returned.
+async fn run_batch(url: &str, targets: &[String], output_dir: &str) -> Result<usize, String> {
+    let mut parsed_targets = Vec::with_capacity(targets.len());
+    for name in targets {
+        let name = name.trim();
+        match SubconverterTarget::from_str(name) {
+            Some(target) => parsed_targets.push(target),
+            None => return Err(format!("Invalid target '{}'", name)),
+        }
+    }
+
+    let query = match url.strip_prefix('/') {
+        Some(uri) => {
+            let query_string = uri.split_once('?').map(|(_, q)| q).unwrap_or_default();
+            web::Query::<SubconverterQuery>::from_query(query_string)
+                .map_err(|e| format!("Invalid request URI '{}': {}", url, e))?
+                .into_inner()
+        }
+        None => SubconverterQuery {
+            url: Some(url.to_string()),
+            ..Default::default()
+        },
+    };
+
+    let results = sub_process_batch(query, &parsed_targets, &Settings::current())
+        .await
+        .map_err(|response| format!("Conversion failed: {}", response.content))?;
+
+    fs::create_dir_all(output_dir)
+        .map_err(|e| format!("Failed to create {}: {}", output_dir, e))?;
+    let mut failed = 0;
+    for (target, response) in results {
+        if response.status_code != 200 {
+            error!("Target {} failed: {}", target.to_str(), response.content);
+            failed += 1;
+            continue;
+        }
+        for name in SUBSCRIPTION_HEADERS {
+            if let Some(value) = response.headers.get(name) {
+                info!("{} {}: {}", target.to_str(), name, value);
+            }
+        }
+        let path = Path::new(output_dir).join(format!("config.{}", target.file_extension()));
+        match fs::write(&path, response.content) {
+            Ok(()) => info!("Wrote {} to {}", target.to_str(), path.display()),
+            Err(e) => {
+                error!("Failed to write {}: {}", path.display(), e);
+                failed += 1;
+            }
+        }
+    }
+    Ok(failed)
+}
@@ -1,3 +1,4 @@
+use crate::utils::plugin_options::normalize_plugin_options;
 use crate::{Proxy, ProxyType};
 
 impl Proxy {
@@ -169,6 +170,9 @@ impl Proxy {
             underlying_proxy,
         );
 
+        let plugin_opts = normalize_plugin_options(plugin_opts);
+        let plugin_opts = plugin_opts.as_str();
+
         // Set up the combined proxy with ShadowsocksProxy
         let ss_proxy = crate::models::proxy_node::shadowsocks::ShadowsocksProxy {
             server: server.to_string(),
@@ -359,7 +363,7 @@ impl Proxy {
         udp: Option<bool>,
         underlying_proxy: Option<String>,
     ) -> Self {
-        let mut dns_set = std::collections::HashSet::new();
+        let mut dns_set = indexmap::IndexSet::new();
         for dns in dns_servers {
             dns_set.insert(dns);
         }
@@ -407,7 +411,7 @@ impl Proxy {
         allow_insecure: Option<bool>,
         underlying_proxy: Option<String>,
     ) -> Self {
-        let mut alpn_set = std::collections::HashSet::new();
+        let mut alpn_set = indexmap::IndexSet::new();
         for proto in alpn {
             alpn_set.insert(proto);
         }
@@ -462,7 +466,7 @@ impl Proxy {
         allow_insecure: Option<bool>,
         underlying_proxy: Option<String>,
     ) -> Self {
-        let mut alpn_set = std::collections::HashSet::new();
+        let mut alpn_set = indexmap::IndexSet::new();
         for proto in alpn {
             alpn_set.insert(proto);
         }
@@ -0,0 +1,188 @@
+//! Warnings collected while converting
+//!
+//! Problems that do not stop a conversion, like a node the target cannot
+//! express, are logged and pushed to the [`WarningCollector`] of the
+//! [`ExtraSettings`](super::ExtraSettings). The conversion returns them in
+//! [`SubconverterResult::warnings`](crate::SubconverterResult), the server
+//! sums them up in the `X-Subconverter-Warnings` header.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use log::warn;
+use serde::Serialize;
+
+use super::Proxy;
+
+/// Number of kinds listed in the `X-Subconverter-Warnings` header
+const HEADER_KINDS: usize = 3;
+
+/// What a conversion warning is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WarningKind {
+    /// A problem of the config found by the builder, e.g. a broken rename rule
+    Config,
+    /// A subscription URL failed and was skipped
+    FailedUrl,
+    /// A node the target cannot express was left out
+    UnsupportedNode,
+    /// A rename rule left a node without a remark, the original was kept
+    RenameFailed,
+    /// A proxy group matched no nodes and got the placeholder policy
+    EmptyGroup,
+    /// Rules reference a group that is neither a proxy group nor a node
+    MissingRuleGroup,
+}
+
+impl WarningKind {
+    /// Name of the kind, as in the JSON responses and the header
+    pub fn name(self) -> &'static str {
+        match self {
+            WarningKind::Config => "config",
+            WarningKind::FailedUrl => "failed_url",
+            WarningKind::UnsupportedNode => "unsupported_node",
+            WarningKind::RenameFailed => "rename_failed",
+            WarningKind::EmptyGroup => "empty_group",
+            WarningKind::MissingRuleGroup => "missing_rule_group",
+        }
+    }
+}
+
+impl fmt::Display for WarningKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A problem that did not stop the conversion
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConversionWarning {
+    pub kind: WarningKind,
+    pub message: String,
+}
+
+impl fmt::Display for ConversionWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.kind, self.message)
+    }
+}
+
+/// Collects the warnings of a conversion
+///
+/// Clones share the same list, so the clones of the extra settings handed
+/// to the generators report to the conversion. The default collector only
+/// logs, for generators called directly.
+#[derive(Debug, Clone, Default)]
+pub struct WarningCollector {
+    warnings: Option<Arc<Mutex<Vec<ConversionWarning>>>>,
+}
+
+impl WarningCollector {
+    /// A collector keeping the warnings
+    pub fn new() -> Self {
+        WarningCollector {
+            warnings: Some(Arc::new(Mutex::new(Vec::new()))),
+        }
+    }
+
+    /// Log a warning and keep it
+    pub fn push(&self, kind: WarningKind, message: impl Into<String>) {
+        let message = message.into();
+        warn!("{}", message);
+        if let Some(warnings) = &self.warnings {
+            warnings
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(ConversionWarning { kind, message });
+        }
+    }
+
+    /// Report a node left out because `target` cannot express it
+    pub fn unsupported_node(&self, node: &Proxy, target: &str) {
+        self.push(
+            WarningKind::UnsupportedNode,
+            format!(
+                "Skipping {} node '{}', not supported by {}",
+                node.proxy_type, node.remark, target
+            ),
+        );
+    }
+
+    /// The warnings kept so far, leaving the collector empty
+    pub fn take(&self) -> Vec<ConversionWarning> {
+        match &self.warnings {
+            Some(warnings) => {
+                std::mem::take(&mut *warnings.lock().unwrap_or_else(|e| e.into_inner()))
+            }
+            None => Vec::new(),
+        }
+    }
+}
+
+/// Value of the `X-Subconverter-Warnings` header, `None` without warnings
+///
+/// The count, then the first kinds in the order they happened, e.g.
+/// `3; unsupported_node, empty_group`.
+pub fn warnings_header(warnings: &[ConversionWarning]) -> Option<String> {
+    if warnings.is_empty() {
+        return None;
+    }
+    let mut kinds: Vec<&str> = Vec::new();
+    for warning in warnings {
+        if !kinds.contains(&warning.kind.name()) {
+            kinds.push(warning.kind.name());
+        }
+    }
+    let more = kinds.len() > HEADER_KINDS;
+    kinds.truncate(HEADER_KINDS);
+    Some(format!(
+        "{}; {}{}",
+        warnings.len(),
+        kinds.join(", "),
+        if more { ", ..." } else { "" }
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_warnings_header() {
+        assert_eq!(warnings_header(&[]), None);
+
+        let collector = WarningCollector::new();
+        for kind in [
+            WarningKind::UnsupportedNode,
+            WarningKind::EmptyGroup,
+            WarningKind::UnsupportedNode,
+        ] {
+            collector.push(kind, "message");
+        }
+        let warnings = collector.take();
+        assert_eq!(
+            warnings_header(&warnings).as_deref(),
+            Some("3; unsupported_node, empty_group")
+        );
+        assert!(collector.take().is_empty());
+
+        for kind in [
+            WarningKind::Config,
+            WarningKind::FailedUrl,
+            WarningKind::RenameFailed,
+            WarningKind::MissingRuleGroup,
+        ] {
+            collector.push(kind, "message");
+        }
+        assert_eq!(
+            warnings_header(&collector.take()).as_deref(),
+            Some("4; config, failed_url, rename_failed, ...")
+        );
+
+        // The default collector only logs
+        let collector = WarningCollector::default();
+        collector.push(WarningKind::Config, "message");
+        assert!(collector.take().is_empty());
+    }
+}
@@ -1,6 +1,54 @@
+use std::collections::HashMap;
+
 use crate::Settings;
 
-use super::RegexMatchConfigs;
+use super::{NodePlugins, ProxyType, RegexMatchConfigs, WarningCollector};
+
+/// A QuantumultX `server_remote` entry referencing the node list of the conversion
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuanxServerRemote {
+    /// URL of the node list
+    pub url: String,
+    /// Tag of the resource
+    pub tag: String,
+    /// `as-policy` of the resource (e.g. `static`), `None` to leave it out
+    pub as_policy: Option<String>,
+}
+
+/// What a proxy group whose rules matched nothing is filled with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EmptyGroupPlaceholder {
+    /// Fill the group with `DIRECT`
+    #[default]
+    Direct,
+    /// Fill the group with `REJECT`
+    Reject,
+    /// Fail the conversion
+    Error,
+}
+
+impl EmptyGroupPlaceholder {
+    /// Parse `direct`, `reject` or `error`, case-insensitively
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "direct" => Some(Self::Direct),
+            "reject" => Some(Self::Reject),
+            "error" => Some(Self::Error),
+            _ => None,
+        }
+    }
+
+    /// The policy written into an empty group
+    ///
+    /// Generators called directly with `Error` fall back to `DIRECT`, the
+    /// conversion itself fails before generating anything.
+    pub fn policy(&self) -> &'static str {
+        match self {
+            Self::Reject => "REJECT",
+            Self::Direct | Self::Error => "DIRECT",
+        }
+    }
+}
 
 /// Settings for subscription export operations
 #[derive(Debug, Clone)]
@@ -31,10 +79,18 @@ pub struct ExtraSettings {
     pub clash_script: bool,
     /// Path to Surge SSR binary
     pub surge_ssr_path: String,
+    /// Let Surge resolve the hostnames of the nodes itself
+    pub surge_resolve_hostname: bool,
+    /// Add the `GLOBAL` group and the Clash mode rules to sing-box configs
+    pub singbox_add_clash_modes: bool,
+    /// Set the alterId of every VMess node to 0, so clients use AEAD
+    pub vmess_aead_force: bool,
     /// Prefix for managed configs
     pub managed_config_prefix: String,
     /// QuantumultX device ID
     pub quanx_dev_id: String,
+    /// Reference the nodes through `server_remote` instead of writing them to `server_local`
+    pub quanx_server_remote: Option<QuanxServerRemote>,
     /// UDP support flag
     pub udp: Option<bool>,
     /// TCP Fast Open support flag
@@ -43,14 +99,55 @@ pub struct ExtraSettings {
     pub skip_cert_verify: Option<bool>,
     /// TLS 1.3 support flag
     pub tls13: Option<bool>,
+    /// Interface to dial the nodes from when they do not set one
+    pub interface: Option<String>,
+    /// Routing mark of the nodes when they do not set one
+    pub routing_mark: Option<u32>,
     /// Whether to use classical ruleset in Clash
     pub clash_classical_ruleset: bool,
+    /// Write the rules of the rulesets into Loon configs instead of
+    /// referencing them in `[Remote Rule]`
+    pub expand_rulesets: bool,
     /// Script for sorting nodes
     pub sort_script: String,
+    /// Nodes whose remark matches are moved to the front after sorting, empty to disable
+    pub pin_regex: String,
     /// Style for Clash proxies output
     pub clash_proxies_style: String,
     /// Style for Clash proxy groups output
     pub clash_proxy_groups_style: String,
+    /// Whether to normalize generated Clash output for Clash.Meta
+    pub sanitize_output: bool,
+    /// Whether to write Shadowsocks nodes as origin/plain links in SSR output
+    pub ssr_format_ss: bool,
+    /// Maximum number of rules written to the output, 0 for unlimited
+    pub max_allowed_rules: usize,
+    /// Maximum number of characters in a node remark
+    pub max_remark_length: Option<usize>,
+    /// Template naming every node, e.g. `{flag} {remark}-{index:02}`, instead of the emoji rules
+    pub remark_template: Option<String>,
+    /// Node list URLs of proxy providers by name, written as Surge `policy-path`
+    pub provider_urls: HashMap<String, String>,
+    /// Update interval in seconds of the provider node lists, 0 to leave it to the client
+    pub provider_update_interval: u32,
+    /// Clash `profile-update-interval` in hours, 0 to leave it out
+    pub profile_update_interval: u32,
+    /// Proxy types to keep, empty to keep all types
+    pub include_types: Vec<ProxyType>,
+    /// Proxy types to drop, applied after `include_types`
+    pub exclude_types: Vec<ProxyType>,
+    /// Regexes of the server addresses to keep, empty to keep all nodes
+    pub include_servers: Vec<String>,
+    /// Regexes of the server addresses to drop, applied after `include_servers`
+    pub exclude_servers: Vec<String>,
+    /// Write Clash proxies of unknown types, e.g. anytls, back to Clash output as they came
+    pub preserve_unknown_proxies: bool,
+    /// What proxy groups whose rules matched nothing are filled with
+    pub empty_group_placeholder: EmptyGroupPlaceholder,
+    /// Node filters and transforms registered by the embedding crate
+    pub plugins: NodePlugins,
+    /// Where the problems that do not stop the conversion are reported
+    pub warnings: WarningCollector,
     /// Whether the export is authorized
     pub authorized: bool,
     /// JavaScript runtime context (not implemented in Rust version)
@@ -59,8 +156,13 @@ pub struct ExtraSettings {
 
 impl Default for ExtraSettings {
     fn default() -> Self {
-        let global = Settings::current();
+        Self::from_settings(&Settings::current())
+    }
+}
 
+impl ExtraSettings {
+    /// Defaults taken from `global`, the snapshot of the conversion
+    pub fn from_settings(global: &Settings) -> Self {
         ExtraSettings {
             enable_rule_generator: global.enable_rule_gen,
             overwrite_original_rules: global.overwrite_original_rules,
@@ -75,14 +177,22 @@ impl Default for ExtraSettings {
             clash_new_field_name: true,
             clash_script: false,
             surge_ssr_path: global.surge_ssr_path.clone(),
+            surge_resolve_hostname: global.surge_resolve_hostname,
+            singbox_add_clash_modes: global.singbox_add_clash_modes,
+            vmess_aead_force: global.vmess_aead_force,
             managed_config_prefix: String::new(),
             quanx_dev_id: String::new(),
+            quanx_server_remote: None,
             udp: None,
             tfo: None,
             skip_cert_verify: None,
             tls13: None,
+            interface: None,
+            routing_mark: None,
             clash_classical_ruleset: false,
+            expand_rulesets: true,
             sort_script: String::new(),
+            pin_regex: String::new(),
             clash_proxies_style: if global.clash_proxies_style.is_empty() {
                 "flow".to_string()
             } else {
@@ -93,6 +203,22 @@ impl Default for ExtraSettings {
             } else {
                 global.clash_proxy_groups_style.clone()
             },
+            sanitize_output: true,
+            ssr_format_ss: false,
+            max_allowed_rules: global.max_allowed_rules,
+            max_remark_length: None,
+            remark_template: None,
+            provider_urls: HashMap::new(),
+            provider_update_interval: 0,
+            profile_update_interval: 0,
+            include_types: Vec::new(),
+            exclude_types: Vec::new(),
+            include_servers: Vec::new(),
+            exclude_servers: Vec::new(),
+            preserve_unknown_proxies: true,
+            empty_group_placeholder: EmptyGroupPlaceholder::default(),
+            plugins: NodePlugins::default(),
+            warnings: WarningCollector::default(),
             authorized: false,
             js_context: None,
         }
@@ -51,9 +51,11 @@
 pub mod builder;
 pub mod ciphers;
 pub mod configs;
+pub mod conversion_warning;
 pub mod cron;
 pub mod extra_settings;
 pub mod ini_bindings;
+pub mod node_plugin;
 pub mod proxy;
 pub mod proxy_group_config;
 pub mod proxy_node;
@@ -61,15 +63,17 @@ pub mod regex_match_config;
 pub mod ruleset;
 pub mod subconverter_target;
 
-pub use extra_settings::ExtraSettings;
+pub use conversion_warning::{ConversionWarning, WarningCollector, WarningKind};
+pub use extra_settings::{EmptyGroupPlaceholder, ExtraSettings, QuanxServerRemote};
+pub use node_plugin::{NodeFilter, NodePlugins, NodeTransform};
 pub use proxy_group_config::{
-    BalanceStrategy, ProxyGroupConfig, ProxyGroupConfigs, ProxyGroupType,
+    BalanceStrategy, ProxyGroupConfig, ProxyGroupConfigs, ProxyGroupType, SourceProxyGroups,
 };
 pub use regex_match_config::{RegexMatchConfig, RegexMatchConfigs};
 pub use subconverter_target::SubconverterTarget;
 
 pub use proxy::{Proxy, ProxyType};
-pub use ruleset::{RulesetConfig, RulesetContent, RulesetType};
+pub use ruleset::{RulesetBehavior, RulesetConfig, RulesetContent, RulesetType};
 
 // Re-export constants to module scope for use by other modules
 // Default proxy group names
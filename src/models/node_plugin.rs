@@ -0,0 +1,118 @@
+//! Hooks for embedding crates to filter and transform nodes
+//!
+//! Filters and transforms run in [`preprocess_nodes`](crate::utils::preprocess_nodes)
+//! after the include/exclude filters of the subscription and before any
+//! rename, emoji or sort operation. All filters run first, then all transforms,
+//! each in the order they were added.
+//!
+//! # Example
+//!
+//! ```rust
+//! use subconverter::models::{NodeFilter, NodeTransform, Proxy};
+//! use subconverter::utils::base64::base64_encode;
+//! use subconverter::{subconverter, SubconverterConfigBuilder, SubconverterTarget};
+//!
+//! /// Drop nodes listening on port 80
+//! struct NoPlainHttp;
+//!
+//! impl NodeFilter for NoPlainHttp {
+//!     fn keep(&self, node: &Proxy) -> bool {
+//!         node.port != 80
+//!     }
+//! }
+//!
+//! /// Uppercase every remark
+//! struct Uppercase;
+//!
+//! impl NodeTransform for Uppercase {
+//!     fn apply(&self, node: &mut Proxy) {
+//!         node.remark = node.remark.to_uppercase();
+//!     }
+//! }
+//!
+//! let links = "ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@1.2.3.4:80#plain\n\
+//!              ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@1.2.3.4:8388#secure";
+//! let mut builder = SubconverterConfigBuilder::new();
+//! builder
+//!     .target(SubconverterTarget::Clash)
+//!     .add_url(&format!("data:text/plain;base64,{}", base64_encode(links)))
+//!     .nodelist(true)
+//!     .add_filter(Box::new(NoPlainHttp))
+//!     .add_transform(Box::new(Uppercase));
+//! let config = builder.build().unwrap();
+//!
+//! let result = tokio::runtime::Builder::new_current_thread()
+//!     .enable_all()
+//!     .build()
+//!     .unwrap()
+//!     .block_on(subconverter(config))
+//!     .unwrap();
+//! assert!(result.content.contains("SECURE"));
+//! assert!(!result.content.contains("plain"));
+//! ```
+
+use std::fmt;
+use std::sync::Arc;
+
+use super::Proxy;
+
+/// Decides whether a node is kept in the output
+pub trait NodeFilter: Send + Sync {
+    /// Return `false` to drop the node
+    fn keep(&self, node: &Proxy) -> bool;
+}
+
+/// Modifies a node in place
+pub trait NodeTransform: Send + Sync {
+    /// Apply the transformation to the node
+    fn apply(&self, node: &mut Proxy);
+}
+
+impl<F: Fn(&Proxy) -> bool + Send + Sync> NodeFilter for F {
+    fn keep(&self, node: &Proxy) -> bool {
+        self(node)
+    }
+}
+
+impl<F: Fn(&mut Proxy) + Send + Sync> NodeTransform for F {
+    fn apply(&self, node: &mut Proxy) {
+        self(node)
+    }
+}
+
+/// Filters and transforms registered for a conversion
+#[derive(Clone, Default)]
+pub struct NodePlugins {
+    /// Filters, applied in order
+    pub filters: Vec<Arc<dyn NodeFilter>>,
+    /// Transforms, applied in order after the filters
+    pub transforms: Vec<Arc<dyn NodeTransform>>,
+}
+
+impl NodePlugins {
+    /// Whether no filter or transform is registered
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty() && self.transforms.is_empty()
+    }
+
+    /// Drop the nodes rejected by any filter, then run every transform on the rest
+    pub fn apply(&self, nodes: &mut Vec<Proxy>) {
+        if !self.filters.is_empty() {
+            nodes.retain(|node| self.filters.iter().all(|filter| filter.keep(node)));
+        }
+        for node in nodes.iter_mut() {
+            for transform in &self.transforms {
+                transform.apply(node);
+            }
+        }
+    }
+}
+
+impl fmt::Debug for NodePlugins {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NodePlugins")
+            .field("filters", &self.filters.len())
+            .field("transforms", &self.transforms.len())
+            .finish()
+    }
+}
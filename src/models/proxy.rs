@@ -2,48 +2,76 @@
 //!
 //! Contains the core data structures for proxy configurations.
 
-use std::collections::HashSet;
+use std::convert::Infallible;
+use std::fmt;
+use std::str::FromStr;
+
+use indexmap::IndexSet;
 
 use super::proxy_node::combined::CombinedProxy;
 
-/// Represents the type of a proxy.
-/// This is the canonical enum used for proxy type identification across the application.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub enum ProxyType {
-    Unknown,
-    Shadowsocks,
-    ShadowsocksR,
-    VMess,
-    Trojan,
-    Snell,
-    HTTP,
-    HTTPS,
-    Socks5,
-    WireGuard,
-    Hysteria,
-    Hysteria2,
-    // new proxy types could be added as enum combined proxy types
-    Vless,
-}
+/// Declares `ProxyType` together with the name of each variant
+///
+/// The names are the single source for `Display`, `FromStr` and the type
+/// filters, a variant cannot be added without one.
+macro_rules! proxy_types {
+    ($($(#[$meta:meta])* $variant:ident => $name:literal,)+) => {
+        /// Represents the type of a proxy.
+        /// This is the canonical enum used for proxy type identification across the application.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum ProxyType {
+            $($(#[$meta])* $variant,)+
+        }
 
-/// Converts a `ProxyType` into a human-readable name.
-impl ProxyType {
-    pub fn to_string(self) -> &'static str {
-        match self {
-            ProxyType::Shadowsocks => "SS",
-            ProxyType::ShadowsocksR => "SSR",
-            ProxyType::VMess => "VMess",
-            ProxyType::Trojan => "Trojan",
-            ProxyType::Snell => "Snell",
-            ProxyType::HTTP => "HTTP",
-            ProxyType::HTTPS => "HTTPS",
-            ProxyType::Socks5 => "SOCKS5",
-            ProxyType::WireGuard => "WireGuard",
-            ProxyType::Hysteria => "Hysteria",
-            ProxyType::Hysteria2 => "Hysteria2",
-            ProxyType::Vless => "Vless",
-            ProxyType::Unknown => "Unknown",
+        impl ProxyType {
+            /// Every proxy type, in declaration order
+            pub const ALL: &'static [ProxyType] = &[$(ProxyType::$variant,)+];
+
+            /// Human-readable name, e.g. the `[SS]` of appended proxy types
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(ProxyType::$variant => $name,)+
+                }
+            }
         }
+    };
+}
+
+proxy_types! {
+    Unknown => "Unknown",
+    Shadowsocks => "SS",
+    ShadowsocksR => "SSR",
+    VMess => "VMess",
+    Trojan => "Trojan",
+    Snell => "Snell",
+    HTTP => "HTTP",
+    HTTPS => "HTTPS",
+    Socks5 => "SOCKS5",
+    WireGuard => "WireGuard",
+    Hysteria => "Hysteria",
+    Hysteria2 => "Hysteria2",
+    Vless => "Vless",
+    /// No parser produces it yet, every generator skips it
+    Tuic => "TUIC",
+}
+
+impl fmt::Display for ProxyType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Parses a name of `Display` case-insensitively, other names give `Unknown`
+impl FromStr for ProxyType {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim();
+        Ok(ProxyType::ALL
+            .iter()
+            .copied()
+            .find(|proxy_type| proxy_type.name().eq_ignore_ascii_case(name))
+            .unwrap_or(ProxyType::Unknown))
     }
 }
 
@@ -63,7 +91,8 @@ pub struct Proxy {
     pub password: Option<String>,
     pub encrypt_method: Option<String>,
     pub plugin: Option<String>,
-    /// Plugin options in the format of `key1=value1;key2=value2`
+    /// Plugin options as SIP003 `key1=value1;key2=value2` pairs, switches such
+    /// as `tls` have no value. See [`crate::utils::plugin_options`].
     pub plugin_option: Option<String>,
     pub protocol: Option<String>,
     pub protocol_param: Option<String>,
@@ -88,8 +117,14 @@ pub struct Proxy {
     pub tls13: Option<bool>,
 
     pub underlying_proxy: Option<String>,
+    /// Network interface the node is dialed from, `interface-name` of Clash
+    pub interface: Option<String>,
+    /// Linux routing mark of the node connections, `routing-mark` of Clash
+    pub routing_mark: Option<u32>,
 
     pub snell_version: u16,
+    pub reuse: Option<bool>,
+    pub ecn: Option<bool>,
     pub server_name: Option<String>,
 
     pub self_ip: Option<String>,
@@ -97,7 +132,7 @@ pub struct Proxy {
     pub public_key: Option<String>,
     pub private_key: Option<String>,
     pub pre_shared_key: Option<String>,
-    pub dns_servers: HashSet<String>,
+    pub dns_servers: IndexSet<String>,
     pub mtu: u16,
     pub allowed_ips: String,
     pub keep_alive: u16,
@@ -113,15 +148,34 @@ pub struct Proxy {
     pub auth_str: Option<String>,
     pub sni: Option<String>,
     pub fingerprint: Option<String>,
+    /// uTLS client fingerprint used by Clash.Meta and sing-box
+    pub client_fingerprint: Option<String>,
+    /// REALITY public key, the server is authenticated with it instead of a certificate
+    pub reality_public_key: Option<String>,
+    pub reality_short_id: Option<String>,
+    /// Whether Encrypted Client Hello is enabled
+    pub ech_enabled: Option<bool>,
+    /// Base64 encoded ECHConfigList, looked up through DNS when absent
+    pub ech_config: Option<String>,
+    pub smux_enabled: Option<bool>,
+    pub smux_max_streams: Option<u32>,
     pub ca: Option<String>,
     pub ca_str: Option<String>,
     pub recv_window_conn: u32,
     pub recv_window: u32,
     pub disable_mtu_discovery: Option<bool>,
     pub hop_interval: u32,
-    pub alpn: HashSet<String>,
+    pub alpn: IndexSet<String>,
 
     pub cwnd: u32,
+
+    /// Clash proxy of a type not modelled here, such as anytls or mieru,
+    /// kept verbatim to be written back to Clash output
+    pub raw_passthrough: Option<serde_yaml::Mapping>,
+    /// Identifier the source gives the node, such as the `id` of a SIP008
+    /// server, telling apart nodes that share a remark. Nodes listed again
+    /// with the same id, server and port are dropped when preprocessing.
+    pub source_id: Option<String>,
 }
 
 /// Implement Default for Proxy
@@ -160,14 +214,18 @@ impl Default for Proxy {
             allow_insecure: None,
             tls13: None,
             underlying_proxy: None,
+            interface: None,
+            routing_mark: None,
             snell_version: 0,
+            reuse: None,
+            ecn: None,
             server_name: None,
             self_ip: None,
             self_ipv6: None,
             public_key: None,
             private_key: None,
             pre_shared_key: None,
-            dns_servers: HashSet::new(),
+            dns_servers: IndexSet::new(),
             mtu: 0,
             allowed_ips: String::from("0.0.0.0/0, ::/0"),
             keep_alive: 0,
@@ -180,14 +238,23 @@ impl Default for Proxy {
             auth_str: None,
             sni: None,
             fingerprint: None,
+            client_fingerprint: None,
+            reality_public_key: None,
+            reality_short_id: None,
+            ech_enabled: None,
+            ech_config: None,
+            smux_enabled: None,
+            smux_max_streams: None,
             ca: None,
             ca_str: None,
             recv_window_conn: 0,
             recv_window: 0,
             disable_mtu_discovery: None,
             hop_interval: 0,
-            alpn: HashSet::new(),
+            alpn: IndexSet::new(),
             cwnd: 0,
+            raw_passthrough: None,
+            source_id: None,
         }
     }
 }
@@ -197,6 +264,65 @@ impl Proxy {
         matches!(self.proxy_type, ProxyType::Vless | ProxyType::Shadowsocks)
     }
 
+    /// Whether the node is a Clash proxy kept verbatim, see `raw_passthrough`
+    pub fn is_passthrough(&self) -> bool {
+        self.raw_passthrough.is_some()
+    }
+
+    /// Total length of the text fields of the node, in bytes
+    pub fn text_len(&self) -> usize {
+        let options = [
+            &self.username,
+            &self.password,
+            &self.encrypt_method,
+            &self.plugin,
+            &self.plugin_option,
+            &self.protocol,
+            &self.protocol_param,
+            &self.obfs,
+            &self.obfs_param,
+            &self.user_id,
+            &self.transfer_protocol,
+            &self.fake_type,
+            &self.host,
+            &self.path,
+            &self.edge,
+            &self.quic_secure,
+            &self.quic_secret,
+            &self.underlying_proxy,
+            &self.interface,
+            &self.server_name,
+            &self.self_ip,
+            &self.self_ipv6,
+            &self.public_key,
+            &self.private_key,
+            &self.pre_shared_key,
+            &self.test_url,
+            &self.client_id,
+            &self.ports,
+            &self.auth,
+            &self.auth_str,
+            &self.sni,
+            &self.fingerprint,
+            &self.client_fingerprint,
+            &self.reality_public_key,
+            &self.reality_short_id,
+            &self.ech_config,
+            &self.ca,
+            &self.ca_str,
+            &self.source_id,
+        ];
+        let strings = [&self.group, &self.remark, &self.hostname, &self.allowed_ips];
+        options
+            .into_iter()
+            .flatten()
+            .map(String::len)
+            .sum::<usize>()
+            + strings.into_iter().map(String::len).sum::<usize>()
+            + self.dns_servers.iter().map(String::len).sum::<usize>()
+            + self.alpn.iter().map(String::len).sum::<usize>()
+    }
+
     /// 设置 UDP 支持，如果值已存在则不覆盖
     pub fn with_udp(mut self, udp: Option<bool>) -> Self {
         if self.udp.is_none() {
@@ -266,6 +392,23 @@ impl Proxy {
 
         self
     }
+
+    /// Fill the interface and routing mark the node does not set itself
+    pub fn apply_dial_defaults(
+        mut self,
+        default_interface: Option<String>,
+        default_routing_mark: Option<u32>,
+    ) -> Self {
+        if self.interface.is_none() {
+            self.interface = default_interface;
+        }
+
+        if self.routing_mark.is_none() {
+            self.routing_mark = default_routing_mark;
+        }
+
+        self
+    }
 }
 
 /// Default provider group names as constants.
@@ -279,3 +422,40 @@ pub const SNELL_DEFAULT_GROUP: &str = "SnellProvider";
 pub const WG_DEFAULT_GROUP: &str = "WireGuardProvider";
 pub const HYSTERIA_DEFAULT_GROUP: &str = "HysteriaProvider";
 pub const HYSTERIA2_DEFAULT_GROUP: &str = "Hysteria2Provider";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_proxy_type_names_round_trip() {
+        for &proxy_type in ProxyType::ALL {
+            let name = proxy_type.to_string();
+            assert_eq!(name.parse::<ProxyType>(), Ok(proxy_type), "{}", name);
+            assert_eq!(name.to_lowercase().parse::<ProxyType>(), Ok(proxy_type));
+            assert_eq!(name.to_uppercase().parse::<ProxyType>(), Ok(proxy_type));
+            assert_eq!(format!(" {} ", name).parse::<ProxyType>(), Ok(proxy_type));
+        }
+        let mut names: Vec<String> = ProxyType::ALL
+            .iter()
+            .map(|proxy_type| proxy_type.name().to_lowercase())
+            .collect();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), ProxyType::ALL.len(), "names must be unique");
+    }
+
+    #[test]
+    fn test_unknown_proxy_type_names() {
+        for name in ["", "  ", "bogus", "ss,ssr", "shadowsocks", "vless2"] {
+            assert_eq!(
+                name.parse::<ProxyType>(),
+                Ok(ProxyType::Unknown),
+                "{}",
+                name
+            );
+        }
+        assert_eq!("vless".parse::<ProxyType>(), Ok(ProxyType::Vless));
+        assert_eq!("tuic".parse::<ProxyType>(), Ok(ProxyType::Tuic));
+    }
+}
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 /// Type of proxy group
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ProxyGroupType {
@@ -71,6 +73,10 @@ pub struct ProxyGroupConfig {
     pub persistent: bool,
     /// Whether to evaluate before use
     pub evaluate_before_use: bool,
+    /// Regex a node remark must match to stay in the group
+    pub include_filter: Option<String>,
+    /// Regex removing matching nodes from the group
+    pub exclude_filter: Option<String>,
 }
 
 impl Default for ProxyGroupConfig {
@@ -89,6 +95,8 @@ impl Default for ProxyGroupConfig {
             disable_udp: false,
             persistent: false,
             evaluate_before_use: false,
+            include_filter: None,
+            exclude_filter: None,
         }
     }
 }
@@ -112,11 +120,47 @@ impl ProxyGroupConfig {
     pub fn strategy_str(&self) -> &'static str {
         self.strategy.as_str()
     }
+
+    /// Whether the group runs health checks and uses url / interval / timeout / tolerance
+    pub fn is_tested_group(&self) -> bool {
+        matches!(
+            self.group_type,
+            ProxyGroupType::URLTest
+                | ProxyGroupType::LoadBalance
+                | ProxyGroupType::Fallback
+                | ProxyGroupType::Smart
+        )
+    }
 }
 
 /// A collection of proxy group configurations
 pub type ProxyGroupConfigs = Vec<ProxyGroupConfig>;
 
+/// Proxy groups defined by a source configuration, e.g. a full Surge config
+#[derive(Debug, Clone, Default)]
+pub struct SourceProxyGroups {
+    pub groups: ProxyGroupConfigs,
+    /// `policy-path` node list URLs, keyed by the name of the group using them
+    pub provider_urls: HashMap<String, String>,
+}
+
+impl SourceProxyGroups {
+    /// Points the `[]name` members at the remarks the nodes got by renaming
+    ///
+    /// `renamed` maps the remarks before renaming to the final ones, members
+    /// naming no node, e.g. other groups or `DIRECT`, are kept.
+    pub fn rename_members(&mut self, renamed: &HashMap<String, String>) {
+        for member in self.groups.iter_mut().flat_map(|group| &mut group.proxies) {
+            let Some(name) = member.strip_prefix("[]") else {
+                continue;
+            };
+            if let Some(remark) = renamed.get(name) {
+                *member = format!("[]{}", remark);
+            }
+        }
+    }
+}
+
 use serde::ser::SerializeStruct;
 use serde::{Serialize, Serializer};
 
@@ -1,12 +1,12 @@
+use indexmap::{IndexMap, IndexSet};
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VlessProxy {
     pub uuid: String,
     pub flow: Option<String>,
     pub tls: bool,
-    pub alpn: HashSet<String>,
+    pub alpn: IndexSet<String>,
     pub udp: bool,
     pub packet_addr: Option<bool>,
     pub xudp: Option<bool>,
@@ -16,12 +16,12 @@ pub struct VlessProxy {
     pub reality_short_id: Option<String>,
     pub http_method: Option<String>,
     pub http_path: Option<String>,
-    pub http_headers: Option<HashMap<String, Vec<String>>>,
+    pub http_headers: Option<IndexMap<String, Vec<String>>>,
     pub h2_host: Option<Vec<String>>,
     pub h2_path: Option<String>,
     pub grpc_service_name: Option<String>,
     pub ws_path: Option<String>,
-    pub ws_headers: Option<HashMap<String, String>>,
+    pub ws_headers: Option<IndexMap<String, String>>,
     pub skip_cert_verify: Option<bool>,
     pub fingerprint: Option<String>,
     pub servername: Option<String>,
@@ -34,7 +34,7 @@ impl Default for VlessProxy {
             uuid: String::new(),
             flow: None,
             tls: false,
-            alpn: HashSet::new(),
+            alpn: IndexSet::new(),
             udp: true,
             packet_addr: None,
             xudp: None,
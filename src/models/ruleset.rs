@@ -3,6 +3,8 @@ use std::{
     sync::{Arc, RwLock},
 };
 
+use crate::utils::reg_match;
+
 /// Enum defining the type of ruleset
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RulesetType {
@@ -29,6 +31,7 @@ pub static RULESET_TYPES: once_cell::sync::Lazy<RulesetMapping> =
         types.insert("clash-domain:".to_string(), RulesetType::ClashDomain);
         types.insert("clash-ipcidr:".to_string(), RulesetType::ClashIpcidr);
         types.insert("clash-classical:".to_string(), RulesetType::ClashClassical);
+        types.insert("clash-classic:".to_string(), RulesetType::ClashClassical);
         types.insert("quanx:".to_string(), RulesetType::Quanx);
         types.insert("surge:".to_string(), RulesetType::Surge);
         types
@@ -47,6 +50,89 @@ pub fn get_ruleset_type_from_url(url: &str) -> Option<RulesetType> {
     None
 }
 
+/// Strip a ruleset type prefix such as `clash-domain:` from a ruleset path
+pub fn strip_ruleset_prefix(url: &str) -> &str {
+    RULESET_TYPES
+        .keys()
+        .find_map(|prefix| url.strip_prefix(prefix.as_str()))
+        .unwrap_or(url)
+}
+
+/// Behavior of a Clash rule provider
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RulesetBehavior {
+    Domain,
+    Ipcidr,
+    Classical,
+}
+
+impl RulesetBehavior {
+    /// Name of the behavior as used in Clash `rule-providers`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RulesetBehavior::Domain => "domain",
+            RulesetBehavior::Ipcidr => "ipcidr",
+            RulesetBehavior::Classical => "classical",
+        }
+    }
+}
+
+/// Number of leading rule lines inspected when guessing the behavior of untyped rulesets
+const BEHAVIOR_SAMPLE_LINES: usize = 20;
+
+/// Strip Clash payload list syntax from a ruleset line
+fn strip_payload_item(line: &str) -> &str {
+    let line = line.strip_prefix('-').map(str::trim).unwrap_or(line);
+    line.trim_matches(|c| c == '\'' || c == '"')
+}
+
+fn is_ip_cidr(value: &str) -> bool {
+    let (address, prefix) = match value.split_once('/') {
+        Some((address, prefix)) => (address, Some(prefix)),
+        None => (value, None),
+    };
+    address.parse::<std::net::IpAddr>().is_ok()
+        && prefix.is_none_or(|prefix| prefix.parse::<u8>().is_ok())
+}
+
+fn is_domain(value: &str) -> bool {
+    !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | '+' | '*'))
+}
+
+/// Guess the behavior of a ruleset from its first rule lines
+///
+/// Lists of bare IP ranges are `ipcidr`, lists of bare domains (like Surge
+/// DOMAIN-SET files) are `domain`, anything else is `classical`.
+pub fn detect_ruleset_behavior(content: &str) -> RulesetBehavior {
+    let samples: Vec<&str> = content
+        .lines()
+        .map(str::trim)
+        .filter(|line| {
+            !line.is_empty()
+                && !line.starts_with('#')
+                && !line.starts_with(';')
+                && !line.starts_with("//")
+                && *line != "payload:"
+        })
+        .map(strip_payload_item)
+        .take(BEHAVIOR_SAMPLE_LINES)
+        .collect();
+
+    if samples.is_empty() {
+        return RulesetBehavior::Classical;
+    }
+    if samples.iter().all(|line| is_ip_cidr(line)) {
+        RulesetBehavior::Ipcidr
+    } else if samples.iter().all(|line| is_domain(line)) {
+        RulesetBehavior::Domain
+    } else {
+        RulesetBehavior::Classical
+    }
+}
+
 #[derive(Debug, Clone, Default, PartialEq)]
 pub struct RulesetConfig {
     pub group: String,
@@ -117,6 +203,23 @@ impl RulesetContent {
         }
     }
 
+    /// Behavior to use when this ruleset is served as a Clash rule provider
+    ///
+    /// Rulesets with an explicit type prefix use the behavior of that type,
+    /// untyped rulesets are classified by sampling their content.
+    pub fn behavior(&self) -> RulesetBehavior {
+        if get_ruleset_type_from_url(&self.rule_path_typed).is_none() {
+            return detect_ruleset_behavior(&self.get_rule_content());
+        }
+        match self.rule_type {
+            RulesetType::ClashDomain => RulesetBehavior::Domain,
+            RulesetType::ClashIpcidr => RulesetBehavior::Ipcidr,
+            RulesetType::ClashClassical | RulesetType::Surge | RulesetType::Quanx => {
+                RulesetBehavior::Classical
+            }
+        }
+    }
+
     /// Check if rule content has been set
     /// Simulates std::shared_future::valid() in C++
     pub fn has_rule_content(&self) -> bool {
@@ -133,3 +236,114 @@ pub fn parse_ruleset(content: &str, group: &str) -> RulesetContent {
     ruleset.set_rule_content(content);
     ruleset
 }
+
+/// Parse `rename_rule_group` items of the form `from@to`
+///
+/// Items without a `@` or with an empty side are skipped.
+pub fn parse_rule_group_map(items: &[String]) -> Vec<(String, String)> {
+    items
+        .iter()
+        .filter_map(|item| item.split_once('@'))
+        .map(|(from, to)| (from.trim().to_string(), to.trim().to_string()))
+        .filter(|(from, to)| !from.is_empty() && !to.is_empty())
+        .collect()
+}
+
+/// Format a rule group map back into `from@to` items
+pub fn rule_group_map_to_ini(map: &[(String, String)]) -> Vec<String> {
+    map.iter()
+        .map(|(from, to)| format!("{}@{}", from, to))
+        .collect()
+}
+
+/// Look up the group a ruleset group is renamed to
+///
+/// Exact matches take precedence over entries whose name is a regex matching
+/// the whole group, `None` when no entry applies.
+pub fn map_rule_group<'a>(group: &str, map: &'a [(String, String)]) -> Option<&'a str> {
+    map.iter()
+        .find(|(from, _)| from == group)
+        .or_else(|| map.iter().find(|(from, _)| reg_match(group, from)))
+        .map(|(_, to)| to.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn typed_ruleset(url: &str, content: &str) -> RulesetContent {
+        let mut ruleset = RulesetContent::new(url, "Proxy");
+        if let Some(rule_type) = get_ruleset_type_from_url(url) {
+            ruleset.rule_type = rule_type;
+        }
+        ruleset.set_rule_content(content);
+        ruleset
+    }
+
+    #[test]
+    fn test_behavior_from_prefix() {
+        let domains = "example.com\n.google.com\n";
+        let cases = [
+            (
+                "surge:https://example.com/a.list",
+                RulesetBehavior::Classical,
+            ),
+            (
+                "quanx:https://example.com/a.list",
+                RulesetBehavior::Classical,
+            ),
+            (
+                "clash-domain:https://example.com/a.yaml",
+                RulesetBehavior::Domain,
+            ),
+            (
+                "clash-ipcidr:https://example.com/a.yaml",
+                RulesetBehavior::Ipcidr,
+            ),
+            (
+                "clash-classical:https://example.com/a.yaml",
+                RulesetBehavior::Classical,
+            ),
+            (
+                "clash-classic:https://example.com/a.yaml",
+                RulesetBehavior::Classical,
+            ),
+        ];
+        for (url, expected) in cases {
+            assert_eq!(typed_ruleset(url, domains).behavior(), expected, "{}", url);
+        }
+    }
+
+    #[test]
+    fn test_behavior_detected_from_content() {
+        let domain_set = "# DOMAIN-SET\n\n.apple.com\nicloud.com\n+.mzstatic.com\n";
+        let ruleset = typed_ruleset("https://example.com/apple.txt", domain_set);
+        assert_eq!(ruleset.behavior(), RulesetBehavior::Domain);
+
+        let payload = "payload:\n  - '10.0.0.0/8'\n  - '2001:db8::/32'\n";
+        assert_eq!(detect_ruleset_behavior(payload), RulesetBehavior::Ipcidr);
+
+        let classical = "DOMAIN-SUFFIX,apple.com\nIP-CIDR,17.0.0.0/8,no-resolve\n";
+        assert_eq!(
+            detect_ruleset_behavior(classical),
+            RulesetBehavior::Classical
+        );
+    }
+
+    #[test]
+    fn test_map_rule_group() {
+        let map = parse_rule_group_map(&[
+            "Proxy@🚀 节点选择".to_string(),
+            "(?i)stream.*@🎥 Streaming".to_string(),
+            "invalid".to_string(),
+        ]);
+        assert_eq!(map.len(), 2);
+        assert_eq!(map_rule_group("Proxy", &map), Some("🚀 节点选择"));
+        assert_eq!(map_rule_group("Streaming US", &map), Some("🎥 Streaming"));
+        assert_eq!(map_rule_group("DIRECT", &map), None);
+        assert_eq!(
+            rule_group_map_to_ini(&map),
+            vec!["Proxy@🚀 节点选择", "(?i)stream.*@🎥 Streaming"]
+        );
+    }
+}
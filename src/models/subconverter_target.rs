@@ -1,4 +1,12 @@
+use std::future::Future;
+
+use log::{debug, warn};
+
 use crate::settings::external::ExternalSettings;
+use crate::utils::file::load_content_async;
+use crate::utils::file_get_async;
+use crate::utils::memory_cache;
+use crate::Settings;
 
 /// The output format for subconverter
 #[derive(Debug, Clone, PartialEq, Hash, Eq)]
@@ -47,7 +55,11 @@ impl SubconverterTarget {
             "shadowrocket" => Some(SubconverterTarget::Mixed),
             // Map surfboardios to regular Surfboard
             "surfboardios" => Some(SubconverterTarget::Surfboard),
-            _ => None,
+            // Versioned Surge as produced by `to_str`, e.g. "surge4"
+            other => other
+                .strip_prefix("surge")
+                .and_then(|ver| ver.parse::<i32>().ok())
+                .map(SubconverterTarget::Surge),
         }
     }
 
@@ -74,6 +86,42 @@ impl SubconverterTarget {
         }
     }
 
+    /// HTTP Content-Type of the generated configuration
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            SubconverterTarget::Clash | SubconverterTarget::ClashR => "text/yaml",
+            SubconverterTarget::SingBox | SubconverterTarget::SSSub => "application/json",
+            // SSD is an `ssd://` link holding base64
+            _ => "text/plain",
+        }
+    }
+
+    /// User-Agent of the subscription requests, `None` for the default of the client
+    ///
+    /// Many providers only serve Clash YAML, with every field of the nodes,
+    /// to a Clash User-Agent and a base64 list of links otherwise.
+    pub fn subscription_user_agent(&self) -> Option<&'static str> {
+        match self {
+            SubconverterTarget::Clash | SubconverterTarget::ClashR => Some("clash-verge/v1.7.7"),
+            _ => None,
+        }
+    }
+
+    /// File extension used when writing the generated configuration to disk
+    pub fn file_extension(&self) -> &'static str {
+        match self {
+            SubconverterTarget::Clash | SubconverterTarget::ClashR => "yml",
+            SubconverterTarget::SingBox | SubconverterTarget::SSSub => "json",
+            SubconverterTarget::Surge(_)
+            | SubconverterTarget::Surfboard
+            | SubconverterTarget::Mellow
+            | SubconverterTarget::Quantumult
+            | SubconverterTarget::QuantumultX
+            | SubconverterTarget::Loon => "conf",
+            _ => "txt",
+        }
+    }
+
     pub fn is_clash(&self) -> bool {
         matches!(self, SubconverterTarget::Clash | SubconverterTarget::ClashR)
     }
@@ -90,6 +138,37 @@ impl SubconverterTarget {
         )
     }
 
+    /// Gets the base configuration path set for this target in the settings
+    pub fn get_base_path_from_settings<'a>(&self, settings: &'a Settings) -> Option<&'a str> {
+        let path = match self {
+            SubconverterTarget::Clash | SubconverterTarget::ClashR => &settings.clash_base,
+            SubconverterTarget::Surge(_) => &settings.surge_base,
+            SubconverterTarget::Surfboard => &settings.surfboard_base,
+            SubconverterTarget::Mellow => &settings.mellow_base,
+            SubconverterTarget::Quantumult => &settings.quan_base,
+            SubconverterTarget::QuantumultX => &settings.quanx_base,
+            SubconverterTarget::Loon => &settings.loon_base,
+            SubconverterTarget::SSSub => &settings.ssub_base,
+            SubconverterTarget::SingBox => &settings.singbox_base,
+            _ => return None,
+        };
+        Some(path.as_str()).filter(|path| !path.is_empty())
+    }
+
+    /// Loads the default base configuration of this target from the settings
+    ///
+    /// See [`load_base_content`] for how the path is resolved.
+    pub async fn get_base_content<F, Fut>(&self, settings: &Settings, fetcher: F) -> String
+    where
+        F: FnOnce(String) -> Fut,
+        Fut: Future<Output = Result<String, String>>,
+    {
+        match self.get_base_path_from_settings(settings) {
+            Some(path) => load_base_content(path, settings.cache_config, fetcher).await,
+            None => String::new(),
+        }
+    }
+
     /// Gets the base content for this target from the external config
     pub fn get_base_content_from_external(&self, external: &ExternalSettings) -> Option<String> {
         match self {
@@ -160,3 +239,147 @@ impl SubconverterTarget {
         }
     }
 }
+
+/// Fetches a remote base configuration through the proxied, size limited HTTP client
+///
+/// Proxy and size limit are those of `settings`.
+pub async fn fetch_base_url(url: String, settings: &Settings) -> Result<String, String> {
+    load_content_async(&url, settings).await
+}
+
+/// Loads a base configuration from an http(s) URL or a local file
+///
+/// URLs are requested through `fetcher` and kept in the memory cache for
+/// `cache_timeout` seconds. Anything else is read as a local file. Failures
+/// are logged and result in an empty base.
+pub async fn load_base_content<F, Fut>(path: &str, cache_timeout: u32, fetcher: F) -> String
+where
+    F: FnOnce(String) -> Fut,
+    Fut: Future<Output = Result<String, String>>,
+{
+    if path.is_empty() {
+        return String::new();
+    }
+
+    if !path.starts_with("http://") && !path.starts_with("https://") {
+        return match file_get_async(path, None).await {
+            Ok(content) => {
+                debug!("Loaded rule base from file: {}", path);
+                content
+            }
+            Err(e) => {
+                warn!("Failed to load rule base from file {}: {}", path, e);
+                String::new()
+            }
+        };
+    }
+
+    if cache_timeout > 0 {
+        if let Some(content) = memory_cache::get_if_valid(path, cache_timeout) {
+            debug!("Using cached rule base for URL: {}", path);
+            return content;
+        }
+    }
+
+    match fetcher(path.to_string()).await {
+        Ok(content) if !content.is_empty() => {
+            debug!("Loaded rule base from URL: {}", path);
+            if cache_timeout > 0 {
+                if let Err(e) = memory_cache::store(path, &content) {
+                    warn!("Failed to store rule base in cache: {}", e);
+                }
+            }
+            content
+        }
+        Ok(_) => {
+            warn!("Empty rule base from URL: {}", path);
+            String::new()
+        }
+        Err(e) => {
+            warn!("Failed to load rule base from URL {}: {}", path, e);
+            String::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn test_versioned_surge_target() {
+        let target = SubconverterTarget::from_str("surge4").unwrap();
+        assert_eq!(target, SubconverterTarget::Surge(4));
+        assert_eq!(SubconverterTarget::from_str(&target.to_str()), Some(target));
+        assert_eq!(SubconverterTarget::from_str("surgex"), None);
+        assert_eq!(SubconverterTarget::Surge(4).file_extension(), "conf");
+        assert_eq!(SubconverterTarget::Clash.file_extension(), "yml");
+        assert_eq!(SubconverterTarget::SingBox.file_extension(), "json");
+    }
+
+    #[test]
+    fn test_base_content_from_file() {
+        let path = std::env::temp_dir().join("subconverter_test_clash_base.yaml");
+        std::fs::write(&path, "mixed-port: 7890\n").unwrap();
+        let settings = Settings {
+            clash_base: path.to_string_lossy().to_string(),
+            ..Default::default()
+        };
+
+        let content = block_on(
+            SubconverterTarget::ClashR.get_base_content(&settings, |url: String| async move {
+                Err(format!("unexpected fetch of {}", url))
+            }),
+        );
+        assert_eq!(content, "mixed-port: 7890\n");
+        assert_eq!(
+            block_on(
+                SubconverterTarget::Loon
+                    .get_base_content(&settings, |url| fetch_base_url(url, &settings))
+            ),
+            ""
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_base_content_from_url() {
+        let url = "https://example.com/subconverter_test_surge_base.conf";
+        let settings = Settings {
+            surge_base: url.to_string(),
+            cache_config: 60,
+            ..Default::default()
+        };
+
+        let content = block_on(SubconverterTarget::Surge(4).get_base_content(
+            &settings,
+            |requested: String| async move {
+                assert_eq!(requested, url);
+                Ok("[General]\n".to_string())
+            },
+        ));
+        assert_eq!(content, "[General]\n");
+
+        // Served from the cache even though the upstream is now failing
+        let cached = block_on(
+            SubconverterTarget::Surge(3)
+                .get_base_content(&settings, |_: String| async { Err("offline".to_string()) }),
+        );
+        assert_eq!(cached, "[General]\n");
+
+        let failed = block_on(load_base_content(
+            "https://example.com/subconverter_test_missing.conf",
+            0,
+            |_: String| async { Err("offline".to_string()) },
+        ));
+        assert_eq!(failed, "");
+    }
+}
@@ -1,5 +1,8 @@
-use crate::utils::base64::base64_decode;
-use crate::Proxy;
+use std::fmt;
+
+use crate::utils::base64::decode_lenient;
+use crate::{Proxy, ProxyType};
+use log::debug;
 
 /// Explode a proxy link into a Proxy object
 ///
@@ -53,6 +56,8 @@ pub fn explode(link: &str, node: &mut Proxy) -> bool {
         super::httpsub::explode_http_sub(link, node)
     } else if link.starts_with("trojan://") {
         super::trojan::explode_trojan(link, node)
+    } else if link.starts_with("trojan-go://") {
+        super::trojan::explode_trojan_go(link, node)
     } else if link.starts_with("snell://") {
         super::snell::explode_snell(link, node)
     } else if link.starts_with("wg://") || link.starts_with("wireguard://") {
@@ -71,17 +76,136 @@ pub fn explode(link: &str, node: &mut Proxy) -> bool {
     }
 }
 
+/// How many base64 layers may wrap the links of a subscription
+pub const MAX_DECODE_DEPTH: usize = 2;
+
+/// Default bound of the text of the parsed nodes, as a multiple of the
+/// content size
+///
+/// Base64 layers only shrink the content and the fields of a node are taken
+/// from its text, so nodes only outgrow their content when they share fields:
+/// the defaults of SSD servers or the merge keys and aliases of Clash YAML.
+pub const DEFAULT_MAX_DECODED_RATIO: usize = 2;
+
+/// Bounds on the content of a subscription, guarding against base64 bombs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentLimits {
+    /// Maximum number of base64 layers, 0 for unlimited
+    pub max_decode_depth: usize,
+    /// Maximum size of the text of the parsed nodes, as a multiple of the
+    /// content size, 0 for unlimited
+    pub max_decoded_ratio: usize,
+    /// Maximum number of nodes in the content, 0 for unlimited
+    pub max_nodes: usize,
+}
+
+impl Default for ContentLimits {
+    fn default() -> Self {
+        ContentLimits {
+            max_decode_depth: MAX_DECODE_DEPTH,
+            max_decoded_ratio: DEFAULT_MAX_DECODED_RATIO,
+            max_nodes: 0,
+        }
+    }
+}
+
+impl ContentLimits {
+    fn check_node_count(&self, count: usize) -> Result<(), ParseError> {
+        if self.max_nodes > 0 && count > self.max_nodes {
+            return Err(ParseError::TooManyNodes {
+                count,
+                limit: self.max_nodes,
+            });
+        }
+        Ok(())
+    }
+
+    /// Check the text of the nodes parsed from `input` bytes of content
+    fn check_decoded_size(&self, nodes: &[Proxy], input: usize) -> Result<(), ParseError> {
+        if self.max_decoded_ratio == 0 {
+            return Ok(());
+        }
+        let size = nodes.iter().map(Proxy::text_len).sum::<usize>();
+        if size > input.saturating_mul(self.max_decoded_ratio) {
+            return Err(ParseError::DecodedTooLarge {
+                size,
+                input,
+                ratio: self.max_decoded_ratio,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Peel off the base64 layers wrapping a subscription
+///
+/// Layers are decoded while the result holds no link and is still base64.
+/// Returns an empty string when `sub` is not base64 at all.
+fn decode_layers(sub: &str, limits: &ContentLimits) -> Result<String, ParseError> {
+    let mut decoded = String::new();
+    for depth in 1.. {
+        let layer = if depth == 1 { sub } else { decoded.as_str() };
+        if depth > 1 && layer.contains("://") {
+            break;
+        }
+        let Some((next, variant)) = decode_lenient(layer) else {
+            break;
+        };
+        if limits.max_decode_depth > 0 && depth > limits.max_decode_depth {
+            return Err(ParseError::TooDeeplyEncoded {
+                limit: limits.max_decode_depth,
+            });
+        }
+        debug!("Decoded base64 layer {} as {:?}", depth, variant);
+        decoded = next;
+    }
+    Ok(decoded)
+}
+
+/// Whether a line of the content starts a top level `proxies` key
+///
+/// Such content is meant as Clash YAML, even when it fails to parse.
+fn has_clash_proxies_key(content: &str) -> bool {
+    content.lines().any(|line| {
+        let line = line.trim_start_matches('\u{feff}');
+        line.starts_with("proxies:") || line.starts_with("Proxy:")
+    })
+}
+
+/// Parse the content as Clash YAML
+///
+/// A YAML error is returned for content with a `proxies` key, any other
+/// content is left to the detection of the other formats.
+fn explode_clash_checked(content: &str, nodes: &mut Vec<Proxy>) -> Result<bool, ParseError> {
+    match super::explode_clash::explode_clash(content, nodes) {
+        Ok(found) => Ok(found),
+        Err(e) if has_clash_proxies_key(content) => Err(ParseError::InvalidClash(e)),
+        Err(e) => {
+            debug!("Not Clash YAML: {}", e);
+            Ok(false)
+        }
+    }
+}
+
 /// Explode a subscription content into a vector of Proxy objects
 ///
 /// This function parses a subscription content (which may contain multiple proxy links)
 /// and returns a vector of Proxy objects
 pub fn explode_sub(sub: &str, nodes: &mut Vec<Proxy>) -> bool {
+    explode_sub_limited(sub, nodes, &ContentLimits::default()).unwrap_or(false)
+}
+
+fn explode_sub_limited(
+    sub: &str,
+    nodes: &mut Vec<Proxy>,
+    limits: &ContentLimits,
+) -> Result<bool, ParseError> {
     // Trim the subscription content
     let sub = sub.trim();
 
     // Check for empty subscription
     if sub.is_empty() {
-        return false;
+        return Ok(false);
     }
 
     let mut processed = false;
@@ -100,7 +224,7 @@ pub fn explode_sub(sub: &str, nodes: &mut Vec<Proxy>) -> bool {
             || sub.contains("Proxy:")
             || sub.contains("proxies:"))
     {
-        if super::explode_clash::explode_clash(sub, nodes) {
+        if explode_clash_checked(sub, nodes)? {
             processed = true;
         }
     }
@@ -112,8 +236,13 @@ pub fn explode_sub(sub: &str, nodes: &mut Vec<Proxy>) -> bool {
 
     // If no specific format was detected, try as a normal subscription
     if !processed {
-        // Try to decode as base64
-        let decoded = base64_decode(sub, false);
+        // Try to decode as base64, nested layers included
+        let mut decoded = decode_layers(sub, limits)?;
+
+        // Plain link lists are used as-is when they are not base64 encoded
+        if decoded.is_empty() && sub.contains("://") {
+            decoded = sub.to_string();
+        }
 
         // Check if it's a Surge format after decoding
         if decoded.contains("vmess=")
@@ -122,7 +251,7 @@ pub fn explode_sub(sub: &str, nodes: &mut Vec<Proxy>) -> bool {
             || decoded.contains("trojan=")
         {
             if super::surge::explode_surge(&decoded, nodes) {
-                return true;
+                return Ok(true);
             }
         }
 
@@ -135,14 +264,15 @@ pub fn explode_sub(sub: &str, nodes: &mut Vec<Proxy>) -> bool {
             ' '
         };
 
-        let lines: Vec<&str> = decoded.split(delimiter).collect();
+        let lines: Vec<&str> = decoded
+            .split(delimiter)
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect();
+        // Refuse oversized lists before building any node, only links count
+        limits.check_node_count(lines.iter().filter(|line| line.contains("://")).count())?;
 
         for line in lines {
-            let line = line.trim().trim_end_matches('\r');
-            if line.is_empty() {
-                continue;
-            }
-
             let mut node = Proxy::default();
             if explode(line, &mut node) {
                 nodes.push(node);
@@ -150,7 +280,7 @@ pub fn explode_sub(sub: &str, nodes: &mut Vec<Proxy>) -> bool {
         }
     }
 
-    !nodes.is_empty()
+    Ok(!nodes.is_empty())
 }
 
 /// Explodes a configuration file content into a vector of Proxy objects
@@ -165,12 +295,20 @@ pub fn explode_sub(sub: &str, nodes: &mut Vec<Proxy>) -> bool {
 /// # Returns
 /// Number of nodes successfully parsed, or 0 if parsing failed
 pub fn explode_conf_content(content: &str, nodes: &mut Vec<Proxy>) -> i32 {
+    explode_conf_content_limited(content, nodes, &ContentLimits::default()).unwrap_or(0)
+}
+
+fn explode_conf_content_limited(
+    content: &str,
+    nodes: &mut Vec<Proxy>,
+    limits: &ContentLimits,
+) -> Result<i32, ParseError> {
     // Trim the content
     let content = content.trim();
 
     // Check for empty content
     if content.is_empty() {
-        return 0;
+        return Ok(0);
     }
 
     let orig_size = nodes.len();
@@ -178,8 +316,18 @@ pub fn explode_conf_content(content: &str, nodes: &mut Vec<Proxy>) -> i32 {
 
     // Try to parse as JSON
     if content.starts_with('{') {
+        // Try to parse as SIP008 online configuration
+        if super::ss::explode_sip008(content, nodes) {
+            parsed = true;
+        }
         // Try to parse as V2Ray configuration
-        if super::vmess::explode_vmess_conf(content, nodes) {
+        else if super::vmess::explode_vmess_conf(content, nodes) {
+            parsed = true;
+        }
+        // Try ShadowsocksR and Shadowsocks client configurations
+        else if content.contains("\"protocol\"") && super::ssr::explode_ssr_conf(content, nodes) {
+            parsed = true;
+        } else if super::ss::explode_ss_conf(content, nodes) {
             parsed = true;
         }
         // Try Netch configuration
@@ -191,7 +339,7 @@ pub fn explode_conf_content(content: &str, nodes: &mut Vec<Proxy>) -> i32 {
     }
     // Try to parse as YAML/Clash
     else if content.contains("proxies:") || content.contains("Proxy:") {
-        if super::explode_clash::explode_clash(content, nodes) {
+        if explode_clash_checked(content, nodes)? {
             parsed = true;
         }
     }
@@ -226,13 +374,312 @@ pub fn explode_conf_content(content: &str, nodes: &mut Vec<Proxy>) -> i32 {
     }
 
     // If no specific format was detected, try as a simple subscription
-    if !parsed && explode_sub(content, nodes) {
+    if !parsed && explode_sub_limited(content, nodes, limits)? {
         parsed = true;
     }
 
     if parsed {
-        (nodes.len() - orig_size) as i32
+        limits.check_node_count(nodes.len() - orig_size)?;
+        limits.check_decoded_size(&nodes[orig_size..], content.len())?;
+        Ok((nodes.len() - orig_size) as i32)
+    } else {
+        Ok(0)
+    }
+}
+
+/// Error returned by [`explode_any`] and [`explode_any_with_limits`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// The content is empty
+    Empty,
+    /// The content is in no supported format or holds no valid node
+    NoValidNode,
+    /// The content is wrapped in more base64 layers than allowed
+    TooDeeplyEncoded { limit: usize },
+    /// The text of the parsed nodes grew beyond `ratio` times the `input` size
+    DecodedTooLarge {
+        size: usize,
+        input: usize,
+        ratio: usize,
+    },
+    /// The content holds more nodes than allowed
+    TooManyNodes { count: usize, limit: usize },
+    /// The content has a `proxies` key but is not valid YAML, with the error
+    /// of the YAML parser
+    InvalidClash(String),
+}
+
+impl ParseError {
+    /// Whether parsing was aborted by one of the [`ContentLimits`]
+    pub fn is_limit(&self) -> bool {
+        matches!(
+            self,
+            ParseError::TooDeeplyEncoded { .. }
+                | ParseError::DecodedTooLarge { .. }
+                | ParseError::TooManyNodes { .. }
+        )
+    }
+
+    /// Whether the error says more than that no node was found, such as a
+    /// broken limit or the position of a YAML error
+    pub fn is_detailed(&self) -> bool {
+        !matches!(self, ParseError::Empty | ParseError::NoValidNode)
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Empty => write!(f, "Empty content"),
+            ParseError::NoValidNode => write!(f, "No valid node found"),
+            ParseError::TooDeeplyEncoded { limit } => {
+                write!(f, "Content is base64 encoded more than {} times", limit)
+            }
+            ParseError::DecodedTooLarge { size, input, ratio } => write!(
+                f,
+                "Parsed {} bytes of nodes from {} bytes of content, more than {} times its size",
+                size, input, ratio
+            ),
+            ParseError::TooManyNodes { count, limit } => write!(
+                f,
+                "Content holds {} nodes, more than the limit of {}",
+                count, limit
+            ),
+            ParseError::InvalidClash(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parse content of any supported format into nodes
+///
+/// The format is detected in this order: a single share link, then the
+/// configuration formats handled by [`explode_conf_content`] (SIP008, V2Ray, SS
+/// and SSR JSON, Clash YAML, SSD, SSTap, Surge, Quantumult) and finally base64 encoded
+/// or plain link lists.
+///
+/// # Example
+///
+/// ```rust
+/// use subconverter::parser::explode_any;
+///
+/// let nodes = explode_any("ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@1.2.3.4:8388#Node").unwrap();
+/// assert_eq!(nodes[0].remark, "Node");
+/// ```
+pub fn explode_any(content: &str) -> Result<Vec<Proxy>, ParseError> {
+    explode_any_with_limits(content, &ContentLimits::default())
+}
+
+/// Parse content of any supported format into nodes within `limits`
+///
+/// Same as [`explode_any`], parsing is aborted as soon as the content breaks
+/// one of the limits.
+pub fn explode_any_with_limits(
+    content: &str,
+    limits: &ContentLimits,
+) -> Result<Vec<Proxy>, ParseError> {
+    let content = content.trim();
+    if content.is_empty() {
+        return Err(ParseError::Empty);
+    }
+
+    if !content.contains(char::is_whitespace) {
+        let mut node = Proxy::default();
+        if explode(content, &mut node) && node.proxy_type != ProxyType::Unknown {
+            return Ok(vec![node]);
+        }
+    }
+
+    let mut nodes = Vec::new();
+    if explode_conf_content_limited(content, &mut nodes, limits)? > 0 {
+        Ok(nodes)
     } else {
-        0
+        Err(ParseError::NoValidNode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::base64::base64_encode;
+
+    const SS_LINK: &str = "ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@1.2.3.4:8388#Node%20A";
+    const TROJAN_LINK: &str = "trojan://password@example.com:443#Node%20B";
+
+    #[test]
+    fn test_explode_any_formats() {
+        let link_list = format!("{}\n{}", SS_LINK, TROJAN_LINK);
+        let clash = "proxies:\n  - {name: Node C, type: ss, server: 1.2.3.4, port: 8388, cipher: aes-256-gcm, password: password}\n";
+        let ss_json = r#"{"configs":[{"server":"1.2.3.4","server_port":8388,"password":"password","method":"aes-256-gcm","remarks":"Node D"}]}"#;
+
+        for (content, expected) in [
+            (SS_LINK.to_string(), 1),
+            (link_list.clone(), 2),
+            (base64_encode(&link_list), 2),
+            (clash.to_string(), 1),
+            (ss_json.to_string(), 1),
+        ] {
+            let nodes = explode_any(&content).unwrap();
+            assert_eq!(nodes.len(), expected, "{}", content);
+        }
+
+        assert_eq!(explode_any("  \n").unwrap_err(), ParseError::Empty);
+        assert_eq!(
+            explode_any("not a subscription").unwrap_err(),
+            ParseError::NoValidNode
+        );
+    }
+
+    /// `count` links wrapped in `layers` of base64
+    fn bomb(layers: usize, count: usize) -> String {
+        let mut content = vec![SS_LINK; count].join("\n");
+        for _ in 0..layers {
+            content = base64_encode(&content);
+        }
+        content
+    }
+
+    #[test]
+    fn test_remarks_are_decoded_once() {
+        let remark = |link: &str| {
+            let mut node = Proxy::default();
+            assert!(explode(link, &mut node), "{}", link);
+            node.remark
+        };
+
+        for (fragment, expected) in [
+            ("HK+BGP", "HK+BGP"),
+            ("HK%2BBGP", "HK+BGP"),
+            ("%E9%A6%99%E6%B8%AF+01", "香港+01"),
+            ("香港 01", "香港 01"),
+            ("50%2525", "50%25"),
+        ] {
+            for link in [
+                "ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@1.2.3.4:8388",
+                "trojan://password@example.com:443",
+                "hysteria2://password@example.com:443?sni=example.com",
+                "vless://b831381d-6324-4d53-ad4f-8cda48b30811@example.com:443?security=tls",
+            ] {
+                assert_eq!(
+                    remark(&format!("{}#{}", link, fragment)),
+                    expected,
+                    "{}",
+                    link
+                );
+            }
+        }
+
+        // Query values keep their `+` too
+        let mut node = Proxy::default();
+        assert!(explode(
+            "hysteria2://pass%2Bword@example.com:443?sni=a+b.example.com#HK",
+            &mut node
+        ));
+        assert_eq!(node.password.as_deref(), Some("pass+word"));
+        assert_eq!(node.sni.as_deref(), Some("a+b.example.com"));
+        let mut node = Proxy::default();
+        assert!(explode(
+            "trojan://password@example.com:443?type=ws&path=%2Fws+1#HK",
+            &mut node
+        ));
+        assert_eq!(node.path.as_deref(), Some("/ws+1"));
+    }
+
+    #[test]
+    fn test_base64_bomb_limits() {
+        // Two layers are still decoded
+        let nodes = explode_any(&bomb(2, 3)).unwrap();
+        assert_eq!(nodes.len(), 3);
+
+        assert_eq!(
+            explode_any(&bomb(3, 3)).unwrap_err(),
+            ParseError::TooDeeplyEncoded { limit: 2 }
+        );
+
+        let limits = ContentLimits {
+            max_nodes: 100,
+            ..Default::default()
+        };
+        assert_eq!(
+            explode_any_with_limits(&bomb(2, 5000), &limits).unwrap_err(),
+            ParseError::TooManyNodes {
+                count: 5000,
+                limit: 100
+            }
+        );
+
+        // Base64 layers shrink the content, the nodes stay below its size
+        let limits = ContentLimits {
+            max_decoded_ratio: 1,
+            ..Default::default()
+        };
+        assert!(explode_any_with_limits(&bomb(2, 3), &limits).is_ok());
+    }
+
+    #[test]
+    fn test_shared_fields_outgrow_the_content() {
+        // Every SSD server takes the shared password
+        let servers = vec![r#"{"server":"1.2.3.4"}"#; 50].join(",");
+        let ssd = format!(
+            "ssd://{}",
+            base64_encode(&format!(
+                r#"{{"airport":"A","port":8388,"encryption":"aes-256-gcm","password":"{}","servers":[{}]}}"#,
+                "p".repeat(1000),
+                servers
+            ))
+        );
+        let error = explode_any(&ssd).unwrap_err();
+        assert!(
+            matches!(error, ParseError::DecodedTooLarge { ratio: 2, .. }),
+            "{}",
+            error
+        );
+        assert!(error.is_limit());
+
+        // Every Clash proxy merges the anchored password
+        let proxies = (0..50)
+            .map(|i| format!("  - {{<<: *common, name: N{}, server: 1.2.3.4}}\n", i))
+            .collect::<String>();
+        let clash = format!(
+            "x-common: &common {{type: ss, port: 8388, cipher: aes-256-gcm, password: {}}}\nproxies:\n{}",
+            "p".repeat(1000),
+            proxies
+        );
+        let error = explode_any(&clash).unwrap_err();
+        assert!(
+            matches!(error, ParseError::DecodedTooLarge { ratio: 2, .. }),
+            "{}",
+            error
+        );
+
+        // Without the limit both are parsed
+        let limits = ContentLimits {
+            max_decoded_ratio: 0,
+            ..Default::default()
+        };
+        assert_eq!(explode_any_with_limits(&ssd, &limits).unwrap().len(), 50);
+        assert_eq!(explode_any_with_limits(&clash, &limits).unwrap().len(), 50);
+
+        // Regular subscriptions stay within the default
+        let links = include_str!("../../../tests/fixtures/links.txt");
+        assert!(explode_any(links).is_ok());
+        assert!(explode_any(&base64_encode(links)).is_ok());
+        assert!(explode_any(include_str!("../../../tests/fixtures/clash.yaml")).is_ok());
+    }
+
+    #[test]
+    fn test_invalid_clash_yaml_is_reported() {
+        let content = include_str!("../../../tests/fixtures/clash_tab_indent.yaml");
+        let error = explode_any(content).unwrap_err();
+        assert!(matches!(error, ParseError::InvalidClash(_)), "{:?}", error);
+        assert!(error.to_string().contains("line 4"), "{}", error);
+        assert!(!error.is_limit());
+
+        // Without a top level proxies key, other formats are still tried
+        let links = format!("{}\ntrojan://password@example.com:443#proxies:2", SS_LINK);
+        let nodes = explode_any(&links).unwrap();
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[1].remark, "proxies:2");
     }
 }
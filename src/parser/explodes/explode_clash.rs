@@ -4,39 +4,49 @@ use crate::{
         SNELL_DEFAULT_GROUP, SOCKS_DEFAULT_GROUP, SSR_DEFAULT_GROUP, SS_DEFAULT_GROUP,
         TROJAN_DEFAULT_GROUP, V2RAY_DEFAULT_GROUP, WG_DEFAULT_GROUP,
     },
-    parser::yaml::clash::parse_clash_yaml,
+    parser::yaml::clash::{
+        apply_dial_options, load_clash_yaml, parse_clash_yaml, passthrough_proxy,
+    },
+    utils::{
+        plugin_options::{from_clash_plugin, join_plugin_options},
+        string::parse_mbps,
+    },
 };
+use log::debug;
 use serde_yaml::Value;
 
 /// Parse a Clash YAML configuration into a vector of Proxy objects
-pub fn explode_clash(content: &str, nodes: &mut Vec<Proxy>) -> bool {
+///
+/// Returns whether any node was found, or the YAML error of the content with
+/// its line and column.
+pub fn explode_clash(content: &str, nodes: &mut Vec<Proxy>) -> Result<bool, String> {
     // 首先尝试使用新的YAML解析器
     match parse_clash_yaml(content) {
         Ok(mut proxies) => {
             if !proxies.is_empty() {
                 nodes.append(&mut proxies);
-                return true;
+                return Ok(true);
             }
         }
         Err(e) => {
             // 失败时记录错误并尝试旧的解析方式
-            eprintln!("新YAML解析器失败: {}", e);
+            debug!(
+                "Typed Clash parser failed, trying the proxies one by one: {}",
+                e
+            );
         }
     }
 
     // 回退到旧的解析方式
     // Parse the YAML content
-    let yaml: Value = match serde_yaml::from_str(content) {
-        Ok(y) => y,
-        Err(_) => return false,
-    };
+    let yaml: Value = load_clash_yaml(content)?;
 
     // Extract proxies section
     let proxies = match yaml.get("proxies") {
         Some(Value::Sequence(seq)) => seq,
         _ => match yaml.get("Proxy") {
             Some(Value::Sequence(seq)) => seq,
-            _ => return false,
+            _ => return Ok(false),
         },
     };
 
@@ -50,7 +60,7 @@ pub fn explode_clash(content: &str, nodes: &mut Vec<Proxy>) -> bool {
         }
     }
 
-    success
+    Ok(success)
 }
 
 /// Parse a single proxy from Clash YAML
@@ -78,7 +88,7 @@ fn parse_clash_proxy(proxy: &Value) -> Option<Proxy> {
     let skip_cert_verify = proxy.get("skip-cert-verify").and_then(|v| v.as_bool());
 
     // Process based on proxy type
-    match proxy_type.as_str() {
+    let mut node = match proxy_type.as_str() {
         "ss" | "shadowsocks" => {
             parse_clash_ss(proxy, name, server, port, udp, tfo, skip_cert_verify)
         }
@@ -96,7 +106,54 @@ fn parse_clash_proxy(proxy: &Value) -> Option<Proxy> {
         "wireguard" => parse_clash_wireguard(proxy, name, server, port, udp),
         "hysteria" => parse_clash_hysteria(proxy, name, server, port, tfo, skip_cert_verify),
         "hysteria2" => parse_clash_hysteria2(proxy, name, server, port, tfo, skip_cert_verify),
-        _ => None,
+        // Kept verbatim, as by the typed parser
+        _ => passthrough_proxy(proxy),
+    }?;
+    apply_dial_options(proxy, &mut node);
+    Some(node)
+}
+
+/// Parse Clash.Meta client fingerprint, smux, REALITY and ECH options
+fn parse_clash_meta_options(proxy: &Value, node: &mut Proxy) {
+    node.client_fingerprint = proxy
+        .get("client-fingerprint")
+        .and_then(|v| v.as_str())
+        .or_else(|| {
+            // sing-box style `tls: {enabled, utls: {enabled, fingerprint}}`
+            let utls = proxy.get("tls")?.get("utls")?;
+            if utls.get("enabled").and_then(|v| v.as_bool()) == Some(false) {
+                return None;
+            }
+            utls.get("fingerprint").and_then(|v| v.as_str())
+        })
+        .map(|v| v.to_string());
+
+    if let Some(smux) = proxy.get("smux") {
+        node.smux_enabled = Some(smux.get("enabled").and_then(|v| v.as_bool()) == Some(true));
+        node.smux_max_streams = smux
+            .get("max-streams")
+            .and_then(|v| v.as_u64())
+            .map(|v| v as u32);
+    }
+
+    if let Some(reality) = proxy.get("reality-opts") {
+        let field = |key: &str| {
+            reality
+                .get(key)
+                .and_then(|v| v.as_str())
+                .map(str::to_string)
+        };
+        node.reality_public_key = field("public-key");
+        node.reality_short_id = field("short-id");
+    }
+
+    if let Some(ech) = proxy.get("ech-opts") {
+        node.ech_enabled = Some(ech.get("enable").and_then(|v| v.as_bool()) == Some(true));
+        node.ech_config = ech
+            .get("config")
+            .and_then(|v| v.as_str())
+            .filter(|v| !v.is_empty())
+            .map(str::to_string);
     }
 }
 
@@ -125,101 +182,27 @@ fn parse_clash_ss(
         .unwrap_or("");
 
     // Extract plugin information
-    let mut plugin = "";
-    let mut pluginopts_mode = "";
-    let mut pluginopts_host = "";
-    let mut path = "";
-    let mut tls = "";
-    let mut pluginopts_mux = "";
+    let mut plugin = String::new();
     let mut pluginopts = String::new();
 
     // Check if plugin is defined
     if let Some(plugin_val) = proxy.get("plugin").and_then(|v| v.as_str()) {
-        match plugin_val {
-            "obfs" => {
-                plugin = "obfs-local";
-                if let Some(plugin_opts) = proxy.get("plugin-opts").and_then(|v| v.as_mapping()) {
-                    if let Some(mode) = plugin_opts
-                        .get(&Value::String("mode".to_string()))
-                        .and_then(|v| v.as_str())
-                    {
-                        pluginopts_mode = mode;
-                    }
-                    if let Some(host) = plugin_opts
-                        .get(&Value::String("host".to_string()))
-                        .and_then(|v| v.as_str())
-                    {
-                        pluginopts_host = host;
-                    }
-                }
-            }
-            "v2ray-plugin" => {
-                plugin = "v2ray-plugin";
-                if let Some(plugin_opts) = proxy.get("plugin-opts").and_then(|v| v.as_mapping()) {
-                    if let Some(mode) = plugin_opts
-                        .get(&Value::String("mode".to_string()))
-                        .and_then(|v| v.as_str())
-                    {
-                        pluginopts_mode = mode;
-                    }
-                    if let Some(host) = plugin_opts
-                        .get(&Value::String("host".to_string()))
-                        .and_then(|v| v.as_str())
-                    {
-                        pluginopts_host = host;
-                    }
-                    if let Some(plugin_tls) = plugin_opts
-                        .get(&Value::String("tls".to_string()))
-                        .and_then(|v| v.as_bool())
-                    {
-                        tls = if plugin_tls { "tls;" } else { "" };
-                    }
-                    if let Some(plugin_path) = plugin_opts
-                        .get(&Value::String("path".to_string()))
-                        .and_then(|v| v.as_str())
-                    {
-                        path = plugin_path;
-                    }
-                    if let Some(mux) = plugin_opts
-                        .get(&Value::String("mux".to_string()))
-                        .and_then(|v| v.as_bool())
-                    {
-                        pluginopts_mux = if mux { "mux=4;" } else { "" };
-                    }
-                }
-            }
-            _ => {}
-        }
+        let opts = proxy
+            .get("plugin-opts")
+            .and_then(|v| v.as_mapping())
+            .into_iter()
+            .flatten()
+            .filter_map(|(key, value)| Some((key.as_str()?, value)));
+        (plugin, pluginopts) = from_clash_plugin(plugin_val, opts);
     } else if let Some(obfs) = proxy.get("obfs").and_then(|v| v.as_str()) {
         // Legacy support for obfs and obfs-host fields
-        plugin = "obfs-local";
-        pluginopts_mode = obfs;
-        if let Some(obfs_host) = proxy.get("obfs-host").and_then(|v| v.as_str()) {
-            pluginopts_host = obfs_host;
-        }
-    }
-
-    // Format plugin options based on plugin type
-    match plugin {
-        "simple-obfs" | "obfs-local" => {
-            pluginopts = format!("obfs={}", pluginopts_mode);
-            if !pluginopts_host.is_empty() {
-                pluginopts.push_str(&format!(";obfs-host={}", pluginopts_host));
-            }
-        }
-        "v2ray-plugin" => {
-            pluginopts = format!("mode={};{}{}", pluginopts_mode, tls, pluginopts_mux);
-            if !pluginopts_host.is_empty() {
-                pluginopts.push_str(&format!("host={};", pluginopts_host));
-            }
-            if !path.is_empty() {
-                pluginopts.push_str(&format!("path={};", path));
-            }
-            if !pluginopts_mux.is_empty() {
-                pluginopts.push_str(&format!("mux={};", pluginopts_mux));
-            }
-        }
-        _ => {}
+        plugin = "obfs-local".to_string();
+        let obfs_host = proxy.get("obfs-host").and_then(|v| v.as_str());
+        pluginopts = join_plugin_options(
+            [("obfs", obfs)]
+                .into_iter()
+                .chain(obfs_host.map(|host| ("obfs-host", host))),
+        );
     }
 
     // Handle special cipher types (support for go-shadowsocks2)
@@ -230,9 +213,6 @@ fn parse_clash_ss(
         // Not implementing the full C++ transformation for now
     }
 
-    // Convert pluginopts String to &str
-    let pluginopts_str = Box::leak(pluginopts.into_boxed_str());
-
     Some(Proxy::ss_construct(
         SS_DEFAULT_GROUP,
         name,
@@ -240,8 +220,8 @@ fn parse_clash_ss(
         port,
         password,
         cipher,
-        plugin,
-        pluginopts_str,
+        &plugin,
+        &pluginopts,
         udp,
         tfo,
         skip_cert_verify,
@@ -338,7 +318,11 @@ fn parse_clash_vmess(
         .unwrap_or("tcp");
 
     // Get TLS settings
-    let tls = proxy.get("tls").and_then(|v| v.as_bool()).unwrap_or(false);
+    let tls = match proxy.get("tls") {
+        Some(Value::Mapping(tls)) => tls.get("enabled").and_then(|v| v.as_bool()).unwrap_or(true),
+        Some(tls) => tls.as_bool().unwrap_or(false),
+        None => false,
+    };
     let sni = proxy
         .get("servername")
         .and_then(|v| v.as_str())
@@ -361,11 +345,8 @@ fn parse_clash_vmess(
             .get(&Value::String("headers".to_string()))
             .and_then(|v| v.as_mapping())
         {
-            if let Some(host_val) = headers
-                .get(&Value::String("Host".to_string()))
-                .and_then(|v| v.as_str())
-            {
-                host = host_val.to_string();
+            if let Some(host_val) = yaml_strings(headers.get("Host")).into_iter().next() {
+                host = host_val;
             }
         }
     }
@@ -378,39 +359,18 @@ fn parse_clash_vmess(
             path = path_val.to_string();
         }
 
-        if let Some(hosts) = h2_opts
-            .get(&Value::String("host".to_string()))
-            .and_then(|v| v.as_sequence())
-        {
-            if !hosts.is_empty() {
-                if let Some(first_host) = hosts.get(0).and_then(|v| v.as_str()) {
-                    host = first_host.to_string();
-                }
-            }
+        if let Some(first_host) = yaml_strings(h2_opts.get("host")).into_iter().next() {
+            host = first_host;
         }
     }
     // Handle HTTP options
     else if let Some(http_opts) = proxy.get("http-opts").and_then(|v| v.as_mapping()) {
-        if let Some(paths) = http_opts
-            .get(&Value::String("path".to_string()))
-            .and_then(|v| v.as_sequence())
-        {
-            if !paths.is_empty() {
-                if let Some(first_path) = paths.get(0).and_then(|v| v.as_str()) {
-                    path = first_path.to_string();
-                }
-            }
+        if let Some(first_path) = yaml_strings(http_opts.get("path")).into_iter().next() {
+            path = first_path;
         }
 
-        if let Some(hosts) = http_opts
-            .get(&Value::String("host".to_string()))
-            .and_then(|v| v.as_sequence())
-        {
-            if !hosts.is_empty() {
-                if let Some(first_host) = hosts.get(0).and_then(|v| v.as_str()) {
-                    host = first_host.to_string();
-                }
-            }
+        if let Some(first_host) = yaml_strings(http_opts.get("host")).into_iter().next() {
+            host = first_host;
         }
     }
     // Handle gRPC options
@@ -429,7 +389,7 @@ fn parse_clash_vmess(
     // Get edge value
     let edge = "";
 
-    Some(Proxy::vmess_construct(
+    let mut node = Proxy::vmess_construct(
         V2RAY_DEFAULT_GROUP,
         name,
         server,
@@ -449,7 +409,9 @@ fn parse_clash_vmess(
         skip_cert_verify,
         None,
         underlying_proxy,
-    ))
+    );
+    parse_clash_meta_options(proxy, &mut node);
+    Some(node)
 }
 
 /// Parse a SOCKS5 proxy from Clash YAML
@@ -566,17 +528,14 @@ fn parse_clash_trojan(
                 .get(&Value::String("headers".to_string()))
                 .and_then(|v| v.as_mapping())
             {
-                if let Some(host_val) = headers
-                    .get(&Value::String("Host".to_string()))
-                    .and_then(|v| v.as_str())
-                {
-                    host = host_val.to_string();
+                if let Some(host_val) = yaml_strings(headers.get("Host")).into_iter().next() {
+                    host = host_val;
                 }
             }
         }
     }
 
-    Some(Proxy::trojan_construct(
+    let mut node = Proxy::trojan_construct(
         TROJAN_DEFAULT_GROUP.to_string(),
         name.to_string(),
         server.to_string(),
@@ -592,7 +551,9 @@ fn parse_clash_trojan(
         skip_cert_verify,
         None,
         Some(underlying_proxy.to_string()),
-    ))
+    );
+    parse_clash_meta_options(proxy, &mut node);
+    Some(node)
 }
 
 /// Parse a Snell proxy from Clash YAML
@@ -618,15 +579,21 @@ fn parse_clash_snell(
         .and_then(|v| v.as_str())
         .unwrap_or("");
 
-    // Get obfs settings
+    // Get obfs settings, either from `obfs-opts` or the flat legacy keys
     let version = proxy.get("version").and_then(|v| v.as_u64()).unwrap_or(1) as u16;
-    let obfs = proxy.get("obfs").and_then(|v| v.as_str()).unwrap_or("");
-    let obfs_host = proxy
-        .get("obfs-host")
+    let obfs_opts = proxy.get("obfs-opts");
+    let obfs = obfs_opts
+        .and_then(|opts| opts.get("mode"))
+        .or_else(|| proxy.get("obfs"))
+        .and_then(|v| v.as_str())
+        .unwrap_or("");
+    let obfs_host = obfs_opts
+        .and_then(|opts| opts.get("host"))
+        .or_else(|| proxy.get("obfs-host"))
         .and_then(|v| v.as_str())
         .unwrap_or("");
 
-    Some(Proxy::snell_construct(
+    let mut node = Proxy::snell_construct(
         SNELL_DEFAULT_GROUP.to_string(),
         name.to_string(),
         server.to_string(),
@@ -639,7 +606,10 @@ fn parse_clash_snell(
         tfo,
         skip_cert_verify,
         Some(underlying_proxy.to_string()),
-    ))
+    );
+    node.reuse = proxy.get("reuse").and_then(|v| v.as_bool());
+    node.ecn = proxy.get("ecn").and_then(|v| v.as_bool());
+    Some(node)
 }
 
 /// Parse a WireGuard proxy from Clash YAML
@@ -727,6 +697,45 @@ fn parse_clash_wireguard(
     ))
 }
 
+/// Bandwidth in Mbps from a number or a string such as `100 Mbps`
+fn yaml_mbps(value: Option<&Value>) -> u32 {
+    match value {
+        Some(Value::Number(number)) => number.as_u64().unwrap_or(0) as u32,
+        Some(Value::String(text)) => parse_mbps(text),
+        _ => 0,
+    }
+}
+
+/// Read a list written either as a sequence or a single string, kept whole
+/// even with commas, such as a path or a host
+fn yaml_strings(value: Option<&Value>) -> Vec<String> {
+    match value {
+        Some(Value::String(text)) if !text.is_empty() => vec![text.clone()],
+        Some(Value::String(_)) => Vec::new(),
+        value => yaml_comma_list(value),
+    }
+}
+
+/// Read a list written either as a sequence or a comma separated string,
+/// such as `alpn`
+fn yaml_comma_list(value: Option<&Value>) -> Vec<String> {
+    match value {
+        Some(Value::Sequence(items)) => items
+            .iter()
+            .filter_map(|item| item.as_str())
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect(),
+        Some(Value::String(text)) => text
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
 /// Parse a Hysteria proxy from Clash YAML
 fn parse_clash_hysteria(
     proxy: &Value,
@@ -755,26 +764,12 @@ fn parse_clash_hysteria(
     let ports = proxy.get("ports").and_then(|v| v.as_str()).unwrap_or("");
 
     // Get up/down speeds
-    let up_mbps = proxy.get("up").and_then(|v| v.as_u64()).unwrap_or(0);
-    let down_mbps = proxy.get("down").and_then(|v| v.as_u64()).unwrap_or(0);
-    let up_speed = if up_mbps > 0 {
-        Some(up_mbps as u32)
-    } else {
-        None
-    };
-    let down_speed = if down_mbps > 0 {
-        Some(down_mbps as u32)
-    } else {
-        None
-    };
+    let up_speed = Some(yaml_mbps(proxy.get("up"))).filter(|v| *v > 0);
+    let down_speed = Some(yaml_mbps(proxy.get("down"))).filter(|v| *v > 0);
 
     // Get TLS settings
     let sni = proxy.get("sni").and_then(|v| v.as_str()).unwrap_or("");
-    let alpn_value = proxy.get("alpn").and_then(|v| v.as_str()).unwrap_or("");
-    let mut alpn = Vec::new();
-    if !alpn_value.is_empty() {
-        alpn.push(alpn_value.to_string());
-    }
+    let alpn = yaml_comma_list(proxy.get("alpn"));
 
     let fingerprint = proxy
         .get("fingerprint")
@@ -884,29 +879,15 @@ fn parse_clash_hysteria2(
         None => None,
     };
     // Get up/down speeds
-    let up_mbps = match proxy.get("up").and_then(|v| v.as_u64()) {
-        Some(v) => Some(v as u32),
-        None => None,
-    };
-    let down_mbps = match proxy.get("down").and_then(|v| v.as_u64()) {
-        Some(v) => Some(v as u32),
-        None => None,
-    };
+    let up_mbps = Some(yaml_mbps(proxy.get("up"))).filter(|v| *v > 0);
+    let down_mbps = Some(yaml_mbps(proxy.get("down"))).filter(|v| *v > 0);
 
     // Get TLS settings
     let sni = match proxy.get("sni").and_then(|v| v.as_str()) {
         Some(v) => Some(v.to_owned()),
         None => None,
     };
-    let alpn = proxy
-        .get("alpn")
-        .and_then(|v| v.as_sequence())
-        .map(|v| {
-            v.iter()
-                .map(|v| v.as_str().unwrap_or("").to_owned())
-                .collect()
-        })
-        .unwrap_or_default();
+    let alpn = yaml_comma_list(proxy.get("alpn"));
 
     let fingerprint = match proxy.get("fingerprint").and_then(|v| v.as_str()) {
         Some(v) => Some(v.to_owned()),
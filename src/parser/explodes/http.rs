@@ -1,5 +1,5 @@
 use crate::models::{Proxy, HTTP_DEFAULT_GROUP};
-use crate::utils::url::url_decode;
+use crate::utils::url::decode_query_pairs;
 use url::Url;
 
 /// Parse an HTTP/HTTPS link into a Proxy object
@@ -27,14 +27,14 @@ pub fn explode_http(link: &str, node: &mut Proxy) -> bool {
     let mut group = String::new();
 
     // Parse query parameters
-    for (key, value) in url.query_pairs() {
-        match key.as_ref() {
-            "server" => server = url_decode(&value),
-            "port" => port = url_decode(&value),
-            "user" => username = url_decode(&value),
-            "pass" => password = url_decode(&value),
-            "remarks" => remarks = url_decode(&value),
-            "group" => group = url_decode(&value),
+    for (key, value) in decode_query_pairs(url.query().unwrap_or("")) {
+        match key.as_str() {
+            "server" => server = value,
+            "port" => port = value,
+            "user" => username = value,
+            "pass" => password = value,
+            "remarks" => remarks = value,
+            "group" => group = value,
             _ => {}
         }
     }
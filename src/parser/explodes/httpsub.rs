@@ -1,4 +1,4 @@
-use crate::utils::url::url_decode;
+use crate::utils::url::decode_query_pairs;
 use crate::{models::HTTP_DEFAULT_GROUP, Proxy};
 use url::Url;
 
@@ -23,10 +23,10 @@ pub fn explode_http_sub(link: &str, node: &mut Proxy) -> bool {
     let mut password = String::new();
 
     // Extract query parameters
-    for (key, value) in url.query_pairs() {
-        match key.as_ref() {
-            "remarks" => remarks = url_decode(&value),
-            "group" => group = url_decode(&value),
+    for (key, value) in decode_query_pairs(url.query().unwrap_or("")) {
+        match key.as_str() {
+            "remarks" => remarks = value,
+            "group" => group = value,
             _ => {}
         }
     }
@@ -1,4 +1,8 @@
-use crate::{models::HYSTERIA_DEFAULT_GROUP, utils::url_decode, Proxy};
+use crate::{
+    models::HYSTERIA_DEFAULT_GROUP,
+    utils::{decode_component, decode_query_pairs},
+    Proxy,
+};
 use std::collections::HashMap;
 use url::Url;
 
@@ -23,10 +27,7 @@ pub fn explode_hysteria(hysteria: &str, node: &mut Proxy) -> bool {
     let port = url.port().unwrap_or(443);
 
     // Extract parameters from the query string
-    let mut params = HashMap::new();
-    for (key, value) in url.query_pairs() {
-        params.insert(key.to_string(), url_decode(&value));
-    }
+    let params: HashMap<String, String> = decode_query_pairs(url.query().unwrap_or("")).collect();
 
     // Extract auth string
     let auth = params.get("auth").map(|s| s.as_str()).unwrap_or("");
@@ -63,11 +64,11 @@ pub fn explode_hysteria(hysteria: &str, node: &mut Proxy) -> bool {
         .unwrap_or(false);
 
     // Extract remark from the fragment
-    let remark = url.fragment().unwrap_or("");
+    let remark = decode_component(url.fragment().unwrap_or(""));
     let formatted_remark = if remark.is_empty() {
         format!("{} ({})", host, port)
     } else {
-        remark.to_string()
+        remark
     };
 
     // Create the proxy object using the hysteria_construct method
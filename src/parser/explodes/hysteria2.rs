@@ -1,4 +1,8 @@
-use crate::{models::HYSTERIA2_DEFAULT_GROUP, utils::url_decode, Proxy};
+use crate::{
+    models::HYSTERIA2_DEFAULT_GROUP,
+    utils::{decode_component, decode_query_pairs},
+    Proxy,
+};
 use url::Url;
 
 /// Parse a Hysteria2 link into a Proxy object
@@ -19,7 +23,7 @@ pub fn explode_hysteria2(hysteria2: &str, node: &mut Proxy) -> bool {
     let port = url.port().unwrap_or(443);
 
     // Extract password (username in URL)
-    let password = url.username();
+    let password = decode_component(url.username());
 
     // Extract parameters from the query string
     let mut up_speed = None;
@@ -35,25 +39,22 @@ pub fn explode_hysteria2(hysteria2: &str, node: &mut Proxy) -> bool {
     let mut ports = String::new();
     let mut alpn = Vec::new();
 
-    for (key, value) in url.query_pairs() {
-        match key.as_ref() {
+    for (key, value) in decode_query_pairs(url.query().unwrap_or("")) {
+        match key.as_str() {
             "up" => up_speed = value.parse::<u32>().ok(),
             "down" => down_speed = value.parse::<u32>().ok(),
-            "obfs" => obfs = url_decode(&value),
-            "obfs-password" => obfs_param = url_decode(&value),
-            "sni" => sni = url_decode(&value),
-            "insecure" => {
-                allow_insecure =
-                    Some(value.as_ref() == "1" || value.as_ref().to_lowercase() == "true")
-            }
-            "fingerprint" => fingerprint = url_decode(&value),
-            "ca" => ca = url_decode(&value),
-            "caStr" => ca_str = url_decode(&value),
-            "ports" => ports = url_decode(&value),
-            "mport" => ports = url_decode(&value),
+            "obfs" => obfs = value,
+            "obfs-password" => obfs_param = value,
+            "sni" => sni = value,
+            "insecure" => allow_insecure = Some(value == "1" || value.to_lowercase() == "true"),
+            "fingerprint" => fingerprint = value,
+            "ca" => ca = value,
+            "caStr" => ca_str = value,
+            "ports" => ports = value,
+            "mport" => ports = value,
             "cwnd" => cwnd = value.parse::<u32>().ok(),
             "alpn" => {
-                for a in url_decode(&value).split(',') {
+                for a in value.split(',') {
                     alpn.push(a.to_string());
                 }
             }
@@ -62,7 +63,7 @@ pub fn explode_hysteria2(hysteria2: &str, node: &mut Proxy) -> bool {
     }
 
     // Extract remark from the fragment
-    let remark = url_decode(url.fragment().unwrap_or(""));
+    let remark = decode_component(url.fragment().unwrap_or(""));
 
     // Create formatted strings
     let remark_str = if remark.is_empty() {
@@ -115,7 +116,7 @@ pub fn explode_std_hysteria2(hysteria2: &str, node: &mut Proxy) -> bool {
     let port = url.port().unwrap_or(443);
 
     // Extract password (username in URL)
-    let password = url.username();
+    let password = decode_component(url.username());
 
     // Extract parameters from the query string
     let mut up_speed = None;
@@ -131,11 +132,10 @@ pub fn explode_std_hysteria2(hysteria2: &str, node: &mut Proxy) -> bool {
     let mut ports = String::new();
     let mut alpn = Vec::new();
 
-    for (key, value) in url.query_pairs() {
-        let value_decoded = url_decode(&value);
-        match key.as_ref() {
+    for (key, value) in decode_query_pairs(url.query().unwrap_or("")) {
+        match key.as_str() {
             "bandwidth" => {
-                let parts: Vec<&str> = value_decoded.split(',').collect();
+                let parts: Vec<&str> = value.split(',').collect();
                 if parts.len() >= 1 {
                     up_speed = parts[0].parse::<u32>().ok();
                 }
@@ -143,19 +143,16 @@ pub fn explode_std_hysteria2(hysteria2: &str, node: &mut Proxy) -> bool {
                     down_speed = parts[1].parse::<u32>().ok();
                 }
             }
-            "obfs" => obfs = value_decoded,
-            "obfs-password" => obfs_param = value_decoded,
-            "sni" => sni = value_decoded,
-            "insecure" => {
-                allow_insecure =
-                    Some(value.as_ref() == "1" || value.as_ref().to_lowercase() == "true")
-            }
-            "pinSHA256" => fingerprint = value_decoded,
-            "ca" => ca = value_decoded,
-            "ports" => ports = value_decoded,
+            "obfs" => obfs = value,
+            "obfs-password" => obfs_param = value,
+            "sni" => sni = value,
+            "insecure" => allow_insecure = Some(value == "1" || value.to_lowercase() == "true"),
+            "pinSHA256" => fingerprint = value,
+            "ca" => ca = value,
+            "ports" => ports = value,
             "cwnd" => cwnd = value.parse::<u32>().ok(),
             "alpn" => {
-                for a in value_decoded.split(',') {
+                for a in value.split(',') {
                     alpn.push(a.to_string());
                 }
             }
@@ -164,7 +161,7 @@ pub fn explode_std_hysteria2(hysteria2: &str, node: &mut Proxy) -> bool {
     }
 
     // Extract remark from the fragment
-    let remark = url_decode(url.fragment().unwrap_or(""));
+    let remark = decode_component(url.fragment().unwrap_or(""));
 
     // Create formatted strings
     let remark_str = if remark.is_empty() {
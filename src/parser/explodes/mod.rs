@@ -17,7 +17,10 @@ mod vless;
 mod vmess;
 mod wireguard;
 
-pub use common::{explode, explode_conf_content, explode_sub};
+pub use common::{
+    explode, explode_any, explode_any_with_limits, explode_conf_content, explode_sub,
+    ContentLimits, ParseError,
+};
 pub use explode_clash::explode_clash;
 pub use http::explode_http;
 pub use httpsub::explode_http_sub;
@@ -27,10 +30,10 @@ pub use netch::{explode_netch, explode_netch_conf};
 pub use quan::explode_quan;
 pub use snell::{explode_snell, explode_snell_surge};
 pub use socks::explode_socks;
-pub use ss::{explode_ss, explode_ss_android, explode_ss_conf, explode_ssd};
+pub use ss::{explode_sip008, explode_ss, explode_ss_android, explode_ss_conf, explode_ssd};
 pub use ssr::{explode_ssr, explode_ssr_conf};
 pub use sstap::explode_sstap;
-pub use surge::explode_surge;
+pub use surge::{explode_surge, explode_surge_groups};
 pub use trojan::{explode_trojan, explode_trojan_go};
 pub use vless::explode_vless;
 pub use vmess::{
@@ -88,7 +88,7 @@ fn parse_quan_ss(name: &str, config_parts: Vec<&str>, node: &mut Proxy) -> bool
     // Parse additional options
     for i in 5..config_parts.len() {
         if config_parts[i].starts_with("obfs=") {
-            plugin = "obfs";
+            plugin = "obfs-local";
 
             let obfs_parts: Vec<&str> = config_parts[i][5..].split(',').collect();
             if !obfs_parts.is_empty() {
@@ -1,6 +1,6 @@
 use crate::{
     models::{Proxy, SNELL_DEFAULT_GROUP},
-    utils::url_decode,
+    utils::{decode_component, decode_query_pairs},
 };
 use std::collections::HashMap;
 use url::Url;
@@ -35,10 +35,7 @@ pub fn explode_snell(snell: &str, node: &mut Proxy) -> bool {
     }
 
     // Extract parameters from the query string
-    let mut params = HashMap::new();
-    for (key, value) in url.query_pairs() {
-        params.insert(key.to_string(), url_decode(&value));
-    }
+    let params: HashMap<String, String> = decode_query_pairs(url.query().unwrap_or("")).collect();
 
     // Extract obfs
     let obfs = params.get("obfs").map(|s| s.as_str()).unwrap_or("none");
@@ -60,7 +57,7 @@ pub fn explode_snell(snell: &str, node: &mut Proxy) -> bool {
         .map(|s| s == "true" || s == "1");
 
     // Extract remark from the fragment
-    let remark = url_decode(url.fragment().unwrap_or(""));
+    let remark = decode_component(url.fragment().unwrap_or(""));
     let formatted_remark = if remark.is_empty() {
         format!("{} ({})", host, port)
     } else {
@@ -1,5 +1,6 @@
 use crate::models::{Proxy, SOCKS_DEFAULT_GROUP};
 use crate::utils::base64::url_safe_base64_decode;
+use crate::utils::url::decode_query_pairs;
 use std::collections::HashMap;
 use url::Url;
 
@@ -114,10 +115,8 @@ fn parse_telegram_socks(link: &str, node: &mut Proxy) -> bool {
     };
 
     // Extract query parameters
-    let query_pairs: HashMap<String, String> = url
-        .query_pairs()
-        .map(|(k, v)| (k.to_string(), v.to_string()))
-        .collect();
+    let query_pairs: HashMap<String, String> =
+        decode_query_pairs(url.query().unwrap_or("")).collect();
 
     // Get required parameters
     let server = match query_pairs.get("server") {
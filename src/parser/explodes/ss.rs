@@ -1,5 +1,7 @@
 use crate::models::{Proxy, SS_DEFAULT_GROUP};
-use crate::utils::url::url_decode;
+use crate::utils::base64::decode_lenient;
+use crate::utils::network::split_host_port;
+use crate::utils::url::{decode_component, decode_query_pairs};
 use base64::engine::general_purpose::STANDARD;
 use base64::Engine;
 use serde_json::Value;
@@ -20,7 +22,7 @@ pub fn explode_ss(ss: &str, node: &mut Proxy) -> bool {
     // Extract fragment (remark) if present
     let mut ps = String::new();
     if let Some(hash_pos) = ss_content.find('#') {
-        ps = url_decode(&ss_content[hash_pos + 1..]);
+        ps = decode_component(&ss_content[hash_pos + 1..]);
         ss_content = ss_content[..hash_pos].to_string();
     }
 
@@ -34,9 +36,9 @@ pub fn explode_ss(ss: &str, node: &mut Proxy) -> bool {
         ss_content = ss_content[..query_pos].to_string();
 
         // Parse query parameters
-        for (key, value) in url::form_urlencoded::parse(addition.as_bytes()) {
+        for (key, value) in decode_query_pairs(&addition) {
             if key == "plugin" {
-                let plugins = url_decode(&value);
+                let plugins = value;
                 if let Some(semicolon_pos) = plugins.find(';') {
                     plugin = plugins[..semicolon_pos].to_string();
                     plugin_opts = plugins[semicolon_pos + 1..].to_string();
@@ -45,7 +47,9 @@ pub fn explode_ss(ss: &str, node: &mut Proxy) -> bool {
                 }
             } else if key == "group" {
                 if !value.is_empty() {
-                    group = crate::utils::base64::url_safe_base64_decode(&value);
+                    group = decode_lenient(&value)
+                        .map(|(decoded, _)| decoded)
+                        .unwrap_or_default();
                 }
             }
         }
@@ -68,18 +72,18 @@ pub fn explode_ss(ss: &str, node: &mut Proxy) -> bool {
         let server_port = parts[1];
 
         // Parse server and port
-        let server_port_parts: Vec<&str> = server_port.split(':').collect();
-        if server_port_parts.len() < 2 {
-            return false;
+        match split_host_port(server_port) {
+            Some((host, host_port)) => {
+                server = host;
+                port = host_port;
+            }
+            None => return false,
         }
-        server = server_port_parts[0].to_string();
-        port = match server_port_parts[1].parse::<u16>() {
-            Ok(p) => p,
-            Err(_) => return false,
-        };
 
         // Decode the secret part
-        let decoded_secret = crate::utils::base64::url_safe_base64_decode(secret);
+        let decoded_secret = decode_lenient(secret)
+            .map(|(decoded, _)| decoded)
+            .unwrap_or_default();
         let method_pass: Vec<&str> = decoded_secret.split(':').collect();
         if method_pass.len() < 2 {
             return false;
@@ -88,10 +92,10 @@ pub fn explode_ss(ss: &str, node: &mut Proxy) -> bool {
         password = method_pass[1..].join(":"); // In case password contains colons
     } else {
         // Legacy format
-        let decoded = crate::utils::base64::url_safe_base64_decode(&ss_content);
-        if decoded.is_empty() {
-            return false;
-        }
+        let decoded = match decode_lenient(&ss_content) {
+            Some((decoded, _)) => decoded,
+            None => return false,
+        };
 
         // Parse method:password@server:port
         let parts: Vec<&str> = decoded.split('@').collect();
@@ -111,15 +115,13 @@ pub fn explode_ss(ss: &str, node: &mut Proxy) -> bool {
         password = method_pass_parts[1..].join(":"); // In case password contains colons
 
         // Parse server and port
-        let server_port_parts: Vec<&str> = server_port.split(':').collect();
-        if server_port_parts.len() < 2 {
-            return false;
+        match split_host_port(server_port) {
+            Some((host, host_port)) => {
+                server = host;
+                port = host_port;
+            }
+            None => return false,
         }
-        server = server_port_parts[0].to_string();
-        port = match server_port_parts[1].parse::<u16>() {
-            Ok(p) => p,
-            Err(_) => return false,
-        };
     }
 
     // Skip if port is 0
@@ -152,6 +154,69 @@ pub fn explode_ss(ss: &str, node: &mut Proxy) -> bool {
     true
 }
 
+/// Whether the JSON is a SIP008 online config, `version` 1 and a server list
+fn is_sip008(json: &Value) -> bool {
+    json["version"].as_u64() == Some(1) && json["servers"].is_array()
+}
+
+/// Parse a SIP008 online config into a vector of Proxy objects
+///
+/// The `id` of each server is kept as the source id of its node. Servers
+/// without address, port or method are skipped.
+pub fn explode_sip008(content: &str, nodes: &mut Vec<Proxy>) -> bool {
+    let json: Value = match serde_json::from_str(content) {
+        Ok(json) => json,
+        Err(_) => return false,
+    };
+    if !is_sip008(&json) {
+        return false;
+    }
+
+    let orig_size = nodes.len();
+    for server_json in json["servers"].as_array().into_iter().flatten() {
+        let server = server_json["server"].as_str().unwrap_or("");
+        let port_num = server_json["server_port"]
+            .as_u64()
+            .and_then(|port| u16::try_from(port).ok())
+            .unwrap_or(0);
+        let method = server_json["method"].as_str().unwrap_or("");
+        if server.is_empty() || port_num == 0 || method.is_empty() {
+            continue;
+        }
+        let password = server_json["password"].as_str().unwrap_or("");
+        let remark = match server_json["remarks"].as_str() {
+            Some(remarks) if !remarks.is_empty() => remarks.to_string(),
+            _ => format!("{} ({})", server, port_num),
+        };
+        let plugin = server_json["plugin"].as_str().unwrap_or("");
+        let plugin_opts = server_json["plugin_opts"].as_str().unwrap_or("");
+
+        let mut node = Proxy::ss_construct(
+            SS_DEFAULT_GROUP,
+            &remark,
+            server,
+            port_num,
+            password,
+            method,
+            plugin,
+            plugin_opts,
+            None,
+            None,
+            None,
+            None,
+            "",
+        );
+        node.id = nodes.len() as u32;
+        node.source_id = server_json["id"]
+            .as_str()
+            .filter(|id| !id.is_empty())
+            .map(str::to_string);
+        nodes.push(node);
+    }
+
+    nodes.len() > orig_size
+}
+
 /// Parse a SSD (Shadowsocks subscription) link into a vector of Proxy objects
 pub fn explode_ssd(link: &str, nodes: &mut Vec<Proxy>) -> bool {
     // Check if the link starts with ssd://
@@ -179,6 +244,12 @@ pub fn explode_ssd(link: &str, nodes: &mut Vec<Proxy>) -> bool {
 
     // Extract common fields
     let airport = json["airport"].as_str().unwrap_or("");
+    // The airport name is the group of the nodes
+    let group = if airport.is_empty() {
+        SS_DEFAULT_GROUP
+    } else {
+        airport
+    };
     let port = json["port"].as_u64().unwrap_or(0) as u16;
     let encryption = json["encryption"].as_str().unwrap_or("");
     let password = json["password"].as_str().unwrap_or("");
@@ -204,7 +275,7 @@ pub fn explode_ssd(link: &str, nodes: &mut Vec<Proxy>) -> bool {
 
         // Create the proxy object
         let node = Proxy::ss_construct(
-            SS_DEFAULT_GROUP,
+            group,
             &formatted_remark,
             server_host,
             server_port,
@@ -315,6 +386,9 @@ pub fn explode_ss_conf(content: &str, nodes: &mut Vec<Proxy>) -> bool {
     };
 
     // Check for different configuration formats
+    if is_sip008(&json) {
+        return explode_sip008(content, nodes);
+    }
     if json["configs"].is_array() || json["proxies"].is_array() {
         return explode_ss_android(content, nodes);
     }
@@ -421,3 +495,72 @@ pub fn explode_ss_conf(content: &str, nodes: &mut Vec<Proxy>) -> bool {
 
     false
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::infoparser::get_sub_info_from_sip008;
+
+    /// The example document of the SIP008 specification
+    const SIP008_EXAMPLE: &str = r#"{
+    "version": 1,
+    "servers": [
+        {
+            "id": "27b8a625-4f4b-4428-9f0f-8a2317db7c79",
+            "remarks": "Name of the server",
+            "server": "example.com",
+            "server_port": 8388,
+            "password": "example",
+            "method": "chacha20-ietf-poly1305",
+            "plugin": "xxx",
+            "plugin_opts": "xxxxx"
+        },
+        {
+            "id": "7842c068-c667-41f2-8f7d-04feece3cb67",
+            "remarks": "Name of the server",
+            "server": "example.com",
+            "server_port": 8388,
+            "password": "example",
+            "method": "chacha20-ietf-poly1305",
+            "plugin": "xxx",
+            "plugin_opts": "xxxxx"
+        }
+    ],
+    "bytes_used": 274877906944,
+    "bytes_remaining": 824633720832
+}"#;
+
+    #[test]
+    fn test_sip008_example() {
+        let mut nodes = Vec::new();
+        assert!(explode_ss_conf(SIP008_EXAMPLE, &mut nodes));
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[0].remark, "Name of the server");
+        assert_eq!(nodes[0].hostname, "example.com");
+        assert_eq!(nodes[0].port, 8388);
+        assert_eq!(nodes[0].password.as_deref(), Some("example"));
+        assert_eq!(
+            nodes[0].encrypt_method.as_deref(),
+            Some("chacha20-ietf-poly1305")
+        );
+        assert_eq!(nodes[0].plugin.as_deref(), Some("xxx"));
+        // Same remark, told apart by their ids
+        assert_eq!(
+            nodes[0].source_id.as_deref(),
+            Some("27b8a625-4f4b-4428-9f0f-8a2317db7c79")
+        );
+        assert_eq!(
+            nodes[1].source_id.as_deref(),
+            Some("7842c068-c667-41f2-8f7d-04feece3cb67")
+        );
+
+        assert_eq!(
+            get_sub_info_from_sip008(SIP008_EXAMPLE).as_deref(),
+            Some("upload=0; download=274877906944; total=1099511627776;")
+        );
+        assert_eq!(
+            get_sub_info_from_sip008(r#"{"version":1,"servers":[]}"#),
+            None
+        );
+    }
+}
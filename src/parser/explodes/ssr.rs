@@ -1,7 +1,7 @@
 use crate::models::{Proxy, SSR_DEFAULT_GROUP, SS_CIPHERS};
-use crate::utils::base64::{base64_decode, url_safe_base64_decode};
+use crate::utils::base64::decode_lenient;
+use crate::utils::url::decode_query_pairs;
 use serde_json::Value;
-use url::Url;
 
 /// Parse a ShadowsocksR link into a Proxy object
 /// Based on the C++ implementation in explodeSSR function
@@ -15,10 +15,10 @@ pub fn explode_ssr(ssr: &str, node: &mut Proxy) -> bool {
     let encoded = &ssr[6..];
 
     // Decode base64
-    let mut decoded = base64_decode(encoded, true);
-    if decoded.is_empty() {
-        return false;
-    }
+    let mut decoded = match decode_lenient(encoded) {
+        Some((decoded, _)) => decoded,
+        None => return false,
+    };
 
     // Replace \r with empty string
     decoded = decoded.replace('\r', "");
@@ -34,19 +34,18 @@ pub fn explode_ssr(ssr: &str, node: &mut Proxy) -> bool {
         _strobfs = decoded[query_pos + 2..].to_string();
         decoded = decoded[..query_pos].to_string();
 
-        // Parse query parameters
-        let url_str = format!("http://localhost/?{}", _strobfs);
-        if let Ok(url) = Url::parse(&url_str) {
-            for (key, value) in url.query_pairs() {
-                let decoded_value = url_safe_base64_decode(&value);
-
-                match key.as_ref() {
-                    "group" => group = decoded_value,
-                    "remarks" => remarks = decoded_value,
-                    "obfsparam" => obfsparam = decoded_value.replace(" ", ""),
-                    "protoparam" => protoparam = decoded_value.replace(" ", ""),
-                    _ => {}
-                }
+        // Parse query parameters, a `+` of the standard base64 alphabet is kept
+        for (key, value) in decode_query_pairs(&_strobfs) {
+            let decoded_value = decode_lenient(&value)
+                .map(|(decoded, _)| decoded)
+                .unwrap_or_default();
+
+            match key.as_str() {
+                "group" => group = decoded_value,
+                "remarks" => remarks = decoded_value,
+                "obfsparam" => obfsparam = decoded_value.replace(" ", ""),
+                "protoparam" => protoparam = decoded_value.replace(" ", ""),
+                _ => {}
             }
         }
     }
@@ -65,7 +64,9 @@ pub fn explode_ssr(ssr: &str, node: &mut Proxy) -> bool {
     let password_encoded = parts[5];
 
     // Decode password (base64 encoded)
-    let password = url_safe_base64_decode(password_encoded);
+    let password = decode_lenient(password_encoded)
+        .map(|(decoded, _)| decoded)
+        .unwrap_or_default();
 
     // Parse port
     let port = match port_str.parse::<u16>() {
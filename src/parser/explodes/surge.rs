@@ -1,7 +1,9 @@
 use crate::models::{
-    Proxy, HTTP_DEFAULT_GROUP, SNELL_DEFAULT_GROUP, SOCKS_DEFAULT_GROUP, SS_DEFAULT_GROUP,
-    TROJAN_DEFAULT_GROUP, V2RAY_DEFAULT_GROUP,
+    Proxy, ProxyGroupConfig, ProxyGroupType, SourceProxyGroups, HTTP_DEFAULT_GROUP,
+    SNELL_DEFAULT_GROUP, SOCKS_DEFAULT_GROUP, SS_DEFAULT_GROUP, TROJAN_DEFAULT_GROUP,
+    V2RAY_DEFAULT_GROUP,
 };
+use log::warn;
 
 /// Parse a Surge configuration into a vector of Proxy objects
 pub fn explode_surge(content: &str, nodes: &mut Vec<Proxy>) -> bool {
@@ -94,6 +96,79 @@ pub fn explode_surge(content: &str, nodes: &mut Vec<Proxy>) -> bool {
     success
 }
 
+/// Parse the `[Proxy Group]` section of a Surge configuration
+///
+/// Select, url-test, fallback and load-balance groups are read, other group
+/// types are skipped. Members are kept as exact `[]name` references, a
+/// `policy-path` becomes a provider named after its group.
+pub fn explode_surge_groups(content: &str) -> SourceProxyGroups {
+    let mut source = SourceProxyGroups::default();
+    let mut in_group_section = false;
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with("//") {
+            continue;
+        }
+        if line.starts_with('[') && line.ends_with(']') {
+            in_group_section = line == "[Proxy Group]";
+            continue;
+        }
+        if !in_group_section {
+            continue;
+        }
+
+        let Some((name, config)) = line.split_once('=') else {
+            continue;
+        };
+        let name = name.trim();
+        let mut args = config.split(',').map(str::trim);
+        let group_type = match args.next().unwrap_or("") {
+            "select" => ProxyGroupType::Select,
+            "url-test" => ProxyGroupType::URLTest,
+            "fallback" => ProxyGroupType::Fallback,
+            "load-balance" => ProxyGroupType::LoadBalance,
+            other => {
+                warn!("Skipping Surge proxy group '{}' of type '{}'", name, other);
+                continue;
+            }
+        };
+
+        let mut group = ProxyGroupConfig::new(name.to_string(), group_type);
+        for arg in args.filter(|arg| !arg.is_empty()) {
+            let Some((key, value)) = arg.split_once('=') else {
+                group.proxies.push(format!("[]{}", arg));
+                continue;
+            };
+            let value = value.trim();
+            match key.trim() {
+                "url" => group.url = value.to_string(),
+                "interval" => group.interval = value.parse().unwrap_or(0),
+                "timeout" => group.timeout = value.parse().unwrap_or(0),
+                "tolerance" => group.tolerance = value.parse().unwrap_or(0),
+                "persistent" => group.persistent = value == "true" || value == "1",
+                "evaluate-before-use" => {
+                    group.evaluate_before_use = value == "true" || value == "1"
+                }
+                "policy-regex-filter" => group.include_filter = Some(value.to_string()),
+                "include-all-proxies" if value == "true" || value == "1" => {
+                    group.proxies.push(".*".to_string())
+                }
+                "policy-path" => {
+                    group.using_provider.push(name.to_string());
+                    source
+                        .provider_urls
+                        .insert(name.to_string(), value.to_string());
+                }
+                _ => {}
+            }
+        }
+        source.groups.push(group);
+    }
+
+    source
+}
+
 /// Parse a Surge 2 custom Shadowsocks configuration line
 fn parse_surge_custom_ss(config: &str, name: &str, node: &mut Proxy) -> bool {
     // Split the configuration into parts
@@ -667,6 +742,8 @@ fn parse_surge_snell(config: &str, name: &str, node: &mut Proxy) -> bool {
     let mut udp = None;
     let mut tfo = None;
     let mut scv = None;
+    let mut reuse = None;
+    let mut ecn = None;
 
     // Parse additional parameters
     for i in 3..parts.len() {
@@ -686,6 +763,8 @@ fn parse_surge_snell(config: &str, name: &str, node: &mut Proxy) -> bool {
             "tfo" => tfo = Some(value == "true" || value == "1"),
             "skip-cert-verify" => scv = Some(value == "true" || value == "1"),
             "version" => version = value.to_string(),
+            "reuse" => reuse = Some(value == "true" || value == "1"),
+            "ecn" => ecn = Some(value == "true" || value == "1"),
             _ => {}
         }
     }
@@ -709,6 +788,8 @@ fn parse_surge_snell(config: &str, name: &str, node: &mut Proxy) -> bool {
         scv,
         None,
     );
+    node.reuse = reuse;
+    node.ecn = ecn;
 
     true
 }
@@ -1,29 +1,57 @@
-use crate::{models::TROJAN_DEFAULT_GROUP, utils::url_decode, Proxy};
+use crate::{
+    models::TROJAN_DEFAULT_GROUP,
+    utils::{decode_component, decode_query_pairs, network::unbracket_host},
+    Proxy,
+};
+use log::warn;
 use std::collections::HashMap;
 use url::Url;
 
 /// Parse a Trojan link into a Proxy object
+///
+/// Format: `trojan://password@host:port?sni=xxx&allowInsecure=1&type=ws&path=/x&host=h#name`,
+/// `type=grpc` takes the service name from `serviceName`.
 pub fn explode_trojan(trojan: &str, node: &mut Proxy) -> bool {
     // Check if the link starts with trojan://
     if !trojan.starts_with("trojan://") {
         return false;
     }
+    explode_trojan_url(trojan, node)
+}
+
+/// Parse a Trojan-Go link into a Proxy object
+///
+/// Besides the Trojan parameters, trojan-go links may carry `ws=1` with
+/// `wsPath` and an `encryption` layer. Only `encryption=none` can be
+/// represented, links with a Shadowsocks layer are rejected.
+pub fn explode_trojan_go(trojan_go: &str, node: &mut Proxy) -> bool {
+    // Check if the link starts with trojan-go://
+    if !trojan_go.starts_with("trojan-go://") {
+        return false;
+    }
+    explode_trojan_url(trojan_go, node)
+}
 
+fn is_enabled(value: Option<&String>) -> Option<bool> {
+    value.map(|s| s == "1" || s.eq_ignore_ascii_case("true"))
+}
+
+fn explode_trojan_url(link: &str, node: &mut Proxy) -> bool {
     // Try to parse as URL
-    let url = match Url::parse(trojan) {
+    let url = match Url::parse(link) {
         Ok(url) => url,
         Err(_) => return false,
     };
 
     // Extract password
-    let password = url.username();
+    let password = decode_component(url.username());
     if password.is_empty() {
         return false;
     }
 
     // Extract host and port
     let host = match url.host_str() {
-        Some(host) => host,
+        Some(host) => unbracket_host(host).to_string(),
         None => return false,
     };
     let port = url.port().unwrap_or(443);
@@ -33,70 +61,63 @@ pub fn explode_trojan(trojan: &str, node: &mut Proxy) -> bool {
         return false;
     }
 
-    // Extract parameters from the query string
-    let mut params = HashMap::new();
-    for (key, value) in url.query_pairs() {
-        params.insert(key.to_string(), url_decode(&value));
+    // Extract parameters from the query string, a `+` is kept as it is
+    let params: HashMap<String, String> = decode_query_pairs(url.query().unwrap_or("")).collect();
+    let param = |key: &str| params.get(key).filter(|s| !s.is_empty()).cloned();
+
+    if let Some(encryption) = param("encryption") {
+        if encryption != "none" {
+            warn!(
+                "Skipping trojan-go node {}:{}, encryption '{}' is not supported",
+                host, port, encryption
+            );
+            return false;
+        }
     }
 
     // Extract SNI - check for both "sni" and "peer" parameters (like in C++)
-    let sni = params
-        .get("sni")
-        .map(|s| s.to_string())
-        .or_else(|| params.get("peer").map(|s| s.to_string()));
+    let sni = param("sni").or_else(|| param("peer"));
 
     // Extract TLS verification setting
-    let skip_cert_verify = params
-        .get("allowInsecure")
-        .map(|s| s == "1" || s.to_lowercase() == "true");
+    let skip_cert_verify = is_enabled(params.get("allowInsecure"));
 
     // Extract TCP Fast Open setting
-    let tfo = params
-        .get("tfo")
-        .map(|s| s == "1" || s.to_lowercase() == "true");
+    let tfo = is_enabled(params.get("tfo"));
 
     // Extract group parameter
-    let group = params
-        .get("group")
-        .map(|s| url_decode(s))
-        .unwrap_or_else(|| TROJAN_DEFAULT_GROUP.to_string());
-
-    // Handle WebSocket support
-    let mut network = None;
-    let mut path = None;
+    let group = param("group").unwrap_or_else(|| TROJAN_DEFAULT_GROUP.to_string());
 
-    if params.get("ws").map(|s| s == "1").unwrap_or(false) {
+    // Transport, `ws=1` is the trojan-go way of enabling WebSocket
+    let mut network = param("type").map(|s| s.to_lowercase());
+    if is_enabled(params.get("ws")) == Some(true) {
         network = Some("ws".to_string());
-        path = params.get("wspath").map(|s| s.to_string());
-    } else if params.get("type").map(|s| s == "ws").unwrap_or(false) {
-        network = Some("ws".to_string());
-        if let Some(p) = params.get("path") {
-            let p_str = p.to_string();
-            if p_str.starts_with("%2F") {
-                path = Some(url_decode(&p_str));
-            } else {
-                path = Some(p_str);
-            }
-        }
     }
+    let path = match network.as_deref() {
+        Some("ws") => param("path")
+            .or_else(|| param("wsPath"))
+            .or_else(|| param("wspath")),
+        Some("grpc") => param("serviceName").or_else(|| param("path")),
+        _ => param("path"),
+    };
+    let host_header = param("host").or_else(|| sni.clone());
 
     // Extract remark from the fragment
-    let remark = url_decode(&url.fragment().unwrap_or(""));
+    let remark = decode_component(url.fragment().unwrap_or(""));
     let formatted_remark = if remark.is_empty() {
         format!("{} ({})", host, port)
     } else {
-        remark.to_string()
+        remark
     };
 
     // Create the proxy object
     *node = Proxy::trojan_construct(
         group,
         formatted_remark,
-        host.to_string(),
+        host,
         port,
-        password.to_string(),
-        network,
-        sni.clone(),
+        password,
+        network.filter(|network| network != "tcp"),
+        host_header,
         path,
         sni,
         true,             // tls_secure
@@ -110,89 +131,73 @@ pub fn explode_trojan(trojan: &str, node: &mut Proxy) -> bool {
     true
 }
 
-/// Parse a Trojan-Go link into a Proxy object
-pub fn explode_trojan_go(trojan_go: &str, node: &mut Proxy) -> bool {
-    // Check if the link starts with trojan-go://
-    if !trojan_go.starts_with("trojan-go://") {
-        return false;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::explodes::explode;
+    use crate::ProxyType;
+
+    #[test]
+    fn test_explode_trojan_plain() {
+        let mut node = Proxy::default();
+        assert!(explode(
+            "trojan://p%40ss@example.com:443?sni=cdn.example.com&allowInsecure=true#%E9%A6%99%E6%B8%AF%2001",
+            &mut node
+        ));
+        assert_eq!(node.proxy_type, ProxyType::Trojan);
+        assert_eq!(node.remark, "香港 01");
+        assert_eq!(node.hostname, "example.com");
+        assert_eq!(node.port, 443);
+        assert_eq!(node.password.as_deref(), Some("p@ss"));
+        assert_eq!(node.sni.as_deref(), Some("cdn.example.com"));
+        assert_eq!(node.allow_insecure, Some(true));
+        assert_eq!(node.transfer_protocol, None);
+        assert!(node.tls_secure);
     }
 
-    // Try to parse as URL
-    let url = match Url::parse(trojan_go) {
-        Ok(url) => url,
-        Err(_) => return false,
-    };
-
-    // Extract password
-    let password = url.username();
-    if password.is_empty() {
-        return false;
+    #[test]
+    fn test_explode_trojan_transports() {
+        let mut node = Proxy::default();
+        assert!(explode_trojan(
+            "trojan://password@example.com:443?sni=example.com&allowInsecure=1&type=ws&path=%2Fws&host=cdn.example.com#WS",
+            &mut node
+        ));
+        assert_eq!(node.transfer_protocol.as_deref(), Some("ws"));
+        assert_eq!(node.path.as_deref(), Some("/ws"));
+        assert_eq!(node.host.as_deref(), Some("cdn.example.com"));
+        assert_eq!(node.allow_insecure, Some(true));
+
+        let mut node = Proxy::default();
+        assert!(explode_trojan(
+            "trojan://password@example.com:443?type=grpc&serviceName=trojan-grpc&sni=example.com#gRPC",
+            &mut node
+        ));
+        assert_eq!(node.transfer_protocol.as_deref(), Some("grpc"));
+        assert_eq!(node.path.as_deref(), Some("trojan-grpc"));
+
+        let mut node = Proxy::default();
+        assert!(explode(
+            "trojan-go://password@example.com:443/?sni=example.com&ws=1&wsPath=%2Fgo&encryption=none#Go",
+            &mut node
+        ));
+        assert_eq!(node.remark, "Go");
+        assert_eq!(node.transfer_protocol.as_deref(), Some("ws"));
+        assert_eq!(node.path.as_deref(), Some("/go"));
+        assert!(!explode_trojan_go(
+            "trojan-go://password@example.com:443/?encryption=ss%3Baes-128-gcm%3Bsecret",
+            &mut node
+        ));
     }
 
-    // Extract host and port
-    let host = match url.host_str() {
-        Some(host) => host,
-        None => return false,
-    };
-    let port = url.port().unwrap_or(443);
-
-    // Skip if port is 0
-    if port == 0 {
-        return false;
+    #[test]
+    fn test_explode_trojan_ipv6() {
+        let mut node = Proxy::default();
+        assert!(explode_trojan(
+            "trojan://password@[2001:db8::2]:8443?sni=example.com",
+            &mut node
+        ));
+        assert_eq!(node.hostname, "2001:db8::2");
+        assert_eq!(node.port, 8443);
+        assert_eq!(node.remark, "2001:db8::2 (8443)");
     }
-
-    // Extract parameters from the query string
-    let mut params = HashMap::new();
-    for (key, value) in url.query_pairs() {
-        params.insert(key.to_string(), url_decode(&value));
-    }
-
-    // Extract network, host, path
-    let network = params.get("type").map(|s| s.to_string());
-    let host_param = params.get("host").map(|s| s.to_string());
-    let path = params.get("path").map(|s| s.to_string());
-    let sni = params.get("sni").map(|s| s.to_string());
-    // Extract TLS verification setting
-    let skip_cert_verify = params
-        .get("allowInsecure")
-        .map(|s| s == "1" || s.to_lowercase() == "true");
-
-    // Extract TFO setting
-    let tfo = params
-        .get("tfo")
-        .map(|s| s == "1" || s.to_lowercase() == "true");
-
-    // Extract group parameter
-    let group = params
-        .get("group")
-        .map_or_else(|| TROJAN_DEFAULT_GROUP, |s| s);
-
-    // Extract remark from the fragment
-    let remark = url_decode(url.fragment().unwrap_or(""));
-    let formatted_remark = if remark.is_empty() {
-        format!("{} ({})", host, port)
-    } else {
-        remark.to_string()
-    };
-
-    // Create the proxy object
-    *node = Proxy::trojan_construct(
-        group.to_string(),
-        formatted_remark,
-        host.to_string(),
-        port,
-        password.to_string(),
-        network,
-        host_param,
-        path,
-        sni,
-        true,             // tls_secure
-        None,             // udp
-        tfo,              // tfo
-        skip_cert_verify, // allow_insecure
-        None,             // tls13
-        None,             // underlying_proxy
-    );
-
-    true
 }
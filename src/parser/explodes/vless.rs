@@ -1,8 +1,9 @@
 use crate::models::proxy_node::combined::CombinedProxy;
 use crate::models::proxy_node::vless::VlessProxy;
 use crate::models::{Proxy, ProxyType};
-use crate::utils::url_decode;
-use std::collections::{HashMap, HashSet};
+use crate::utils::{decode_component, decode_query_pairs};
+use indexmap::{IndexMap, IndexSet};
+use std::collections::HashMap;
 use url::Url;
 
 /// Parse a VLESS link into a Proxy object
@@ -19,10 +20,7 @@ pub fn explode_vless(vless: &str, node: &mut Proxy) -> bool {
     };
 
     // Extract parameters from the query string
-    let mut params = HashMap::new();
-    for (key, value) in url.query_pairs() {
-        params.insert(key.to_string(), url_decode(&value));
-    }
+    let params: HashMap<String, String> = decode_query_pairs(url.query().unwrap_or("")).collect();
 
     // Extract required fields
     let uuid = match url.username() {
@@ -54,7 +52,7 @@ pub fn explode_vless(vless: &str, node: &mut Proxy) -> bool {
         .map(|s| {
             s.split(',')
                 .map(|s| s.trim().to_string())
-                .collect::<HashSet<_>>()
+                .collect::<IndexSet<_>>()
         })
         .unwrap_or_default();
 
@@ -100,7 +98,7 @@ pub fn explode_vless(vless: &str, node: &mut Proxy) -> bool {
     match network.as_str() {
         "tcp" => {
             if fake_type != "none" {
-                let mut http_headers = HashMap::new();
+                let mut http_headers = IndexMap::new();
                 let mut http_path = vec!["/".to_string()];
 
                 if let Some(host) = params.get("host") {
@@ -132,7 +130,7 @@ pub fn explode_vless(vless: &str, node: &mut Proxy) -> bool {
             vless_proxy.h2_host = Some(h2_headers.get("Host").unwrap_or(&vec![]).clone());
         }
         "ws" | "httpupgrade" => {
-            let mut ws_headers = HashMap::new();
+            let mut ws_headers = IndexMap::new();
             ws_headers.insert("User-Agent".to_string(), "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36".to_string());
 
             if let Some(host) = params.get("host") {
@@ -164,7 +162,7 @@ pub fn explode_vless(vless: &str, node: &mut Proxy) -> bool {
 
     node.proxy_type = ProxyType::Vless;
     node.combined_proxy = Some(CombinedProxy::Vless(vless_proxy));
-    node.remark = url_decode(url.fragment().unwrap_or(""));
+    node.remark = decode_component(url.fragment().unwrap_or(""));
     node.hostname = host.to_string();
     node.port = port;
 
@@ -1,4 +1,7 @@
-use crate::{utils::url_decode, Proxy};
+use crate::{
+    utils::{decode_component, decode_query_pairs},
+    Proxy,
+};
 use regex::Regex;
 use std::collections::HashMap;
 use url::Url;
@@ -17,10 +20,7 @@ pub fn explode_wireguard(wireguard: &str, node: &mut Proxy) -> bool {
     };
 
     // Extract parameters from the query string
-    let mut params = HashMap::new();
-    for (key, value) in url.query_pairs() {
-        params.insert(key.to_string(), url_decode(&value));
-    }
+    let params: HashMap<String, String> = decode_query_pairs(url.query().unwrap_or("")).collect();
 
     // Extract required fields
     let private_key = match params.get("privateKey") {
@@ -65,7 +65,7 @@ pub fn explode_wireguard(wireguard: &str, node: &mut Proxy) -> bool {
     };
 
     // Extract remark from the fragment
-    let remark = url_decode(url.fragment().unwrap_or(""));
+    let remark = decode_component(url.fragment().unwrap_or(""));
     let formatted_remark = if remark.is_empty() {
         format!("{} ({})", host, port)
     } else {
@@ -2,6 +2,7 @@ use std::time::{Duration, UNIX_EPOCH};
 
 use crate::models::{Proxy, RegexMatchConfigs};
 use crate::utils::base64::url_safe_base64_decode;
+use crate::utils::regexp::{compiled_regex, RegexFlags};
 use crate::utils::system::safe_system_time;
 use crate::utils::url::get_url_arg;
 use regex::Regex;
@@ -110,7 +111,7 @@ pub fn get_sub_info_from_nodes(
         // Extract stream info if not already found
         if stream_info.is_empty() {
             for rule in stream_rules {
-                let re = Regex::new(&rule._match).ok()?;
+                let re = compiled_regex(&rule._match, RegexFlags::default())?;
                 if re.is_match(remarks) {
                     let new_remark = re.replace(remarks, &rule.replace).to_string();
                     if new_remark != *remarks {
@@ -124,7 +125,7 @@ pub fn get_sub_info_from_nodes(
         // Extract time info if not already found
         if time_info.is_empty() {
             for rule in time_rules {
-                let re = Regex::new(&rule._match).ok()?;
+                let re = compiled_regex(&rule._match, RegexFlags::default())?;
                 if re.is_match(remarks) {
                     let new_remark = re.replace(remarks, &rule.replace).to_string();
                     if new_remark != *remarks {
@@ -236,3 +237,110 @@ pub fn get_sub_info_from_ssd(sub: &str) -> Option<String> {
 
     Some(result)
 }
+
+/// Extracts subscription info from a SIP008 online config
+///
+/// `bytes_used` becomes the download and, with `bytes_remaining`, the total
+/// of the info. Returns `None` when the config reports no usage.
+pub fn get_sub_info_from_sip008(content: &str) -> Option<String> {
+    let content = content.trim();
+    if !content.starts_with('{') {
+        return None;
+    }
+    let json: serde_json::Value = serde_json::from_str(content).ok()?;
+    if json["version"].as_u64() != Some(1) || !json["servers"].is_array() {
+        return None;
+    }
+
+    let used = json["bytes_used"].as_u64();
+    let remaining = json["bytes_remaining"].as_u64();
+    if used.is_none() && remaining.is_none() {
+        return None;
+    }
+    let used = used.unwrap_or(0);
+    let mut result = format!("upload=0; download={};", used);
+    if let Some(remaining) = remaining {
+        result.push_str(&format!(" total={};", used.saturating_add(remaining)));
+    }
+    Some(result)
+}
+
+/// Parses a subscription info string into used bytes, total bytes and expiry timestamp
+///
+/// Accepts both `;` and `,` separated `key=value` items, so it handles the raw
+/// Subscription-Userinfo header as well as the values produced by this module.
+pub fn parse_sub_info(sub_info: &str) -> (u64, u64, u64) {
+    let mut used = 0u64;
+    let mut total = 0u64;
+    let mut expire = 0u64;
+
+    for item in sub_info.split([';', ',']) {
+        let Some((key, value)) = item.trim().split_once('=') else {
+            continue;
+        };
+        let value = value.trim().parse::<u64>().unwrap_or(0);
+        match key.trim() {
+            "upload" | "download" => used = used.saturating_add(value),
+            "total" => total = value,
+            "expire" => expire = value,
+            _ => {}
+        }
+    }
+
+    (used, total, expire)
+}
+
+/// Formats a unix timestamp as a `YYYY-MM-DD` date in UTC
+fn timestamp_to_date(timestamp: u64) -> String {
+    // Civil date from days since the epoch, see http://howardhinnant.github.io/date_algorithms.html
+    let days = (timestamp / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let doe = days.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Builds unreachable placeholder nodes showing remaining traffic and expiry
+///
+/// `{traffic}` in `traffic_remark` is replaced with the remaining traffic in GB and
+/// `{expire}` in `expire_remark` with the expiry date. A node is only produced when
+/// the corresponding value is present in `sub_info`.
+pub fn build_info_nodes(sub_info: &str, traffic_remark: &str, expire_remark: &str) -> Vec<Proxy> {
+    let (used, total, expire) = parse_sub_info(sub_info);
+    let mut remarks = Vec::new();
+
+    if total > 0 {
+        let left = total.saturating_sub(used) as f64 / 1024_f64.powi(3);
+        remarks.push(traffic_remark.replace("{traffic}", &format!("{:.2} GB", left)));
+    }
+    if expire > 0 {
+        remarks.push(expire_remark.replace("{expire}", &timestamp_to_date(expire)));
+    }
+
+    remarks
+        .iter()
+        .map(|remark| {
+            Proxy::ss_construct(
+                "",
+                remark,
+                "127.0.0.1",
+                1234,
+                "info",
+                "aes-128-gcm",
+                "",
+                "",
+                None,
+                None,
+                None,
+                None,
+                "",
+            )
+        })
+        .collect()
+}
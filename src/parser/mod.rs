@@ -4,3 +4,5 @@ pub mod parse_settings;
 pub mod subparser;
 pub mod types;
 pub mod yaml;
+
+pub use explodes::{explode_any, explode_any_with_limits, ContentLimits, ParseError};
@@ -1,6 +1,10 @@
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
 
-use crate::models::RegexMatchConfigs;
+use crate::models::{RegexMatchConfigs, SourceProxyGroups};
+use crate::parser::explodes::ContentLimits;
+use crate::utils::fetcher::{default_fetcher, SubscriptionFetcher};
 use crate::utils::http::{parse_proxy, ProxyConfig};
 use crate::Settings;
 use case_insensitive_string::CaseInsensitiveString;
@@ -12,6 +16,9 @@ pub struct ParseSettings {
     /// Proxy to use for downloading subscriptions
     pub proxy: ProxyConfig,
 
+    /// Loads subscriptions, data URIs and local files
+    pub fetcher: Arc<dyn SubscriptionFetcher>,
+
     /// Array of remarks to exclude
     pub exclude_remarks: Option<Vec<String>>,
 
@@ -30,25 +37,52 @@ pub struct ParseSettings {
     /// Whether operations requiring authorization are allowed
     pub authorized: bool,
 
+    /// Whether the `proxy-providers` of a Clash config are fetched, always
+    /// done for authorized requests
+    pub follow_providers: bool,
+
     /// HTTP request headers
     pub request_header: Option<HashMap<CaseInsensitiveString, String>>,
 
+    /// Maximum size in bytes of an inline `data:` URI payload, 0 for unlimited
+    pub max_data_uri_size: i64,
+
+    /// Maximum size in bytes of a downloaded subscription, 0 for unlimited
+    pub max_download_size: i64,
+
+    /// Bounds on base64 layers and node count of the subscription content
+    pub content_limits: ContentLimits,
+
+    /// Time spent downloading the subscription, filled in while parsing
+    pub fetch_time: Duration,
+
+    /// Number of nodes found before the remark filters were applied
+    pub parsed_count: usize,
+
+    /// Proxy groups of a full Surge configuration, filled in while parsing
+    pub source_groups: Option<SourceProxyGroups>,
+
     /// JavaScript runtime - optional depending on feature flags
-    #[cfg(feature = "js_runtime")]
+    #[cfg(feature = "scripting")]
     pub js_runtime: Option<()>, // Placeholder for actual JS runtime type
 
     /// JavaScript context - optional depending on feature flags
-    #[cfg(feature = "js_runtime")]
+    #[cfg(feature = "scripting")]
     pub js_context: Option<()>, // Placeholder for actual JS context type
 }
 
 impl Default for ParseSettings {
     fn default() -> Self {
-        // Get global settings
-        let settings = Settings::current();
+        Self::from_settings(&Settings::current())
+    }
+}
 
+impl ParseSettings {
+    /// Parse settings with the defaults of `settings`
+    pub fn from_settings(settings: &Settings) -> Self {
         ParseSettings {
             proxy: parse_proxy(&settings.proxy_subscription),
+            fetcher: default_fetcher(),
             exclude_remarks: if settings.exclude_remarks.is_empty() {
                 None
             } else {
@@ -63,10 +97,21 @@ impl Default for ParseSettings {
             time_rules: None,   // TODO: Get from global settings
             sub_info: None,
             authorized: !settings.api_access_token.is_empty(),
+            follow_providers: false,
             request_header: None,
-            #[cfg(feature = "js_runtime")]
+            max_data_uri_size: settings.max_allowed_data_uri_size,
+            max_download_size: settings.max_allowed_download_size,
+            content_limits: ContentLimits {
+                max_decoded_ratio: settings.max_decoded_size_ratio,
+                max_nodes: settings.max_allowed_proxies,
+                ..Default::default()
+            },
+            fetch_time: Duration::ZERO,
+            parsed_count: 0,
+            source_groups: None,
+            #[cfg(feature = "scripting")]
             js_runtime: None,
-            #[cfg(feature = "js_runtime")]
+            #[cfg(feature = "scripting")]
             js_context: None,
         }
     }
@@ -1,13 +1,20 @@
-use crate::models::Proxy;
+use crate::models::{Proxy, SourceProxyGroups};
 use crate::parser::explodes::*;
-use crate::parser::infoparser::{get_sub_info_from_nodes, get_sub_info_from_ssd};
+use crate::parser::infoparser::{
+    get_sub_info_from_nodes, get_sub_info_from_sip008, get_sub_info_from_ssd,
+};
 use crate::parser::parse_settings::ParseSettings;
+use crate::parser::yaml::clash::load_clash_yaml;
+use crate::utils::fetcher::{FetchOptions, SubscriptionFetcher};
+use crate::utils::file_exists;
 use crate::utils::http::get_sub_info_from_header;
 use crate::utils::matcher::{apply_matcher, reg_find};
 use crate::utils::network::is_link;
-use crate::utils::url::url_decode;
-use crate::utils::{file_exists, file_get_async, web_get_async};
+use crate::utils::system::{elapsed_since, safe_system_time};
+use crate::utils::url::{decode_component, get_url_arg};
+use case_insensitive_string::CaseInsensitiveString;
 use log::warn;
+use std::collections::HashMap;
 
 /// Equivalent to ConfType enum in C++
 #[derive(Debug, PartialEq, Eq)]
@@ -15,6 +22,7 @@ pub enum ConfType {
     SOCKS,
     HTTP,
     SUB,
+    Data,
     Netch,
     Local,
     Unknown,
@@ -46,6 +54,7 @@ pub async fn add_nodes(
     let time_rules = parse_settings.time_rules.as_ref();
     let request_header = parse_settings.request_header.as_ref();
     let authorized = parse_settings.authorized;
+    let fetcher = parse_settings.fetcher.clone();
 
     // Variables to store data during processing
     let mut nodes: Vec<Proxy> = Vec::new();
@@ -56,17 +65,17 @@ pub async fn add_nodes(
     link = link.replace("\"", "");
 
     // Handle JavaScript scripts (Not implementing JS support here)
-    #[cfg(feature = "js_runtime")]
+    #[cfg(feature = "scripting")]
     if authorized && link.starts_with("script:") {
         // Script processing would go here
         return Err("Script processing not implemented".to_string());
     }
 
     // Handle tag: prefix for custom group
-    if link.starts_with("tag:") {
-        if let Some(pos) = link.find(',') {
-            custom_group = link[4..pos].to_string();
-            link = link[pos + 1..].to_string();
+    if let Some(tagged) = link.strip_prefix("tag:") {
+        if let Some((group, rest)) = split_tag(tagged) {
+            custom_group = group;
+            link = rest.to_string();
         }
     }
 
@@ -83,11 +92,13 @@ pub async fn add_nodes(
         ConfType::SOCKS
     } else if link.starts_with("https://t.me/http") || link.starts_with("tg://http") {
         ConfType::HTTP
+    } else if link.starts_with("data:") {
+        ConfType::Data
     } else if is_link(&link) || link.starts_with("surge:///install-config") {
         ConfType::SUB
     } else if link.starts_with("Netch://") {
         ConfType::Netch
-    } else if file_exists(&link).await {
+    } else if link.starts_with("file://") || file_exists(&link).await {
         ConfType::Local
     } else {
         // Default to Unknown for direct proxy links or invalid links
@@ -99,13 +110,20 @@ pub async fn add_nodes(
             // Handle subscription links
             if link.starts_with("surge:///install-config") {
                 // Extract URL from Surge config link
-                if let Some(url_arg) = get_url_arg(&link, "url") {
-                    link = url_decode(&url_arg);
+                let url_arg = get_url_arg(&link, "url");
+                if !url_arg.is_empty() {
+                    link = url_arg;
                 }
             }
 
             // Download subscription content
-            let response = match web_get_async(&link, proxy, request_header).await {
+            let fetch_start = safe_system_time();
+            let opts = FetchOptions {
+                proxy: proxy.clone(),
+                headers: request_header.cloned(),
+                max_size: parse_settings.max_download_size,
+            };
+            let response = match fetcher.fetch(&link, &opts).await {
                 Ok(response) => response,
                 Err(e) => {
                     warn!("Failed to get subscription content from {}: {}", link, e);
@@ -113,13 +131,24 @@ pub async fn add_nodes(
                 }
             };
 
+            parse_settings.fetch_time = elapsed_since(fetch_start);
+
             let sub_content = response.body;
             let headers = response.headers;
 
             if !sub_content.is_empty() {
                 // Parse the subscription content
-                let result = explode_conf_content(&sub_content, &mut nodes);
-                if result > 0 {
+                let parsed = explode_any_with_limits(&sub_content, &parse_settings.content_limits);
+                let parsed = with_provider_nodes(parsed, &sub_content, parse_settings).await;
+                if let Err(e) = &parsed {
+                    if e.is_detailed() {
+                        warn!("Failed to parse subscription content from {}: {}", link, e);
+                        return Err(e.to_string());
+                    }
+                }
+                if let Ok(parsed) = parsed {
+                    nodes = parsed;
+                    parse_settings.source_groups = source_groups(&sub_content);
                     // Get subscription info
                     if sub_content.starts_with("ssd://") {
                         // Extract info from SSD subscription
@@ -131,6 +160,9 @@ pub async fn add_nodes(
                         let header_info = get_sub_info_from_header(&headers);
                         if !header_info.is_empty() {
                             parse_settings.sub_info = Some(header_info);
+                        } else if let Some(info) = get_sub_info_from_sip008(&sub_content) {
+                            // SIP008 reports the usage in the config itself
+                            parse_settings.sub_info = Some(info);
                         } else {
                             // If no header info, try from nodes
                             if let (Some(stream_rules_unwrapped), Some(time_rules_unwrapped)) =
@@ -147,6 +179,8 @@ pub async fn add_nodes(
                         }
                     }
 
+                    parse_settings.parsed_count = nodes.len();
+
                     // Filter nodes and set group info
                     filter_nodes(&mut nodes, exclude_remarks, include_remarks, group_id);
 
@@ -168,13 +202,74 @@ pub async fn add_nodes(
                 Err("Cannot download subscription data".to_string())
             }
         }
+        ConfType::Data => {
+            // Inline subscription content, decoded without touching the network
+            let opts = FetchOptions {
+                max_size: parse_settings.max_data_uri_size,
+                ..Default::default()
+            };
+            let sub_content = fetcher.fetch(&link, &opts).await?.body;
+
+            let parsed = explode_any_with_limits(&sub_content, &parse_settings.content_limits);
+            let parsed = with_provider_nodes(parsed, &sub_content, parse_settings).await;
+            if let Err(e) = &parsed {
+                if e.is_detailed() {
+                    warn!("Failed to parse data URI content: {}", e);
+                    return Err(e.to_string());
+                }
+            }
+            if let Ok(parsed) = parsed {
+                nodes = parsed;
+                parse_settings.source_groups = source_groups(&sub_content);
+                if sub_content.starts_with("ssd://") {
+                    if let Some(info) = get_sub_info_from_ssd(&sub_content) {
+                        parse_settings.sub_info = Some(info);
+                    }
+                } else if let Some(info) = get_sub_info_from_sip008(&sub_content) {
+                    parse_settings.sub_info = Some(info);
+                } else if let (Some(stream_rules_unwrapped), Some(time_rules_unwrapped)) =
+                    (stream_rules, time_rules)
+                {
+                    if let Some(info) = get_sub_info_from_nodes(
+                        &nodes,
+                        stream_rules_unwrapped,
+                        time_rules_unwrapped,
+                    ) {
+                        parse_settings.sub_info = Some(info);
+                    }
+                }
+
+                parse_settings.parsed_count = nodes.len();
+
+                filter_nodes(&mut nodes, exclude_remarks, include_remarks, group_id);
+
+                // Set group_id and custom_group for all nodes
+                for node in &mut nodes {
+                    node.group_id = group_id;
+                    if !custom_group.is_empty() {
+                        node.group = custom_group.clone();
+                    }
+                }
+
+                all_nodes.append(&mut nodes);
+                Ok(())
+            } else {
+                Err("No valid link found in data URI".to_string())
+            }
+        }
         ConfType::Local => {
             if !authorized {
                 return Err("Not authorized to access local files".to_string());
             }
 
             // Read and parse local file
-            let result = explode_conf(&link, &mut nodes).await;
+            let result = explode_conf(
+                fetcher.as_ref(),
+                &link,
+                &mut nodes,
+                &parse_settings.content_limits,
+            )
+            .await?;
             if result > 0 {
                 // The rest is similar to SUB case
                 // Get subscription info
@@ -198,6 +293,8 @@ pub async fn add_nodes(
                     }
                 }
 
+                parse_settings.parsed_count = nodes.len();
+
                 filter_nodes(&mut nodes, exclude_remarks, include_remarks, group_id);
 
                 // Set group_id and custom_group for all nodes
@@ -224,6 +321,7 @@ pub async fn add_nodes(
                 if !custom_group.is_empty() {
                     node.group = custom_group;
                 }
+                parse_settings.parsed_count = 1;
                 all_nodes.push(node);
                 Ok(())
             } else {
@@ -233,30 +331,186 @@ pub async fn add_nodes(
     }
 }
 
-/// Extracts a specific argument from a URL
-fn get_url_arg(url: &str, arg_name: &str) -> Option<String> {
-    if let Some(query_start) = url.find('?') {
-        let query = &url[query_start + 1..];
-        for pair in query.split('&') {
-            let parts: Vec<&str> = pair.split('=').collect();
-            if parts.len() == 2 && parts[0] == arg_name {
-                return Some(parts[1].to_string());
+/// Add the nodes of the `proxy-providers` of a Clash config to `parsed`
+///
+/// Providers are only followed for authorized requests or when asked to.
+/// A config without inline proxies is fine as long as its providers hold
+/// nodes. The node limit applies to the config and its providers together.
+async fn with_provider_nodes(
+    parsed: Result<Vec<Proxy>, ParseError>,
+    content: &str,
+    parse_settings: &ParseSettings,
+) -> Result<Vec<Proxy>, ParseError> {
+    if !(parse_settings.authorized || parse_settings.follow_providers)
+        || !content.contains("proxy-providers:")
+    {
+        return parsed;
+    }
+    let mut nodes = match parsed {
+        Ok(nodes) => nodes,
+        Err(e) if e.is_detailed() => return Err(e),
+        Err(e) => {
+            let provider_nodes = clash_provider_nodes(content, 0, parse_settings).await?;
+            return if provider_nodes.is_empty() {
+                Err(e)
+            } else {
+                Ok(provider_nodes)
+            };
+        }
+    };
+    let mut provider_nodes = clash_provider_nodes(content, nodes.len(), parse_settings).await?;
+    nodes.append(&mut provider_nodes);
+    Ok(nodes)
+}
+
+/// Nodes of the `http` proxy providers of a Clash config
+///
+/// Every provider is fetched like a subscription with the same size limit,
+/// its nodes get the provider name as group. Providers may be hosted by
+/// anyone, so only the User-Agent of the request is sent to them. The
+/// content of a provider is parsed as is, providers it declares are not
+/// followed. A provider that fails is skipped with a warning, going over
+/// the node limit with the `parsed` nodes of the config fails.
+async fn clash_provider_nodes(
+    content: &str,
+    parsed: usize,
+    parse_settings: &ParseSettings,
+) -> Result<Vec<Proxy>, ParseError> {
+    let Ok(yaml) = load_clash_yaml(content) else {
+        return Ok(Vec::new());
+    };
+    let Some(providers) = yaml.get("proxy-providers").and_then(|v| v.as_mapping()) else {
+        return Ok(Vec::new());
+    };
+
+    let user_agent = CaseInsensitiveString::new("User-Agent");
+    let opts = FetchOptions {
+        proxy: parse_settings.proxy.clone(),
+        headers: parse_settings
+            .request_header
+            .as_ref()
+            .and_then(|headers| headers.get(&user_agent))
+            .map(|value| HashMap::from([(user_agent.clone(), value.clone())])),
+        max_size: parse_settings.max_download_size,
+    };
+    let max_nodes = parse_settings.content_limits.max_nodes;
+    let mut nodes = Vec::new();
+    for (name, provider) in providers {
+        let Some(name) = name.as_str() else {
+            continue;
+        };
+        let url = provider
+            .get("url")
+            .and_then(|v| v.as_str())
+            .unwrap_or_default();
+        if provider.get("type").and_then(|v| v.as_str()) != Some("http") || !is_link(url) {
+            continue;
+        }
+        let body = match parse_settings.fetcher.fetch(url, &opts).await {
+            Ok(fetched) => fetched.body,
+            Err(e) => {
+                warn!(
+                    "Failed to fetch proxy provider '{}' from {}: {}",
+                    name, url, e
+                );
+                continue;
             }
+        };
+        // Only the nodes left to the limit may come from this provider
+        let found = parsed + nodes.len();
+        let limits = ContentLimits {
+            max_nodes: if max_nodes > 0 {
+                max_nodes.saturating_sub(found).max(1)
+            } else {
+                0
+            },
+            ..parse_settings.content_limits
+        };
+        match explode_any_with_limits(&body, &limits) {
+            Ok(provider_nodes) if max_nodes > 0 && found + provider_nodes.len() > max_nodes => {
+                return Err(ParseError::TooManyNodes {
+                    count: found + provider_nodes.len(),
+                    limit: max_nodes,
+                });
+            }
+            Ok(mut provider_nodes) => {
+                for node in &mut provider_nodes {
+                    node.group = name.to_string();
+                }
+                nodes.append(&mut provider_nodes);
+            }
+            Err(ParseError::TooManyNodes { count, .. }) => {
+                return Err(ParseError::TooManyNodes {
+                    count: found + count,
+                    limit: max_nodes,
+                });
+            }
+            Err(e) => warn!("Failed to parse proxy provider '{}': {}", name, e),
         }
     }
-    None
+    Ok(nodes)
+}
+
+/// Proxy groups of a full Surge configuration, `None` for other content
+fn source_groups(content: &str) -> Option<SourceProxyGroups> {
+    if !content.contains("[Proxy Group]") {
+        return None;
+    }
+    Some(explode_surge_groups(content)).filter(|source| !source.groups.is_empty())
 }
 
 /// Parses a configuration file into a vector of Proxy objects
-/// Returns the number of proxies parsed
-async fn explode_conf(path: &str, nodes: &mut Vec<Proxy>) -> i32 {
+/// Returns the number of proxies parsed, or why the file could not be parsed
+async fn explode_conf(
+    fetcher: &dyn SubscriptionFetcher,
+    path: &str,
+    nodes: &mut Vec<Proxy>,
+    limits: &ContentLimits,
+) -> Result<i32, String> {
     // TODO: 安全问题，但是旧版subconverter也有……
-    match file_get_async(path, None).await {
-        Ok(content) => explode_conf_content(&content, nodes),
-        Err(_) => 0,
+    match fetcher.fetch(path, &FetchOptions::default()).await {
+        Ok(fetched) => match explode_any_with_limits(&fetched.body, limits) {
+            Ok(mut parsed) => {
+                nodes.append(&mut parsed);
+                Ok(nodes.len() as i32)
+            }
+            Err(e) if e.is_detailed() => {
+                warn!("Failed to parse configuration file {}: {}", path, e);
+                Err(e.to_string())
+            }
+            Err(_) => Ok(0),
+        },
+        Err(_) => Ok(0),
     }
 }
 
+/// Split the `label,link` after a `tag:` prefix into the label and the link
+///
+/// The label ends at the first comma followed by something that starts a
+/// link: a URI scheme, a path or `nullnode`, so labels may hold commas.
+/// Without one, the first comma ends it. The label is percent-decoded, so a
+/// comma can also be written `%2C`.
+fn split_tag(tagged: &str) -> Option<(String, &str)> {
+    let starts_link = |rest: &str| {
+        let scheme = rest.split_once(':').map(|(scheme, _)| scheme);
+        rest.starts_with('/')
+            || rest.starts_with("./")
+            || rest == "nullnode"
+            || scheme.is_some_and(|scheme| {
+                scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                    && scheme
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+            })
+    };
+    let pos = tagged
+        .match_indices(',')
+        .map(|(pos, _)| pos)
+        .find(|&pos| starts_link(&tagged[pos + 1..]))
+        .or_else(|| tagged.find(','))?;
+    Some((decode_component(&tagged[..pos]), &tagged[pos + 1..]))
+}
+
 /// Filters nodes based on include/exclude rules
 fn filter_nodes(
     nodes: &mut Vec<Proxy>,
@@ -335,3 +589,336 @@ fn should_ignore(
     // A node is ignored if it's excluded OR not included
     excluded || !included
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::exports::proxy_to_clash::proxy_to_clash;
+    use crate::models::ExtraSettings;
+    use crate::utils::base64::base64_encode;
+    use crate::utils::fetcher::{FetchedBody, MemoryFetcher};
+    use std::sync::Arc;
+
+    const SS_LINKS: &str = "ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@1.2.3.4:8388#Node%20A\n\
+                            ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@5.6.7.8:8388#Node%20B";
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    fn data_uri() -> String {
+        format!("data:text/plain;base64,{}", base64_encode(SS_LINKS))
+    }
+
+    #[test]
+    fn test_split_tag() {
+        assert_eq!(
+            split_tag("HK, US,https://example.com/sub?a=1,2"),
+            Some(("HK, US".to_string(), "https://example.com/sub?a=1,2"))
+        );
+        assert_eq!(
+            split_tag("A,B,ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@1.2.3.4:8388"),
+            Some((
+                "A,B".to_string(),
+                "ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@1.2.3.4:8388"
+            ))
+        );
+        assert_eq!(
+            split_tag("Mine,nullnode"),
+            Some(("Mine".to_string(), "nullnode"))
+        );
+        assert_eq!(
+            split_tag("HK%2CUS,data:,x"),
+            Some(("HK,US".to_string(), "data:,x"))
+        );
+        assert_eq!(
+            split_tag("Local,subs.txt"),
+            Some(("Local".to_string(), "subs.txt"))
+        );
+        assert_eq!(split_tag("no-link"), None);
+    }
+
+    #[test]
+    fn test_data_uri_to_clash() {
+        let mut parse_settings = ParseSettings::default();
+        parse_settings.exclude_remarks = None;
+        parse_settings.include_remarks = None;
+        let mut nodes = Vec::new();
+        block_on(add_nodes(data_uri(), &mut nodes, 1, &mut parse_settings)).unwrap();
+        assert_eq!(nodes.len(), 2);
+
+        let mut ext = ExtraSettings::default();
+        ext.nodelist = true;
+        let output = proxy_to_clash(
+            &mut nodes,
+            "{}",
+            &mut Vec::new(),
+            &Vec::new(),
+            false,
+            &mut ext,
+        );
+        assert!(output.contains("Node A"));
+        assert!(output.contains("Node B"));
+        assert!(output.contains("server: 1.2.3.4"));
+        assert!(output.contains("type: ss"));
+    }
+
+    #[test]
+    fn test_data_uri_filter_and_limit() {
+        let mut parse_settings = ParseSettings::default();
+        parse_settings.exclude_remarks = Some(vec!["Node B".to_string()]);
+        parse_settings.include_remarks = None;
+        let mut nodes = Vec::new();
+        block_on(add_nodes(data_uri(), &mut nodes, 1, &mut parse_settings)).unwrap();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].remark, "Node A");
+
+        // The bare form carries the links without any encoding
+        let bare = format!("data:,{}", SS_LINKS.replace('\n', "%0A"));
+        let mut nodes = Vec::new();
+        block_on(add_nodes(bare, &mut nodes, 1, &mut parse_settings)).unwrap();
+        assert_eq!(nodes.len(), 1);
+
+        parse_settings.max_data_uri_size = 16;
+        let mut nodes = Vec::new();
+        assert!(block_on(add_nodes(data_uri(), &mut nodes, 1, &mut parse_settings)).is_err());
+        assert!(nodes.is_empty());
+    }
+
+    #[test]
+    fn test_fetcher_serves_subscriptions() {
+        let mut body = FetchedBody::new(base64_encode(SS_LINKS));
+        body.headers.insert(
+            "subscription-userinfo".to_string(),
+            "upload=1; download=2; total=10".to_string(),
+        );
+        let mut fetcher = MemoryFetcher::new();
+        fetcher
+            .insert("https://example.com/sub", body)
+            .insert("file:///srv/sub.txt", FetchedBody::new(SS_LINKS));
+
+        let mut parse_settings = ParseSettings::default();
+        parse_settings.exclude_remarks = None;
+        parse_settings.include_remarks = None;
+        parse_settings.fetcher = Arc::new(fetcher);
+        let mut nodes = Vec::new();
+        block_on(add_nodes(
+            "https://example.com/sub".to_string(),
+            &mut nodes,
+            1,
+            &mut parse_settings,
+        ))
+        .unwrap();
+        assert_eq!(nodes.len(), 2);
+        assert!(parse_settings.sub_info.as_ref().unwrap().contains("upload=1"));
+
+        // Local files still need an authorized request
+        let link = "file:///srv/sub.txt".to_string();
+        parse_settings.authorized = false;
+        let mut nodes = Vec::new();
+        assert!(block_on(add_nodes(link.clone(), &mut nodes, 1, &mut parse_settings)).is_err());
+        parse_settings.authorized = true;
+        block_on(add_nodes(link, &mut nodes, 1, &mut parse_settings)).unwrap();
+        assert_eq!(nodes.len(), 2);
+    }
+
+    #[test]
+    fn test_base64_bomb_is_rejected() {
+        let two_layers = base64_encode(&base64_encode(SS_LINKS));
+        let mut fetcher = MemoryFetcher::new();
+        fetcher
+            .insert(
+                "https://example.com/two",
+                FetchedBody::new(two_layers.clone()),
+            )
+            .insert(
+                "https://example.com/three",
+                FetchedBody::new(base64_encode(&two_layers)),
+            );
+
+        let mut parse_settings = ParseSettings::default();
+        parse_settings.exclude_remarks = None;
+        parse_settings.include_remarks = None;
+        parse_settings.fetcher = Arc::new(fetcher);
+        parse_settings.content_limits.max_nodes = 0;
+        let mut nodes = Vec::new();
+        block_on(add_nodes(
+            "https://example.com/two".to_string(),
+            &mut nodes,
+            1,
+            &mut parse_settings,
+        ))
+        .unwrap();
+        assert_eq!(nodes.len(), 2);
+
+        let mut nodes = Vec::new();
+        let error = block_on(add_nodes(
+            "https://example.com/three".to_string(),
+            &mut nodes,
+            1,
+            &mut parse_settings,
+        ))
+        .unwrap_err();
+        assert_eq!(error, "Content is base64 encoded more than 2 times");
+
+        parse_settings.content_limits.max_nodes = 1;
+        let error = block_on(add_nodes(
+            "https://example.com/two".to_string(),
+            &mut nodes,
+            1,
+            &mut parse_settings,
+        ))
+        .unwrap_err();
+        assert_eq!(error, "Content holds 2 nodes, more than the limit of 1");
+        assert!(nodes.is_empty());
+    }
+
+    /// Serves a [`MemoryFetcher`] and keeps the headers of every request
+    struct RecordingFetcher {
+        inner: MemoryFetcher,
+        requests: std::sync::Mutex<Vec<(String, HashMap<CaseInsensitiveString, String>)>>,
+    }
+
+    impl SubscriptionFetcher for RecordingFetcher {
+        fn fetch<'a>(
+            &'a self,
+            url: &'a str,
+            opts: &'a FetchOptions,
+        ) -> crate::utils::fetcher::FetchFuture<'a> {
+            self.requests
+                .lock()
+                .unwrap()
+                .push((url.to_string(), opts.headers.clone().unwrap_or_default()));
+            self.inner.fetch(url, opts)
+        }
+    }
+
+    #[test]
+    fn test_clash_proxy_providers() {
+        let config = r#"
+proxy-providers:
+  HK:
+    type: http
+    url: https://example.com/providers/hk.yaml
+    interval: 3600
+    health-check:
+      enable: true
+      url: https://www.gstatic.com/generate_204
+      interval: 300
+  Local:
+    type: file
+    path: ./providers/local.yaml
+  JP:
+    type: http
+    url: https://third-party.example.net/jp.yaml
+proxy-groups:
+  - {name: Proxy, type: select, use: [HK, JP]}
+"#;
+        let provider = r#"
+proxies:
+  - {name: HK 01, type: ss, server: hk.example.com, port: 8388, cipher: aes-128-gcm, password: secret}
+  - {name: HK 02, type: trojan, server: hk2.example.com, port: 443, password: secret}
+proxy-providers:
+  Nested:
+    type: http
+    url: https://example.com/providers/nested.yaml
+"#;
+        let mut fetcher = MemoryFetcher::new();
+        fetcher
+            .insert("https://example.com/clash.yaml", FetchedBody::new(config))
+            .insert(
+                "https://example.com/providers/hk.yaml",
+                FetchedBody::new(provider),
+            )
+            .insert(
+                "https://third-party.example.net/jp.yaml",
+                FetchedBody::new("ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@9.9.9.9:8388#JP%2001"),
+            )
+            .insert(
+                "https://example.com/providers/nested.yaml",
+                FetchedBody::new(SS_LINKS),
+            );
+        let fetcher = Arc::new(RecordingFetcher {
+            inner: fetcher,
+            requests: Default::default(),
+        });
+        let mut parse_settings = ParseSettings::default();
+        parse_settings.exclude_remarks = None;
+        parse_settings.include_remarks = None;
+        parse_settings.fetcher = fetcher.clone();
+        parse_settings.authorized = false;
+        parse_settings.request_header = Some(HashMap::from([
+            (
+                CaseInsensitiveString::new("Authorization"),
+                "Bearer secret".to_string(),
+            ),
+            (
+                CaseInsensitiveString::new("User-Agent"),
+                "clash.meta".to_string(),
+            ),
+        ]));
+        let link = "https://example.com/clash.yaml".to_string();
+
+        // Providers are only followed when asked to or authorized
+        let mut nodes = Vec::new();
+        assert!(block_on(add_nodes(link.clone(), &mut nodes, 1, &mut parse_settings)).is_err());
+        assert!(nodes.is_empty());
+
+        parse_settings.follow_providers = true;
+        block_on(add_nodes(link.clone(), &mut nodes, 1, &mut parse_settings)).unwrap();
+        let remarks: Vec<&str> = nodes.iter().map(|node| node.remark.as_str()).collect();
+        // The providers of the provider are not followed
+        assert_eq!(remarks, vec!["HK 01", "HK 02", "JP 01"]);
+        let groups: Vec<&str> = nodes.iter().map(|node| node.group.as_str()).collect();
+        assert_eq!(groups, vec!["HK", "HK", "JP"]);
+        assert!(nodes.iter().all(|node| node.group_id == 1));
+
+        // The credentials of the request stay with the subscription
+        let authorization = CaseInsensitiveString::new("Authorization");
+        let user_agent = CaseInsensitiveString::new("User-Agent");
+        let requests = fetcher.requests.lock().unwrap().clone();
+        assert_eq!(requests.len(), 4);
+        for (url, headers) in &requests {
+            let is_provider = url.as_str() != link;
+            assert_eq!(
+                headers.contains_key(&authorization),
+                !is_provider,
+                "{}",
+                url
+            );
+            assert_eq!(headers[&user_agent], "clash.meta", "{}", url);
+        }
+
+        // The node limit counts the nodes of all providers
+        parse_settings.content_limits.max_nodes = 2;
+        let mut nodes = Vec::new();
+        let err = block_on(add_nodes(link, &mut nodes, 1, &mut parse_settings)).unwrap_err();
+        assert_eq!(err, "Content holds 3 nodes, more than the limit of 2");
+        assert!(nodes.is_empty());
+    }
+
+    #[test]
+    fn test_yaml_error_is_reported() {
+        let mut fetcher = MemoryFetcher::new();
+        fetcher.insert(
+            "https://example.com/clash.yaml",
+            FetchedBody::new(include_str!("../../tests/fixtures/clash_tab_indent.yaml")),
+        );
+        let mut parse_settings = ParseSettings::default();
+        parse_settings.fetcher = Arc::new(fetcher);
+        let mut nodes = Vec::new();
+        let error = block_on(add_nodes(
+            "https://example.com/clash.yaml".to_string(),
+            &mut nodes,
+            1,
+            &mut parse_settings,
+        ))
+        .unwrap_err();
+        assert!(error.starts_with("Failed to parse Clash YAML"), "{}", error);
+        assert!(error.contains("line 4"), "{}", error);
+    }
+}
@@ -4,7 +4,8 @@ use serde::Deserialize;
 /// Represents a Clash configuration input structure
 #[derive(Debug, Clone, Deserialize)]
 pub struct ClashYamlInput {
-    #[serde(default)]
+    /// `Proxy` is the key of older Clash versions
+    #[serde(default, alias = "Proxy")]
     pub proxies: Vec<ClashProxyYamlInput>,
 }
 
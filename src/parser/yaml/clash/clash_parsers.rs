@@ -3,6 +3,73 @@ use crate::parser::yaml::clash::clash_proxy_types::ClashProxyYamlInput;
 
 use super::ClashYamlInput;
 
+/// Load a Clash configuration as a YAML value with the merge keys applied
+///
+/// Subscriptions often share proxy fields through anchors, `<<: *common`.
+/// Aliases are resolved by the YAML parser, the `<<` keys are merged here
+/// so every proxy mapping holds all of its fields.
+pub fn load_clash_yaml(content: &str) -> Result<serde_yaml::Value, String> {
+    let mut value: serde_yaml::Value =
+        serde_yaml::from_str(content).map_err(|e| format!("Failed to parse Clash YAML: {}", e))?;
+    value
+        .apply_merge()
+        .map_err(|e| format!("Failed to merge Clash YAML keys: {}", e))?;
+    Ok(value)
+}
+
+/// Keep a Clash proxy of a type not modelled here as it is
+///
+/// The node carries the mapping in `raw_passthrough`, its name as remark and
+/// its server and port so that groups and matchers can still select it.
+/// Returns `None` when the proxy has no name or type.
+pub fn passthrough_proxy(proxy: &serde_yaml::Value) -> Option<Proxy> {
+    let raw = proxy.as_mapping()?;
+    let name = raw.get("name").and_then(|v| v.as_str())?;
+    let proxy_type = raw.get("type").and_then(|v| v.as_str())?;
+    if name.is_empty() || proxy_type.is_empty() {
+        return None;
+    }
+    Some(Proxy {
+        remark: name.to_string(),
+        hostname: raw
+            .get("server")
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string(),
+        port: raw
+            .get("port")
+            .and_then(|v| v.as_u64())
+            .and_then(|port| u16::try_from(port).ok())
+            .unwrap_or(0),
+        raw_passthrough: Some(raw.clone()),
+        ..Default::default()
+    })
+}
+
+/// The proxy a Clash proxy is dialed through, `dialer-proxy` of Clash.Meta
+/// or the older `underlying-proxy`
+fn underlying_proxy(proxy: &serde_yaml::Value) -> Option<String> {
+    proxy
+        .get("dialer-proxy")
+        .or_else(|| proxy.get("underlying-proxy"))
+        .and_then(|v| v.as_str())
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+}
+
+/// Copy the `interface-name` and `routing-mark` of a Clash proxy onto its node
+pub fn apply_dial_options(proxy: &serde_yaml::Value, node: &mut Proxy) {
+    node.interface = proxy
+        .get("interface-name")
+        .and_then(|v| v.as_str())
+        .filter(|name| !name.is_empty())
+        .map(str::to_string);
+    node.routing_mark = proxy
+        .get("routing-mark")
+        .and_then(|v| v.as_u64())
+        .and_then(|mark| u32::try_from(mark).ok());
+}
+
 /// Parse Clash configuration from YAML string
 ///
 /// This function is the Rust equivalent of the C++ `explodeClash` function.
@@ -11,15 +78,25 @@ use super::ClashYamlInput;
 /// 2. Proper error handling with Result type
 /// 3. Automatic deserialization using serde
 /// 4. Cleaner pattern matching compared to C++ if/else chains
+///
+/// Proxies of unknown types, e.g. anytls or mieru, are kept verbatim through
+/// [`passthrough_proxy`].
 pub fn parse_clash_yaml(content: &str) -> Result<Vec<Proxy>, String> {
-    let clash_input: ClashYamlInput = match serde_yaml::from_str(content) {
+    let value = load_clash_yaml(content)?;
+    // The same key the typed parse reads, `proxies` or the legacy `Proxy`
+    let raw_proxies = match value.get("proxies").or_else(|| value.get("Proxy")) {
+        Some(serde_yaml::Value::Sequence(seq)) => seq.clone(),
+        _ => Vec::new(),
+    };
+    let clash_input: ClashYamlInput = match serde_yaml::from_value(value) {
         Ok(input) => input,
         Err(e) => return Err(format!("Failed to parse Clash YAML: {}", e)),
     };
 
     let mut proxies = Vec::new();
 
-    for proxy in clash_input.extract_proxies() {
+    for (index, proxy) in clash_input.extract_proxies().into_iter().enumerate() {
+        let parsed = proxies.len();
         match proxy {
             ClashProxyYamlInput::Shadowsocks(ss) => {
                 proxies.push(ss.into());
@@ -55,10 +132,161 @@ pub fn parse_clash_yaml(content: &str) -> Result<Vec<Proxy>, String> {
                 proxies.push(vless.into());
             }
             ClashProxyYamlInput::Unknown => {
-                // Skip unknown proxy types
+                if let Some(node) = raw_proxies.get(index).and_then(passthrough_proxy) {
+                    proxies.push(node);
+                }
             }
         }
+
+        // Proxy chains and dial options are declared the same way for every type
+        if let (Some(node), Some(raw)) = (proxies.get_mut(parsed), raw_proxies.get(index)) {
+            node.underlying_proxy = underlying_proxy(raw);
+            apply_dial_options(raw, node);
+        }
     }
 
     Ok(proxies)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ProxyType;
+
+    const ANCHORED_PROXIES: &str = r#"
+x-ports:
+  default: &port 8388
+x-common: &common
+  type: ss
+  cipher: aes-128-gcm
+  password: secret
+  udp: true
+
+proxies:
+  - <<: *common
+    name: HK 01
+    server: hk.example.com
+    port: *port
+  - <<: [*common]
+    name: JP 01
+    server: jp.example.com
+    port: 443
+    cipher: chacha20-ietf-poly1305
+"#;
+
+    #[test]
+    fn test_parse_anchors_and_merge_keys() {
+        let proxies = parse_clash_yaml(ANCHORED_PROXIES).unwrap();
+        assert_eq!(proxies.len(), 2);
+
+        let hk = &proxies[0];
+        assert_eq!(hk.proxy_type, ProxyType::Shadowsocks);
+        assert_eq!(hk.remark, "HK 01");
+        assert_eq!(hk.port, 8388);
+        assert_eq!(hk.encrypt_method.as_deref(), Some("aes-128-gcm"));
+        assert_eq!(hk.password.as_deref(), Some("secret"));
+        assert_eq!(hk.udp, Some(true));
+
+        // Keys of the proxy win over the merged ones
+        let jp = &proxies[1];
+        assert_eq!(jp.port, 443);
+        assert_eq!(jp.encrypt_method.as_deref(), Some("chacha20-ietf-poly1305"));
+        assert_eq!(jp.password.as_deref(), Some("secret"));
+    }
+
+    #[test]
+    fn test_unknown_types_are_kept_verbatim() {
+        let proxies = parse_clash_yaml(
+            r#"
+proxies:
+  - {name: HK 01, type: ss, server: hk.example.com, port: 8388, cipher: aes-128-gcm, password: secret}
+  - {name: JP AnyTLS, type: anytls, server: jp.example.com, port: 443, password: secret, idle-session-timeout: 30}
+  - {type: mieru, server: us.example.com, port: 2999}
+"#,
+        )
+        .unwrap();
+        assert_eq!(proxies.len(), 2);
+
+        let anytls = &proxies[1];
+        assert_eq!(anytls.proxy_type, ProxyType::Unknown);
+        assert_eq!(anytls.remark, "JP AnyTLS");
+        assert_eq!(anytls.hostname, "jp.example.com");
+        assert_eq!(anytls.port, 443);
+        let raw = anytls.raw_passthrough.as_ref().unwrap();
+        assert_eq!(
+            raw.get("idle-session-timeout").and_then(|v| v.as_u64()),
+            Some(30)
+        );
+    }
+
+    fn alpn(proxy: &Proxy) -> Vec<&str> {
+        proxy.alpn.iter().map(String::as_str).collect()
+    }
+
+    #[test]
+    fn test_alpn_as_sequence_or_string() {
+        let source = r#"
+proxies:
+  - {name: HY, type: hysteria, server: hy.example.com, port: 443, auth-str: secret, up: 10, down: 50, alpn: [h3]}
+  - {name: HY2, type: hysteria2, server: hy2.example.com, port: 443, password: secret, alpn: [h3, h2]}
+  - {name: HY2 String, type: hysteria2, server: hy2.example.com, port: 443, password: secret, alpn: "h3, h2"}
+  - {name: VLESS, type: vless, server: vless.example.com, port: 443, uuid: b831381d-6324-4d53-ad4f-8cda48b30811, tls: true, alpn: h2}
+"#;
+        let proxies = parse_clash_yaml(source).unwrap();
+        assert_eq!(proxies.len(), 4);
+        assert_eq!(alpn(&proxies[0]), vec!["h3"]);
+        assert_eq!(alpn(&proxies[1]), vec!["h3", "h2"]);
+        assert_eq!(alpn(&proxies[2]), vec!["h3", "h2"]);
+        assert_eq!(proxies[3].proxy_type, ProxyType::Vless);
+
+        // The legacy key is read as well
+        let mut nodes = Vec::new();
+        let legacy = source.replace("proxies:", "Proxy:");
+        assert!(crate::parser::explodes::explode_clash(&legacy, &mut nodes).unwrap());
+        assert_eq!(alpn(&nodes[0]), vec!["h3"]);
+        assert_eq!(alpn(&nodes[1]), vec!["h3", "h2"]);
+        assert_eq!(alpn(&nodes[2]), vec!["h3", "h2"]);
+    }
+
+    #[test]
+    fn test_vmess_ws_opts() {
+        let proxies = parse_clash_yaml(
+            r#"
+proxies:
+  - {name: WS Opts, type: vmess, server: jp.example.com, port: 443, uuid: b831381d-6324-4d53-ad4f-8cda48b30811, alterId: 0, cipher: auto, tls: true, servername: jp.example.com, network: ws, ws-opts: {path: /ws, headers: {Host: jp.example.com}}}
+  - {name: WS Legacy, type: vmess, server: jp.example.com, port: 443, uuid: b831381d-6324-4d53-ad4f-8cda48b30811, alterId: 0, cipher: auto, network: ws, ws-path: /legacy, ws-headers: {Host: cdn.example.com}}
+"#,
+        )
+        .unwrap();
+        assert_eq!(proxies[0].path.as_deref(), Some("/ws"));
+        assert_eq!(proxies[0].host.as_deref(), Some("jp.example.com"));
+        assert!(proxies[0].tls_secure);
+        assert_eq!(proxies[0].server_name.as_deref(), Some("jp.example.com"));
+        assert_eq!(proxies[1].path.as_deref(), Some("/legacy"));
+        assert_eq!(proxies[1].host.as_deref(), Some("cdn.example.com"));
+    }
+
+    #[test]
+    fn test_legacy_key_keeps_unknown_types() {
+        let source = r#"
+Proxy:
+  - {name: HK 01, type: ss, server: hk.example.com, port: 8388, cipher: aes-128-gcm, password: secret}
+  - {name: JP AnyTLS, type: anytls, server: jp.example.com, port: 443, password: secret, dialer-proxy: HK 01}
+"#;
+        let proxies = parse_clash_yaml(source).unwrap();
+        assert_eq!(proxies.len(), 2);
+        let anytls = &proxies[1];
+        assert_eq!(anytls.proxy_type, ProxyType::Unknown);
+        assert_eq!(anytls.remark, "JP AnyTLS");
+        assert!(anytls.raw_passthrough.is_some());
+        assert_eq!(anytls.underlying_proxy.as_deref(), Some("HK 01"));
+
+        // Nor are they lost when the untyped parser reads the proxies one by one
+        let mut nodes = Vec::new();
+        let broken = format!("{}  - {{name: Broken, type: ss, port: [1]}}\n", source);
+        assert!(crate::parser::explodes::explode_clash(&broken, &mut nodes).unwrap());
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[1].remark, "JP AnyTLS");
+        assert!(nodes[1].raw_passthrough.is_some());
+    }
+}
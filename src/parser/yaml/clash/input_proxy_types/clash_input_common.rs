@@ -0,0 +1,105 @@
+use serde::Deserialize;
+
+use crate::models::proxy::Proxy;
+
+/// smux multiplexing options used by Clash.Meta
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SmuxOptions {
+    #[serde(default)]
+    pub enabled: Option<bool>,
+    #[serde(alias = "max_streams", default)]
+    pub max_streams: Option<u32>,
+}
+
+/// uTLS options inside a detailed TLS section
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct UtlsOptions {
+    #[serde(default)]
+    pub enabled: Option<bool>,
+    #[serde(default)]
+    pub fingerprint: Option<String>,
+}
+
+/// REALITY options of Clash.Meta, `reality-opts`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RealityOptions {
+    pub public_key: String,
+    #[serde(default)]
+    pub short_id: String,
+}
+
+/// Encrypted Client Hello options of Clash.Meta, `ech-opts`
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct EchOptions {
+    #[serde(default)]
+    pub enable: Option<bool>,
+    /// Base64 encoded ECHConfigList
+    #[serde(default)]
+    pub config: Option<String>,
+}
+
+/// The `tls` field, either a plain switch or a sing-box style section
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum TlsField {
+    Enabled(bool),
+    Detailed {
+        #[serde(default)]
+        enabled: Option<bool>,
+        #[serde(default)]
+        utls: Option<UtlsOptions>,
+    },
+}
+
+impl TlsField {
+    /// Whether TLS is turned on
+    pub fn enabled(&self) -> bool {
+        match self {
+            TlsField::Enabled(enabled) => *enabled,
+            TlsField::Detailed { enabled, .. } => enabled.unwrap_or(true),
+        }
+    }
+
+    /// uTLS fingerprint from a detailed section, if it is enabled
+    pub fn utls_fingerprint(&self) -> Option<String> {
+        match self {
+            TlsField::Detailed {
+                utls: Some(utls), ..
+            } if utls.enabled.unwrap_or(true) => utls.fingerprint.clone(),
+            _ => None,
+        }
+    }
+}
+
+/// Copy client fingerprint and smux settings onto a parsed proxy
+pub fn apply_meta_options(
+    proxy: &mut Proxy,
+    client_fingerprint: Option<String>,
+    tls: Option<&TlsField>,
+    smux: Option<SmuxOptions>,
+) {
+    proxy.client_fingerprint =
+        client_fingerprint.or_else(|| tls.and_then(TlsField::utls_fingerprint));
+    if let Some(smux) = smux {
+        proxy.smux_enabled = Some(smux.enabled.unwrap_or(false));
+        proxy.smux_max_streams = smux.max_streams;
+    }
+}
+
+/// Copy REALITY and ECH settings onto a parsed proxy
+pub fn apply_tls_extras(
+    proxy: &mut Proxy,
+    reality: Option<&RealityOptions>,
+    ech: Option<EchOptions>,
+) {
+    if let Some(reality) = reality {
+        proxy.reality_public_key = Some(reality.public_key.clone());
+        proxy.reality_short_id = Some(reality.short_id.clone());
+    }
+    if let Some(ech) = ech {
+        proxy.ech_enabled = Some(ech.enable.unwrap_or(false));
+        proxy.ech_config = ech.config.filter(|config| !config.is_empty());
+    }
+}
@@ -2,7 +2,8 @@ use serde::Deserialize;
 
 use crate::models::proxy::Proxy;
 use crate::models::proxy::ProxyType;
-use crate::utils::deserialize::deserialize_string_or_number;
+use crate::utils::deserialize::{deserialize_comma_list_or_seq, deserialize_string_or_number};
+use crate::utils::string::parse_mbps;
 use crate::utils::tribool::OptionSetExt;
 
 /// Represents a Hysteria proxy in Clash configuration
@@ -36,7 +37,7 @@ pub struct ClashInputHysteria {
     sni: Option<String>,
     #[serde(default)]
     fingerprint: Option<String>,
-    #[serde(default)]
+    #[serde(default, deserialize_with = "deserialize_comma_list_or_seq")]
     alpn: Option<Vec<String>>,
     #[serde(default)]
     ca: Option<String>,
@@ -173,13 +174,13 @@ impl Into<Proxy> for ClashInputHysteria {
 
         // Handle upload/download speed
         if let Some(up_value) = self.up {
-            proxy.up_speed = up_value.replace("Mbps", "").parse().unwrap_or(0);
+            proxy.up_speed = parse_mbps(&up_value);
         } else if let Some(up_speed) = self.up_speed {
             proxy.up_speed = up_speed;
         }
 
         if let Some(down_value) = self.down {
-            proxy.down_speed = down_value.replace("Mbps", "").parse().unwrap_or(0);
+            proxy.down_speed = parse_mbps(&down_value);
         } else if let Some(down_speed) = self.down_speed {
             proxy.down_speed = down_speed;
         }
@@ -195,7 +196,7 @@ impl Into<Proxy> for ClashInputHysteria {
         proxy.sni = self.sni;
         proxy.fingerprint = self.fingerprint;
 
-        // Handle alpn as a HashSet
+        // Handle alpn as an IndexSet
         if let Some(alpn_values) = self.alpn {
             for value in alpn_values {
                 proxy.alpn.insert(value);
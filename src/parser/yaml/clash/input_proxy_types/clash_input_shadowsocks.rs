@@ -1,9 +1,11 @@
 use std::collections::HashMap;
 
 use serde::Deserialize;
+use serde_yaml::Value;
 
 use crate::models::proxy::Proxy;
 use crate::models::proxy::ProxyType;
+use crate::utils::plugin_options::from_clash_plugin;
 use crate::utils::tribool::OptionSetExt;
 
 /// Represents a Shadowsocks proxy in Clash configuration
@@ -24,7 +26,7 @@ pub struct ClashInputShadowsocks {
     #[serde(default)]
     plugin: Option<String>,
     #[serde(alias = "plugin-opts", default)]
-    plugin_opts: Option<HashMap<String, String>>,
+    plugin_opts: Option<HashMap<String, Value>>,
 }
 
 impl ClashInputShadowsocks {
@@ -64,7 +66,7 @@ impl ClashInputShadowsocks {
         self.plugin.as_deref()
     }
 
-    pub fn plugin_opts(&self) -> Option<&HashMap<String, String>> {
+    pub fn plugin_opts(&self) -> Option<&HashMap<String, Value>> {
         self.plugin_opts.as_ref()
     }
 }
@@ -83,17 +85,13 @@ impl Into<Proxy> for ClashInputShadowsocks {
         proxy.allow_insecure.set_if_some(self.skip_cert_verify);
 
         if let Some(plugin_name) = self.plugin {
-            proxy.plugin = Some(plugin_name);
-            if let Some(opts) = self.plugin_opts {
-                let mut plugin_opts_str = String::new();
-                for (key, value) in opts {
-                    if !plugin_opts_str.is_empty() {
-                        plugin_opts_str.push(';');
-                    }
-                    plugin_opts_str.push_str(&format!("{}={}", key, value));
-                }
-                proxy.plugin_option = Some(plugin_opts_str);
-            }
+            let opts = self.plugin_opts.unwrap_or_default();
+            let (plugin, plugin_opts) = from_clash_plugin(
+                &plugin_name,
+                opts.iter().map(|(key, value)| (key.as_str(), value)),
+            );
+            proxy.plugin = Some(plugin);
+            proxy.plugin_option = Some(plugin_opts).filter(|opts| !opts.is_empty());
         }
 
         proxy
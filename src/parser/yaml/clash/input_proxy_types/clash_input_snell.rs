@@ -24,6 +24,10 @@ pub struct ClashInputSnell {
     udp: Option<bool>,
     #[serde(default)]
     tfo: Option<bool>,
+    #[serde(default)]
+    reuse: Option<bool>,
+    #[serde(default)]
+    ecn: Option<bool>,
 }
 
 impl ClashInputSnell {
@@ -62,6 +66,14 @@ impl ClashInputSnell {
     pub fn tfo(&self) -> Option<bool> {
         self.tfo
     }
+
+    pub fn reuse(&self) -> Option<bool> {
+        self.reuse
+    }
+
+    pub fn ecn(&self) -> Option<bool> {
+        self.ecn
+    }
 }
 
 impl Into<Proxy> for ClashInputSnell {
@@ -88,6 +100,8 @@ impl Into<Proxy> for ClashInputSnell {
 
         proxy.udp.set_if_some(self.udp);
         proxy.tcp_fast_open.set_if_some(self.tfo);
+        proxy.reuse = self.reuse;
+        proxy.ecn = self.ecn;
 
         proxy
     }
@@ -1,8 +1,10 @@
-
 use serde::Deserialize;
 
 use crate::models::proxy::Proxy;
 use crate::models::proxy::ProxyType;
+use crate::parser::yaml::clash::input_proxy_types::clash_input_common::{
+    apply_meta_options, apply_tls_extras, EchOptions, RealityOptions, SmuxOptions, TlsField,
+};
 use crate::utils::tribool::OptionSetExt;
 
 /// Represents a Trojan proxy in Clash configuration
@@ -23,6 +25,16 @@ pub struct ClashInputTrojan {
     network: Option<String>,
     #[serde(default)]
     sni: Option<String>,
+    #[serde(default)]
+    tls: Option<TlsField>,
+    #[serde(alias = "client-fingerprint", default)]
+    client_fingerprint: Option<String>,
+    #[serde(alias = "reality-opts", default)]
+    reality_opts: Option<RealityOptions>,
+    #[serde(alias = "ech-opts", default)]
+    ech_opts: Option<EchOptions>,
+    #[serde(default)]
+    smux: Option<SmuxOptions>,
 }
 
 impl ClashInputTrojan {
@@ -61,6 +73,10 @@ impl ClashInputTrojan {
     pub fn sni(&self) -> Option<&str> {
         self.sni.as_deref()
     }
+
+    pub fn client_fingerprint(&self) -> Option<&str> {
+        self.client_fingerprint.as_deref()
+    }
 }
 
 impl Into<Proxy> for ClashInputTrojan {
@@ -74,7 +90,18 @@ impl Into<Proxy> for ClashInputTrojan {
         proxy.udp.set_if_some(self.udp);
         proxy.tcp_fast_open.set_if_some(self.tfo);
         proxy.allow_insecure.set_if_some(self.skip_cert_verify);
+        // The host defaults to the SNI, as for trojan links
+        proxy.host = self.sni.clone();
         proxy.sni = self.sni;
+        // Trojan always runs over TLS unless it is turned off explicitly
+        proxy.tls_secure = self.tls.as_ref().is_none_or(TlsField::enabled);
+        apply_meta_options(
+            &mut proxy,
+            self.client_fingerprint,
+            self.tls.as_ref(),
+            self.smux,
+        );
+        apply_tls_extras(&mut proxy, self.reality_opts.as_ref(), self.ech_opts);
 
         if let Some(net) = self.network {
             proxy.transfer_protocol = Some(net);
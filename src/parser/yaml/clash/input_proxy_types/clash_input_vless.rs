@@ -1,11 +1,15 @@
-use std::collections::HashMap;
-
+use indexmap::IndexMap;
 use serde::Deserialize;
 
 use crate::models::proxy::Proxy;
 use crate::models::proxy::ProxyType;
 use crate::models::proxy_node::combined::CombinedProxy;
 use crate::models::proxy_node::vless::VlessProxy;
+use crate::parser::yaml::clash::input_proxy_types::clash_input_common::{
+    apply_meta_options, apply_tls_extras, EchOptions, RealityOptions, SmuxOptions, TlsField,
+};
+use crate::utils::deserialize::{deserialize_comma_list_or_seq, deserialize_string_or_seq};
+use crate::utils::tribool::OptionSetExt;
 
 /// Represents a VLESS proxy in Clash configuration
 #[derive(Debug, Clone, Deserialize)]
@@ -18,8 +22,8 @@ pub struct ClashInputVLess {
     #[serde(default)]
     flow: Option<String>,
     #[serde(default)]
-    tls: Option<bool>,
-    #[serde(default)]
+    tls: Option<TlsField>,
+    #[serde(default, deserialize_with = "deserialize_comma_list_or_seq")]
     alpn: Option<Vec<String>>,
     #[serde(default)]
     udp: Option<bool>,
@@ -33,6 +37,8 @@ pub struct ClashInputVLess {
     network: Option<String>,
     #[serde(alias = "reality-opts", default)]
     reality_opts: Option<RealityOptions>,
+    #[serde(alias = "ech-opts", default)]
+    ech_opts: Option<EchOptions>,
     #[serde(alias = "http-opts", default)]
     http_opts: Option<HttpOptions>,
     #[serde(alias = "h2-opts", default)]
@@ -44,7 +50,7 @@ pub struct ClashInputVLess {
     #[serde(alias = "ws-path", default)]
     ws_path: Option<String>,
     #[serde(alias = "ws-headers", default)]
-    ws_headers: Option<HashMap<String, String>>,
+    ws_headers: Option<IndexMap<String, String>>,
     #[serde(alias = "skip-cert-verify", default)]
     skip_cert_verify: Option<bool>,
     #[serde(default)]
@@ -53,6 +59,8 @@ pub struct ClashInputVLess {
     servername: Option<String>,
     #[serde(alias = "client-fingerprint", default)]
     client_fingerprint: Option<String>,
+    #[serde(default)]
+    smux: Option<SmuxOptions>,
 }
 
 impl ClashInputVLess {
@@ -77,7 +85,7 @@ impl ClashInputVLess {
     }
 
     pub fn tls(&self) -> Option<bool> {
-        self.tls
+        self.tls.as_ref().map(TlsField::enabled)
     }
 
     pub fn alpn(&self) -> Option<&Vec<String>> {
@@ -128,7 +136,7 @@ impl ClashInputVLess {
         self.ws_path.as_deref()
     }
 
-    pub fn ws_headers(&self) -> Option<&HashMap<String, String>> {
+    pub fn ws_headers(&self) -> Option<&IndexMap<String, String>> {
         self.ws_headers.as_ref()
     }
 
@@ -149,26 +157,16 @@ impl ClashInputVLess {
     }
 }
 
-/// Reality options for VLESS proxy
-#[derive(Debug, Clone, Deserialize)]
-#[serde(rename_all = "kebab-case")]
-pub struct RealityOptions {
-    #[serde(rename = "public-key")]
-    pub public_key: String,
-    #[serde(rename = "short-id")]
-    pub short_id: String,
-}
-
 /// HTTP options for VLESS proxy
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct HttpOptions {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub method: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, deserialize_with = "deserialize_string_or_seq")]
     pub path: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub headers: Option<HashMap<String, Vec<String>>>,
+    pub headers: Option<IndexMap<String, Vec<String>>>,
 }
 
 /// HTTP2 options for VLESS proxy
@@ -177,7 +175,7 @@ pub struct HttpOptions {
 pub struct H2Options {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub path: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, deserialize_with = "deserialize_string_or_seq")]
     pub host: Option<Vec<String>>,
 }
 
@@ -196,7 +194,7 @@ pub struct WsOptions {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub path: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub headers: Option<HashMap<String, String>>,
+    pub headers: Option<IndexMap<String, String>>,
     #[serde(rename = "max-early-data", skip_serializing_if = "Option::is_none")]
     pub max_early_data: Option<i32>,
     #[serde(
@@ -221,7 +219,9 @@ impl Into<Proxy> for ClashInputVLess {
         let mut vless_proxy = VlessProxy::default();
         vless_proxy.uuid = self.uuid;
         vless_proxy.flow = self.flow;
-        vless_proxy.tls = self.tls.unwrap_or(false);
+        vless_proxy.tls = self.tls.as_ref().is_some_and(TlsField::enabled);
+        proxy.udp.set_if_some(self.udp);
+        proxy.allow_insecure.set_if_some(self.skip_cert_verify);
         vless_proxy.udp = self.udp.unwrap_or(true);
         vless_proxy.packet_addr = self.packet_addr;
         vless_proxy.xudp = self.xudp;
@@ -230,7 +230,14 @@ impl Into<Proxy> for ClashInputVLess {
         vless_proxy.skip_cert_verify = self.skip_cert_verify;
         vless_proxy.fingerprint = self.fingerprint;
         vless_proxy.servername = self.servername;
-        vless_proxy.client_fingerprint = self.client_fingerprint;
+        apply_meta_options(
+            &mut proxy,
+            self.client_fingerprint,
+            self.tls.as_ref(),
+            self.smux,
+        );
+        vless_proxy.client_fingerprint = proxy.client_fingerprint.clone();
+        apply_tls_extras(&mut proxy, self.reality_opts.as_ref(), self.ech_opts);
 
         // Handle ALPN
         if let Some(alpn_values) = self.alpn {
@@ -4,6 +4,10 @@ use serde::Deserialize;
 
 use crate::models::proxy::Proxy;
 use crate::models::proxy::ProxyType;
+use crate::parser::yaml::clash::input_proxy_types::clash_input_common::{
+    apply_meta_options, apply_tls_extras, EchOptions, RealityOptions, SmuxOptions, TlsField,
+};
+use crate::parser::yaml::clash::input_proxy_types::clash_input_vless::WsOptions;
 use crate::utils::tribool::OptionSetExt;
 
 /// Represents a VMess proxy in Clash configuration
@@ -25,14 +29,24 @@ pub struct ClashInputVMess {
     skip_cert_verify: Option<bool>,
     #[serde(default)]
     network: Option<String>,
+    #[serde(alias = "ws-opts", default)]
+    ws_opts: Option<WsOptions>,
     #[serde(alias = "ws-path", default)]
     ws_path: Option<String>,
     #[serde(alias = "ws-headers", default)]
     ws_headers: Option<HashMap<String, String>>,
     #[serde(default)]
-    tls: Option<bool>,
+    tls: Option<TlsField>,
     #[serde(alias = "servername", default)]
     servername: Option<String>,
+    #[serde(alias = "client-fingerprint", default)]
+    client_fingerprint: Option<String>,
+    #[serde(alias = "reality-opts", default)]
+    reality_opts: Option<RealityOptions>,
+    #[serde(alias = "ech-opts", default)]
+    ech_opts: Option<EchOptions>,
+    #[serde(default)]
+    smux: Option<SmuxOptions>,
 }
 
 impl ClashInputVMess {
@@ -85,12 +99,16 @@ impl ClashInputVMess {
     }
 
     pub fn tls(&self) -> Option<bool> {
-        self.tls
+        self.tls.as_ref().map(TlsField::enabled)
     }
 
     pub fn servername(&self) -> Option<&str> {
         self.servername.as_deref()
     }
+
+    pub fn client_fingerprint(&self) -> Option<&str> {
+        self.client_fingerprint.as_deref()
+    }
 }
 
 impl Into<Proxy> for ClashInputVMess {
@@ -106,18 +124,35 @@ impl Into<Proxy> for ClashInputVMess {
         proxy.udp.set_if_some(self.udp);
         proxy.tcp_fast_open.set_if_some(self.tfo);
         proxy.allow_insecure.set_if_some(self.skip_cert_verify);
-        proxy.tls_secure = self.tls.unwrap_or(false);
+        proxy.tls_secure = self.tls.as_ref().is_some_and(TlsField::enabled);
         proxy.server_name = self.servername;
+        apply_meta_options(
+            &mut proxy,
+            self.client_fingerprint,
+            self.tls.as_ref(),
+            self.smux,
+        );
+        apply_tls_extras(&mut proxy, self.reality_opts.as_ref(), self.ech_opts);
+
+        // `ws-opts` wins over the legacy `ws-path` and `ws-headers`
+        let (ws_path, ws_headers) = match self.ws_opts {
+            Some(opts) => (
+                opts.path,
+                opts.headers
+                    .map(|headers| headers.into_iter().collect::<HashMap<_, _>>()),
+            ),
+            None => (self.ws_path, self.ws_headers),
+        };
 
         // Network protocol handling
         if let Some(net) = self.network {
             proxy.transfer_protocol = Some(net.clone());
             match net.as_str() {
                 "ws" => {
-                    if let Some(path) = self.ws_path {
+                    if let Some(path) = ws_path {
                         proxy.path = Some(path);
                     }
-                    if let Some(headers) = self.ws_headers {
+                    if let Some(headers) = ws_headers {
                         if let Some(host) = headers.get("Host") {
                             proxy.host = Some(host.clone());
                         }
@@ -127,10 +162,10 @@ impl Into<Proxy> for ClashInputVMess {
                     }
                 }
                 "http" => {
-                    if let Some(path) = self.ws_path {
+                    if let Some(path) = ws_path {
                         proxy.path = Some(path);
                     }
-                    if let Some(headers) = self.ws_headers {
+                    if let Some(headers) = ws_headers {
                         if let Some(host) = headers.get("Host") {
                             proxy.host = Some(host.clone());
                         }
@@ -140,17 +175,17 @@ impl Into<Proxy> for ClashInputVMess {
                     }
                 }
                 "h2" => {
-                    if let Some(path) = self.ws_path {
+                    if let Some(path) = ws_path {
                         proxy.path = Some(path);
                     }
-                    if let Some(headers) = self.ws_headers {
+                    if let Some(headers) = ws_headers {
                         if let Some(host) = headers.get("Host") {
                             proxy.host = Some(host.clone());
                         }
                     }
                 }
                 "grpc" => {
-                    if let Some(path) = self.ws_path {
+                    if let Some(path) = ws_path {
                         proxy.path = Some(path);
                     }
                     proxy.host = Some(self.server);
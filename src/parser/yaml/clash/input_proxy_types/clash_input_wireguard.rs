@@ -1,5 +1,4 @@
-use std::collections::HashSet;
-
+use indexmap::IndexSet;
 use serde::Deserialize;
 
 use crate::models::proxy::Proxy;
@@ -102,8 +101,8 @@ impl Into<Proxy> for ClashInputWireGuard {
         proxy.self_ipv6 = self.ipv6;
         proxy.pre_shared_key = self.preshared_key;
 
-        // Convert Vec<String> to HashSet<String> for dns_servers
-        let mut dns_set = HashSet::new();
+        // Convert Vec<String> to IndexSet<String> for dns_servers
+        let mut dns_set = IndexSet::new();
         if let Some(dns_servers) = self.dns {
             for dns_server in dns_servers {
                 dns_set.insert(dns_server);
@@ -4,5 +4,5 @@ mod clash_proxy_types;
 mod input_proxy_types;
 
 pub use clash_input::ClashYamlInput;
-pub use clash_parsers::parse_clash_yaml;
+pub use clash_parsers::{apply_dial_options, load_clash_yaml, parse_clash_yaml, passthrough_proxy};
 pub use clash_proxy_types::ClashProxyYamlInput;
@@ -0,0 +1,103 @@
+//! The public API of the library
+//!
+//! Everything needed to convert subscriptions from another crate is
+//! re-exported here, `use subconverter::prelude::*;` is enough for the common
+//! workflows. Items in this module follow semver: they are only removed or
+//! changed in a breaking way with a new minor version while the crate is at
+//! `0.x`. Paths into the other modules are internal and may change at any time.
+//!
+//! # Convert a subscription to a Clash configuration
+//!
+//! ```no_run
+//! use subconverter::prelude::*;
+//!
+//! async fn to_clash(url: &str) -> Result<String, String> {
+//!     let mut builder = SubconverterConfigBuilder::new();
+//!     builder.target(SubconverterTarget::Clash).add_url(url);
+//!     let result = subconverter(builder.build()?).await?;
+//!     Ok(result.content)
+//! }
+//! ```
+//!
+//! # Parse a subscription into nodes
+//!
+//! ```no_run
+//! use subconverter::prelude::*;
+//!
+//! async fn hong_kong_nodes(url: &str) -> Result<Vec<Proxy>, String> {
+//!     let options = ParseOptions {
+//!         include_remarks: vec!["(HK|Hong Kong)".to_string()],
+//!         ..Default::default()
+//!     };
+//!     let nodes = parse_subscription(url, options, 0).await?;
+//!     Ok(nodes
+//!         .into_iter()
+//!         .filter(|node| node.proxy_type != ProxyType::ShadowsocksR)
+//!         .collect())
+//! }
+//! ```
+//!
+//! # Build custom proxy groups
+//!
+//! ```no_run
+//! use subconverter::prelude::*;
+//!
+//! async fn with_groups(url: &str) -> Result<SubconverterResult, String> {
+//!     let groups = vec![
+//!         ProxyGroupConfig {
+//!             name: "Auto".to_string(),
+//!             group_type: ProxyGroupType::URLTest,
+//!             proxies: vec![".*".to_string()],
+//!             url: "http://www.gstatic.com/generate_204".to_string(),
+//!             interval: 300,
+//!             ..Default::default()
+//!         },
+//!         ProxyGroupConfig {
+//!             name: "Proxy".to_string(),
+//!             proxies: vec!["[]Auto".to_string(), "[]DIRECT".to_string()],
+//!             ..Default::default()
+//!         },
+//!     ];
+//!
+//!     let mut builder = SubconverterConfigBuilder::new();
+//!     builder
+//!         .target(SubconverterTarget::Surge(4))
+//!         .add_url(url)
+//!         .proxy_groups(groups);
+//!     Ok(subconverter(builder.build()?).await?)
+//! }
+//! ```
+//!
+//! # Reuse the parsed nodes for several targets
+//!
+//! ```no_run
+//! use subconverter::prelude::*;
+//!
+//! async fn clash_and_surge(config: &SubconverterConfig) -> Result<(String, String), String> {
+//!     let node_set = process_nodes(fetch_nodes(config).await?, config).await?;
+//!     let clash = generate(&node_set, &SubconverterTarget::Clash, config).await?;
+//!     let surge = generate(&node_set, &SubconverterTarget::Surge(4), config).await?;
+//!     Ok((clash.content, surge.content))
+//! }
+//! ```
+
+pub use crate::interfaces::subconverter::{
+    apply_source_groups, fetch_nodes, generate, parse_subscription, parse_subscription_with_info,
+    process_nodes, subconverter, subconverter_batch, NodeSet, ParseOptions, ParsedSubscription,
+    SubconverterConfig, SubconverterConfigBuilder, SubconverterResult,
+};
+pub use crate::interfaces::validation::{
+    ConfigValidationError, ValidationIssue, ValidationSeverity,
+};
+pub use crate::interfaces::ConversionError;
+pub use crate::models::{
+    ConversionWarning, ExtraSettings, NodeFilter, NodeTransform, Proxy, ProxyGroupConfig,
+    ProxyGroupConfigs, ProxyGroupType, ProxyType, SubconverterTarget, WarningKind,
+};
+pub use crate::settings::settings::settings_struct::{
+    update_settings_from_content, update_settings_from_file,
+};
+pub use crate::settings::Settings;
+pub use crate::utils::fetcher::{
+    FetchOptions, FetchedBody, FetcherRouter, MemoryFetcher, SubscriptionFetcher,
+};
@@ -0,0 +1,302 @@
+//! Background refresh of the rulesets configured in the settings
+//!
+//! The refresher loads the rulesets of the settings when the server starts and
+//! re-fetches each remote ruleset once its update interval elapsed. Every
+//! round builds a new snapshot and swaps it in as a whole, so a request that
+//! took a snapshot keeps a consistent set of rulesets while a refresh runs.
+//! A ruleset that fails to refresh keeps its previous content.
+
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use log::{debug, info, warn};
+use serde::Serialize;
+
+use crate::models::ruleset::RulesetContent;
+use crate::models::RulesetConfig;
+use crate::rulesets::ruleset::load_ruleset;
+use crate::utils::http::parse_proxy;
+use crate::utils::system::safe_system_time;
+use crate::Settings;
+
+/// How often the refresher looks for rulesets due for a refresh
+pub const REFRESH_TICK: Duration = Duration::from_secs(60);
+
+/// A configured ruleset and the result of its last refresh
+#[derive(Debug, Clone)]
+pub struct RulesetEntry {
+    pub config: RulesetConfig,
+    pub content: Option<RulesetContent>,
+    pub last_refresh: Option<SystemTime>,
+    pub last_error: Option<String>,
+}
+
+/// The rulesets of one refresh round
+#[derive(Debug, Clone, Default)]
+pub struct RulesetSnapshot {
+    pub entries: Vec<RulesetEntry>,
+}
+
+impl RulesetSnapshot {
+    /// Whether the snapshot holds exactly the rulesets in `configs`
+    pub fn matches(&self, configs: &[RulesetConfig]) -> bool {
+        self.entries.len() == configs.len()
+            && self
+                .entries
+                .iter()
+                .zip(configs)
+                .all(|(entry, config)| entry.config == *config)
+    }
+
+    /// Loaded contents for `configs`, `None` when the snapshot holds other rulesets
+    ///
+    /// Inline rules are placed first like `refresh_rulesets` does, rulesets
+    /// that never loaded are left out.
+    pub fn contents_for(&self, configs: &[RulesetConfig]) -> Option<Vec<RulesetContent>> {
+        if configs.is_empty() || !self.matches(configs) {
+            return None;
+        }
+        let (inline, fetched): (Vec<&RulesetEntry>, Vec<&RulesetEntry>) = self
+            .entries
+            .iter()
+            .partition(|entry| entry.config.url.contains("[]"));
+        Some(
+            inline
+                .into_iter()
+                .chain(fetched)
+                .filter_map(|entry| entry.content.clone())
+                .collect(),
+        )
+    }
+}
+
+/// Holder of the current snapshot, swapped as a whole by each refresh round
+#[derive(Debug, Default)]
+pub struct RulesetStore {
+    current: RwLock<Arc<RulesetSnapshot>>,
+}
+
+impl RulesetStore {
+    /// The current snapshot, unaffected by later swaps
+    pub fn snapshot(&self) -> Arc<RulesetSnapshot> {
+        match self.current.read() {
+            Ok(guard) => guard.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    /// Replace the current snapshot, returning the previous one
+    pub fn swap(&self, snapshot: RulesetSnapshot) -> Arc<RulesetSnapshot> {
+        let mut guard = match self.current.write() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        std::mem::replace(&mut *guard, Arc::new(snapshot))
+    }
+}
+
+static RULESET_STORE: OnceLock<RulesetStore> = OnceLock::new();
+
+/// The store shared by the refresher and the requests
+pub fn ruleset_store() -> &'static RulesetStore {
+    RULESET_STORE.get_or_init(RulesetStore::default)
+}
+
+/// Whether a ruleset refreshed at `last_refresh` is due at `now`
+///
+/// Rulesets that never loaded are always due, an interval of 0 means the
+/// ruleset is loaded once and never refreshed.
+pub fn should_refresh(last_refresh: Option<SystemTime>, interval: u32, now: SystemTime) -> bool {
+    match last_refresh {
+        None => true,
+        Some(_) if interval == 0 => false,
+        Some(last) => {
+            now.duration_since(last).unwrap_or_default() >= Duration::from_secs(u64::from(interval))
+        }
+    }
+}
+
+/// Run one refresh round over `configs` and swap in the result
+///
+/// Entries of the current snapshot are reused for configs that did not
+/// change. Returns the number of rulesets that were fetched successfully.
+pub async fn refresh_due_rulesets(store: &RulesetStore, configs: &[RulesetConfig]) -> usize {
+    let previous = store.snapshot();
+    let (proxy, max_size, async_fetch) = {
+        let settings = Settings::current();
+        (
+            parse_proxy(&settings.proxy_ruleset),
+            settings.max_allowed_download_size,
+            settings.async_fetch_ruleset,
+        )
+    };
+
+    let mut refreshed = 0;
+    let mut entries = Vec::with_capacity(configs.len());
+    for config in configs {
+        let mut entry = previous
+            .entries
+            .iter()
+            .find(|entry| entry.config == *config)
+            .cloned()
+            .unwrap_or_else(|| RulesetEntry {
+                config: config.clone(),
+                content: None,
+                last_refresh: None,
+                last_error: None,
+            });
+
+        let now = safe_system_time();
+        if should_refresh(entry.last_refresh, config.interval, now) {
+            // Bypass the cache, a due ruleset has to come from its source
+            match load_ruleset(config, &proxy, max_size, 0, async_fetch).await {
+                Ok(content) => {
+                    debug!("Refreshed ruleset '{}'", config.url);
+                    entry.content = Some(content);
+                    entry.last_refresh = Some(now);
+                    entry.last_error = None;
+                    refreshed += 1;
+                }
+                Err(e) => {
+                    warn!(
+                        "Failed to refresh ruleset '{}', keeping the previous content: {}",
+                        config.url, e
+                    );
+                    entry.last_error = Some(e);
+                }
+            }
+        }
+        entries.push(entry);
+    }
+
+    store.swap(RulesetSnapshot { entries });
+    refreshed
+}
+
+/// Refresh the rulesets of the settings forever, meant to be spawned as a background task
+///
+/// The rulesets are re-read from the settings every round so a settings
+/// reload is picked up on the next tick.
+pub async fn run_ruleset_refresher() {
+    let store = ruleset_store();
+    loop {
+        let configs = Settings::current().custom_rulesets.clone();
+        let refreshed = refresh_due_rulesets(store, &configs).await;
+        if refreshed > 0 {
+            info!("Refreshed {} of {} rulesets", refreshed, configs.len());
+        }
+        tokio::time::sleep(REFRESH_TICK).await;
+    }
+}
+
+/// Refresh state of a ruleset as reported by the status endpoint
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct RulesetRefreshStatus {
+    pub group: String,
+    pub url: String,
+    pub interval: u32,
+    pub loaded: bool,
+    /// Unix time of the last successful refresh
+    pub last_refresh: Option<u64>,
+    pub last_error: Option<String>,
+}
+
+/// Refresh state of the rulesets in the current snapshot
+pub fn ruleset_refresh_status() -> Vec<RulesetRefreshStatus> {
+    ruleset_store()
+        .snapshot()
+        .entries
+        .iter()
+        .map(|entry| RulesetRefreshStatus {
+            group: entry.config.group.clone(),
+            url: entry.config.url.clone(),
+            interval: entry.config.interval,
+            loaded: entry.content.is_some(),
+            last_refresh: entry.last_refresh.and_then(|time| {
+                time.duration_since(UNIX_EPOCH)
+                    .ok()
+                    .map(|elapsed| elapsed.as_secs())
+            }),
+            last_error: entry.last_error.clone(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(group: &str, url: &str, interval: u32) -> RulesetConfig {
+        RulesetConfig {
+            group: group.to_string(),
+            url: url.to_string(),
+            interval,
+        }
+    }
+
+    fn loaded_entry(config: &RulesetConfig, content: &str) -> RulesetEntry {
+        let mut ruleset = RulesetContent::new("", &config.group);
+        ruleset.set_rule_content(content);
+        RulesetEntry {
+            config: config.clone(),
+            content: Some(ruleset),
+            last_refresh: Some(SystemTime::now()),
+            last_error: None,
+        }
+    }
+
+    #[test]
+    fn test_should_refresh() {
+        let now = SystemTime::now();
+        let hour_ago = now - Duration::from_secs(3600);
+        assert!(should_refresh(None, 0, now));
+        assert!(should_refresh(None, 86400, now));
+        assert!(!should_refresh(Some(hour_ago), 0, now));
+        assert!(!should_refresh(Some(hour_ago), 7200, now));
+        assert!(should_refresh(Some(hour_ago), 3600, now));
+        assert!(should_refresh(Some(hour_ago), 600, now));
+        // A clock going backwards does not trigger a refresh
+        assert!(!should_refresh(
+            Some(now + Duration::from_secs(60)),
+            600,
+            now
+        ));
+    }
+
+    #[test]
+    fn test_swap_keeps_snapshots_consistent() {
+        let configs = vec![config("DIRECT", "[]GEOIP,CN", 0)];
+        let store = RulesetStore::default();
+        assert!(store.snapshot().contents_for(&configs).is_none());
+
+        let refreshed = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(refresh_due_rulesets(&store, &configs));
+        assert_eq!(refreshed, 1);
+
+        let before = store.snapshot();
+        let contents = before.contents_for(&configs).unwrap();
+        assert_eq!(contents[0].get_rule_content(), "[]GEOIP,CN");
+
+        let previous = store.swap(RulesetSnapshot {
+            entries: vec![loaded_entry(&configs[0], "[]FINAL")],
+        });
+
+        // Holders of the old snapshot still see the old content
+        assert!(Arc::ptr_eq(&previous, &before));
+        assert_eq!(
+            before.contents_for(&configs).unwrap()[0].get_rule_content(),
+            "[]GEOIP,CN"
+        );
+        assert_eq!(
+            store.snapshot().contents_for(&configs).unwrap()[0].get_rule_content(),
+            "[]FINAL"
+        );
+        assert!(store
+            .snapshot()
+            .contents_for(&[config("Proxy", "[]FINAL", 0)])
+            .is_none());
+    }
+}
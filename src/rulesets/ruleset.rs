@@ -1,20 +1,22 @@
-use std::future::Future;
-use std::pin::Pin;
-
 use log::{debug, info, warn};
 
-use crate::models::ruleset::{get_ruleset_type_from_url, RulesetContent, RulesetType};
+use crate::models::ruleset::{
+    get_ruleset_type_from_url, map_rule_group, RulesetContent, RulesetType,
+};
 use crate::models::RulesetConfig;
 use crate::utils::file::read_file_async;
 use crate::utils::file_exists;
-use crate::utils::http::{parse_proxy, web_get_async, ProxyConfig};
+use crate::utils::http::{parse_proxy, web_get_async_limited, ProxyConfig};
 use crate::utils::memory_cache;
 use crate::Settings;
 
 /// Fetch ruleset content from file or URL with async operations
+///
+/// Downloads larger than `max_size` bytes are refused, 0 means no limit.
 pub async fn fetch_ruleset(
     url: &str,
     proxy: &ProxyConfig,
+    max_size: i64,
     cache_timeout: u32,
     _async_fetch: bool,
 ) -> Result<String, String> {
@@ -54,7 +56,7 @@ pub async fn fetch_ruleset(
     }
 
     // For URLs, fetch content and cache
-    match fetch_from_url(url, proxy).await {
+    match fetch_from_url(url, proxy, max_size).await {
         Ok(content) => {
             // Store in memory cache if caching is enabled
             if cache_timeout > 0 {
@@ -69,154 +71,160 @@ pub async fn fetch_ruleset(
 }
 
 /// Helper function to fetch content from URL asynchronously
-async fn fetch_from_url(url: &str, proxy: &ProxyConfig) -> Result<String, String> {
+async fn fetch_from_url(url: &str, proxy: &ProxyConfig, max_size: i64) -> Result<String, String> {
     debug!("Fetching ruleset from URL: {}", url);
-    match web_get_async(url, proxy, None).await {
+    match web_get_async_limited(url, proxy, None, max_size).await {
         Ok(response) => Ok(response.body),
-        Err(e) => Err(e.message),
+        Err(e) => Err(format!(
+            "Failed to fetch ruleset from URL {}: {}",
+            url, e.message
+        )),
     }
 }
 
-/// Refresh rulesets based on configuration
-pub async fn refresh_rulesets(
-    ruleset_list: &[RulesetConfig],
-    ruleset_content_array: &mut Vec<RulesetContent>,
-) {
-    // Clear existing ruleset content
-    ruleset_content_array.clear();
-
-    // Get global settings
-    let settings = Settings::current();
-    let proxy = parse_proxy(&settings.proxy_ruleset);
-
-    // Collect inline rules first (these don't need fetching)
-    for ruleset_config in ruleset_list {
-        let rule_group = &ruleset_config.group;
-        let rule_url = &ruleset_config.url;
+/// Load the content of a single ruleset
+///
+/// Inline rules (`[]` prefix) are used as is, other rulesets are read from a
+/// file or fetched from their URL with the ruleset type prefix stripped.
+pub async fn load_ruleset(
+    ruleset_config: &RulesetConfig,
+    proxy: &ProxyConfig,
+    max_size: i64,
+    cache_timeout: u32,
+    async_fetch: bool,
+) -> Result<RulesetContent, String> {
+    let rule_group = &ruleset_config.group;
+    let rule_url = &ruleset_config.url;
+
+    // Check if it's an inline rule (with [] prefix)
+    if let Some(pos) = rule_url.find("[]") {
+        info!(
+            "Adding rule '{}' with group '{}'",
+            &rule_url[pos + 2..],
+            rule_group
+        );
+
+        let mut ruleset = RulesetContent::new("", rule_group);
+        ruleset.set_rule_content(&rule_url[pos..]);
+        return Ok(ruleset);
+    }
 
-        // Check if it's an inline rule (with [] prefix)
-        if let Some(pos) = rule_url.find("[]") {
+    // Determine ruleset type from URL and trim its prefix
+    let (fetch_url, url_type) = match get_ruleset_type_from_url(rule_url) {
+        Some(detected_type) => {
+            let prefix_len = crate::models::ruleset::RULESET_TYPES
+                .iter()
+                .find(|(prefix, prefix_type)| {
+                    rule_url.starts_with(prefix.as_str()) && **prefix_type == detected_type
+                })
+                .map(|(prefix, _)| prefix.len())
+                .unwrap_or_default();
             info!(
-                "Adding rule '{}' with group '{}'",
-                &rule_url[pos + 2..],
+                "Preparing {} ruleset URL '{}' with group '{}'",
+                &rule_url[..prefix_len],
+                &rule_url[prefix_len..],
                 rule_group
             );
-
-            let mut ruleset = RulesetContent::new("", rule_group);
-            ruleset.set_rule_content(&rule_url[pos..]);
-            ruleset_content_array.push(ruleset);
-        }
-    }
-
-    // Create a vector of boxed futures for parallel ruleset fetching
-    let mut fetch_futures: Vec<Pin<Box<dyn Future<Output = FetchResult> + 'static>>> = Vec::new();
-
-    // Prepare futures for all non-inline rulesets
-    for ruleset_config in ruleset_list {
-        let rule_group = ruleset_config.group.clone();
-        let rule_url = ruleset_config.url.clone();
-        let interval = ruleset_config.interval;
-
-        // Skip inline rules
-        if rule_url.contains("[]") {
-            continue;
+            (&rule_url[prefix_len..], detected_type)
         }
-
-        // Determine ruleset type from URL
-        if let Some(detected_type) = get_ruleset_type_from_url(&rule_url) {
-            // Find prefix length and trim it from the URL
-            for (prefix, prefix_type) in crate::models::ruleset::RULESET_TYPES.iter() {
-                if rule_url.starts_with(prefix) && *prefix_type == detected_type {
-                    let rule_url_without_prefix = rule_url[prefix.len()..].to_string();
-
-                    info!(
-                        "Preparing {} ruleset URL '{}' with group '{}'",
-                        prefix, rule_url_without_prefix, rule_group
-                    );
-
-                    // Clone needed values for the future closure
-                    let proxy_clone = proxy.clone();
-                    let cache_ruleset = settings.cache_ruleset;
-                    let async_fetch = settings.async_fetch_ruleset;
-                    let fetch_url = rule_url_without_prefix.clone();
-
-                    // Create the future and box it
-                    let future = async move {
-                        let content =
-                            fetch_ruleset(&fetch_url, &proxy_clone, cache_ruleset, async_fetch)
-                                .await;
-
-                        FetchResult {
-                            url: fetch_url,
-                            group: rule_group,
-                            original_url: rule_url,
-                            url_type: detected_type,
-                            interval,
-                            content: content.ok(),
-                        }
-                    };
-
-                    fetch_futures.push(Box::pin(future));
-                    break;
-                }
-            }
-        } else {
+        None => {
             // No special prefix, use default type
             info!(
                 "Preparing ruleset URL '{}' with group '{}'",
                 rule_url, rule_group
             );
+            (rule_url.as_str(), RulesetType::default())
+        }
+    };
 
-            // Clone needed values for the future closure
-            let proxy_clone = proxy.clone();
-            let cache_ruleset = settings.cache_ruleset;
-            let async_fetch = settings.async_fetch_ruleset;
-            let fetch_url = rule_url.clone();
-
-            // Create the future and box it
-            let future = async move {
-                let content =
-                    fetch_ruleset(&fetch_url, &proxy_clone, cache_ruleset, async_fetch).await;
-
-                FetchResult {
-                    url: fetch_url.clone(),
-                    group: rule_group,
-                    original_url: fetch_url,
-                    url_type: RulesetType::default(),
-                    interval,
-                    content: content.ok(),
-                }
-            };
+    let content = fetch_ruleset(fetch_url, proxy, max_size, cache_timeout, async_fetch).await?;
+
+    let mut ruleset = RulesetContent::new(fetch_url, rule_group);
+    ruleset.rule_path_typed = rule_url.clone();
+    ruleset.rule_type = url_type;
+    ruleset.update_interval = ruleset_config.interval;
+    ruleset.set_rule_content(&content);
+    Ok(ruleset)
+}
+
+/// Refresh rulesets based on configuration
+///
+/// The proxy and caching of the downloads are taken from `settings`.
+pub async fn refresh_rulesets(
+    ruleset_list: &[RulesetConfig],
+    ruleset_content_array: &mut Vec<RulesetContent>,
+    settings: &Settings,
+) {
+    // Clear existing ruleset content
+    ruleset_content_array.clear();
 
-            fetch_futures.push(Box::pin(future));
+    let proxy = parse_proxy(&settings.proxy_ruleset);
+    let max_size = settings.max_allowed_download_size;
+    let cache_ruleset = settings.cache_ruleset;
+    let async_fetch = settings.async_fetch_ruleset;
+
+    // Inline rules come first, then the fetched rulesets in their configured order
+    let (inline, fetched): (Vec<&RulesetConfig>, Vec<&RulesetConfig>) = ruleset_list
+        .iter()
+        .partition(|ruleset_config| ruleset_config.url.contains("[]"));
+    for ruleset_config in inline.into_iter().chain(fetched) {
+        match load_ruleset(ruleset_config, &proxy, max_size, cache_ruleset, async_fetch).await {
+            Ok(ruleset) => ruleset_content_array.push(ruleset),
+            Err(e) => warn!("Failed to load ruleset '{}': {}", ruleset_config.url, e),
         }
     }
+}
+
+/// Policies every client understands without a matching proxy group
+const BUILTIN_POLICIES: [&str; 7] = [
+    "DIRECT",
+    "REJECT",
+    "REJECT-TINYGIF",
+    "REJECT-DROP",
+    "REJECT-NO-DROP",
+    "PASS",
+    "COMPATIBLE",
+];
+
+/// Whether `name` is a policy such as `DIRECT` that needs no proxy group
+pub fn is_builtin_policy(name: &str) -> bool {
+    BUILTIN_POLICIES
+        .iter()
+        .any(|policy| policy.eq_ignore_ascii_case(name))
+}
 
-    // Process each future sequentially (could be optimized to process in batches)
-    for future in fetch_futures {
-        let result = future.await;
-        if let Some(content) = result.content {
-            // Set ruleset properties
-            let mut ruleset = RulesetContent::new(&result.url, &result.group);
-            ruleset.rule_path_typed = result.original_url;
-            ruleset.rule_type = result.url_type;
-            ruleset.update_interval = result.interval;
-
-            // Set rule content
-            ruleset.set_rule_content(&content);
-            ruleset_content_array.push(ruleset);
+/// Rename the groups of the rulesets through the `rename_rule_group` table
+///
+/// Groups without a matching entry are left untouched.
+pub fn remap_rule_groups(ruleset_content: &mut [RulesetContent], map: &[(String, String)]) {
+    if map.is_empty() {
+        return;
+    }
+    for ruleset in ruleset_content.iter_mut() {
+        if let Some(group) = map_rule_group(&ruleset.group, map) {
+            debug!("Mapping ruleset group '{}' to '{}'", ruleset.group, group);
+            ruleset.group = group.to_string();
         }
     }
 }
 
-/// Helper struct to store fetch results and metadata
-struct FetchResult {
-    url: String,
-    group: String,
-    original_url: String,
-    url_type: RulesetType,
-    interval: u32,
-    content: Option<String>,
+/// Groups referenced by rulesets that are neither in `group_names` nor a built-in policy
+pub fn missing_rule_groups(
+    ruleset_content: &[RulesetContent],
+    group_names: &[&str],
+) -> Vec<String> {
+    let mut missing: Vec<String> = Vec::new();
+    for ruleset in ruleset_content {
+        let group = ruleset.group.as_str();
+        if group_names.contains(&group)
+            || is_builtin_policy(group)
+            || missing.iter().any(|name| name == group)
+        {
+            continue;
+        }
+        missing.push(group.to_string());
+    }
+    missing
 }
 
 #[cfg(test)]
@@ -250,7 +258,7 @@ mod tests {
             memory_cache::store(test_url, cache_content).unwrap();
 
             // Test memory cache hit
-            let result1 = fetch_ruleset(test_url, proxy, 3600, false).await;
+            let result1 = fetch_ruleset(test_url, proxy, 0, 3600, false).await;
             assert!(result1.is_ok());
             if let Ok(content) = result1 {
                 assert_eq!(content, cache_content);
@@ -265,7 +273,7 @@ mod tests {
             memory_cache::store(test_url, updated_content).unwrap();
 
             // Test cache hit with updated content
-            let result2 = fetch_ruleset(test_url, proxy, 3600, false).await;
+            let result2 = fetch_ruleset(test_url, proxy, 0, 3600, false).await;
             assert!(result2.is_ok());
             if let Ok(content) = result2 {
                 assert_eq!(content, updated_content);
@@ -295,7 +303,7 @@ mod tests {
             memory_cache::store(test_url, cache_content).unwrap();
 
             // Force cache expiration by using zero cache_timeout
-            let result_no_cache = fetch_ruleset(test_url, proxy, 0, false).await;
+            let result_no_cache = fetch_ruleset(test_url, proxy, 0, 0, false).await;
 
             // This will fail since we can't actually make HTTP requests in tests
             assert!(result_no_cache.is_err());
@@ -307,4 +315,27 @@ mod tests {
             memory_cache::remove(test_url);
         });
     }
+
+    #[test]
+    fn test_remap_rule_groups() {
+        let map = vec![
+            ("Proxy".to_string(), "🚀 节点选择".to_string()),
+            ("Ad.*".to_string(), "🛑 广告拦截".to_string()),
+        ];
+        let mut rulesets = vec![
+            RulesetContent::new("rules/proxy.list", "Proxy"),
+            RulesetContent::new("rules/ads.list", "AdBlock"),
+            RulesetContent::new("rules/apple.list", "Apple"),
+            RulesetContent::new("", "DIRECT"),
+        ];
+        remap_rule_groups(&mut rulesets, &map);
+        let groups: Vec<&str> = rulesets.iter().map(|r| r.group.as_str()).collect();
+        assert_eq!(
+            groups,
+            vec!["🚀 节点选择", "🛑 广告拦截", "Apple", "DIRECT"]
+        );
+
+        let missing = missing_rule_groups(&rulesets, &["🚀 节点选择", "🛑 广告拦截"]);
+        assert_eq!(missing, vec!["Apple"]);
+    }
 }
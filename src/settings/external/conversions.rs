@@ -2,6 +2,7 @@ use super::external_struct::ExternalSettings;
 use super::ini_external::IniExternalSettings;
 use super::toml_external::TomlExternalSettings;
 use super::yaml_external::YamlExternalSettings;
+use crate::models::ruleset::parse_rule_group_map;
 
 // TODO: Implement template handling and global settings like in C++
 // In C++, there is a template rendering system and global settings
@@ -41,9 +42,15 @@ impl From<YamlExternalSettings> for ExternalSettings {
         settings.include_remarks = yaml_settings.custom.filtering.include_remarks;
         settings.exclude_remarks = yaml_settings.custom.filtering.exclude_remarks;
 
+        // Insert options
+        settings.enable_insert = yaml_settings.custom.insert.enable_insert;
+        settings.insert_urls = yaml_settings.custom.insert.insert_url;
+        settings.prepend_insert = yaml_settings.custom.insert.prepend_insert_url;
+
         // Copy processed fields
         settings.custom_rulesets = yaml_settings.parsed_rulesets;
         settings.custom_proxy_groups = yaml_settings.parsed_custom_proxy_groups;
+        settings.rule_group_map = parse_rule_group_map(&yaml_settings.custom.rename_rule_group);
         settings.rename_nodes = yaml_settings.parsed_rename;
         settings.emojis = yaml_settings.parsed_emojis;
 
@@ -88,9 +95,15 @@ impl From<TomlExternalSettings> for ExternalSettings {
         settings.include_remarks = toml_settings.custom.filtering.include_remarks;
         settings.exclude_remarks = toml_settings.custom.filtering.exclude_remarks;
 
+        // Insert options
+        settings.enable_insert = toml_settings.custom.insert.enable_insert;
+        settings.insert_urls = toml_settings.custom.insert.insert_url;
+        settings.prepend_insert = toml_settings.custom.insert.prepend_insert_url;
+
         // Copy processed fields
         settings.custom_rulesets = toml_settings.parsed_rulesets;
         settings.custom_proxy_groups = toml_settings.parsed_custom_proxy_groups;
+        settings.rule_group_map = parse_rule_group_map(&toml_settings.custom.rename_rule_group);
         settings.rename_nodes = toml_settings.parsed_rename;
         settings.emojis = toml_settings.parsed_emojis;
 
@@ -129,9 +142,15 @@ impl From<IniExternalSettings> for ExternalSettings {
         settings.include_remarks = ini_settings.include_remarks;
         settings.exclude_remarks = ini_settings.exclude_remarks;
 
+        // Insert options
+        settings.enable_insert = ini_settings.enable_insert;
+        settings.insert_urls = ini_settings.insert_url;
+        settings.prepend_insert = ini_settings.prepend_insert_url;
+
         // Copy processed fields
         settings.custom_rulesets = ini_settings.parsed_rulesets;
         settings.custom_proxy_groups = ini_settings.parsed_custom_proxy_groups;
+        settings.rule_group_map = parse_rule_group_map(&ini_settings.rename_rule_group);
         settings.rename_nodes = ini_settings.parsed_rename;
         settings.emojis = ini_settings.parsed_emojis;
 
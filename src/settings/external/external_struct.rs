@@ -3,8 +3,11 @@ use std::collections::HashMap;
 use toml;
 
 use crate::models::{ProxyGroupConfig, RegexMatchConfig, RulesetConfig};
-use crate::settings::Settings;
-use crate::utils::file::load_content_async;
+use crate::settings::utils::interpolate_env_in;
+use crate::settings::{resolve_ini_imports, Settings};
+use crate::utils::fetcher::{default_fetcher, FetchOptions, SubscriptionFetcher};
+use crate::utils::http::parse_proxy;
+use crate::utils::{file_exists, is_link, starts_with};
 // TODO: Implement template rendering module similar to C++ render_template function
 
 use super::ini_external::IniExternalSettings;
@@ -40,10 +43,16 @@ pub struct ExternalSettings {
     // #[serde(default, deserialize_with = "deserialize_rulesets")]
     pub custom_rulesets: Vec<RulesetConfig>,
     pub custom_proxy_groups: Vec<ProxyGroupConfig>,
+    pub rule_group_map: Vec<(String, String)>,
 
     // Node operations
     pub rename_nodes: Vec<RegexMatchConfig>,
 
+    // Insert options
+    pub enable_insert: Option<bool>,
+    pub insert_urls: Vec<String>,
+    pub prepend_insert: Option<bool>,
+
     // Template arguments
     pub tpl_args: Option<HashMap<String, String>>,
 }
@@ -53,6 +62,26 @@ impl ExternalSettings {
         Self::default()
     }
 
+    /// Clear the rule bases that are neither links nor existing files under
+    /// `base_path`, the bases of the settings are used instead
+    pub async fn clear_unusable_bases(&mut self, base_path: &str) {
+        for base in [
+            &mut self.clash_rule_base,
+            &mut self.surge_rule_base,
+            &mut self.surfboard_rule_base,
+            &mut self.mellow_rule_base,
+            &mut self.quan_rule_base,
+            &mut self.quanx_rule_base,
+            &mut self.loon_rule_base,
+            &mut self.sssub_rule_base,
+            &mut self.singbox_rule_base,
+        ] {
+            if !is_link(base) && !(starts_with(base, base_path) && file_exists(base).await) {
+                base.clear();
+            }
+        }
+    }
+
     /// Load external configuration from file or URL
     // pub fn load_from_file_sync(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
     //     // Load content from file or URL
@@ -62,15 +91,43 @@ impl ExternalSettings {
     // }
 
     /// Load external configuration from file or URL asynchronously
-    pub async fn load_from_file(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        // Load content from file or URL asynchronously
-        let _content = load_content_async(path).await?;
+    pub async fn load_from_file(
+        path: &str,
+        settings: &Settings,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::load_with_fetcher(path, default_fetcher().as_ref(), settings).await
+    }
+
+    /// Load external configuration from file or URL through `fetcher`
+    ///
+    /// Limits, proxy and imports follow `settings`, the snapshot of the request.
+    pub async fn load_with_fetcher(
+        path: &str,
+        fetcher: &dyn SubscriptionFetcher,
+        settings: &Settings,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let opts = FetchOptions {
+            proxy: parse_proxy(&settings.proxy_config),
+            max_size: settings.max_allowed_download_size,
+            ..Default::default()
+        };
+        let fetched = fetcher
+            .fetch(path, &opts)
+            .await
+            .map_err(|e| format!("Failed to load external config {}: {}", path, e))?;
 
-        Self::parse_content(&_content).await
+        Self::parse_content(&fetched.body, path, settings).await
     }
 
     /// Parse the content and return an ExternalSettings object
-    async fn parse_content(content: &str) -> Result<Self, Box<dyn std::error::Error>> {
+    ///
+    /// `origin` is the path or URL of the content, INI imports are resolved
+    /// relative to it.
+    async fn parse_content(
+        content: &str,
+        origin: &str,
+        global: &Settings,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
         // TODO: Implement template rendering here
         // In C++: if(render_template(config, *ext.tpl_args, base_content, global.templatePath) != 0)
         //           base_content = config;
@@ -78,37 +135,77 @@ impl ExternalSettings {
         // Try YAML format first
         if content.contains("custom:") {
             let mut yaml_settings: YamlExternalSettings = serde_yaml::from_str(content)?;
-            yaml_settings.process_imports().await?;
+            yaml_settings.process_imports(global).await?;
             // Convert to ExternalSettings
-            let config = Self::from(yaml_settings);
+            let mut config = Self::from(yaml_settings);
+            config.interpolate_env();
             return Ok(config);
         }
 
         if toml::from_str::<toml::Value>(content).is_ok() {
             let mut toml_settings: TomlExternalSettings = toml::from_str(content)?;
-            toml_settings.process_imports().await?;
+            toml_settings.process_imports(global).await?;
             // Convert to ExternalSettings
-            let config = Self::from(toml_settings);
+            let mut config = Self::from(toml_settings);
+            config.interpolate_env();
             return Ok(config);
         }
 
         // Fall back to INI format
+        let content = resolve_ini_imports(
+            content,
+            origin,
+            global.api_mode,
+            &parse_proxy(&global.proxy_config),
+            &global.base_path,
+            global.cache_config,
+        )
+        .await?;
         let mut ini_settings = IniExternalSettings::new();
-        match ini_settings.load_from_ini(content) {
+        match ini_settings.load_from_ini(&content) {
             Ok(_) => {
                 // Process any imports
-                ini_settings.process_imports().await?;
+                ini_settings.process_imports(global).await?;
                 // Convert to ExternalSettings
-                let config = Self::from(ini_settings);
+                let mut config = Self::from(ini_settings);
+                config.interpolate_env();
                 return Ok(config);
             }
             Err(e) => Err(format!("Failed to parse external config as INI: {}", e).into()),
         }
     }
 
+    /// Resolve `${NAME}` and `${NAME:-default}` references in the string values
+    ///
+    /// Inline rules and the content of the rulesets are left untouched.
+    pub fn interpolate_env(&mut self) {
+        interpolate_env_in(
+            [
+                &mut self.clash_rule_base,
+                &mut self.surge_rule_base,
+                &mut self.surfboard_rule_base,
+                &mut self.mellow_rule_base,
+                &mut self.quan_rule_base,
+                &mut self.quanx_rule_base,
+                &mut self.loon_rule_base,
+                &mut self.sssub_rule_base,
+                &mut self.singbox_rule_base,
+            ]
+            .into_iter()
+            .chain(&mut self.include_remarks)
+            .chain(&mut self.exclude_remarks)
+            .chain(
+                self.custom_rulesets
+                    .iter_mut()
+                    .map(|ruleset| &mut ruleset.url)
+                    .filter(|url| !url.starts_with("[]")),
+            )
+            .chain(self.tpl_args.iter_mut().flat_map(|args| args.values_mut())),
+        );
+    }
+
     /// Validate rulesets count
-    pub fn validate_rulesets(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let settings = Settings::current();
+    pub fn validate_rulesets(&self, settings: &Settings) -> Result<(), Box<dyn std::error::Error>> {
         if settings.max_allowed_rulesets > 0
             && self.custom_rulesets.len() > settings.max_allowed_rulesets
         {
@@ -117,3 +214,122 @@ impl ExternalSettings {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str) -> ExternalSettings {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime
+            .block_on(ExternalSettings::parse_content(
+                content,
+                "",
+                &Settings::default(),
+            ))
+            .unwrap()
+    }
+
+    fn rulesets(config: &ExternalSettings) -> Vec<(&str, &str)> {
+        config
+            .custom_rulesets
+            .iter()
+            .map(|ruleset| (ruleset.group.as_str(), ruleset.url.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn test_inline_rules_in_every_format() {
+        let expected = [
+            ("🎯 Direct", "[]GEOIP,CN,no-resolve"),
+            ("🚀 Proxy", "[]DOMAIN-SUFFIX,google.com"),
+            ("🚀 Proxy", "[]IP-CIDR,8.8.8.8/32,no-resolve"),
+        ];
+
+        let ini = parse(
+            "[custom]
+ruleset=🎯 Direct,[]GEOIP,CN,no-resolve
+ruleset_inline=🚀 Proxy,DOMAIN-SUFFIX,google.com`IP-CIDR,8.8.8.8/32,no-resolve
+",
+        );
+        assert_eq!(rulesets(&ini), expected);
+
+        let yaml = parse(
+            r#"
+custom:
+  rulesets:
+    - "🎯 Direct,[]GEOIP,CN,no-resolve"
+  ruleset_inline:
+    - "🚀 Proxy,DOMAIN-SUFFIX,google.com`IP-CIDR,8.8.8.8/32,no-resolve"
+"#,
+        );
+        assert_eq!(rulesets(&yaml), expected);
+
+        let toml = parse(
+            r#"
+[custom]
+ruleset_inline = ["🚀 Proxy,DOMAIN-SUFFIX,google.com`IP-CIDR,8.8.8.8/32,no-resolve"]
+
+[[custom.custom_rulesets]]
+group = "🎯 Direct"
+ruleset = "[]GEOIP,CN,no-resolve"
+"#,
+        );
+        assert_eq!(rulesets(&toml), expected);
+    }
+
+    #[test]
+    fn test_insert_settings_in_every_format() {
+        let insert = |config: ExternalSettings| {
+            (
+                config.enable_insert,
+                config.insert_urls,
+                config.prepend_insert,
+            )
+        };
+        let expected = (
+            Some(true),
+            vec![
+                "https://example.com/a".to_string(),
+                "https://example.com/b".to_string(),
+            ],
+            Some(false),
+        );
+
+        let ini = parse(
+            "[custom]
+enable_insert=true
+insert_url=https://example.com/a|https://example.com/b
+prepend_insert_url=false
+",
+        );
+        assert_eq!(insert(ini), expected);
+
+        let yaml = parse(
+            r#"
+custom:
+  enable_insert: true
+  insert_url:
+    - "https://example.com/a"
+    - "https://example.com/b"
+  prepend_insert_url: false
+"#,
+        );
+        assert_eq!(insert(yaml), expected);
+
+        let toml = parse(
+            r#"
+[custom]
+enable_insert = true
+insert_url = ["https://example.com/a", "https://example.com/b"]
+prepend_insert_url = false
+"#,
+        );
+        assert_eq!(insert(toml), expected);
+
+        assert_eq!(insert(parse("[custom]\n")), (None, vec![], None));
+    }
+}
@@ -7,6 +7,8 @@ use crate::models::{ProxyGroupConfigs, RegexMatchConfig, RegexMatchConfigs, Rule
 use crate::settings::{import_items, Settings};
 use crate::utils::http::parse_proxy;
 
+use super::expand_inline_rules;
+
 /// INI external settings structure
 #[derive(Debug, Clone, Deserialize, Default)]
 #[serde(default)]
@@ -38,6 +40,12 @@ pub struct IniExternalSettings {
     // Rulesets and proxy groups (stored as raw strings)
     pub rulesets: Vec<String>,
     pub custom_proxy_groups: Vec<String>,
+    pub rename_rule_group: Vec<String>,
+
+    // Insert options
+    pub enable_insert: Option<bool>,
+    pub insert_url: Vec<String>,
+    pub prepend_insert_url: Option<bool>,
 
     // fields
     pub rename_nodes: Vec<String>,
@@ -118,12 +126,27 @@ impl IniExternalSettings {
             "exclude_remarks" => {
                 self.exclude_remarks = value.split(',').map(|s| s.trim().to_string()).collect();
             }
+            "enable_insert" => self.enable_insert = Some(parse_bool(value)),
+            "insert_url" => {
+                self.insert_url = value
+                    .split('|')
+                    .filter(|url| !url.is_empty())
+                    .map(String::from)
+                    .collect();
+            }
+            "prepend_insert_url" => self.prepend_insert_url = Some(parse_bool(value)),
             "ruleset" | "surge_ruleset" => {
                 self.rulesets.push(value.to_string());
             }
+            "ruleset_inline" => {
+                self.rulesets.extend(expand_inline_rules(value));
+            }
             "custom_proxy_group" => {
                 self.custom_proxy_groups.push(value.to_string());
             }
+            "rename_rule_group" => {
+                self.rename_rule_group.push(value.to_string());
+            }
             "emoji" => {
                 self.emojis.push(value.to_string());
             }
@@ -146,8 +169,10 @@ impl IniExternalSettings {
         }
     }
 
-    pub async fn process_imports(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        let global = Settings::current();
+    pub async fn process_imports(
+        &mut self,
+        global: &Settings,
+    ) -> Result<(), Box<dyn std::error::Error>> {
         let proxy_config = parse_proxy(&global.proxy_config);
         // Process rename nodes
         import_items(
@@ -155,12 +180,20 @@ impl IniExternalSettings {
             false,
             &proxy_config,
             &global.base_path,
+            global.cache_config,
         )
         .await?;
         self.parsed_rename = RegexMatchConfigs::from_ini_with_delimiter(&self.rename_nodes, "@");
 
         // Process emoji rules
-        import_items(&mut self.emojis, false, &proxy_config, &global.base_path).await?;
+        import_items(
+            &mut self.emojis,
+            false,
+            &proxy_config,
+            &global.base_path,
+            global.cache_config,
+        )
+        .await?;
         self.parsed_emojis = RegexMatchConfigs::from_ini_with_delimiter(&self.emojis, ",");
 
         // Process imports for rulesets
@@ -169,6 +202,7 @@ impl IniExternalSettings {
             global.api_mode,
             &proxy_config,
             &global.base_path,
+            global.cache_config,
         )
         .await?;
         self.parsed_rulesets = RulesetConfigs::from_ini(&self.rulesets);
@@ -179,6 +213,7 @@ impl IniExternalSettings {
             global.api_mode,
             &proxy_config,
             &global.base_path,
+            global.cache_config,
         )
         .await?;
         self.parsed_custom_proxy_groups = ProxyGroupConfigs::from_ini(&custom_proxy_groups);
@@ -199,3 +234,61 @@ fn parse_bool_with_true_default(value: &str) -> bool {
         parse_bool(value)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::ruleconvert::{ruleset_to_clash_str, ruleset_to_surge, RuleTarget};
+    use crate::rulesets::ruleset::load_ruleset;
+    use crate::utils::IniReader;
+
+    #[test]
+    fn test_inline_rules_pass_through() {
+        let ini = "[custom]
+ruleset=🎯 Direct,[]GEOIP,CN,no-resolve
+ruleset_inline=🚀 Proxy,DOMAIN-SUFFIX,google.com`IP-CIDR,8.8.8.8/32,no-resolve
+";
+        let mut settings = IniExternalSettings::new();
+        settings.load_from_ini(ini).unwrap();
+        let configs = RulesetConfigs::from_ini(&settings.rulesets);
+        assert_eq!(configs.len(), 3);
+        assert!(configs.iter().all(|config| config.url.starts_with("[]")));
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let proxy = parse_proxy("NONE");
+        let rulesets: Vec<_> = configs
+            .iter()
+            .map(|config| {
+                runtime
+                    .block_on(load_ruleset(config, &proxy, 0, 0, false))
+                    .unwrap()
+            })
+            .collect();
+        let expected = [
+            "GEOIP,CN,🎯 Direct,no-resolve",
+            "DOMAIN-SUFFIX,google.com,🚀 Proxy",
+            "IP-CIDR,8.8.8.8/32,🚀 Proxy,no-resolve",
+        ];
+
+        let clash = ruleset_to_clash_str(
+            &serde_yaml::Value::Null,
+            &rulesets,
+            true,
+            true,
+            0,
+            RuleTarget::Meta,
+        );
+        let surge = {
+            let mut base = IniReader::new();
+            runtime.block_on(ruleset_to_surge(&mut base, &rulesets, 4, true, "", 0));
+            base.to_string()
+        };
+        for rule in expected {
+            assert!(clash.contains(&format!("  - {}\n", rule)), "{}", clash);
+            assert!(surge.lines().any(|line| line == rule), "{}", surge);
+        }
+    }
+}
@@ -5,3 +5,21 @@ pub mod toml_external;
 pub mod yaml_external;
 
 pub use external_struct::ExternalSettings;
+
+/// Expand a `ruleset_inline` value into one `[]` ruleset line per rule
+///
+/// The value is a group followed by literal rules separated by backticks,
+/// e.g. `🎯 Direct,GEOIP,CN,no-resolve`DOMAIN-SUFFIX,cn`, in every format of
+/// external config.
+fn expand_inline_rules(value: &str) -> Vec<String> {
+    let Some((group, rules)) = value.split_once(',') else {
+        return Vec::new();
+    };
+    rules
+        .split('`')
+        .map(|rule| rule.trim())
+        .map(|rule| rule.strip_prefix("[]").unwrap_or(rule))
+        .filter(|rule| !rule.is_empty())
+        .map(|rule| format!("{},[]{}", group.trim(), rule))
+        .collect()
+}
@@ -1,12 +1,16 @@
 use serde::Deserialize;
 use std::collections::HashMap;
 
+use super::super::ini_bindings::FromIni;
+use crate::models::ruleset::RulesetConfigs;
 use crate::models::{ProxyGroupConfigs, RegexMatchConfig, RulesetConfig};
 use crate::settings::import_toml::import_toml_items;
 use crate::settings::toml_deserializer::*;
 use crate::settings::Settings;
 use crate::utils::http::parse_proxy;
 
+use super::expand_inline_rules;
+
 // Default value functions
 fn default_true() -> bool {
     true
@@ -53,6 +57,15 @@ pub struct FilteringSettings {
     pub exclude_remarks: Vec<String>,
 }
 
+/// Insert settings
+#[derive(Debug, Clone, Deserialize, Default)]
+#[serde(default)]
+pub struct InsertSettings {
+    pub enable_insert: Option<bool>,
+    pub insert_url: Vec<String>,
+    pub prepend_insert_url: Option<bool>,
+}
+
 /// Custom settings
 #[derive(Debug, Clone, Deserialize, Default)]
 #[serde(default)]
@@ -73,12 +86,19 @@ pub struct CustomSettings {
     #[serde(flatten)]
     pub filtering: FilteringSettings,
 
+    // Insert settings
+    #[serde(flatten)]
+    pub insert: InsertSettings,
+
     // Emoji and rename rules
     pub rename_node: Vec<RegexMatchRuleInToml>,
 
     // Custom rulesets and proxy groups
     pub custom_rulesets: Vec<RulesetConfigInToml>,
+    /// Literal rules of a group, see [`expand_inline_rules`]
+    pub ruleset_inline: Vec<String>,
     pub custom_proxy_groups: Vec<ProxyGroupConfigInToml>,
+    pub rename_rule_group: Vec<String>,
 }
 
 /// Main TOML external settings structure
@@ -101,8 +121,10 @@ pub struct TomlExternalSettings {
 }
 
 impl TomlExternalSettings {
-    pub async fn process_imports(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        let global = Settings::current();
+    pub async fn process_imports(
+        &mut self,
+        global: &Settings,
+    ) -> Result<(), Box<dyn std::error::Error>> {
         let proxy_config = parse_proxy(&global.proxy_config);
 
         import_toml_items(
@@ -111,6 +133,7 @@ impl TomlExternalSettings {
             "rename_node",
             &proxy_config,
             &global.base_path,
+            global.cache_config,
         )
         .await?;
         self.parsed_rename = self
@@ -127,6 +150,7 @@ impl TomlExternalSettings {
             "emoji",
             &proxy_config,
             &global.base_path,
+            global.cache_config,
         )
         .await?;
         self.parsed_emojis = self
@@ -144,10 +168,19 @@ impl TomlExternalSettings {
             "rulesets",
             &proxy_config,
             &global.base_path,
+            global.cache_config,
         )
         .await?;
+        let inline_rules: Vec<String> = self
+            .custom
+            .ruleset_inline
+            .iter()
+            .flat_map(|value| expand_inline_rules(value))
+            .collect();
+        let inline_rulesets = RulesetConfigs::from_ini(&inline_rules);
         if global.max_allowed_rulesets > 0
-            && self.custom.custom_rulesets.len() > global.max_allowed_rulesets
+            && self.custom.custom_rulesets.len() + inline_rulesets.len()
+                > global.max_allowed_rulesets
         {
             return Err(format!(
                 "Number of rulesets exceeds the maximum allowed: {}",
@@ -161,6 +194,7 @@ impl TomlExternalSettings {
             .custom_rulesets
             .iter()
             .map(|r| r.clone().into())
+            .chain(inline_rulesets)
             .collect();
 
         // Process imports for proxy groups
@@ -170,6 +204,7 @@ impl TomlExternalSettings {
             "custom_group",
             &proxy_config,
             &global.base_path,
+            global.cache_config,
         )
         .await?;
         self.parsed_custom_proxy_groups = self
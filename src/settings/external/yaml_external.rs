@@ -7,6 +7,8 @@ use crate::models::{ProxyGroupConfigs, RegexMatchConfig, RegexMatchConfigs};
 use crate::settings::yaml_deserializer::deserialize_template_args_as_hash_map;
 use crate::{settings::import_items, utils::http::parse_proxy, Settings};
 
+use super::expand_inline_rules;
+
 // Default value functions
 fn default_true() -> bool {
     true
@@ -53,6 +55,15 @@ pub struct FilteringSettings {
     pub exclude_remarks: Vec<String>,
 }
 
+/// Insert settings
+#[derive(Debug, Clone, Deserialize, Default)]
+#[serde(default)]
+pub struct InsertSettings {
+    pub enable_insert: Option<bool>,
+    pub insert_url: Vec<String>,
+    pub prepend_insert_url: Option<bool>,
+}
+
 /// Custom settings
 #[derive(Debug, Clone, Deserialize, Default)]
 #[serde(default)]
@@ -73,6 +84,10 @@ pub struct CustomSettings {
     #[serde(flatten)]
     pub filtering: FilteringSettings,
 
+    // Insert settings
+    #[serde(flatten)]
+    pub insert: InsertSettings,
+
     // Emoji and rename rules
     #[serde(alias = "emoji")]
     pub emojis: Vec<String>,
@@ -81,8 +96,11 @@ pub struct CustomSettings {
     // Custom rulesets and proxy groups
     #[serde(alias = "surge_ruleset")]
     pub rulesets: Vec<String>,
+    /// Literal rules of a group, see [`expand_inline_rules`]
+    pub ruleset_inline: Vec<String>,
     #[serde(alias = "custom_proxy_group")]
     pub proxy_groups: Vec<String>,
+    pub rename_rule_group: Vec<String>,
 }
 
 /// Main YAML external settings structure
@@ -105,8 +123,10 @@ pub struct YamlExternalSettings {
 }
 
 impl YamlExternalSettings {
-    pub async fn process_imports(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        let global = Settings::current();
+    pub async fn process_imports(
+        &mut self,
+        global: &Settings,
+    ) -> Result<(), Box<dyn std::error::Error>> {
         let proxy_config = parse_proxy(&global.proxy_config);
 
         // Process rename nodes
@@ -115,6 +135,7 @@ impl YamlExternalSettings {
             false,
             &proxy_config,
             &global.base_path,
+            global.cache_config,
         )
         .await?;
         self.parsed_rename =
@@ -126,6 +147,7 @@ impl YamlExternalSettings {
             false,
             &proxy_config,
             &global.base_path,
+            global.cache_config,
         )
         .await?;
         self.parsed_emojis = RegexMatchConfigs::from_ini_with_delimiter(&self.custom.emojis, ",");
@@ -136,8 +158,15 @@ impl YamlExternalSettings {
             global.api_mode,
             &proxy_config,
             &global.base_path,
+            global.cache_config,
         )
         .await?;
+        let inline_rules = self
+            .custom
+            .ruleset_inline
+            .iter()
+            .flat_map(|value| expand_inline_rules(value));
+        self.custom.rulesets.extend(inline_rules);
         self.parsed_rulesets = RulesetConfigs::from_ini(&self.custom.rulesets);
 
         // Process imports for proxy groups
@@ -146,6 +175,7 @@ impl YamlExternalSettings {
             global.api_mode,
             &proxy_config,
             &global.base_path,
+            global.cache_config,
         )
         .await?;
         self.parsed_custom_proxy_groups = ProxyGroupConfigs::from_ini(&self.custom.proxy_groups);
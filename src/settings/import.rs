@@ -1,79 +1,387 @@
-use crate::utils::{file_exists, file_get_async, http::ProxyConfig};
+use std::path::Path;
+
+use crate::utils::fetcher::{default_fetcher, FetchOptions};
+use crate::utils::{file_exists, file_get_async, http::ProxyConfig, memory_cache};
+
+/// How deep imports may be nested, the including file not counted
+pub const MAX_IMPORT_DEPTH: usize = 5;
+
+/// Resolve an import path against the file or URL that includes it
+///
+/// URLs and absolute paths are kept as they are, relative ones are joined to
+/// the directory of `including`. Without an including file the path is left
+/// relative to the working directory.
+pub fn resolve_import_path(path: &str, including: Option<&str>) -> String {
+    let Some(including) = including.filter(|including| !including.is_empty()) else {
+        return path.to_string();
+    };
+    if is_url(path) || Path::new(path).is_absolute() {
+        return path.to_string();
+    }
+    if is_url(including) {
+        return url::Url::parse(including)
+            .and_then(|base| base.join(path))
+            .map(|url| url.to_string())
+            .unwrap_or_else(|_| path.to_string());
+    }
+    match Path::new(including).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.join(path).to_string_lossy().into_owned(),
+        _ => path.to_string(),
+    }
+}
+
+fn is_url(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://")
+}
+
+/// Check that `path` may be imported at `depth` by the last file of `chain`
+fn check_import_chain(chain: &[String], depth: usize, path: &str) -> Result<(), String> {
+    if let Some(start) = chain.iter().position(|included| included == path) {
+        let mut cycle = chain[start..].to_vec();
+        cycle.push(path.to_string());
+        return Err(format!("Import cycle: {}", cycle.join(" -> ")));
+    }
+    if depth > MAX_IMPORT_DEPTH {
+        return Err(format!(
+            "Imports nested deeper than {} levels at {}",
+            MAX_IMPORT_DEPTH, path
+        ));
+    }
+    Ok(())
+}
+
+/// Load the content of an import source
+///
+/// URLs go through the default fetcher and the memory cache, kept for
+/// `cache_timeout` seconds, files outside `base_path` are refused when
+/// `scope_limit` is set.
+pub(crate) async fn load_import(
+    path: &str,
+    scope_limit: bool,
+    proxy_config: &ProxyConfig,
+    base_path: &str,
+    cache_timeout: u32,
+) -> Result<String, String> {
+    let content = if is_url(path) {
+        if cache_timeout > 0 {
+            if let Some(content) = memory_cache::get_if_valid(path, cache_timeout) {
+                log::debug!("Using cached import for URL: {}", path);
+                return Ok(content);
+            }
+        }
+        let opts = FetchOptions {
+            proxy: proxy_config.clone(),
+            ..Default::default()
+        };
+        let content = default_fetcher().fetch(path, &opts).await?.body;
+        if cache_timeout > 0 && !content.is_empty() {
+            if let Err(e) = memory_cache::store(path, &content) {
+                log::warn!("Failed to cache import {}: {}", path, e);
+            }
+        }
+        content
+    } else if file_exists(path).await {
+        file_get_async(path, scope_limit.then_some(base_path))
+            .await
+            .map_err(|e| format!("Failed to read {}: {}", path, e))?
+    } else {
+        log::error!("File not found or not a valid URL: {}", path);
+        return Err(format!("File not found or not a valid URL: {}", path));
+    };
+
+    if content.is_empty() {
+        return Err("Empty content from import source".to_string());
+    }
+    Ok(content)
+}
+
+/// The items of an imported file, without empty lines and comments
+fn import_lines(content: &str) -> Vec<String> {
+    // Old Mac style files only use '\r'
+    let delimiter = if !content.contains('\n') && content.contains('\r') {
+        '\r'
+    } else {
+        '\n'
+    };
+    content
+        .split(delimiter)
+        .map(str::trim)
+        .filter(|line| {
+            !(line.is_empty()
+                || line.starts_with(';')
+                || line.starts_with('#')
+                || line.starts_with("//"))
+        })
+        .map(str::to_string)
+        .collect()
+}
 
 /// Import items from external files or URLs
 ///
 /// This function processes configuration items that start with "!!import:"
 /// and replaces them with the content from the specified file or URL.
+/// Imported files may import further files, relative to themselves.
 pub async fn import_items(
     target: &mut Vec<String>,
     scope_limit: bool,
     proxy_config: &ProxyConfig,
     base_path: &str,
+    cache_timeout: u32,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let mut result = Vec::new();
     let mut item_count = 0;
+    // Items left to expand, with the chain of files they come from
+    let mut stack = vec![(std::mem::take(target).into_iter(), Vec::<String>::new())];
 
-    for item in target.iter() {
-        if !item.starts_with("!!import:") {
-            result.push(item.clone());
+    loop {
+        let depth = stack.len();
+        let Some((items, chain)) = stack.last_mut() else {
+            break;
+        };
+        let Some(item) = items.next() else {
+            stack.pop();
             continue;
-        }
+        };
+        let Some(path) = item.strip_prefix("!!import:") else {
+            if !chain.is_empty() {
+                item_count += 1;
+            }
+            result.push(item);
+            continue;
+        };
 
-        let path = item[9..].to_string(); // Extract path after "!!import:"
+        let path = resolve_import_path(path.trim(), chain.last().map(String::as_str));
+        check_import_chain(chain, depth, &path)?;
         log::info!("Trying to import items from {}", path);
 
-        // Function to determine content line breaks
-        let get_line_break = |content: &str| -> char {
-            if content.contains("\r\n") {
-                '\n' // Windows style but we normalize to '\n'
-            } else if content.contains('\r') {
-                '\r' // Old Mac style
-            } else {
-                '\n' // Unix style
-            }
-        };
+        let mut chain = chain.clone();
+        let content =
+            load_import(&path, scope_limit, proxy_config, base_path, cache_timeout).await?;
+        chain.push(path);
+        stack.push((import_lines(&content).into_iter(), chain));
+    }
+
+    *target = result;
+    log::info!("Imported {} item(s).", item_count);
+
+    Ok(())
+}
+
+/// Expand the `import=path` lines of an INI config
+///
+/// Each directive is replaced by the content of the imported file, itself
+/// expanded, and followed by the header of the section it appeared in. Later
+/// values therefore win over imported ones, both between files and within the
+/// including section. Relative paths are resolved against `origin`, the path
+/// or URL of the config.
+pub async fn resolve_ini_imports(
+    content: &str,
+    origin: &str,
+    scope_limit: bool,
+    proxy_config: &ProxyConfig,
+    base_path: &str,
+    cache_timeout: u32,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let origin_chain = if origin.is_empty() {
+        Vec::new()
+    } else {
+        vec![origin.to_string()]
+    };
+    let lines: Vec<String> = content.lines().map(str::to_string).collect();
+    let mut result = String::new();
+    // Lines left to expand, with the chain of files and the current section
+    let mut stack = vec![(lines.into_iter(), origin_chain, None::<String>)];
 
-        let content = if path.starts_with("http://") || path.starts_with("https://") {
-            // Fetch from URL
-            let response = crate::utils::http::web_get_async(&path, &proxy_config, None).await?;
-            response.body
-        } else if file_exists(&path).await {
-            // Read from file
-            if scope_limit {
-                file_get_async(&path, Some(base_path)).await?
-            } else {
-                file_get_async(&path, None).await?
+    loop {
+        let depth = stack.len();
+        let Some((lines, chain, section)) = stack.last_mut() else {
+            break;
+        };
+        let Some(line) = lines.next() else {
+            stack.pop();
+            // Continue the section of the including file
+            if let Some((_, _, Some(section))) = stack.last() {
+                result.push_str(section);
+                result.push('\n');
             }
-        } else {
-            log::error!("File not found or not a valid URL: {}", path);
-            return Err(format!("File not found or not a valid URL: {}", path).into());
+            continue;
         };
 
-        if content.is_empty() {
-            return Err("Empty content from import source".into());
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') && trimmed.ends_with(']') {
+            *section = Some(trimmed.to_string());
         }
+        let Some(path) = trimmed
+            .split_once('=')
+            .filter(|(key, _)| key.trim() == "import")
+            .map(|(_, path)| path.trim())
+        else {
+            result.push_str(&line);
+            result.push('\n');
+            continue;
+        };
 
-        // Process content line by line
-        let delimiter = get_line_break(&content);
-        for line in content.split(delimiter) {
-            let line = line.trim();
+        let path = resolve_import_path(path, chain.last().map(String::as_str));
+        check_import_chain(chain, depth, &path)?;
+        log::info!("Trying to import config from {}", path);
 
-            // Skip empty lines and comments
-            if line.is_empty()
-                || line.starts_with(';')
-                || line.starts_with('#')
-                || (line.len() >= 2 && line.starts_with("//"))
-            {
-                continue;
-            }
+        let mut chain = chain.clone();
+        let content =
+            load_import(&path, scope_limit, proxy_config, base_path, cache_timeout).await?;
+        chain.push(path);
+        let lines: Vec<String> = content.lines().map(str::to_string).collect();
+        stack.push((lines.into_iter(), chain, None));
+    }
 
-            result.push(line.to_string());
-            item_count += 1;
-        }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(future)
     }
 
-    *target = result;
-    log::info!("Imported {} item(s).", item_count);
+    #[test]
+    fn test_resolve_import_path() {
+        assert_eq!(resolve_import_path("a.txt", None), "a.txt");
+        assert_eq!(
+            resolve_import_path("snippets/a.txt", Some("base/pref.ini")),
+            Path::new("base").join("snippets/a.txt").to_string_lossy()
+        );
+        assert_eq!(
+            resolve_import_path("../rules.list", Some("https://example.com/cfg/ext.ini")),
+            "https://example.com/rules.list"
+        );
+        assert_eq!(
+            resolve_import_path("https://example.com/a.txt", Some("base/pref.ini")),
+            "https://example.com/a.txt"
+        );
+    }
 
-    Ok(())
+    #[test]
+    fn test_nested_item_imports() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("more")).unwrap();
+        std::fs::write(
+            dir.path().join("first.txt"),
+            "# comment\nA@1\n!!import:more/second.txt\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("more/second.txt"), "B@2\r\n\r\nC@3\r\n").unwrap();
+
+        let first = dir.path().join("first.txt");
+        let mut items = vec!["Z@0".to_string(), format!("!!import:{}", first.display())];
+        block_on(import_items(
+            &mut items,
+            false,
+            &ProxyConfig::default(),
+            "",
+            0,
+        ))
+        .unwrap();
+        assert_eq!(items, ["Z@0", "A@1", "B@2", "C@3"]);
+    }
+
+    #[test]
+    fn test_ini_import_chain_later_wins() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("a.ini"),
+            "[custom]\nclash_rule_base=a.yml\nimport=b.ini\nenable_rule_generator=true\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("b.ini"),
+            "[custom]\nclash_rule_base=b.yml\nimport=c.ini\n[template]\nkey=b\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("c.ini"),
+            "[custom]\nclash_rule_base=c.yml\nsurge_rule_base=c.conf\n",
+        )
+        .unwrap();
+
+        let origin = dir.path().join("a.ini");
+        let origin = origin.to_str().unwrap();
+        let content = std::fs::read_to_string(origin).unwrap();
+        let resolved = block_on(resolve_ini_imports(
+            &content,
+            origin,
+            false,
+            &ProxyConfig::default(),
+            "",
+            0,
+        ))
+        .unwrap();
+        assert_eq!(
+            resolved,
+            "[custom]\nclash_rule_base=a.yml\n\
+             [custom]\nclash_rule_base=b.yml\n\
+             [custom]\nclash_rule_base=c.yml\nsurge_rule_base=c.conf\n\
+             [custom]\n[template]\nkey=b\n\
+             [custom]\nenable_rule_generator=true\n"
+        );
+    }
+
+    #[test]
+    fn test_ini_import_cycle_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.ini"), "[custom]\nimport=b.ini\n").unwrap();
+        std::fs::write(dir.path().join("b.ini"), "[custom]\nimport=c.ini\n").unwrap();
+        std::fs::write(dir.path().join("c.ini"), "[custom]\nimport=a.ini\n").unwrap();
+
+        let path = |name: &str| dir.path().join(name).to_string_lossy().into_owned();
+        let content = std::fs::read_to_string(path("a.ini")).unwrap();
+        let error = block_on(resolve_ini_imports(
+            &content,
+            &path("a.ini"),
+            false,
+            &ProxyConfig::default(),
+            "",
+            0,
+        ))
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "Import cycle: {} -> {} -> {} -> {}",
+                path("a.ini"),
+                path("b.ini"),
+                path("c.ini"),
+                path("a.ini")
+            )
+        );
+    }
+
+    #[test]
+    fn test_import_depth_is_limited() {
+        let dir = tempfile::tempdir().unwrap();
+        for level in 0..=MAX_IMPORT_DEPTH + 1 {
+            std::fs::write(
+                dir.path().join(format!("{}.txt", level)),
+                format!("item{}\n!!import:{}.txt\n", level, level + 1),
+            )
+            .unwrap();
+        }
+
+        let mut items = vec![format!("!!import:{}", dir.path().join("0.txt").display())];
+        let error = block_on(import_items(
+            &mut items,
+            false,
+            &ProxyConfig::default(),
+            "",
+            0,
+        ))
+        .unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("Imports nested deeper than 5 levels"));
+    }
 }
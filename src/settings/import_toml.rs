@@ -1,5 +1,6 @@
-use crate::utils::{file_exists, file_get_async, http::ProxyConfig};
+use crate::utils::http::ProxyConfig;
 
+use super::import::load_import;
 use super::toml_deserializer::ImportableInToml;
 
 /// Import items from external files or URLs
@@ -12,6 +13,7 @@ pub async fn import_toml_items<T: ImportableInToml>(
     import_key: &str,
     proxy_config: &ProxyConfig,
     base_path: &str,
+    cache_timeout: u32,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let mut result = Vec::new();
     let mut item_count = 0;
@@ -25,25 +27,8 @@ pub async fn import_toml_items<T: ImportableInToml>(
         let path = item.get_import_path().unwrap();
         log::info!("Trying to import items from {}", path);
 
-        let content = if path.starts_with("http://") || path.starts_with("https://") {
-            // Fetch from URL
-            let response = crate::utils::http::web_get_async(&path, &proxy_config, None).await?;
-            response.body
-        } else if file_exists(&path).await {
-            // Read from file
-            if scope_limit {
-                file_get_async(&path, Some(base_path)).await?
-            } else {
-                file_get_async(&path, None).await?
-            }
-        } else {
-            log::error!("File not found or not a valid URL: {}", path);
-            return Err(format!("File not found or not a valid URL: {}", path).into());
-        };
-
-        if content.is_empty() {
-            return Err("Empty content from import source".into());
-        }
+        let content =
+            load_import(&path, scope_limit, proxy_config, base_path, cache_timeout).await?;
 
         let toml_root_node = toml::from_str::<toml::Value>(&content)?;
         if let Some(sub_nodes) = toml_root_node.get(import_key) {
@@ -2,6 +2,7 @@ use crate::models::cron::{CronTaskConfig, CronTaskConfigs};
 use crate::models::proxy_group_config::{ProxyGroupConfig, ProxyGroupConfigs, ProxyGroupType};
 use crate::models::regex_match_config::{RegexMatchConfig, RegexMatchConfigs};
 use crate::models::ruleset::{RulesetConfig, RulesetConfigs};
+use crate::settings::yaml_deserializer::{ToIni, ToIniWithDelimiter};
 use crate::utils::string::starts_with;
 
 /// Parse group times string into interval, timeout, and tolerance values
@@ -66,25 +67,33 @@ impl FromIni<ProxyGroupConfigs> for ProxyGroupConfigs {
                 _ => ProxyGroupType::Select,
             };
 
-            if conf.group_type == ProxyGroupType::URLTest
-                || conf.group_type == ProxyGroupType::LoadBalance
-                || conf.group_type == ProxyGroupType::Fallback
-            {
-                if rules_upper_bound < 5 {
-                    continue;
-                }
-                rules_upper_bound -= 2;
-                conf.url = v_array[rules_upper_bound].to_string();
+            if conf.is_tested_group() {
+                // The test url and times are either two separate fields, or a
+                // single `url,interval,timeout,tolerance` field
+                let last = v_array[rules_upper_bound - 1];
+                let times = match last.split_once(',') {
+                    Some((url, times)) if url.contains("://") => {
+                        if rules_upper_bound < 4 {
+                            continue;
+                        }
+                        rules_upper_bound -= 1;
+                        conf.url = url.to_string();
+                        times
+                    }
+                    _ => {
+                        if rules_upper_bound < 5 {
+                            continue;
+                        }
+                        rules_upper_bound -= 2;
+                        conf.url = v_array[rules_upper_bound].to_string();
+                        last
+                    }
+                };
 
                 let mut interval = 0;
                 let mut timeout = 5;
                 let mut tolerance = 0;
-                parse_group_times(
-                    v_array[rules_upper_bound + 1],
-                    &mut interval,
-                    &mut timeout,
-                    &mut tolerance,
-                );
+                parse_group_times(times, &mut interval, &mut timeout, &mut tolerance);
                 conf.interval = interval;
                 conf.timeout = timeout;
                 conf.tolerance = tolerance;
@@ -96,6 +105,10 @@ impl FromIni<ProxyGroupConfigs> for ProxyGroupConfigs {
                     for provider in provider_list {
                         conf.using_provider.push(provider.to_string());
                     }
+                } else if let Some(filter) = v_array[i].strip_prefix("!!INCLUDE=") {
+                    conf.include_filter = Some(filter.to_string());
+                } else if let Some(filter) = v_array[i].strip_prefix("!!EXCLUDE=") {
+                    conf.exclude_filter = Some(filter.to_string());
                 } else {
                     conf.proxies.push(v_array[i].to_string());
                 }
@@ -218,3 +231,61 @@ impl FromIni<CronTaskConfigs> for CronTaskConfigs {
         confs
     }
 }
+
+/// Write a ProxyGroupConfig back as an INI line, the inverse of `from_ini`
+impl ToIni for ProxyGroupConfig {
+    fn to_ini(&self) -> String {
+        let mut parts = vec![self.name.clone(), self.group_type.as_str().to_string()];
+        parts.extend(self.proxies.iter().cloned());
+        if !self.using_provider.is_empty() {
+            parts.push(format!("!!PROVIDER={}", self.using_provider.join(",")));
+        }
+        if let Some(filter) = &self.include_filter {
+            parts.push(format!("!!INCLUDE={}", filter));
+        }
+        if let Some(filter) = &self.exclude_filter {
+            parts.push(format!("!!EXCLUDE={}", filter));
+        }
+
+        if self.is_tested_group() {
+            parts.push(self.url.clone());
+            parts.push(format!(
+                "{},{},{}",
+                self.interval, self.timeout, self.tolerance
+            ));
+        }
+
+        parts.join("`")
+    }
+}
+
+/// Write a RulesetConfig back as an INI line, the inverse of `from_ini`
+impl ToIni for RulesetConfig {
+    fn to_ini(&self) -> String {
+        if self.url.starts_with("[]") || self.interval == 0 {
+            format!("{},{}", self.group, self.url)
+        } else {
+            format!("{},{},{}", self.group, self.url, self.interval)
+        }
+    }
+}
+
+/// Write a RegexMatchConfig back as an INI line, the inverse of `from_ini_with_delimiter`
+impl ToIniWithDelimiter for RegexMatchConfig {
+    fn to_ini_with_delimiter(&self, delimiter: &str) -> String {
+        if self.replace == "script" {
+            return format!("script:{}", self._match);
+        }
+        format!("{}{}{}", self._match, delimiter, self.replace)
+    }
+}
+
+/// Write a CronTaskConfig back as an INI line, the inverse of `from_ini`
+impl ToIni for CronTaskConfig {
+    fn to_ini(&self) -> String {
+        format!(
+            "{}`{}`{}`{}",
+            self.name, self.cron_exp, self.path, self.timeout
+        )
+    }
+}
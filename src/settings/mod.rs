@@ -8,10 +8,14 @@ pub mod ini_bindings;
 pub mod settings;
 pub mod toml_deserializer;
 pub mod utils;
+#[cfg(all(feature = "watch", not(target_arch = "wasm32")))]
+pub mod watcher;
 pub mod yaml_deserializer;
 
 // Re-export settings struct and functions
 pub use external::ExternalSettings;
 pub use import::*;
 pub use ini_bindings::*;
-pub use settings::settings_struct::{refresh_configuration, update_settings_from_file, Settings};
+pub use settings::settings_struct::{
+    refresh_configuration, update_settings_from_file, Settings, SettingsFormat,
+};
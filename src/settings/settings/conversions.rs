@@ -1,15 +1,50 @@
 // Conversion implementation for Settings struct
 
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 use super::ini_settings::IniSettings;
 use super::settings_struct::{default_listen_address, Settings};
-use super::toml_settings::TomlSettings;
-use super::yaml_settings::YamlSettings;
+use super::toml_settings::{self, TomlSettings};
+use super::yaml_settings::{self, YamlSettings};
 
 use crate::constants::log_level::{
-    LOG_LEVEL_DEBUG, LOG_LEVEL_ERROR, LOG_LEVEL_INFO, LOG_LEVEL_VERBOSE, LOG_LEVEL_WARNING,
+    LOG_LEVEL_DEBUG, LOG_LEVEL_ERROR, LOG_LEVEL_FATAL, LOG_LEVEL_INFO, LOG_LEVEL_VERBOSE,
+    LOG_LEVEL_WARNING,
 };
+use crate::models::ruleset::{parse_rule_group_map, rule_group_map_to_ini};
+use crate::models::{ProxyGroupConfig, ProxyGroupType, RegexMatchConfig};
+use crate::settings::toml_deserializer::{
+    ProxyGroupConfigInToml, RegexMatchRuleInToml, RulesetConfigInToml, TaskConfigInToml,
+};
+use crate::settings::yaml_deserializer::{
+    ProxyGroupConfigInYaml, RegexMatchRuleInYaml, RulesetConfigInYaml, TaskConfigInYaml, ToIni,
+    ToIniWithDelimiter,
+};
+
+/// Parse a log level name as used in the settings files
+pub(crate) fn log_level_from_str(level: &str) -> u32 {
+    match level {
+        "debug" => LOG_LEVEL_DEBUG,
+        "info" => LOG_LEVEL_INFO,
+        "warning" => LOG_LEVEL_WARNING,
+        "error" => LOG_LEVEL_ERROR,
+        "fatal" => LOG_LEVEL_FATAL,
+        "verbose" => LOG_LEVEL_VERBOSE,
+        _ => LOG_LEVEL_INFO,
+    }
+}
+
+/// Name of a log level as written to the settings files
+pub(crate) fn log_level_to_str(level: u32) -> &'static str {
+    match level {
+        LOG_LEVEL_DEBUG => "debug",
+        LOG_LEVEL_WARNING => "warning",
+        LOG_LEVEL_ERROR => "error",
+        LOG_LEVEL_FATAL => "fatal",
+        LOG_LEVEL_VERBOSE => "verbose",
+        _ => "info",
+    }
+}
 
 // Conversion from YamlSettings to Settings
 impl From<YamlSettings> for Settings {
@@ -37,6 +72,7 @@ impl From<YamlSettings> for Settings {
         settings.proxy_subscription = yaml_settings.common.proxy_subscription;
         settings.append_type = yaml_settings.common.append_proxy_type;
         settings.reload_conf_on_request = yaml_settings.common.reload_conf_on_request;
+        settings.filter_script = yaml_settings.common.filter_script;
 
         // Process default_url and insert_url
         if !yaml_settings.common.default_url.is_empty() {
@@ -58,16 +94,21 @@ impl From<YamlSettings> for Settings {
         settings.sort_script = yaml_settings.node_pref.sort_script;
         settings.filter_deprecated = yaml_settings.node_pref.filter_deprecated_nodes;
         settings.append_userinfo = yaml_settings.node_pref.append_sub_userinfo;
+        settings.append_info = yaml_settings.node_pref.append_info;
+        settings.info_traffic_remark = yaml_settings.node_pref.info_traffic_remark.clone();
+        settings.info_expire_remark = yaml_settings.node_pref.info_expire_remark.clone();
         settings.clash_use_new_field = yaml_settings.node_pref.clash_use_new_field_name;
         settings.clash_proxies_style = yaml_settings.node_pref.clash_proxies_style;
         settings.clash_proxy_groups_style = yaml_settings.node_pref.clash_proxy_groups_style;
         settings.singbox_add_clash_modes = yaml_settings.node_pref.singbox_add_clash_modes;
+        settings.vmess_aead_force = yaml_settings.node_pref.vmess_aead_force;
         // Managed config
         settings.write_managed_config = yaml_settings.managed_config.write_managed_config;
         settings.managed_config_prefix = yaml_settings.managed_config.managed_config_prefix;
         settings.update_interval = yaml_settings.managed_config.config_update_interval;
         settings.update_strict = yaml_settings.managed_config.config_update_strict;
         settings.quanx_dev_id = yaml_settings.managed_config.quanx_device_id;
+        settings.profile_web_page_url = yaml_settings.managed_config.profile_web_page_url;
 
         // Surge external proxy
         settings.surge_ssr_path = yaml_settings.surge_external_proxy.surge_ssr_path;
@@ -80,16 +121,20 @@ impl From<YamlSettings> for Settings {
         // Server
         settings.listen_address = yaml_settings.server.listen;
         settings.listen_port = yaml_settings.server.port;
+        settings.health_check_url = yaml_settings.server.health_check_url;
+        settings.health_check_timeout = yaml_settings.server.health_check_timeout;
+        settings.serve_file = !yaml_settings.server.serve_file_root.is_empty();
+        settings.serve_file_root = yaml_settings.server.serve_file_root;
+
+        // Aliases
+        settings.aliases = yaml_settings
+            .aliases
+            .into_iter()
+            .map(|alias| (alias.uri, alias.target))
+            .collect();
 
         // Advanced
-        settings.log_level = match yaml_settings.advanced.log_level.as_str() {
-            "debug" => LOG_LEVEL_DEBUG,
-            "info" => LOG_LEVEL_INFO,
-            "warning" => LOG_LEVEL_WARNING,
-            "error" => LOG_LEVEL_ERROR,
-            "verbose" => LOG_LEVEL_VERBOSE,
-            _ => LOG_LEVEL_INFO,
-        };
+        settings.log_level = log_level_from_str(&yaml_settings.advanced.log_level);
         if yaml_settings.advanced.print_debug_info {
             settings.log_level = LOG_LEVEL_VERBOSE;
         }
@@ -97,23 +142,34 @@ impl From<YamlSettings> for Settings {
         settings.max_concur_threads = yaml_settings.advanced.max_concurrent_threads;
         settings.max_allowed_rulesets = yaml_settings.advanced.max_allowed_rulesets;
         settings.max_allowed_rules = yaml_settings.advanced.max_allowed_rules;
+        settings.max_allowed_proxies = yaml_settings.advanced.max_allowed_proxies;
         settings.max_allowed_download_size = yaml_settings.advanced.max_allowed_download_size;
+        settings.max_allowed_data_uri_size = yaml_settings.advanced.max_allowed_data_uri_size;
+        settings.max_decoded_size_ratio = yaml_settings.advanced.max_decoded_size_ratio;
+        settings.gzip_response = yaml_settings.advanced.gzip_response;
+        settings.compression_level = yaml_settings.advanced.compression_level;
         settings.cache_subscription = yaml_settings.advanced.cache_subscription;
         settings.cache_config = yaml_settings.advanced.cache_config;
         settings.cache_ruleset = yaml_settings.advanced.cache_ruleset;
         settings.script_clean_context = yaml_settings.advanced.script_clean_context;
         settings.async_fetch_ruleset = yaml_settings.advanced.async_fetch_ruleset;
         settings.skip_failed_links = yaml_settings.advanced.skip_failed_links;
+        settings.node_check_marker = yaml_settings.advanced.node_check_marker.clone();
+        settings.node_check_timeout = yaml_settings.advanced.node_check_timeout;
+        settings.node_check_concurrency = yaml_settings.advanced.node_check_concurrency;
+        settings.node_check_deadline = yaml_settings.advanced.node_check_deadline;
+        settings.geoip_database = yaml_settings.advanced.geoip_database.clone();
 
         // Template
         settings.template_path = yaml_settings.template.template_path;
         settings.template_vars = yaml_settings.template.globals;
-        settings.template_vars = HashMap::new();
 
         // Ruleset settings
         settings.enable_rule_gen = yaml_settings.rulesets.enabled;
         settings.overwrite_original_rules = yaml_settings.rulesets.overwrite_original_rules;
         settings.update_ruleset_on_request = yaml_settings.rulesets.update_ruleset_on_request;
+        settings.ruleset_group_map =
+            parse_rule_group_map(&yaml_settings.rulesets.rename_rule_group);
 
         // update fields processed
         settings.renames = yaml_settings.parsed_rename;
@@ -155,6 +211,7 @@ impl From<TomlSettings> for Settings {
         settings.proxy_subscription = common.proxy_subscription;
         settings.append_type = common.append_proxy_type;
         settings.reload_conf_on_request = common.reload_conf_on_request;
+        settings.filter_script = common.filter_script;
 
         // Process default_url and insert_url
         if !common.default_urls.is_empty() {
@@ -177,10 +234,14 @@ impl From<TomlSettings> for Settings {
         settings.sort_script = node_pref.sort_script.clone();
         settings.filter_deprecated = node_pref.filter_deprecated_nodes;
         settings.append_userinfo = node_pref.append_sub_userinfo;
+        settings.append_info = node_pref.append_info;
+        settings.info_traffic_remark = node_pref.info_traffic_remark.clone();
+        settings.info_expire_remark = node_pref.info_expire_remark.clone();
         settings.clash_use_new_field = node_pref.clash_use_new_field_name;
         settings.clash_proxies_style = node_pref.clash_proxies_style.clone();
         settings.clash_proxy_groups_style = node_pref.clash_proxy_groups_style.clone();
         settings.singbox_add_clash_modes = node_pref.singbox_add_clash_modes;
+        settings.vmess_aead_force = node_pref.vmess_aead_force;
 
         // Managed config
         settings.write_managed_config = toml_settings.managed_config.write_managed_config;
@@ -188,6 +249,7 @@ impl From<TomlSettings> for Settings {
         settings.update_interval = toml_settings.managed_config.config_update_interval;
         settings.update_strict = toml_settings.managed_config.config_update_strict;
         settings.quanx_dev_id = toml_settings.managed_config.quanx_device_id.clone();
+        settings.profile_web_page_url = toml_settings.managed_config.profile_web_page_url.clone();
 
         // Surge external proxy
         settings.surge_ssr_path = toml_settings.surge_external_proxy.surge_ssr_path.clone();
@@ -200,17 +262,20 @@ impl From<TomlSettings> for Settings {
         // Server
         settings.listen_address = toml_settings.server.listen.clone();
         settings.listen_port = toml_settings.server.port;
+        settings.health_check_url = toml_settings.server.health_check_url.clone();
+        settings.health_check_timeout = toml_settings.server.health_check_timeout;
+        settings.serve_file_root = toml_settings.server.serve_file_root.clone();
+        settings.serve_file = !settings.serve_file_root.is_empty();
+
+        // Aliases
+        settings.aliases = toml_settings
+            .aliases
+            .iter()
+            .map(|alias| (alias.uri.clone(), alias.target.clone()))
+            .collect();
 
         // Advanced
-        let log_level = &toml_settings.advanced.log_level;
-        settings.log_level = match log_level.as_str() {
-            "debug" => LOG_LEVEL_DEBUG,
-            "info" => LOG_LEVEL_INFO,
-            "warning" => LOG_LEVEL_WARNING,
-            "error" => LOG_LEVEL_ERROR,
-            "verbose" => LOG_LEVEL_VERBOSE,
-            _ => LOG_LEVEL_INFO,
-        };
+        settings.log_level = log_level_from_str(&toml_settings.advanced.log_level);
         if toml_settings.advanced.print_debug_info {
             settings.log_level = LOG_LEVEL_VERBOSE;
         }
@@ -218,13 +283,23 @@ impl From<TomlSettings> for Settings {
         settings.max_concur_threads = toml_settings.advanced.max_concurrent_threads;
         settings.max_allowed_rulesets = toml_settings.advanced.max_allowed_rulesets;
         settings.max_allowed_rules = toml_settings.advanced.max_allowed_rules;
+        settings.max_allowed_proxies = toml_settings.advanced.max_allowed_proxies;
         settings.max_allowed_download_size = toml_settings.advanced.max_allowed_download_size;
+        settings.max_allowed_data_uri_size = toml_settings.advanced.max_allowed_data_uri_size;
+        settings.max_decoded_size_ratio = toml_settings.advanced.max_decoded_size_ratio;
+        settings.gzip_response = toml_settings.advanced.gzip_response;
+        settings.compression_level = toml_settings.advanced.compression_level;
         settings.cache_subscription = toml_settings.advanced.cache_subscription;
         settings.cache_config = toml_settings.advanced.cache_config;
         settings.cache_ruleset = toml_settings.advanced.cache_ruleset;
         settings.script_clean_context = toml_settings.advanced.script_clean_context;
         settings.async_fetch_ruleset = toml_settings.advanced.async_fetch_ruleset;
         settings.skip_failed_links = toml_settings.advanced.skip_failed_links;
+        settings.node_check_marker = toml_settings.advanced.node_check_marker.clone();
+        settings.node_check_timeout = toml_settings.advanced.node_check_timeout;
+        settings.node_check_concurrency = toml_settings.advanced.node_check_concurrency;
+        settings.node_check_deadline = toml_settings.advanced.node_check_deadline;
+        settings.geoip_database = toml_settings.advanced.geoip_database.clone();
 
         // Template
         settings.template_path = toml_settings.template.template_path.clone();
@@ -236,6 +311,7 @@ impl From<TomlSettings> for Settings {
             settings.overwrite_original_rules = toml_settings.ruleset.overwrite_original_rules;
             settings.update_ruleset_on_request = toml_settings.ruleset.update_ruleset_on_request;
         }
+        settings.ruleset_group_map = parse_rule_group_map(&toml_settings.ruleset.rename_rule_group);
 
         // Ensure listen_address is not empty
         if settings.listen_address.trim().is_empty() {
@@ -317,10 +393,14 @@ impl From<IniSettings> for Settings {
         settings.sort_script = ini_settings.sort_script.clone();
         settings.filter_deprecated = ini_settings.filter_deprecated;
         settings.append_userinfo = ini_settings.append_sub_userinfo;
+        settings.append_info = ini_settings.append_info;
+        settings.info_traffic_remark = ini_settings.info_traffic_remark.clone();
+        settings.info_expire_remark = ini_settings.info_expire_remark.clone();
         settings.clash_use_new_field = ini_settings.clash_use_new_field;
         settings.clash_proxies_style = ini_settings.clash_proxies_style.clone();
         settings.clash_proxy_groups_style = ini_settings.clash_proxy_groups_style.clone();
         settings.singbox_add_clash_modes = ini_settings.singbox_add_clash_modes;
+        settings.vmess_aead_force = ini_settings.vmess_aead_force;
         // Set rename_node from parsed_rename
         settings.renames = ini_settings.parsed_rename;
 
@@ -335,6 +415,7 @@ impl From<IniSettings> for Settings {
         settings.update_interval = ini_settings.update_interval;
         settings.update_strict = ini_settings.update_strict;
         settings.quanx_dev_id = ini_settings.quanx_dev_id.clone();
+        settings.profile_web_page_url = ini_settings.profile_web_page_url.clone();
 
         // RULESET SECTION
         settings.enable_rule_gen = ini_settings.enable_rule_gen;
@@ -347,6 +428,7 @@ impl From<IniSettings> for Settings {
             settings.overwrite_original_rules = false;
             settings.update_ruleset_on_request = false;
         }
+        settings.ruleset_group_map = parse_rule_group_map(&ini_settings.rename_rule_group);
         // PROXY GROUP SECTION
         settings.custom_proxy_groups = ini_settings.parsed_proxy_group;
 
@@ -366,6 +448,8 @@ impl From<IniSettings> for Settings {
         settings.listen_port = ini_settings.listen_port;
         settings.serve_file = ini_settings.serve_file;
         settings.serve_file_root = ini_settings.serve_file_root.clone();
+        settings.health_check_url = ini_settings.health_check_url.clone();
+        settings.health_check_timeout = ini_settings.health_check_timeout;
 
         // ADVANCED SECTION
         settings.log_level = ini_settings.log_level;
@@ -376,7 +460,12 @@ impl From<IniSettings> for Settings {
         settings.max_concur_threads = ini_settings.max_concur_threads;
         settings.max_allowed_rulesets = ini_settings.max_allowed_rulesets;
         settings.max_allowed_rules = ini_settings.max_allowed_rules;
+        settings.max_allowed_proxies = ini_settings.max_allowed_proxies;
         settings.max_allowed_download_size = ini_settings.max_allowed_download_size;
+        settings.max_allowed_data_uri_size = ini_settings.max_allowed_data_uri_size;
+        settings.max_decoded_size_ratio = ini_settings.max_decoded_size_ratio;
+        settings.gzip_response = ini_settings.gzip_response;
+        settings.compression_level = ini_settings.compression_level;
         if ini_settings.enable_cache {
             settings.cache_subscription = ini_settings.cache_subscription;
             settings.cache_config = ini_settings.cache_config;
@@ -391,6 +480,11 @@ impl From<IniSettings> for Settings {
         settings.script_clean_context = ini_settings.script_clean_context;
         settings.async_fetch_ruleset = ini_settings.async_fetch_ruleset;
         settings.skip_failed_links = ini_settings.skip_failed_links;
+        settings.node_check_marker = ini_settings.node_check_marker.clone();
+        settings.node_check_timeout = ini_settings.node_check_timeout;
+        settings.node_check_concurrency = ini_settings.node_check_concurrency;
+        settings.node_check_deadline = ini_settings.node_check_deadline;
+        settings.geoip_database = ini_settings.geoip_database.clone();
 
         // EMOJIS SECTION
         settings.add_emoji = ini_settings.add_emoji;
@@ -405,3 +499,570 @@ impl From<IniSettings> for Settings {
         settings
     }
 }
+
+/// Group members, with providers in the `!!PROVIDER=` form used by INI and YAML
+fn group_rules(group: &ProxyGroupConfig) -> Vec<String> {
+    let mut rules = group.proxies.clone();
+    if !group.using_provider.is_empty() {
+        rules.push(format!("!!PROVIDER={}", group.using_provider.join(",")));
+    }
+    rules
+}
+
+fn sorted_aliases(settings: &Settings) -> BTreeMap<&String, &String> {
+    settings.aliases.iter().collect()
+}
+
+fn enable_cache(settings: &Settings) -> bool {
+    settings.cache_subscription > 0 || settings.cache_config > 0 || settings.cache_ruleset > 0
+}
+
+fn regex_rule_to_toml(rule: &RegexMatchConfig) -> RegexMatchRuleInToml {
+    if rule.replace == "script" {
+        return RegexMatchRuleInToml {
+            script: Some(rule._match.clone()),
+            ..Default::default()
+        };
+    }
+    RegexMatchRuleInToml {
+        match_str: Some(rule._match.clone()),
+        replace: Some(rule.replace.clone()),
+        ..Default::default()
+    }
+}
+
+fn regex_rule_to_yaml(rule: &RegexMatchConfig) -> RegexMatchRuleInYaml {
+    if rule.replace == "script" {
+        return RegexMatchRuleInYaml {
+            script: Some(rule._match.clone()),
+            ..Default::default()
+        };
+    }
+    RegexMatchRuleInYaml {
+        match_str: Some(rule._match.clone()),
+        replace: Some(rule.replace.clone()),
+        ..Default::default()
+    }
+}
+
+// Conversion from Settings to IniSettings, used when saving settings
+impl From<&Settings> for IniSettings {
+    fn from(settings: &Settings) -> Self {
+        let mut ini_settings = IniSettings::new();
+
+        // COMMON SECTION
+        ini_settings.api_mode = settings.api_mode;
+        ini_settings.api_access_token = settings.api_access_token.clone();
+        ini_settings.default_url = settings.default_urls.join("|");
+        ini_settings.enable_insert = settings.enable_insert;
+        ini_settings.insert_url = settings.insert_urls.join("|");
+        ini_settings.prepend_insert_url = settings.prepend_insert;
+        ini_settings.exclude_remarks = settings.exclude_remarks.clone();
+        ini_settings.include_remarks = settings.include_remarks.clone();
+        ini_settings.enable_filter = !settings.filter_script.is_empty();
+        ini_settings.filter_script = settings.filter_script.clone();
+        ini_settings.base_path = settings.base_path.clone();
+        ini_settings.clash_base = settings.clash_base.clone();
+        ini_settings.surge_base = settings.surge_base.clone();
+        ini_settings.surfboard_base = settings.surfboard_base.clone();
+        ini_settings.mellow_base = settings.mellow_base.clone();
+        ini_settings.quan_base = settings.quan_base.clone();
+        ini_settings.quanx_base = settings.quanx_base.clone();
+        ini_settings.loon_base = settings.loon_base.clone();
+        ini_settings.ssub_base = settings.ssub_base.clone();
+        ini_settings.singbox_base = settings.singbox_base.clone();
+        ini_settings.default_ext_config = settings.default_ext_config.clone();
+        ini_settings.append_type = settings.append_type;
+        ini_settings.proxy_config = settings.proxy_config.clone();
+        ini_settings.proxy_ruleset = settings.proxy_ruleset.clone();
+        ini_settings.proxy_subscription = settings.proxy_subscription.clone();
+        ini_settings.reload_conf_on_request = settings.reload_conf_on_request;
+
+        // SURGE EXTERNAL PROXY SECTION
+        ini_settings.surge_ssr_path = settings.surge_ssr_path.clone();
+        ini_settings.surge_resolve_hostname = settings.surge_resolve_hostname;
+
+        // NODE PREFERENCES SECTION
+        ini_settings.udp_flag = settings.udp_flag;
+        ini_settings.tfo_flag = settings.tfo_flag;
+        ini_settings.skip_cert_verify = settings.skip_cert_verify;
+        ini_settings.tls13_flag = settings.tls13_flag;
+        ini_settings.enable_sort = settings.enable_sort;
+        ini_settings.sort_script = settings.sort_script.clone();
+        ini_settings.filter_deprecated = settings.filter_deprecated;
+        ini_settings.append_sub_userinfo = settings.append_userinfo;
+        ini_settings.append_info = settings.append_info;
+        ini_settings.info_traffic_remark = settings.info_traffic_remark.clone();
+        ini_settings.info_expire_remark = settings.info_expire_remark.clone();
+        ini_settings.clash_use_new_field = settings.clash_use_new_field;
+        ini_settings.clash_proxies_style = settings.clash_proxies_style.clone();
+        ini_settings.clash_proxy_groups_style = settings.clash_proxy_groups_style.clone();
+        ini_settings.singbox_add_clash_modes = settings.singbox_add_clash_modes;
+        ini_settings.vmess_aead_force = settings.vmess_aead_force;
+        ini_settings.rename_node = settings
+            .renames
+            .iter()
+            .map(|rule| rule.to_ini_with_delimiter("@"))
+            .collect();
+
+        // USERINFO SECTION
+        ini_settings.stream_rule = settings
+            .stream_rules
+            .iter()
+            .map(|rule| rule.to_ini_with_delimiter("|"))
+            .collect();
+        ini_settings.time_rule = settings
+            .time_rules
+            .iter()
+            .map(|rule| rule.to_ini_with_delimiter("|"))
+            .collect();
+
+        // MANAGED CONFIG SECTION
+        ini_settings.write_managed_config = settings.write_managed_config;
+        ini_settings.managed_config_prefix = settings.managed_config_prefix.clone();
+        ini_settings.update_interval = settings.update_interval;
+        ini_settings.update_strict = settings.update_strict;
+        ini_settings.quanx_dev_id = settings.quanx_dev_id.clone();
+        ini_settings.profile_web_page_url = settings.profile_web_page_url.clone();
+
+        // RULESET SECTION
+        ini_settings.enable_rule_gen = settings.enable_rule_gen;
+        ini_settings.overwrite_original_rules = settings.overwrite_original_rules;
+        ini_settings.update_ruleset_on_request = settings.update_ruleset_on_request;
+        ini_settings.rulesets = settings.custom_rulesets.iter().map(ToIni::to_ini).collect();
+        ini_settings.rename_rule_group = rule_group_map_to_ini(&settings.ruleset_group_map);
+
+        // PROXY GROUP SECTION
+        ini_settings.custom_proxy_group = settings
+            .custom_proxy_groups
+            .iter()
+            .map(ToIni::to_ini)
+            .collect();
+
+        // TEMPLATE SECTION
+        ini_settings.template_path = settings.template_path.clone();
+        ini_settings.template_vars = settings.template_vars.clone();
+
+        // ALIASES SECTION
+        ini_settings.aliases = settings.aliases.clone();
+
+        // TASKS SECTION
+        ini_settings.enable_cron = settings.enable_cron;
+        ini_settings.cron_tasks = settings.cron_tasks.iter().map(ToIni::to_ini).collect();
+
+        // SERVER SECTION
+        ini_settings.listen_address = settings.listen_address.clone();
+        ini_settings.listen_port = settings.listen_port;
+        ini_settings.serve_file = settings.serve_file;
+        ini_settings.serve_file_root = settings.serve_file_root.clone();
+        ini_settings.health_check_url = settings.health_check_url.clone();
+        ini_settings.health_check_timeout = settings.health_check_timeout;
+
+        // ADVANCED SECTION
+        ini_settings.log_level = settings.log_level;
+        ini_settings.max_pending_conns = settings.max_pending_conns;
+        ini_settings.max_concur_threads = settings.max_concur_threads;
+        ini_settings.max_allowed_rulesets = settings.max_allowed_rulesets;
+        ini_settings.max_allowed_rules = settings.max_allowed_rules;
+        ini_settings.max_allowed_proxies = settings.max_allowed_proxies;
+        ini_settings.max_allowed_download_size = settings.max_allowed_download_size;
+        ini_settings.max_allowed_data_uri_size = settings.max_allowed_data_uri_size;
+        ini_settings.max_decoded_size_ratio = settings.max_decoded_size_ratio;
+        ini_settings.gzip_response = settings.gzip_response;
+        ini_settings.compression_level = settings.compression_level;
+        ini_settings.enable_cache = enable_cache(settings);
+        ini_settings.cache_subscription = settings.cache_subscription;
+        ini_settings.cache_config = settings.cache_config;
+        ini_settings.cache_ruleset = settings.cache_ruleset;
+        ini_settings.serve_cache_on_fetch_fail = settings.serve_cache_on_fetch_fail;
+        ini_settings.script_clean_context = settings.script_clean_context;
+        ini_settings.async_fetch_ruleset = settings.async_fetch_ruleset;
+        ini_settings.skip_failed_links = settings.skip_failed_links;
+        ini_settings.node_check_marker = settings.node_check_marker.clone();
+        ini_settings.node_check_timeout = settings.node_check_timeout;
+        ini_settings.node_check_concurrency = settings.node_check_concurrency;
+        ini_settings.node_check_deadline = settings.node_check_deadline;
+        ini_settings.geoip_database = settings.geoip_database.clone();
+
+        // EMOJIS SECTION
+        ini_settings.add_emoji = settings.add_emoji;
+        ini_settings.remove_emoji = settings.remove_emoji;
+        ini_settings.emoji_rules = settings
+            .emojis
+            .iter()
+            .map(|rule| rule.to_ini_with_delimiter(","))
+            .collect();
+
+        ini_settings
+    }
+}
+
+// Conversion from Settings to TomlSettings, used when saving settings
+impl From<&Settings> for TomlSettings {
+    fn from(settings: &Settings) -> Self {
+        let common = toml_settings::CommonSettings {
+            api_mode: settings.api_mode,
+            api_access_token: settings.api_access_token.clone(),
+            default_urls: settings.default_urls.clone(),
+            enable_insert: settings.enable_insert,
+            insert_urls: settings.insert_urls.clone(),
+            prepend_insert_url: settings.prepend_insert,
+            exclude_remarks: settings.exclude_remarks.clone(),
+            include_remarks: settings.include_remarks.clone(),
+            enable_filter: !settings.filter_script.is_empty(),
+            filter_script: settings.filter_script.clone(),
+            default_external_config: settings.default_ext_config.clone(),
+            base_path: settings.base_path.clone(),
+            clash_rule_base: settings.clash_base.clone(),
+            surge_rule_base: settings.surge_base.clone(),
+            surfboard_rule_base: settings.surfboard_base.clone(),
+            mellow_rule_base: settings.mellow_base.clone(),
+            quan_rule_base: settings.quan_base.clone(),
+            quanx_rule_base: settings.quanx_base.clone(),
+            loon_rule_base: settings.loon_base.clone(),
+            sssub_rule_base: settings.ssub_base.clone(),
+            singbox_rule_base: settings.singbox_base.clone(),
+            proxy_config: settings.proxy_config.clone(),
+            proxy_ruleset: settings.proxy_ruleset.clone(),
+            proxy_subscription: settings.proxy_subscription.clone(),
+            append_proxy_type: settings.append_type,
+            reload_conf_on_request: settings.reload_conf_on_request,
+        };
+
+        let node_pref = toml_settings::NodePreferences {
+            udp_flag: settings.udp_flag,
+            tcp_fast_open_flag: settings.tfo_flag,
+            skip_cert_verify_flag: settings.skip_cert_verify,
+            tls13_flag: settings.tls13_flag,
+            sort_flag: settings.enable_sort,
+            sort_script: settings.sort_script.clone(),
+            filter_deprecated_nodes: settings.filter_deprecated,
+            append_sub_userinfo: settings.append_userinfo,
+            append_info: settings.append_info,
+            info_traffic_remark: settings.info_traffic_remark.clone(),
+            info_expire_remark: settings.info_expire_remark.clone(),
+            clash_use_new_field_name: settings.clash_use_new_field,
+            clash_proxies_style: settings.clash_proxies_style.clone(),
+            clash_proxy_groups_style: settings.clash_proxy_groups_style.clone(),
+            singbox_add_clash_modes: settings.singbox_add_clash_modes,
+            vmess_aead_force: settings.vmess_aead_force,
+            rename_node: settings.renames.iter().map(regex_rule_to_toml).collect(),
+        };
+
+        let rulesets = settings
+            .custom_rulesets
+            .iter()
+            .map(|ruleset| RulesetConfigInToml {
+                group: ruleset.group.clone(),
+                ruleset: Some(ruleset.url.clone()),
+                interval: Some(ruleset.interval),
+                ..Default::default()
+            })
+            .collect();
+
+        let custom_proxy_groups = settings
+            .custom_proxy_groups
+            .iter()
+            .map(|group| {
+                let tested = group.is_tested_group();
+                ProxyGroupConfigInToml {
+                    name: group.name.clone(),
+                    group_type: group.type_str().to_string(),
+                    strategy: (group.group_type == ProxyGroupType::LoadBalance)
+                        .then(|| group.strategy_str().to_string()),
+                    rule: group.proxies.clone(),
+                    url: tested.then(|| group.url.clone()),
+                    interval: tested.then_some(group.interval),
+                    lazy: group.lazy.then_some(true),
+                    tolerance: tested.then_some(group.tolerance),
+                    timeout: tested.then_some(group.timeout),
+                    disable_udp: group.disable_udp.then_some(true),
+                    include: group.include_filter.clone(),
+                    exclude: group.exclude_filter.clone(),
+                    import: None,
+                }
+            })
+            .collect();
+
+        TomlSettings {
+            version: 1,
+            common,
+            userinfo: toml_settings::UserInfoSettings {
+                stream_rule: settings
+                    .stream_rules
+                    .iter()
+                    .map(regex_rule_to_toml)
+                    .collect(),
+                time_rule: settings.time_rules.iter().map(regex_rule_to_toml).collect(),
+            },
+            node_pref,
+            managed_config: toml_settings::ManagedConfigSettings {
+                write_managed_config: settings.write_managed_config,
+                managed_config_prefix: settings.managed_config_prefix.clone(),
+                config_update_interval: settings.update_interval,
+                config_update_strict: settings.update_strict,
+                quanx_device_id: settings.quanx_dev_id.clone(),
+                profile_web_page_url: settings.profile_web_page_url.clone(),
+            },
+            surge_external_proxy: toml_settings::SurgeExternalProxySettings {
+                surge_ssr_path: settings.surge_ssr_path.clone(),
+                resolve_hostname: settings.surge_resolve_hostname,
+            },
+            emojis: toml_settings::EmojiSettings {
+                add_emoji: settings.add_emoji,
+                remove_old_emoji: settings.remove_emoji,
+                emoji: settings.emojis.iter().map(regex_rule_to_toml).collect(),
+            },
+            ruleset: toml_settings::RulesetSettings {
+                enabled: settings.enable_rule_gen,
+                overwrite_original_rules: settings.overwrite_original_rules,
+                update_ruleset_on_request: settings.update_ruleset_on_request,
+                rename_rule_group: rule_group_map_to_ini(&settings.ruleset_group_map),
+            },
+            rulesets,
+            custom_proxy_groups,
+            template: toml_settings::TemplateSettings {
+                template_path: settings.template_path.clone(),
+                globals: settings.template_vars.clone(),
+            },
+            aliases: sorted_aliases(settings)
+                .into_iter()
+                .map(|(uri, target)| toml_settings::AliasConfig {
+                    uri: uri.clone(),
+                    target: target.clone(),
+                })
+                .collect(),
+            tasks: settings
+                .cron_tasks
+                .iter()
+                .map(|task| TaskConfigInToml {
+                    name: task.name.clone(),
+                    cronexp: task.cron_exp.clone(),
+                    path: task.path.clone(),
+                    timeout: task.timeout,
+                    import: None,
+                })
+                .collect(),
+            server: toml_settings::ServerSettings {
+                listen: settings.listen_address.clone(),
+                port: settings.listen_port,
+                serve_file_root: settings.serve_file_root.clone(),
+                health_check_url: settings.health_check_url.clone(),
+                health_check_timeout: settings.health_check_timeout,
+            },
+            advanced: toml_settings::AdvancedSettings {
+                log_level: log_level_to_str(settings.log_level).to_string(),
+                print_debug_info: false,
+                max_pending_connections: settings.max_pending_conns,
+                max_concurrent_threads: settings.max_concur_threads,
+                max_allowed_rulesets: settings.max_allowed_rulesets,
+                max_allowed_rules: settings.max_allowed_rules,
+                max_allowed_proxies: settings.max_allowed_proxies,
+                max_allowed_download_size: settings.max_allowed_download_size,
+                max_allowed_data_uri_size: settings.max_allowed_data_uri_size,
+                max_decoded_size_ratio: settings.max_decoded_size_ratio,
+                gzip_response: settings.gzip_response,
+                compression_level: settings.compression_level,
+                enable_cache: enable_cache(settings),
+                cache_subscription: settings.cache_subscription,
+                cache_config: settings.cache_config,
+                cache_ruleset: settings.cache_ruleset,
+                script_clean_context: settings.script_clean_context,
+                async_fetch_ruleset: settings.async_fetch_ruleset,
+                skip_failed_links: settings.skip_failed_links,
+                node_check_marker: settings.node_check_marker.clone(),
+                node_check_timeout: settings.node_check_timeout,
+                node_check_concurrency: settings.node_check_concurrency,
+                node_check_deadline: settings.node_check_deadline,
+                geoip_database: settings.geoip_database.clone(),
+            },
+            ..Default::default()
+        }
+    }
+}
+
+// Conversion from Settings to YamlSettings, used when saving settings
+impl From<&Settings> for YamlSettings {
+    fn from(settings: &Settings) -> Self {
+        let common = yaml_settings::CommonSettings {
+            api_mode: settings.api_mode,
+            api_access_token: settings.api_access_token.clone(),
+            default_url: settings.default_urls.clone(),
+            enable_insert: settings.enable_insert,
+            insert_url: settings.insert_urls.clone(),
+            prepend_insert_url: settings.prepend_insert,
+            exclude_remarks: settings.exclude_remarks.clone(),
+            include_remarks: settings.include_remarks.clone(),
+            enable_filter: !settings.filter_script.is_empty(),
+            filter_script: settings.filter_script.clone(),
+            default_external_config: settings.default_ext_config.clone(),
+            base_path: settings.base_path.clone(),
+            clash_rule_base: settings.clash_base.clone(),
+            surge_rule_base: settings.surge_base.clone(),
+            surfboard_rule_base: settings.surfboard_base.clone(),
+            mellow_rule_base: settings.mellow_base.clone(),
+            quan_rule_base: settings.quan_base.clone(),
+            quanx_rule_base: settings.quanx_base.clone(),
+            loon_rule_base: settings.loon_base.clone(),
+            sssub_rule_base: settings.ssub_base.clone(),
+            singbox_rule_base: settings.singbox_base.clone(),
+            proxy_config: settings.proxy_config.clone(),
+            proxy_ruleset: settings.proxy_ruleset.clone(),
+            proxy_subscription: settings.proxy_subscription.clone(),
+            append_proxy_type: settings.append_type,
+            reload_conf_on_request: settings.reload_conf_on_request,
+        };
+
+        let node_pref = yaml_settings::NodePreferences {
+            udp_flag: settings.udp_flag,
+            tcp_fast_open_flag: settings.tfo_flag,
+            skip_cert_verify_flag: settings.skip_cert_verify,
+            tls13_flag: settings.tls13_flag,
+            sort_flag: settings.enable_sort,
+            sort_script: settings.sort_script.clone(),
+            filter_deprecated_nodes: settings.filter_deprecated,
+            append_sub_userinfo: settings.append_userinfo,
+            append_info: settings.append_info,
+            info_traffic_remark: settings.info_traffic_remark.clone(),
+            info_expire_remark: settings.info_expire_remark.clone(),
+            clash_use_new_field_name: settings.clash_use_new_field,
+            clash_proxies_style: settings.clash_proxies_style.clone(),
+            clash_proxy_groups_style: settings.clash_proxy_groups_style.clone(),
+            singbox_add_clash_modes: settings.singbox_add_clash_modes,
+            vmess_aead_force: settings.vmess_aead_force,
+            rename_node: settings.renames.iter().map(regex_rule_to_yaml).collect(),
+        };
+
+        let rulesets = settings
+            .custom_rulesets
+            .iter()
+            .map(|ruleset| match ruleset.url.strip_prefix("[]") {
+                Some(rule) => RulesetConfigInYaml {
+                    group: ruleset.group.clone(),
+                    rule: Some(rule.to_string()),
+                    ..Default::default()
+                },
+                None => RulesetConfigInYaml {
+                    group: ruleset.group.clone(),
+                    ruleset: Some(ruleset.url.clone()),
+                    interval: (ruleset.interval > 0).then_some(ruleset.interval),
+                    ..Default::default()
+                },
+            })
+            .collect();
+
+        let custom_proxy_group = settings
+            .custom_proxy_groups
+            .iter()
+            .map(|group| {
+                let tested = group.is_tested_group();
+                ProxyGroupConfigInYaml {
+                    name: group.name.clone(),
+                    group_type: group.type_str().to_string(),
+                    rule: group_rules(group),
+                    url: tested.then(|| group.url.clone()),
+                    interval: tested.then_some(group.interval),
+                    tolerance: tested.then_some(group.tolerance),
+                    timeout: tested.then_some(group.timeout),
+                    include: group.include_filter.clone(),
+                    exclude: group.exclude_filter.clone(),
+                    import: None,
+                }
+            })
+            .collect();
+
+        YamlSettings {
+            common,
+            userinfo: yaml_settings::UserInfoSettings {
+                stream_rule: settings
+                    .stream_rules
+                    .iter()
+                    .map(regex_rule_to_yaml)
+                    .collect(),
+                time_rule: settings.time_rules.iter().map(regex_rule_to_yaml).collect(),
+            },
+            node_pref,
+            managed_config: yaml_settings::ManagedConfigSettings {
+                write_managed_config: settings.write_managed_config,
+                managed_config_prefix: settings.managed_config_prefix.clone(),
+                config_update_interval: settings.update_interval,
+                config_update_strict: settings.update_strict,
+                quanx_device_id: settings.quanx_dev_id.clone(),
+                profile_web_page_url: settings.profile_web_page_url.clone(),
+            },
+            surge_external_proxy: yaml_settings::SurgeExternalProxySettings {
+                surge_ssr_path: settings.surge_ssr_path.clone(),
+                resolve_hostname: settings.surge_resolve_hostname,
+            },
+            emojis: yaml_settings::EmojiSettings {
+                add_emoji: settings.add_emoji,
+                remove_old_emoji: settings.remove_emoji,
+                rules: settings.emojis.iter().map(regex_rule_to_yaml).collect(),
+            },
+            rulesets: yaml_settings::RulesetSettings {
+                enabled: settings.enable_rule_gen,
+                overwrite_original_rules: settings.overwrite_original_rules,
+                update_ruleset_on_request: settings.update_ruleset_on_request,
+                rename_rule_group: rule_group_map_to_ini(&settings.ruleset_group_map),
+                rulesets,
+            },
+            proxy_groups: yaml_settings::ProxyGroupsSettings { custom_proxy_group },
+            template: yaml_settings::TemplateSettings {
+                template_path: settings.template_path.clone(),
+                globals: settings.template_vars.clone(),
+            },
+            aliases: sorted_aliases(settings)
+                .into_iter()
+                .map(|(uri, target)| yaml_settings::AliasConfig {
+                    uri: uri.clone(),
+                    target: target.clone(),
+                })
+                .collect(),
+            tasks: settings
+                .cron_tasks
+                .iter()
+                .map(|task| TaskConfigInYaml {
+                    name: task.name.clone(),
+                    cronexp: task.cron_exp.clone(),
+                    path: task.path.clone(),
+                    timeout: task.timeout,
+                    import: None,
+                })
+                .collect(),
+            server: yaml_settings::ServerSettings {
+                listen: settings.listen_address.clone(),
+                port: settings.listen_port,
+                serve_file_root: settings.serve_file_root.clone(),
+                health_check_url: settings.health_check_url.clone(),
+                health_check_timeout: settings.health_check_timeout,
+            },
+            advanced: yaml_settings::AdvancedSettings {
+                log_level: log_level_to_str(settings.log_level).to_string(),
+                print_debug_info: false,
+                max_pending_connections: settings.max_pending_conns,
+                max_concurrent_threads: settings.max_concur_threads,
+                max_allowed_rulesets: settings.max_allowed_rulesets,
+                max_allowed_rules: settings.max_allowed_rules,
+                max_allowed_proxies: settings.max_allowed_proxies,
+                max_allowed_download_size: settings.max_allowed_download_size,
+                max_allowed_data_uri_size: settings.max_allowed_data_uri_size,
+                max_decoded_size_ratio: settings.max_decoded_size_ratio,
+                gzip_response: settings.gzip_response,
+                compression_level: settings.compression_level,
+                enable_cache: enable_cache(settings),
+                cache_subscription: settings.cache_subscription,
+                cache_config: settings.cache_config,
+                cache_ruleset: settings.cache_ruleset,
+                script_clean_context: settings.script_clean_context,
+                async_fetch_ruleset: settings.async_fetch_ruleset,
+                skip_failed_links: settings.skip_failed_links,
+                node_check_marker: settings.node_check_marker.clone(),
+                node_check_timeout: settings.node_check_timeout,
+                node_check_concurrency: settings.node_check_concurrency,
+                node_check_deadline: settings.node_check_deadline,
+                geoip_database: settings.geoip_database.clone(),
+            },
+            ..Default::default()
+        }
+    }
+}
@@ -1,7 +1,9 @@
 use super::super::ini_bindings::{FromIni, FromIniWithDelimiter};
+use super::conversions::{log_level_from_str, log_level_to_str};
 use crate::utils::http::parse_proxy;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Display, Write};
 use std::fs;
 
 use crate::{
@@ -51,6 +53,16 @@ pub struct IniSettings {
     pub prepend_insert: bool,
     #[serde(default)]
     pub skip_failed_links: bool,
+    #[serde(default = "default_node_check_marker")]
+    pub node_check_marker: String,
+    #[serde(default = "default_node_check_timeout")]
+    pub node_check_timeout: u32,
+    #[serde(default = "default_node_check_concurrency")]
+    pub node_check_concurrency: usize,
+    #[serde(default = "default_node_check_deadline")]
+    pub node_check_deadline: u32,
+    #[serde(default)]
+    pub geoip_database: String,
     #[serde(default)]
     pub write_managed_config: bool,
     #[serde(default = "default_true")]
@@ -64,6 +76,12 @@ pub struct IniSettings {
     #[serde(default = "default_true")]
     pub append_sub_userinfo: bool,
     #[serde(default)]
+    pub append_info: bool,
+    #[serde(default = "default_info_traffic_remark")]
+    pub info_traffic_remark: String,
+    #[serde(default = "default_info_expire_remark")]
+    pub info_expire_remark: String,
+    #[serde(default)]
     pub async_fetch_ruleset: bool,
     #[serde(default)]
     pub surge_resolve_hostname: bool,
@@ -73,6 +91,14 @@ pub struct IniSettings {
     pub log_level: u32,
     #[serde(default = "default_max_download_size")]
     pub max_allowed_download_size: i64,
+    #[serde(default = "default_max_data_uri_size")]
+    pub max_allowed_data_uri_size: i64,
+    #[serde(default = "default_max_decoded_size_ratio")]
+    pub max_decoded_size_ratio: usize,
+    #[serde(default)]
+    pub gzip_response: bool,
+    #[serde(default)]
+    pub compression_level: u32,
     pub template_path: String,
     #[serde(default)]
     pub template_vars: HashMap<String, String>,
@@ -109,6 +135,8 @@ pub struct IniSettings {
     #[serde(default)]
     pub singbox_add_clash_modes: bool,
     #[serde(default)]
+    pub vmess_aead_force: bool,
+    #[serde(default)]
     pub rename_node: Vec<String>,
     #[serde(default)]
     pub stream_rule: Vec<String>,
@@ -139,6 +167,7 @@ pub struct IniSettings {
     pub singbox_base: String,
     pub surge_ssr_path: String,
     pub quanx_dev_id: String,
+    pub profile_web_page_url: String,
 
     // Cache system
     #[serde(default)]
@@ -157,6 +186,8 @@ pub struct IniSettings {
     pub max_allowed_rulesets: usize,
     #[serde(default = "default_max_rules")]
     pub max_allowed_rules: usize,
+    #[serde(default = "default_max_proxies")]
+    pub max_allowed_proxies: usize,
     #[serde(default)]
     pub script_clean_context: bool,
 
@@ -170,12 +201,18 @@ pub struct IniSettings {
     #[serde(default)]
     pub rulesets: Vec<String>,
     #[serde(default)]
+    pub rename_rule_group: Vec<String>,
+    #[serde(default)]
     pub custom_proxy_group: Vec<String>,
 
     // Webserver settings
     #[serde(default)]
     pub serve_file: bool,
     pub serve_file_root: String,
+    #[serde(default = "default_health_check_url")]
+    pub health_check_url: String,
+    #[serde(default = "default_health_check_timeout")]
+    pub health_check_timeout: u32,
 
     // Aliases
     #[serde(default)]
@@ -226,6 +263,30 @@ fn default_max_download_size() -> i64 {
     32 * 1024 * 1024 // 32MB
 }
 
+fn default_max_data_uri_size() -> i64 {
+    1024 * 1024 // 1MB
+}
+
+fn default_max_decoded_size_ratio() -> usize {
+    2
+}
+
+fn default_node_check_marker() -> String {
+    "[DEAD]".to_string()
+}
+
+fn default_node_check_timeout() -> u32 {
+    1500
+}
+
+fn default_node_check_concurrency() -> usize {
+    32
+}
+
+fn default_node_check_deadline() -> u32 {
+    10
+}
+
 fn default_cache_subscription() -> u32 {
     60
 }
@@ -238,6 +299,22 @@ fn default_cache_ruleset() -> u32 {
     21600
 }
 
+fn default_health_check_url() -> String {
+    "http://www.gstatic.com/generate_204".to_string()
+}
+
+fn default_health_check_timeout() -> u32 {
+    5
+}
+
+fn default_info_traffic_remark() -> String {
+    "剩余流量: {traffic}".to_string()
+}
+
+fn default_info_expire_remark() -> String {
+    "到期时间: {expire}".to_string()
+}
+
 fn default_max_rulesets() -> usize {
     64
 }
@@ -246,6 +323,10 @@ fn default_max_rules() -> usize {
     32768
 }
 
+fn default_max_proxies() -> usize {
+    8192
+}
+
 impl IniSettings {
     /// Create a new settings instance with default values
     pub fn new() -> Self {
@@ -257,20 +338,48 @@ impl IniSettings {
         let proxy_config = parse_proxy(&self.proxy_config);
 
         // Process rule rename_node
-        import_items(&mut self.rename_node, false, &proxy_config, &self.base_path).await?;
+        import_items(
+            &mut self.rename_node,
+            false,
+            &proxy_config,
+            &self.base_path,
+            self.cache_config,
+        )
+        .await?;
         self.parsed_rename = RegexMatchConfigs::from_ini_with_delimiter(&self.rename_node, "@");
 
         // Process stream rules
-        import_items(&mut self.stream_rule, false, &proxy_config, &self.base_path).await?;
+        import_items(
+            &mut self.stream_rule,
+            false,
+            &proxy_config,
+            &self.base_path,
+            self.cache_config,
+        )
+        .await?;
         self.parsed_stream_rule =
             RegexMatchConfigs::from_ini_with_delimiter(&self.stream_rule, "|");
 
         // Process time rules
-        import_items(&mut self.time_rule, false, &proxy_config, &self.base_path).await?;
+        import_items(
+            &mut self.time_rule,
+            false,
+            &proxy_config,
+            &self.base_path,
+            self.cache_config,
+        )
+        .await?;
         self.parsed_time_rule = RegexMatchConfigs::from_ini_with_delimiter(&self.time_rule, "|");
 
         // Process emoji rules
-        import_items(&mut self.emoji_rules, false, &proxy_config, &self.base_path).await?;
+        import_items(
+            &mut self.emoji_rules,
+            false,
+            &proxy_config,
+            &self.base_path,
+            self.cache_config,
+        )
+        .await?;
         self.parsed_emoji_rules =
             RegexMatchConfigs::from_ini_with_delimiter(&self.emoji_rules, ",");
 
@@ -280,16 +389,31 @@ impl IniSettings {
             false,
             &proxy_config,
             &self.base_path,
+            self.cache_config,
         )
         .await?;
         self.parsed_proxy_group = ProxyGroupConfigs::from_ini(&self.custom_proxy_group);
 
         // Process rulesets
-        import_items(&mut self.rulesets, false, &proxy_config, &self.base_path).await?;
+        import_items(
+            &mut self.rulesets,
+            false,
+            &proxy_config,
+            &self.base_path,
+            self.cache_config,
+        )
+        .await?;
         self.parsed_ruleset = RulesetConfigs::from_ini(&self.rulesets);
 
         // Process cron tasks
-        import_items(&mut self.cron_tasks, false, &proxy_config, &self.base_path).await?;
+        import_items(
+            &mut self.cron_tasks,
+            false,
+            &proxy_config,
+            &self.base_path,
+            self.cache_config,
+        )
+        .await?;
         self.parsed_tasks = CronTaskConfigs::from_ini(&self.cron_tasks);
 
         Ok(())
@@ -366,7 +490,7 @@ impl IniSettings {
             "default_url" => self.default_url = value.to_string(),
             "enable_insert" => self.enable_insert = parse_bool(value),
             "insert_url" => self.insert_url = value.to_string(),
-            "prepend_insert_url" => self.prepend_insert = parse_bool(value),
+            "prepend_insert_url" => self.prepend_insert_url = parse_bool(value),
             "exclude_remarks" => self.exclude_remarks.push(value.to_owned()),
             "include_remarks" => self.include_remarks.push(value.to_owned()),
             "enable_filter" => self.enable_filter = parse_bool(value),
@@ -407,12 +531,18 @@ impl IniSettings {
             "tls13_flag" => self.tls13_flag = Some(parse_bool(value)),
             "sort_flag" => self.enable_sort = parse_bool(value),
             "sort_script" => self.sort_script = value.to_string(),
-            "filter_deprecated" => self.filter_deprecated = parse_bool(value),
+            "filter_deprecated" | "filter_deprecated_nodes" => {
+                self.filter_deprecated = parse_bool(value)
+            }
             "append_sub_userinfo" => self.append_sub_userinfo = parse_bool(value),
+            "append_info" => self.append_info = parse_bool(value),
+            "info_traffic_remark" => self.info_traffic_remark = value.to_string(),
+            "info_expire_remark" => self.info_expire_remark = value.to_string(),
             "clash_use_new_field_name" => self.clash_use_new_field = parse_bool(value),
             "clash_proxies_style" => self.clash_proxies_style = value.to_string(),
             "clash_proxy_groups_style" => self.clash_proxy_groups_style = value.to_string(),
             "singbox_add_clash_modes" => self.singbox_add_clash_modes = parse_bool(value),
+            "vmess_aead_force" => self.vmess_aead_force = parse_bool(value),
             "rename_node" => self.rename_node.push(value.to_string()),
             _ => {}
         }
@@ -437,6 +567,7 @@ impl IniSettings {
             }
             "config_update_strict" => self.update_strict = parse_bool(value),
             "quanx_device_id" => self.quanx_dev_id = value.to_string(),
+            "profile_web_page_url" => self.profile_web_page_url = value.to_string(),
             _ => {}
         }
     }
@@ -458,6 +589,7 @@ impl IniSettings {
             "ruleset" | "surge_ruleset" => {
                 self.rulesets.push(value.to_string());
             }
+            "rename_rule_group" => self.rename_rule_group.push(value.to_string()),
             _ => {}
         }
     }
@@ -498,6 +630,7 @@ impl IniSettings {
                     // Format is typically "name`cronexp`path`timeout"
                     self.enable_cron = true;
                     // Tasks will be processed in process_imports
+                    self.cron_tasks.push(value.to_string());
                 }
             }
             _ => {}
@@ -516,6 +649,12 @@ impl IniSettings {
                 self.serve_file_root = value.to_string();
                 self.serve_file = !self.serve_file_root.is_empty();
             }
+            "health_check_url" => self.health_check_url = value.to_string(),
+            "health_check_timeout" => {
+                if let Ok(val) = value.parse() {
+                    self.health_check_timeout = val
+                }
+            }
             _ => {}
         }
     }
@@ -523,9 +662,7 @@ impl IniSettings {
     fn process_advanced_section(&mut self, key: &str, value: &str) {
         match key {
             "log_level" => {
-                if let Ok(val) = value.parse() {
-                    self.log_level = val
-                }
+                self.log_level = value.parse().unwrap_or_else(|_| log_level_from_str(value))
             }
             "print_debug_info" => self.print_dbg_info = parse_bool(value),
             "max_pending_connections" => {
@@ -548,11 +685,32 @@ impl IniSettings {
                     self.max_allowed_rules = val
                 }
             }
+            "max_allowed_proxies" => {
+                if let Ok(val) = value.parse() {
+                    self.max_allowed_proxies = val
+                }
+            }
             "max_allowed_download_size" => {
                 if let Ok(val) = value.parse() {
                     self.max_allowed_download_size = val
                 }
             }
+            "max_allowed_data_uri_size" => {
+                if let Ok(val) = value.parse() {
+                    self.max_allowed_data_uri_size = val
+                }
+            }
+            "max_decoded_size_ratio" => {
+                if let Ok(val) = value.parse() {
+                    self.max_decoded_size_ratio = val
+                }
+            }
+            "gzip_response" => self.gzip_response = parse_bool(value),
+            "compression_level" => {
+                if let Ok(val) = value.parse() {
+                    self.compression_level = val
+                }
+            }
             "enable_cache" => {
                 self.enable_cache = parse_bool(value);
             }
@@ -575,12 +733,239 @@ impl IniSettings {
             "script_clean_context" => self.script_clean_context = parse_bool(value),
             "async_fetch_ruleset" => self.async_fetch_ruleset = parse_bool(value),
             "skip_failed_links" => self.skip_failed_links = parse_bool(value),
+            "node_check_marker" => self.node_check_marker = value.to_string(),
+            "node_check_timeout" => {
+                if let Ok(val) = value.parse() {
+                    self.node_check_timeout = val
+                }
+            }
+            "node_check_concurrency" => {
+                if let Ok(val) = value.parse() {
+                    self.node_check_concurrency = val
+                }
+            }
+            "node_check_deadline" => {
+                if let Ok(val) = value.parse() {
+                    self.node_check_deadline = val
+                }
+            }
+            "geoip_database" => self.geoip_database = value.to_string(),
             _ => {}
         }
     }
+
+    /// Render the settings as INI content that `load_from_ini` reads back
+    pub fn to_ini(&self) -> String {
+        let mut out = String::new();
+
+        write_section(&mut out, "common");
+        write_value(&mut out, "api_mode", self.api_mode);
+        write_value(&mut out, "api_access_token", &self.api_access_token);
+        write_value(&mut out, "default_url", &self.default_url);
+        write_value(&mut out, "enable_insert", self.enable_insert);
+        write_value(&mut out, "insert_url", &self.insert_url);
+        write_value(&mut out, "prepend_insert_url", self.prepend_insert_url);
+        write_values(&mut out, "exclude_remarks", &self.exclude_remarks);
+        write_values(&mut out, "include_remarks", &self.include_remarks);
+        write_value(&mut out, "enable_filter", self.enable_filter);
+        write_value(&mut out, "filter_script", &self.filter_script);
+        write_value(&mut out, "base_path", &self.base_path);
+        write_value(&mut out, "clash_rule_base", &self.clash_base);
+        write_value(&mut out, "surge_rule_base", &self.surge_base);
+        write_value(&mut out, "surfboard_rule_base", &self.surfboard_base);
+        write_value(&mut out, "mellow_rule_base", &self.mellow_base);
+        write_value(&mut out, "quan_rule_base", &self.quan_base);
+        write_value(&mut out, "quanx_rule_base", &self.quanx_base);
+        write_value(&mut out, "loon_rule_base", &self.loon_base);
+        write_value(&mut out, "sssub_rule_base", &self.ssub_base);
+        write_value(&mut out, "singbox_rule_base", &self.singbox_base);
+        write_value(
+            &mut out,
+            "default_external_config",
+            &self.default_ext_config,
+        );
+        write_value(&mut out, "proxy_config", &self.proxy_config);
+        write_value(&mut out, "proxy_ruleset", &self.proxy_ruleset);
+        write_value(&mut out, "proxy_subscription", &self.proxy_subscription);
+        write_value(&mut out, "append_proxy_type", self.append_type);
+        write_value(
+            &mut out,
+            "reload_conf_on_request",
+            self.reload_conf_on_request,
+        );
+
+        write_section(&mut out, "userinfo");
+        write_values(&mut out, "stream_rule", &self.stream_rule);
+        write_values(&mut out, "time_rule", &self.time_rule);
+
+        write_section(&mut out, "node_pref");
+        // Unset flags are left out so the target's default applies
+        let flags = [
+            ("udp_flag", self.udp_flag),
+            ("tcp_fast_open_flag", self.tfo_flag),
+            ("skip_cert_verify_flag", self.skip_cert_verify),
+            ("tls13_flag", self.tls13_flag),
+        ];
+        for (key, flag) in flags {
+            if let Some(flag) = flag {
+                write_value(&mut out, key, flag);
+            }
+        }
+        write_value(&mut out, "sort_flag", self.enable_sort);
+        write_value(&mut out, "sort_script", &self.sort_script);
+        write_value(&mut out, "filter_deprecated_nodes", self.filter_deprecated);
+        write_value(&mut out, "append_sub_userinfo", self.append_sub_userinfo);
+        write_value(&mut out, "append_info", self.append_info);
+        write_value(&mut out, "info_traffic_remark", &self.info_traffic_remark);
+        write_value(&mut out, "info_expire_remark", &self.info_expire_remark);
+        write_value(
+            &mut out,
+            "clash_use_new_field_name",
+            self.clash_use_new_field,
+        );
+        write_value(&mut out, "clash_proxies_style", &self.clash_proxies_style);
+        write_value(
+            &mut out,
+            "clash_proxy_groups_style",
+            &self.clash_proxy_groups_style,
+        );
+        write_value(
+            &mut out,
+            "singbox_add_clash_modes",
+            self.singbox_add_clash_modes,
+        );
+        write_value(&mut out, "vmess_aead_force", self.vmess_aead_force);
+        write_values(&mut out, "rename_node", &self.rename_node);
+
+        write_section(&mut out, "managed_config");
+        write_value(&mut out, "write_managed_config", self.write_managed_config);
+        write_value(
+            &mut out,
+            "managed_config_prefix",
+            &self.managed_config_prefix,
+        );
+        write_value(&mut out, "config_update_interval", self.update_interval);
+        write_value(&mut out, "config_update_strict", self.update_strict);
+        write_value(&mut out, "quanx_device_id", &self.quanx_dev_id);
+        write_value(&mut out, "profile_web_page_url", &self.profile_web_page_url);
+
+        write_section(&mut out, "surge_external_proxy");
+        write_value(&mut out, "surge_ssr_path", &self.surge_ssr_path);
+        write_value(&mut out, "resolve_hostname", self.surge_resolve_hostname);
+
+        write_section(&mut out, "emojis");
+        write_value(&mut out, "add_emoji", self.add_emoji);
+        write_value(&mut out, "remove_old_emoji", self.remove_emoji);
+        write_values(&mut out, "rule", &self.emoji_rules);
+
+        write_section(&mut out, "rulesets");
+        write_value(&mut out, "enabled", self.enable_rule_gen);
+        write_value(
+            &mut out,
+            "overwrite_original_rules",
+            self.overwrite_original_rules,
+        );
+        write_value(
+            &mut out,
+            "update_ruleset_on_request",
+            self.update_ruleset_on_request,
+        );
+        write_values(&mut out, "ruleset", &self.rulesets);
+        write_values(&mut out, "rename_rule_group", &self.rename_rule_group);
+
+        write_section(&mut out, "proxy_groups");
+        write_values(&mut out, "custom_proxy_group", &self.custom_proxy_group);
+
+        write_section(&mut out, "template");
+        write_value(&mut out, "template_path", &self.template_path);
+        for (key, value) in self.template_vars.iter().collect::<BTreeMap<_, _>>() {
+            write_value(&mut out, key, value);
+        }
+
+        write_section(&mut out, "aliases");
+        for (uri, target) in self.aliases.iter().collect::<BTreeMap<_, _>>() {
+            write_value(&mut out, uri, target);
+        }
+
+        write_section(&mut out, "tasks");
+        write_values(&mut out, "task", &self.cron_tasks);
+
+        write_section(&mut out, "server");
+        write_value(&mut out, "listen", &self.listen_address);
+        write_value(&mut out, "port", self.listen_port);
+        write_value(&mut out, "serve_file_root", &self.serve_file_root);
+        write_value(&mut out, "health_check_url", &self.health_check_url);
+        write_value(&mut out, "health_check_timeout", self.health_check_timeout);
+
+        write_section(&mut out, "advanced");
+        write_value(&mut out, "log_level", log_level_to_str(self.log_level));
+        write_value(&mut out, "print_debug_info", self.print_dbg_info);
+        write_value(&mut out, "max_pending_connections", self.max_pending_conns);
+        write_value(&mut out, "max_concurrent_threads", self.max_concur_threads);
+        write_value(&mut out, "max_allowed_rulesets", self.max_allowed_rulesets);
+        write_value(&mut out, "max_allowed_rules", self.max_allowed_rules);
+        write_value(&mut out, "max_allowed_proxies", self.max_allowed_proxies);
+        write_value(
+            &mut out,
+            "max_allowed_download_size",
+            self.max_allowed_download_size,
+        );
+        write_value(
+            &mut out,
+            "max_allowed_data_uri_size",
+            self.max_allowed_data_uri_size,
+        );
+        write_value(
+            &mut out,
+            "max_decoded_size_ratio",
+            self.max_decoded_size_ratio,
+        );
+        write_value(&mut out, "gzip_response", self.gzip_response);
+        write_value(&mut out, "compression_level", self.compression_level);
+        write_value(&mut out, "enable_cache", self.enable_cache);
+        write_value(&mut out, "cache_subscription", self.cache_subscription);
+        write_value(&mut out, "cache_config", self.cache_config);
+        write_value(&mut out, "cache_ruleset", self.cache_ruleset);
+        write_value(
+            &mut out,
+            "serve_cache_on_fetch_fail",
+            self.serve_cache_on_fetch_fail,
+        );
+        write_value(&mut out, "script_clean_context", self.script_clean_context);
+        write_value(&mut out, "async_fetch_ruleset", self.async_fetch_ruleset);
+        write_value(&mut out, "skip_failed_links", self.skip_failed_links);
+        write_value(&mut out, "node_check_marker", &self.node_check_marker);
+        write_value(&mut out, "node_check_timeout", self.node_check_timeout);
+        write_value(
+            &mut out,
+            "node_check_concurrency",
+            self.node_check_concurrency,
+        );
+        write_value(&mut out, "node_check_deadline", self.node_check_deadline);
+        write_value(&mut out, "geoip_database", &self.geoip_database);
+
+        out
+    }
 }
 
 /// Parse a string as boolean
 fn parse_bool(value: &str) -> bool {
     value.to_lowercase() == "true" || value == "1"
 }
+
+fn write_section(out: &mut String, name: &str) {
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    let _ = writeln!(out, "[{}]", name);
+}
+
+fn write_value(out: &mut String, key: &str, value: impl Display) {
+    let _ = writeln!(out, "{}={}", key, value);
+}
+
+fn write_values(out: &mut String, key: &str, values: &[String]) {
+    for value in values {
+        write_value(out, key, value);
+    }
+}
@@ -14,13 +14,16 @@ use log::info;
 use serde_yaml;
 use toml;
 
+use super::{IniSettings, TomlSettings, YamlSettings};
 use crate::models::cron::CronTaskConfigs;
 use crate::models::proxy_group_config::ProxyGroupConfig;
 use crate::models::ruleset::RulesetContent;
 use crate::models::RegexMatchConfig;
 use crate::models::RegexMatchConfigs;
 use crate::models::RulesetConfig;
+use crate::settings::utils::interpolate_env_in;
 use crate::utils::file::copy_file;
+use crate::utils::file::write_file_async;
 use crate::utils::file_exists;
 use crate::utils::file_get_async;
 use crate::utils::http::ProxyConfig;
@@ -98,17 +101,31 @@ impl<'a, T> std::ops::DerefMut for MockWriteGuard<'a, T> {
 // Type aliases for consistent code across targets
 #[cfg(target_arch = "wasm32")]
 type GlobalLock<T> = MockRwLock<T>;
-#[cfg(target_arch = "wasm32")]
-type GlobalLockReadGuard<'a, T> = MockReadGuard<'a, T>;
-#[cfg(target_arch = "wasm32")]
-type GlobalLockWriteGuard<'a, T> = MockWriteGuard<'a, T>;
 
 #[cfg(not(target_arch = "wasm32"))]
 type GlobalLock<T> = RwLock<T>;
-#[cfg(not(target_arch = "wasm32"))]
-type GlobalLockReadGuard<'a, T> = std::sync::RwLockReadGuard<'a, T>;
-#[cfg(not(target_arch = "wasm32"))]
-type GlobalLockWriteGuard<'a, T> = std::sync::RwLockWriteGuard<'a, T>;
+
+/// File formats settings can be loaded from and saved to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingsFormat {
+    Ini,
+    Toml,
+    Yaml,
+}
+
+impl SettingsFormat {
+    /// Pick the format from the file extension, falling back to INI like the loader does
+    pub fn from_path(path: &str) -> Self {
+        let path = path.to_lowercase();
+        if path.ends_with(".yml") || path.ends_with(".yaml") {
+            SettingsFormat::Yaml
+        } else if path.ends_with(".toml") {
+            SettingsFormat::Toml
+        } else {
+            SettingsFormat::Ini
+        }
+    }
+}
 
 /// Settings structure to hold global configuration
 #[derive(Debug, Clone)]
@@ -139,12 +156,25 @@ pub struct Settings {
     pub max_concur_threads: u32,
     pub prepend_insert: bool,
     pub skip_failed_links: bool,
+    /// Appended to the remark of nodes failing the `check` probe, after a space
+    pub node_check_marker: String,
+    /// Timeout of one node probe in milliseconds
+    pub node_check_timeout: u32,
+    pub node_check_concurrency: usize,
+    /// Time budget in seconds for probing all nodes of a request
+    pub node_check_deadline: u32,
+    /// Path of the MaxMind country database used by `geo_emoji`, empty to disable it
+    pub geoip_database: String,
     pub api_mode: bool,
     pub write_managed_config: bool,
     pub enable_rule_gen: bool,
     pub update_ruleset_on_request: bool,
     pub overwrite_original_rules: bool,
+    pub ruleset_group_map: Vec<(String, String)>,
     pub append_userinfo: bool,
+    pub append_info: bool,
+    pub info_traffic_remark: String,
+    pub info_expire_remark: String,
     pub async_fetch_ruleset: bool,
     pub surge_resolve_hostname: bool,
     pub api_access_token: String,
@@ -152,6 +182,13 @@ pub struct Settings {
     pub custom_group: String,
     pub log_level: u32,
     pub max_allowed_download_size: i64,
+    pub max_allowed_data_uri_size: i64,
+    /// Bound of the text of the nodes parsed from a subscription, as a multiple of its size
+    pub max_decoded_size_ratio: usize,
+    /// Compress responses with gzip, Brotli or zstd following `Accept-Encoding`
+    pub gzip_response: bool,
+    /// Level of the gzip compression from 1 to 9, 0 for the default level of each encoding
+    pub compression_level: u32,
     pub template_path: String,
     /// Template variables used for template rendering
     pub template_vars: HashMap<String, String>,
@@ -178,6 +215,8 @@ pub struct Settings {
     pub update_strict: bool,
     pub clash_use_new_field: bool,
     pub singbox_add_clash_modes: bool,
+    /// Write every VMess node with alterId 0, i.e. AEAD
+    pub vmess_aead_force: bool,
     pub clash_proxies_style: String,
     pub clash_proxy_groups_style: String,
     pub proxy_config: String,
@@ -199,6 +238,8 @@ pub struct Settings {
     pub singbox_base: String,
     pub surge_ssr_path: String,
     pub quanx_dev_id: String,
+    /// Sent as the `Profile-Web-Page-Url` header of Clash responses
+    pub profile_web_page_url: String,
 
     // Cache system
     pub serve_cache_on_fetch_fail: bool,
@@ -211,10 +252,13 @@ pub struct Settings {
     pub listen_port: u32,
     pub serve_file: bool,
     pub serve_file_root: String,
+    pub health_check_url: String,
+    pub health_check_timeout: u32,
 
     // Limits
     pub max_allowed_rulesets: usize,
     pub max_allowed_rules: usize,
+    pub max_allowed_proxies: usize,
     pub script_clean_context: bool,
 
     // Cron system
@@ -251,6 +295,30 @@ pub fn default_max_download_size() -> i64 {
     32 * 1024 * 1024 // 32MB
 }
 
+pub fn default_max_data_uri_size() -> i64 {
+    1024 * 1024 // 1MB
+}
+
+pub fn default_max_decoded_size_ratio() -> usize {
+    2
+}
+
+pub fn default_node_check_marker() -> String {
+    "[DEAD]".to_string()
+}
+
+pub fn default_node_check_timeout() -> u32 {
+    1500
+}
+
+pub fn default_node_check_concurrency() -> usize {
+    32
+}
+
+pub fn default_node_check_deadline() -> u32 {
+    10
+}
+
 pub fn default_cache_subscription() -> u32 {
     60
 }
@@ -263,6 +331,22 @@ pub fn default_cache_ruleset() -> u32 {
     21600
 }
 
+pub fn default_health_check_url() -> String {
+    "http://www.gstatic.com/generate_204".to_string()
+}
+
+pub fn default_health_check_timeout() -> u32 {
+    5
+}
+
+pub fn default_info_traffic_remark() -> String {
+    "剩余流量: {traffic}".to_string()
+}
+
+pub fn default_info_expire_remark() -> String {
+    "到期时间: {expire}".to_string()
+}
+
 pub fn default_max_rulesets() -> usize {
     64
 }
@@ -271,6 +355,10 @@ pub fn default_max_rules() -> usize {
     32768
 }
 
+pub fn default_max_proxies() -> usize {
+    8192
+}
+
 impl Default for Settings {
     fn default() -> Self {
         Settings {
@@ -293,12 +381,21 @@ impl Default for Settings {
             max_concur_threads: default_max_concur_threads(),
             prepend_insert: false,
             skip_failed_links: false,
+            node_check_marker: default_node_check_marker(),
+            node_check_timeout: default_node_check_timeout(),
+            node_check_concurrency: default_node_check_concurrency(),
+            node_check_deadline: default_node_check_deadline(),
+            geoip_database: String::new(),
             api_mode: false,
             write_managed_config: false,
             enable_rule_gen: default_true(),
             update_ruleset_on_request: false,
             overwrite_original_rules: false,
+            ruleset_group_map: Vec::new(),
             append_userinfo: default_true(),
+            append_info: false,
+            info_traffic_remark: default_info_traffic_remark(),
+            info_expire_remark: default_info_expire_remark(),
             async_fetch_ruleset: false,
             surge_resolve_hostname: false,
             api_access_token: String::new(),
@@ -306,6 +403,10 @@ impl Default for Settings {
             custom_group: String::new(),
             log_level: default_log_level(),
             max_allowed_download_size: default_max_download_size(),
+            max_allowed_data_uri_size: default_max_data_uri_size(),
+            max_decoded_size_ratio: default_max_decoded_size_ratio(),
+            gzip_response: false,
+            compression_level: 0,
             template_path: String::new(),
             template_vars: HashMap::new(),
 
@@ -329,6 +430,7 @@ impl Default for Settings {
             update_strict: false,
             clash_use_new_field: default_true(),
             singbox_add_clash_modes: false,
+            vmess_aead_force: false,
             clash_proxies_style: String::new(),
             clash_proxy_groups_style: String::new(),
             proxy_config: String::new(),
@@ -350,6 +452,7 @@ impl Default for Settings {
             singbox_base: String::new(),
             surge_ssr_path: String::new(),
             quanx_dev_id: String::new(),
+            profile_web_page_url: String::new(),
 
             // Cache system
             serve_cache_on_fetch_fail: false,
@@ -362,10 +465,13 @@ impl Default for Settings {
             listen_port: default_listen_port(),
             serve_file: false,
             serve_file_root: String::new(),
+            health_check_url: default_health_check_url(),
+            health_check_timeout: default_health_check_timeout(),
 
             // Limits
             max_allowed_rulesets: default_max_rulesets(),
             max_allowed_rules: default_max_rules(),
+            max_allowed_proxies: default_max_proxies(),
             script_clean_context: false,
 
             // Cron system
@@ -381,27 +487,32 @@ impl Settings {
         Self::default()
     }
 
-    /// Get a mutable reference to the current settings
-    pub fn current_mut() -> GlobalLockWriteGuard<'static, Arc<Settings>> {
+    /// Snapshot of the current settings
+    ///
+    /// The settings are never modified in place, a reload swaps in a new
+    /// `Arc` with [`Settings::replace`]. A request should take one snapshot
+    /// when it starts and pass it down, so it sees the same settings until it
+    /// is done even when they are reloaded meanwhile.
+    pub fn current() -> Arc<Settings> {
         #[cfg(not(target_arch = "wasm32"))]
         {
-            GLOBAL.write().unwrap()
+            GLOBAL.read().unwrap().clone()
         }
         #[cfg(target_arch = "wasm32")]
         {
-            get_global().write().unwrap()
+            get_global().read().unwrap().clone()
         }
     }
 
-    /// Get a read-only reference to the current settings
-    pub fn current() -> GlobalLockReadGuard<'static, Arc<Settings>> {
+    /// Swap in new settings, snapshots taken before keep the old ones
+    pub fn replace(settings: Settings) {
         #[cfg(not(target_arch = "wasm32"))]
         {
-            GLOBAL.read().unwrap()
+            *GLOBAL.write().unwrap() = Arc::new(settings);
         }
         #[cfg(target_arch = "wasm32")]
         {
-            get_global().read().unwrap()
+            *get_global().write().unwrap() = Arc::new(settings);
         }
     }
 
@@ -416,6 +527,7 @@ impl Settings {
             yaml_settings.process_imports_and_inis().await?;
 
             let mut _settings = Settings::from(yaml_settings);
+            _settings.interpolate_env();
 
             return Ok(_settings);
         }
@@ -435,6 +547,7 @@ impl Settings {
             toml_settings.process_imports().await?;
 
             let mut settings = Settings::from(toml_settings);
+            settings.interpolate_env();
 
             // Ensure listen_address is not empty
             if settings.listen_address.trim().is_empty() {
@@ -452,12 +565,22 @@ impl Settings {
             .into());
         }
 
-        // Default to INI
+        // Default to INI, `import=` lines are resolved relative to the file
+        let content = crate::settings::resolve_ini_imports(
+            content,
+            file_path,
+            false,
+            &ProxyConfig::default(),
+            "",
+            0,
+        )
+        .await?;
         let mut ini_settings = crate::settings::settings::ini_settings::IniSettings::new();
         ini_settings.load_from_ini(&content)?;
         ini_settings.process_imports().await?;
 
         let mut settings = Settings::from(ini_settings);
+        settings.interpolate_env();
 
         // Ensure listen_address is not empty
         if settings.listen_address.trim().is_empty() {
@@ -467,6 +590,76 @@ impl Settings {
         Ok(settings)
     }
 
+    /// Resolve `${NAME}` and `${NAME:-default}` references in the string settings
+    ///
+    /// Secrets and deployment specific values can then live in environment
+    /// variables instead of the pref file. Inline rules are left untouched.
+    pub fn interpolate_env(&mut self) {
+        interpolate_env_in(
+            [
+                &mut self.default_ext_config,
+                &mut self.managed_config_prefix,
+                &mut self.api_access_token,
+                &mut self.base_path,
+                &mut self.custom_group,
+                &mut self.template_path,
+                &mut self.proxy_config,
+                &mut self.proxy_ruleset,
+                &mut self.proxy_subscription,
+                &mut self.clash_base,
+                &mut self.surge_base,
+                &mut self.surfboard_base,
+                &mut self.mellow_base,
+                &mut self.quan_base,
+                &mut self.quanx_base,
+                &mut self.loon_base,
+                &mut self.ssub_base,
+                &mut self.singbox_base,
+                &mut self.surge_ssr_path,
+                &mut self.quanx_dev_id,
+                &mut self.profile_web_page_url,
+                &mut self.listen_address,
+                &mut self.serve_file_root,
+                &mut self.health_check_url,
+            ]
+            .into_iter()
+            .chain(&mut self.default_urls)
+            .chain(&mut self.insert_urls)
+            .chain(&mut self.exclude_remarks)
+            .chain(&mut self.include_remarks)
+            .chain(self.template_vars.values_mut())
+            .chain(self.aliases.values_mut())
+            .chain(
+                self.custom_rulesets
+                    .iter_mut()
+                    .map(|ruleset| &mut ruleset.url)
+                    .filter(|url| !url.starts_with("[]")),
+            )
+            .chain(self.cron_tasks.iter_mut().map(|task| &mut task.path)),
+        );
+    }
+
+    /// Serialize the effective settings in the given format
+    pub fn to_content(&self, format: SettingsFormat) -> Result<String, Box<dyn std::error::Error>> {
+        let content = match format {
+            SettingsFormat::Ini => IniSettings::from(self).to_ini(),
+            SettingsFormat::Toml => toml::to_string(&TomlSettings::from(self))?,
+            SettingsFormat::Yaml => serde_yaml::to_string(&YamlSettings::from(self))?,
+        };
+        Ok(content)
+    }
+
+    /// Write the effective settings to a file, e.g. to migrate an INI pref to TOML
+    pub async fn save_to_file(
+        &self,
+        path: &str,
+        format: SettingsFormat,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let content = self.to_content(format)?;
+        write_file_async(path, &content).await?;
+        Ok(())
+    }
+
     /// Load settings from file or URL asynchronously
     pub async fn load_from_file(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
         let mut _content = String::new();
@@ -501,46 +694,24 @@ fn get_global() -> &'static GlobalLock<Arc<Settings>> {
 
 /// Refresh the configuration asynchronously
 pub async fn refresh_configuration() {
-    #[cfg(not(target_arch = "wasm32"))]
-    let settings = GLOBAL.read().unwrap();
-    #[cfg(target_arch = "wasm32")]
-    let settings = get_global().read().unwrap();
-
-    let path = settings.pref_path.clone();
-    drop(settings); // Release the lock before potential long operation
+    let path = Settings::current().pref_path.clone();
 
     match Settings::load_from_file(&path).await {
-        Ok(new_settings) => {
-            #[cfg(not(target_arch = "wasm32"))]
-            {
-                *GLOBAL.write().unwrap() = Arc::new(new_settings);
-            }
-            #[cfg(target_arch = "wasm32")]
-            {
-                *get_global().write().unwrap() = Arc::new(new_settings);
-            }
-        }
+        Ok(new_settings) => Settings::replace(new_settings),
         Err(err) => {
             eprintln!("Failed to refresh configuration from '{}': {}", path, err);
         }
     }
 }
 
-/// Update settings directly from file path with proper locking
+/// Update settings directly from file path
 pub async fn update_settings_from_file(path: &str) -> Result<(), Box<dyn std::error::Error>> {
     let path = path.to_owned();
 
     match Settings::load_from_file(&path).await {
         Ok(new_settings) => {
             debug!("Updating settings from file: {}", path);
-            #[cfg(not(target_arch = "wasm32"))]
-            {
-                *GLOBAL.write().unwrap() = Arc::new(new_settings);
-            }
-            #[cfg(target_arch = "wasm32")]
-            {
-                *get_global().write().unwrap() = Arc::new(new_settings);
-            }
+            Settings::replace(new_settings);
             Ok(())
         }
         Err(err) => {
@@ -597,14 +768,7 @@ pub async fn update_settings_from_content(content: &str) -> Result<(), Box<dyn s
 
     match Settings::load_from_content(&content, "").await {
         Ok(settings) => {
-            #[cfg(not(target_arch = "wasm32"))]
-            {
-                *GLOBAL.write().unwrap() = Arc::new(settings);
-            }
-            #[cfg(target_arch = "wasm32")]
-            {
-                *get_global().write().unwrap() = Arc::new(settings);
-            }
+            Settings::replace(settings);
             Ok(())
         }
         Err(err) => {
@@ -613,3 +777,191 @@ pub async fn update_settings_from_content(content: &str) -> Result<(), Box<dyn s
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INI_FIXTURE: &str = r#"[common]
+api_mode=false
+api_access_token=secret
+default_url=https://a.example/sub|https://b.example/sub
+exclude_remarks=(到期|剩余流量)
+clash_rule_base=base/all_base.tpl
+
+[node_pref]
+udp_flag=true
+sort_flag=true
+rename_node=\(?((x|X)?(\d+)(\.?\d+)?)((\s?倍率?)|(x|X))\)?@$1x
+
+[emojis]
+add_emoji=true
+rule=(流量|时间|应急),🏳️‍🌈
+
+[rulesets]
+enabled=true
+ruleset=🎯 全球直连,rules/LocalAreaNetwork.list
+ruleset=🐟 漏网之鱼,[]FINAL
+
+[proxy_groups]
+custom_proxy_group=🚀 节点选择`select`[]♻️ 自动选择`[]DIRECT
+custom_proxy_group=♻️ 自动选择`url-test`.*`http://www.gstatic.com/generate_204`300,5,50
+
+[template]
+template_path=base
+clash.http_port=7890
+
+[aliases]
+/clash=/sub?target=clash
+
+[server]
+listen=0.0.0.0
+port=25501
+
+[advanced]
+log_level=debug
+max_allowed_rules=1000
+"#;
+
+    fn assert_same_settings(expected: &Settings, actual: &Settings) {
+        assert_eq!(actual.api_access_token, expected.api_access_token);
+        assert_eq!(actual.default_urls, expected.default_urls);
+        assert_eq!(actual.exclude_remarks, expected.exclude_remarks);
+        assert_eq!(actual.clash_base, expected.clash_base);
+        assert_eq!(actual.udp_flag, Some(true));
+        assert_eq!(actual.enable_sort, expected.enable_sort);
+        assert_eq!(actual.add_emoji, expected.add_emoji);
+        assert_eq!(actual.renames[0]._match, expected.renames[0]._match);
+        assert_eq!(actual.renames[0].replace, expected.renames[0].replace);
+        assert_eq!(actual.emojis[0].replace, expected.emojis[0].replace);
+        assert_eq!(actual.custom_rulesets, expected.custom_rulesets);
+
+        assert_eq!(actual.custom_proxy_groups.len(), 2);
+        for (actual, expected) in actual
+            .custom_proxy_groups
+            .iter()
+            .zip(&expected.custom_proxy_groups)
+        {
+            assert_eq!(actual.name, expected.name);
+            assert_eq!(actual.group_type, expected.group_type);
+            assert_eq!(actual.proxies, expected.proxies);
+        }
+        let url_test = &actual.custom_proxy_groups[1];
+        assert_eq!(
+            (url_test.interval, url_test.timeout, url_test.tolerance),
+            (300, 5, 50)
+        );
+
+        assert_eq!(actual.template_path, expected.template_path);
+        assert_eq!(actual.template_vars, expected.template_vars);
+        assert_eq!(actual.aliases, expected.aliases);
+        assert_eq!(actual.listen_address, "0.0.0.0");
+        assert_eq!(actual.listen_port, 25501);
+        assert_eq!(actual.log_level, expected.log_level);
+        assert_eq!(actual.max_allowed_rules, 1000);
+    }
+
+    #[test]
+    fn test_save_to_file_round_trip() {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let original = Settings::load_from_content(INI_FIXTURE, "pref.ini")
+                    .await
+                    .unwrap();
+                assert_eq!(original.custom_rulesets.len(), 2);
+                assert_eq!(
+                    original.log_level,
+                    crate::constants::log_level::LOG_LEVEL_DEBUG
+                );
+
+                let dir = tempfile::tempdir().unwrap();
+                for file_name in ["pref.toml", "pref.yml", "pref.ini"] {
+                    let path = dir.path().join(file_name);
+                    let path = path.to_str().unwrap();
+                    original
+                        .save_to_file(path, SettingsFormat::from_path(path))
+                        .await
+                        .unwrap();
+
+                    let reloaded = Settings::load_from_file(path).await.unwrap();
+                    assert_same_settings(&original, &reloaded);
+                }
+            });
+    }
+
+    #[test]
+    fn test_load_interpolates_env() {
+        std::env::set_var("SUBCONVERTER_TEST_API_TOKEN", "from-env");
+        std::env::remove_var("SUBCONVERTER_TEST_MISSING");
+        let content = r#"[common]
+api_access_token=${SUBCONVERTER_TEST_API_TOKEN}
+default_url=https://a.example/sub?token=${SUBCONVERTER_TEST_MISSING:-fallback}
+
+[managed_config]
+managed_config_prefix=${SUBCONVERTER_TEST_MISSING}
+
+[rulesets]
+enabled=true
+ruleset=Proxy,[]DOMAIN,${SUBCONVERTER_TEST_API_TOKEN}.example
+"#;
+
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let dir = tempfile::tempdir().unwrap();
+                let path = dir.path().join("pref.ini");
+                std::fs::write(&path, content).unwrap();
+
+                let settings = Settings::load_from_file(path.to_str().unwrap())
+                    .await
+                    .unwrap();
+                assert_eq!(settings.api_access_token, "from-env");
+                assert_eq!(
+                    settings.default_urls,
+                    vec!["https://a.example/sub?token=fallback"]
+                );
+                assert_eq!(
+                    settings.managed_config_prefix,
+                    "${SUBCONVERTER_TEST_MISSING}"
+                );
+                assert_eq!(
+                    settings.custom_rulesets[0].url,
+                    "[]DOMAIN,${SUBCONVERTER_TEST_API_TOKEN}.example"
+                );
+            });
+    }
+
+    #[test]
+    fn test_load_resolves_ini_imports() {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let dir = tempfile::tempdir().unwrap();
+                std::fs::create_dir(dir.path().join("snippets")).unwrap();
+                std::fs::write(
+                    dir.path().join("pref.ini"),
+                    "[advanced]\nimport=snippets/advanced.ini\nmax_pending_connections=20\n",
+                )
+                .unwrap();
+                std::fs::write(
+                    dir.path().join("snippets/advanced.ini"),
+                    "[advanced]\nmax_pending_connections=10\nmax_concurrent_threads=4\n",
+                )
+                .unwrap();
+
+                let path = dir.path().join("pref.ini");
+                let settings = Settings::load_from_file(path.to_str().unwrap())
+                    .await
+                    .unwrap();
+                assert_eq!(settings.max_pending_conns, 20);
+                assert_eq!(settings.max_concur_threads, 4);
+            });
+    }
+}
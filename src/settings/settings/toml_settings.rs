@@ -14,6 +14,7 @@ use crate::{
             deserialize_template_as_template_settings, ProxyGroupConfigInToml,
             RegexMatchRuleInToml, RulesetConfigInToml, TaskConfigInToml,
         },
+        utils::serialize_template_globals,
     },
     utils::http::parse_proxy,
 };
@@ -72,6 +73,22 @@ fn default_cache_ruleset() -> u32 {
     21600
 }
 
+fn default_health_check_url() -> String {
+    "http://www.gstatic.com/generate_204".to_string()
+}
+
+fn default_health_check_timeout() -> u32 {
+    5
+}
+
+fn default_info_traffic_remark() -> String {
+    "剩余流量: {traffic}".to_string()
+}
+
+fn default_info_expire_remark() -> String {
+    "到期时间: {expire}".to_string()
+}
+
 fn default_max_rulesets() -> usize {
     64
 }
@@ -80,9 +97,37 @@ fn default_max_rules() -> usize {
     32768
 }
 
+fn default_max_proxies() -> usize {
+    8192
+}
+
 fn default_max_download_size() -> i64 {
     32 * 1024 * 1024 // 32MB
 }
+
+fn default_max_data_uri_size() -> i64 {
+    1024 * 1024 // 1MB
+}
+
+fn default_max_decoded_size_ratio() -> usize {
+    2
+}
+
+fn default_node_check_marker() -> String {
+    "[DEAD]".to_string()
+}
+
+fn default_node_check_timeout() -> u32 {
+    1500
+}
+
+fn default_node_check_concurrency() -> usize {
+    32
+}
+
+fn default_node_check_deadline() -> u32 {
+    10
+}
 /// User info settings
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(default)]
@@ -144,6 +189,11 @@ pub struct NodePreferences {
     pub filter_deprecated_nodes: bool,
     #[serde(default = "default_true")]
     pub append_sub_userinfo: bool,
+    pub append_info: bool,
+    #[serde(default = "default_info_traffic_remark")]
+    pub info_traffic_remark: String,
+    #[serde(default = "default_info_expire_remark")]
+    pub info_expire_remark: String,
     #[serde(default = "default_true")]
     pub clash_use_new_field_name: bool,
     #[serde(default = "default_empty_string")]
@@ -151,6 +201,7 @@ pub struct NodePreferences {
     #[serde(default = "default_empty_string")]
     pub clash_proxy_groups_style: String,
     pub singbox_add_clash_modes: bool,
+    pub vmess_aead_force: bool,
     pub rename_node: Vec<RegexMatchRuleInToml>,
 }
 
@@ -166,6 +217,7 @@ pub struct ManagedConfigSettings {
     pub config_update_interval: u32,
     pub config_update_strict: bool,
     pub quanx_device_id: String,
+    pub profile_web_page_url: String,
 }
 
 fn default_update_interval() -> u32 {
@@ -198,6 +250,7 @@ pub struct RulesetSettings {
     pub enabled: bool,
     pub overwrite_original_rules: bool,
     pub update_ruleset_on_request: bool,
+    pub rename_rule_group: Vec<String>,
 }
 
 /// Template variable
@@ -222,6 +275,7 @@ impl Default for TemplateVariable {
 #[serde(default)]
 pub struct TemplateSettings {
     pub template_path: String,
+    #[serde(serialize_with = "serialize_template_globals")]
     pub globals: HashMap<String, String>,
 }
 
@@ -242,6 +296,10 @@ pub struct ServerSettings {
     #[serde(default = "default_listen_port")]
     pub port: u32,
     pub serve_file_root: String,
+    #[serde(default = "default_health_check_url")]
+    pub health_check_url: String,
+    #[serde(default = "default_health_check_timeout")]
+    pub health_check_timeout: u32,
 }
 
 /// Advanced settings
@@ -259,8 +317,18 @@ pub struct AdvancedSettings {
     pub max_allowed_rulesets: usize,
     #[serde(default = "default_max_rules")]
     pub max_allowed_rules: usize,
+    #[serde(default = "default_max_proxies")]
+    pub max_allowed_proxies: usize,
     #[serde(default = "default_max_download_size")]
     pub max_allowed_download_size: i64,
+    #[serde(default = "default_max_data_uri_size")]
+    pub max_allowed_data_uri_size: i64,
+    #[serde(default = "default_max_decoded_size_ratio")]
+    pub max_decoded_size_ratio: usize,
+    #[serde(default)]
+    pub gzip_response: bool,
+    #[serde(default)]
+    pub compression_level: u32,
     pub enable_cache: bool,
     #[serde(default = "default_cache_subscription")]
     pub cache_subscription: u32,
@@ -271,6 +339,15 @@ pub struct AdvancedSettings {
     pub script_clean_context: bool,
     pub async_fetch_ruleset: bool,
     pub skip_failed_links: bool,
+    #[serde(default = "default_node_check_marker")]
+    pub node_check_marker: String,
+    #[serde(default = "default_node_check_timeout")]
+    pub node_check_timeout: u32,
+    #[serde(default = "default_node_check_concurrency")]
+    pub node_check_concurrency: usize,
+    #[serde(default = "default_node_check_deadline")]
+    pub node_check_deadline: u32,
+    pub geoip_database: String,
 }
 
 /// Main TOML settings structure
@@ -291,10 +368,7 @@ pub struct TomlSettings {
     pub rulesets: Vec<RulesetConfigInToml>,
     #[serde(rename = "custom_groups")]
     pub custom_proxy_groups: Vec<ProxyGroupConfigInToml>,
-    #[serde(
-        rename = "template.globals",
-        deserialize_with = "deserialize_template_as_template_settings"
-    )]
+    #[serde(deserialize_with = "deserialize_template_as_template_settings")]
     pub template: TemplateSettings,
     pub aliases: Vec<AliasConfig>,
     pub tasks: Vec<TaskConfigInToml>,
@@ -329,6 +403,7 @@ impl TomlSettings {
             "rename_node",
             &proxy_config,
             &self.common.base_path,
+            self.advanced.cache_config,
         )
         .await?;
         self.parsed_rename = self
@@ -345,6 +420,7 @@ impl TomlSettings {
             "stream_rule",
             &proxy_config,
             &self.common.base_path,
+            self.advanced.cache_config,
         )
         .await?;
         self.parsed_stream_rule = self
@@ -361,6 +437,7 @@ impl TomlSettings {
             "time_rule",
             &proxy_config,
             &self.common.base_path,
+            self.advanced.cache_config,
         )
         .await?;
         self.parsed_time_rule = self
@@ -377,6 +454,7 @@ impl TomlSettings {
             "emoji",
             &proxy_config,
             &self.common.base_path,
+            self.advanced.cache_config,
         )
         .await?;
         self.parsed_emoji_rules = self.emojis.emoji.iter().map(|r| r.clone().into()).collect();
@@ -388,6 +466,7 @@ impl TomlSettings {
             "rulesets",
             &proxy_config,
             &self.common.base_path,
+            self.advanced.cache_config,
         )
         .await?;
 
@@ -409,6 +488,7 @@ impl TomlSettings {
             "custom_groups",
             &proxy_config,
             &self.common.base_path,
+            self.advanced.cache_config,
         )
         .await?;
         self.parsed_proxy_group = self
@@ -424,6 +504,7 @@ impl TomlSettings {
             "tasks",
             &proxy_config,
             &self.common.base_path,
+            self.advanced.cache_config,
         )
         .await?;
         self.parsed_tasks = self.tasks.iter().map(|r| r.clone().into()).collect();
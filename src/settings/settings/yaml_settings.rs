@@ -1,13 +1,13 @@
 use std::collections::HashMap;
 
 use super::super::ini_bindings::{FromIni, FromIniWithDelimiter};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     models::{
         cron::CronTaskConfigs, ruleset::RulesetConfigs, ProxyGroupConfigs, RegexMatchConfigs,
     },
-    settings::{import_items, yaml_deserializer::*},
+    settings::{import_items, utils::serialize_template_globals, yaml_deserializer::*},
     utils::http::parse_proxy,
 };
 
@@ -65,6 +65,22 @@ fn default_cache_ruleset() -> u32 {
     21600
 }
 
+fn default_health_check_url() -> String {
+    "http://www.gstatic.com/generate_204".to_string()
+}
+
+fn default_health_check_timeout() -> u32 {
+    5
+}
+
+fn default_info_traffic_remark() -> String {
+    "剩余流量: {traffic}".to_string()
+}
+
+fn default_info_expire_remark() -> String {
+    "到期时间: {expire}".to_string()
+}
+
 fn default_max_rulesets() -> usize {
     64
 }
@@ -73,12 +89,40 @@ fn default_max_rules() -> usize {
     32768
 }
 
+fn default_max_proxies() -> usize {
+    8192
+}
+
 fn default_max_download_size() -> i64 {
     32 * 1024 * 1024 // 32MB
 }
 
+fn default_max_data_uri_size() -> i64 {
+    1024 * 1024 // 1MB
+}
+
+fn default_max_decoded_size_ratio() -> usize {
+    2
+}
+
+fn default_node_check_marker() -> String {
+    "[DEAD]".to_string()
+}
+
+fn default_node_check_timeout() -> u32 {
+    1500
+}
+
+fn default_node_check_concurrency() -> usize {
+    32
+}
+
+fn default_node_check_deadline() -> u32 {
+    10
+}
+
 /// User info settings
-#[derive(Debug, Clone, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(default)]
 pub struct UserInfoSettings {
     pub stream_rule: Vec<RegexMatchRuleInYaml>,
@@ -86,7 +130,7 @@ pub struct UserInfoSettings {
 }
 
 /// Common settings section
-#[derive(Debug, Clone, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(default)]
 pub struct CommonSettings {
     pub api_mode: bool,
@@ -124,28 +168,38 @@ pub struct CommonSettings {
 }
 
 /// Node preferences
-#[derive(Debug, Clone, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(default)]
 pub struct NodePreferences {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub udp_flag: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub tcp_fast_open_flag: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub skip_cert_verify_flag: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub tls13_flag: Option<bool>,
     pub sort_flag: bool,
     pub sort_script: String,
     pub filter_deprecated_nodes: bool,
     #[serde(default = "default_true")]
     pub append_sub_userinfo: bool,
+    pub append_info: bool,
+    #[serde(default = "default_info_traffic_remark")]
+    pub info_traffic_remark: String,
+    #[serde(default = "default_info_expire_remark")]
+    pub info_expire_remark: String,
     #[serde(default = "default_true")]
     pub clash_use_new_field_name: bool,
     pub clash_proxies_style: String,
     pub clash_proxy_groups_style: String,
     pub singbox_add_clash_modes: bool,
+    pub vmess_aead_force: bool,
     pub rename_node: Vec<RegexMatchRuleInYaml>,
 }
 
 /// Managed config settings
-#[derive(Debug, Clone, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(default)]
 pub struct ManagedConfigSettings {
     #[serde(default = "default_true")]
@@ -156,6 +210,7 @@ pub struct ManagedConfigSettings {
     pub config_update_interval: u32,
     pub config_update_strict: bool,
     pub quanx_device_id: String,
+    pub profile_web_page_url: String,
 }
 
 fn default_update_interval() -> u32 {
@@ -163,7 +218,7 @@ fn default_update_interval() -> u32 {
 }
 
 /// Surge external proxy settings
-#[derive(Debug, Clone, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(default)]
 pub struct SurgeExternalProxySettings {
     pub surge_ssr_path: String,
@@ -172,7 +227,7 @@ pub struct SurgeExternalProxySettings {
 }
 
 /// Emoji settings
-#[derive(Debug, Clone, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(default)]
 pub struct EmojiSettings {
     pub add_emoji: bool,
@@ -182,26 +237,27 @@ pub struct EmojiSettings {
 }
 
 /// Ruleset settings
-#[derive(Debug, Clone, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(default)]
 pub struct RulesetSettings {
     #[serde(default = "default_true")]
     pub enabled: bool,
     pub overwrite_original_rules: bool,
     pub update_ruleset_on_request: bool,
+    pub rename_rule_group: Vec<String>,
     #[serde(alias = "surge_ruleset")]
     pub rulesets: Vec<RulesetConfigInYaml>,
 }
 
 /// Proxy groups settings
-#[derive(Debug, Clone, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(default)]
 pub struct ProxyGroupsSettings {
     pub custom_proxy_group: Vec<ProxyGroupConfigInYaml>,
 }
 
 /// Template variable
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TemplateVariable {
     pub key: String,
     #[serde(default = "default_yaml_value")]
@@ -218,15 +274,16 @@ impl Default for TemplateVariable {
 }
 
 /// Template settings
-#[derive(Debug, Clone, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(default)]
 pub struct TemplateSettings {
     pub template_path: String,
+    #[serde(serialize_with = "serialize_template_globals")]
     pub globals: HashMap<String, String>,
 }
 
 /// Alias configuration
-#[derive(Debug, Clone, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(default)]
 pub struct AliasConfig {
     pub uri: String,
@@ -234,7 +291,7 @@ pub struct AliasConfig {
 }
 
 /// Server settings
-#[derive(Debug, Clone, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(default)]
 pub struct ServerSettings {
     #[serde(default = "default_listen_address")]
@@ -242,10 +299,14 @@ pub struct ServerSettings {
     #[serde(default = "default_listen_port")]
     pub port: u32,
     pub serve_file_root: String,
+    #[serde(default = "default_health_check_url")]
+    pub health_check_url: String,
+    #[serde(default = "default_health_check_timeout")]
+    pub health_check_timeout: u32,
 }
 
 /// Advanced settings
-#[derive(Debug, Clone, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(default)]
 pub struct AdvancedSettings {
     #[serde(default = "default_info_log_level")]
@@ -259,8 +320,18 @@ pub struct AdvancedSettings {
     pub max_allowed_rulesets: usize,
     #[serde(default = "default_max_rules")]
     pub max_allowed_rules: usize,
+    #[serde(default = "default_max_proxies")]
+    pub max_allowed_proxies: usize,
     #[serde(default = "default_max_download_size")]
     pub max_allowed_download_size: i64,
+    #[serde(default = "default_max_data_uri_size")]
+    pub max_allowed_data_uri_size: i64,
+    #[serde(default = "default_max_decoded_size_ratio")]
+    pub max_decoded_size_ratio: usize,
+    #[serde(default)]
+    pub gzip_response: bool,
+    #[serde(default)]
+    pub compression_level: u32,
     pub enable_cache: bool,
     #[serde(default = "default_cache_subscription")]
     pub cache_subscription: u32,
@@ -272,10 +343,19 @@ pub struct AdvancedSettings {
     pub script_clean_context: bool,
     pub async_fetch_ruleset: bool,
     pub skip_failed_links: bool,
+    #[serde(default = "default_node_check_marker")]
+    pub node_check_marker: String,
+    #[serde(default = "default_node_check_timeout")]
+    pub node_check_timeout: u32,
+    #[serde(default = "default_node_check_concurrency")]
+    pub node_check_concurrency: usize,
+    #[serde(default = "default_node_check_deadline")]
+    pub node_check_deadline: u32,
+    pub geoip_database: String,
 }
 
 /// Main YAML settings structure
-#[derive(Debug, Clone, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(default)]
 pub struct YamlSettings {
     pub common: CommonSettings,
@@ -333,6 +413,7 @@ impl YamlSettings {
             false,
             proxy_config,
             &self.common.base_path,
+            self.advanced.cache_config,
         )
         .await?;
         self.parsed_rename = RegexMatchConfigs::from_ini_with_delimiter(&rename_nodes, "@");
@@ -350,6 +431,7 @@ impl YamlSettings {
             false,
             proxy_config,
             &self.common.base_path,
+            self.advanced.cache_config,
         )
         .await?;
         self.parsed_stream_rule = RegexMatchConfigs::from_ini_with_delimiter(&stream_rules, "|");
@@ -361,7 +443,14 @@ impl YamlSettings {
             .iter()
             .map(|rule| rule.to_ini_with_delimiter("|"))
             .collect::<Vec<String>>();
-        import_items(&mut time_rules, false, proxy_config, &self.common.base_path).await?;
+        import_items(
+            &mut time_rules,
+            false,
+            proxy_config,
+            &self.common.base_path,
+            self.advanced.cache_config,
+        )
+        .await?;
         self.parsed_time_rule = RegexMatchConfigs::from_ini_with_delimiter(&time_rules, "|");
 
         // read emojis
@@ -376,6 +465,7 @@ impl YamlSettings {
             false,
             proxy_config,
             &self.common.base_path,
+            self.advanced.cache_config,
         )
         .await?;
         self.parsed_emoji_rules = RegexMatchConfigs::from_ini_with_delimiter(&emoji_rules, ",");
@@ -387,7 +477,14 @@ impl YamlSettings {
             .iter()
             .map(|rule| rule.to_ini())
             .collect::<Vec<String>>();
-        import_items(&mut rulesets, false, proxy_config, &self.common.base_path).await?;
+        import_items(
+            &mut rulesets,
+            false,
+            proxy_config,
+            &self.common.base_path,
+            self.advanced.cache_config,
+        )
+        .await?;
         self.parsed_ruleset = RulesetConfigs::from_ini(&rulesets);
 
         // read proxy groups
@@ -403,6 +500,7 @@ impl YamlSettings {
             false,
             proxy_config,
             &self.common.base_path,
+            self.advanced.cache_config,
         )
         .await?;
 
@@ -413,7 +511,14 @@ impl YamlSettings {
             .iter()
             .map(|task| task.to_ini())
             .collect::<Vec<String>>();
-        import_items(&mut tasks, false, proxy_config, &self.common.base_path).await?;
+        import_items(
+            &mut tasks,
+            false,
+            proxy_config,
+            &self.common.base_path,
+            self.advanced.cache_config,
+        )
+        .await?;
         self.parsed_tasks = CronTaskConfigs::from_ini(&tasks);
         Ok(())
     }
@@ -106,6 +106,10 @@ pub struct ProxyGroupConfigInToml {
     pub tolerance: Option<u32>,
     pub timeout: Option<u32>,
     pub disable_udp: Option<bool>,
+    /// Regex a node remark must match to stay in the group
+    pub include: Option<String>,
+    /// Regex removing matching nodes from the group
+    pub exclude: Option<String>,
     pub import: Option<String>,
 }
 
@@ -155,6 +159,8 @@ impl Into<ProxyGroupConfig> for ProxyGroupConfigInToml {
             persistent: false,
             evaluate_before_use: false,
             using_provider: Vec::new(),
+            include_filter: self.include,
+            exclude_filter: self.exclude,
         };
 
         // 根据不同的代理组类型设置特定属性
@@ -237,6 +243,13 @@ where
         {
             let mut template_settings = TemplateSettings::default();
             while let Some(key) = map.next_key::<String>()? {
+                if key == "globals" {
+                    let value = map.next_value::<Vec<TemplateArgument>>()?;
+                    for item in value {
+                        template_settings.globals.insert(item.key, item.value);
+                    }
+                    continue;
+                }
                 let value = map.next_value::<String>()?;
                 if key == "template_path" {
                     template_settings.template_path = value.clone();
@@ -1,3 +1,9 @@
+use std::collections::{BTreeMap, HashMap};
+
+use log::warn;
+use serde::ser::SerializeSeq;
+use serde::{Serialize, Serializer};
+
 /// Trait to convert a string to a boolean
 pub trait IniToBool {
     fn to_bool(&self) -> bool;
@@ -21,3 +27,116 @@ impl IniToBoolOpt for Option<&str> {
         }
     }
 }
+
+/// Serialize template globals as a `[{key, value}]` list sorted by key,
+/// the layout both the YAML and TOML settings files use
+pub fn serialize_template_globals<S>(
+    globals: &HashMap<String, String>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    #[derive(Serialize)]
+    struct TemplateGlobal<'a> {
+        key: &'a str,
+        value: &'a str,
+    }
+
+    let sorted: BTreeMap<&String, &String> = globals.iter().collect();
+    let mut seq = serializer.serialize_seq(Some(sorted.len()))?;
+    for (key, value) in sorted {
+        seq.serialize_element(&TemplateGlobal { key, value })?;
+    }
+    seq.end()
+}
+
+fn is_env_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Replace `${NAME}` and `${NAME:-default}` references with environment variables
+///
+/// The default is used when the variable is unset or empty. A reference to a
+/// missing variable without a default is kept as written.
+pub fn interpolate_env(value: &str) -> String {
+    interpolate_env_with(value, |name| std::env::var(name).ok())
+}
+
+fn interpolate_env_with(value: &str, lookup: impl Fn(&str) -> Option<String>) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        result.push_str(&rest[..start]);
+        let Some(len) = rest[start..].find('}') else {
+            rest = &rest[start..];
+            break;
+        };
+        let reference = &rest[start..start + len + 1];
+        rest = &rest[start + len + 1..];
+
+        let expr = &reference[2..reference.len() - 1];
+        let (name, default) = match expr.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (expr, None),
+        };
+        if !is_env_name(name) {
+            result.push_str(reference);
+            continue;
+        }
+        match (lookup(name).filter(|value| !value.is_empty()), default) {
+            (Some(value), _) => result.push_str(&value),
+            (None, Some(default)) => result.push_str(default),
+            (None, None) => {
+                warn!(
+                    "Environment variable '{}' is not set, keeping '{}' as is",
+                    name, reference
+                );
+                result.push_str(reference);
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Interpolate environment variables into every string in place
+pub fn interpolate_env_in<'a>(values: impl IntoIterator<Item = &'a mut String>) {
+    for value in values {
+        if value.contains("${") {
+            *value = interpolate_env(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interpolate_env() {
+        let lookup = |name: &str| match name {
+            "TOKEN" => Some("secret".to_string()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        };
+        assert_eq!(interpolate_env_with("${TOKEN}", lookup), "secret");
+        assert_eq!(
+            interpolate_env_with("https://x/${TOKEN}/${PORT:-25500}", lookup),
+            "https://x/secret/25500"
+        );
+        assert_eq!(
+            interpolate_env_with("${EMPTY:-fallback}", lookup),
+            "fallback"
+        );
+        assert_eq!(interpolate_env_with("${MISSING}", lookup), "${MISSING}");
+        assert_eq!(interpolate_env_with("${TOKEN:-}", lookup), "secret");
+        assert_eq!(
+            interpolate_env_with("$1x ${not a name}", lookup),
+            "$1x ${not a name}"
+        );
+        assert_eq!(interpolate_env_with("${TOKEN", lookup), "${TOKEN");
+    }
+}
@@ -0,0 +1,212 @@
+//! Settings file watcher
+//!
+//! Watches the active settings file together with local external configs and
+//! rulesets, and reloads the settings once the files stop changing. A reload
+//! that fails keeps the previous settings in place.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::{error, info, warn};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::models::ruleset::strip_ruleset_prefix;
+use crate::settings::{update_settings_from_file, Settings};
+use crate::utils::memory_cache;
+use crate::utils::network::is_link;
+
+/// Quiet period after the last change before settings are reloaded
+pub const DEBOUNCE_DELAY: Duration = Duration::from_millis(500);
+
+/// Collapses bursts of file events into a single reload
+#[derive(Debug)]
+pub struct Debouncer {
+    delay: Duration,
+    last_event: Option<Instant>,
+}
+
+impl Debouncer {
+    pub fn new(delay: Duration) -> Self {
+        Debouncer {
+            delay,
+            last_event: None,
+        }
+    }
+
+    /// Record a change event
+    pub fn touch(&mut self, at: Instant) {
+        self.last_event = Some(at);
+    }
+
+    /// Whether a pending change has been quiet for the whole delay,
+    /// resets the pending state when it returns true
+    pub fn ready(&mut self, now: Instant) -> bool {
+        match self.last_event {
+            Some(at) if now.saturating_duration_since(at) >= self.delay => {
+                self.last_event = None;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Local files whose changes should trigger a reload
+pub fn watched_paths(settings: &Settings) -> Vec<PathBuf> {
+    let rulesets = settings
+        .custom_rulesets
+        .iter()
+        .filter(|ruleset| !ruleset.url.starts_with("[]"))
+        .map(|ruleset| strip_ruleset_prefix(&ruleset.url));
+
+    let mut seen = HashSet::new();
+    [
+        settings.pref_path.as_str(),
+        settings.default_ext_config.as_str(),
+    ]
+    .into_iter()
+    .chain(rulesets)
+    .filter(|path| !path.is_empty() && !is_link(path))
+    .filter_map(|path| Path::new(path).canonicalize().ok())
+    .filter(|path| seen.insert(path.clone()))
+    .collect()
+}
+
+/// Reload the settings from `path` and drop cached downloads
+///
+/// Returns false and keeps serving the current settings if the file can not
+/// be loaded.
+pub async fn reload_settings(path: &str) -> bool {
+    match update_settings_from_file(path).await {
+        Ok(()) => {
+            memory_cache::clear();
+            info!("Reloaded settings from {}", path);
+            true
+        }
+        Err(err) => {
+            error!(
+                "Failed to reload settings from {}, keeping previous settings: {}",
+                path, err
+            );
+            false
+        }
+    }
+}
+
+/// Bring the directory watches in line with the current settings
+///
+/// Parent directories are watched instead of the files themselves so that
+/// editors replacing a file on save are still noticed.
+fn update_watches(
+    watcher: &mut RecommendedWatcher,
+    files: &mut HashSet<PathBuf>,
+    dirs: &mut HashSet<PathBuf>,
+) {
+    *files = watched_paths(&Settings::current()).into_iter().collect();
+    let wanted: HashSet<PathBuf> = files
+        .iter()
+        .filter_map(|file| file.parent().map(Path::to_path_buf))
+        .collect();
+
+    for dir in dirs.difference(&wanted) {
+        let _ = watcher.unwatch(dir);
+    }
+    dirs.retain(|dir| wanted.contains(dir));
+    for dir in wanted {
+        if dirs.contains(&dir) {
+            continue;
+        }
+        match watcher.watch(&dir, RecursiveMode::NonRecursive) {
+            Ok(()) => {
+                dirs.insert(dir);
+            }
+            Err(err) => warn!("Failed to watch {}: {}", dir.display(), err),
+        }
+    }
+}
+
+/// Start watching the settings files on a background thread
+pub fn start_settings_watcher() -> notify::Result<()> {
+    let (tx, rx) = mpsc::channel::<Event>();
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<Event>| match res {
+        Ok(event) => {
+            let _ = tx.send(event);
+        }
+        Err(err) => warn!("Settings watcher error: {}", err),
+    })?;
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+
+    let mut files = HashSet::new();
+    let mut dirs = HashSet::new();
+    update_watches(&mut watcher, &mut files, &mut dirs);
+    info!("Watching {} settings file(s) for changes", files.len());
+
+    thread::spawn(move || {
+        let mut debouncer = Debouncer::new(DEBOUNCE_DELAY);
+        loop {
+            match rx.recv_timeout(DEBOUNCE_DELAY) {
+                Ok(event) => {
+                    let relevant = !matches!(event.kind, EventKind::Access(_))
+                        && event.paths.iter().any(|path| files.contains(path));
+                    if relevant {
+                        debouncer.touch(Instant::now());
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+
+            if debouncer.ready(Instant::now()) {
+                let pref_path = Settings::current().pref_path.clone();
+                if runtime.block_on(reload_settings(&pref_path)) {
+                    update_watches(&mut watcher, &mut files, &mut dirs);
+                }
+            }
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_debouncer_waits_for_quiet_period() {
+        let start = Instant::now();
+        let mut debouncer = Debouncer::new(DEBOUNCE_DELAY);
+        assert!(!debouncer.ready(start));
+
+        debouncer.touch(start);
+        debouncer.touch(start + Duration::from_millis(300));
+        assert!(!debouncer.ready(start + Duration::from_millis(600)));
+        assert!(debouncer.ready(start + Duration::from_millis(800)));
+        // Fires only once per burst
+        assert!(!debouncer.ready(start + Duration::from_millis(1500)));
+    }
+
+    #[test]
+    fn test_failed_reload_keeps_settings() {
+        let mut file = tempfile::Builder::new().suffix(".yml").tempfile().unwrap();
+        writeln!(file, "common:\n  api_mode: [unclosed").unwrap();
+        let path = file.path().to_str().unwrap().to_string();
+
+        let before = Settings::current().pref_path.clone();
+        let reloaded = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(reload_settings(&path));
+
+        assert!(!reloaded);
+        assert_eq!(Settings::current().pref_path, before);
+    }
+}
@@ -2,19 +2,22 @@ use std::{collections::HashMap, fmt};
 
 use serde::{
     de::{MapAccess, SeqAccess, Visitor},
-    Deserialize,
+    Deserialize, Serialize,
 };
 
 use super::settings::yaml_settings::TemplateSettings;
 
 /// Stream rule configuration
-#[derive(Debug, Clone, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(default)]
 pub struct RegexMatchRuleInYaml {
-    #[serde(rename = "match")]
+    #[serde(rename = "match", skip_serializing_if = "Option::is_none")]
     pub match_str: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub replace: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub script: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub import: Option<String>,
 }
 
@@ -106,19 +109,28 @@ impl ToIni for TaskConfigInYaml {
 }
 
 /// Proxy group configuration
-#[derive(Debug, Clone, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(default)]
 pub struct ProxyGroupConfigInYaml {
     pub name: String,
     #[serde(rename = "type")]
     pub group_type: String,
     pub rule: Vec<String>,
-    #[serde(default = "default_test_url")]
+    #[serde(default = "default_test_url", skip_serializing_if = "Option::is_none")]
     pub url: Option<String>,
-    #[serde(default = "default_interval")]
+    #[serde(default = "default_interval", skip_serializing_if = "Option::is_none")]
     pub interval: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub tolerance: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub timeout: Option<u32>,
+    /// Regex a node remark must match to stay in the group
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include: Option<String>,
+    /// Regex removing matching nodes from the group
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclude: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub import: Option<String>,
 }
 
@@ -138,6 +150,12 @@ impl ToIni for ProxyGroupConfigInYaml {
         for rule in &self.rule {
             temp_array.push(rule.clone());
         }
+        if let Some(include) = &self.include {
+            temp_array.push(format!("!!INCLUDE={}", include));
+        }
+        if let Some(exclude) = &self.exclude {
+            temp_array.push(format!("!!EXCLUDE={}", exclude));
+        }
 
         // Check if we have enough elements based on group type
         match self.group_type.as_str() {
@@ -192,24 +210,29 @@ fn default_interval() -> Option<u32> {
 }
 
 /// Task configuration
-#[derive(Debug, Clone, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(default)]
 pub struct TaskConfigInYaml {
     pub name: String,
     pub cronexp: String,
     pub path: String,
     pub timeout: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub import: Option<String>,
 }
 
 /// Ruleset configuration
-#[derive(Debug, Clone, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(default)]
 pub struct RulesetConfigInYaml {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub rule: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub ruleset: Option<String>,
     pub group: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub interval: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub import: Option<String>,
 }
 
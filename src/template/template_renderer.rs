@@ -1,6 +1,5 @@
 use crate::api::SubconverterQuery;
 use crate::utils::{file_exists, file_get_async};
-use crate::Settings;
 use log::{debug, error};
 use minijinja::{
     context, escape_formatter, Environment, Error as JinjaError, ErrorKind, UndefinedBehavior,
@@ -31,6 +30,7 @@ pub struct TemplateArgs {
 /// * `content` - The template content
 /// * `args` - Template arguments
 /// * `include_scope` - The directory scope for included templates
+/// * `managed_config_prefix` - The prefix `getLink` puts before its path
 ///
 /// # Returns
 /// * `Ok(String)` - The rendered template
@@ -39,6 +39,7 @@ pub fn render_template(
     content: &str,
     args: &TemplateArgs,
     _include_scope: &str,
+    managed_config_prefix: &str,
 ) -> Result<String, Box<dyn std::error::Error>> {
     // let env_lock = match TEMPLATE_ENV.lock() {
     //     Ok(env) => env,
@@ -62,7 +63,10 @@ pub fn render_template(
     env.add_filter("replace", filter_replace);
     env.add_filter("find", filter_find);
 
-    env.add_function("getLink", fn_get_link);
+    let managed_config_prefix = managed_config_prefix.to_string();
+    env.add_function("getLink", move |path: Value| {
+        fn_get_link(&managed_config_prefix, path)
+    });
     env.add_function("startsWith", fn_starts_with);
     env.add_function("endsWith", fn_ends_with);
     env.add_function("bool", fn_to_bool);
@@ -111,6 +115,7 @@ pub fn render_template(
 /// * `path` - Path to the template file
 /// * `args` - Template arguments
 /// * `include_scope` - The directory scope for included templates
+/// * `managed_config_prefix` - The prefix `getLink` puts before its path
 ///
 /// # Returns
 /// * `Ok(String)` - The rendered template
@@ -119,6 +124,7 @@ pub async fn render_template_file(
     path: &str,
     args: &TemplateArgs,
     include_scope: &str,
+    managed_config_prefix: &str,
 ) -> Result<String, Box<dyn std::error::Error>> {
     let content;
     if file_exists(path).await {
@@ -135,7 +141,7 @@ pub async fn render_template_file(
         return Err(format!("Template file not found: {}", path).into());
     }
 
-    render_template(&content, args, include_scope)
+    render_template(&content, args, include_scope, managed_config_prefix)
 }
 
 // Filter implementations
@@ -212,10 +218,9 @@ fn filter_find(value: Value, pattern: Value) -> Result<bool, JinjaError> {
 
 // Function implementations
 
-fn fn_get_link(path: Value) -> Result<String, JinjaError> {
+fn fn_get_link(managed_config_prefix: &str, path: Value) -> Result<String, JinjaError> {
     let path_str = path.to_string();
-    let settings = Settings::current();
-    Ok(format!("{}{}", settings.managed_config_prefix, path_str))
+    Ok(format!("{}{}", managed_config_prefix, path_str))
 }
 
 fn fn_starts_with(s: Value, prefix: Value) -> Result<bool, JinjaError> {
@@ -44,6 +44,47 @@ pub fn base64_decode(input: &str, accept_urlsafe: bool) -> String {
     String::from_utf8_lossy(&decoded).to_string()
 }
 
+/// Alphabet a [`decode_lenient`] call ended up decoding with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Base64Variant {
+    Standard,
+    UrlSafe,
+    /// Characters of both alphabets were mixed in the input
+    Mixed,
+}
+
+/// Decodes Base64 the way subscription providers actually produce it.
+///
+/// Whitespace anywhere in the input is ignored, padding may be missing and
+/// the standard alphabet is tried before the URL-safe one. Inputs mixing both
+/// alphabets are decoded as well. The result must be valid UTF-8.
+///
+/// # Returns
+/// The decoded string and the variant it was decoded with, or `None` if the
+/// input is not Base64 at all.
+pub fn decode_lenient(input: &str) -> Option<(String, Base64Variant)> {
+    let compact: String = input.chars().filter(|c| !c.is_whitespace()).collect();
+    let compact = compact.trim_end_matches('=');
+    if compact.is_empty() {
+        return None;
+    }
+
+    let (decoded, variant) = if let Ok(decoded) = STANDARD_NO_PAD.decode(compact) {
+        (decoded, Base64Variant::Standard)
+    } else if let Ok(decoded) = URL_SAFE_NO_PAD.decode(compact) {
+        (decoded, Base64Variant::UrlSafe)
+    } else {
+        let normalized = compact.replace('-', "+").replace('_', "/");
+        (
+            STANDARD_NO_PAD.decode(normalized).ok()?,
+            Base64Variant::Mixed,
+        )
+    };
+    String::from_utf8(decoded)
+        .ok()
+        .map(|decoded| (decoded, variant))
+}
+
 /// Decodes a URL-safe Base64 string to its original form.
 pub fn url_safe_base64_decode(input: &str) -> String {
     base64_decode(input, true)
@@ -57,6 +98,8 @@ pub fn url_safe_base64_encode(input: &str) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ::base64::engine::general_purpose::{STANDARD, URL_SAFE};
+    use ::base64::Engine as _;
 
     #[test]
     fn test_base64_encode() {
@@ -71,4 +114,68 @@ mod tests {
         let decoded = base64_decode(input, true);
         assert_eq!(decoded, "64.137.228.35:5760:auth_sha1_v4:chacha20:tls1.2_ticket_auth:ZG91Yi5pby9zc3poZngvKjU3NjA/?remarks=5pys5YWN6LS56LSm5Y-35p2l6IeqOmRvdWIuaW8vc3N6aGZ4Lw");
     }
+
+    #[test]
+    fn test_decode_lenient_recoverable() {
+        let plain = "ss://YWVzLTEyOC1nY206cGFzcw@1.2.3.4:8388#A?B>C\nvmess://x\n";
+        let standard = STANDARD.encode(plain);
+        let url_safe = URL_SAFE.encode(plain);
+        assert!(standard.contains('+') || standard.contains('/'));
+
+        let cases = [
+            (standard.clone(), Base64Variant::Standard),
+            (
+                standard.trim_end_matches('=').to_string(),
+                Base64Variant::Standard,
+            ),
+            (
+                format!("{}==", standard.trim_end_matches('=')),
+                Base64Variant::Standard,
+            ),
+            (format!(" {}\r\n", standard), Base64Variant::Standard),
+            (
+                standard
+                    .as_bytes()
+                    .chunks(16)
+                    .map(|c| std::str::from_utf8(c).unwrap())
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                Base64Variant::Standard,
+            ),
+            (url_safe.clone(), Base64Variant::UrlSafe),
+            (
+                url_safe.trim_end_matches('=').to_string(),
+                Base64Variant::UrlSafe,
+            ),
+        ];
+        for (input, variant) in cases {
+            assert_eq!(
+                decode_lenient(&input),
+                Some((plain.to_string(), variant)),
+                "input: {:?}",
+                input
+            );
+        }
+
+        assert_eq!(
+            decode_lenient("PDw_Pz4+"),
+            Some(("<<??>>".to_string(), Base64Variant::Mixed))
+        );
+    }
+
+    #[test]
+    fn test_decode_lenient_invalid() {
+        for input in [
+            "",
+            "   \n",
+            "====",
+            "ss://1.2.3.4:8388",
+            "abc*def",
+            "QUJD=REVG",
+            "QUJDR",
+            "//79/Q",
+        ] {
+            assert_eq!(decode_lenient(input), None, "input: {:?}", input);
+        }
+    }
 }
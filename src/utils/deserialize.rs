@@ -67,3 +67,124 @@ where
 
     deserializer.deserialize_any(StringOrNumberVisitor)
 }
+
+/// Helper function to deserialize lists that may also be written as a string
+///
+/// Clash configs write fields like the HTTP `path` as a sequence, but some
+/// providers use a single string, taken as one element even with commas.
+pub fn deserialize_string_or_seq<'de, D>(deserializer: D) -> Result<Option<Vec<String>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_any(StringOrSeqVisitor {
+        split_commas: false,
+    })
+}
+
+/// Helper function to deserialize lists that may also be written as a comma
+/// separated string
+///
+/// Clash configs write `alpn` as a sequence, `alpn: [h3, h2]`, but some
+/// providers use a string such as `h3,h2`.
+pub fn deserialize_comma_list_or_seq<'de, D>(
+    deserializer: D,
+) -> Result<Option<Vec<String>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_any(StringOrSeqVisitor { split_commas: true })
+}
+
+struct StringOrSeqVisitor {
+    /// Whether a single string holds several comma separated elements
+    split_commas: bool,
+}
+
+impl<'de> Visitor<'de> for StringOrSeqVisitor {
+    type Value = Option<Vec<String>>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("string or sequence of strings")
+    }
+
+    // Handle a single string
+    fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        if !self.split_commas {
+            return Ok(Some(if value.is_empty() {
+                Vec::new()
+            } else {
+                vec![value.to_string()]
+            }));
+        }
+        Ok(Some(
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(str::to_string)
+                .collect(),
+        ))
+    }
+
+    // Handle sequence
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        let mut values = Vec::new();
+        while let Some(value) = seq.next_element::<String>()? {
+            if !value.is_empty() {
+                values.push(value);
+            }
+        }
+        Ok(Some(values))
+    }
+
+    // Handle None
+    fn visit_none<E>(self) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(None)
+    }
+
+    // Handle null
+    fn visit_unit<E>(self) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    struct Lists {
+        #[serde(default, deserialize_with = "deserialize_string_or_seq")]
+        path: Option<Vec<String>>,
+        #[serde(default, deserialize_with = "deserialize_comma_list_or_seq")]
+        alpn: Option<Vec<String>>,
+    }
+
+    #[test]
+    fn test_only_alpn_splits_on_commas() {
+        let lists: Lists = serde_yaml::from_str("path: /a,b\nalpn: h3, h2\n").unwrap();
+        assert_eq!(lists.path.unwrap(), ["/a,b"]);
+        assert_eq!(lists.alpn.unwrap(), ["h3", "h2"]);
+
+        let lists: Lists = serde_yaml::from_str("path: [/a, /b]\nalpn: [h3]\n").unwrap();
+        assert_eq!(lists.path.unwrap(), ["/a", "/b"]);
+        assert_eq!(lists.alpn.unwrap(), ["h3"]);
+
+        let lists: Lists = serde_yaml::from_str("path: ''\n").unwrap();
+        assert!(lists.path.unwrap().is_empty());
+        assert!(lists.alpn.is_none());
+    }
+}
@@ -0,0 +1,243 @@
+//! Loading of subscriptions and external configs
+//!
+//! Everything the conversion downloads goes through a [`SubscriptionFetcher`].
+//! The default one is a [`FetcherRouter`] that picks a fetcher by scheme:
+//! `data:` URIs are decoded in place, `http(s)://` URLs are downloaded and
+//! everything else is read as a local file. Libraries and tests can swap in
+//! their own fetcher, e.g. a [`MemoryFetcher`] serving fixtures.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use case_insensitive_string::CaseInsensitiveString;
+#[cfg(not(target_arch = "wasm32"))]
+use futures::future::BoxFuture;
+#[cfg(target_arch = "wasm32")]
+use futures::future::LocalBoxFuture;
+use once_cell::sync::Lazy;
+
+use crate::utils::file_get_async;
+use crate::utils::http::{web_get_async_limited, ProxyConfig};
+use crate::utils::url::decode_data_uri;
+
+/// The fetcher shared by every conversion that does not set its own
+static DEFAULT_FETCHER: Lazy<Arc<dyn SubscriptionFetcher>> =
+    Lazy::new(|| Arc::new(FetcherRouter::default()));
+
+/// Pending result of [`SubscriptionFetcher::fetch`]
+#[cfg(not(target_arch = "wasm32"))]
+pub type FetchFuture<'a> = BoxFuture<'a, Result<FetchedBody, String>>;
+/// Pending result of [`SubscriptionFetcher::fetch`]
+#[cfg(target_arch = "wasm32")]
+pub type FetchFuture<'a> = LocalBoxFuture<'a, Result<FetchedBody, String>>;
+
+/// How a body is fetched
+#[derive(Debug, Clone, Default)]
+pub struct FetchOptions {
+    /// Proxy used for downloads
+    pub proxy: ProxyConfig,
+    /// Extra request headers
+    pub headers: Option<HashMap<CaseInsensitiveString, String>>,
+    /// Maximum size in bytes of the body, 0 for unlimited
+    pub max_size: i64,
+}
+
+/// A fetched subscription or config
+#[derive(Debug, Clone, Default)]
+pub struct FetchedBody {
+    pub body: String,
+    /// Response headers with lowercase names, empty when not fetched over HTTP
+    pub headers: HashMap<String, String>,
+}
+
+impl FetchedBody {
+    pub fn new(body: impl Into<String>) -> Self {
+        FetchedBody {
+            body: body.into(),
+            headers: HashMap::new(),
+        }
+    }
+}
+
+/// Loads the content behind a subscription or config URL
+pub trait SubscriptionFetcher: Send + Sync {
+    fn fetch<'a>(&'a self, url: &'a str, opts: &'a FetchOptions) -> FetchFuture<'a>;
+}
+
+impl fmt::Debug for dyn SubscriptionFetcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SubscriptionFetcher")
+    }
+}
+
+/// The fetcher used when none is configured
+pub fn default_fetcher() -> Arc<dyn SubscriptionFetcher> {
+    DEFAULT_FETCHER.clone()
+}
+
+/// Downloads `http(s)://` URLs
+pub struct HttpFetcher;
+
+impl SubscriptionFetcher for HttpFetcher {
+    fn fetch<'a>(&'a self, url: &'a str, opts: &'a FetchOptions) -> FetchFuture<'a> {
+        Box::pin(async move {
+            let response =
+                web_get_async_limited(url, &opts.proxy, opts.headers.as_ref(), opts.max_size)
+                    .await
+                    .map_err(|e| e.to_string())?;
+            Ok(FetchedBody {
+                body: response.body,
+                headers: response.headers,
+            })
+        })
+    }
+}
+
+/// Decodes `data:` URIs, `max_size` limits the length of the URI
+pub struct DataUriFetcher;
+
+impl SubscriptionFetcher for DataUriFetcher {
+    fn fetch<'a>(&'a self, url: &'a str, opts: &'a FetchOptions) -> FetchFuture<'a> {
+        Box::pin(async move {
+            if opts.max_size > 0 && url.len() as i64 > opts.max_size {
+                return Err(format!(
+                    "Data URI size {} exceeds the limit of {} bytes",
+                    url.len(),
+                    opts.max_size
+                ));
+            }
+            decode_data_uri(url)
+                .map(FetchedBody::new)
+                .ok_or_else(|| "Invalid data URI".to_string())
+        })
+    }
+}
+
+/// Reads local files, given as a path or a `file://` URL
+///
+/// Whether local files may be read at all is up to the caller.
+pub struct FileFetcher;
+
+impl SubscriptionFetcher for FileFetcher {
+    fn fetch<'a>(&'a self, url: &'a str, _opts: &'a FetchOptions) -> FetchFuture<'a> {
+        Box::pin(async move {
+            let path = url.strip_prefix("file://").unwrap_or(url);
+            file_get_async(path, None)
+                .await
+                .map(FetchedBody::new)
+                .map_err(|e| format!("Failed to read file {}: {}", path, e))
+        })
+    }
+}
+
+/// Picks a fetcher by the scheme of the URL
+pub struct FetcherRouter {
+    /// `http://` and `https://` URLs
+    pub http: Arc<dyn SubscriptionFetcher>,
+    /// `data:` URIs
+    pub data: Arc<dyn SubscriptionFetcher>,
+    /// Everything else
+    pub file: Arc<dyn SubscriptionFetcher>,
+}
+
+impl Default for FetcherRouter {
+    fn default() -> Self {
+        FetcherRouter {
+            http: Arc::new(HttpFetcher),
+            data: Arc::new(DataUriFetcher),
+            file: Arc::new(FileFetcher),
+        }
+    }
+}
+
+impl FetcherRouter {
+    fn route(&self, url: &str) -> &dyn SubscriptionFetcher {
+        if url.starts_with("data:") {
+            self.data.as_ref()
+        } else if url.starts_with("http://") || url.starts_with("https://") {
+            self.http.as_ref()
+        } else {
+            self.file.as_ref()
+        }
+    }
+}
+
+impl SubscriptionFetcher for FetcherRouter {
+    fn fetch<'a>(&'a self, url: &'a str, opts: &'a FetchOptions) -> FetchFuture<'a> {
+        self.route(url).fetch(url, opts)
+    }
+}
+
+/// Serves fixed bodies by URL, unknown URLs fail
+#[derive(Debug, Clone, Default)]
+pub struct MemoryFetcher {
+    bodies: HashMap<String, FetchedBody>,
+}
+
+impl MemoryFetcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serve `body` for `url`
+    pub fn insert(&mut self, url: &str, body: FetchedBody) -> &mut Self {
+        self.bodies.insert(url.to_string(), body);
+        self
+    }
+}
+
+impl SubscriptionFetcher for MemoryFetcher {
+    fn fetch<'a>(&'a self, url: &'a str, _opts: &'a FetchOptions) -> FetchFuture<'a> {
+        Box::pin(async move {
+            self.bodies
+                .get(url)
+                .cloned()
+                .ok_or_else(|| format!("No body for {}", url))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn test_router_picks_fetcher_by_scheme() {
+        let mut remote = MemoryFetcher::new();
+        remote.insert("https://example.com/sub", FetchedBody::new("remote"));
+        let mut local = MemoryFetcher::new();
+        local.insert("file:///etc/sub.txt", FetchedBody::new("local"));
+        let router = FetcherRouter {
+            http: Arc::new(remote),
+            file: Arc::new(local),
+            ..Default::default()
+        };
+        let opts = FetchOptions::default();
+
+        let fetch =
+            |url: &'static str| block_on(router.fetch(url, &opts)).map(|fetched| fetched.body);
+        assert_eq!(fetch("https://example.com/sub").unwrap(), "remote");
+        assert_eq!(fetch("file:///etc/sub.txt").unwrap(), "local");
+        assert_eq!(fetch("data:,inline").unwrap(), "inline");
+        assert!(fetch("https://example.com/other").is_err());
+    }
+
+    #[test]
+    fn test_data_uri_size_limit() {
+        let opts = FetchOptions {
+            max_size: 8,
+            ..Default::default()
+        };
+        let err = block_on(DataUriFetcher.fetch("data:,too long", &opts)).unwrap_err();
+        assert_eq!(err, "Data URI size 14 exceeds the limit of 8 bytes");
+    }
+}
@@ -1,5 +1,5 @@
 use crate::settings::Settings;
-use crate::utils::http::{parse_proxy, web_get_async};
+use crate::utils::http::{parse_proxy, web_get_async_limited};
 
 // Import platform-specific implementations
 #[cfg(not(target_arch = "wasm32"))]
@@ -7,11 +7,52 @@ mod platform {
     pub use crate::utils::file_std::*;
 }
 
-#[cfg(target_arch = "wasm32")]
+#[cfg(all(target_arch = "wasm32", feature = "wasm-vfs"))]
 mod platform {
     pub use crate::utils::file_wasm::*;
 }
 
+/// Without the `wasm-vfs` feature a wasm32 build has no local files, only URLs load
+#[cfg(all(target_arch = "wasm32", not(feature = "wasm-vfs")))]
+mod platform {
+    use std::io;
+    use std::path::Path;
+
+    fn no_file_system(path: &str) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("Cannot access {}, built without the wasm-vfs feature", path),
+        )
+    }
+
+    pub async fn read_file(path: &str) -> io::Result<String> {
+        Err(no_file_system(path))
+    }
+
+    pub async fn read_file_async(path: &str) -> io::Result<String> {
+        Err(no_file_system(path))
+    }
+
+    pub async fn file_exists(_path: &str) -> bool {
+        false
+    }
+
+    pub async fn copy_file(src: &str, _dst: &str) -> io::Result<()> {
+        Err(no_file_system(src))
+    }
+
+    pub async fn write_file_async(path: &str, _content: &str) -> io::Result<()> {
+        Err(no_file_system(path))
+    }
+
+    pub async fn file_get_async<P: AsRef<Path>>(
+        path: P,
+        _base_path: Option<&str>,
+    ) -> io::Result<String> {
+        Err(no_file_system(&path.as_ref().to_string_lossy()))
+    }
+}
+
 // Re-export platform-specific implementations
 pub use platform::*;
 
@@ -21,14 +62,17 @@ pub use platform::*;
 ///
 /// # Arguments
 /// * `path` - Path to the file or URL to load
+/// * `settings` - Settings giving the proxy and size limit of URLs
 ///
 /// # Returns
 /// * `Ok(String)` - The content
 /// * `Err(String)` - Error message if loading failed
-pub async fn load_content_async(path: &str) -> Result<String, String> {
+pub async fn load_content_async(path: &str, settings: &Settings) -> Result<String, String> {
     if path.starts_with("http://") || path.starts_with("https://") {
         // It's a URL, use HTTP client
-        match web_get_async(path, &parse_proxy(&Settings::current().proxy_config), None).await {
+        let proxy = parse_proxy(&settings.proxy_config);
+        let max_size = settings.max_allowed_download_size;
+        match web_get_async_limited(path, &proxy, None, max_size).await {
             Ok(response) => Ok(response.body),
             Err(e) => Err(format!("Failed to read file from URL: {}", e)),
         }
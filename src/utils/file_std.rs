@@ -78,6 +78,16 @@ pub async fn copy_file(src: &str, dst: &str) -> io::Result<()> {
     Ok(())
 }
 
+/// Write a string to a file, creating parent directories if needed
+pub async fn write_file_async(path: &str, content: &str) -> io::Result<()> {
+    if let Some(parent) = Path::new(path).parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent)?;
+        }
+    }
+    fs::write(path, content)
+}
+
 /// Async version of file_get that reads file contents asynchronously
 ///
 /// # Arguments
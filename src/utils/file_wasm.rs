@@ -165,6 +165,14 @@ pub async fn copy_file(src: &str, dst: &str) -> io::Result<()> {
     }
 }
 
+/// Write a string to a file (async)
+pub async fn write_file_async(path: &str, content: &str) -> io::Result<()> {
+    let vfs = get_vfs().await?;
+    vfs.write_file(path, content.as_bytes().to_vec())
+        .await
+        .map_err(map_vfs_error)
+}
+
 /// Async version of file_get that reads file contents asynchronously
 /// (This is now the primary implementation)
 ///
@@ -0,0 +1,256 @@
+//! Country flags for nodes whose remark carries no country hint
+//!
+//! The server of a node is resolved and looked up in a country database,
+//! the flag of the country is prepended to the remark. Only nodes no emoji
+//! rule matched are tagged.
+
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+#[cfg(feature = "geoip")]
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use futures::stream::{self, StreamExt};
+use log::debug;
+#[cfg(feature = "geoip")]
+use once_cell::sync::Lazy;
+use tokio::time::timeout;
+
+use crate::models::{Proxy, RegexMatchConfigs};
+
+/// Timeout of resolving a single hostname
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(2);
+/// Maximum number of hostnames resolved at the same time
+const LOOKUP_CONCURRENCY: usize = 16;
+
+/// Opened country databases keyed by path
+#[cfg(feature = "geoip")]
+static DATABASES: Lazy<Mutex<HashMap<String, Arc<MaxmindDatabase>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Resolves the hostname of a node server
+pub trait HostResolver: Send + Sync {
+    fn resolve<'a>(&'a self, host: &'a str) -> BoxFuture<'a, Vec<IpAddr>>;
+}
+
+/// Finds the ISO 3166 country code of an address
+pub trait CountryDatabase: Send + Sync {
+    fn country_code(&self, ip: IpAddr) -> Option<String>;
+}
+
+/// Resolver of the operating system
+pub struct SystemResolver;
+
+impl HostResolver for SystemResolver {
+    fn resolve<'a>(&'a self, host: &'a str) -> BoxFuture<'a, Vec<IpAddr>> {
+        Box::pin(async move {
+            match tokio::net::lookup_host((host, 0)).await {
+                Ok(addrs) => addrs.map(|addr| addr.ip()).collect(),
+                Err(e) => {
+                    debug!("Failed to resolve '{}': {}", host, e);
+                    Vec::new()
+                }
+            }
+        })
+    }
+}
+
+/// A MaxMind country database, e.g. GeoLite2-Country.mmdb
+#[cfg(feature = "geoip")]
+pub struct MaxmindDatabase {
+    reader: maxminddb::Reader<Vec<u8>>,
+}
+
+#[cfg(feature = "geoip")]
+impl MaxmindDatabase {
+    pub fn open(path: &str) -> Result<Self, String> {
+        maxminddb::Reader::open_readfile(path)
+            .map(|reader| MaxmindDatabase { reader })
+            .map_err(|e| format!("Failed to open GeoIP database '{}': {}", path, e))
+    }
+
+    /// The database at `path`, opened on first use
+    pub fn shared(path: &str) -> Result<Arc<Self>, String> {
+        let mut databases = DATABASES.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(database) = databases.get(path) {
+            return Ok(database.clone());
+        }
+        let database = Arc::new(Self::open(path)?);
+        databases.insert(path.to_string(), database.clone());
+        Ok(database)
+    }
+}
+
+#[cfg(feature = "geoip")]
+impl CountryDatabase for MaxmindDatabase {
+    fn country_code(&self, ip: IpAddr) -> Option<String> {
+        let country: maxminddb::geoip2::Country = self.reader.lookup(ip).ok()?;
+        country.country?.iso_code.map(str::to_string)
+    }
+}
+
+/// Flag emoji of a two-letter country code
+pub fn country_flag(code: &str) -> Option<String> {
+    if code.len() != 2 || !code.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    code.to_ascii_uppercase()
+        .chars()
+        .map(|c| char::from_u32(0x1F1E6 + (c as u32 - 'A' as u32)))
+        .collect()
+}
+
+/// Whether the remark already starts with an emoji rule replacement or a flag
+fn has_country_hint(remark: &str, emoji_array: &RegexMatchConfigs) -> bool {
+    let regional_indicator = remark
+        .chars()
+        .next()
+        .is_some_and(|c| ('\u{1F1E6}'..='\u{1F1FF}').contains(&c));
+    regional_indicator
+        || emoji_array
+            .iter()
+            .any(|pattern| !pattern.replace.is_empty() && remark.starts_with(&pattern.replace))
+}
+
+/// Prepend the flag of the server country to nodes without a country hint
+///
+/// Every hostname is looked up once, IP addresses are not resolved. Returns
+/// the number of tagged nodes.
+pub async fn add_geo_emoji(
+    nodes: &mut [Proxy],
+    emoji_array: &RegexMatchConfigs,
+    resolver: &dyn HostResolver,
+    database: &dyn CountryDatabase,
+) -> usize {
+    let untagged: Vec<usize> = nodes
+        .iter()
+        .enumerate()
+        .filter(|(_, node)| {
+            !node.hostname.is_empty() && !has_country_hint(&node.remark, emoji_array)
+        })
+        .map(|(index, _)| index)
+        .collect();
+    let hosts: HashSet<String> = untagged
+        .iter()
+        .map(|index| nodes[*index].hostname.clone())
+        .collect();
+
+    let countries: HashMap<String, String> = stream::iter(hosts)
+        .map(|host| async move {
+            let ips = match host.parse::<IpAddr>() {
+                Ok(ip) => vec![ip],
+                Err(_) => timeout(LOOKUP_TIMEOUT, resolver.resolve(&host))
+                    .await
+                    .unwrap_or_default(),
+            };
+            let code = ips.into_iter().find_map(|ip| database.country_code(ip));
+            debug!("GeoIP lookup of '{}': {:?}", host, code);
+            (host, code)
+        })
+        .buffer_unordered(LOOKUP_CONCURRENCY)
+        .filter_map(|(host, code)| async move { Some((host, code?)) })
+        .collect()
+        .await;
+
+    let mut tagged = 0;
+    for index in untagged {
+        let node = &mut nodes[index];
+        if let Some(flag) = countries
+            .get(&node.hostname)
+            .and_then(|code| country_flag(code))
+        {
+            node.remark = format!("{} {}", flag, node.remark);
+            tagged += 1;
+        }
+    }
+    tagged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::RegexMatchConfig;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct StubResolver {
+        calls: AtomicUsize,
+    }
+
+    impl HostResolver for StubResolver {
+        fn resolve<'a>(&'a self, host: &'a str) -> BoxFuture<'a, Vec<IpAddr>> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async move {
+                match host {
+                    "jp.example.com" => vec!["10.0.0.2".parse().unwrap()],
+                    _ => Vec::new(),
+                }
+            })
+        }
+    }
+
+    struct StubDatabase;
+
+    impl CountryDatabase for StubDatabase {
+        fn country_code(&self, ip: IpAddr) -> Option<String> {
+            match ip.to_string().as_str() {
+                "10.0.0.1" => Some("US".to_string()),
+                "10.0.0.2" => Some("JP".to_string()),
+                _ => None,
+            }
+        }
+    }
+
+    fn node(remark: &str, hostname: &str) -> Proxy {
+        Proxy {
+            remark: remark.to_string(),
+            hostname: hostname.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_country_flag() {
+        assert_eq!(country_flag("jp").as_deref(), Some("🇯🇵"));
+        assert_eq!(country_flag("USA"), None);
+    }
+
+    #[test]
+    fn test_add_geo_emoji() {
+        let emojis = vec![RegexMatchConfig {
+            _match: "HK".to_string(),
+            replace: "🇭🇰".to_string(),
+        }];
+        let mut nodes = vec![
+            node("Server-01", "10.0.0.1"),
+            node("Server-02", "jp.example.com"),
+            node("Server-03", "jp.example.com"),
+            node("🇭🇰 HK 01", "10.0.0.1"),
+            node("Server-04", "unknown.example.com"),
+        ];
+        let resolver = StubResolver {
+            calls: AtomicUsize::new(0),
+        };
+
+        let tagged = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(add_geo_emoji(&mut nodes, &emojis, &resolver, &StubDatabase));
+
+        assert_eq!(tagged, 3);
+        let remarks: Vec<&str> = nodes.iter().map(|node| node.remark.as_str()).collect();
+        assert_eq!(
+            remarks,
+            [
+                "🇺🇸 Server-01",
+                "🇯🇵 Server-02",
+                "🇯🇵 Server-03",
+                "🇭🇰 HK 01",
+                "Server-04"
+            ]
+        );
+        // IP addresses are not resolved, repeated hostnames only once
+        assert_eq!(resolver.calls.load(Ordering::SeqCst), 2);
+    }
+}
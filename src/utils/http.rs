@@ -120,3 +120,71 @@ pub fn get_sub_info_from_response(
         false
     }
 }
+
+/// Build a `Content-Disposition` header offering `filename` as a download
+///
+/// `filename=` holds an ASCII fallback where every other character, quotes
+/// and path separators are replaced by `_`. `filename*=` holds the name
+/// itself, UTF-8 percent-encoded as described in RFC 5987.
+pub fn content_disposition(filename: &str) -> String {
+    let fallback: String = filename
+        .chars()
+        .map(|c| match c {
+            '"' | '\\' | '/' => '_',
+            c if c.is_ascii() && !c.is_ascii_control() => c,
+            _ => '_',
+        })
+        .collect();
+    format!(
+        "attachment; filename=\"{}\"; filename*=UTF-8''{}",
+        fallback,
+        rfc5987_encode(filename)
+    )
+}
+
+/// Percent-encode every byte that is not an RFC 5987 `attr-char`
+fn rfc5987_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z'
+            | b'a'..=b'z'
+            | b'0'..=b'9'
+            | b'!'
+            | b'#'
+            | b'$'
+            | b'&'
+            | b'+'
+            | b'-'
+            | b'.'
+            | b'^'
+            | b'_'
+            | b'`'
+            | b'|'
+            | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_disposition() {
+        assert_eq!(
+            content_disposition("clash.yaml"),
+            "attachment; filename=\"clash.yaml\"; filename*=UTF-8''clash.yaml"
+        );
+        assert_eq!(
+            content_disposition("配置.yaml"),
+            "attachment; filename=\"__.yaml\"; filename*=UTF-8''%E9%85%8D%E7%BD%AE.yaml"
+        );
+        assert_eq!(
+            content_disposition("🚀 \"fast\".yaml"),
+            "attachment; filename=\"_ _fast_.yaml\"; filename*=UTF-8''%F0%9F%9A%80%20%22fast%22.yaml"
+        );
+    }
+}
@@ -1,13 +1,32 @@
 use crate::utils::system::get_system_proxy;
 use case_insensitive_string::CaseInsensitiveString;
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::error::Error as StdError;
+use std::sync::Arc;
 use std::time::Duration;
 
 use reqwest::{Client, Proxy};
 
 /// Default timeout for HTTP requests in seconds
 const DEFAULT_TIMEOUT: u64 = 15;
+/// How long an idle pooled connection is kept open in seconds
+const POOL_IDLE_TIMEOUT: u64 = 90;
+/// Idle connections kept per host
+const POOL_MAX_IDLE_PER_HOST: usize = 8;
+/// Interval of TCP keep-alive probes in seconds
+const TCP_KEEPALIVE: u64 = 60;
+
+thread_local! {
+    /// Shared HTTP clients of this thread keyed by proxy, an empty key for
+    /// direct connections
+    ///
+    /// Every client keeps its own connection pool, so fetching many rulesets
+    /// from the same host reuses connections and the TLS setup. The pooled
+    /// connections are driven by the runtime that opened them, so each actix
+    /// worker, running its own runtime on its own thread, gets its clients.
+    static CLIENTS: RefCell<HashMap<String, Arc<Client>>> = RefCell::new(HashMap::new());
+}
 
 #[derive(Debug, Clone)]
 pub struct ProxyConfig {
@@ -71,6 +90,40 @@ pub fn parse_proxy(proxy_str: &str) -> ProxyConfig {
     ProxyConfig { proxy: None }
 }
 
+/// The shared client of this thread for the proxy of `proxy_config`, built
+/// on first use
+fn shared_client(proxy_config: &ProxyConfig) -> Result<Arc<Client>, HttpError> {
+    let proxy = proxy_config.proxy.as_deref().unwrap_or_default();
+    if let Some(client) = CLIENTS.with(|clients| clients.borrow().get(proxy).cloned()) {
+        return Ok(client);
+    }
+
+    let mut client_builder = Client::builder()
+        .timeout(Duration::from_secs(DEFAULT_TIMEOUT))
+        .pool_idle_timeout(Duration::from_secs(POOL_IDLE_TIMEOUT))
+        .pool_max_idle_per_host(POOL_MAX_IDLE_PER_HOST)
+        .tcp_keepalive(Duration::from_secs(TCP_KEEPALIVE))
+        .user_agent("subconverter-rs");
+    if !proxy.is_empty() {
+        let proxy = Proxy::all(proxy).map_err(|e| HttpError {
+            message: format!("Failed to set proxy: {}", e),
+            status: None,
+        })?;
+        client_builder = client_builder.proxy(proxy);
+    }
+    let client = Arc::new(client_builder.build().map_err(|e| HttpError {
+        message: format!("Failed to build HTTP client: {}", e),
+        status: None,
+    })?);
+
+    CLIENTS.with(|clients| {
+        clients
+            .borrow_mut()
+            .insert(proxy.to_string(), client.clone())
+    });
+    Ok(client)
+}
+
 /// Makes an HTTP request to the specified URL
 ///
 /// # Arguments
@@ -86,36 +139,25 @@ pub async fn web_get_async(
     proxy_config: &ProxyConfig,
     headers: Option<&HashMap<CaseInsensitiveString, String>>,
 ) -> Result<HttpResponse, HttpError> {
-    // Build client with proxy if specified
-    let mut client_builder = Client::builder()
-        .timeout(Duration::from_secs(DEFAULT_TIMEOUT))
-        .user_agent("subconverter-rs");
-
-    if let Some(proxy) = &proxy_config.proxy {
-        if !proxy.is_empty() {
-            match Proxy::all(proxy) {
-                Ok(proxy) => {
-                    client_builder = client_builder.proxy(proxy);
-                }
-                Err(e) => {
-                    return Err(HttpError {
-                        message: format!("Failed to set proxy: {}", e),
-                        status: None,
-                    });
-                }
-            }
-        }
-    }
+    web_get_async_limited(url, proxy_config, headers, 0).await
+}
 
-    let client = match client_builder.build() {
-        Ok(client) => client,
-        Err(e) => {
-            return Err(HttpError {
-                message: format!("Failed to build HTTP client: {}", e),
-                status: None,
-            });
-        }
-    };
+/// Makes an HTTP request, giving up once the body grows past `max_size` bytes
+///
+/// The body is read chunk by chunk so an oversized response is dropped
+/// mid-download instead of being buffered in full. A `max_size` of 0 or less
+/// disables the limit.
+///
+/// # Returns
+/// * `Ok(HttpResponse)` - The response with status, body, and headers
+/// * `Err(HttpError)` - Error details if the request failed or the body was too large
+pub async fn web_get_async_limited(
+    url: &str,
+    proxy_config: &ProxyConfig,
+    headers: Option<&HashMap<CaseInsensitiveString, String>>,
+    max_size: i64,
+) -> Result<HttpResponse, HttpError> {
+    let client = shared_client(proxy_config)?;
 
     // Build request with headers if specified
     let mut request_builder = client.get(url);
@@ -126,7 +168,7 @@ pub async fn web_get_async(
     }
 
     // Send request and get response
-    let response = match request_builder.send().await {
+    let mut response = match request_builder.send().await {
         Ok(resp) => resp,
         Err(e) => {
             return Err(HttpError {
@@ -147,18 +189,43 @@ pub async fn web_get_async(
         }
     }
 
+    let limit = u64::try_from(max_size).unwrap_or(0);
+    let too_large = || HttpError {
+        message: format!(
+            "Response body exceeds the maximum allowed download size of {} bytes",
+            limit
+        ),
+        status: Some(status),
+    };
+    if limit > 0 && response.content_length().is_some_and(|len| len > limit) {
+        return Err(too_large());
+    }
+
     // Get response body, even for error responses
-    match response.text().await {
-        Ok(body) => Ok(HttpResponse {
-            status,
-            body,
-            headers: resp_headers,
-        }),
-        Err(e) => Err(HttpError {
-            message: format!("Failed to read response body: {}", e),
-            status: Some(status),
-        }),
+    let mut body = Vec::new();
+    loop {
+        match response.chunk().await {
+            Ok(Some(chunk)) => {
+                if limit > 0 && (body.len() + chunk.len()) as u64 > limit {
+                    return Err(too_large());
+                }
+                body.extend_from_slice(&chunk);
+            }
+            Ok(None) => break,
+            Err(e) => {
+                return Err(HttpError {
+                    message: format!("Failed to read response body: {}", e),
+                    status: Some(status),
+                })
+            }
+        }
     }
+
+    Ok(HttpResponse {
+        status,
+        body: String::from_utf8_lossy(&body).into_owned(),
+        headers: resp_headers,
+    })
 }
 
 /// Synchronous version of web_get_async that uses tokio runtime to run the async function
@@ -292,3 +359,72 @@ pub fn get_sub_info_from_response(
         false
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Serve a single response without Content-Length so the body is streamed until close
+    fn serve_once(body_size: usize) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0; 1024];
+            let _ = stream.read(&mut buf);
+            let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nConnection: close\r\n\r\n");
+            let _ = stream.write_all(&vec![b'a'; body_size]);
+        });
+        format!("http://{}/sub", addr)
+    }
+
+    fn fetch(url: &str, max_size: i64) -> Result<HttpResponse, HttpError> {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(web_get_async_limited(
+                url,
+                &ProxyConfig::default(),
+                None,
+                max_size,
+            ))
+    }
+
+    #[test]
+    fn test_download_size_limit() {
+        let err = fetch(&serve_once(64 * 1024), 1024).unwrap_err();
+        assert!(err.message.contains("maximum allowed download size"));
+
+        let response = fetch(&serve_once(512), 1024).unwrap();
+        assert_eq!(response.body.len(), 512);
+
+        let response = fetch(&serve_once(4096), 0).unwrap();
+        assert_eq!(response.body.len(), 4096);
+    }
+
+    #[test]
+    fn test_shared_client_per_proxy() {
+        let direct = shared_client(&ProxyConfig::default()).unwrap();
+        let empty = ProxyConfig {
+            proxy: Some(String::new()),
+        };
+        assert!(Arc::ptr_eq(&direct, &shared_client(&empty).unwrap()));
+
+        let proxy_a = parse_proxy("http://127.0.0.1:18080");
+        let proxy_b = parse_proxy("http://127.0.0.1:18081");
+        let client_a = shared_client(&proxy_a).unwrap();
+        assert!(Arc::ptr_eq(&client_a, &shared_client(&proxy_a).unwrap()));
+        assert!(!Arc::ptr_eq(&client_a, &direct));
+        assert!(!Arc::ptr_eq(&client_a, &shared_client(&proxy_b).unwrap()));
+
+        // Another thread, as another actix worker, builds its own
+        let other = std::thread::spawn(|| shared_client(&ProxyConfig::default()).unwrap())
+            .join()
+            .unwrap();
+        assert!(!Arc::ptr_eq(&direct, &other));
+    }
+}
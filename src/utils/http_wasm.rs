@@ -347,6 +347,30 @@ pub async fn web_get_async(
     }
 }
 
+/// Makes an HTTP request, failing if the body is larger than `max_size` bytes
+///
+/// The fetch API hands over the whole body at once, so unlike the native
+/// version the size is only checked after the download. A `max_size` of 0 or
+/// less disables the limit.
+pub async fn web_get_async_limited(
+    url: &str,
+    proxy_config: &ProxyConfig,
+    headers: Option<&HashMap<CaseInsensitiveString, String>>,
+    max_size: i64,
+) -> Result<HttpResponse, HttpError> {
+    let response = web_get_async(url, proxy_config, headers).await?;
+    if max_size > 0 && response.body.len() as i64 > max_size {
+        return Err(HttpError {
+            message: format!(
+                "Response body exceeds the maximum allowed download size of {} bytes",
+                max_size
+            ),
+            status: Some(response.status),
+        });
+    }
+    Ok(response)
+}
+
 /// Synchronous version of web_get_async that uses tokio runtime to run the async function
 ///
 /// This function is provided for compatibility with the existing codebase.
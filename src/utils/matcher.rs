@@ -1,4 +1,5 @@
 use crate::models::{Proxy, ProxyType};
+use crate::utils::regexp::{compiled_regex, RegexFlags};
 use lazy_static::lazy_static;
 use regex::Regex;
 use std::collections::HashMap;
@@ -224,10 +225,11 @@ pub fn reg_find(text: &str, pattern: &str) -> bool {
         return true;
     }
 
-    match Regex::new(&format!("(?i){}", pattern)) {
-        Ok(re) => re.is_match(text),
-        Err(_) => false,
-    }
+    let flags = RegexFlags {
+        case_insensitive: true,
+        ..Default::default()
+    };
+    compiled_regex(pattern, flags).is_some_and(|re| re.is_match(text))
 }
 
 /// Check if a string fully matches a regular expression pattern
@@ -244,10 +246,12 @@ pub fn reg_match(text: &str, pattern: &str) -> bool {
         return true;
     }
 
-    match Regex::new(&format!("(?i)^{}$", pattern)) {
-        Ok(re) => re.is_match(text),
-        Err(_) => false,
-    }
+    let flags = RegexFlags {
+        case_insensitive: true,
+        full_match: true,
+        ..Default::default()
+    };
+    compiled_regex(pattern, flags).is_some_and(|re| re.is_match(text))
 }
 
 #[cfg(test)]
@@ -1,6 +1,8 @@
 //! Network utilities for IP address handling and validation
 
-use std::net::{IpAddr, ToSocketAddrs};
+use std::net::{IpAddr, Ipv6Addr, ToSocketAddrs};
+
+use crate::models::SubconverterTarget;
 
 /// Resolves a hostname to its IP address
 ///
@@ -67,9 +69,71 @@ pub fn is_ipv4(s: &str) -> bool {
 ///
 /// True if the string is a valid IPv6 address, false otherwise
 pub fn is_ipv6(s: &str) -> bool {
-    // Basic implementation - placeholder
-    // In a real implementation, we'd do proper IPv6 validation
-    s.contains(':')
+    s.parse::<Ipv6Addr>().is_ok()
+}
+
+/// Splits an address into host and port
+///
+/// IPv6 hosts may be written as `[2001:db8::1]:443` or, when the host part is
+/// still a valid IPv6 address, as `2001:db8::1:443`. The returned host never
+/// contains brackets.
+///
+/// # Returns
+///
+/// The host and port, or `None` if the address has no valid port
+pub fn split_host_port(addr: &str) -> Option<(String, u16)> {
+    let addr = addr.trim();
+    let (host, port) = match addr.strip_prefix('[') {
+        Some(rest) => {
+            let (host, port) = rest.split_once(']')?;
+            (host, port.strip_prefix(':')?)
+        }
+        None => {
+            let (host, port) = addr.rsplit_once(':')?;
+            if host.contains(':') && !is_ipv6(host) {
+                return None;
+            }
+            (host, port)
+        }
+    };
+
+    if host.is_empty() {
+        return None;
+    }
+    let port = port.parse::<u16>().ok()?;
+    Some((host.to_string(), port))
+}
+
+/// Removes the brackets around an IPv6 literal such as `[2001:db8::1]`
+pub fn unbracket_host(host: &str) -> &str {
+    host.strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+        .unwrap_or(host)
+}
+
+/// Wraps an IPv6 literal in brackets, other hosts are returned as is
+pub fn bracket_host(host: &str) -> String {
+    if is_ipv6(host) {
+        format!("[{}]", host)
+    } else {
+        host.to_string()
+    }
+}
+
+/// Formats a server hostname for the given output format
+///
+/// Formats that write the server as `host:port` or otherwise can not take a bare
+/// IPv6 literal get it wrapped in brackets.
+pub fn format_host_for(target: &SubconverterTarget, host: &str) -> String {
+    match target {
+        SubconverterTarget::Surge(_)
+        | SubconverterTarget::Surfboard
+        | SubconverterTarget::QuantumultX
+        | SubconverterTarget::SS
+        | SubconverterTarget::Trojan
+        | SubconverterTarget::Mixed => bracket_host(host),
+        _ => host.to_string(),
+    }
 }
 
 /// Checks if a string is a valid URL
@@ -114,6 +178,43 @@ mod tests {
         assert!(!is_ipv6("not an ip"));
     }
 
+    #[test]
+    fn test_split_host_port() {
+        assert_eq!(
+            split_host_port("example.com:443"),
+            Some(("example.com".to_string(), 443))
+        );
+        assert_eq!(
+            split_host_port("[2001:db8::1]:8388"),
+            Some(("2001:db8::1".to_string(), 8388))
+        );
+        assert_eq!(
+            split_host_port("2001:db8::1:8388"),
+            Some(("2001:db8::1".to_string(), 8388))
+        );
+        assert_eq!(split_host_port("2001:db8::"), None);
+        assert_eq!(split_host_port("[2001:db8::1]"), None);
+        assert_eq!(split_host_port("example.com"), None);
+        assert_eq!(split_host_port("example.com:port"), None);
+    }
+
+    #[test]
+    fn test_format_host_for() {
+        let surge = SubconverterTarget::Surge(4);
+        assert_eq!(format_host_for(&surge, "2001:db8::1"), "[2001:db8::1]");
+        assert_eq!(format_host_for(&surge, "example.com"), "example.com");
+        assert_eq!(
+            format_host_for(&SubconverterTarget::QuantumultX, "::1"),
+            "[::1]"
+        );
+        assert_eq!(
+            format_host_for(&SubconverterTarget::Clash, "2001:db8::1"),
+            "2001:db8::1"
+        );
+        assert_eq!(unbracket_host("[2001:db8::1]"), "2001:db8::1");
+        assert_eq!(unbracket_host("example.com"), "example.com");
+    }
+
     #[test]
     fn test_hostname_to_ip_addr() {
         // This test might be flaky depending on network conditions
@@ -0,0 +1,257 @@
+//! Reachability probes for subscription nodes
+//!
+//! A node counts as reachable when a TCP connection to its server can be
+//! opened, for TLS nodes the server also has to answer a ClientHello with a
+//! TLS record. Nodes running over UDP are not probed.
+
+use std::net::IpAddr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use futures::stream::{self, StreamExt};
+use log::debug;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::{timeout, timeout_at, Instant};
+
+use crate::models::proxy_node::combined::CombinedProxy;
+use crate::models::{Proxy, ProxyType};
+
+/// Limits of one round of node probes
+#[derive(Debug, Clone)]
+pub struct NodeCheckOptions {
+    /// Timeout of a single probe
+    pub timeout: Duration,
+    /// Maximum number of probes running at the same time
+    pub concurrency: usize,
+    /// Time budget for probing all nodes
+    pub deadline: Duration,
+}
+
+/// What a single node is probed with
+#[derive(Debug, Clone, PartialEq)]
+enum Probe {
+    Tcp,
+    Tls(Option<String>),
+}
+
+fn node_probe(node: &Proxy) -> Option<Probe> {
+    match node.proxy_type {
+        ProxyType::WireGuard | ProxyType::Hysteria | ProxyType::Hysteria2 | ProxyType::Tuic => {
+            return None
+        }
+        ProxyType::Unknown => return None,
+        _ => {}
+    }
+    if node.hostname.is_empty() || node.port == 0 {
+        return None;
+    }
+
+    let vless_tls = matches!(&node.combined_proxy, Some(CombinedProxy::Vless(vless)) if vless.tls);
+    if node.proxy_type == ProxyType::Trojan || node.tls_secure || vless_tls {
+        let server_name = node
+            .sni
+            .iter()
+            .chain(node.host.iter())
+            .map(String::as_str)
+            .chain(std::iter::once(node.hostname.as_str()))
+            .find(|name| !name.is_empty() && name.parse::<IpAddr>().is_err())
+            .map(str::to_string);
+        Some(Probe::Tls(server_name))
+    } else {
+        Some(Probe::Tcp)
+    }
+}
+
+/// Probe all nodes with a bounded number of concurrent connections
+///
+/// The result holds one entry per node, `None` for nodes that are not
+/// probed or were still pending when the deadline passed.
+pub async fn check_nodes(nodes: &[Proxy], options: &NodeCheckOptions) -> Vec<Option<bool>> {
+    let mut results = vec![None; nodes.len()];
+    let deadline = Instant::now() + options.deadline;
+
+    let probes = nodes
+        .iter()
+        .enumerate()
+        .filter_map(|(index, node)| {
+            node_probe(node).map(|probe| (index, node.hostname.clone(), node.port, probe))
+        })
+        .collect::<Vec<_>>();
+    let mut pending = stream::iter(probes)
+        .map(|(index, host, port, probe)| async move {
+            let reachable = probe_server(&host, port, &probe, options.timeout).await;
+            debug!(
+                "Probed {}:{} with {:?}: {}",
+                host,
+                port,
+                probe,
+                if reachable {
+                    "reachable"
+                } else {
+                    "unreachable"
+                }
+            );
+            (index, reachable)
+        })
+        .buffer_unordered(options.concurrency.max(1));
+
+    loop {
+        match timeout_at(deadline, pending.next()).await {
+            Ok(Some((index, reachable))) => results[index] = Some(reachable),
+            Ok(None) => break,
+            Err(_) => {
+                debug!("Node check deadline reached, leaving the remaining nodes unchecked");
+                break;
+            }
+        }
+    }
+    results
+}
+
+async fn probe_server(host: &str, port: u16, probe: &Probe, limit: Duration) -> bool {
+    let attempt = async {
+        let mut stream = TcpStream::connect((host, port)).await?;
+        if let Probe::Tls(server_name) = probe {
+            stream
+                .write_all(&client_hello(server_name.as_deref()))
+                .await?;
+            let mut header = [0u8; 5];
+            stream.read_exact(&mut header).await?;
+            // A handshake or an alert record both come from a TLS server
+            return Ok::<bool, std::io::Error>(matches!(header[0], 0x15 | 0x16));
+        }
+        Ok(true)
+    };
+    matches!(timeout(limit, attempt).await, Ok(Ok(true)))
+}
+
+fn push_u16(out: &mut Vec<u8>, value: usize) {
+    out.extend_from_slice(&(value as u16).to_be_bytes());
+}
+
+fn push_extension(out: &mut Vec<u8>, kind: u16, data: &[u8]) {
+    out.extend_from_slice(&kind.to_be_bytes());
+    push_u16(out, data.len());
+    out.extend_from_slice(data);
+}
+
+/// A minimal ClientHello offering TLS 1.2 and 1.3, enough to make a server answer
+fn client_hello(server_name: Option<&str>) -> Vec<u8> {
+    let mut body = vec![0x03, 0x03];
+    let seed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos())
+        .unwrap_or_default();
+    // The random field only has to look random, the probe never completes the handshake
+    let random = seed.to_le_bytes();
+    body.extend_from_slice(&random);
+    body.extend(random.iter().map(|byte| byte.wrapping_mul(151)));
+    body.push(0);
+    let ciphers: [u16; 7] = [0x1301, 0x1302, 0x1303, 0xc02b, 0xc02f, 0xc02c, 0xc030];
+    push_u16(&mut body, ciphers.len() * 2);
+    for cipher in ciphers {
+        body.extend_from_slice(&cipher.to_be_bytes());
+    }
+    body.extend_from_slice(&[0x01, 0x00]);
+
+    let mut extensions = Vec::new();
+    if let Some(name) = server_name {
+        let mut data = Vec::new();
+        push_u16(&mut data, name.len() + 3);
+        data.push(0);
+        push_u16(&mut data, name.len());
+        data.extend_from_slice(name.as_bytes());
+        push_extension(&mut extensions, 0x0000, &data);
+    }
+    push_extension(
+        &mut extensions,
+        0x000a,
+        &[0x00, 0x04, 0x00, 0x1d, 0x00, 0x17],
+    );
+    push_extension(&mut extensions, 0x000b, &[0x01, 0x00]);
+    push_extension(
+        &mut extensions,
+        0x000d,
+        &[0x00, 0x06, 0x04, 0x03, 0x08, 0x04, 0x04, 0x01],
+    );
+    push_extension(&mut extensions, 0x002b, &[0x04, 0x03, 0x04, 0x03, 0x03]);
+    push_u16(&mut body, extensions.len());
+    body.extend_from_slice(&extensions);
+
+    let mut handshake = vec![0x01];
+    handshake.extend_from_slice(&(body.len() as u32).to_be_bytes()[1..]);
+    handshake.extend_from_slice(&body);
+
+    let mut record = vec![0x16, 0x03, 0x01];
+    push_u16(&mut record, handshake.len());
+    record.extend_from_slice(&handshake);
+    record
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    fn node(hostname: &str, port: u16) -> Proxy {
+        Proxy {
+            proxy_type: ProxyType::Shadowsocks,
+            remark: hostname.to_string(),
+            hostname: hostname.to_string(),
+            port,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_check_nodes() {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+                let port = listener.local_addr().unwrap().port();
+                tokio::spawn(
+                    async move { while let Ok((_socket, _)) = listener.accept().await {} },
+                );
+
+                // Nothing listens on the port of a dropped listener anymore
+                let closed_port = TcpListener::bind("127.0.0.1:0")
+                    .await
+                    .unwrap()
+                    .local_addr()
+                    .unwrap()
+                    .port();
+
+                let mut udp_node = node("127.0.0.1", port);
+                udp_node.proxy_type = ProxyType::Hysteria2;
+                let nodes = vec![
+                    node("127.0.0.1", port),
+                    node("127.0.0.1", closed_port),
+                    udp_node,
+                ];
+                let options = NodeCheckOptions {
+                    timeout: Duration::from_millis(200),
+                    concurrency: 2,
+                    deadline: Duration::from_secs(2),
+                };
+
+                let results = check_nodes(&nodes, &options).await;
+                assert_eq!(results, vec![Some(true), Some(false), None]);
+            });
+    }
+
+    #[test]
+    fn test_client_hello_lengths() {
+        let hello = client_hello(Some("example.com"));
+        assert_eq!(hello[0], 0x16);
+        assert_eq!(
+            u16::from_be_bytes([hello[3], hello[4]]) as usize,
+            hello.len() - 5
+        );
+        assert_eq!(hello[5], 0x01);
+        let body_len = u32::from_be_bytes([0, hello[6], hello[7], hello[8]]) as usize;
+        assert_eq!(body_len, hello.len() - 9);
+    }
+}
@@ -1,7 +1,9 @@
-use log::{debug, info};
+use log::{debug, info, warn};
 use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 
 use crate::models::{
+    conversion_warning::WarningKind,
     extra_settings::ExtraSettings,
     proxy::{Proxy, ProxyType},
     regex_match_config::RegexMatchConfigs,
@@ -9,12 +11,14 @@ use crate::models::{
 use crate::utils::{
     matcher::{apply_matcher, reg_find},
     reg_replace,
+    regexp::{compiled_regex, RegexFlags},
+    remark_template::RemarkTemplate,
     string::{remove_emoji, trim},
 };
 
 /// Applies a rename configuration to a node
 /// Similar to the C++ nodeRename function
-fn node_rename(node: &mut Proxy, rename_array: &RegexMatchConfigs, _extra: &ExtraSettings) {
+fn node_rename(node: &mut Proxy, rename_array: &RegexMatchConfigs, extra: &ExtraSettings) {
     let original_remark = node.remark.clone();
 
     for pattern in rename_array {
@@ -29,12 +33,21 @@ fn node_rename(node: &mut Proxy, rename_array: &RegexMatchConfigs, _extra: &Extr
 
     // If the remark is empty after processing, restore the original
     if node.remark.is_empty() {
+        if !original_remark.is_empty() {
+            extra.warnings.push(
+                WarningKind::RenameFailed,
+                format!(
+                    "Rename rules left node '{}' without a remark, keeping it",
+                    original_remark
+                ),
+            );
+        }
         node.remark = original_remark;
     }
 }
 
-/// Adds emoji to node remark based on regex matching
-fn add_emoji(node: &Proxy, emoji_array: &RegexMatchConfigs, _extra: &ExtraSettings) -> String {
+/// The emoji of the first emoji rule matching the node
+fn find_emoji<'a>(node: &Proxy, emoji_array: &'a RegexMatchConfigs) -> Option<&'a str> {
     for pattern in emoji_array {
         // Skip patterns with empty replace
         if pattern.replace.is_empty() {
@@ -45,19 +58,27 @@ fn add_emoji(node: &Proxy, emoji_array: &RegexMatchConfigs, _extra: &ExtraSettin
         let mut real_rule = String::new();
         if apply_matcher(&pattern._match, &mut real_rule, node) {
             if real_rule.is_empty() || reg_find(&node.remark, &real_rule) {
-                return format!("{} {}", pattern.replace, node.remark);
+                return Some(&pattern.replace);
             }
         }
     }
 
-    node.remark.clone()
+    None
+}
+
+/// Adds emoji to node remark based on regex matching
+fn add_emoji(node: &Proxy, emoji_array: &RegexMatchConfigs, _extra: &ExtraSettings) -> String {
+    match find_emoji(node, emoji_array) {
+        Some(emoji) => format!("{} {}", emoji, node.remark),
+        None => node.remark.clone(),
+    }
 }
 
 /// Sorts nodes by a specified criterion
-fn sort_nodes(nodes: &mut Vec<Proxy>, _sort_script: &str) {
+fn sort_nodes<T>(nodes: &mut [(T, Proxy)], _sort_script: &str) {
     // Skip script-based sorting since we're not implementing JavaScript support
     // Default sort by remark
-    nodes.sort_by(|a, b| {
+    nodes.sort_by(|(_, a), (_, b)| {
         if a.proxy_type == ProxyType::Unknown {
             return Ordering::Greater;
         }
@@ -68,16 +89,174 @@ fn sort_nodes(nodes: &mut Vec<Proxy>, _sort_script: &str) {
     });
 }
 
+/// Position class of a node when pinning: inserted, pinned or the rest
+///
+/// Nodes of insert URLs (negative `group_id`) only rank first with
+/// `keep_inserted_first`.
+pub fn pin_rank(node: &Proxy, pin_regex: &str, keep_inserted_first: bool) -> u8 {
+    if keep_inserted_first && node.group_id < 0 {
+        0
+    } else if reg_find(&node.remark, pin_regex) {
+        1
+    } else {
+        2
+    }
+}
+
+/// Moves the nodes whose remark matches `pin_regex` to the front, keeping their order
+///
+/// Runs after sorting. With `keep_inserted_first`, nodes of the insert URLs
+/// stay ahead of the pinned ones.
+pub fn pin_nodes(nodes: &mut [Proxy], pin_regex: &str, keep_inserted_first: bool) {
+    if pin_regex.is_empty() {
+        return;
+    }
+    // The sort is stable, so every rank keeps its current order
+    nodes.sort_by_key(|node| pin_rank(node, pin_regex, keep_inserted_first));
+}
+
+/// Parse a comma-separated list of proxy type names, warning about unknown names
+pub fn parse_proxy_types(list: &str) -> Vec<ProxyType> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .filter_map(|name| match name.parse() {
+            Ok(ProxyType::Unknown) | Err(_) => {
+                warn!("Ignoring unknown proxy type '{}' in node type filter", name);
+                None
+            }
+            Ok(proxy_type) => Some(proxy_type),
+        })
+        .collect()
+}
+
+/// Keeps the nodes of `include` types, all types when empty, then drops the `exclude` types
+fn filter_node_types(nodes: &mut Vec<Proxy>, include: &[ProxyType], exclude: &[ProxyType]) {
+    if include.is_empty() && exclude.is_empty() {
+        return;
+    }
+    let before = nodes.len();
+    nodes.retain(|node| {
+        (include.is_empty() || include.contains(&node.proxy_type))
+            && !exclude.contains(&node.proxy_type)
+    });
+    debug!(
+        "Node type filter dropped {} of {} nodes",
+        before - nodes.len(),
+        before
+    );
+}
+
+/// Keeps the nodes whose server matches an `include` regex, all nodes when
+/// empty, then drops those matching an `exclude` regex
+///
+/// Host names are case-insensitive, so are the patterns. This runs after
+/// the remark filters of the subscriptions, so a node has to pass both.
+fn filter_node_servers(nodes: &mut Vec<Proxy>, include: &[String], exclude: &[String]) {
+    if include.is_empty() && exclude.is_empty() {
+        return;
+    }
+    let flags = RegexFlags {
+        case_insensitive: true,
+        ..Default::default()
+    };
+    let server_matches = |node: &Proxy, pattern: &String| {
+        compiled_regex(pattern, flags).is_some_and(|re| re.is_match(&node.hostname))
+    };
+    let before = nodes.len();
+    nodes.retain(|node| {
+        (include.is_empty() || include.iter().any(|pattern| server_matches(node, pattern)))
+            && !exclude.iter().any(|pattern| server_matches(node, pattern))
+    });
+    debug!(
+        "Node server filter dropped {} of {} nodes",
+        before - nodes.len(),
+        before
+    );
+}
+
+/// Drops the nodes listed again with the same source id, server and port,
+/// e.g. a SIP008 config fetched from two mirrors
+///
+/// The first node is kept. Nodes without a source id are never dropped, and
+/// nodes sharing only a remark are told apart later by their suffixes.
+fn dedup_source_ids(nodes: &mut Vec<Proxy>) {
+    let before = nodes.len();
+    let mut seen = HashSet::new();
+    nodes.retain(|node| match &node.source_id {
+        Some(id) => seen.insert((id.clone(), node.hostname.clone(), node.port)),
+        None => true,
+    });
+    if nodes.len() < before {
+        debug!(
+            "Dropped {} nodes listed again with the same source id",
+            before - nodes.len()
+        );
+    }
+}
+
+/// Points the underlying proxies of the nodes at the renamed remarks
+///
+/// `renamed` maps the remarks before renaming to the final ones, the first
+/// node wins for duplicate remarks. References to no node are kept with a
+/// warning, as they may name a proxy group of the client.
+fn remap_underlying_proxies(nodes: &mut [Proxy], renamed: &HashMap<String, String>) {
+    let remarks: HashSet<String> = nodes.iter().map(|node| node.remark.clone()).collect();
+    for node in nodes.iter_mut() {
+        let Some(parent) = node
+            .underlying_proxy
+            .as_mut()
+            .filter(|name| !name.is_empty())
+        else {
+            continue;
+        };
+        if let Some(name) = renamed.get(parent.as_str()) {
+            *parent = name.clone();
+        }
+        if !remarks.contains(parent.as_str()) {
+            warn!(
+                "Underlying proxy '{}' of node '{}' is not among the nodes",
+                parent, node.remark
+            );
+        }
+    }
+}
+
 /// Preprocesses nodes before conversion
 /// Based on the C++ preprocessNodes function
+///
+/// Returns the final remark of each remark before renaming, the first node
+/// wins for duplicate remarks.
 pub fn preprocess_nodes(
     nodes: &mut Vec<Proxy>,
     extra: &ExtraSettings,
     rename_patterns: &RegexMatchConfigs,
     emoji_patterns: &RegexMatchConfigs,
-) {
+) -> HashMap<String, String> {
+    filter_node_types(nodes, &extra.include_types, &extra.exclude_types);
+    filter_node_servers(nodes, &extra.include_servers, &extra.exclude_servers);
+    dedup_source_ids(nodes);
+
+    // Run the filters and transforms of the embedding crate before renaming
+    if !extra.plugins.is_empty() {
+        extra.plugins.apply(nodes);
+        debug!("Node plugins left {} nodes", nodes.len());
+    }
+
+    let remark_template = extra
+        .remark_template
+        .as_deref()
+        .filter(|template| !template.trim().is_empty())
+        .map(RemarkTemplate::parse);
+
+    // Each node with its remark before renaming, to follow the chains of underlying proxies
+    let mut with_remarks: Vec<(String, Proxy)> = nodes
+        .drain(..)
+        .map(|node| (node.remark.clone(), node))
+        .collect();
+
     // Process each node
-    for node in nodes.iter_mut() {
+    for (_, node) in with_remarks.iter_mut() {
         // Remove emoji if needed
         if extra.remove_emoji {
             node.remark = trim(&remove_emoji(&node.remark)).to_string();
@@ -86,19 +265,67 @@ pub fn preprocess_nodes(
         // Apply rename patterns
         node_rename(node, rename_patterns, extra);
 
-        // Add emoji if needed
-        if extra.add_emoji {
+        // The template places the emoji itself through `{flag}`
+        if remark_template.is_none() && extra.add_emoji {
             node.remark = add_emoji(node, emoji_patterns, extra);
         }
     }
 
     // Sort nodes if needed
     if extra.sort_flag {
-        info!("Sorting {} nodes", nodes.len());
-        sort_nodes(nodes, &extra.sort_script);
+        info!("Sorting {} nodes", with_remarks.len());
+        sort_nodes(&mut with_remarks, &extra.sort_script);
+    }
+
+    // Applied after sorting, so `{index}` follows the order of the output
+    if let Some(template) = &remark_template {
+        for (index, (_, node)) in with_remarks.iter_mut().enumerate() {
+            let flag = find_emoji(node, emoji_patterns).unwrap_or_default();
+            let remark = template.render(node, index + 1, flag);
+            if !remark.is_empty() {
+                node.remark = remark;
+            }
+        }
+    }
+
+    let mut renamed = HashMap::new();
+    for (before, node) in with_remarks {
+        renamed.entry(before).or_insert_with(|| node.remark.clone());
+        nodes.push(node);
     }
+    remap_underlying_proxies(nodes, &renamed);
 
     debug!("Node preprocessing completed for {} nodes", nodes.len());
+    renamed
+}
+
+/// Counts the VMess nodes with a legacy alterId, setting it to 0 with `force_aead`
+///
+/// Modern cores reject a non-zero alterId or fall back to the legacy
+/// header, alterId 0 makes them use AEAD.
+pub fn normalize_vmess_alter_ids(nodes: &mut [Proxy], force_aead: bool) -> usize {
+    let mut legacy = 0;
+    for node in nodes
+        .iter_mut()
+        .filter(|node| node.proxy_type == ProxyType::VMess && node.alter_id > 0)
+    {
+        legacy += 1;
+        if force_aead {
+            node.alter_id = 0;
+        }
+    }
+    if legacy > 0 {
+        warn!(
+            "{} VMess nodes have a legacy alterId > 0{}",
+            legacy,
+            if force_aead {
+                ", writing them with alterId 0"
+            } else {
+                ""
+            }
+        );
+    }
+    legacy
 }
 
 /// Appends proxy type to node remark
@@ -107,9 +334,285 @@ pub fn append_type_to_remark(nodes: &mut Vec<Proxy>) {
         match node.proxy_type {
             ProxyType::Unknown => {}
             _ => {
-                let type_str = node.proxy_type.to_string();
-                node.remark = format!("{} ({})", node.remark, type_str);
+                node.remark = format!("{} ({})", node.remark, node.proxy_type);
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::RegexMatchConfig;
+    use std::sync::Arc;
+
+    fn node(remark: &str, port: u16) -> Proxy {
+        Proxy {
+            proxy_type: ProxyType::Shadowsocks,
+            remark: remark.to_string(),
+            port,
+            ..Default::default()
+        }
+    }
+
+    fn rule(_match: &str, replace: &str) -> RegexMatchConfig {
+        RegexMatchConfig {
+            _match: _match.to_string(),
+            replace: replace.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_dedup_source_ids() {
+        let with_id = |remark: &str, id: Option<&str>, port| Proxy {
+            source_id: id.map(str::to_string),
+            hostname: "example.com".to_string(),
+            ..node(remark, port)
+        };
+        let mut nodes = vec![
+            with_id("A", Some("27b8a625"), 8388),
+            with_id("A", Some("7842c068"), 8388),
+            with_id("A mirror", Some("27b8a625"), 8388),
+            with_id("A moved", Some("27b8a625"), 8389),
+            with_id("B", None, 8388),
+            with_id("B", None, 8388),
+        ];
+        preprocess_nodes(
+            &mut nodes,
+            &ExtraSettings::default(),
+            &Vec::new(),
+            &Vec::new(),
+        );
+
+        let remarks: Vec<_> = nodes.iter().map(|node| node.remark.as_str()).collect();
+        assert_eq!(remarks, ["A", "A", "A moved", "B", "B"]);
+    }
+
+    #[test]
+    fn test_plugins_run_before_rename_and_emoji() {
+        let mut extra = ExtraSettings::default();
+        extra.add_emoji = true;
+        extra
+            .plugins
+            .filters
+            .push(Arc::new(|node: &Proxy| node.port != 80));
+        extra.plugins.transforms.push(Arc::new(|node: &mut Proxy| {
+            node.remark = node.remark.to_uppercase()
+        }));
+
+        let mut nodes = vec![node("hk plain", 80), node("hk 01", 443)];
+        // Both patterns only match the uppercased remark
+        preprocess_nodes(
+            &mut nodes,
+            &extra,
+            &vec![rule("^HK", "Hong Kong")],
+            &vec![rule("^Hong Kong", "🇭🇰")],
+        );
+
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].remark, "🇭🇰 Hong Kong 01");
+    }
+
+    #[test]
+    fn test_node_type_filters() {
+        let typed = |proxy_type: ProxyType| Proxy {
+            proxy_type,
+            remark: proxy_type.to_string(),
+            ..Default::default()
+        };
+        let nodes = vec![
+            typed(ProxyType::Shadowsocks),
+            typed(ProxyType::ShadowsocksR),
+            typed(ProxyType::Socks5),
+            typed(ProxyType::VMess),
+            typed(ProxyType::Trojan),
+        ];
+        let remarks_after = |include: &str, exclude: &str| {
+            let mut extra = ExtraSettings::default();
+            extra.include_types = parse_proxy_types(include);
+            extra.exclude_types = parse_proxy_types(exclude);
+            let mut filtered = nodes.clone();
+            preprocess_nodes(&mut filtered, &extra, &Vec::new(), &Vec::new());
+            filtered
+                .into_iter()
+                .map(|node| node.remark)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(remarks_after("", "ssr,socks5"), ["SS", "VMess", "Trojan"]);
+        assert_eq!(remarks_after("vmess, Trojan", ""), ["VMess", "Trojan"]);
+        // Exclusions win over inclusions, unknown names are ignored
+        assert_eq!(remarks_after("ss,trojan,bogus", "trojan"), ["SS"]);
+        assert_eq!(parse_proxy_types("bogus,"), Vec::new());
+    }
+
+    #[test]
+    fn test_node_server_filters() {
+        let served = |remark: &str, hostname: &str| Proxy {
+            remark: remark.to_string(),
+            hostname: hostname.to_string(),
+            ..Default::default()
+        };
+        let nodes = vec![
+            served("HK 01", "hk1.example.com"),
+            served("HK 02", "hk.cdn-relay.net"),
+            served("JP 01", "203.0.113.7"),
+            served("US 01", "US.CDN-RELAY.NET"),
+        ];
+        let remarks_after = |include: &[&str], exclude: &[&str]| {
+            let mut extra = ExtraSettings::default();
+            extra.include_servers = include.iter().map(|p| p.to_string()).collect();
+            extra.exclude_servers = exclude.iter().map(|p| p.to_string()).collect();
+            let mut filtered = nodes.clone();
+            preprocess_nodes(&mut filtered, &extra, &Vec::new(), &Vec::new());
+            filtered
+                .into_iter()
+                .map(|node| node.remark)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(remarks_after(&[], &[]).len(), 4);
+        // Host names match whatever their case
+        assert_eq!(
+            remarks_after(&[], &[r"\.cdn-relay\.net$"]),
+            ["HK 01", "JP 01"]
+        );
+        assert_eq!(
+            remarks_after(&[r"^203\.0\.113\.", "example"], &[]),
+            ["HK 01", "JP 01"]
+        );
+        assert_eq!(remarks_after(&["example", "relay"], &["^hk"]), ["US 01"]);
+    }
+
+    #[test]
+    fn test_normalize_vmess_alter_ids() {
+        let vmess = |alter_id: u16, cipher: &str| Proxy {
+            proxy_type: ProxyType::VMess,
+            alter_id,
+            encrypt_method: Some(cipher.to_string()),
+            ..Default::default()
+        };
+        let nodes = vec![
+            vmess(64, "auto"),
+            vmess(0, "zero"),
+            vmess(1, "none"),
+            Proxy {
+                alter_id: 2,
+                ..node("not vmess", 443)
+            },
+        ];
+        let alter_ids =
+            |nodes: &[Proxy]| nodes.iter().map(|node| node.alter_id).collect::<Vec<_>>();
+
+        let mut kept = nodes.clone();
+        assert_eq!(normalize_vmess_alter_ids(&mut kept, false), 2);
+        assert_eq!(alter_ids(&kept), [64, 0, 1, 2]);
+
+        let mut forced = nodes.clone();
+        assert_eq!(normalize_vmess_alter_ids(&mut forced, true), 2);
+        assert_eq!(alter_ids(&forced), [0, 0, 0, 2]);
+        // The ciphers are left alone
+        let ciphers: Vec<_> = forced
+            .iter()
+            .take(3)
+            .map(|node| node.encrypt_method.as_deref().unwrap())
+            .collect();
+        assert_eq!(ciphers, ["auto", "zero", "none"]);
+
+        assert_eq!(normalize_vmess_alter_ids(&mut forced, true), 0);
+    }
+
+    #[test]
+    fn test_underlying_proxy_follows_rename() {
+        let mut exit = node("JP Exit", 443);
+        exit.underlying_proxy = Some("HK Relay".to_string());
+        let mut orphan = node("US Exit", 443);
+        orphan.underlying_proxy = Some("Gone".to_string());
+        let mut nodes = vec![exit, node("HK Relay", 8388), orphan];
+
+        preprocess_nodes(
+            &mut nodes,
+            &ExtraSettings::default(),
+            &vec![rule("Relay", "Transit")],
+            &Vec::new(),
+        );
+
+        assert_eq!(nodes[1].remark, "HK Transit");
+        assert_eq!(nodes[0].underlying_proxy.as_deref(), Some("HK Transit"));
+        // Unknown references are left alone
+        assert_eq!(nodes[2].underlying_proxy.as_deref(), Some("Gone"));
+    }
+
+    #[test]
+    fn test_remark_template() {
+        let mut extra = ExtraSettings::default();
+        extra.add_emoji = true;
+        extra.remark_template = Some("{flag} {remark}-{type}-{index:02}{isp}".to_string());
+        let mut nodes = vec![
+            node("hk 01", 443),
+            node("Japan 02", 443),
+            node("US 03", 8388),
+        ];
+        preprocess_nodes(
+            &mut nodes,
+            &extra,
+            &vec![rule("^hk", "Hong Kong")],
+            &vec![rule("^Hong Kong", "🇭🇰"), rule("Japan", "🇯🇵")],
+        );
+
+        let remarks: Vec<_> = nodes.iter().map(|node| node.remark.as_str()).collect();
+        assert_eq!(
+            remarks,
+            [
+                "🇭🇰 Hong Kong 01-SS-01",
+                "🇯🇵 Japan 02-SS-02",
+                // No emoji rule matched, so no flag
+                "US 03-SS-03"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_remark_template_index_follows_sort() {
+        let mut extra = ExtraSettings::default();
+        extra.sort_flag = true;
+        extra.remark_template = Some("{remark} #{index}".to_string());
+        let mut nodes = vec![node("US", 443), node("JP", 443), node("HK", 443)];
+        preprocess_nodes(&mut nodes, &extra, &Vec::new(), &Vec::new());
+
+        let remarks: Vec<_> = nodes.iter().map(|node| node.remark.as_str()).collect();
+        assert_eq!(remarks, ["HK #1", "JP #2", "US #3"]);
+    }
+
+    #[test]
+    fn test_pin_nodes_after_sort() {
+        let mut extra = ExtraSettings::default();
+        extra.sort_flag = true;
+        let mut nodes = vec![
+            node("US 01", 443),
+            node("JP IPLC 02", 443),
+            node("HK 01", 443),
+            node("SG 01", 443),
+            node("HK IPLC 01", 443),
+        ];
+        preprocess_nodes(&mut nodes, &extra, &Vec::new(), &Vec::new());
+        pin_nodes(&mut nodes, "iplc", false);
+
+        let remarks: Vec<_> = nodes.iter().map(|node| node.remark.as_str()).collect();
+        assert_eq!(
+            remarks,
+            ["HK IPLC 01", "JP IPLC 02", "HK 01", "SG 01", "US 01"]
+        );
+    }
+
+    #[test]
+    fn test_pin_nodes_keeps_inserted_first() {
+        let mut inserted = node("ZZ Insert", 443);
+        inserted.group_id = -1;
+        let mut nodes = vec![node("HK 01", 443), inserted, node("HK IPLC 01", 443)];
+        pin_nodes(&mut nodes, "IPLC", true);
+
+        let remarks: Vec<_> = nodes.iter().map(|node| node.remark.as_str()).collect();
+        assert_eq!(remarks, ["ZZ Insert", "HK IPLC 01", "HK 01"]);
+    }
+}
@@ -0,0 +1,145 @@
+//! Shadowsocks plugin options as kept in `Proxy::plugin_option`
+//!
+//! Options are SIP003 `key=value` pairs separated by `;`, switches such as
+//! `tls` have no value. Parsers normalize to this form, every generator
+//! converts from it once.
+
+use serde_yaml::Value as YamlValue;
+
+/// Split plugin options into `(key, value)` pairs, switches get an empty value
+///
+/// Blank entries are skipped and keys and values are trimmed.
+pub fn parse_plugin_options(opts: &str) -> Vec<(&str, &str)> {
+    opts.split(';')
+        .map(str::trim)
+        .filter(|opt| !opt.is_empty())
+        .map(|opt| match opt.split_once('=') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => (opt, ""),
+        })
+        .collect()
+}
+
+/// Value of the option `key`, an empty string for a switch
+pub fn plugin_option_value<'a>(opts: &'a str, key: &str) -> Option<&'a str> {
+    parse_plugin_options(opts)
+        .into_iter()
+        .find(|(name, _)| *name == key)
+        .map(|(_, value)| value)
+}
+
+/// Join `(key, value)` pairs into plugin options, an empty value makes a switch
+pub fn join_plugin_options<K: AsRef<str>, V: AsRef<str>>(
+    pairs: impl IntoIterator<Item = (K, V)>,
+) -> String {
+    pairs
+        .into_iter()
+        .filter(|(key, _)| !key.as_ref().is_empty())
+        .map(|(key, value)| match value.as_ref() {
+            "" => key.as_ref().to_string(),
+            value => format!("{}={}", key.as_ref(), value),
+        })
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+/// Rewrite plugin options to the canonical form
+pub fn normalize_plugin_options(opts: &str) -> String {
+    join_plugin_options(parse_plugin_options(opts))
+}
+
+/// Plugin name and options of a Clash `plugin` and `plugin-opts` pair
+///
+/// Clash names simple-obfs `obfs` with the `mode` and `host` options, the
+/// v2ray-plugin `tls` and `mux` options are booleans there.
+pub fn from_clash_plugin<'a>(
+    plugin: &str,
+    opts: impl IntoIterator<Item = (&'a str, &'a YamlValue)>,
+) -> (String, String) {
+    let mut pairs = Vec::new();
+    for (key, value) in opts {
+        // Options such as the shadow-tls `version` are numbers
+        let value = match value {
+            YamlValue::String(value) => value.clone(),
+            YamlValue::Bool(value) => value.to_string(),
+            YamlValue::Number(value) => value.to_string(),
+            _ => continue,
+        };
+        pairs.push((key, value));
+    }
+
+    match plugin {
+        "obfs" => {
+            let mut obfs = Vec::new();
+            if let Some((_, mode)) = pairs.iter().find(|(key, _)| *key == "mode") {
+                obfs.push(("obfs", mode.clone()));
+            }
+            if let Some((_, host)) = pairs.iter().find(|(key, _)| *key == "host") {
+                obfs.push(("obfs-host", host.clone()));
+            }
+            ("obfs-local".to_string(), join_plugin_options(obfs))
+        }
+        "v2ray-plugin" => {
+            let opts = pairs.into_iter().filter_map(|(key, value)| match key {
+                "tls" if value == "true" => Some((key, String::new())),
+                "mux" if value == "true" => Some((key, "4".to_string())),
+                "tls" | "mux" if value == "false" => None,
+                _ => Some((key, value)),
+            });
+            (plugin.to_string(), join_plugin_options(opts))
+        }
+        _ => (plugin.to_string(), join_plugin_options(pairs)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_plugin_options() {
+        assert_eq!(
+            normalize_plugin_options(" obfs=http ; obfs-host=a.com;"),
+            "obfs=http;obfs-host=a.com"
+        );
+        assert_eq!(
+            normalize_plugin_options("mode=websocket;tls;;host=a.com;path=/ws;mux=4;"),
+            "mode=websocket;tls;host=a.com;path=/ws;mux=4"
+        );
+        assert_eq!(normalize_plugin_options(""), "");
+    }
+
+    #[test]
+    fn test_plugin_option_value() {
+        let opts = "mode=websocket;tls;path=/ws?ed=2048";
+        assert_eq!(plugin_option_value(opts, "mode"), Some("websocket"));
+        assert_eq!(plugin_option_value(opts, "tls"), Some(""));
+        assert_eq!(plugin_option_value(opts, "path"), Some("/ws?ed=2048"));
+        assert_eq!(plugin_option_value(opts, "host"), None);
+    }
+
+    #[test]
+    fn test_from_clash_plugin() {
+        let opts: serde_yaml::Mapping = serde_yaml::from_str("{mode: http, host: a.com}").unwrap();
+        let pairs = opts.iter().filter_map(|(k, v)| Some((k.as_str()?, v)));
+        assert_eq!(
+            from_clash_plugin("obfs", pairs),
+            (
+                "obfs-local".to_string(),
+                "obfs=http;obfs-host=a.com".to_string()
+            )
+        );
+
+        let opts: serde_yaml::Mapping =
+            serde_yaml::from_str("{mode: websocket, tls: true, host: a.com, path: /ws, mux: true}")
+                .unwrap();
+        let pairs = opts.iter().filter_map(|(k, v)| Some((k.as_str()?, v)));
+        assert_eq!(
+            from_clash_plugin("v2ray-plugin", pairs),
+            (
+                "v2ray-plugin".to_string(),
+                "mode=websocket;tls;host=a.com;path=/ws;mux=4".to_string()
+            )
+        );
+    }
+}
@@ -3,8 +3,72 @@
 //! This module provides utility functions for working with regular expressions,
 //! similar to the C++ implementation in subconverter.
 
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+
+use log::warn;
+use once_cell::sync::Lazy;
 use regex::{Regex, RegexBuilder};
 
+/// Most patterns kept per set of flags, the cache starts over when full
+const REGEX_CACHE_CAPACITY: usize = 4096;
+
+/// Compiled patterns shared by every request, `None` for invalid ones
+static REGEX_CACHE: Lazy<Mutex<HashMap<RegexFlags, HashMap<String, Option<Regex>>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// How a pattern is compiled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct RegexFlags {
+    pub case_insensitive: bool,
+    pub multi_line: bool,
+    /// Anchor the pattern to the whole string
+    pub full_match: bool,
+}
+
+/// Compiles a pattern once and returns the shared compiled regex
+///
+/// Rename, emoji, filter and group patterns are applied to every node, so
+/// each pattern is only compiled on its first use. An invalid pattern is
+/// logged once and then keeps returning `None`.
+pub fn compiled_regex(pattern: &str, flags: RegexFlags) -> Option<Regex> {
+    let mut cache = REGEX_CACHE.lock().unwrap_or_else(PoisonError::into_inner);
+    let patterns = cache.entry(flags).or_default();
+    if let Some(regex) = patterns.get(pattern) {
+        return regex.clone();
+    }
+
+    let source = if flags.full_match {
+        format!("^{}$", pattern)
+    } else {
+        pattern.to_string()
+    };
+    let regex = match RegexBuilder::new(&source)
+        .case_insensitive(flags.case_insensitive)
+        .multi_line(flags.multi_line)
+        .build()
+    {
+        Ok(regex) => Some(regex),
+        Err(e) => {
+            warn!("Invalid regex '{}': {}", pattern, e);
+            None
+        }
+    };
+    if patterns.len() >= REGEX_CACHE_CAPACITY {
+        patterns.clear();
+    }
+    patterns.insert(pattern.to_string(), regex.clone());
+    regex
+}
+
+/// Splits a leading `(?i)` off `match_pattern`
+fn split_case_flag(match_pattern: &str) -> (&str, bool) {
+    match match_pattern.strip_prefix("(?i)") {
+        Some(pattern) => (pattern, true),
+        None => (match_pattern, false),
+    }
+}
+
 /// Checks if a regular expression pattern is valid
 ///
 /// # Arguments
@@ -29,21 +93,14 @@ pub fn reg_valid(reg: &str) -> bool {
 ///
 /// `true` if the pattern is found, `false` otherwise
 pub fn reg_find(src: &str, match_pattern: &str) -> bool {
-    let (pattern, case_insensitive) = if match_pattern.starts_with("(?i)") {
-        (&match_pattern[4..], true)
-    } else {
-        (match_pattern, false)
+    let (pattern, case_insensitive) = split_case_flag(match_pattern);
+    let flags = RegexFlags {
+        case_insensitive,
+        multi_line: true,
+        ..Default::default()
     };
 
-    if let Ok(regex) = RegexBuilder::new(pattern)
-        .case_insensitive(case_insensitive)
-        .multi_line(true)
-        .build()
-    {
-        regex.is_match(src)
-    } else {
-        false
-    }
+    compiled_regex(pattern, flags).is_some_and(|regex| regex.is_match(src))
 }
 
 /// Replaces matches of a pattern with a replacement string
@@ -66,17 +123,14 @@ pub fn reg_replace(
     global: bool,
     multiline: bool,
 ) -> String {
-    let (pattern, case_insensitive) = if match_pattern.starts_with("(?i)") {
-        (&match_pattern[4..], true)
-    } else {
-        (match_pattern, false)
+    let (pattern, case_insensitive) = split_case_flag(match_pattern);
+    let flags = RegexFlags {
+        case_insensitive,
+        multi_line: multiline,
+        ..Default::default()
     };
 
-    if let Ok(regex) = RegexBuilder::new(pattern)
-        .case_insensitive(case_insensitive)
-        .multi_line(multiline)
-        .build()
-    {
+    if let Some(regex) = compiled_regex(pattern, flags) {
         if global {
             regex.replace_all(src, rep).to_string()
         } else {
@@ -98,20 +152,14 @@ pub fn reg_replace(
 ///
 /// `true` if the string fully matches the pattern, `false` otherwise
 pub fn reg_match(src: &str, match_pattern: &str) -> bool {
-    let (pattern, case_insensitive) = if match_pattern.starts_with("(?i)") {
-        (&match_pattern[4..], true)
-    } else {
-        (match_pattern, false)
+    let (pattern, case_insensitive) = split_case_flag(match_pattern);
+    let flags = RegexFlags {
+        case_insensitive,
+        full_match: true,
+        ..Default::default()
     };
 
-    if let Ok(regex) = RegexBuilder::new(&format!("^{}$", pattern))
-        .case_insensitive(case_insensitive)
-        .build()
-    {
-        regex.is_match(src)
-    } else {
-        false
-    }
+    compiled_regex(pattern, flags).is_some_and(|regex| regex.is_match(src))
 }
 
 /// Gets the capturing groups from a regex match
@@ -125,17 +173,14 @@ pub fn reg_match(src: &str, match_pattern: &str) -> bool {
 ///
 /// A vector of matched capturing groups, or an empty vector if no match
 pub fn reg_get_match(src: &str, match_pattern: &str) -> Vec<String> {
-    let (pattern, case_insensitive) = if match_pattern.starts_with("(?i)") {
-        (&match_pattern[4..], true)
-    } else {
-        (match_pattern, false)
+    let (pattern, case_insensitive) = split_case_flag(match_pattern);
+    let flags = RegexFlags {
+        case_insensitive,
+        multi_line: true,
+        ..Default::default()
     };
 
-    if let Ok(regex) = RegexBuilder::new(pattern)
-        .case_insensitive(case_insensitive)
-        .multi_line(true)
-        .build()
-    {
+    if let Some(regex) = compiled_regex(pattern, flags) {
         if let Some(caps) = regex.captures(src) {
             let mut results = Vec::new();
             for i in 0..caps.len() {
@@ -164,19 +209,16 @@ pub fn reg_get_match(src: &str, match_pattern: &str) -> Vec<String> {
 ///
 /// A vector of matched strings
 pub fn reg_get_all_match(src: &str, match_pattern: &str, group_only: bool) -> Vec<String> {
-    let (pattern, case_insensitive) = if match_pattern.starts_with("(?i)") {
-        (&match_pattern[4..], true)
-    } else {
-        (match_pattern, false)
+    let (pattern, case_insensitive) = split_case_flag(match_pattern);
+    let flags = RegexFlags {
+        case_insensitive,
+        multi_line: true,
+        ..Default::default()
     };
 
     let mut results = Vec::new();
 
-    if let Ok(regex) = RegexBuilder::new(pattern)
-        .case_insensitive(case_insensitive)
-        .multi_line(true)
-        .build()
-    {
+    if let Some(regex) = compiled_regex(pattern, flags) {
         if group_only {
             for caps in regex.captures_iter(src) {
                 // Skip the 0th capture (the full match) when group_only is true
@@ -272,6 +314,28 @@ mod tests {
         assert_eq!(group_only[1], "456");
     }
 
+    #[test]
+    fn test_compiled_regex_is_shared() {
+        let flags = RegexFlags {
+            case_insensitive: true,
+            ..Default::default()
+        };
+        let first = compiled_regex("cache(d)? test", flags).unwrap();
+        let second = compiled_regex("cache(d)? test", flags).unwrap();
+        assert_eq!(first.as_str(), second.as_str());
+        assert!(second.is_match("CACHED TEST"));
+
+        let full = RegexFlags {
+            full_match: true,
+            ..flags
+        };
+        assert!(!compiled_regex("cache", full).unwrap().is_match("cached"));
+        // Invalid patterns are remembered as such
+        assert!(compiled_regex("(cache", flags).is_none());
+        assert!(compiled_regex("(cache", flags).is_none());
+        assert!(!reg_find("(cache", "(cache"));
+    }
+
     #[test]
     fn test_reg_trim() {
         assert_eq!(reg_trim("  hello world  "), "hello world");
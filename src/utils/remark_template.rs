@@ -0,0 +1,207 @@
+//! Node remarks built from a template
+//!
+//! A template such as `{flag} {remark}-{type}-{index:02}` names every node
+//! instead of a chain of regex renames. Placeholders take an optional width,
+//! `{index:02}` pads with zeros and `{port:5}` with spaces, `{{` and `}}` are
+//! literal braces.
+
+use log::warn;
+
+use crate::models::Proxy;
+
+/// Values a template placeholder can stand for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    /// The remark of the node after the renames
+    Remark,
+    Type,
+    Server,
+    Port,
+    /// Position of the node in the list, starting at 1
+    Index,
+    Group,
+    /// Emoji of the first matching emoji rule
+    Flag,
+    /// Unknown placeholder or invalid width, rendered empty
+    Unsupported,
+}
+
+impl Field {
+    fn from_name(name: &str) -> Self {
+        match name {
+            "remark" => Field::Remark,
+            "type" => Field::Type,
+            "server" => Field::Server,
+            "port" => Field::Port,
+            "index" => Field::Index,
+            "group" => Field::Group,
+            "flag" => Field::Flag,
+            _ => Field::Unsupported,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Text(String),
+    Placeholder {
+        field: Field,
+        width: usize,
+        zero_pad: bool,
+    },
+}
+
+/// A parsed `remark_tpl`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemarkTemplate {
+    segments: Vec<Segment>,
+}
+
+impl RemarkTemplate {
+    /// Parse `template`, warning once about the placeholders it does not support
+    ///
+    /// A `{` without its closing brace is kept as text.
+    pub fn parse(template: &str) -> Self {
+        let mut segments = Vec::new();
+        let mut unsupported = Vec::new();
+        let mut text = String::new();
+        let mut rest = template;
+
+        while let Some(pos) = rest.find(['{', '}']) {
+            text.push_str(&rest[..pos]);
+            let tail = &rest[pos..];
+            if tail.starts_with("{{") || tail.starts_with("}}") {
+                text.push_str(&tail[..1]);
+                rest = &tail[2..];
+                continue;
+            }
+            if tail.starts_with('}') {
+                text.push('}');
+                rest = &tail[1..];
+                continue;
+            }
+            let Some(end) = tail.find('}') else {
+                rest = tail;
+                break;
+            };
+            let placeholder = &tail[1..end];
+            rest = &tail[end + 1..];
+
+            let (name, spec) = placeholder
+                .split_once(':')
+                .map_or((placeholder, None), |(name, spec)| (name, Some(spec)));
+            let mut field = Field::from_name(name.trim());
+            let (width, zero_pad) = match spec.map(parse_width) {
+                Some(Some(width)) => width,
+                Some(None) => {
+                    field = Field::Unsupported;
+                    (0, false)
+                }
+                None => (0, false),
+            };
+            if field == Field::Unsupported {
+                unsupported.push(format!("{{{}}}", placeholder));
+            }
+
+            if !text.is_empty() {
+                segments.push(Segment::Text(std::mem::take(&mut text)));
+            }
+            segments.push(Segment::Placeholder {
+                field,
+                width,
+                zero_pad,
+            });
+        }
+        text.push_str(rest);
+        if !text.is_empty() {
+            segments.push(Segment::Text(text));
+        }
+
+        if !unsupported.is_empty() {
+            warn!(
+                "Remark template '{}' has unsupported placeholders {}, they are left empty",
+                template,
+                unsupported.join(", ")
+            );
+        }
+        RemarkTemplate { segments }
+    }
+
+    /// The remark of `node`, the `index`th node of the list, with `flag` as its emoji
+    ///
+    /// Surrounding whitespace is trimmed, e.g. when no emoji rule matched a
+    /// template starting with `{flag} `.
+    pub fn render(&self, node: &Proxy, index: usize, flag: &str) -> String {
+        let mut remark = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Text(text) => remark.push_str(text),
+                Segment::Placeholder {
+                    field,
+                    width,
+                    zero_pad,
+                } => {
+                    let value = match field {
+                        Field::Remark => node.remark.clone(),
+                        Field::Type => node.proxy_type.to_string(),
+                        Field::Server => node.hostname.clone(),
+                        Field::Port => node.port.to_string(),
+                        Field::Index => index.to_string(),
+                        Field::Group => node.group.clone(),
+                        Field::Flag => flag.to_string(),
+                        Field::Unsupported => continue,
+                    };
+                    let padding = width.saturating_sub(value.chars().count());
+                    let pad = if *zero_pad { '0' } else { ' ' };
+                    remark.extend(std::iter::repeat(pad).take(padding));
+                    remark.push_str(&value);
+                }
+            }
+        }
+        remark.trim().to_string()
+    }
+}
+
+/// Parse a `02` or `5` width, `None` when it is not a number
+fn parse_width(spec: &str) -> Option<(usize, bool)> {
+    let spec = spec.trim();
+    if spec.is_empty() {
+        return Some((0, false));
+    }
+    let width = spec.parse().ok()?;
+    Some((width, spec.len() > 1 && spec.starts_with('0')))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ProxyType;
+
+    fn node(remark: &str, hostname: &str, port: u16) -> Proxy {
+        Proxy {
+            proxy_type: ProxyType::Trojan,
+            remark: remark.to_string(),
+            hostname: hostname.to_string(),
+            port,
+            group: "Airport".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_render_placeholders() {
+        let template =
+            RemarkTemplate::parse("{group}|{remark}|{type}|{server}:{port:5}|{index:03}");
+        let rendered = template.render(&node("HK 01", "hk.example.com", 443), 7, "");
+        assert_eq!(rendered, "Airport|HK 01|Trojan|hk.example.com:  443|007");
+    }
+
+    #[test]
+    fn test_unsupported_placeholders_are_empty() {
+        let template = RemarkTemplate::parse("{flag}{isp}-{index:x}-{{{type}}}-{open");
+        assert_eq!(
+            template.render(&node("HK", "hk.example.com", 443), 1, "🇭🇰"),
+            "🇭🇰--{Trojan}-{open"
+        );
+    }
+}
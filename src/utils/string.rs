@@ -352,6 +352,32 @@ pub fn normalize_file_path(path: &str) -> String {
     }
 }
 
+/// Parse a bandwidth such as `100`, `100 Mbps` or `1 Gbps` into Mbps
+///
+/// Bare numbers are taken as Mbps, unparsable values give 0.
+pub fn parse_mbps(value: &str) -> u32 {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let Ok(number) = number.parse::<f64>() else {
+        return 0;
+    };
+    let factor = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "m" | "mbps" => 1.0,
+        "g" | "gbps" => 1000.0,
+        "k" | "kbps" => 0.001,
+        _ => return 0,
+    };
+    (number * factor).round() as u32
+}
+
+/// Format a bandwidth in Mbps the way Clash.Meta writes it, e.g. `100 Mbps`
+pub fn format_mbps(mbps: u32) -> String {
+    format!("{} Mbps", mbps)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -485,4 +511,14 @@ mod tests {
         assert_eq!(normalize_file_path("dir/file.txt"), "/dir/file.txt");
         assert_eq!(normalize_file_path("/dir/file.txt"), "/dir/file.txt");
     }
+
+    #[test]
+    fn test_parse_mbps() {
+        assert_eq!(parse_mbps("100"), 100);
+        assert_eq!(parse_mbps("100 Mbps"), 100);
+        assert_eq!(parse_mbps("100Mbps"), 100);
+        assert_eq!(parse_mbps("1 Gbps"), 1000);
+        assert_eq!(parse_mbps("fast"), 0);
+        assert_eq!(parse_mbps(&format_mbps(50)), 50);
+    }
 }
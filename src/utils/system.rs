@@ -47,6 +47,13 @@ pub fn safe_system_time() -> SystemTime {
     }
 }
 
+/// Time elapsed since `since`, measured with [`safe_system_time`]
+///
+/// Returns zero if the clock went backwards.
+pub fn elapsed_since(since: SystemTime) -> Duration {
+    safe_system_time().duration_since(since).unwrap_or_default()
+}
+
 /// Get the current timestamp in seconds since UNIX epoch
 ///
 /// This function safely gets the current time and converts it to seconds
@@ -3,7 +3,9 @@
 //! This module provides utility traits to extend Option<T> with tribool-like functionality,
 //! similar to the C++ tribool implementation in the original subconverter.
 
+use serde::de::{self, Deserializer, Visitor};
 use serde_json::{Map, Value as JsonValue};
+use std::fmt;
 
 /// Trait for types that can be applied to a JSON object conditionally
 pub trait JsonApplicable {
@@ -262,3 +264,79 @@ impl BoolTriboolExt for Option<bool> {
         }
     }
 }
+
+/// Parse a tribool from a query or config value
+///
+/// `true`/`1` and `false`/`0` are defined, anything else (including an empty
+/// value) is undefined.
+pub fn parse_tribool(value: &str) -> Option<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "true" | "1" => Some(true),
+        "false" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+struct TriboolVisitor;
+
+impl<'de> Visitor<'de> for TriboolVisitor {
+    type Value = Option<bool>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a boolean, 0/1 or an empty value")
+    }
+
+    fn visit_bool<E: de::Error>(self, value: bool) -> Result<Self::Value, E> {
+        Ok(Some(value))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Self::Value, E> {
+        Ok(parse_tribool(&value.to_string()))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Self::Value, E> {
+        Ok(parse_tribool(&value.to_string()))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+        Ok(parse_tribool(value))
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_any(TriboolVisitor)
+    }
+}
+
+/// Deserialize an `Option<bool>` tribool, accepting `true`/`false`, `1`/`0`
+/// and treating any other value as undefined
+///
+/// Use with `#[serde(default, deserialize_with = "deserialize_tribool")]`.
+pub fn deserialize_tribool<'de, D>(deserializer: D) -> Result<Option<bool>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_any(TriboolVisitor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tribool() {
+        assert_eq!(parse_tribool("true"), Some(true));
+        assert_eq!(parse_tribool("1"), Some(true));
+        assert_eq!(parse_tribool("False"), Some(false));
+        assert_eq!(parse_tribool("0"), Some(false));
+        assert_eq!(parse_tribool(""), None);
+        assert_eq!(parse_tribool("maybe"), None);
+    }
+}
@@ -1,5 +1,6 @@
 //! URL encoding/decoding utilities
 
+use crate::utils::base64::base64_decode;
 
 /// Encodes a string using URL encoding
 ///
@@ -20,7 +21,7 @@ pub fn url_encode(input: &str) -> String {
     urlencoding::encode(input).into_owned()
 }
 
-/// Decodes a URL-encoded string
+/// Decodes a URL-encoded string, the same as [`decode_component`]
 ///
 /// # Arguments
 /// * `input` - The URL-encoded string to decode
@@ -31,19 +32,85 @@ pub fn url_encode(input: &str) -> String {
 ///
 /// # Examples
 /// ```
-/// use subconverter_rs::utils::url::url_decode;
+/// use subconverter::utils::url::url_decode;
 ///
 /// let decoded = url_decode("Hello%20World%21");
 /// assert_eq!(decoded, "Hello World!");
 /// ```
 pub fn url_decode(input: &str) -> String {
+    decode_component(input)
+}
+
+/// Decodes a percent-encoded URL component, such as the fragment or a query
+/// value of a share link
+///
+/// `+` is kept as it is, RFC 3986 gives it no meaning, so a remark like
+/// `HK+BGP` survives. Invalid escapes are left alone and the original string
+/// is returned when the result is not UTF-8. Decode a component only once, a
+/// literal `%25` would otherwise turn into another escape.
+///
+/// # Examples
+/// ```
+/// use subconverter::utils::url::decode_component;
+///
+/// assert_eq!(decode_component("HK+BGP%20%E9%A6%99%E6%B8%AF"), "HK+BGP 香港");
+/// assert_eq!(decode_component("100%25"), "100%");
+/// ```
+pub fn decode_component(input: &str) -> String {
     urlencoding::decode(input)
         .map(|cow| cow.into_owned())
         .unwrap_or_else(|_| input.to_string())
 }
 
+/// Decodes an `application/x-www-form-urlencoded` value, where `+` stands for a space
+///
+/// # Examples
+/// ```
+/// use subconverter::utils::url::decode_form;
+///
+/// assert_eq!(decode_form("Hong+Kong%2B"), "Hong Kong+");
+/// ```
+pub fn decode_form(input: &str) -> String {
+    decode_component(&input.replace('+', " "))
+}
+
+/// Splits the query of a share link into key / value pairs decoded with
+/// [`decode_component`]
+///
+/// Unlike `Url::query_pairs`, which decodes form style, `+` is kept. Pairs are
+/// separated by `&` and a pair without `=` yields an empty value.
+pub fn decode_query_pairs(query: &str) -> impl Iterator<Item = (String, String)> + '_ {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (decode_component(key), decode_component(value))
+        })
+}
+
+/// Splits a query string into raw key / value pairs
+///
+/// Pairs are separated by `&` or `;`, and anything up to a leading `?` is
+/// skipped. A pair without `=` yields an empty value.
+fn url_arg_pairs(url_params: &str) -> impl Iterator<Item = (&str, &str)> {
+    let query = match url_params.split_once('?') {
+        Some((prefix, query)) if !prefix.contains(['=', '&', ';']) => query,
+        _ => url_params,
+    };
+
+    query
+        .split(['&', ';'])
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| pair.split_once('=').unwrap_or((pair, "")))
+}
+
 /// Extracts a parameter value from a URL query string
 ///
+/// Keys are matched exactly and the value is URL-decoded. If the parameter is
+/// repeated, the last value is returned.
+///
 /// # Arguments
 /// * `url_params` - The URL query string containing parameters
 /// * `param_name` - The name of the parameter to extract
@@ -53,7 +120,7 @@ pub fn url_decode(input: &str) -> String {
 ///
 /// # Examples
 /// ```
-/// use subconverter_rs::utils::url::get_url_arg;
+/// use subconverter::utils::url::get_url_arg;
 ///
 /// let query = "host=example.com&port=443&mode=ws";
 /// assert_eq!(get_url_arg(query, "host"), "example.com");
@@ -61,39 +128,145 @@ pub fn url_decode(input: &str) -> String {
 /// assert_eq!(get_url_arg(query, "unknown"), "");
 /// ```
 pub fn get_url_arg(url_params: &str, param_name: &str) -> String {
-    let pattern = format!("{}=", param_name);
-    let mut pos = url_params.len();
-
-    while pos > 0 {
-        // Find the pattern starting from pos, moving backward
-        if let Some(found_pos) = url_params[..pos].rfind(&pattern) {
-            // Check if this is a proper parameter boundary
-            if found_pos == 0
-                || url_params.as_bytes()[found_pos - 1] == b'&'
-                || url_params.as_bytes()[found_pos - 1] == b'?'
-            {
-                // Extract the value
-                let start = found_pos + pattern.len();
-                let end = match url_params[start..].find('&') {
-                    Some(ampersand_pos) => start + ampersand_pos,
-                    None => url_params.len(),
-                };
-                return url_params[start..end].to_string();
-            }
-            // Move position backward to continue searching
-            pos = found_pos;
-        } else {
-            // Pattern not found
-            break;
-        }
-
-        // Prevent unsigned integer underflow
-        if pos == 0 {
-            break;
-        }
-        pos -= 1;
+    get_url_args(url_params, param_name)
+        .pop()
+        .unwrap_or_default()
+}
+
+/// Extracts every value of a repeated parameter from a URL query string
+///
+/// # Arguments
+/// * `url_params` - The URL query string containing parameters
+/// * `param_name` - The name of the parameter to extract
+///
+/// # Returns
+/// * URL-decoded values in the order they appear, empty if the parameter is not found
+///
+/// # Examples
+/// ```
+/// use subconverter::utils::url::get_url_args;
+///
+/// let query = "alpn=h2&host=example.com&alpn=http%2F1.1";
+/// assert_eq!(get_url_args(query, "alpn"), vec!["h2", "http/1.1"]);
+/// ```
+pub fn get_url_args(url_params: &str, param_name: &str) -> Vec<String> {
+    url_arg_pairs(url_params)
+        .filter(|(key, _)| *key == param_name || url_decode(key) == param_name)
+        .map(|(_, value)| url_decode(value))
+        .collect()
+}
+
+/// Decodes the payload of a `data:` URI
+///
+/// Both `data:text/plain;base64,<payload>` and the bare `data:,<payload>` form are
+/// accepted. The payload is percent-decoded first, and spaces in base64 payloads
+/// are treated as `+` since query string decoding turns them into spaces.
+///
+/// # Arguments
+/// * `uri` - The data URI to decode
+///
+/// # Returns
+/// * `Some(String)` containing the decoded payload
+/// * `None` if the input is not a data URI or the base64 payload is invalid
+///
+/// # Examples
+/// ```
+/// use subconverter::utils::url::decode_data_uri;
+///
+/// assert_eq!(decode_data_uri("data:,hello%20world"), Some("hello world".to_string()));
+/// assert_eq!(decode_data_uri("data:text/plain;base64,aGVsbG8="), Some("hello".to_string()));
+/// assert_eq!(decode_data_uri("https://example.com"), None);
+/// ```
+pub fn decode_data_uri(uri: &str) -> Option<String> {
+    let rest = uri.strip_prefix("data:")?;
+    let (meta, payload) = rest.split_once(',')?;
+    let payload = url_decode(payload);
+
+    let is_base64 = meta
+        .split(';')
+        .any(|param| param.trim().eq_ignore_ascii_case("base64"));
+    if !is_base64 {
+        return Some(payload);
+    }
+
+    // Normalize to the URL-safe alphabet so both encodings decode the same way
+    let payload = payload.trim().replace([' ', '+'], "-").replace('/', "_");
+    if payload.is_empty() {
+        return Some(String::new());
+    }
+    let decoded = base64_decode(&payload, true);
+    if decoded.is_empty() {
+        None
+    } else {
+        Some(decoded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_url_arg_matches_exact_keys() {
+        let opts = "obfs=http&obfs-host=cdn.example.com&host=example.com";
+        assert_eq!(get_url_arg(opts, "host"), "example.com");
+        assert_eq!(get_url_arg(opts, "obfs-host"), "cdn.example.com");
+        assert_eq!(get_url_arg("obfs-host=cdn.example.com", "host"), "");
+        assert_eq!(get_url_arg(opts, "obfs"), "http");
     }
 
-    // Parameter not found
-    String::new()
+    #[test]
+    fn test_get_url_arg_separators_and_prefix() {
+        assert_eq!(
+            get_url_arg("mode=websocket;host=example.com;tls", "host"),
+            "example.com"
+        );
+        assert_eq!(get_url_arg("upload=1; download=2", "download"), "2");
+        assert_eq!(
+            get_url_arg("https://example.com/sub?target=clash&url=a", "target"),
+            "clash"
+        );
+        assert_eq!(get_url_arg("mode=websocket;tls", "tls"), "");
+    }
+
+    #[test]
+    fn test_get_url_arg_decodes_values() {
+        assert_eq!(
+            get_url_arg("path=%2Fws%3Bed%3D2048&tls", "path"),
+            "/ws;ed=2048"
+        );
+        assert_eq!(get_url_arg("token=a=b==&x=1", "token"), "a=b==");
+        assert_eq!(get_url_arg("remark=Hong%20Kong", "remark"), "Hong Kong");
+        assert_eq!(get_url_arg("plugin%2Dopts=x", "plugin-opts"), "x");
+    }
+
+    #[test]
+    fn test_component_and_form_decoding() {
+        assert_eq!(decode_component("HK+BGP"), "HK+BGP");
+        assert_eq!(decode_component("HK%2BBGP"), "HK+BGP");
+        assert_eq!(decode_component("香港+01"), "香港+01");
+        // Decoded once, the escape produced is not decoded again
+        assert_eq!(decode_component("50%2525"), "50%25");
+        assert_eq!(decode_component("100%"), "100%");
+        assert_eq!(decode_form("HK+BGP"), "HK BGP");
+        assert_eq!(decode_form("HK%2BBGP"), "HK+BGP");
+
+        let pairs: Vec<_> = decode_query_pairs("sni=a+b.example&alpn=h2%2Ch3&tls").collect();
+        assert_eq!(
+            pairs,
+            [
+                ("sni".to_string(), "a+b.example".to_string()),
+                ("alpn".to_string(), "h2,h3".to_string()),
+                ("tls".to_string(), String::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_get_url_args_repeated_keys() {
+        let query = "alpn=h2&alpn=http%2F1.1&host=example.com";
+        assert_eq!(get_url_args(query, "alpn"), vec!["h2", "http/1.1"]);
+        assert_eq!(get_url_arg(query, "alpn"), "http/1.1");
+        assert!(get_url_args(query, "sni").is_empty());
+    }
 }
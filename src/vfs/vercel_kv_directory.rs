@@ -342,6 +342,35 @@ impl VercelKvVfs {
         }
     }
 
+    /// List a single page of directory contents.
+    /// Pages are ordered by entry name; `cursor` is the `next_cursor` of the previous page,
+    /// the name of its last entry. Never triggers a GitHub load. Every page reads the
+    /// current listing, so entries written or deleted between pages show up or vanish
+    /// without shifting the entries that come after the cursor.
+    pub(crate) async fn list_directory_page_impl(
+        &self,
+        path: &str,
+        cursor: Option<&str>,
+        page_size: usize,
+    ) -> Result<DirectoryPage, VfsError> {
+        log_debug!(
+            "Listing directory page for '{}' (cursor: {:?}, page size: {})",
+            path,
+            cursor,
+            page_size
+        );
+        let mut entries = self.list_directory_impl(path, true).await?;
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        let page = paginate_directory_entries(&entries, cursor, page_size);
+        log_debug!(
+            "Directory page for '{}' returning {} entries, next cursor: {:?}",
+            path,
+            page.entries.len(),
+            page.next_cursor
+        );
+        Ok(page)
+    }
+
     /// Create directory
     pub(crate) async fn create_directory_impl(&self, path: &str) -> Result<(), VfsError> {
         let normalized_path = normalize_path(path);
@@ -324,80 +324,79 @@ impl VercelKvVfs {
         let mut failures = 0;
 
         if shallow {
-            // --- Shallow Mode: Synchronous metadata updates per directory ---
-            log::info!("Processing files in shallow mode (synchronous metadata update per dir)...");
+            // --- Shallow Mode: Read metadata per directory, write back in batches ---
+            log::info!("Processing files in shallow mode (batched metadata writes)...");
+            // Directories whose updated metadata is waiting to be flushed, with their file counts
+            let mut pending_writes: Vec<(String, DirectoryMetadata)> = Vec::new();
+            let mut pending_file_counts: Vec<usize> = Vec::new();
+            let mut write_round_trips = 0;
+
             for (parent_dir, files) in files_by_parent {
-                let vfs = self.clone(); // Clone VFS for this iteration
-                let parent_dir_clone = parent_dir.clone();
-                log::debug!(
-                    "Shallow updating metadata for directory: {}",
-                    parent_dir_clone
-                );
+                log::debug!("Shallow updating metadata for directory: {}", parent_dir);
 
-                let mut current_batch_success = true; // Track success for this specific directory
+                // Add placeholders to the result list, they exist conceptually even if
+                // the metadata update below fails.
+                for file_attrs in &files {
+                    final_loaded_files.push(LoadedFile {
+                        path: file_attrs.path.clone(),
+                        size: file_attrs.size,
+                        is_placeholder: true,
+                        is_directory: false,
+                    });
+                }
 
-                // Perform read-modify-write synchronously for this directory
-                match vfs
+                match self
                     .store
-                    .read_directory_metadata_from_kv(&parent_dir_clone)
+                    .read_directory_metadata_from_kv(&parent_dir)
                     .await
                 {
                     Ok(mut dir_metadata) => {
-                        let mut files_in_batch = 0;
+                        let files_in_batch = files.len();
                         for file_attrs in files {
-                            files_in_batch += 1;
-                            // Add placeholder to final result list immediately
-                            final_loaded_files.push(LoadedFile {
-                                path: file_attrs.path.clone(),
-                                size: file_attrs.size,
-                                is_placeholder: true,
-                                is_directory: false,
-                            });
-                            // Update the metadata map
-                            dir_metadata
-                                .files
-                                .insert(get_filename(&file_attrs.path), file_attrs.clone());
                             // Update memory cache (still useful)
-                            vfs.store
+                            self.store
                                 .write_to_metadata_cache(&file_attrs.path, file_attrs.clone())
                                 .await;
+                            // Update the metadata map
+                            dir_metadata
+                                .files
+                                .insert(get_filename(&file_attrs.path), file_attrs);
                         }
-
-                        // Write the updated metadata back SYNCHRONOUSLY
-                        let write_result = vfs
-                            .store
-                            .write_directory_metadata_to_kv(&parent_dir_clone, &dir_metadata)
-                            .await;
-
-                        if let Err(e) = write_result {
-                            log::error!(
-                                "Failed to write updated metadata for dir '{}': {:?}",
-                                parent_dir_clone,
-                                e
-                            );
-                            current_batch_success = false;
-                            failures += files_in_batch; // Mark all files in this batch as failed
-                        } else {
-                            successes += files_in_batch; // Mark all files as successful for this batch
-                        }
+                        pending_writes.push((parent_dir, dir_metadata));
+                        pending_file_counts.push(files_in_batch);
                     }
                     Err(e) => {
-                        log::error!("Failed to read initial metadata for dir '{}': {:?}. Skipping update for {} files.", parent_dir_clone, e, files.len());
-                        current_batch_success = false;
+                        log::error!("Failed to read initial metadata for dir '{}': {:?}. Skipping update for {} files.", parent_dir, e, files.len());
                         failures += files.len(); // Count files as failures if initial read failed
-                                                 // Add placeholders to results anyway, even if metadata write fails?
-                                                 // Let's add them, as they exist conceptually.
-                        for file_attrs in files {
-                            final_loaded_files.push(LoadedFile {
-                                path: file_attrs.path.clone(),
-                                size: file_attrs.size,
-                                is_placeholder: true,
-                                is_directory: false,
-                            });
-                        }
                     }
                 }
+
+                if pending_writes.len() >= KV_WRITE_BATCH_SIZE {
+                    let (ok, failed, trips) = self
+                        .flush_directory_metadata_batch(
+                            &mut pending_writes,
+                            &mut pending_file_counts,
+                        )
+                        .await;
+                    successes += ok;
+                    failures += failed;
+                    write_round_trips += trips;
+                }
+            }
+
+            if !pending_writes.is_empty() {
+                let (ok, failed, trips) = self
+                    .flush_directory_metadata_batch(&mut pending_writes, &mut pending_file_counts)
+                    .await;
+                successes += ok;
+                failures += failed;
+                write_round_trips += trips;
             }
+
+            log::info!(
+                "Shallow metadata update issued {} batched KV write calls",
+                write_round_trips
+            );
         } else {
             // --- Deep Mode: Use buffer_unordered for concurrent reads and collect results ---
             log::info!("Processing files in deep mode using buffer_unordered...");
@@ -494,6 +493,33 @@ impl VercelKvVfs {
         })
     }
 
+    /// Flush pending directory metadata writes as a single batched KV call.
+    /// Returns the number of files written, the number of files whose write failed,
+    /// and the number of KV round-trips issued. Both pending lists are drained.
+    async fn flush_directory_metadata_batch(
+        &self,
+        pending_writes: &mut Vec<(String, DirectoryMetadata)>,
+        pending_file_counts: &mut Vec<usize>,
+    ) -> (usize, usize, usize) {
+        let file_count: usize = pending_file_counts.drain(..).sum();
+        let batch = std::mem::take(pending_writes);
+        match self
+            .store
+            .write_directory_metadata_batch_to_kv(&batch)
+            .await
+        {
+            Ok(round_trips) => (file_count, 0, round_trips),
+            Err(e) => {
+                log::error!(
+                    "Failed to write batched metadata for {} directories: {:?}",
+                    batch.len(),
+                    e
+                );
+                (0, file_count, 1)
+            }
+        }
+    }
+
     /// Load information about a specific file from GitHub without downloading content
     /// This still relies on the GitHub Tree Cache, which is independent of the new metadata storage.
     pub(crate) async fn load_github_file_info_impl(
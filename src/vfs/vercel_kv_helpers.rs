@@ -1,4 +1,6 @@
 use crate::vfs::vercel_kv_types::*;
+use crate::vfs::VfsError;
+use std::future::Future;
 
 //------------------------------------------------------------------------------
 // LOGGING
@@ -125,7 +127,6 @@ pub fn get_key_with_suffix(path: &str, suffix: &str) -> String {
 /// # Returns
 /// A content key string for KV storage
 pub fn get_content_key(path: &str) -> String {
-    
     get_key_with_suffix(path, FILE_CONTENT_SUFFIX)
 }
 
@@ -241,3 +242,212 @@ pub fn guess_file_type(path: &str) -> String {
     // log_debug!("Guessed file type for '{}': '{}'", path, file_type);
     file_type
 }
+
+//------------------------------------------------------------------------------
+// DIRECTORY PAGINATION
+//------------------------------------------------------------------------------
+
+/// Slices a directory listing into a single page
+///
+/// Entries are ordered by name and the cursor is the name of the last entry of the
+/// previous page, so pages stay stable when entries are added between calls.
+///
+/// # Arguments
+/// * `entries` - The full directory listing, sorted by name
+/// * `cursor` - Name of the last entry already returned, or `None` for the first page
+/// * `page_size` - Maximum number of entries in the page
+///
+/// # Returns
+/// The requested page and the cursor for the following one
+pub fn paginate_directory_entries(
+    entries: &[DirectoryEntry],
+    cursor: Option<&str>,
+    page_size: usize,
+) -> DirectoryPage {
+    let page_size = page_size.max(1);
+
+    let start = match cursor {
+        Some(after) if !after.is_empty() => entries.partition_point(|e| e.name.as_str() <= after),
+        _ => 0,
+    };
+    let end = (start + page_size).min(entries.len());
+    let page = entries[start..end].to_vec();
+
+    let next_cursor = if end < entries.len() {
+        page.last().map(|e| e.name.clone())
+    } else {
+        None
+    };
+
+    DirectoryPage {
+        entries: page,
+        next_cursor,
+    }
+}
+
+//------------------------------------------------------------------------------
+// BATCHED WRITES
+//------------------------------------------------------------------------------
+
+/// Writes directory metadata through `set_batch`, `KV_WRITE_BATCH_SIZE` directories per call
+///
+/// `set_batch` receives a JSON array of `[key, value]` pairs, the format of the
+/// `kv_set_text_batch` binding.
+///
+/// # Returns
+/// The number of `set_batch` calls, each one being a KV round-trip
+pub async fn write_directory_metadata_batches<F, Fut>(
+    batch: &[(String, DirectoryMetadata)],
+    mut set_batch: F,
+) -> Result<usize, VfsError>
+where
+    F: FnMut(String) -> Fut,
+    Fut: Future<Output = Result<(), VfsError>>,
+{
+    let mut round_trips = 0;
+    for chunk in batch.chunks(KV_WRITE_BATCH_SIZE) {
+        let mut entries = Vec::with_capacity(chunk.len());
+        for (dir_path, dir_metadata) in chunk {
+            let metadata_json_string = serde_json::to_string(dir_metadata).map_err(|e| {
+                VfsError::Other(format!(
+                    "Failed to serialize DirectoryMetadata for '{}': {}",
+                    dir_path, e
+                ))
+            })?;
+            entries.push((get_directory_marker_key(dir_path), metadata_json_string));
+        }
+
+        let entries_json = serde_json::to_string(&entries)
+            .map_err(|e| VfsError::Other(format!("Failed to serialize KV write batch: {}", e)))?;
+
+        log_debug!(
+            "Writing batch of {} directory metadata entries to KV",
+            entries.len()
+        );
+        set_batch(entries_json).await?;
+        round_trips += 1;
+    }
+    Ok(round_trips)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+
+    fn file_attributes(path: &str) -> FileAttributes {
+        let mut attributes = FileAttributes::new();
+        attributes.path = path.to_string();
+        attributes
+    }
+
+    #[test]
+    fn test_batched_metadata_writes_of_250_files() {
+        // 250 files spread over 125 directories, as grouped by the GitHub loader
+        let mut by_dir: HashMap<String, DirectoryMetadata> = HashMap::new();
+        for i in 0..250 {
+            let dir = format!("rules/set{}", i % 125);
+            let path = format!("{}/file{}.list", dir, i);
+            by_dir
+                .entry(dir)
+                .or_default()
+                .files
+                .insert(get_filename(&path), file_attributes(&path));
+        }
+        let batch: Vec<(String, DirectoryMetadata)> = by_dir.into_iter().collect();
+
+        // Mocked `kv_set_text_batch` binding, recording every round-trip
+        let kv = RefCell::new(HashMap::new());
+        let calls = RefCell::new(0);
+        let round_trips =
+            futures::executor::block_on(write_directory_metadata_batches(&batch, |entries_json| {
+                *calls.borrow_mut() += 1;
+                let entries: Vec<(String, String)> = serde_json::from_str(&entries_json).unwrap();
+                kv.borrow_mut().extend(entries);
+                async { Ok(()) }
+            }))
+            .unwrap();
+
+        // One call per directory before batching, one per KV_WRITE_BATCH_SIZE directories now
+        assert_eq!(round_trips, 3);
+        assert_eq!(*calls.borrow(), 3);
+        let kv = kv.into_inner();
+        assert_eq!(kv.len(), 125);
+        let stored: usize = kv
+            .values()
+            .map(|json| {
+                serde_json::from_str::<DirectoryMetadata>(json)
+                    .unwrap()
+                    .files
+                    .len()
+            })
+            .sum();
+        assert_eq!(stored, 250);
+        assert!(kv.contains_key(&get_directory_marker_key("rules/set0")));
+    }
+
+    #[test]
+    fn test_failed_batch_stops_the_writes() {
+        let batch: Vec<(String, DirectoryMetadata)> = (0..120)
+            .map(|i| (format!("dir{}", i), DirectoryMetadata::default()))
+            .collect();
+        let calls = RefCell::new(0);
+        let result = futures::executor::block_on(write_directory_metadata_batches(&batch, |_| {
+            *calls.borrow_mut() += 1;
+            async { Err(VfsError::StorageError("quota exceeded".to_string())) }
+        }));
+        assert!(matches!(result, Err(VfsError::StorageError(_))));
+        assert_eq!(*calls.borrow(), 1);
+    }
+
+    #[test]
+    fn test_paginate_directory_entries() {
+        let entries: Vec<DirectoryEntry> = (0..250)
+            .map(|i| {
+                let name = format!("file{:03}", i);
+                DirectoryEntry::new(name.clone(), name, false, None)
+            })
+            .collect();
+
+        let mut cursor = None;
+        let mut pages = Vec::new();
+        loop {
+            let page = paginate_directory_entries(
+                &entries,
+                cursor.as_deref(),
+                DEFAULT_DIRECTORY_PAGE_SIZE,
+            );
+            pages.push(page.entries.len());
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+        assert_eq!(pages, vec![100, 100, 50]);
+    }
+
+    #[test]
+    fn test_paginate_changing_directory() {
+        let listing = |names: &[&str]| -> Vec<DirectoryEntry> {
+            names
+                .iter()
+                .map(|name| DirectoryEntry::new(name.to_string(), name.to_string(), false, None))
+                .collect()
+        };
+        let names = |page: &DirectoryPage| -> Vec<String> {
+            page.entries.iter().map(|e| e.name.clone()).collect()
+        };
+
+        let first = paginate_directory_entries(&listing(&["a", "b", "c", "d", "e"]), None, 2);
+        assert_eq!(names(&first), ["a", "b"]);
+
+        // "a" and "c" were deleted and "bb" and "f" written before the next page
+        let entries = listing(&["b", "bb", "d", "e", "f"]);
+        let second = paginate_directory_entries(&entries, first.next_cursor.as_deref(), 2);
+        assert_eq!(names(&second), ["bb", "d"]);
+        let third = paginate_directory_entries(&entries, second.next_cursor.as_deref(), 2);
+        assert_eq!(names(&third), ["e", "f"]);
+        assert_eq!(third.next_cursor, None);
+    }
+}
@@ -15,6 +15,9 @@ extern "C" {
     #[wasm_bindgen(catch)]
     pub async fn kv_set_text(key: &str, value: &str) -> Result<(), JsValue>;
 
+    #[wasm_bindgen(catch)]
+    pub async fn kv_set_text_batch(entries_json: &str) -> Result<(), JsValue>;
+
     #[wasm_bindgen(catch)]
     pub async fn kv_exists(key: &str) -> Result<JsValue, JsValue>;
 
@@ -254,6 +254,21 @@ impl VercelKvStore {
         }
     }
 
+    /// Writes several DirectoryMetadata objects using batched KV calls.
+    /// Entries are grouped into chunks of `KV_WRITE_BATCH_SIZE`, each chunk being a
+    /// single round-trip. Returns the number of KV calls issued.
+    pub async fn write_directory_metadata_batch_to_kv(
+        &self,
+        batch: &[(String, DirectoryMetadata)],
+    ) -> Result<usize, VfsError> {
+        write_directory_metadata_batches(batch, |entries_json| async move {
+            kv_set_text_batch(&entries_json)
+                .await
+                .map_err(|e| js_error_to_vfs(e, "Failed to write directory metadata batch to KV"))
+        })
+        .await
+    }
+
     /// Writes DirectoryMetadata to KV in background (non-blocking).
     pub fn write_directory_metadata_to_kv_background(
         &self,
@@ -106,6 +106,15 @@ impl DirectoryEntry {
     }
 }
 
+/// One page of a directory listing
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DirectoryPage {
+    /// Entries in this page, ordered by name
+    pub entries: Vec<DirectoryEntry>,
+    /// Cursor to pass to the next `list_directory_page` call, `None` when exhausted
+    pub next_cursor: Option<String>,
+}
+
 /// Represents a file that was loaded from GitHub
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct LoadedFile {
@@ -135,6 +144,10 @@ pub struct LoadDirectoryResult {
 // Constants
 pub const FILE_CONTENT_SUFFIX: &str = "@@content";
 pub const DIRECTORY_MARKER_SUFFIX: &str = "/@@dir";
+/// Default number of entries returned by a paginated directory listing
+pub const DEFAULT_DIRECTORY_PAGE_SIZE: usize = 100;
+/// Maximum number of KV writes grouped into a single batch call
+pub const KV_WRITE_BATCH_SIZE: usize = 50;
 
 //------------------------------------------------------------------------------
 // NEW DIRECTORY METADATA TYPE
@@ -196,6 +209,12 @@ pub trait VirtualFileSystem {
         &self,
         path: &str,
     ) -> impl std::future::Future<Output = Result<Vec<DirectoryEntry>, VfsError>>;
+    /// List one page of a directory, starting after `cursor` (or from the beginning)
+    fn list_directory_page(
+        &self,
+        path: &str,
+        cursor: Option<&str>,
+    ) -> impl std::future::Future<Output = Result<DirectoryPage, VfsError>>;
     fn create_directory(
         &self,
         path: &str,
@@ -164,6 +164,17 @@ impl VirtualFileSystem for VercelKvVfs {
         async move { self.list_directory_impl(path, true).await }
     }
 
+    fn list_directory_page(
+        &self,
+        path: &str,
+        cursor: Option<&str>,
+    ) -> impl std::future::Future<Output = Result<DirectoryPage, VfsError>> {
+        async move {
+            self.list_directory_page_impl(path, cursor, DEFAULT_DIRECTORY_PAGE_SIZE)
+                .await
+        }
+    }
+
     fn create_directory(
         &self,
         path: &str,
@@ -0,0 +1,264 @@
+use std::future::{ready, Ready};
+use std::io::Write;
+
+use actix_web::body::{self, BoxBody, MessageBody};
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::error::ErrorInternalServerError;
+use actix_web::http::header::{self, HeaderValue};
+use actix_web::middleware::{Compress, Condition};
+use actix_web::Error;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use futures::future::LocalBoxFuture;
+
+use crate::Settings;
+
+/// Compression of the responses following `Accept-Encoding`, enabled by the
+/// `gzip_response` setting
+///
+/// Bodies are compressed per request, so each response gets the
+/// `Content-Encoding` and `Vary` headers matching its client while the other
+/// headers, such as `Subscription-UserInfo`, are kept. Wrap it outside of
+/// [`gzip_level_middleware`] so responses already compressed there are left
+/// as they are.
+pub fn compress_middleware(settings: &Settings) -> Condition<Compress> {
+    Condition::new(settings.gzip_response, Compress::default())
+}
+
+/// Gzip compression at the `compression_level` setting
+///
+/// Enabled with `gzip_response` and a level from 1 to 9. Clients accepting
+/// gzip get it at that level, the others are left to [`compress_middleware`].
+pub fn gzip_level_middleware(settings: &Settings) -> Condition<GzipLevel> {
+    Condition::new(
+        settings.gzip_response && settings.compression_level > 0,
+        GzipLevel::new(settings.compression_level),
+    )
+}
+
+/// Middleware compressing the responses with gzip at a fixed level
+pub struct GzipLevel {
+    level: u32,
+}
+
+impl GzipLevel {
+    /// Levels above 9 are taken as 9
+    pub fn new(level: u32) -> Self {
+        Self {
+            level: level.min(9),
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for GzipLevel
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Transform = GzipLevelMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(GzipLevelMiddleware {
+            service,
+            level: self.level,
+        }))
+    }
+}
+
+pub struct GzipLevelMiddleware<S> {
+    service: S,
+    level: u32,
+}
+
+impl<S, B> Service<ServiceRequest> for GzipLevelMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let gzip = req
+            .headers()
+            .get(header::ACCEPT_ENCODING)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(accepts_gzip);
+        let level = self.level;
+        let response = self.service.call(req);
+
+        Box::pin(async move {
+            let response = response.await?;
+            if !gzip || response.headers().contains_key(header::CONTENT_ENCODING) {
+                return Ok(response.map_into_boxed_body());
+            }
+
+            let (request, response) = response.into_parts();
+            let (mut response, body) = response.into_parts();
+            let body = body::to_bytes(body)
+                .await
+                .map_err(|_| ErrorInternalServerError("Failed to read the response body"))?;
+            if body.is_empty() {
+                return Ok(ServiceResponse::new(
+                    request,
+                    response.set_body(body).map_into_boxed_body(),
+                ));
+            }
+
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::new(level));
+            encoder.write_all(&body)?;
+            let compressed = encoder.finish()?;
+
+            let headers = response.headers_mut();
+            headers.insert(header::CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+            headers.append(header::VARY, HeaderValue::from_static("accept-encoding"));
+            headers.remove(header::CONTENT_LENGTH);
+            Ok(ServiceResponse::new(
+                request,
+                response.set_body(compressed).map_into_boxed_body(),
+            ))
+        })
+    }
+}
+
+/// Whether an `Accept-Encoding` value allows gzip, `gzip;q=0` refuses it
+fn accepts_gzip(accept_encoding: &str) -> bool {
+    accept_encoding.split(',').any(|coding| {
+        let mut params = coding.split(';').map(str::trim);
+        params
+            .next()
+            .is_some_and(|name| name.eq_ignore_ascii_case("gzip"))
+            && params.all(|param| {
+                param
+                    .strip_prefix("q=")
+                    .and_then(|q| q.parse::<f32>().ok())
+                    .map_or(true, |q| q > 0.0)
+            })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::SubResponse;
+    use actix_web::http::header::HeaderMap;
+    use actix_web::test::{call_service, init_service, read_body, TestRequest};
+    use actix_web::{web, App};
+    use std::io::Read;
+
+    /// Request `/sub` through the middlewares set up as in `main` from `settings`
+    fn request(settings: &Settings, accept_encoding: &str, config: &str) -> (HeaderMap, Vec<u8>) {
+        let config = config.to_string();
+        let handler = move || {
+            let config = config.clone();
+            async move {
+                let mut response = SubResponse::ok(config, "text/yaml".to_string());
+                response.headers.insert(
+                    "Subscription-UserInfo".to_string(),
+                    "upload=0; download=1024; total=4096;".to_string(),
+                );
+                response.to_http_response()
+            }
+        };
+
+        actix_web::rt::System::new().block_on(async {
+            let app = init_service(
+                App::new()
+                    .wrap(gzip_level_middleware(settings))
+                    .wrap(compress_middleware(settings))
+                    .route("/sub", web::get().to(handler)),
+            )
+            .await;
+            let req = TestRequest::get()
+                .uri("/sub")
+                .insert_header((header::ACCEPT_ENCODING, accept_encoding))
+                .to_request();
+            let response = call_service(&app, req).await;
+            let headers = response.headers().clone();
+            (headers, read_body(response).await.to_vec())
+        })
+    }
+
+    fn gunzip(body: &[u8]) -> String {
+        let mut decoded = String::new();
+        flate2::read::GzDecoder::new(body)
+            .read_to_string(&mut decoded)
+            .unwrap();
+        decoded
+    }
+
+    fn assert_gzip(headers: &HeaderMap, body: &[u8], config: &str) {
+        assert_eq!(headers.get(header::CONTENT_ENCODING).unwrap(), "gzip");
+        assert!(headers
+            .get_all(header::VARY)
+            .filter_map(|value| value.to_str().ok())
+            .any(|value| value.to_ascii_lowercase().contains("accept-encoding")));
+        assert_eq!(
+            headers.get("subscription-userinfo").unwrap(),
+            "upload=0; download=1024; total=4096;"
+        );
+        assert!(body.len() < config.len());
+        assert_eq!(gunzip(body), config);
+    }
+
+    #[test]
+    fn test_gzip_response_disabled() {
+        let config = "proxies:\n  - name: HK\n".repeat(200);
+        let settings = Settings {
+            gzip_response: false,
+            compression_level: 9,
+            ..Default::default()
+        };
+
+        let (headers, body) = request(&settings, "gzip", &config);
+        assert!(headers.get(header::CONTENT_ENCODING).is_none());
+        assert_eq!(body, config.as_bytes());
+    }
+
+    #[test]
+    fn test_gzip_response_keeps_userinfo() {
+        let config = "proxies:\n  - name: HK\n".repeat(200);
+        let settings = Settings {
+            gzip_response: true,
+            ..Default::default()
+        };
+
+        let (headers, body) = request(&settings, "gzip", &config);
+        assert_gzip(&headers, &body, &config);
+    }
+
+    #[test]
+    fn test_gzip_response_at_compression_level() {
+        let config = "proxies:\n  - name: HK\n    server: hk.example.com\n".repeat(500);
+        let level = |compression_level| Settings {
+            gzip_response: true,
+            compression_level,
+            ..Default::default()
+        };
+
+        let (headers, fastest) = request(&level(1), "br, gzip", &config);
+        assert_gzip(&headers, &fastest, &config);
+        let (headers, smallest) = request(&level(9), "br, gzip", &config);
+        assert_gzip(&headers, &smallest, &config);
+        assert!(smallest.len() <= fastest.len());
+
+        // Clients refusing gzip are left to the other encodings
+        let (headers, _) = request(&level(9), "gzip;q=0, br", &config);
+        assert_eq!(headers.get(header::CONTENT_ENCODING).unwrap(), "br");
+    }
+
+    #[test]
+    fn test_accepts_gzip() {
+        assert!(accepts_gzip("gzip"));
+        assert!(accepts_gzip("br, GZIP;q=0.5"));
+        assert!(!accepts_gzip("gzip;q=0"));
+        assert!(!accepts_gzip("br, deflate"));
+    }
+}
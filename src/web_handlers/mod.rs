@@ -1,2 +1,8 @@
+pub mod compress;
+#[cfg(feature = "web-ui")]
+pub mod ui;
 pub mod web_api;
+pub use compress::*;
+#[cfg(feature = "web-ui")]
+pub use ui::*;
 pub use web_api::*;
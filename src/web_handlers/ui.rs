@@ -0,0 +1,63 @@
+use actix_web::{web, HttpResponse};
+
+const INDEX_HTML: &str = include_str!("../../assets/ui/index.html");
+const APP_JS: &str = include_str!("../../assets/ui/app.js");
+const STYLE_CSS: &str = include_str!("../../assets/ui/style.css");
+
+/// Look up an embedded UI asset, returning its content and content type
+fn ui_asset(name: &str) -> Option<(&'static str, &'static str)> {
+    match name {
+        "" | "index.html" => Some((INDEX_HTML, "text/html; charset=utf-8")),
+        "app.js" => Some((APP_JS, "text/javascript; charset=utf-8")),
+        "style.css" => Some((STYLE_CSS, "text/css; charset=utf-8")),
+        _ => None,
+    }
+}
+
+fn asset_response(name: &str) -> HttpResponse {
+    match ui_asset(name) {
+        Some((content, content_type)) => {
+            HttpResponse::Ok().content_type(content_type).body(content)
+        }
+        None => HttpResponse::NotFound()
+            .content_type("text/plain")
+            .body(format!("UI asset not found: {}", name)),
+    }
+}
+
+/// Handler serving the URL builder page
+pub async fn ui_index_handler() -> HttpResponse {
+    asset_response("index.html")
+}
+
+/// Handler serving the scripts and styles of the URL builder page
+pub async fn ui_asset_handler(path: web::Path<String>) -> HttpResponse {
+    asset_response(&path.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::header;
+
+    #[test]
+    fn test_ui_assets() {
+        let response = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(ui_index_handler());
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(
+            response.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/html; charset=utf-8"
+        );
+
+        assert_eq!(
+            ui_asset("app.js").unwrap().1,
+            "text/javascript; charset=utf-8"
+        );
+        assert!(ui_asset("style.css").unwrap().0.contains("main"));
+        assert_eq!(asset_response("../Cargo.toml").status().as_u16(), 404);
+    }
+}
@@ -1,7 +1,14 @@
-use actix_web::{web, HttpRequest, HttpResponse};
+use std::sync::Arc;
+
+use actix_web::{http::header, web, HttpRequest, HttpResponse};
 use log::error;
 
-use crate::api::{sub_process, SubResponse, SubconverterQuery};
+use crate::api::{
+    parse_process, status_process, sub_process_with, targets_process, ParseQuery, StatusQuery,
+    SubResponse, SubconverterQuery,
+};
+use crate::utils::fetcher::{default_fetcher, SubscriptionFetcher};
+use crate::Settings;
 impl SubResponse {
     /// Convert SubResponse to HttpResponse
     pub fn to_http_response(self) -> HttpResponse {
@@ -29,18 +36,65 @@ impl SubResponse {
     }
 }
 
-pub async fn sub_handler(req: HttpRequest, query: web::Query<SubconverterQuery>) -> HttpResponse {
+/// Whether the client asked for JSON error bodies through the `Accept` header
+fn accepts_json(req: &HttpRequest) -> bool {
+    req.headers()
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.contains("application/json"))
+}
+
+/// Settings of a request, from the app data when registered, the global settings otherwise
+fn request_settings(req: &HttpRequest) -> Arc<Settings> {
+    req.app_data::<web::Data<Settings>>()
+        .map(|settings| settings.clone().into_inner())
+        .unwrap_or_else(Settings::current)
+}
+
+/// Fetcher of a request, from the app data when registered, the default fetcher otherwise
+fn request_fetcher(req: &HttpRequest) -> Arc<dyn SubscriptionFetcher> {
+    req.app_data::<web::Data<dyn SubscriptionFetcher>>()
+        .map(|fetcher| fetcher.clone().into_inner())
+        .unwrap_or_else(default_fetcher)
+}
+
+/// Run the conversion of a request with its settings and fetcher
+async fn convert(req: &HttpRequest, query: SubconverterQuery) -> HttpResponse {
     let req_url = req.uri().to_string();
+    let result = sub_process_with(
+        Some(req_url),
+        query,
+        request_settings(req),
+        request_fetcher(req),
+    )
+    .await;
+    sub_http_response(req, result)
+}
 
-    match sub_process(Some(req_url), query.into_inner()).await {
-        Ok(response) => response.to_http_response(),
+/// Turn the result of `sub_process` into a response, with problem-details
+/// JSON errors for clients accepting JSON
+fn sub_http_response(
+    req: &HttpRequest,
+    result: Result<SubResponse, Box<dyn std::error::Error>>,
+) -> HttpResponse {
+    let response = match result {
+        Ok(response) => response,
         Err(e) => {
             error!("Subconverter process error: {}", e);
-            HttpResponse::InternalServerError().body(format!("Internal server error: {}", e))
+            SubResponse::error(format!("Internal server error: {}", e), 500)
         }
+    };
+    if accepts_json(req) {
+        response.into_problem_details().to_http_response()
+    } else {
+        response.to_http_response()
     }
 }
 
+pub async fn sub_handler(req: HttpRequest, query: web::Query<SubconverterQuery>) -> HttpResponse {
+    convert(&req, query.into_inner()).await
+}
+
 /// Handler for simple conversion (no rules)
 pub async fn simple_handler(
     req: HttpRequest,
@@ -48,7 +102,6 @@ pub async fn simple_handler(
     query: web::Query<SubconverterQuery>,
 ) -> HttpResponse {
     let target_type = &path.0;
-    let req_url = req.uri().to_string();
 
     // Set appropriate target based on path
     match target_type.as_str() {
@@ -59,16 +112,15 @@ pub async fn simple_handler(
             modified_query.target = Some(target_type.clone());
 
             // Reuse the sub_handler logic
-            match sub_process(Some(req_url), modified_query).await {
-                Ok(response) => response.to_http_response(),
-                Err(e) => {
-                    error!("Subconverter process error: {}", e);
-                    HttpResponse::InternalServerError()
-                        .body(format!("Internal server error: {}", e))
-                }
-            }
+            convert(&req, modified_query).await
         }
-        _ => HttpResponse::BadRequest().body(format!("Unsupported target type: {}", target_type)),
+        _ => sub_http_response(
+            &req,
+            Ok(SubResponse::error(
+                format!("Unsupported target type: {}", target_type),
+                400,
+            )),
+        ),
     }
 }
 
@@ -77,8 +129,6 @@ pub async fn surge_to_clash_handler(
     req: HttpRequest,
     query: web::Query<SubconverterQuery>,
 ) -> HttpResponse {
-    let req_url = req.uri().to_string();
-
     // Create a modified query with the target set to Clash
     let mut modified_query = query.into_inner();
     modified_query.target = Some("clash".to_string());
@@ -86,19 +136,206 @@ pub async fn surge_to_clash_handler(
     // Set nodelist to true for this special case
     modified_query.list = Some(true);
 
-    // Reuse the sub_process logic
-    match sub_process(Some(req_url), modified_query).await {
-        Ok(response) => response.to_http_response(),
-        Err(e) => {
-            error!("Subconverter process error: {}", e);
-            HttpResponse::InternalServerError().body(format!("Internal server error: {}", e))
-        }
+    // Carry over the policy groups of a full Surge config unless the request brings its own
+    if modified_query.groups.is_none() && modified_query.config.is_none() {
+        modified_query.source_groups.get_or_insert(true);
     }
+
+    // Reuse the sub_process logic
+    convert(&req, modified_query).await
+}
+
+/// Handler for the service status / deep health check
+pub async fn status_handler(query: web::Query<StatusQuery>) -> HttpResponse {
+    status_process(query.into_inner()).await.to_http_response()
+}
+
+/// Handler returning the parsed nodes of a subscription as JSON
+pub async fn parse_handler(query: web::Query<ParseQuery>) -> HttpResponse {
+    parse_process(query.into_inner()).await.to_http_response()
+}
+
+/// Handler listing the supported targets and their query options
+pub async fn targets_handler() -> HttpResponse {
+    targets_process().to_http_response()
 }
 
 /// Register the API endpoints with Actix Web
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.route("/sub", web::get().to(sub_handler))
         .route("/surge2clash", web::get().to(surge_to_clash_handler))
-        .route("/{target_type}", web::get().to(simple_handler));
+        .route("/status", web::get().to(status_handler))
+        .route("/parse", web::get().to(parse_handler))
+        .route("/api/targets", web::get().to(targets_handler));
+    // Registered before the catch-all target route which would match `/ui`
+    #[cfg(feature = "web-ui")]
+    cfg.route("/ui", web::get().to(super::ui_index_handler))
+        .route("/ui/{file}", web::get().to(super::ui_asset_handler));
+    cfg.route("/{target_type}", web::get().to(simple_handler));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::base64::base64_encode;
+    use crate::utils::fetcher::{FetchedBody, MemoryFetcher};
+    use actix_web::test::{call_service, init_service, read_body, TestRequest};
+    use actix_web::App;
+
+    const SUB_URL: &str = "https://example.com/sub";
+    const INSERT_URL: &str = "https://example.com/pinned";
+
+    /// Status and body of a GET request against the API with stubbed
+    /// settings and subscriptions, as JSON when `json` is set
+    async fn get(uri: &str, settings: Settings, json: bool) -> (u16, String) {
+        let mut fetcher = MemoryFetcher::new();
+        fetcher.insert(
+            SUB_URL,
+            FetchedBody::new(base64_encode(
+                "ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@1.2.3.4:8388#Node%20A",
+            )),
+        );
+        fetcher.insert(
+            INSERT_URL,
+            FetchedBody::new(base64_encode(
+                "ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@5.6.7.8:8388#Pinned",
+            )),
+        );
+        let fetcher: Arc<dyn SubscriptionFetcher> = Arc::new(fetcher);
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(settings))
+                .app_data(web::Data::from(fetcher))
+                .configure(config),
+        )
+        .await;
+        let mut req = TestRequest::get().uri(uri);
+        if json {
+            req = req.insert_header((header::ACCEPT, "application/json"));
+        }
+        let response = call_service(&app, req.to_request()).await;
+        let status = response.status().as_u16();
+        let body = read_body(response).await;
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    /// Settings that count as loaded, so the requests do not load the pref file
+    fn loaded_settings() -> Settings {
+        Settings {
+            pref_path: "pref.ini".to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Check the plain and the JSON body of the error returned for `uri`
+    async fn assert_error(uri: &str, settings: fn() -> Settings, status: u16, code: &str) {
+        let (plain_status, plain) = get(uri, settings(), false).await;
+        assert_eq!(plain_status, status, "{}: {}", uri, plain);
+        assert!(serde_json::from_str::<serde_json::Value>(&plain).is_err());
+
+        let (json_status, json) = get(uri, settings(), true).await;
+        assert_eq!(json_status, status);
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(json["error"], code);
+        assert_eq!(json["detail"], plain);
+    }
+
+    #[actix_web::test]
+    async fn test_sub_handler_converts() {
+        let (status, body) = get(
+            &format!("/sub?target=clash&list=true&url={}", SUB_URL),
+            loaded_settings(),
+            false,
+        )
+        .await;
+        assert_eq!(status, 200);
+        assert!(body.contains("Node A"));
+    }
+
+    #[actix_web::test]
+    async fn test_sub_handler_invalid_target() {
+        let uri = format!("/sub?url={}", SUB_URL);
+        assert_error(&uri, loaded_settings, 400, "invalid_request").await;
+        let uri = format!("/sub?target=nope&url={}", SUB_URL);
+        assert_error(&uri, loaded_settings, 400, "invalid_request").await;
+    }
+
+    #[actix_web::test]
+    async fn test_sub_handler_no_nodes() {
+        let uri = format!("/sub?target=clash&exclude=Node&url={}", SUB_URL);
+        assert_error(&uri, loaded_settings, 422, "unprocessable").await;
+    }
+
+    #[actix_web::test]
+    async fn test_sub_handler_upstream_failure() {
+        let uri = "/sub?target=clash&url=https://example.com/missing";
+        assert_error(uri, loaded_settings, 502, "upstream_failed").await;
+    }
+
+    #[actix_web::test]
+    async fn test_sub_handler_internal_error() {
+        // Settings without a pref file are loaded first, which fails without one
+        let uri = format!("/sub?target=clash&url={}", SUB_URL);
+        assert_error(&uri, Settings::default, 500, "internal_error").await;
+    }
+
+    #[test]
+    fn test_error_body_follows_accept_header() {
+        let error = || Ok(SubResponse::error("No nodes were found!".to_string(), 422));
+
+        let req = TestRequest::default()
+            .insert_header((header::ACCEPT, "application/json"))
+            .to_http_request();
+        let response = sub_http_response(&req, error());
+        assert_eq!(response.status().as_u16(), 422);
+        assert_eq!(
+            response.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/json"
+        );
+
+        let req = TestRequest::default().to_http_request();
+        let response = sub_http_response(&req, error());
+        assert_eq!(response.status().as_u16(), 422);
+        assert_eq!(
+            response.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/plain"
+        );
+
+        let response = sub_http_response(&req, Err("settings not loaded".into()));
+        assert_eq!(response.status().as_u16(), 500);
+    }
+
+    /// Whether the nodes of the insert URL end up in the output of `query`
+    async fn inserted(enable_insert: bool, query: &str) -> bool {
+        let settings = Settings {
+            enable_insert,
+            insert_urls: vec![INSERT_URL.to_string()],
+            ..loaded_settings()
+        };
+        let uri = format!("/sub?target=clash&list=true&url={}{}", SUB_URL, query);
+        let (status, body) = get(&uri, settings, false).await;
+        assert_eq!(status, 200, "{}", body);
+        assert!(body.contains("Node A"));
+        body.contains("Pinned")
+    }
+
+    #[actix_web::test]
+    async fn test_insert_enabled_without_param() {
+        assert!(inserted(true, "").await);
+    }
+
+    #[actix_web::test]
+    async fn test_insert_enabled_with_param() {
+        assert!(!inserted(true, "&insert=false").await);
+    }
+
+    #[actix_web::test]
+    async fn test_insert_disabled_without_param() {
+        assert!(!inserted(false, "").await);
+    }
+
+    #[actix_web::test]
+    async fn test_insert_disabled_with_param() {
+        assert!(inserted(false, "&insert=true").await);
+    }
 }
@@ -0,0 +1,49 @@
+//! The library API without the default features
+//!
+//! CI runs this with `cargo test --no-default-features --test core_api`, the
+//! conversion pipeline must build and work without the server.
+
+use subconverter::prelude::*;
+use subconverter::{explode_any, ParseError};
+
+const LINK: &str = "ss://YWVzLTI1Ni1nY206cGFzc3dvcmQ@1.2.3.4:8388#Node%20A";
+
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
+
+#[test]
+fn test_parse_without_server() {
+    let nodes = explode_any(LINK).unwrap();
+    assert_eq!(nodes.len(), 1);
+    assert_eq!(nodes[0].proxy_type, ProxyType::Shadowsocks);
+    assert_eq!(nodes[0].remark, "Node A");
+
+    assert!(matches!(explode_any(""), Err(ParseError::Empty)));
+}
+
+#[test]
+fn test_convert_without_server() {
+    for target in [
+        SubconverterTarget::Clash,
+        SubconverterTarget::Surge(4),
+        SubconverterTarget::SingBox,
+    ] {
+        let mut builder = SubconverterConfigBuilder::new();
+        builder
+            .target(target.clone())
+            .add_content(LINK)
+            .nodelist(true);
+        let result = block_on(subconverter(builder.build().unwrap())).unwrap();
+        assert!(
+            result.content.contains("Node A") && result.content.contains("1.2.3.4"),
+            "{}: {}",
+            target.to_str(),
+            result.content
+        );
+    }
+}
@@ -0,0 +1,148 @@
+//! End-to-end conversions compared with known good outputs
+//!
+//! Every subscription in `tests/fixtures` is converted to each target with
+//! the fixed settings of [`builder`] and compared byte for byte with
+//! `tests/golden/<fixture>/<target>`. Nothing is fetched: subscriptions are
+//! passed as content, rulesets and rule bases are local files.
+//!
+//! A missing expected file fails the test like a different one. Files are
+//! only written with `UPDATE_GOLDEN=1`, to record a new fixture or target
+//! or after an intended change of the output; review their diff:
+//!
+//! ```sh
+//! UPDATE_GOLDEN=1 cargo test --test golden
+//! ```
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use subconverter::models::ruleset::RulesetConfigs;
+use subconverter::models::RegexMatchConfigs;
+use subconverter::prelude::*;
+use subconverter::settings::{FromIni, FromIniWithDelimiter};
+
+/// Subscriptions under `tests/fixtures`
+const FIXTURES: [&str; 2] = ["links.txt", "clash.yaml"];
+
+/// Targets and the names of their expected output files
+fn targets() -> [(SubconverterTarget, &'static str); 4] {
+    [
+        (SubconverterTarget::Clash, "clash.yaml"),
+        (SubconverterTarget::Surge(4), "surge.conf"),
+        (SubconverterTarget::QuantumultX, "quanx.conf"),
+        (SubconverterTarget::SingBox, "singbox.json"),
+    ]
+}
+
+fn lines(lines: &[&str]) -> Vec<String> {
+    lines.iter().map(|line| line.to_string()).collect()
+}
+
+/// The same settings for every fixture, paths are relative to the crate root
+fn builder(target: SubconverterTarget) -> SubconverterConfigBuilder {
+    let mut builder = SubconverterConfigBuilder::new();
+    builder
+        .target(target)
+        .exclude_remarks(lines(&["(Expire|Traffic)"]))
+        .rename_array(RegexMatchConfigs::from_ini_with_delimiter(
+            &lines(&[
+                "Hong Kong@HK",
+                "Japan@JP",
+                "United States@US",
+                "Singapore@SG",
+            ]),
+            "@",
+        ))
+        .emoji_array(RegexMatchConfigs::from_ini_with_delimiter(
+            &lines(&[
+                "(HK|Hong Kong),🇭🇰",
+                "(JP|Japan),🇯🇵",
+                "(US|United States),🇺🇸",
+                "(SG|Singapore),🇸🇬",
+            ]),
+            ",",
+        ))
+        .add_emoji(true)
+        .remove_emoji(true)
+        .proxy_groups(ProxyGroupConfigs::from_ini(&lines(&[
+            "Proxy`select`[]Auto`[]Asia`[]DIRECT`.*",
+            "Auto`url-test`.*`http://www.gstatic.com/generate_204`300",
+            "Asia`select`(HK|JP|SG)",
+            "Final`select`[]Proxy`[]DIRECT",
+        ])))
+        .ruleset_configs(RulesetConfigs::from_ini(&lines(&[
+            "DIRECT,tests/fixtures/rules/direct.list",
+            "REJECT,tests/fixtures/rules/reject.list",
+            "Asia,[]DOMAIN-SUFFIX,example.jp",
+            "DIRECT,[]GEOIP,CN",
+            "Final,[]FINAL",
+        ])))
+        .enable_rule_generator(true)
+        .clash_new_field_name(true)
+        .udp(Some(true))
+        .clash_rule_base("tests/fixtures/base/clash.yml")
+        .surge_rule_base("tests/fixtures/base/surge.conf")
+        .quanx_rule_base("tests/fixtures/base/quanx.conf")
+        .singbox_rule_base("tests/fixtures/base/singbox.json");
+    builder
+}
+
+fn convert(subscription: &str, target: SubconverterTarget) -> String {
+    let mut builder = builder(target);
+    builder.add_content(subscription);
+    let config = builder.build().unwrap();
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(subconverter(config))
+        .unwrap()
+        .content
+}
+
+fn crate_path(path: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join(path)
+}
+
+#[test]
+fn test_golden_outputs() {
+    let update = std::env::var("UPDATE_GOLDEN").is_ok_and(|value| value == "1");
+    let mut mismatches = Vec::new();
+
+    for fixture in FIXTURES {
+        let subscription = fs::read_to_string(crate_path("tests/fixtures").join(fixture)).unwrap();
+        let name = fixture.split('.').next().unwrap();
+        for (target, file) in targets() {
+            let output = convert(&subscription, target);
+            let golden = crate_path("tests/golden").join(name).join(file);
+
+            match fs::read_to_string(&golden) {
+                Ok(expected) if expected == output => {}
+                _ if update => {
+                    eprintln!("Writing {}", golden.display());
+                    fs::create_dir_all(golden.parent().unwrap()).unwrap();
+                    fs::write(&golden, &output).unwrap();
+                }
+                Ok(_) => mismatches.push(golden.display().to_string()),
+                Err(_) => mismatches.push(format!("{} (missing)", golden.display())),
+            }
+        }
+    }
+
+    assert!(
+        mismatches.is_empty(),
+        "Output differs from {}\nrun `UPDATE_GOLDEN=1 cargo test --test golden` and review the diff",
+        mismatches.join("\n")
+    );
+}
+
+#[test]
+fn test_conversion_is_repeatable() {
+    let subscription = fs::read_to_string(crate_path("tests/fixtures/links.txt")).unwrap();
+    for (target, _) in targets() {
+        assert_eq!(
+            convert(&subscription, target.clone()),
+            convert(&subscription, target)
+        );
+    }
+}